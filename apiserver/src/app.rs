@@ -13,21 +13,31 @@
 // limitations under the License.
 
 use crate::context::Context;
-use crate::{routes, swagger};
+use crate::handlers::quota::QuotaLayer;
+use crate::{handlers, routes, swagger};
 
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
+
+use amp_resources::telemetry;
+use axum::extract::Request;
+use axum::middleware;
 use tokio::signal;
 use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::TraceLayer;
+use tracing::Span;
 
 pub async fn run(ctx: Arc<Context>) {
     let port = ctx.config.port;
 
     // build our application with a route
-    let app = routes::build().merge(swagger::build()).with_state(ctx).layer((
-        TraceLayer::new_for_http(),
+    let audit = middleware::from_fn_with_state(ctx.clone(), handlers::audit::record);
+    let quota = QuotaLayer::new(ctx.quotas.clone(), ctx.config.auth_token.clone());
+    let activity = middleware::from_fn_with_state(ctx.clone(), handlers::activity::record);
+    let app = routes::build().layer(activity).layer(audit).layer(quota).merge(routes::probes()).merge(swagger::build());
+    let app = app.with_state(ctx).layer((
+        TraceLayer::new_for_http().make_span_with(span),
         // Graceful shutdown will wait for outstanding requests to complete. Add a timeout so
         // requests don't hang forever.
         TimeoutLayer::new(Duration::from_secs(10)),
@@ -38,6 +48,8 @@ pub async fn run(ctx: Arc<Context>) {
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
 
     // Run the server with graceful shutdown
+    // The client addresses identify the anonymous callers for the rate limiting.
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
     let server = axum::serve(listener, app).with_graceful_shutdown(shutdown_signal()).await;
     if let Err(err) = server {
         tracing::error!("Server error: {}", err);
@@ -45,6 +57,37 @@ pub async fn run(ctx: Arc<Context>) {
     }
 }
 
+/// The span of the request, it continues the trace propagated by the caller, if any.
+/// The playbook and actor of the path are recorded, so they're logged along
+/// with every event of the request.
+fn span(request: &Request) -> Span {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        playbook = tracing::field::Empty,
+        actor = tracing::field::Empty,
+    );
+    let mut segments = request.uri().path().trim_start_matches("/v1/").split('/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("playbooks"), Some(id), _) => {
+            span.record("playbook", id);
+        }
+        (Some("actors"), Some(pid), name) => {
+            span.record("playbook", pid);
+            if let Some(name) = name {
+                span.record("actor", name);
+            }
+        }
+        _ => {}
+    }
+    if let Some(traceparent) = request.headers().get("traceparent").and_then(|value| value.to_str().ok()) {
+        telemetry::follow(&span, traceparent);
+    }
+
+    span
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c().await.expect("failed to install Ctrl+C handler");
@@ -12,6 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use amp_resources::cost::Prices;
+use amp_resources::secret::{Provider, Vault};
+
 /// The configuration parameters for the application.
 ///
 /// These can either be passed on the command line, or pulled from environment variables.
@@ -28,6 +31,16 @@
 /// See `.env.sample` in the repository root for details.
 #[derive(Clone, clap::Parser)]
 pub struct Config {
+    /// The name of the Kubernetes namespace that Amphitheatre is
+    /// currently running in, the default is `amp-system`
+    #[clap(long, env = "AMP_NAMESPACE", default_value = "amp-system")]
+    pub namespace: String,
+
+    /// The name of this apiserver instance, it's the name of its pod in the
+    /// namespace above, and owns the operations started by it.
+    #[clap(long, env = "HOSTNAME", default_value = "amp-apiserver")]
+    pub instance: String,
+
     /// The Server port.
     #[clap(long, env = "AMP_PORT")]
     pub port: u16,
@@ -35,4 +48,171 @@ pub struct Config {
     /// The NATS URL.
     #[clap(long, env = "AMP_NATS_URL")]
     pub nats_url: String,
+
+    /// The maximum number of log lines per second sent to a single client,
+    /// the default is `200`.
+    #[clap(long, env = "AMP_LOG_MAX_LINES_PER_SECOND", default_value = "200")]
+    pub log_max_lines_per_second: u32,
+
+    /// The bearer token required by the port-forward and exec endpoints,
+    /// the endpoints are disabled if it's not set.
+    #[clap(long, env = "AMP_AUTH_TOKEN")]
+    pub auth_token: Option<String>,
+
+    /// The secret of the push webhooks of the git providers, the HMAC key of
+    /// the signatures of GitHub and Gitea, or the token of GitLab, the
+    /// webhooks are disabled if it's not set.
+    #[clap(long, env = "AMP_WEBHOOK_SECRET")]
+    pub webhook_secret: Option<String>,
+
+    /// The provider of the registry and repository credentials, `kubernetes`,
+    /// `vault` or `external-secrets`, the default is `kubernetes`.
+    #[clap(long, env = "AMP_SECRETS_PROVIDER", default_value = "kubernetes")]
+    pub secrets_provider: String,
+
+    /// The address of Vault, required by the `vault` provider.
+    #[clap(long, env = "AMP_VAULT_ADDR")]
+    pub vault_addr: Option<String>,
+
+    /// The role of the Vault Kubernetes auth method, the default is `amp-apiserver`.
+    #[clap(long, env = "AMP_VAULT_ROLE", default_value = "amp-apiserver")]
+    pub vault_role: String,
+
+    /// The path of the Vault secret holding the credentials,
+    /// the default is `secret/data/amphitheatre`.
+    #[clap(long, env = "AMP_VAULT_PATH", default_value = "secret/data/amphitheatre")]
+    pub vault_path: String,
+
+    /// The idle timeout in seconds of the port-forward connections,
+    /// the default is `300`.
+    #[clap(long, env = "AMP_FORWARD_IDLE_TIMEOUT", default_value = "300")]
+    pub forward_idle_timeout: u64,
+
+    /// The default image signing mode of playbooks, `key` or `keyless`,
+    /// the images will not be signed if it's not set.
+    #[clap(long, env = "AMP_IMAGE_SIGNING")]
+    pub image_signing: Option<String>,
+
+    /// How long in days the audit events of the mutating API calls
+    /// are retained, the default is `90`.
+    #[clap(long, env = "AMP_AUDIT_RETENTION_DAYS", default_value = "90")]
+    pub audit_retention_days: u64,
+
+    /// The maximum size in bytes of the sources uploaded from local,
+    /// the default is `104857600` (100 MiB).
+    #[clap(long, env = "AMP_UPLOAD_MAX_SIZE", default_value = "104857600")]
+    pub upload_max_size: usize,
+
+    /// The backend of the artifact store, `pvc`, `s3` or `gcs`, the default is `pvc`.
+    #[clap(long, env = "AMP_ARTIFACT_BACKEND", default_value = "pvc")]
+    pub artifact_backend: String,
+
+    /// The directory of the artifacts for the `pvc` backend, it's the mount of a
+    /// `ReadWriteMany` volume shared with the controllers, or the bucket for the
+    /// `s3` and `gcs` backends, the default is `/var/lib/amp/artifacts`.
+    #[clap(long, env = "AMP_ARTIFACT_LOCATION", default_value = "/var/lib/amp/artifacts")]
+    pub artifact_location: String,
+
+    /// The endpoint of the S3 compatible service, e.g. MinIO, it's AWS S3 if not set.
+    #[clap(long, env = "AMP_ARTIFACT_S3_ENDPOINT")]
+    pub artifact_s3_endpoint: Option<String>,
+
+    /// The region of the S3 bucket, the default is `us-east-1`.
+    #[clap(long, env = "AMP_ARTIFACT_S3_REGION", default_value = "us-east-1")]
+    pub artifact_s3_region: String,
+
+    /// How long in days the sources uploaded from local are retained,
+    /// `0` keeps them forever, the default is `7`.
+    #[clap(long, env = "AMP_ARTIFACT_SOURCES_RETENTION_DAYS", default_value = "7")]
+    pub artifact_sources_retention_days: u64,
+
+    /// How long in days the archived logs of the builds are retained,
+    /// `0` keeps them forever, the default is `30`.
+    #[clap(long, env = "AMP_ARTIFACT_BUILD_LOGS_RETENTION_DAYS", default_value = "30")]
+    pub artifact_build_logs_retention_days: u64,
+
+    /// How long in days the generated SBOMs are retained,
+    /// `0` keeps them forever, the default is `90`.
+    #[clap(long, env = "AMP_ARTIFACT_SBOMS_RETENTION_DAYS", default_value = "90")]
+    pub artifact_sboms_retention_days: u64,
+
+    /// The sustained number of requests per second allowed for each caller,
+    /// identified by its token or address, `0` disables the limiting, the
+    /// default is `10`.
+    #[clap(long, env = "AMP_RATE_LIMIT_PER_SECOND", default_value = "10")]
+    pub rate_limit_per_second: f64,
+
+    /// The number of requests a caller may burst above the sustained rate,
+    /// the default is `50`.
+    #[clap(long, env = "AMP_RATE_LIMIT_BURST", default_value = "50")]
+    pub rate_limit_burst: u32,
+
+    /// The format of the logs, `text` or `json`, the default is `text`.
+    #[clap(long, env = "AMP_LOG_FORMAT", default_value = "text")]
+    pub log_format: String,
+
+    /// The log level of each module, e.g. `info,amp_workflow=debug,kube=warn`,
+    /// it's overridden at runtime by the `amp-logging` ConfigMap, `RUST_LOG`
+    /// is used if it's not set, and the default is `info`.
+    #[clap(long, env = "AMP_LOG_FILTER")]
+    pub log_filter: Option<String>,
+
+    /// The OTLP gRPC endpoint the traces are exported to, e.g.
+    /// `http://otel-collector:4317`, they are not exported if it's not set.
+    #[clap(long, env = "AMP_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// The index of the character hub, an HTTP(S) URL or an OCI repository,
+    /// e.g. `oci://ghcr.io/amphitheatre-app/hub`, the qualified names of the
+    /// `hub` registry, e.g. `amphitheatre/redis@1.2.0`, are fetched from it.
+    #[clap(long, env = "AMP_HUB_URL")]
+    pub hub_url: Option<String>,
+
+    /// The URL of Prometheus the resource usage of the actors is queried from,
+    /// e.g. `http://prometheus.monitoring.svc:9090`, it's read from the
+    /// metrics-server if not set.
+    #[clap(long, env = "AMP_PROMETHEUS_URL")]
+    pub prometheus_url: Option<String>,
+
+    /// The currency of the unit prices, the default is `USD`.
+    #[clap(long, env = "AMP_PRICE_CURRENCY", default_value = "USD")]
+    pub price_currency: String,
+
+    /// The price of a cpu core requested for an hour.
+    #[clap(long, env = "AMP_PRICE_CPU_HOUR")]
+    pub price_cpu_hour: Option<f64>,
+
+    /// The price of a GiB of memory requested for an hour.
+    #[clap(long, env = "AMP_PRICE_MEMORY_GIB_HOUR")]
+    pub price_memory_gib_hour: Option<f64>,
+
+    /// The price of a build minute.
+    #[clap(long, env = "AMP_PRICE_BUILD_MINUTE")]
+    pub price_build_minute: Option<f64>,
+}
+
+impl Config {
+    /// Returns the unit prices the costs are estimated with, the ones of the
+    /// `amp-prices` ConfigMap take precedence.
+    pub fn prices(&self) -> Prices {
+        Prices {
+            currency: Some(self.price_currency.clone()),
+            cpu_hour: self.price_cpu_hour,
+            memory_gib_hour: self.price_memory_gib_hour,
+            build_minute: self.price_build_minute,
+        }
+    }
+
+    /// Returns the provider the credentials are read from, the Secret synced by
+    /// the External Secrets Operator is read like the one maintained by hand.
+    pub fn secrets_provider(&self) -> anyhow::Result<Provider> {
+        match self.secrets_provider.as_str() {
+            "kubernetes" | "external-secrets" => Ok(Provider::Kubernetes),
+            "vault" => {
+                let addr = self.vault_addr.as_deref().ok_or_else(|| anyhow::anyhow!("AMP_VAULT_ADDR is required"))?;
+                Ok(Provider::Vault(Vault::new(addr, &self.vault_role, &self.vault_path)))
+            }
+            provider => Err(anyhow::anyhow!("Unknown secrets provider: {}", provider)),
+        }
+    }
 }
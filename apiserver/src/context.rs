@@ -12,9 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
+use amp_resources::artifact::{Lifecycle, Store};
+use amp_resources::secret::Provider;
+use async_nats::jetstream;
 use kube::Client;
 
 use crate::config::Config;
+use crate::services::audit::AuditRepository;
+use crate::services::operation::OperationRepository;
+use crate::services::quota::Quotas;
 
 /// The core type through which handler functions can access common API state.
 ///
@@ -28,10 +36,44 @@ use crate::config::Config;
 pub struct Context {
     pub config: Config,
     pub k8s: Client,
+    pub nats: async_nats::Client,
+    pub audit: AuditRepository,
+    pub operations: OperationRepository,
+    pub quotas: Quotas,
+    pub artifacts: Store,
+    pub secrets: Provider,
 }
 
 impl Context {
     pub async fn new(config: Config) -> anyhow::Result<Context> {
-        Ok(Context { config, k8s: Client::try_default().await? })
+        let client = async_nats::connect(&config.nats_url)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to connect to NATS: {}, {}", &config.nats_url, e))?;
+        let jetstream = jetstream::new(client.clone());
+        let retention = Duration::from_secs(config.audit_retention_days * 24 * 60 * 60);
+        let audit = AuditRepository::new(jetstream.clone(), retention);
+
+        // The operations in progress were lost with the previous apiserver.
+        let k8s = Client::try_default().await?;
+        let operations = OperationRepository::new(jetstream, config.instance.clone());
+        operations.interrupt(&k8s, &config.namespace).await?;
+
+        let secrets = config.secrets_provider()?;
+        let quotas = Quotas::new(config.rate_limit_per_second, config.rate_limit_burst);
+
+        let lifecycle = Lifecycle::days(
+            config.artifact_sources_retention_days,
+            config.artifact_build_logs_retention_days,
+            config.artifact_sboms_retention_days,
+        );
+        let artifacts = Store::open(
+            &config.artifact_backend,
+            &config.artifact_location,
+            config.artifact_s3_endpoint.as_deref(),
+            &config.artifact_s3_region,
+            lifecycle,
+        )?;
+
+        Ok(Context { config, k8s, nats: client, audit, operations, quotas, artifacts, secrets })
     }
 }
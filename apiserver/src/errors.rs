@@ -33,14 +33,26 @@ pub enum ApiError {
     #[error("Not Found")]
     NotFound,
 
-    #[error("Resolve Error")]
-    ResolveError,
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Bad Request: {0}")]
+    BadRequest(String),
+
+    #[error("Payload Too Large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Resolve Error: {0}")]
+    ResolveError(#[source] amp_resolver::errors::ResolveError),
 
     #[error("NATS Error: {0}")]
     NatsError(#[source] async_nats::Error),
 
     #[error("Resource Error: {0}")]
     ResourceError(#[source] amp_resources::error::Error),
+
+    #[error("Timeout: {0}")]
+    Timeout(String),
 }
 
 impl IntoResponse for ApiError {
@@ -50,9 +62,13 @@ impl IntoResponse for ApiError {
             Self::KubernetesError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             Self::InternalServerError => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             Self::NotFound => (StatusCode::NOT_FOUND, self.to_string()),
-            Self::ResolveError => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            Self::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
+            Self::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            Self::PayloadTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
+            Self::ResolveError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             Self::NatsError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             Self::ResourceError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            Self::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
         };
 
         error!("{} - {}", status, message);
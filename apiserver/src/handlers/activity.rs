@@ -0,0 +1,102 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+use tokio::time::{interval_at, Instant};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::context::Context;
+use crate::services::playbook::PlaybookService;
+
+/// How often the activity is recorded again while the streams of the request are open.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The activity of the playbook kept by the streams of a request, e.g. the
+/// logs, port-forward and exec of its actors, it's recorded again every
+/// interval until all of them are closed, so it doesn't go idle meanwhile.
+#[derive(Clone)]
+pub struct Activity {
+    _refresher: Arc<Refresher>,
+}
+
+struct Refresher(JoinHandle<()>);
+
+impl Drop for Refresher {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// The middleware recording the activity of the playbook in the path, e.g. the
+/// logs, port-forward and exec of its actors, the hibernated playbook is woken
+/// up by it. The deletion and the explicit stop of playbook are not activities.
+/// The event streams keep it while open, the WebSocket handlers take the
+/// [`Activity`] from the extensions of request into their connections.
+pub async fn record(State(ctx): State<Arc<Context>>, mut request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if *request.method() == Method::DELETE || path.ends_with("/actions/stop") {
+        return next.run(request).await;
+    }
+    let mut segments = path.trim_start_matches("/v1/").split('/');
+    let Some(id) = (match (segments.next(), segments.next()) {
+        (Some("playbooks" | "actors"), Some(id)) => id.parse::<Uuid>().ok(),
+        _ => None,
+    }) else {
+        return next.run(request).await;
+    };
+
+    let (first, refreshed) = (ctx.clone(), ctx);
+    tokio::spawn(touch(first, id));
+    let refresher = tokio::spawn(async move {
+        let mut interval = interval_at(Instant::now() + REFRESH_INTERVAL, REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            touch(refreshed.clone(), id).await;
+        }
+    });
+    let activity = Activity { _refresher: Arc::new(Refresher(refresher)) };
+    request.extensions_mut().insert(activity.clone());
+
+    let response = next.run(request).await;
+    let streaming =
+        response.headers().get(CONTENT_TYPE).is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"));
+    if !streaming {
+        return response;
+    }
+
+    // The activity is kept along with the body, until the client disconnects.
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &activity;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+async fn touch(ctx: Arc<Context>, id: Uuid) {
+    if let Err(err) = PlaybookService::touch(ctx, id).await {
+        debug!("Failed to record the activity of playbook {}: {}", id, err);
+    }
+}
@@ -14,25 +14,34 @@
 
 use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Duration;
 
 use amp_common::sync::Synchronization;
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use amp_resources::artifact::Kind;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::response::sse::{Event, KeepAlive};
 use axum::response::{IntoResponse, Sse};
-use axum::Json;
+use axum::{Extension, Json};
 
-use futures::{Stream, StreamExt};
-use tokio_stream::wrappers::ReceiverStream;
+use futures::Stream;
+use serde_json::json;
 
 use tracing::info;
 use uuid::Uuid;
 
-use super::Result;
+use super::activity::Activity;
+use super::{authorize, Result};
 use crate::context::Context;
 use crate::errors::ApiError;
+use crate::requests::actor::{
+    CreateActorRequest, ExecQuery, ForwardQuery, LogsQuery, PromoteActorRequest, ScaleActorRequest,
+};
 use crate::services::actor::ActorService;
-use crate::services::logger::Logger;
+use crate::services::forwarder::Forwarder;
+use crate::services::logger::{self, Logger, RateLimiter};
+use crate::services::terminal::Terminal;
 
 // The Actors Service Handlers.
 // See [API Documentation: actor](https://docs.amphitheatre.app/api/actor)
@@ -44,7 +53,7 @@ use crate::services::logger::Logger;
         ("pid" = Uuid, description = "The id of playbook"),
     ),
     responses(
-        (status = 200, description="List all actors of playbook successfully", body = [ActorSpec]),
+        (status = 200, description="List all actors of playbook successfully", body = [ActorDetail]),
         (status = 404, description = "Playbook not found")
     ),
     tag = "Actors"
@@ -53,6 +62,48 @@ pub async fn list(Path(pid): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Res
     Ok(Json(ActorService::list(ctx, pid).await?))
 }
 
+/// Create a detached actor, which is deployed in its own namespace without a playbook.
+#[utoipa::path(
+    post, path = "/v1/actors",
+    request_body(
+        content = inline(CreateActorRequest),
+        description = "Create actor request",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 201, description = "Actor created successfully"),
+        (status = 400, description = "Invalid actor name")
+    ),
+    tag = "Actors"
+)]
+pub async fn create(State(ctx): State<Arc<Context>>, Json(req): Json<CreateActorRequest>) -> Result<impl IntoResponse> {
+    let pid = ActorService::create(ctx, &req).await?;
+    Ok((StatusCode::CREATED, Json(json!({ "pid": pid, "name": req.name }))))
+}
+
+/// Delete a detached actor and its namespace.
+///
+/// The actor is deleted in background, poll the operation for progress.
+#[utoipa::path(
+    delete, path = "/v1/actors/{pid}/{name}",
+    params(
+        ("pid" = Uuid, description = "The id of detached actor's namespace"),
+        ("name" = String, description = "The name of actor"),
+    ),
+    responses(
+        (status = 202, description = "Actor deletion started, poll the operation for progress", body = Operation),
+        (status = 400, description = "Actor is not detached"),
+        (status = 404, description = "Actor not found")
+    ),
+    tag = "Actors"
+)]
+pub async fn delete(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse> {
+    Ok((StatusCode::ACCEPTED, Json(ActorService::delete(ctx, pid, name).await?)))
+}
+
 /// Returns a actor detail.
 #[utoipa::path(
     get, path = "/v1/actors/{pid}/{name}",
@@ -61,7 +112,7 @@ pub async fn list(Path(pid): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Res
         ("name" = String, description = "The name of actor"),
     ),
     responses(
-        (status = 200, description="Actor found successfully", body = ActorSpec),
+        (status = 200, description="Actor found successfully", body = ActorDetail),
         (status = 404, description = "Actor not found")
     ),
     tag = "Actors"
@@ -74,14 +125,21 @@ pub async fn detail(
 }
 
 /// Output the log streams of actor
+///
+/// The stream is compressed with zstd or gzip if the client accepts it, every
+/// event carries an offset token as its id for resuming after reconnecting.
+/// An `attached` event is sent before the lines of every container, it's
+/// sent again when the container is restarted or replaced by a new pod.
 #[utoipa::path(
     get, path = "/v1/actors/{pid}/{name}/logs",
     params(
         ("pid" = Uuid, description = "The id of playbook"),
         ("name" = String, description = "The name of actor"),
+        LogsQuery,
     ),
     responses(
         (status = 200, description="Actor's logs found successfully"),
+        (status = 400, description = "Invalid since"),
         (status = 404, description = "Actor not found")
     ),
     tag = "Actors"
@@ -89,19 +147,37 @@ pub async fn detail(
 pub async fn logs(
     State(ctx): State<Arc<Context>>,
     Path((pid, name)): Path<(Uuid, String)>,
-) -> Sse<impl Stream<Item = axum::response::Result<Event, Infallible>>> {
+    Query(query): Query<LogsQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = axum::response::Result<Event, Infallible>>>> {
     info!("Start to tail the log stream of actor {} in {}...", name, pid);
+    let since = query
+        .since
+        .as_deref()
+        .map(|value| logger::since(value).ok_or_else(|| ApiError::BadRequest(format!("Invalid since {}", value))));
+    let since = since.transpose()?;
     let (sender, receiver) = tokio::sync::mpsc::channel(100);
 
+    // The `Last-Event-ID` is sent by the EventSource automatically on reconnecting.
+    let offset = headers.get("Last-Event-ID").and_then(|v| v.to_str().ok()).map(String::from).or(query.offset);
+
+    // The client can only lower the server-side rate limit.
+    let limit = ctx.config.log_max_lines_per_second;
+    let limiter = RateLimiter::new(query.rate.map_or(limit, |rate| rate.min(limit)));
+
     // Start to watch the status of the pod.
+    let location = ActorService::locate(&ctx, pid, &name).await?;
     tokio::spawn(async move {
-        Logger::new(ctx.k8s.clone(), sender.clone(), pid, name).start().await;
+        Logger::new(location.client, sender, &location.namespace, location.name)
+            .resume(offset.as_deref())
+            .since(since)
+            .follow(query.follow.unwrap_or(true))
+            .container(query.container)
+            .start()
+            .await;
     });
 
-    let stream = ReceiverStream::new(receiver);
-    let stream = stream.map(Ok);
-
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    Ok(Sse::new(logger::stream(receiver, limiter)).keep_alive(KeepAlive::default()))
 }
 
 /// Returns a actor's info, including environments, volumes...
@@ -170,3 +246,362 @@ pub async fn sync(
     ActorService::sync(ctx, pid, name, req).await.map_err(ApiError::NatsError)?;
     Ok(StatusCode::ACCEPTED)
 }
+
+/// Run a scheduled actor once now, the run is started by the controllers shortly.
+#[utoipa::path(
+    post, path = "/v1/actors/{pid}/{name}/trigger",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+    ),
+    responses(
+        (status = 202, description = "The run of actor is requested successfully"),
+        (status = 400, description = "Actor is not scheduled"),
+        (status = 404, description = "Actor not found")
+    ),
+    tag = "Actors"
+)]
+pub async fn trigger(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse> {
+    let requested_at = ActorService::trigger(ctx, pid, name).await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "requested_at": requested_at }))))
+}
+
+/// Scale a actor to the given number of replicas, which is kept across the updates of actor.
+#[utoipa::path(
+    post, path = "/v1/actors/{pid}/{name}/scale",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+    ),
+    request_body(
+        content = inline(ScaleActorRequest),
+        description = "Scale actor request",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "Actor scaled successfully", body = ActorDetail),
+        (status = 400, description = "Invalid replicas or actor is scheduled"),
+        (status = 404, description = "Actor not found")
+    ),
+    tag = "Actors"
+)]
+pub async fn scale(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+    Json(req): Json<ScaleActorRequest>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(ActorService::scale(ctx, pid, name, &req).await?))
+}
+
+/// Returns a actor's usage of this month, along with its budgets.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/usage",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+    ),
+    responses(
+        (status = 200, description="Actor's usage found successfully", body = ActorUsage),
+        (status = 404, description = "Actor not found")
+    ),
+    tag = "Actors"
+)]
+pub async fn usage(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(ActorService::usage(ctx, pid, name).await?))
+}
+
+/// Returns the SBOM of actor's current image.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/sbom",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+    ),
+    responses(
+        (status = 200, description="Actor's SBOM found successfully"),
+        (status = 404, description = "Actor or SBOM not found")
+    ),
+    tag = "Actors"
+)]
+pub async fn sbom(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(ActorService::sbom(ctx, pid, name).await?))
+}
+
+/// Returns the recent builds of actor, the latest first.
+///
+/// Each attempt is recorded on its own, e.g. the retries of a failed build,
+/// along with the commit, image digest, builder and trigger of it.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/builds",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+    ),
+    responses(
+        (status = 200, description="Actor's builds found successfully", body = [ActorBuild]),
+        (status = 404, description = "Actor not found")
+    ),
+    tag = "Actors"
+)]
+pub async fn builds(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(ActorService::builds(ctx, pid, name).await?))
+}
+
+/// Returns the archived logs of a build of actor.
+///
+/// The logs are archived while the build is running and once it's finished,
+/// so they can be fetched after the build pod is deleted, until they expire.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/builds/{id}/logs",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+        ("id" = String, description = "The id of build, i.e. the name of its build pod"),
+    ),
+    responses(
+        (status = 200, description="The logs of build", content_type = "text/plain"),
+        (status = 404, description = "Actor, build or its logs not found")
+    ),
+    tag = "Actors"
+)]
+pub async fn build_logs(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name, id)): Path<(Uuid, String, String)>,
+) -> Result<impl IntoResponse> {
+    let content = ActorService::build_logs(ctx, pid, name, id).await?;
+    Ok(([(header::CONTENT_TYPE, Kind::BuildLogs.media_type())], content))
+}
+
+/// Promote the built image of a actor to another playbook, e.g. from staging to production.
+///
+/// The image is pinned by its digest and deployed as is by the target playbook,
+/// along with the provenance of it, instead of being built again from the source.
+#[utoipa::path(
+    post, path = "/v1/actors/{pid}/{name}/promote",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+    ),
+    request_body(
+        content = inline(PromoteActorRequest),
+        description = "Promote actor request",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "Actor's image promoted successfully", body = ActorPromotion),
+        (status = 400, description = "Actor has no built image, or the target is its own playbook"),
+        (status = 404, description = "Actor or target playbook not found")
+    ),
+    tag = "Actors"
+)]
+pub async fn promote(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+    Json(req): Json<PromoteActorRequest>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(ActorService::promote(ctx, pid, name, &req).await?))
+}
+
+/// Returns the differences between the actor's synced filesystem and the source.
+///
+/// The source is the snapshot received by the last overwrite synchronization,
+/// only available for the live actors.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/diff",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+    ),
+    responses(
+        (status = 200, description="Actor's filesystem diff found successfully"),
+        (status = 404, description = "Actor not found or not synced yet")
+    ),
+    tag = "Actors"
+)]
+pub async fn diff(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(ActorService::diff(ctx, pid, name).await?))
+}
+
+/// Forwards a WebSocket connection to the port of actor's pod.
+///
+/// The binary messages are the raw TCP stream in both directions. It requires
+/// the configured token, in the `Authorization: Bearer` header or `token` query.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/forward/{port}",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+        ("port" = u16, description = "The port of actor to forward to"),
+        ForwardQuery,
+    ),
+    responses(
+        (status = 101, description="Switching to the WebSocket protocol"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Actor not found or not running")
+    ),
+    security(("token" = [])),
+    tag = "Actors"
+)]
+pub async fn forward(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name, port)): Path<(Uuid, String, u16)>,
+    Query(query): Query<ForwardQuery>,
+    headers: HeaderMap,
+    activity: Option<Extension<Activity>>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse> {
+    authorize(&ctx, &headers, query.token)?;
+
+    let location = ActorService::locate(&ctx, pid, &name).await?;
+    let pod = ActorService::pod(&location).await?;
+    let forwarder = Forwarder::new(location.client, &location.namespace, location.name, port)
+        .idle_timeout(Duration::from_secs(ctx.config.forward_idle_timeout));
+
+    Ok(ws.on_upgrade(move |socket| async move {
+        let _activity = activity;
+        forwarder.forward(pod, socket).await
+    }))
+}
+
+/// Forwards a WebSocket connection to the debug port of actor's pod.
+///
+/// The actor must be running under the debugger, the binary messages are the
+/// raw stream of the debug protocol, e.g. DAP or the Chrome DevTools protocol.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/debug",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+        ForwardQuery,
+    ),
+    responses(
+        (status = 101, description="Switching to the WebSocket protocol"),
+        (status = 400, description = "Actor is not running under the debugger"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Actor not found or not running")
+    ),
+    security(("token" = [])),
+    tag = "Actors"
+)]
+pub async fn debug(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+    Query(query): Query<ForwardQuery>,
+    headers: HeaderMap,
+    activity: Option<Extension<Activity>>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse> {
+    authorize(&ctx, &headers, query.token)?;
+
+    let location = ActorService::locate(&ctx, pid, &name).await?;
+    let port = ActorService::debug_port(&location).await?;
+    let pod = ActorService::pod(&location).await?;
+    let forwarder = Forwarder::new(location.client, &location.namespace, location.name, port)
+        .idle_timeout(Duration::from_secs(ctx.config.forward_idle_timeout));
+
+    Ok(ws.on_upgrade(move |socket| async move {
+        let _activity = activity;
+        forwarder.forward(pod, socket).await
+    }))
+}
+
+/// Forwards a WebSocket connection to the SSH server of actor's dev container.
+///
+/// The actor must be run as a dev container with the authorized keys, the
+/// binary messages are the raw SSH stream, so the editors connect through a
+/// WebSocket proxy command, e.g. `ssh -o ProxyCommand="websocat --binary {url}" root@web`
+/// for VS Code Remote - SSH.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/ssh",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+        ForwardQuery,
+    ),
+    responses(
+        (status = 101, description="Switching to the WebSocket protocol"),
+        (status = 400, description = "Actor is not a dev container with SSH"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Actor not found or not running")
+    ),
+    security(("token" = [])),
+    tag = "Actors"
+)]
+pub async fn ssh(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+    Query(query): Query<ForwardQuery>,
+    headers: HeaderMap,
+    activity: Option<Extension<Activity>>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse> {
+    authorize(&ctx, &headers, query.token)?;
+
+    let location = ActorService::locate(&ctx, pid, &name).await?;
+    let port = ActorService::ssh_port(&location).await?;
+    let pod = ActorService::pod(&location).await?;
+    let forwarder = Forwarder::new(location.client, &location.namespace, location.name, port)
+        .idle_timeout(Duration::from_secs(ctx.config.forward_idle_timeout));
+
+    Ok(ws.on_upgrade(move |socket| async move {
+        let _activity = activity;
+        forwarder.forward(pod, socket).await
+    }))
+}
+
+/// Executes a command in the actor's container over a WebSocket connection.
+///
+/// The binary messages are the stdin and stdout/stderr of the command, and the
+/// text messages are the control messages, e.g. `{"type":"resize","width":80,"height":24}`.
+/// It requires the configured token, in the `Authorization: Bearer` header or `token` query.
+#[utoipa::path(
+    get, path = "/v1/actors/{pid}/{name}/exec",
+    params(
+        ("pid" = Uuid, description = "The id of playbook"),
+        ("name" = String, description = "The name of actor"),
+        ExecQuery,
+    ),
+    responses(
+        (status = 101, description="Switching to the WebSocket protocol"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Actor not found or not running")
+    ),
+    security(("token" = [])),
+    tag = "Actors"
+)]
+pub async fn exec(
+    State(ctx): State<Arc<Context>>,
+    Path((pid, name)): Path<(Uuid, String)>,
+    Query(query): Query<ExecQuery>,
+    headers: HeaderMap,
+    activity: Option<Extension<Activity>>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse> {
+    authorize(&ctx, &headers, query.token)?;
+
+    let command = query.command.as_deref().unwrap_or("/bin/sh").split_whitespace().map(String::from).collect();
+    let location = ActorService::locate(&ctx, pid, &name).await?;
+    let pod = ActorService::pod(&location).await?;
+    let terminal =
+        Terminal::new(location.client, &location.namespace, location.name, command, query.tty.unwrap_or(true));
+
+    Ok(ws.on_upgrade(move |socket| async move {
+        let _activity = activity;
+        terminal.open(pod, socket).await
+    }))
+}
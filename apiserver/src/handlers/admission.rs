@@ -0,0 +1,36 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_common::resource::Actor;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::Json;
+use kube::core::admission::AdmissionReview;
+
+use super::Result;
+use crate::context::Context;
+use crate::services::admission::AdmissionService;
+
+// The Admission Webhook Handlers, called by the Kubernetes API server
+// through a `ValidatingWebhookConfiguration`, so they are not documented.
+
+/// Validate the actors against the registry policy.
+pub async fn actors(
+    State(ctx): State<Arc<Context>>,
+    Json(review): Json<AdmissionReview<Actor>>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(AdmissionService::actors(ctx, review).await?))
+}
@@ -0,0 +1,69 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::artifact::{self, Kind};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::IntoResponse;
+
+use super::{authorize, equals, Result};
+use crate::context::Context;
+use crate::requests::artifact::ArtifactQuery;
+use crate::services::artifact::ArtifactService;
+
+// The Artifacts Service Handlers.
+
+/// Download an artifact by its SHA-256 digest.
+///
+/// The kinds are `sources` uploaded from local, `build-logs` archived after
+/// the builds and `sboms` of the images, they expire after their retention.
+/// It requires the configured token in the `Authorization: Bearer` header,
+/// or the signature of the artifact in the `token` query, which the build
+/// pods download the uploaded sources with.
+#[utoipa::path(
+    get, path = "/v1/artifacts/{kind}/{digest}",
+    params(
+        ("kind" = String, description = "The kind of artifact, `sources`, `build-logs` or `sboms`"),
+        ("digest" = String, description = "The SHA-256 digest of artifact"),
+        ArtifactQuery,
+    ),
+    responses(
+        (status = 200, description = "The content of artifact"),
+        (status = 400, description = "Invalid digest"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Artifact not found or expired"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    security(("token" = [])),
+    tag = "Artifacts"
+)]
+pub async fn download(
+    State(ctx): State<Arc<Context>>,
+    Path((kind, digest)): Path<(String, String)>,
+    Query(query): Query<ArtifactQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    let signed = match (query.token.as_deref(), ctx.config.auth_token.as_deref(), kind.parse::<Kind>()) {
+        (Some(token), Some(key), Ok(kind)) => equals(token, &artifact::signature(key, kind, &digest)),
+        _ => false,
+    };
+    if !signed {
+        authorize(&ctx, &headers, None)?;
+    }
+
+    let (kind, content) = ArtifactService::download(ctx, kind, digest).await?;
+    Ok(([(header::CONTENT_TYPE, kind.media_type())], content))
+}
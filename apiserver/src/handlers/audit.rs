@@ -0,0 +1,99 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::extract::{Query, Request, State};
+use axum::http::{HeaderMap, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures::TryStreamExt;
+use k8s_openapi::chrono::Utc;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use super::{authorize, Result};
+use crate::context::Context;
+use crate::requests::audit::AuditQuery;
+use crate::services::audit::AuditEvent;
+
+// The Audit Service Handlers.
+
+/// Lists the audit events of the mutating API calls, only for the admins.
+#[utoipa::path(
+    get, path = "/v1/audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "List the audit events successfully", body = AuditPage),
+        (status = 401, description = "Unauthorized"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    security(("token" = [])),
+    tag = "Audit"
+)]
+pub async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse> {
+    authorize(&ctx, &headers, None)?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    Ok(Json(ctx.audit.list(query.offset.unwrap_or_default(), limit).await?))
+}
+
+/// The middleware recording every mutating API call, the call is not
+/// failed if it's unable to be recorded.
+pub async fn record(State(ctx): State<Arc<Context>>, request: Request, next: Next) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(request).await;
+    }
+
+    // Digest the payload as it's read by the handler, instead of buffering it.
+    let (parts, payload) = request.into_parts();
+    let hasher = Arc::new(Mutex::new(Sha256::new()));
+    let digesting = hasher.clone();
+    let payload = payload.into_data_stream().inspect_ok(move |chunk| digesting.lock().unwrap().update(chunk));
+
+    let mut event = AuditEvent {
+        id: 0,
+        actor: actor(&parts.headers),
+        source: parts.headers.get("X-Forwarded-For").and_then(|v| v.to_str().ok()).map(String::from),
+        method: parts.method.to_string(),
+        path: parts.uri.path().to_string(),
+        status: 0,
+        digest: String::new(),
+        timestamp: Utc::now(),
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from_stream(payload))).await;
+    event.status = response.status().as_u16();
+    event.digest = format!("{:x}", hasher.lock().unwrap().clone().finalize());
+    if let Err(err) = ctx.audit.record(&event).await {
+        error!("Failed to record the audit event {} {}: {}", event.method, event.path, err);
+    }
+
+    response
+}
+
+/// Identify the caller by the fingerprint of its bearer token, never the token itself.
+pub(crate) fn actor(headers: &HeaderMap) -> String {
+    let token = headers.get("Authorization").and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer "));
+    match token {
+        Some(token) => format!("token:{}", &format!("{:x}", Sha256::digest(token))[..12]),
+        None => "anonymous".into(),
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::Json;
+
+use super::{authorize, Result};
+use crate::context::Context;
+use crate::requests::envset::ApplyEnvSetRequest;
+use crate::services::envset::EnvSetService;
+
+// The Env Sets Service Handlers.
+
+/// Lists the env sets of the workspace.
+#[utoipa::path(
+    get, path = "/v1/workspaces/{workspace}/envsets",
+    params(
+        ("workspace" = String, description = "The name of workspace"),
+    ),
+    responses(
+        (status = 200, description = "List all env sets successfully", body = [EnvSet]),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "EnvSets"
+)]
+pub async fn list(Path(workspace): Path<String>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(EnvSetService::list(ctx, &workspace).await?))
+}
+
+/// Returns an env set detail.
+#[utoipa::path(
+    get, path = "/v1/workspaces/{workspace}/envsets/{name}",
+    params(
+        ("workspace" = String, description = "The name of workspace"),
+        ("name" = String, description = "The name of env set"),
+    ),
+    responses(
+        (status = 200, description = "Env set found successfully", body = EnvSet),
+        (status = 404, description = "Env set not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "EnvSets"
+)]
+pub async fn detail(
+    Path((workspace, name)): Path<(String, String)>,
+    State(ctx): State<Arc<Context>>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(EnvSetService::get(ctx, &workspace, &name).await?))
+}
+
+/// Create or replace an env set.
+///
+/// The secret refs can only reference the Secrets labelled with
+/// `amphitheatre.app/env-set-shared=true`. It requires the configured token.
+#[utoipa::path(
+    put, path = "/v1/workspaces/{workspace}/envsets/{name}",
+    params(
+        ("workspace" = String, description = "The name of workspace"),
+        ("name" = String, description = "The name of env set"),
+    ),
+    request_body(
+        content = inline(ApplyEnvSetRequest),
+        description = "Apply env set request",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "Env set applied successfully", body = EnvSet),
+        (status = 400, description = "The referenced Secret is not shared"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    security(("token" = [])),
+    tag = "EnvSets"
+)]
+pub async fn apply(
+    Path((workspace, name)): Path<(String, String)>,
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<ApplyEnvSetRequest>,
+) -> Result<impl IntoResponse> {
+    authorize(&ctx, &headers, None)?;
+    Ok(Json(EnvSetService::apply(ctx, &workspace, &name, &req).await?))
+}
+
+/// Delete an env set, it requires the configured token.
+#[utoipa::path(
+    delete, path = "/v1/workspaces/{workspace}/envsets/{name}",
+    params(
+        ("workspace" = String, description = "The name of workspace"),
+        ("name" = String, description = "The name of env set"),
+    ),
+    responses(
+        (status = 204, description = "Env set deleted successfully"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Env set not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    security(("token" = [])),
+    tag = "EnvSets"
+)]
+pub async fn delete(
+    Path((workspace, name)): Path<(String, String)>,
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    authorize(&ctx, &headers, None)?;
+    EnvSetService::delete(ctx, &workspace, &name).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
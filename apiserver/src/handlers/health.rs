@@ -0,0 +1,62 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::health::{self, Check};
+use async_nats::connection::State as Connection;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+
+use crate::context::Context;
+
+// The Health Service Handlers.
+
+/// The process is alive as long as it serves the requests.
+#[utoipa::path(
+    get, path = "/healthz",
+    responses(
+        (status = 200, description = "The apiserver is alive"),
+    ),
+    tag = "Health"
+)]
+pub async fn healthz() -> impl IntoResponse {
+    "ok"
+}
+
+/// Ready once Kubernetes is reachable, the CRDs are installed and the NATS
+/// server persisting the operations and audit log is connected.
+#[utoipa::path(
+    get, path = "/readyz",
+    responses(
+        (status = 200, description = "The apiserver is ready"),
+        (status = 503, description = "Some of the checks failed"),
+    ),
+    tag = "Health"
+)]
+pub async fn readyz(State(ctx): State<Arc<Context>>) -> impl IntoResponse {
+    let nats = match ctx.nats.connection_state() {
+        Connection::Connected => Ok(()),
+        state => Err(format!("The NATS connection is {:?}", state)),
+    };
+    let checks: Vec<Check> =
+        vec![health::kubernetes(&ctx.k8s).await, health::crds(&ctx.k8s).await, Check::new("nats", nats)];
+
+    match health::ready(&checks) {
+        true => (StatusCode::OK, Json(checks)),
+        false => (StatusCode::SERVICE_UNAVAILABLE, Json(checks)),
+    }
+}
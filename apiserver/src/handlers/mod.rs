@@ -12,7 +12,51 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod activity;
 pub mod actor;
+pub mod admission;
+pub mod artifact;
+pub mod audit;
+pub mod envset;
+pub mod health;
+pub mod operation;
 pub mod playbook;
+pub mod quota;
+pub mod search;
+pub mod source;
+pub mod template;
+pub mod webhook;
 
-type Result<T, E = crate::errors::ApiError> = std::result::Result<T, E>;
+use axum::http::header::UPGRADE;
+use axum::http::HeaderMap;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+
+type Result<T, E = ApiError> = std::result::Result<T, E>;
+
+/// Compare the secrets in constant time, so the matched prefix can't be told by the timing.
+pub(crate) fn equals(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Check the token in the `Authorization: Bearer` header or the query against the configured one.
+/// The token in the query is only accepted by the WebSocket upgrades, as the browsers can't set the
+/// headers of them, the query of other requests is likely to be logged along with the URL.
+pub(crate) fn authorize(ctx: &Context, headers: &HeaderMap, token: Option<String>) -> Result<()> {
+    let upgrade =
+        headers.get(UPGRADE).and_then(|v| v.to_str().ok()).is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let token = headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(String::from)
+        .or(token.filter(|_| upgrade));
+
+    let expected = ctx.config.auth_token.as_deref();
+    if !token.zip(expected).is_some_and(|(token, expected)| equals(&token, expected)) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(())
+}
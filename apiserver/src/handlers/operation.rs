@@ -0,0 +1,43 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::Json;
+use uuid::Uuid;
+
+use super::Result;
+use crate::context::Context;
+use crate::services::operation::OperationService;
+
+// The Operations Service Handlers.
+
+/// Returns the progress of a long-running operation.
+#[utoipa::path(
+    get, path = "/v1/operations/{id}",
+    params(
+        ("id" = Uuid, description = "The id of operation"),
+    ),
+    responses(
+        (status = 200, description = "Operation found successfully", body = Operation),
+        (status = 404, description = "Operation not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Operations"
+)]
+pub async fn detail(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(OperationService::get(ctx, id).await?))
+}
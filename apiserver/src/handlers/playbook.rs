@@ -12,26 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
 use std::time::Duration;
 
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::response::sse::{Event, KeepAlive};
 use axum::response::{IntoResponse, Sse};
 use axum::Json;
 use futures::Stream;
 use k8s_openapi::api::core::v1::Event as KEvent;
+use k8s_openapi::chrono::{DateTime, SecondsFormat, Utc};
 use kube::runtime::{watcher, WatchStreamExt};
 use kube::Api;
+use serde_json::json;
 use tokio_stream::StreamExt as _;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use super::Result;
 use crate::context::Context;
-use crate::requests::playbook::{CreatePlaybookRequest, UpdatePlaybookRequest};
+use crate::errors::ApiError;
+use crate::requests::actor::LogsQuery;
+use crate::requests::playbook::{
+    CreatePlaybookQuery, CreatePlaybookRequest, DeletePlaybookQuery, EventsQuery, ExportQuery, ImportPlaybookRequest,
+    ListPlaybooksQuery, ResourcesQuery, UpdatePlaybookRequest,
+};
+use crate::services::cost::CostService;
+use crate::services::logger::{self, Logger, RateLimiter};
+use crate::services::metrics::MetricsService;
 use crate::services::playbook::PlaybookService;
+use crate::services::resource::ResourceService;
+use crate::services::revision::RevisionService;
 
 // The Playbooks Service Handlers.
 // See [API Documentation: playbook](https://docs.amphitheatre.app/api/playbook)
@@ -39,34 +53,45 @@ use crate::services::playbook::PlaybookService;
 /// Lists the playbooks in the current account.
 #[utoipa::path(
     get, path = "/v1/playbooks",
+    params(ListPlaybooksQuery),
     responses(
         (status = 200, description = "List all playbooks successfully", body = [PlaybookSpec]),
         (status = 500, description = "Internal Server Error"),
     ),
     tag = "Playbooks"
 )]
-pub async fn list(State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
-    Ok(Json(PlaybookService::list(ctx).await?))
+pub async fn list(
+    State(ctx): State<Arc<Context>>,
+    Query(query): Query<ListPlaybooksQuery>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(PlaybookService::list(ctx, query.deleted.unwrap_or_default()).await?))
 }
 
 /// Create a playbook in the current account.
 #[utoipa::path(
     post, path = "/v1/playbooks",
+    params(CreatePlaybookQuery),
     request_body(
         content = inline(CreatePlaybookRequest),
         description = "Create playbook request",
         content_type = "application/json"
     ),
     responses(
-        (status = 201, description = "Playbook created successfully", body = PlaybookSpec)
+        (status = 201, description = "Playbook created successfully", body = PlaybookSpec),
+        (status = 200, description = "Playbook planned successfully (dry run)", body = Plan),
     ),
     tag = "Playbooks"
 )]
 pub async fn create(
     State(ctx): State<Arc<Context>>,
+    Query(query): Query<CreatePlaybookQuery>,
     Json(req): Json<CreatePlaybookRequest>,
 ) -> Result<impl IntoResponse> {
-    Ok((StatusCode::CREATED, Json(PlaybookService::create(ctx, &req).await?)))
+    if query.dry_run.unwrap_or_default() {
+        return Ok(Json(PlaybookService::dry_run(ctx, &req).await?).into_response());
+    }
+
+    Ok((StatusCode::CREATED, Json(PlaybookService::create(ctx, &req).await?)).into_response())
 }
 
 /// Returns a playbook detail.
@@ -87,6 +112,9 @@ pub async fn detail(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Re
 }
 
 /// Update a playbook.
+///
+/// The title, description and variables are updated if given, the variables
+/// are replaced as a whole and rolled out to the actors referencing them.
 #[utoipa::path(
     patch, path = "/v1/playbooks/{id}",
     params(
@@ -99,6 +127,7 @@ pub async fn detail(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Re
     ),
     responses(
         (status = 200, description = "Playbook updated successfully", body = PlaybookSpec),
+        (status = 400, description = "Invalid or undefined variables"),
         (status = 404, description = "Playbook not found")
     ),
     tag = "Playbooks"
@@ -112,28 +141,246 @@ pub async fn update(
 }
 
 /// Delete a playbook
+///
+/// The playbook is moved into the trash with its actors scaled to zero, and it's
+/// purged after the retention window unless it's restored, or at once if `purge` is set.
 #[utoipa::path(
     delete, path = "/v1/playbooks/{id}",
     params(
         ("id" = Uuid, description = "The id of playbook"),
+        DeletePlaybookQuery,
     ),
     responses(
-        (status = 204, description = "Playbook deleted successfully"),
+        (status = 200, description = "Playbook moved into the trash successfully"),
+        (status = 202, description = "Playbook deletion started, poll the operation for progress", body = Operation),
         (status = 404, description = "Playbook not found")
     ),
     tag = "Playbooks"
 )]
-pub async fn delete(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
-    PlaybookService::delete(ctx, id).await?;
+pub async fn delete(
+    Path(id): Path<Uuid>,
+    State(ctx): State<Arc<Context>>,
+    Query(query): Query<DeletePlaybookQuery>,
+) -> Result<impl IntoResponse> {
+    if query.purge.unwrap_or_default() {
+        return Ok((StatusCode::ACCEPTED, Json(PlaybookService::purge(ctx, id).await?)).into_response());
+    }
 
-    Ok(StatusCode::NO_CONTENT)
+    let deleted_at = PlaybookService::delete(ctx, id).await?;
+    Ok(Json(json!({ "deleted_at": deleted_at })).into_response())
+}
+
+/// Restore a deleted playbook from the trash, its actors are scaled back.
+#[utoipa::path(
+    post, path = "/v1/playbooks/{id}/restore",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+    ),
+    responses(
+        (status = 200, description = "Playbook restored successfully", body = PlaybookSpec),
+        (status = 400, description = "Playbook is not deleted"),
+        (status = 404, description = "Playbook not found or already purged")
+    ),
+    tag = "Playbooks"
+)]
+pub async fn restore(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(PlaybookService::restore(ctx, id).await?))
+}
+
+/// Returns the revisions of a playbook, ordered from the oldest.
+#[utoipa::path(
+    get, path = "/v1/playbooks/{id}/revisions",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+    ),
+    responses(
+        (status = 200, description = "List all revisions of playbook successfully", body = [Revision]),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn revisions(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(RevisionService::list(ctx, id).await?))
+}
+
+/// Roll back a playbook to a previous revision, its actors are re-applied in background.
+#[utoipa::path(
+    post, path = "/v1/playbooks/{id}/rollback/{rev}",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+        ("rev" = u64, description = "The number of revision"),
+    ),
+    responses(
+        (status = 202, description = "Playbook rollback started, poll the operation for progress", body = Operation),
+        (status = 404, description = "Playbook or revision not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn rollback(
+    Path((id, rev)): Path<(Uuid, u64)>,
+    State(ctx): State<Arc<Context>>,
+) -> Result<impl IntoResponse> {
+    Ok((StatusCode::ACCEPTED, Json(RevisionService::rollback(ctx, id, rev).await?)))
+}
+
+/// Returns the manifests exported for a playbook, as applied by the GitOps tools.
+#[utoipa::path(
+    get, path = "/v1/playbooks/{id}/manifests",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+    ),
+    responses(
+        (status = 200, description = "Playbook manifests found successfully", body = String,
+            content_type = "application/yaml"),
+        (status = 404, description = "Playbook not found or not exported"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn manifests(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    let manifests = PlaybookService::manifests(ctx, id).await?;
+
+    Ok(([(header::CONTENT_TYPE, "application/yaml")], manifests))
+}
+
+/// Export a playbook as a portable document, along with its characters and variables.
+///
+/// The secrets are left out, the document can be imported to another cluster,
+/// or shared with teammates, with the secrets supplied on import. All of the
+/// variables, environment variables and arguments are taken as secrets,
+/// unless they are listed as public.
+#[utoipa::path(
+    get, path = "/v1/playbooks/{id}/export",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+        ExportQuery,
+    ),
+    responses(
+        (status = 200, description = "Playbook exported successfully", body = String,
+            content_type = "application/yaml"),
+        (status = 400, description = "Unknown format"),
+        (status = 404, description = "Playbook not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn export(
+    Path(id): Path<Uuid>,
+    State(ctx): State<Arc<Context>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse> {
+    let public: Vec<String> =
+        query.public.iter().flat_map(|names| names.split(',')).map(|n| n.trim().to_string()).collect();
+    let (format, content) = PlaybookService::export(ctx, id, query.format.as_deref(), &public).await?;
+
+    Ok(([(header::CONTENT_TYPE, format.media_type())], content))
+}
+
+/// Import a playbook from the document exported from another one.
+#[utoipa::path(
+    post, path = "/v1/playbooks/import",
+    request_body(
+        content = inline(ImportPlaybookRequest),
+        description = "Import playbook request",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 201, description = "Playbook imported successfully", body = PlaybookSpec),
+        (status = 400, description = "Invalid document or missing secrets"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn import(
+    State(ctx): State<Arc<Context>>,
+    Json(req): Json<ImportPlaybookRequest>,
+) -> Result<impl IntoResponse> {
+    Ok((StatusCode::CREATED, Json(PlaybookService::import(ctx, &req).await?)))
+}
+
+/// Resolve a playbook and returns what would be changed, without applying anything.
+#[utoipa::path(
+    post, path = "/v1/playbooks/{id}/plan",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+    ),
+    responses(
+        (status = 200, description = "Playbook planned successfully", body = Plan),
+        (status = 404, description = "Playbook not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn plan(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(PlaybookService::plan(ctx, id).await?))
+}
+
+/// Output the log streams of all the actors in playbook
+///
+/// Like `docker compose logs -f`, every line is prefixed with its actor and
+/// container, e.g. `web/app | ...`. The query and the `attached` events are
+/// the same as the logs of a single actor, the latter carry a stable color of
+/// the actor for the clients to render the prefixes in.
+#[utoipa::path(
+    get, path = "/v1/playbooks/{id}/logs",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+        LogsQuery,
+    ),
+    responses(
+        (status = 200, description="Playbook's logs found successfully"),
+        (status = 400, description = "Invalid since"),
+        (status = 404, description = "Playbook not found")
+    ),
+    tag = "Playbooks"
+)]
+pub async fn logs(
+    State(ctx): State<Arc<Context>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<LogsQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = axum::response::Result<Event, Infallible>>>> {
+    info!("Start to tail the log streams of playbook {}...", id);
+    let since = query
+        .since
+        .as_deref()
+        .map(|value| logger::since(value).ok_or_else(|| ApiError::BadRequest(format!("Invalid since {}", value))));
+    let since = since.transpose()?;
+    let (sender, receiver) = tokio::sync::mpsc::channel(100);
+
+    // The `Last-Event-ID` is sent by the EventSource automatically on reconnecting.
+    let offset = headers.get("Last-Event-ID").and_then(|v| v.to_str().ok()).map(String::from).or(query.offset);
+
+    // The client can only lower the server-side rate limit, shared by all the actors.
+    let limit = ctx.config.log_max_lines_per_second;
+    let limiter = RateLimiter::new(query.rate.map_or(limit, |rate| rate.min(limit)));
+
+    let client = PlaybookService::client(&ctx, id).await?;
+    let namespace = PlaybookService::namespace(&ctx, id).await?;
+    let shared = !PlaybookService::dedicated(&ctx, id).await?;
+    tokio::spawn(async move {
+        Logger::playbook(client, sender, &namespace, shared.then_some(id))
+            .resume(offset.as_deref())
+            .since(since)
+            .follow(query.follow.unwrap_or(true))
+            .container(query.container)
+            .start()
+            .await;
+    });
+
+    Ok(Sse::new(logger::stream(receiver, limiter)).keep_alive(KeepAlive::default()))
 }
 
 /// Output the event streams of playbook
+///
+/// Every event carries its time and uid as the id, e.g. `{time}/{uid}`, so
+/// that the clients can resume the stream from it on reconnecting, e.g. when
+/// the apiserver restarts. The events at the same time are sent once per uid.
 #[utoipa::path(
     get, path = "/v1/playbooks/{id}/events",
     params(
         ("id" = Uuid, description = "The id of playbook"),
+        EventsQuery,
     ),
     responses(
         (status = 200, description="Playbook's events found successfully"),
@@ -144,22 +391,163 @@ pub async fn delete(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Re
 pub async fn events(
     Path(id): Path<Uuid>,
     State(ctx): State<Arc<Context>>,
-) -> Sse<impl Stream<Item = axum::response::Result<Event, Infallible>>> {
-    let namespace = format!("amp-{}", id);
-    let api: Api<KEvent> = Api::namespaced(ctx.k8s.clone(), namespace.as_str());
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = axum::response::Result<Event, Infallible>>>> {
+    // The `Last-Event-ID` is sent by the EventSource automatically on reconnecting.
+    let token = headers.get("Last-Event-ID").and_then(|v| v.to_str().ok()).map(String::from).or(query.offset);
+    let (time, uid) = match token.as_deref().map(|token| token.split_once('/')) {
+        Some(Some((time, uid))) => (Some(time), Some(uid.to_string())),
+        Some(None) => (token.as_deref(), None),
+        None => (None, None),
+    };
+    let offset = time.and_then(|time| match DateTime::parse_from_rfc3339(time) {
+        Ok(offset) => Some(offset.with_timezone(&Utc)),
+        Err(err) => {
+            warn!("Ignore the invalid event offset token {}: {}", time, err);
+            None
+        }
+    });
+
+    // The latest time each event is sent at by its uid, as the watcher lists
+    // the existing events again after it restarts.
+    let mut sent: HashMap<String, Option<DateTime<Utc>>> = HashMap::new();
+    if let Some(uid) = uid {
+        sent.insert(uid, offset);
+    }
+
+    let namespace = PlaybookService::namespace(&ctx, id).await?;
+    let client = PlaybookService::client(&ctx, id).await.unwrap_or_else(|err| {
+        warn!("Failed to connect to the cluster of playbook {}, fall back to the control plane: {}", id, err);
+        ctx.k8s.clone()
+    });
+    let api: Api<KEvent> = Api::namespaced(client, namespace.as_str());
     let stream = watcher(api, watcher::Config::default())
         .applied_objects()
+        // The watcher lists all the existing events first, skip the ones sent before reconnecting.
+        .filter(move |result| {
+            let Ok(event) = result else {
+                return true;
+            };
+            let time = timestamp(event);
+            if time.zip(offset).is_some_and(|(time, offset)| time < offset) {
+                return false;
+            }
+            let Some(uid) = event.metadata.uid.clone() else {
+                return true;
+            };
+            if sent.get(&uid).is_some_and(|last| time <= *last) {
+                return false;
+            }
+            sent.insert(uid, time);
+            true
+        })
         .map(|result| match result {
-            Ok(event) => Event::default().json_data(event).unwrap(),
+            Ok(event) => {
+                let time = timestamp(&event).map(|time| time.to_rfc3339_opts(SecondsFormat::Micros, true));
+                let id = time.map(|time| format!("{}/{}", time, event.metadata.uid.as_deref().unwrap_or_default()));
+                let data = Event::default().json_data(event).unwrap();
+                match id {
+                    Some(id) => data.id(id),
+                    None => data,
+                }
+            }
             Err(err) => Event::default().event("error").data(err.to_string()),
         })
         .map(Ok)
         .throttle(Duration::from_secs(1));
 
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Returns the time of the latest occurrence of the event.
+fn timestamp(event: &KEvent) -> Option<DateTime<Utc>> {
+    let event_time = event.event_time.as_ref().map(|time| time.0);
+    let last_timestamp = event.last_timestamp.as_ref().map(|time| time.0);
+    let created = event.metadata.creation_timestamp.as_ref().map(|time| time.0);
+
+    event_time.max(last_timestamp).or(created)
+}
+
+/// Lists the Kubernetes objects managed by Amphitheatre in the namespace of
+/// playbook, or streams their changes (`added`, `modified` and `deleted`)
+/// if `watch` is set, the existing objects are sent as added first.
+///
+/// The changes in between can't be replayed on reconnecting, so a `reset`
+/// event is sent first when resuming, the clients should drop the objects
+/// they know, as the existing ones are sent again.
+#[utoipa::path(
+    get, path = "/v1/playbooks/{id}/resources",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+        ResourcesQuery,
+    ),
+    responses(
+        (status = 200, description = "Playbook's resources found successfully", body = [PlaybookResource]),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn resources(
+    Path(id): Path<Uuid>,
+    State(ctx): State<Arc<Context>>,
+    Query(query): Query<ResourcesQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    if !query.watch.unwrap_or_default() {
+        return Ok(Json(ResourceService::list(ctx, id).await?).into_response());
+    }
+
+    // The `Last-Event-ID` is sent by the EventSource automatically on reconnecting.
+    let reset = headers.contains_key("Last-Event-ID").then(|| Event::default().event("reset").data(""));
+
+    let namespace = PlaybookService::namespace(&ctx, id).await?;
+    let client = PlaybookService::client(&ctx, id).await?;
+    let changes = ResourceService::watch(&client, &namespace).map(|result| {
+        let event = match result {
+            Ok(event) => Event::default().json_data(event).unwrap(),
+            Err(err) => Event::default().event("error").data(err.to_string()),
+        };
+        event.id(Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true))
+    });
+    let stream = tokio_stream::iter(reset).chain(changes).map(Ok::<_, Infallible>);
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()).into_response())
+}
+
+/// Returns the live cpu and memory consumption of a playbook and its actors.
+#[utoipa::path(
+    get, path = "/v1/playbooks/{id}/metrics",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+    ),
+    responses(
+        (status = 200, description = "Playbook's metrics found successfully", body = PlaybookMetrics),
+        (status = 500, description = "Metrics not available"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn metrics(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(MetricsService::playbook(ctx, id).await?))
+}
+
+/// Returns the estimated cost of a playbook and its actors, by the resources requested and their runtime.
+#[utoipa::path(
+    get, path = "/v1/playbooks/{id}/cost",
+    params(
+        ("id" = Uuid, description = "The id of playbook"),
+    ),
+    responses(
+        (status = 200, description = "Playbook's cost estimated successfully", body = PlaybookCost),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Playbooks"
+)]
+pub async fn cost(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(CostService::playbook(ctx, id).await?))
 }
 
-/// Start a playbook.
+/// Start a playbook, the hibernated or archived playbook is resumed with its actors scaled back.
 #[utoipa::path(
     post, path = "/v1/playbooks/{id}/actions/start",
     params(
@@ -178,7 +566,7 @@ pub async fn start(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Res
     Ok(StatusCode::NO_CONTENT)
 }
 
-/// Stop a playbook.
+/// Stop a playbook, it's hibernated with its actors scaled to zero until it's used again.
 #[utoipa::path(
     post, path = "/v1/playbooks/{id}/actions/stop",
     params(
@@ -0,0 +1,157 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+
+use axum::extract::{ConnectInfo, Request};
+use axum::http::header::RETRY_AFTER;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures::future::{self, Either, Ready};
+use serde_json::json;
+use tower::{Layer, Service};
+use tracing::warn;
+
+use super::{audit, equals};
+use crate::services::quota::Quotas;
+
+/// The layer limiting the request rate of every caller, protecting the
+/// Kubernetes API server behind from the runaway clients.
+#[derive(Clone)]
+pub struct QuotaLayer {
+    quotas: Quotas,
+    /// The configured token, the callers presenting it are authenticated.
+    token: Option<String>,
+}
+
+impl QuotaLayer {
+    pub fn new(quotas: Quotas, token: Option<String>) -> Self {
+        Self { quotas, token }
+    }
+}
+
+impl<S> Layer<S> for QuotaLayer {
+    type Service = Quota<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Quota { inner, quotas: self.quotas.clone(), token: self.token.clone() }
+    }
+}
+
+/// The middleware service of the [`QuotaLayer`].
+#[derive(Clone)]
+pub struct Quota<S> {
+    inner: S,
+    quotas: Quotas,
+    token: Option<String>,
+}
+
+impl<S> Service<Request> for Quota<S>
+where
+    S: Service<Request, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<Ready<Result<Response, S::Error>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        if self.quotas.disabled() {
+            return Either::Right(self.inner.call(request));
+        }
+
+        let principal = principal(&request, self.token.as_deref());
+        if let Err(wait) = self.quotas.acquire(&principal) {
+            warn!("Rate limit exceeded by {} on {} {}", principal, request.method(), request.uri().path());
+            let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let message = json!({ "message": "Too Many Requests" });
+            let response = (StatusCode::TOO_MANY_REQUESTS, [(RETRY_AFTER, seconds.to_string())], Json(message));
+            return Either::Left(future::ok(response.into_response()));
+        }
+
+        Either::Right(self.inner.call(request))
+    }
+}
+
+/// Identify the caller by the fingerprint of its token if it's authenticated,
+/// otherwise by its peer address. The forwarded headers are never trusted,
+/// as they're set by the callers themselves.
+fn principal(request: &Request, token: Option<&str>) -> String {
+    let headers = request.headers();
+    let bearer = headers.get("Authorization").and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer "));
+    if bearer.zip(token).is_some_and(|(bearer, token)| equals(bearer, token)) {
+        return audit::actor(headers);
+    }
+
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip().to_string());
+    format!("ip:{}", peer.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use std::convert::Infallible;
+    use tower::ServiceExt;
+
+    fn request(peer: [u8; 4], token: Option<&str>, forwarded: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri("/v1/playbooks");
+        if let Some(token) = token {
+            builder = builder.header("Authorization", format!("Bearer {}", token));
+        }
+        if let Some(forwarded) = forwarded {
+            builder = builder.header("X-Forwarded-For", forwarded);
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from((peer, 8170))));
+        request
+    }
+
+    #[test]
+    fn test_principal() {
+        let authenticated = request([10, 0, 0, 1], Some("secret"), None);
+        assert!(principal(&authenticated, Some("secret")).starts_with("token:"));
+
+        // The invalid tokens and the forwarded addresses are not identities.
+        let invalid = request([10, 0, 0, 1], Some("guess"), Some("192.168.0.1"));
+        assert_eq!(principal(&invalid, Some("secret")), "ip:10.0.0.1");
+
+        let anonymous = request([10, 0, 0, 2], None, Some("192.168.0.1"));
+        assert_eq!(principal(&anonymous, None), "ip:10.0.0.2");
+    }
+
+    #[tokio::test]
+    async fn test_quota_layer() {
+        let layer = QuotaLayer::new(Quotas::new(1.0, 1), None);
+        let service =
+            layer.layer(tower::service_fn(|_: Request| async { Ok::<_, Infallible>(StatusCode::OK.into_response()) }));
+
+        let response = service.clone().oneshot(request([10, 0, 0, 1], None, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Spoofing the forwarded address doesn't get a new bucket.
+        let response = service.clone().oneshot(request([10, 0, 0, 1], None, Some("192.168.0.1"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(RETRY_AFTER));
+
+        let response = service.clone().oneshot(request([10, 0, 0, 2], None, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::response::IntoResponse;
+use axum::Json;
+
+use super::Result;
+use crate::context::Context;
+use crate::requests::search::SearchQuery;
+use crate::services::search::SearchService;
+
+// The Search Service Handlers.
+
+/// Search the playbooks and actors by their titles, descriptions, names,
+/// repositories and labels, the most relevant first.
+#[utoipa::path(
+    get, path = "/v1/search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Search the playbooks and actors successfully", body = [SearchResult]),
+        (status = 400, description = "The search query is empty or the label selector is invalid"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Search"
+)]
+pub async fn search(State(ctx): State<Arc<Context>>, Query(query): Query<SearchQuery>) -> Result<impl IntoResponse> {
+    Ok(Json(SearchService::search(ctx, &query).await?))
+}
@@ -0,0 +1,55 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use axum::body::{self, Body};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+
+use super::Result;
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::source::SourceService;
+
+// The Sources Service Handlers.
+
+/// Upload the local source which is not pushed to Git yet.
+///
+/// The characters whose repository is the returned `upload://{id}` are built
+/// from the source, so the playbooks can be run from uncommitted code.
+#[utoipa::path(
+    post, path = "/v1/sources",
+    request_body(
+        content = Vec<u8>,
+        description = "The uncompressed tar archive of the source",
+        content_type = "application/x-tar"
+    ),
+    responses(
+        (status = 201, description = "Upload the source successfully", body = Upload),
+        (status = 400, description = "Invalid tar archive"),
+        (status = 413, description = "The source is too large"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Sources"
+)]
+pub async fn upload(State(ctx): State<Arc<Context>>, payload: Body) -> Result<impl IntoResponse> {
+    let tarball = body::to_bytes(payload, ctx.config.upload_max_size)
+        .await
+        .map_err(|_| ApiError::PayloadTooLarge(format!("The source exceeds {} bytes", ctx.config.upload_max_size)))?;
+
+    Ok((StatusCode::CREATED, Json(SourceService::upload(ctx, &tarball).await?)))
+}
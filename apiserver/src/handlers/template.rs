@@ -0,0 +1,146 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use uuid::Uuid;
+
+use super::Result;
+use crate::context::Context;
+use crate::requests::template::{CreateTemplateRequest, InstantiateTemplateRequest, UpdateTemplateRequest};
+use crate::services::template::TemplateService;
+
+// The Templates Service Handlers.
+
+/// Lists the playbook templates.
+#[utoipa::path(
+    get, path = "/v1/templates",
+    responses(
+        (status = 200, description = "List all templates successfully", body = [Template]),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Templates"
+)]
+pub async fn list(State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(TemplateService::list(ctx).await?))
+}
+
+/// Create a playbook template.
+#[utoipa::path(
+    post, path = "/v1/templates",
+    request_body(
+        content = inline(CreateTemplateRequest),
+        description = "Create template request",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 201, description = "Template created successfully", body = Template)
+    ),
+    tag = "Templates"
+)]
+pub async fn create(
+    State(ctx): State<Arc<Context>>,
+    Json(req): Json<CreateTemplateRequest>,
+) -> Result<impl IntoResponse> {
+    Ok((StatusCode::CREATED, Json(TemplateService::create(ctx, &req).await?)))
+}
+
+/// Returns a template detail.
+#[utoipa::path(
+    get, path = "/v1/templates/{id}",
+    params(
+        ("id" = Uuid, description = "The id of template"),
+    ),
+    responses(
+        (status = 200, description = "Template found successfully", body = Template),
+        (status = 404, description = "Template not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+    tag = "Templates"
+)]
+pub async fn detail(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    Ok(Json(TemplateService::get(ctx, id).await?))
+}
+
+/// Update a template.
+#[utoipa::path(
+    patch, path = "/v1/templates/{id}",
+    params(
+        ("id" = Uuid, description = "The id of template"),
+    ),
+    request_body(
+        content = inline(UpdateTemplateRequest),
+        description = "Update template request",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "Template updated successfully", body = Template),
+        (status = 404, description = "Template not found"),
+    ),
+    tag = "Templates"
+)]
+pub async fn update(
+    Path(id): Path<Uuid>,
+    State(ctx): State<Arc<Context>>,
+    Json(req): Json<UpdateTemplateRequest>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(TemplateService::update(ctx, id, &req).await?))
+}
+
+/// Delete a template, the playbooks instantiated from it are kept.
+#[utoipa::path(
+    delete, path = "/v1/templates/{id}",
+    params(
+        ("id" = Uuid, description = "The id of template"),
+    ),
+    responses(
+        (status = 204, description = "Template deleted successfully"),
+        (status = 404, description = "Template not found"),
+    ),
+    tag = "Templates"
+)]
+pub async fn delete(Path(id): Path<Uuid>, State(ctx): State<Arc<Context>>) -> Result<impl IntoResponse> {
+    TemplateService::delete(ctx, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Substitute the variables of a template and create a playbook with it.
+#[utoipa::path(
+    post, path = "/v1/templates/{id}/instantiate",
+    params(
+        ("id" = Uuid, description = "The id of template"),
+    ),
+    request_body(
+        content = inline(InstantiateTemplateRequest),
+        description = "Instantiate template request",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 201, description = "Playbook created successfully", body = PlaybookSpec),
+        (status = 400, description = "Missing variables"),
+        (status = 404, description = "Template not found"),
+    ),
+    tag = "Templates"
+)]
+pub async fn instantiate(
+    Path(id): Path<Uuid>,
+    State(ctx): State<Arc<Context>>,
+    Json(req): Json<InstantiateTemplateRequest>,
+) -> Result<impl IntoResponse> {
+    Ok((StatusCode::CREATED, Json(TemplateService::instantiate(ctx, id, &req).await?)))
+}
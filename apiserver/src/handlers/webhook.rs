@@ -0,0 +1,94 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::{hex, hmac};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde_json::{json, Value};
+
+use super::{equals, Result};
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::webhook::WebhookService;
+
+/// Rebuild the actors changed by a push to their branch.
+///
+/// It accepts the push events of GitHub, GitLab and Gitea, only the actors
+/// whose build contexts contain the changed files are rebuilt, so the
+/// characters of a monorepo are rebuilt independently. It requires the
+/// configured webhook secret, the events of GitHub and Gitea are signed with
+/// it in the `X-Hub-Signature-256` header, and GitLab sends it in the
+/// `X-Gitlab-Token` header.
+#[utoipa::path(
+    post, path = "/v1/webhooks/push",
+    request_body(
+        content = Object,
+        description = "The push event of GitHub, GitLab or Gitea",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "The actors to be rebuilt"),
+        (status = 400, description = "Invalid push event"),
+        (status = 401, description = "Missing or invalid signature"),
+    ),
+    tag = "Webhooks"
+)]
+pub async fn push(State(ctx): State<Arc<Context>>, headers: HeaderMap, body: Bytes) -> Result<impl IntoResponse> {
+    verify(ctx.config.webhook_secret.as_deref(), &headers, &body)?;
+    let payload: Value = serde_json::from_slice(&body).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+
+    Ok(Json(json!({ "actors": WebhookService::push(ctx, payload).await? })))
+}
+
+/// Verify the event with the secret, by the HMAC-SHA256 signature of the
+/// body, or the token of GitLab sent as it is.
+fn verify(secret: Option<&str>, headers: &HeaderMap, body: &[u8]) -> Result<()> {
+    let secret = secret.ok_or(ApiError::Unauthorized)?;
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let verified = match (header("X-Hub-Signature-256"), header("X-Gitlab-Token")) {
+        (Some(signature), _) => equals(signature, &format!("sha256={}", hex(&hmac(secret.as_bytes(), body)))),
+        (None, Some(token)) => equals(token, secret),
+        (None, None) => false,
+    };
+    if !verified {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        let body = br#"{"ref": "refs/heads/main"}"#;
+        let signature = format!("sha256={}", hex(&hmac(b"secret", body)));
+        let headers = HeaderMap::from_iter([("X-Hub-Signature-256".parse().unwrap(), signature.parse().unwrap())]);
+        assert!(verify(Some("secret"), &headers, body).is_ok());
+        assert!(verify(Some("other"), &headers, body).is_err());
+        assert!(verify(None, &headers, body).is_err());
+
+        let headers = HeaderMap::from_iter([("X-Gitlab-Token".parse().unwrap(), "secret".parse().unwrap())]);
+        assert!(verify(Some("secret"), &headers, body).is_ok());
+        assert!(verify(Some("secret"), &HeaderMap::new(), body).is_err());
+    }
+}
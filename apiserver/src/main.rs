@@ -16,30 +16,51 @@
 
 use std::sync::Arc;
 
+use amp_resources::logging;
 use amphitheatre::app;
 use amphitheatre::config::Config;
 use amphitheatre::context::Context;
 use clap::Parser;
-use tracing::metadata::LevelFilter;
-use tracing_subscriber::EnvFilter;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing::error;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Enable tracing.
-    tracing_subscriber::registry()
-        .with(EnvFilter::builder().with_default_directive(LevelFilter::INFO.into()).from_env_lossy())
-        .with(tracing_subscriber::fmt::layer().with_file(false).with_target(false))
-        .init();
-
     // This returns an error if the `.env` file doesn't exist, but that's not what we want
     // since we're not going to use a `.env` file if we deploy this application.
     dotenv::dotenv().ok();
 
     // Parse our configuration from the environment.
     // This will exit with a help message if something is wrong.
+    let config = Config::parse();
+
+    // Enable logging, and export the spans if the OTLP endpoint is set.
+    let endpoint = config.otlp_endpoint.as_deref();
+    let filter = logging::directives(config.log_filter.as_deref());
+    logging::init("amp-apiserver", &config.log_format, &filter, endpoint)?;
+
+    // Fetch the qualified characters of the hub registry from the hub index.
+    if let Some(url) = &config.hub_url {
+        amp_resolver::hub::configure(url)?;
+    }
+
     // Then, initialize the shared context.
-    let ctx = Arc::new(Context::new(Config::parse()).await?);
+    let ctx = Arc::new(Context::new(config).await?);
+
+    // Reload the log filter once the logging ConfigMap is changed.
+    let client = ctx.k8s.clone();
+    let namespace = ctx.config.namespace.clone();
+    tokio::spawn(async move { logging::watch(client, &namespace, &filter).await });
+
+    // Delete the expired artifacts hourly.
+    let artifacts = ctx.artifacts.clone();
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = artifacts.expire().await {
+                error!("Failed to delete the expired artifacts: {}", err);
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(60 * 60)).await;
+        }
+    });
 
     // Running the application in a loop.
     app::run(ctx.clone()).await;
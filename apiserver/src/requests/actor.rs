@@ -0,0 +1,80 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::Preface;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateActorRequest {
+    /// The name of actor.
+    pub name: String,
+    /// Where to resolve the character of actor from.
+    pub preface: Preface,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScaleActorRequest {
+    /// The desired number of replicas of actor, zero to stop all its pods.
+    pub replicas: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PromoteActorRequest {
+    /// The id of playbook to promote the built image of actor to, e.g. the production one.
+    pub playbook: Uuid,
+    /// The character of target playbook to deploy the image as, the default is the name of actor.
+    pub character: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogsQuery {
+    /// Resume the stream after the given offset token (the `id` of the last
+    /// received event), the `Last-Event-ID` header takes precedence if present.
+    pub offset: Option<String>,
+    /// Maximum number of lines per second delivered to this client, it can
+    /// only lower the server-side limit.
+    pub rate: Option<u32>,
+    /// Keep streaming the new lines, and re-attach to the restarted
+    /// containers and the new pods, the default is `true`.
+    pub follow: Option<bool>,
+    /// Stream the given container only, e.g. a sidecar, all the containers
+    /// are streamed if it's not set.
+    pub container: Option<String>,
+    /// Only the lines newer than this, a duration like `10m` or an RFC 3339
+    /// time, the offset token takes precedence if present.
+    pub since: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ForwardQuery {
+    /// The access token, for the WebSocket clients which are unable to
+    /// set the `Authorization` header.
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExecQuery {
+    /// The command to execute, separated by whitespace, the default is `/bin/sh`.
+    pub command: Option<String>,
+    /// Whether to allocate a TTY, the default is `true`.
+    pub tty: Option<bool>,
+    /// The access token, for the WebSocket clients which are unable to
+    /// set the `Authorization` header.
+    pub token: Option<String>,
+}
@@ -0,0 +1,30 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::services::envset::SecretRef;
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ApplyEnvSetRequest {
+    /// The plain env vars.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The env vars read from the keys of the shared Secrets in the Amphitheatre namespace.
+    #[serde(default)]
+    pub secrets: BTreeMap<String, SecretRef>,
+}
@@ -12,4 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod actor;
+pub mod artifact;
+pub mod audit;
+pub mod envset;
 pub mod playbook;
+pub mod search;
+pub mod template;
@@ -12,19 +12,559 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use amp_common::resource::Preface;
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct CreatePlaybookRequest {
     pub title: String,
     pub description: Option<String>,
     pub preface: Preface,
+    /// The variables referenced by the strings of the character specs as `${vars.NAME}`, e.g.
+    /// `${vars.API_URL}`, a change of them is rolled out to all the actors referencing them.
+    pub vars: Option<HashMap<String, String>>,
+    /// The other playbooks whose characters are merged into this one, e.g. a base infrastructure
+    /// playbook, the characters of the same name can't be declared more than once.
+    pub includes: Option<Vec<Include>>,
+    /// The image signing mode, `key`, `keyless` or `none`, overrides the platform default.
+    pub signing: Option<String>,
+    /// Delete the playbook after it has been idle for this many seconds, bounded by the workspace policy.
+    pub ttl: Option<i64>,
+    /// Stop the playbook out of the working hours and start it again in the morning, it can be
+    /// stopped or started through the API in between, until the next scheduled time.
+    pub schedule: Option<UptimeSchedule>,
+    /// The shared base images of the monorepos, in the form of `{repo}#{dockerfile}`, which are
+    /// built once per commit before the actors of the repository, e.g.
+    /// `https://github.com/org/monorepo#docker/base.Dockerfile`.
+    pub bases: Option<Vec<String>>,
+    /// The env sets of the workspace applied to all the characters, the later ones override the earlier.
+    pub env_sets: Option<Vec<String>>,
+    /// The env sets applied to the given characters only, after the ones of the playbook.
+    pub character_env_sets: Option<HashMap<String, Vec<String>>>,
+    /// Isolate the actors in the playbook, they can only talk to each other,
+    /// the DNS, and the declared external endpoints.
+    pub network: Option<Network>,
+    /// Override the default resources of the playbook namespace.
+    pub quota: Option<NamespaceQuota>,
+    /// The volumes mounted into the given characters, e.g. the data directory of a database.
+    pub volumes: Option<HashMap<String, Vec<Volume>>>,
+    /// The workloads of the given characters, `deployment` or `statefulset`, the
+    /// latter for the characters needing stable identities and storage.
+    pub workloads: Option<HashMap<String, String>>,
+    /// The schedules of the given characters, they are run as scheduled tasks
+    /// instead of long-running services.
+    pub schedules: Option<HashMap<String, Schedule>>,
+    /// The init containers and sidecars of the given characters.
+    pub containers: Option<HashMap<String, Containers>>,
+    /// The health probes of the given characters, the actors are ready only after their readiness probes succeed.
+    pub probes: Option<HashMap<String, Probes>>,
+    /// How the updates of the given characters are rolled out.
+    pub strategies: Option<HashMap<String, Strategy>>,
+    /// Roll out the updates of the given characters to a part of the traffic
+    /// step by step, and roll them back if the canary pods are unhealthy.
+    pub canaries: Option<HashMap<String, Canary>>,
+    /// The checks run against the given characters after they are deployed, they are Ready
+    /// only after the checks passed, and rolled back to the last verified image if failed.
+    pub verifications: Option<HashMap<String, Verification>>,
+    /// Override when the failed rollouts of the given characters are rolled back to their last
+    /// known-good images, they are rolled back after 3 restarts in CrashLoopBackOff by default.
+    pub rollbacks: Option<HashMap<String, Rollback>>,
+    /// Override how the given characters are exposed outside of the cluster.
+    pub exposures: Option<HashMap<String, Exposure>>,
+    /// Override the default resources and node placement of the build pods of the given characters.
+    pub builds: Option<HashMap<String, BuildResources>>,
+    /// Override how long the finished Jobs of the given characters are kept, e.g. the
+    /// builds, how many times they are retried and how long they may run.
+    pub jobs: Option<HashMap<String, JobSettings>>,
+    /// The names of the image pull secrets in the playbook namespace used by the pods of the
+    /// given characters, besides the registry credentials, e.g. for the images of other registries.
+    pub image_pull_secrets: Option<HashMap<String, Vec<String>>>,
+    /// The kpack builders of the given characters built with Buildpacks, e.g.
+    /// a Go builder for the Go characters.
+    pub builders: Option<HashMap<String, KpackBuilder>>,
+    /// Override the run image of the given characters built with the Buildpacks lifecycle.
+    pub run_images: Option<HashMap<String, String>>,
+    /// Choose the builders of the given characters over the build method of
+    /// their spec, `auto` detects it from the files of their repositories,
+    /// e.g. Kaniko for a Dockerfile, and `nixpacks` builds with Nixpacks.
+    pub build_methods: Option<HashMap<String, String>>,
+    /// Generate the SBOMs of the images built for the given characters, in
+    /// `spdx` or `cyclonedx`, they are read by `/v1/actors/{pid}/{name}/sbom`.
+    pub sboms: Option<HashMap<String, String>>,
+    /// Run the given characters under the debugger of their languages, the
+    /// debug port is forwarded by `/v1/actors/{pid}/{name}/debug`.
+    pub debug: Option<HashMap<String, Debugger>>,
+    /// Run the given characters as long-lived development containers with
+    /// their source in `/workspace`, the editors attach to them by `exec` or
+    /// SSH bridged by `/v1/actors/{pid}/{name}/ssh`.
+    pub devcontainers: Option<HashMap<String, DevContainer>>,
+    /// The reload hooks of the given live characters, the synced files are
+    /// picked up by the running pods without being redeployed.
+    pub reloads: Option<HashMap<String, Reload>>,
+    /// Pin the digests the prebuilt images of the given characters must resolve to, e.g. `sha256:...`.
+    pub image_digests: Option<HashMap<String, String>>,
+    /// Require the prebuilt images of the given characters to be signed by cosign.
+    pub verify_signatures: Option<Vec<String>>,
+    /// The workload cluster the namespace and actors are created in, it's
+    /// registered by a Secret labelled `amphitheatre.app/cluster` holding its
+    /// kubeconfig, they are created in the control plane if not set.
+    pub cluster: Option<String>,
+    /// Where the actors are deployed, `dedicated` creates the `amp-{id}`
+    /// namespace owned by the playbook, `existing` uses the given namespace
+    /// as is, and `shared` shares it with other playbooks, prefixing the names
+    /// of actors. The default is `dedicated`.
+    pub namespace_strategy: Option<String>,
+    /// The namespace of the `existing` and `shared` strategies.
+    pub namespace: Option<String>,
+    /// Export the manifests of the actors for the GitOps review flow.
+    pub export: Option<Export>,
+    /// Deploy the actors by an ArgoCD Application syncing the exported
+    /// manifests instead, the export repository is required.
+    pub argocd: Option<ArgoCd>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Network {
+    /// The external endpoints the actors are allowed to connect to.
+    #[serde(default)]
+    pub egress: Vec<Endpoint>,
+}
+
+/// The resources of the playbook namespace, in Kubernetes quantities.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceQuota {
+    /// The total cpu requested and limited by all pods, e.g. `8`.
+    pub cpu: Option<String>,
+    /// The total memory requested and limited by all pods, e.g. `16Gi`.
+    pub memory: Option<String>,
+    /// The maximum number of pods.
+    pub pods: Option<String>,
+    /// The total storage requested by all persistent volume claims.
+    pub storage: Option<String>,
+    /// The cpu limit of the containers without their own.
+    pub default_cpu: Option<String>,
+    /// The memory limit of the containers without their own.
+    pub default_memory: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Endpoint {
+    /// The IP block of the endpoint, e.g. `10.0.0.0/8`.
+    pub cidr: String,
+    /// The TCP ports of the endpoint, all ports are allowed if empty.
+    #[serde(default)]
+    pub ports: Vec<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Volume {
+    pub name: String,
+    /// The path in the container to mount the volume at.
+    pub mount_path: String,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(flatten)]
+    pub source: VolumeSource,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeSource {
+    /// A scratch directory sharing the lifetime of the pod.
+    EmptyDir { medium: Option<String>, size_limit: Option<String> },
+    /// A persistent volume claim kept across the restarts, deleted with the playbook.
+    Pvc { size: String, storage_class: Option<String>, access_mode: Option<String> },
+    /// A ConfigMap in the playbook namespace.
+    ConfigMap { name: String },
+    /// A Secret in the playbook namespace.
+    Secret { name: String },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Schedule {
+    /// The schedule in Cron format, e.g. `*/30 * * * *`.
+    pub cron: String,
+    /// `Allow`, `Forbid` or `Replace` the concurrent runs, the default is `Forbid`.
+    pub concurrency_policy: Option<String>,
+    /// The number of the successful runs kept, the default is `3`.
+    pub successful_jobs_history_limit: Option<i32>,
+    /// The number of the failed runs kept, the default is `1`.
+    pub failed_jobs_history_limit: Option<i32>,
+    /// Suspend the subsequent runs, the manual runs are still allowed.
+    #[serde(default)]
+    pub suspend: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct UptimeSchedule {
+    /// When to start the playbook in Cron format, e.g. `0 9 * * MON-FRI`.
+    pub start: String,
+    /// When to stop the playbook in Cron format, e.g. `0 19 * * MON-FRI`.
+    pub stop: String,
+    /// The timezone of the schedule as an IANA name, e.g. `Asia/Shanghai`,
+    /// which follows its daylight saving time, or an offset from UTC, e.g.
+    /// `+08:00`, the default is `UTC`.
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Containers {
+    /// Run to completion in order before the application starts, e.g. the migrations.
+    #[serde(default)]
+    pub init_containers: Vec<ContainerSpec>,
+    /// Run alongside the application, e.g. log shippers and proxies.
+    #[serde(default)]
+    pub sidecars: Vec<ContainerSpec>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ContainerSpec {
+    pub name: String,
+    pub image: String,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Mount the volumes of the character by their names.
+    #[serde(default)]
+    pub mounts: Vec<ContainerMount>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ContainerMount {
+    pub volume: String,
+    pub mount_path: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Probes {
+    /// Route the traffic to the pod only after it succeeds.
+    pub readiness: Option<Probe>,
+    /// Restart the container after it fails.
+    pub liveness: Option<Probe>,
+    /// Hold the other probes until it succeeds, for the slow starting applications.
+    pub startup: Option<Probe>,
+}
+
+/// A probe with one of the `http`, `tcp` or `exec` handlers.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Probe {
+    pub http: Option<HttpProbe>,
+    pub tcp: Option<TcpProbe>,
+    pub exec: Option<ExecProbe>,
+    pub initial_delay_seconds: Option<i32>,
+    pub period_seconds: Option<i32>,
+    pub timeout_seconds: Option<i32>,
+    pub failure_threshold: Option<i32>,
+    pub success_threshold: Option<i32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct HttpProbe {
+    /// The path to GET, the default is `/`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub port: i32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct TcpProbe {
+    pub port: i32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ExecProbe {
+    /// The command run in the container, it succeeds if exits with zero.
+    pub command: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Strategy {
+    /// Replace the pods gradually, the default.
+    RollingUpdate {
+        /// The number or percentage of the pods created above the replicas, e.g. `25%`.
+        max_surge: Option<String>,
+        /// The number or percentage of the pods unavailable during the update, e.g. `0`.
+        max_unavailable: Option<String>,
+    },
+    /// Kill all the old pods before creating the new ones.
+    Recreate,
+    /// Deploy the new version next to the old one, and switch the traffic to it after it is ready.
+    BlueGreen,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Canary {
+    pub steps: Vec<CanaryStep>,
+    /// Roll back if the canary pods are not ready in this many seconds of a step, the default is `600`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress_deadline: Option<u64>,
+    /// Roll back if the containers of the canary pods restarted more times than this, the default is `3`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_restarts: Option<i32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct CanaryStep {
+    /// The percentage of the traffic served by the canary pods.
+    pub weight: i32,
+    /// The seconds to observe the ready canary pods before the next step.
+    #[serde(default)]
+    pub pause: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Verification {
+    /// The HTTP requests against the Service of the character.
+    #[serde(default)]
+    pub http: Vec<HttpCheck>,
+    /// The Job running the smoke tests, the URL of the Service is given by `AMP_ACTOR_URL`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<VerificationJob>,
+    /// Fail the verification if it is not passed in this many seconds, the default is `300`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct HttpCheck {
+    /// The path requested, the default is `/`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The port of the Service.
+    pub port: i32,
+    /// The expected status code, the default is `200`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct VerificationJob {
+    pub image: String,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Rollback {
+    /// Roll back the failed rollouts, the default is `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Roll back if a container in CrashLoopBackOff restarted this many times, the default is `3`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_restarts: Option<i32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Exposure {
+    /// `none`, `ingress` or `gateway` (Gateway API), the default is configured by the operator.
+    pub backend: Option<String>,
+    /// The hostname template, the `{character}`, `{playbook}` and `{namespace}` variables are replaced.
+    pub hostname: Option<String>,
+    /// The Secret holding the TLS certificate of the hostname, in the playbook namespace.
+    pub tls_secret: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Export {
+    /// The HTTPS URL of the Git repository the manifests are committed to,
+    /// they are only kept by Amphitheatre if not set.
+    pub repo: Option<String>,
+    /// The branch of the repository, the default is `main`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// The directory of the manifests, the `{playbook}` variable is replaced.
+    pub path: Option<String>,
+    /// The Secret holding the `username` and `password` of the repository, in the Amphitheatre namespace.
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ArgoCd {
+    /// The namespace ArgoCD is installed in, the default is `argocd`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// The ArgoCD project of the Application, the default is `default`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// The name of the cluster registered in ArgoCD, the cluster ArgoCD runs in if not set.
+    pub destination: Option<String>,
+    /// Sync the changes automatically, the default is `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_sync: Option<bool>,
+}
+
+/// The debugger attached to the application, its probes are disabled while debugging.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Debugger {
+    /// The language of the application, `go`, `python` or `node`.
+    pub language: String,
+    /// The command run under the debugger, the binary and its arguments of
+    /// Go, or the script and its arguments of Python, not needed by Node.
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// The port the debugger listens on, the default one of the debugger if not set.
+    pub port: Option<i32>,
+}
+
+/// The development container run in place of the application, it sleeps with the toolchain.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct DevContainer {
+    /// The image with the toolchain, the universal image of the dev containers if not set.
+    pub image: Option<String>,
+    /// The public keys allowed to log in by SSH, the SSH server of the image is not started without them.
+    #[serde(default)]
+    pub authorized_keys: Vec<String>,
+    /// The port the SSH server listens on, `2222` if not set.
+    pub port: Option<i32>,
+}
+
+/// How the live application picks up the files synced into its pods.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Reload {
+    /// The directory of the application the files are synced into, e.g. `/app`.
+    pub path: String,
+    /// The command run in the application container after the files are
+    /// synced, e.g. `["kill", "-HUP", "1"]` or a rebuild script.
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Restart the application container after the files are synced instead.
+    #[serde(default)]
+    pub restart: bool,
+}
+
+/// The resources of the build pods in Kubernetes quantities, and the nodes they are placed on.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct BuildResources {
+    pub cpu_request: Option<String>,
+    pub cpu_limit: Option<String>,
+    pub memory_request: Option<String>,
+    pub memory_limit: Option<String>,
+    /// The labels of the nodes the build pods are scheduled to.
+    #[serde(default)]
+    pub node_selector: HashMap<String, String>,
+    /// The tolerated taints in the form of `kubectl taint`, e.g. `dedicated=builds:NoSchedule`.
+    #[serde(default)]
+    pub tolerations: Vec<String>,
+    /// The PriorityClass of the build pods.
+    pub priority_class: Option<String>,
+    /// The priority of the builds waiting in the queue, the higher ones go first.
+    pub priority: Option<i32>,
+    /// Kill the build if it's not completed in this many seconds.
+    pub timeout: Option<i64>,
+}
+
+/// A playbook included by another, either a template or a preface.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Include {
+    /// The ID of the template instantiated with the variables.
+    pub template: Option<Uuid>,
+    /// The values of the variables of the template, overrides their defaults.
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    /// The preface of the included playbook, e.g. a manifest.
+    pub preface: Option<Preface>,
+}
+
+/// The lifecycle of the Jobs of a character, the unset fields are configured by the operator.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct JobSettings {
+    /// Delete the finished Jobs and their pods after this many seconds, at least 60.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds_after_finished: Option<i32>,
+    /// The number of retries before the Jobs are marked failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backoff_limit: Option<i32>,
+    /// Kill the Jobs still active after this many seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_deadline_seconds: Option<i64>,
+}
+
+/// The reference to a kpack builder.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct KpackBuilder {
+    pub name: String,
+    /// `ClusterBuilder` or `Builder` in the playbook namespace, the default is `ClusterBuilder`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// The buildpacks of the ClusterBuilder, it's created with them if set,
+    /// otherwise it must exist already.
+    #[serde(default)]
+    pub buildpacks: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UpdatePlaybookRequest {
     pub title: Option<String>,
     pub description: Option<String>,
+    /// Replace the variables referenced by the character specs as `${vars.NAME}`,
+    /// the actors referencing the changed ones are updated, an empty map removes them all.
+    pub vars: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPlaybooksQuery {
+    /// List the deleted playbooks in the trash instead, which can be restored.
+    pub deleted: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeletePlaybookQuery {
+    /// Delete the playbook permanently at once, instead of moving it into the trash.
+    pub purge: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CreatePlaybookQuery {
+    /// Resolve and render the playbook without creating it, returns the plan.
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportPlaybookRequest {
+    /// The document exported from a playbook.
+    pub manifest: String,
+    /// The format of document, `yaml` or `toml`, the default is `yaml`.
+    pub format: Option<String>,
+    /// The values of the secret variables left out of the document, all of them are required.
+    pub secrets: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// The format of document, `yaml` or `toml`, the default is `yaml`.
+    pub format: Option<String>,
+    /// The comma-separated names of the variables, environment variables and
+    /// arguments exported as is, e.g. `API_URL,PORT`, the others are secrets.
+    pub public: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventsQuery {
+    /// Resume the stream after the given offset token (the `id` of the last
+    /// received event), the `Last-Event-ID` header takes precedence if present.
+    pub offset: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResourcesQuery {
+    /// Stream the changes of the objects as server-sent events instead.
+    pub watch: Option<bool>,
 }
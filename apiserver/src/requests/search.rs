@@ -0,0 +1,28 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// The words to search for, case-insensitive, each of them must be found in
+    /// the title, description or labels of playbook, or the name or repository of actor.
+    pub q: String,
+    /// Only search the playbooks matching the label selector, e.g. `team=web,env!=prod`.
+    pub labels: Option<String>,
+    /// The maximum number of results, the default is `50` and at most `200`.
+    pub limit: Option<usize>,
+}
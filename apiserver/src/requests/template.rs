@@ -0,0 +1,50 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use amp_common::resource::Preface;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::services::template::Variable;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateTemplateRequest {
+    /// The title of the playbooks, may contain the `{{ name }}` placeholders.
+    pub title: String,
+    pub description: Option<String>,
+    /// The variables to be substituted when instantiating.
+    #[serde(default)]
+    pub variables: Vec<Variable>,
+    /// Where to resolve the characters of the playbooks from,
+    /// the strings in it may contain the `{{ name }}` placeholders.
+    pub preface: Preface,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct UpdateTemplateRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Replace all the variables if given.
+    pub variables: Option<Vec<Variable>>,
+    pub preface: Option<Preface>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct InstantiateTemplateRequest {
+    /// The values of the variables, overrides their defaults.
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
@@ -14,30 +14,103 @@
 
 use std::sync::Arc;
 
-use axum::routing::{delete, get, patch, post};
+use axum::routing::{delete, get, patch, post, put};
 use axum::Router;
+use tower_http::compression::predicate::{And, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
 
 use crate::context::Context;
 use crate::handlers;
 
+/// The liveness and readiness probes, they are neither audited nor rate limited.
+pub fn probes() -> Router<Arc<Context>> {
+    Router::new().route("/healthz", get(handlers::health::healthz)).route("/readyz", get(handlers::health::readyz))
+}
+
 pub fn build() -> Router<Arc<Context>> {
     Router::new()
         // actors
+        .route("/v1/actors", post(handlers::actor::create))
         .route("/v1/actors/:pid/:name", get(handlers::actor::detail))
-        .route("/v1/actors/:pid/:name/logs", get(handlers::actor::logs))
+        .route("/v1/actors/:pid/:name", delete(handlers::actor::delete))
+        .route("/v1/actors/:pid/:name/logs", get(handlers::actor::logs).layer(compression()))
         .route("/v1/actors/:pid/:name/info", get(handlers::actor::info))
         .route("/v1/actors/:pid/:name/stats", get(handlers::actor::stats))
         .route("/v1/actors/:pid/:name/sync", post(handlers::actor::sync))
+        .route("/v1/actors/:pid/:name/trigger", post(handlers::actor::trigger))
+        .route("/v1/actors/:pid/:name/scale", post(handlers::actor::scale))
+        .route("/v1/actors/:pid/:name/usage", get(handlers::actor::usage))
+        .route("/v1/actors/:pid/:name/sbom", get(handlers::actor::sbom))
+        .route("/v1/actors/:pid/:name/builds", get(handlers::actor::builds))
+        .route("/v1/actors/:pid/:name/builds/:id/logs", get(handlers::actor::build_logs))
+        .route("/v1/actors/:pid/:name/promote", post(handlers::actor::promote))
+        .route("/v1/actors/:pid/:name/diff", get(handlers::actor::diff))
+        .route("/v1/actors/:pid/:name/forward/:port", get(handlers::actor::forward))
+        .route("/v1/actors/:pid/:name/debug", get(handlers::actor::debug))
+        .route("/v1/actors/:pid/:name/ssh", get(handlers::actor::ssh))
+        .route("/v1/actors/:pid/:name/exec", get(handlers::actor::exec))
+        //
+        // audit
+        .route("/v1/audit", get(handlers::audit::list))
+        //
+        // search
+        .route("/v1/search", get(handlers::search::search))
+        //
+        // admission webhooks
+        .route("/v1/admission/actors", post(handlers::admission::actors))
+        //
+        // webhooks of the git providers
+        .route("/v1/webhooks/push", post(handlers::webhook::push))
+        //
+        // sources uploaded from local
+        .route("/v1/sources", post(handlers::source::upload))
+        //
+        // artifacts
+        .route("/v1/artifacts/:kind/:digest", get(handlers::artifact::download))
         //
         // playbooks
         .route("/v1/playbooks", get(handlers::playbook::list))
         .route("/v1/playbooks", post(handlers::playbook::create))
+        .route("/v1/playbooks/import", post(handlers::playbook::import))
         .route("/v1/playbooks/:id", get(handlers::playbook::detail))
         .route("/v1/playbooks/:id", patch(handlers::playbook::update))
         .route("/v1/playbooks/:id", delete(handlers::playbook::delete))
+        .route("/v1/playbooks/:id/restore", post(handlers::playbook::restore))
         //
         .route("/v1/playbooks/:id/actions/start", post(handlers::playbook::start))
         .route("/v1/playbooks/:id/actions/stop", post(handlers::playbook::stop))
         .route("/v1/playbooks/:id/events", get(handlers::playbook::events))
+        .route("/v1/playbooks/:id/logs", get(handlers::playbook::logs).layer(compression()))
+        .route("/v1/playbooks/:id/resources", get(handlers::playbook::resources))
+        .route("/v1/playbooks/:id/metrics", get(handlers::playbook::metrics))
+        .route("/v1/playbooks/:id/cost", get(handlers::playbook::cost))
+        .route("/v1/playbooks/:id/plan", post(handlers::playbook::plan))
+        .route("/v1/playbooks/:id/revisions", get(handlers::playbook::revisions))
+        .route("/v1/playbooks/:id/rollback/:rev", post(handlers::playbook::rollback))
+        .route("/v1/playbooks/:id/manifests", get(handlers::playbook::manifests))
+        .route("/v1/playbooks/:id/export", get(handlers::playbook::export))
         .route("/v1/playbooks/:id/actors", get(handlers::actor::list))
+        //
+        // templates
+        .route("/v1/templates", get(handlers::template::list))
+        .route("/v1/templates", post(handlers::template::create))
+        .route("/v1/templates/:id", get(handlers::template::detail))
+        .route("/v1/templates/:id", patch(handlers::template::update))
+        .route("/v1/templates/:id", delete(handlers::template::delete))
+        .route("/v1/templates/:id/instantiate", post(handlers::template::instantiate))
+        //
+        // operations
+        .route("/v1/operations/:id", get(handlers::operation::detail))
+        //
+        // env sets
+        .route("/v1/workspaces/:workspace/envsets", get(handlers::envset::list))
+        .route("/v1/workspaces/:workspace/envsets/:name", get(handlers::envset::detail))
+        .route("/v1/workspaces/:workspace/envsets/:name", put(handlers::envset::apply))
+        .route("/v1/workspaces/:workspace/envsets/:name", delete(handlers::envset::delete))
+}
+
+/// Negotiated zstd/gzip compression for the streaming endpoints, the default
+/// predicate skips `text/event-stream`, so we build our own here.
+fn compression() -> CompressionLayer<And<NotForContentType, NotForContentType>> {
+    CompressionLayer::new().no_br().no_deflate().compress_when(NotForContentType::GRPC.and(NotForContentType::IMAGES))
 }
@@ -13,31 +13,374 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::Duration;
 
-use amp_common::resource::ActorSpec;
-use amp_common::sync::Synchronization;
-use async_nats::jetstream::{self, stream};
+use amp_common::resource::{Actor, ActorSpec, Playbook};
+use amp_common::sync::{EventKinds, Synchronization};
+use async_nats::jetstream::{self, kv, stream};
+use async_nats::RequestErrorKind;
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::chrono::{DateTime, Utc};
+use k8s_openapi::NamespaceResourceScope;
+use kube::api::ListParams;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::time::{sleep, Instant};
 use tracing::error;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::context::Context;
 use crate::errors::ApiError;
+use crate::requests::actor::{CreateActorRequest, PromoteActorRequest, ScaleActorRequest};
+use crate::services::operation::{Operation, OperationService};
+use crate::services::playbook::CLEANUP_TIMEOUT;
 use crate::services::Result;
-use amp_resources::actor;
+use amp_resources::artifact::Kind;
+use amp_resources::build::{
+    self, BUILD_FAILED_CONDITION_TYPE, BUILD_QUEUED_CONDITION_TYPE, BUILD_STUCK_CONDITION_TYPE,
+};
+use amp_resources::canary::CANARY_CONDITION_TYPE;
+use amp_resources::detection::BUILD_METHOD_CONDITION_TYPE;
+use amp_resources::error::Error;
+use amp_resources::exposure::EXPOSED_CONDITION_TYPE;
+use amp_resources::policy::REJECTED_CONDITION_TYPE;
+use amp_resources::usage::BUILDS_PAUSED_CONDITION_TYPE;
+use amp_resources::{
+    actor, cluster, cronjob, debug, devcontainer, namespace, naming, playbook, promotion, replicas, sbom, statefulset,
+    strategy, usage, workspace,
+};
+
+/// The actor along with its live status read from the cluster.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActorDetail {
+    #[serde(flatten)]
+    pub spec: ActorSpec,
+    pub status: LiveStatus,
+}
+
+/// The live status of actor, collected from its conditions, builder job, deployment and pods.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct LiveStatus {
+    /// The phase of the latest build, `Queued`, `Building`, `Running`, `Succeeded` or `Failed`.
+    pub build_phase: Option<String>,
+    /// The position of the build in the queue, if it's waiting for a slot.
+    pub queue_position: Option<usize>,
+    /// The build method detected from the repository in auto mode, `Dockerfile`,
+    /// `Buildpacks`, `Skipped` or `Unrecognized`.
+    pub build_method: Option<String>,
+    /// The phase of the deployment, `Progressing`, `Available` or `Failed`.
+    pub deploy_phase: Option<String>,
+    pub replicas: i32,
+    pub ready_replicas: i32,
+    /// The desired number of replicas, the scaled one or the one of its workload.
+    pub desired_replicas: Option<i32>,
+    /// The digest of the image the pods are running, e.g. `sha256:...`.
+    pub image_digest: Option<String>,
+    /// The latest error of the pods or the actor conditions.
+    pub last_error: Option<String>,
+    /// The URL of actor outside of the cluster, HTTPS once its certificate is issued.
+    pub url: Option<String>,
+}
+
+/// The usage of actor in the current month, along with the budgets of its workspace.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActorUsage {
+    /// The month of the usage, e.g. `2026-10`.
+    pub period: String,
+    pub build_minutes: f64,
+    pub runtime_hours: f64,
+    pub build_minutes_budget: Option<f64>,
+    pub runtime_hours_budget: Option<f64>,
+    /// Whether the new builds are paused, as the build minutes budget is exceeded.
+    pub builds_paused: bool,
+}
+
+/// A build of actor, identified by the name of its build pod.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActorBuild {
+    pub id: String,
+    /// `Running`, `Succeeded`, `Failed` or `Interrupted` by a retry or a newer build.
+    pub status: String,
+    /// The builder, `kaniko`, `lifecycle`, `nixpacks` or `kpack`.
+    pub builder: Option<String>,
+    /// What triggered the build, `push`, `sync`, `upload` or `spec`.
+    pub trigger: Option<String>,
+    /// The commit built from, unknown for the live and uploaded sources.
+    pub revision: Option<String>,
+    /// The digest of the built image, once succeeded.
+    pub digest: Option<String>,
+    pub started: Option<DateTime<Utc>>,
+    pub finished: Option<DateTime<Utc>>,
+    /// How long the build took, or has taken so far, in seconds.
+    pub duration: Option<i64>,
+    /// Whether the logs are archived, fetched by `/v1/actors/{pid}/{name}/builds/{id}/logs`.
+    pub logs: bool,
+}
+
+/// The built image of actor promoted to another playbook.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActorPromotion {
+    /// The id of target playbook and the character the image is deployed as.
+    pub playbook: String,
+    pub character: String,
+    /// The image referenced by its digest, it's deployed as is instead of being built.
+    pub image: String,
+    pub digest: String,
+    /// The commit the image was built from, if known.
+    pub revision: Option<String>,
+    pub promoted_at: DateTime<Utc>,
+}
+
+/// Where the actor of a character lives, along with the client of the
+/// cluster it's deployed to, the workload cluster of its playbook if any.
+pub struct Location {
+    pub client: Client,
+    pub namespace: String,
+    pub name: String,
+}
 
 pub struct ActorService;
 
 impl ActorService {
-    pub async fn get(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<ActorSpec> {
-        let actor = actor::get(&ctx.k8s, &format!("amp-{}", pid), &name).await.map_err(ApiError::ResourceError)?;
+    pub async fn get(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<ActorDetail> {
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let actor = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        let status = Self::status(&actor, &Workloads::list(&client, &namespace).await?);
+
+        Ok(ActorDetail { spec: actor.spec, status })
+    }
+
+    /// Create a detached actor in its own namespace, returns the id of the namespace.
+    pub async fn create(ctx: Arc<Context>, req: &CreateActorRequest) -> Result<Uuid> {
+        // The name is used as-is for the actor and its workloads, unlike the altered names of the characters.
+        if !naming::valid(&req.name) {
+            return Err(ApiError::BadRequest(format!("Invalid actor name {}, it must be an RFC 1123 label", req.name)));
+        }
+
+        let id = Uuid::new_v4();
+        let namespace = format!("amp-{}", id);
+
+        namespace::create_detached(&ctx.k8s, &namespace).await.map_err(ApiError::ResourceError)?;
+        if let Err(err) = actor::create_detached(&ctx.k8s, &namespace, &req.name, &req.preface).await {
+            // Nothing else lives in the namespace, the GC controller collects it if it fails here.
+            if let Err(err) = namespace::delete(&ctx.k8s, &namespace).await {
+                error!("Failed to delete the namespace {} of detached actor: {}", namespace, err);
+            }
+            return Err(ApiError::ResourceError(err));
+        }
 
-        Ok(actor.spec)
+        Ok(id)
     }
 
-    pub async fn list(ctx: Arc<Context>, pid: Uuid) -> Result<Vec<ActorSpec>> {
-        let actors = actor::list(&ctx.k8s, &format!("amp-{}", pid)).await.map_err(ApiError::ResourceError)?;
-        Ok(actors.iter().map(|actor| actor.spec.clone()).collect())
+    /// Delete the detached actor in background, its namespace is deleted by
+    /// the cleanup of actor, which may take minutes.
+    pub async fn delete(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<Operation> {
+        let namespace = format!("amp-{}", pid);
+        let resource = actor::get(&ctx.k8s, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        if !actor::detached(&resource) {
+            return Err(ApiError::BadRequest("only the detached actors can be deleted".into()));
+        }
+
+        let target = format!("{}/{}", pid, name);
+        let steps = ["delete_actor", "delete_namespace"];
+        OperationService::spawn(ctx, "delete_actor", &target, &steps, move |ctx, progress| async move {
+            progress.step("delete_actor").await?;
+            actor::delete(&ctx.k8s, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+
+            progress.step("delete_namespace").await?;
+            let deadline = Instant::now() + CLEANUP_TIMEOUT;
+            while namespace::exists(&ctx.k8s, &namespace).await.map_err(ApiError::ResourceError)? {
+                if Instant::now() > deadline {
+                    return Err(ApiError::Timeout(format!("namespace {} is still terminating", namespace)));
+                }
+                sleep(Duration::from_secs(5)).await;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Request a manual run of the scheduled actor, returns the time of the request.
+    pub async fn trigger(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<String> {
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let resource = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        if cronjob::schedule(&resource).map_err(ApiError::ResourceError)?.is_none() {
+            return Err(ApiError::BadRequest("only the scheduled actors can be triggered".into()));
+        }
+
+        cronjob::request(&client, &resource).await.map_err(ApiError::ResourceError)
+    }
+
+    /// Scale the actor to the given number of replicas, returns the actor with its live status.
+    pub async fn scale(ctx: Arc<Context>, pid: Uuid, name: String, req: &ScaleActorRequest) -> Result<ActorDetail> {
+        if req.replicas < 0 {
+            return Err(ApiError::BadRequest("replicas must not be negative".into()));
+        }
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let resource = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        if cronjob::schedule(&resource).map_err(ApiError::ResourceError)?.is_some() {
+            return Err(ApiError::BadRequest("the scheduled actors can not be scaled".into()));
+        }
+
+        replicas::scale(&client, &resource, req.replicas).await.map_err(ApiError::ResourceError)?;
+
+        let actor = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        let status = Self::status(&actor, &Workloads::list(&client, &namespace).await?);
+        Ok(ActorDetail { spec: actor.spec, status })
+    }
+
+    pub async fn list(ctx: Arc<Context>, pid: Uuid) -> Result<Vec<ActorDetail>> {
+        let (client, actors) = match playbook::get(&ctx.k8s, &pid.to_string()).await {
+            Ok(playbook) => {
+                let client = Self::client(&ctx, &playbook).await?;
+                let actors = actor::list_of(&client, &playbook).await;
+                (client, actors)
+            }
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => {
+                (ctx.k8s.clone(), actor::list(&ctx.k8s, &format!("amp-{}", pid)).await)
+            }
+            Err(err) => return Err(ApiError::ResourceError(err)),
+        };
+        let actors = actors.map_err(ApiError::ResourceError)?;
+
+        // The workloads are listed once per namespace, instead of once per actor.
+        let mut workloads = HashMap::new();
+        let mut details = vec![];
+        for actor in actors {
+            let namespace = actor.namespace().unwrap_or_default();
+            if !workloads.contains_key(&namespace) {
+                workloads.insert(namespace.clone(), Workloads::list(&client, &namespace).await?);
+            }
+            let status = Self::status(&actor, &workloads[&namespace]);
+            details.push(ActorDetail { spec: actor.spec, status });
+        }
+        Ok(details)
+    }
+
+    /// Returns where the actor of character in the playbook lives, the detached
+    /// actors without a playbook live in the `amp-{pid}` namespace of the control plane.
+    pub async fn locate(ctx: &Context, pid: Uuid, character: &str) -> Result<Location> {
+        match playbook::get(&ctx.k8s, &pid.to_string()).await {
+            Ok(playbook) => Ok(Location {
+                client: Self::client(ctx, &playbook).await?,
+                namespace: namespace::of(&playbook),
+                name: namespace::actor_name(&playbook, character),
+            }),
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => {
+                Ok(Location { client: ctx.k8s.clone(), namespace: format!("amp-{}", pid), name: character.to_string() })
+            }
+            Err(err) => Err(ApiError::ResourceError(err)),
+        }
+    }
+
+    /// Returns the client of the cluster the actors of playbook are deployed to.
+    pub async fn client(ctx: &Context, playbook: &Playbook) -> Result<Client> {
+        cluster::client(&ctx.k8s, &ctx.config.namespace, playbook).await.map_err(ApiError::ResourceError)
+    }
+
+    /// Collect the live status of actor from the workloads of its namespace.
+    fn status(actor: &Actor, workloads: &Workloads) -> LiveStatus {
+        let mut status = LiveStatus::default();
+
+        let conditions = actor.status.as_ref().map(|s| s.conditions.clone()).unwrap_or_default();
+        let building = actor.status.as_ref().is_some_and(|s| s.building());
+
+        // The actors built with Dockerfile have a builder job, the others are built by kpack.
+        let job = find(&workloads.jobs, &naming::name(&[&actor.spec.name, "builder"]));
+        status.build_phase = match job.and_then(|job| job.status.clone()) {
+            Some(s) if s.failed >= Some(1) => Some("Failed".into()),
+            Some(s) if s.succeeded >= Some(1) => Some("Succeeded".into()),
+            Some(s) if s.active >= Some(1) => Some("Running".into()),
+            _ if building && build::failed(actor) => Some("Failed".into()),
+            _ if building && build::position(actor).is_some() => Some("Queued".into()),
+            _ if building => Some("Building".into()),
+            _ => None,
+        };
+        if building {
+            status.queue_position = build::position(actor);
+        }
+        status.build_method =
+            conditions.iter().find(|c| c.type_ == BUILD_METHOD_CONDITION_TYPE).map(|c| c.reason.clone());
+
+        // The blue/green actor is served by the Deployment of its active color.
+        if let Some(deployment) = find(&workloads.deployments, &strategy::serving(actor)) {
+            status.desired_replicas = deployment.spec.as_ref().and_then(|spec| spec.replicas);
+            let deployment = deployment.status.clone().unwrap_or_default();
+            let failed = deployment.conditions.unwrap_or_default().iter().any(|c| {
+                (c.type_ == "ReplicaFailure" && c.status == "True")
+                    || (c.type_ == "Progressing" && c.reason.as_deref() == Some("ProgressDeadlineExceeded"))
+            });
+            status.replicas = deployment.replicas.unwrap_or_default();
+            status.ready_replicas = deployment.ready_replicas.unwrap_or_default();
+            status.deploy_phase = Some(if failed {
+                "Failed".into()
+            } else if status.ready_replicas >= status.replicas {
+                "Available".into()
+            } else {
+                "Progressing".into()
+            });
+        }
+
+        if statefulset::stateful(actor) {
+            if let Some(statefulset) = find(&workloads.statefulsets, &statefulset::name(actor)) {
+                status.desired_replicas = statefulset.spec.as_ref().and_then(|spec| spec.replicas);
+                let statefulset = statefulset.status.clone().unwrap_or_default();
+                status.replicas = statefulset.replicas;
+                status.ready_replicas = statefulset.ready_replicas.unwrap_or_default();
+            }
+        }
+        // The scaled replicas take precedence, as the workload may not be scaled yet.
+        status.desired_replicas = replicas::desired(actor).or(status.desired_replicas);
+
+        status.url = conditions.iter().find(|c| c.type_ == EXPOSED_CONDITION_TYPE).map(|c| c.message.clone());
+
+        let character = Some(actor.name_any());
+        let pods = workloads.pods.iter().filter(|pod| pod.labels().get(CHARACTER_LABEL) == character.as_ref());
+        let containers = pods.filter_map(|pod| pod.status.as_ref()?.container_statuses.as_ref()).flatten();
+        for container in containers {
+            if status.image_digest.is_none() {
+                status.image_digest = container.image_id.rsplit_once('@').map(|(_, digest)| digest.to_string());
+            }
+            let waiting = container.state.as_ref().and_then(|state| state.waiting.as_ref());
+            let waiting =
+                waiting.filter(|w| !matches!(w.reason.as_deref(), Some("ContainerCreating" | "PodInitializing")));
+            if let Some(waiting) = waiting {
+                let reason = waiting.reason.as_deref().unwrap_or("Waiting");
+                status.last_error = Some(match &waiting.message {
+                    Some(message) => format!("{}: {}", reason, message),
+                    None => reason.to_string(),
+                });
+            }
+        }
+
+        // Fall back to the latest failed condition of actor, e.g. a rejected image,
+        // the false conditions of the exposure and canary are not failures.
+        if status.last_error.is_none() {
+            let informational = [
+                EXPOSED_CONDITION_TYPE,
+                CANARY_CONDITION_TYPE,
+                BUILD_QUEUED_CONDITION_TYPE,
+                BUILD_STUCK_CONDITION_TYPE,
+            ];
+            let failed = conditions.iter().filter(|c| {
+                (c.status == "False" && !informational.contains(&c.type_.as_str()))
+                    || c.type_ == REJECTED_CONDITION_TYPE
+                    || c.type_ == BUILDS_PAUSED_CONDITION_TYPE
+                    || ([BUILD_FAILED_CONDITION_TYPE, BUILD_STUCK_CONDITION_TYPE].contains(&c.type_.as_str())
+                        && c.status == "True")
+                    || (c.type_ == CANARY_CONDITION_TYPE && c.reason == "RolledBack")
+            });
+            status.last_error = failed.max_by_key(|c| c.last_transition_time.0).map(|c| c.message.clone());
+        }
+
+        status
     }
 
     pub async fn sync(
@@ -62,14 +405,61 @@ impl ActorService {
         // Publish a message to the stream
         let subject = format!("{}.{}", pid, name);
         let payload = serde_json::to_vec(&req)?;
-        jetstream.publish(subject, payload.into()).await?.await?;
+        let ack = jetstream.publish(subject.clone(), payload.into()).await?.await?;
+
+        // Record where the last full sync starts, the syncers of the new pods replay the events from it.
+        if req.kind == EventKinds::Overwrite {
+            let store = match jetstream.get_key_value(SYNCS_BUCKET).await {
+                Ok(store) => store,
+                Err(_) => {
+                    let config = kv::Config { bucket: SYNCS_BUCKET.into(), history: 1, ..Default::default() };
+                    jetstream.create_key_value(config).await?
+                }
+            };
+            store.put(subject, ack.sequence.to_string().into()).await?;
+        }
 
         Ok(())
     }
 
+    /// Returns the name of a running pod of the actor.
+    pub async fn pod(location: &Location) -> Result<String> {
+        let api: Api<Pod> = Api::namespaced(location.client.clone(), &location.namespace);
+        let params = ListParams::default().labels(&format!("amphitheatre.app/character={}", location.name));
+        let pods = api.list(&params).await.map_err(ApiError::KubernetesError)?;
+
+        pods.items
+            .iter()
+            .find(|pod| pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running"))
+            .map(|pod| pod.name_any())
+            .ok_or(ApiError::NotFound)
+    }
+
+    /// Returns the port the debugger of actor listens on.
+    pub async fn debug_port(location: &Location) -> Result<u16> {
+        let Location { client, namespace, name } = location;
+        let actor = actor::get(client, namespace, name).await.map_err(ApiError::ResourceError)?;
+        let debugger = debug::debugger(&actor).map_err(ApiError::ResourceError)?;
+        let debugger = debugger.ok_or_else(|| ApiError::BadRequest(format!("Actor {} is not debugging", name)))?;
+
+        u16::try_from(debugger.port()).map_err(|_| ApiError::BadRequest(format!("Invalid debug port of {}", name)))
+    }
+
+    /// Returns the port the SSH server of the dev container of actor listens on.
+    pub async fn ssh_port(location: &Location) -> Result<u16> {
+        let Location { client, namespace, name } = location;
+        let actor = actor::get(client, namespace, name).await.map_err(ApiError::ResourceError)?;
+        let devcontainer = devcontainer::devcontainer(&actor).map_err(ApiError::ResourceError)?;
+        let devcontainer = devcontainer
+            .filter(|devcontainer| devcontainer.ssh())
+            .ok_or_else(|| ApiError::BadRequest(format!("Actor {} is not a dev container with SSH", name)))?;
+
+        u16::try_from(devcontainer.port()).map_err(|_| ApiError::BadRequest(format!("Invalid SSH port of {}", name)))
+    }
+
     pub async fn stats(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<HashMap<String, String>> {
-        let metrics =
-            actor::metrics(&ctx.k8s, &format!("amp-{}", pid), &name).await.map_err(ApiError::ResourceError)?;
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let metrics = actor::metrics(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
 
         // Just return the metrics for name
         let container = metrics.containers.iter().find(|c| c.name == name).ok_or_else(|| {
@@ -85,7 +475,8 @@ impl ActorService {
     }
 
     pub async fn info(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<HashMap<String, HashMap<String, String>>> {
-        let actor = actor::get(&ctx.k8s, &format!("amp-{}", pid), &name).await.map_err(ApiError::ResourceError)?;
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let actor = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
 
         let mut info = HashMap::new();
         if let Some(deploy) = actor.spec.character.deploy {
@@ -96,4 +487,179 @@ impl ActorService {
 
         Ok(info)
     }
+
+    pub async fn usage(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<ActorUsage> {
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let actor = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+
+        // The detached actors have no playbook, they belong to the default workspace.
+        let workspace = match playbook::get(&ctx.k8s, &pid.to_string()).await {
+            Ok(playbook) => workspace::of(&playbook),
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => workspace::DEFAULT_WORKSPACE.to_string(),
+            Err(err) => return Err(ApiError::ResourceError(err)),
+        };
+        let policies = workspace::load(&ctx.k8s, &ctx.config.namespace).await.map_err(ApiError::ResourceError)?;
+        let policy = policies.get(&workspace).cloned().unwrap_or_default();
+
+        let current = usage::get(&client, &actor, Utc::now()).await.map_err(ApiError::ResourceError)?;
+        Ok(ActorUsage {
+            period: current.period,
+            build_minutes: current.build_minutes,
+            runtime_hours: current.runtime_hours,
+            build_minutes_budget: policy.build_minutes_budget,
+            runtime_hours_budget: policy.runtime_hours_budget,
+            builds_paused: usage::builds_paused(&actor),
+        })
+    }
+
+    pub async fn sbom(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<serde_json::Value> {
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let actor = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        let document = match sbom::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)? {
+            Some(document) => {
+                if let Err(err) = sbom::archive(&client, &ctx.artifacts, &actor, &document).await {
+                    error!("Failed to archive the SBOM of actor {}: {}", name, err);
+                }
+                document
+            }
+            // The generation Job may have been deleted, or the pod evicted.
+            None => sbom::archived(&ctx.artifacts, &actor).await.map_err(ApiError::ResourceError)?,
+        };
+        let document = document.ok_or(ApiError::NotFound)?;
+
+        serde_json::from_str(&document).map_err(|err| {
+            error!("The SBOM of actor {} is not a valid JSON document: {}", name, err);
+            ApiError::InternalServerError
+        })
+    }
+
+    /// Returns the recent builds of actor, the latest first.
+    pub async fn builds(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<Vec<ActorBuild>> {
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let actor = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        let records = build::records(&client, &actor).await.map_err(ApiError::ResourceError)?;
+
+        let now = Utc::now();
+        let builds = records.into_iter().rev().map(|record| ActorBuild {
+            status: format!("{:?}", record.status),
+            trigger: record.trigger.map(|trigger| format!("{:?}", trigger).to_lowercase()),
+            duration: record.duration(now).map(|duration| duration.num_seconds()),
+            logs: record.logs.is_some(),
+            id: record.id,
+            builder: record.builder,
+            revision: record.revision,
+            digest: record.digest,
+            started: record.started,
+            finished: record.finished,
+        });
+
+        Ok(builds.collect())
+    }
+
+    /// Promote the built image of actor to the character of another playbook,
+    /// the character is added to that playbook if it's not declared yet.
+    pub async fn promote(
+        ctx: Arc<Context>,
+        pid: Uuid,
+        name: String,
+        req: &PromoteActorRequest,
+    ) -> Result<ActorPromotion> {
+        if req.playbook == pid {
+            return Err(ApiError::BadRequest("the image can't be promoted to its own playbook".into()));
+        }
+
+        let character = req.character.clone().unwrap_or_else(|| name.clone());
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let actor = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        let target = match playbook::get(&ctx.k8s, &req.playbook.to_string()).await {
+            Ok(target) => target,
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => return Err(ApiError::NotFound),
+            Err(err) => return Err(ApiError::ResourceError(err)),
+        };
+
+        let records = build::records(&client, &actor).await.map_err(ApiError::ResourceError)?;
+        let promotion = promotion::promote(&ctx.k8s, &target, &character, &actor, &records).await;
+        let promotion = promotion.map_err(|err| match err {
+            Error::InvalidPromotion(_) => ApiError::BadRequest(err.to_string()),
+            err => ApiError::ResourceError(err),
+        })?;
+
+        Ok(ActorPromotion {
+            playbook: req.playbook.to_string(),
+            character,
+            image: promotion.image,
+            digest: promotion.digest,
+            revision: promotion.revision,
+            promoted_at: promotion.promoted_at,
+        })
+    }
+
+    /// Returns the archived logs of the build of actor, they outlive the build pod.
+    pub async fn build_logs(ctx: Arc<Context>, pid: Uuid, name: String, id: String) -> Result<Vec<u8>> {
+        let Location { client, namespace, name } = Self::locate(&ctx, pid, &name).await?;
+        let actor = actor::get(&client, &namespace, &name).await.map_err(ApiError::ResourceError)?;
+        let records = build::records(&client, &actor).await.map_err(ApiError::ResourceError)?;
+        let digest = records.into_iter().find(|record| record.id == id).and_then(|record| record.logs);
+        let digest = digest.ok_or(ApiError::NotFound)?;
+
+        let content = ctx.artifacts.get(Kind::BuildLogs, &digest).await.map_err(ApiError::ResourceError)?;
+        content.ok_or(ApiError::NotFound)
+    }
+
+    /// Ask the syncer of actor for the differences between its workspace
+    /// and the source received by the last overwrite synchronization.
+    pub async fn diff(ctx: Arc<Context>, pid: Uuid, name: String) -> Result<serde_json::Value> {
+        let client = async_nats::connect(&ctx.config.nats_url).await.map_err(|err| ApiError::NatsError(err.into()))?;
+
+        let subject = format!("amp.diff.{}.{}", pid, name);
+        let message = client.request(subject, "".into()).await.map_err(|err| match err.kind() {
+            // No syncer is running, or it has not received any source yet.
+            RequestErrorKind::NoResponders | RequestErrorKind::TimedOut => ApiError::NotFound,
+            _ => ApiError::NatsError(err.into()),
+        })?;
+
+        serde_json::from_slice(&message.payload).map_err(|err| {
+            error!("The diff of actor {} is not a valid JSON document: {}", name, err);
+            ApiError::InternalServerError
+        })
+    }
+}
+
+/// The label of the pods naming the character they belong to.
+const CHARACTER_LABEL: &str = "amphitheatre.app/character";
+
+/// The key-value bucket holding the stream sequence of the last full sync of
+/// each actor, keyed by its subject, it's read by the syncers too.
+const SYNCS_BUCKET: &str = "amp-syncs";
+
+/// The workloads of the actors in a namespace, listed once for all of them.
+struct Workloads {
+    jobs: Vec<Job>,
+    deployments: Vec<Deployment>,
+    statefulsets: Vec<StatefulSet>,
+    pods: Vec<Pod>,
+}
+
+impl Workloads {
+    async fn list(client: &Client, namespace: &str) -> Result<Self> {
+        Ok(Workloads {
+            jobs: list(client, namespace, &ListParams::default()).await?,
+            deployments: list(client, namespace, &ListParams::default()).await?,
+            statefulsets: list(client, namespace, &ListParams::default()).await?,
+            pods: list(client, namespace, &ListParams::default().labels(CHARACTER_LABEL)).await?,
+        })
+    }
+}
+
+async fn list<K>(client: &Client, namespace: &str, params: &ListParams) -> Result<Vec<K>>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()> + Clone + DeserializeOwned + Debug,
+{
+    let api: Api<K> = Api::namespaced(client.clone(), namespace);
+    Ok(api.list(params).await.map_err(ApiError::KubernetesError)?.items)
+}
+
+/// Find the object by its name.
+fn find<'a, K: ResourceExt>(objects: &'a [K], name: &str) -> Option<&'a K> {
+    objects.iter().find(|object| object.name_any() == name)
 }
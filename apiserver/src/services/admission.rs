@@ -0,0 +1,54 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_common::resource::Actor;
+use amp_resources::containers::sidecar;
+use amp_resources::policy;
+use kube::core::admission::{AdmissionRequest, AdmissionResponse, AdmissionReview};
+use kube::core::DynamicObject;
+use tracing::info;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::Result;
+
+pub struct AdmissionService;
+
+impl AdmissionService {
+    /// Deny the actor if its image, or the image of its init containers and
+    /// sidecars, is not allowed by the registry policy.
+    pub async fn actors(ctx: Arc<Context>, review: AdmissionReview<Actor>) -> Result<AdmissionReview<DynamicObject>> {
+        let request: AdmissionRequest<Actor> = match review.try_into() {
+            Ok(request) => request,
+            Err(err) => return Ok(AdmissionResponse::invalid(err.to_string()).into_review()),
+        };
+
+        let mut response = AdmissionResponse::from(&request);
+        if let Some(actor) = &request.object {
+            let policy = policy::load(&ctx.k8s, &ctx.config.namespace).await.map_err(ApiError::ResourceError)?;
+            let checked = sidecar::containers(actor).and_then(|containers| {
+                policy.check(&actor.spec)?;
+                policy.check_containers(&containers)
+            });
+            if let Err(err) = checked {
+                info!("Denied actor {}: {}", request.name, err);
+                response = response.deny(err.to_string());
+            }
+        }
+
+        Ok(response.into_review())
+    }
+}
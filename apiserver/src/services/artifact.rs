@@ -0,0 +1,37 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::artifact::Kind;
+use amp_resources::error::Error;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::Result;
+
+pub struct ArtifactService;
+
+impl ArtifactService {
+    /// Returns the kind and the content of the artifact addressed by its digest.
+    pub async fn download(ctx: Arc<Context>, kind: String, digest: String) -> Result<(Kind, Vec<u8>)> {
+        let kind: Kind = kind.parse().map_err(|_| ApiError::NotFound)?;
+        let content = ctx.artifacts.get(kind, &digest).await.map_err(|err| match err {
+            Error::InvalidArtifactDigest(_) => ApiError::BadRequest(err.to_string()),
+            err => ApiError::ResourceError(err),
+        })?;
+
+        Ok((kind, content.ok_or(ApiError::NotFound)?))
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+pub use amp_resources::audit::AuditEvent;
+use amp_resources::audit::Publisher;
+use async_nats::jetstream;
+use async_nats::jetstream::consumer::{pull, AckPolicy, DeliverPolicy};
+use futures::StreamExt;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::errors::ApiError;
+use crate::services::Result;
+
+/// A page of the audit events, ordered from the oldest.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditPage {
+    pub events: Vec<AuditEvent>,
+    /// The offset of the next page, if there are more events.
+    pub next: Option<u64>,
+}
+
+/// The repository of the audit events, which are persisted in a JetStream
+/// stream and expire after the retention period.
+#[derive(Clone)]
+pub struct AuditRepository {
+    publisher: Publisher,
+}
+
+impl AuditRepository {
+    pub fn new(jetstream: jetstream::Context, retention: Duration) -> Self {
+        Self { publisher: Publisher::new(jetstream, retention) }
+    }
+
+    pub async fn record(&self, event: &AuditEvent) -> Result<()> {
+        self.publisher.record(event).await.map_err(ApiError::ResourceError)
+    }
+
+    /// List at most `limit` events starting from the `offset` (sequence).
+    pub async fn list(&self, offset: u64, limit: usize) -> Result<AuditPage> {
+        let mut stream = self.publisher.stream().await.map_err(ApiError::ResourceError)?.clone();
+        let last = stream.info().await.map_err(|err| ApiError::NatsError(err.into()))?.state.last_sequence;
+        if offset > last {
+            return Ok(AuditPage { events: vec![], next: None });
+        }
+
+        // Fetch the page in one batch from an ephemeral consumer, starting from the
+        // first event that has not expired yet if the offset is older than it.
+        let config = pull::Config {
+            deliver_policy: DeliverPolicy::ByStartSequence { start_sequence: offset.max(1) },
+            ack_policy: AckPolicy::None,
+            inactive_threshold: Duration::from_secs(30),
+            ..Default::default()
+        };
+        let consumer = stream.create_consumer(config).await.map_err(|err| ApiError::NatsError(err.into()))?;
+        let batch = consumer.fetch().max_messages(limit).messages().await;
+        let mut messages = batch.map_err(|err| ApiError::NatsError(err.into()))?;
+
+        let mut events: Vec<AuditEvent> = vec![];
+        while let Some(message) = messages.next().await {
+            let message = message.map_err(|err| ApiError::NatsError(err.into()))?;
+            let mut event: AuditEvent =
+                serde_json::from_slice(&message.payload).map_err(|err| ApiError::NatsError(err.into()))?;
+            event.id = message.info().map_err(ApiError::NatsError)?.stream_sequence;
+            events.push(event);
+        }
+
+        let next = events.last().map(|event| event.id + 1).filter(|sequence| *sequence <= last);
+        Ok(AuditPage { events, next })
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_common::resource::Actor;
+use amp_resources::cost::{self, Requests};
+use amp_resources::error::Error;
+use amp_resources::{actor, playbook, statefulset, strategy, usage};
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_openapi::chrono::Utc;
+use kube::{Api, ResourceExt};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::playbook::PlaybookService;
+use crate::services::Result;
+
+/// The estimated cost of playbook, in total and by its actors.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlaybookCost {
+    /// The currency of the costs, e.g. `USD`.
+    pub currency: Option<String>,
+    /// The month of the costs so far, e.g. `2026-10`.
+    pub period: String,
+    /// The cost of the running pods per hour.
+    pub hourly: f64,
+    /// The cost of a month if the pods keep running as now.
+    pub monthly: f64,
+    /// The cost of the runtime and the builds in the current month so far.
+    pub month_to_date: f64,
+    pub actors: Vec<ActorCost>,
+}
+
+/// The estimated cost of actor, by the resources requested by its pods.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActorCost {
+    pub name: String,
+    /// The number of the running pods.
+    pub replicas: i32,
+    /// The cpu in cores requested by each pod.
+    pub cpu: f64,
+    /// The memory in bytes requested by each pod.
+    pub memory: f64,
+    pub hourly: f64,
+    pub monthly: f64,
+    pub month_to_date: f64,
+}
+
+pub struct CostService;
+
+impl CostService {
+    /// Estimate the cost of the actors of playbook with the unit prices, the
+    /// configured ones or the ones of the `amp-prices` ConfigMap.
+    pub async fn playbook(ctx: Arc<Context>, id: Uuid) -> Result<PlaybookCost> {
+        let namespace = PlaybookService::namespace(&ctx, id).await?;
+        let actors = match playbook::get(&ctx.k8s, &id.to_string()).await {
+            Ok(playbook) => actor::list_of(&ctx.k8s, &playbook).await,
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => actor::list(&ctx.k8s, &namespace).await,
+            Err(err) => return Err(ApiError::ResourceError(err)),
+        };
+        let actors = actors.map_err(ApiError::ResourceError)?;
+        let prices =
+            cost::load(&ctx.k8s, &ctx.config.namespace, ctx.config.prices()).await.map_err(ApiError::ResourceError)?;
+
+        let now = Utc::now();
+        let mut total = PlaybookCost {
+            currency: prices.currency.clone(),
+            period: usage::period(now),
+            hourly: 0.0,
+            monthly: 0.0,
+            month_to_date: 0.0,
+            actors: vec![],
+        };
+        let usages = usage::load(&ctx.k8s, &namespace).await.map_err(ApiError::ResourceError)?;
+        for actor in actors {
+            let (requests, replicas) = Self::workload(&ctx, &actor).await?;
+            let estimated = cost::estimate(&prices, &requests, replicas, &usage::of(&usages, &actor, now));
+            total.hourly += estimated.hourly;
+            total.monthly += estimated.monthly;
+            total.month_to_date += estimated.month_to_date;
+            total.actors.push(ActorCost {
+                name: actor.name_any(),
+                replicas,
+                cpu: requests.cpu,
+                memory: requests.memory,
+                hourly: estimated.hourly,
+                monthly: estimated.monthly,
+                month_to_date: estimated.month_to_date,
+            });
+        }
+
+        Ok(total)
+    }
+
+    /// Returns the resources requested by each pod of actor and the number
+    /// of its running pods, the scheduled actors are not running in between.
+    async fn workload(ctx: &Context, actor: &Actor) -> Result<(Requests, i32)> {
+        let namespace = actor.namespace().unwrap_or_default();
+        if statefulset::stateful(actor) {
+            let api: Api<StatefulSet> = Api::namespaced(ctx.k8s.clone(), &namespace);
+            let statefulset = api.get_opt(&statefulset::name(actor)).await.map_err(ApiError::KubernetesError)?;
+            return Ok(statefulset
+                .map(|s| {
+                    let requests = s.spec.and_then(|spec| spec.template.spec).map(|pod| cost::requests(&pod));
+                    (requests.unwrap_or_default(), s.status.map(|status| status.replicas).unwrap_or_default())
+                })
+                .unwrap_or_default());
+        }
+
+        // The blue/green actor is served by the Deployment of its active color.
+        let api: Api<Deployment> = Api::namespaced(ctx.k8s.clone(), &namespace);
+        let deployment = api.get_opt(&strategy::serving(actor)).await.map_err(ApiError::KubernetesError)?;
+        Ok(deployment
+            .map(|d| {
+                let requests = d.spec.and_then(|spec| spec.template.spec).map(|pod| cost::requests(&pod));
+                (requests.unwrap_or_default(), d.status.and_then(|status| status.replicas).unwrap_or_default())
+            })
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use amp_resources::envset;
+use amp_resources::error::Error;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::requests::envset::ApplyEnvSetRequest;
+use crate::services::Result;
+
+/// A named collection of env vars shared by the playbooks of a workspace.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct EnvSet {
+    pub name: String,
+    pub workspace: String,
+    pub env: BTreeMap<String, String>,
+    pub secrets: BTreeMap<String, SecretRef>,
+}
+
+/// The key of a shared Secret in the Amphitheatre namespace.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SecretRef {
+    pub name: String,
+    pub key: String,
+}
+
+impl From<envset::EnvSet> for EnvSet {
+    fn from(set: envset::EnvSet) -> Self {
+        let secrets = set.secrets.into_iter().map(|(k, v)| (k, SecretRef { name: v.name, key: v.key })).collect();
+        EnvSet { name: set.name, workspace: set.workspace, env: set.env, secrets }
+    }
+}
+
+pub struct EnvSetService;
+
+impl EnvSetService {
+    pub async fn get(ctx: Arc<Context>, workspace: &str, name: &str) -> Result<EnvSet> {
+        let set = envset::get(&ctx.k8s, &ctx.config.namespace, workspace, name).await;
+
+        set.map_err(ApiError::ResourceError)?.map(EnvSet::from).ok_or(ApiError::NotFound)
+    }
+
+    pub async fn list(ctx: Arc<Context>, workspace: &str) -> Result<Vec<EnvSet>> {
+        let sets = envset::list(&ctx.k8s, &ctx.config.namespace, workspace).await.map_err(ApiError::ResourceError)?;
+
+        Ok(sets.into_iter().map(EnvSet::from).collect())
+    }
+
+    /// Create or replace the env set, the actors referencing it pick up the
+    /// changes on their next deploy. The referenced Secrets must be shared
+    /// with the env sets.
+    pub async fn apply(ctx: Arc<Context>, workspace: &str, name: &str, req: &ApplyEnvSetRequest) -> Result<EnvSet> {
+        let secrets = req
+            .secrets
+            .iter()
+            .map(|(k, v)| (k.clone(), envset::SecretRef { name: v.name.clone(), key: v.key.clone() }));
+        let set = envset::EnvSet {
+            name: name.into(),
+            workspace: workspace.into(),
+            env: req.env.clone(),
+            secrets: secrets.collect(),
+        };
+        envset::apply(&ctx.k8s, &ctx.config.namespace, &set).await.map_err(|err| match err {
+            Error::SecretNotShared(_) => ApiError::BadRequest(err.to_string()),
+            err => ApiError::ResourceError(err),
+        })?;
+
+        Ok(set.into())
+    }
+
+    pub async fn delete(ctx: Arc<Context>, workspace: &str, name: &str) -> Result<()> {
+        Self::get(ctx.clone(), workspace, name).await?;
+
+        envset::delete(&ctx.k8s, &ctx.config.namespace, workspace, name).await.map_err(ApiError::ResourceError)
+    }
+}
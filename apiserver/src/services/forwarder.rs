@@ -0,0 +1,92 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use axum::extract::ws::{Message, WebSocket};
+use k8s_openapi::api::core::v1::Pod;
+use kube::Api;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{error, info, warn};
+
+/// Tunnels the WebSocket connection to the port of actor's pod.
+pub struct Forwarder {
+    api: Api<Pod>,          // The Kubernetes API client.
+    actor: String,          // The name of the actor.
+    port: u16,              // The port of the pod to forward to.
+    idle_timeout: Duration, // Close the tunnel if no data is transferred within it.
+}
+
+impl Forwarder {
+    /// Creates a new forwarder.
+    pub fn new(client: kube::Client, namespace: &str, actor: String, port: u16) -> Self {
+        let api: Api<Pod> = Api::namespaced(client, namespace);
+
+        Self { api, actor, port, idle_timeout: Duration::from_secs(300) }
+    }
+
+    /// Sets the idle timeout of the tunnel.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Forwards the WebSocket connection to the pod until either side closes it.
+    pub async fn forward(self, pod: String, mut socket: WebSocket) {
+        info!("Opened port-forward to {}:{} of actor {}", pod, self.port, self.actor);
+
+        if let Err(err) = self.tunnel(&pod, &mut socket).await {
+            error!("Port-forward to {}:{} of actor {} failed: {}", pod, self.port, self.actor, err);
+        }
+
+        // The socket may have been closed by the client already.
+        let _ = socket.send(Message::Close(None)).await;
+        info!("Closed port-forward to {}:{} of actor {}", pod, self.port, self.actor);
+    }
+
+    /// Copies the data between the WebSocket and the forwarded port.
+    async fn tunnel(&self, pod: &str, socket: &mut WebSocket) -> anyhow::Result<()> {
+        let mut forwarder = self.api.portforward(pod, &[self.port]).await?;
+        let mut upstream =
+            forwarder.take_stream(self.port).ok_or_else(|| anyhow!("port {} is not forwarded", self.port))?;
+
+        let mut buffer = vec![0u8; 8192];
+        loop {
+            tokio::select! {
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Binary(data))) => upstream.write_all(&data).await?,
+                    Some(Ok(Message::Text(text))) => upstream.write_all(text.as_bytes()).await?,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    // The pings are answered by axum automatically.
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Err(err.into()),
+                },
+                read = upstream.read(&mut buffer) => match read? {
+                    0 => break,
+                    n => socket.send(Message::Binary(buffer[..n].to_vec())).await?,
+                },
+                _ = tokio::time::sleep(self.idle_timeout) => {
+                    warn!("Port-forward to {}:{} is idle for {:?}, closing", pod, self.port, self.idle_timeout);
+                    break;
+                }
+            }
+        }
+
+        drop(upstream);
+        forwarder.abort();
+
+        Ok(())
+    }
+}
@@ -12,64 +12,205 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use axum::response::sse::Event;
 use futures::AsyncBufReadExt;
-use futures::StreamExt;
 use futures::TryStreamExt;
+use futures::{Stream, StreamExt};
 use k8s_openapi::api::core::v1::ContainerStatus;
 use k8s_openapi::api::core::v1::Pod;
-use kube::api::LogParams;
+use k8s_openapi::chrono::{self, DateTime, Utc};
+use kube::api::{ListParams, LogParams};
 use kube::runtime::watcher::Config;
 use kube::runtime::{watcher, WatchStreamExt};
 use kube::Api;
 use kube::ResourceExt;
-use tokio::sync::mpsc::Sender;
+use serde_json::json;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+/// The events sent to the client, only the lines are limited by the rate.
+pub enum Message {
+    /// A line of the containers.
+    Line(Event),
+    /// The control events like `attached`, and the errors, they're always sent.
+    Notice(Event),
+}
+
 pub struct Logger {
-    api: Api<Pod>,                            // The Kubernetes API client.
-    sender: Sender<Event>,                    // The sender of the log stream.
-    config: Config,                           // The configuration of watcher.
-    watches: HashMap<String, JoinHandle<()>>, // The map of watching containers.
+    api: Api<Pod>,                                             // The Kubernetes API client.
+    sender: Sender<Message>,                                   // The sender of the log stream.
+    config: Config,                                            // The configuration of watcher.
+    watches: HashMap<(String, String), (i32, JoinHandle<()>)>, // The watching containers and their restarts.
+    cursor: Arc<Mutex<Cursor>>,                                // The lines sent of every container.
+    since: Option<DateTime<Utc>>,                              // The time to send the lines after.
+    follow: bool,                                              // Whether to keep streaming the new lines.
+    container: Option<String>,                                 // The only container to stream, if any.
+    prefixed: bool,                                            // Whether to prefix the lines with their sources.
 }
 
+/// The label of the pods naming their actors.
+const CHARACTER_LABEL: &str = "amphitheatre.app/character";
+
+/// The label of the pods naming their playbook.
+const PLAYBOOK_LABEL: &str = amp_resources::actor::PLAYBOOK_LABEL;
+
 impl Logger {
-    /// Creates a new logger.
-    pub fn new(client: kube::Client, sender: Sender<Event>, playbook: Uuid, actor: String) -> Self {
-        let api: Api<Pod> = Api::namespaced(client, &format!("amp-{playbook}"));
-        let label_selector = format!("amphitheatre.app/character={actor}");
+    /// Creates a new logger of the actor in the namespace.
+    pub fn new(client: kube::Client, sender: Sender<Message>, namespace: &str, actor: String) -> Self {
+        let api: Api<Pod> = Api::namespaced(client, namespace);
+        let label_selector = format!("{CHARACTER_LABEL}={actor}");
+
+        Self { config: Config::default().labels(&label_selector), ..Self::new_with(api, sender) }
+    }
+
+    /// Creates a new logger of all the actors in the playbook, every line is
+    /// prefixed with its actor and container, like `web/app | ...`. The pods
+    /// in a namespace shared with other playbooks are selected by its label.
+    pub fn playbook(client: kube::Client, sender: Sender<Message>, namespace: &str, playbook: Option<Uuid>) -> Self {
+        let api: Api<Pod> = Api::namespaced(client, namespace);
+        let label_selector = match playbook {
+            Some(playbook) => format!("{CHARACTER_LABEL},{PLAYBOOK_LABEL}={playbook}"),
+            None => CHARACTER_LABEL.to_string(),
+        };
         let config = Config::default().labels(&label_selector);
 
-        Self { api, sender, config, watches: HashMap::new() }
+        Self { prefixed: true, config, ..Self::new_with(api, sender) }
+    }
+
+    fn new_with(api: Api<Pod>, sender: Sender<Message>) -> Self {
+        Self {
+            api,
+            sender,
+            config: Config::default(),
+            watches: HashMap::new(),
+            cursor: Arc::new(Mutex::new(Cursor::default())),
+            since: None,
+            follow: true,
+            container: None,
+            prefixed: false,
+        }
+    }
+
+    /// Resumes the log stream after the given offset token, the lines of each
+    /// container sent before will not be sent again. Invalid tokens are ignored.
+    pub fn resume(mut self, token: Option<&str>) -> Self {
+        let cursor = token.and_then(|token| match token.parse::<Cursor>() {
+            Ok(cursor) => Some(cursor),
+            Err(err) => {
+                warn!("Ignore the invalid log offset token {}: {}", token, err);
+                None
+            }
+        });
+        self.cursor = Arc::new(Mutex::new(cursor.unwrap_or_default()));
+        self
+    }
+
+    /// Sends the lines after the given time only, the containers resumed from
+    /// the offset token are sent after their own positions instead.
+    pub fn since(mut self, since: Option<DateTime<Utc>>) -> Self {
+        self.since = since;
+        self
+    }
+
+    /// Keeps streaming the new lines, or stops after the existing ones are sent.
+    pub fn follow(mut self, follow: bool) -> Self {
+        self.follow = follow;
+        self
+    }
+
+    /// Streams the given container of the pods only.
+    pub fn container(mut self, container: Option<String>) -> Self {
+        self.container = container;
+        self
     }
 
     /// Starts the logger.
     pub async fn start(&mut self) {
-        let watcher = watcher(self.api.clone(), self.config.clone());
+        if !self.follow {
+            return self.dump().await;
+        }
+
+        let watcher = watcher(self.api.clone(), self.config.clone()).default_backoff();
         let mut watcher = watcher.touched_objects().boxed();
 
-        while let Some(pod) = watcher.try_next().await.unwrap() {
+        loop {
+            // Stop watching after the client is gone.
+            if self.sender.is_closed() {
+                self.unsubscribe_all(None);
+                return;
+            }
+
+            let pod = match watcher.try_next().await {
+                Ok(Some(pod)) => pod,
+                Ok(None) => return,
+                Err(err) => {
+                    warn!("Failed to watch the pods, retrying: {}", err);
+                    continue;
+                }
+            };
             let pod_name = pod.name_any();
+            let actor = pod.labels().get(CHARACTER_LABEL).cloned().unwrap_or_default();
+
+            // Unsubscribe all the watches of the pod if pod is terminating,
+            // the new pods replacing it are attached when they are running.
+            if pod.metadata.deletion_timestamp.is_some() {
+                self.unsubscribe_all(Some(&pod_name));
+                continue;
+            }
             if let Some(status) = pod.status {
-                // Unsubscribe all the watches of the pod if pod is terminating.
-                // and then break the loop to exit the function.
-                if status.phase == Some("Terminating".into()) {
-                    self.unsubscribe_all(&pod_name);
-                    return;
-                }
+                self.watches(&pod_name, &actor, status.init_container_statuses).await;
+                self.watches(&pod_name, &actor, status.container_statuses).await;
+            }
+        }
+    }
+
+    /// Sends the existing lines of the containers of all the pods.
+    async fn dump(&mut self) {
+        let labels = self.config.label_selector.clone().unwrap_or_default();
+        let pods = match self.api.list(&ListParams::default().labels(&labels)).await {
+            Ok(pods) => pods.items,
+            Err(err) => {
+                let message = format!("Some error occurred while listing the pods: {}.", err);
+                error!("{}", message);
+                _ = self.sender.send(Message::Notice(Event::default().data(message))).await;
+                return;
+            }
+        };
 
-                self.watches(&pod_name, status.init_container_statuses).await;
-                self.watches(&pod_name, status.container_statuses).await;
+        for pod in pods {
+            let actor = pod.labels().get(CHARACTER_LABEL).cloned().unwrap_or_default();
+            let spec = pod.spec.unwrap_or_default();
+            let containers = spec.init_containers.unwrap_or_default().into_iter().chain(spec.containers);
+            for container in containers {
+                if self.container.as_ref().is_some_and(|name| name != &container.name) {
+                    continue;
+                }
+                let tail = Tail {
+                    api: self.api.clone(),
+                    sender: self.sender.clone(),
+                    cursor: self.cursor.clone(),
+                    pod: pod.name_any(),
+                    prefix: self.prefixed.then(|| format!("{}/{}", actor, container.name)),
+                    container: container.name,
+                    since: self.since,
+                };
+                tail.start(false).await;
             }
         }
     }
 
     /// Watches the containers of the pod.
-    async fn watches(&mut self, pod: &str, containers: Option<Vec<ContainerStatus>>) {
+    async fn watches(&mut self, pod: &str, actor: &str, containers: Option<Vec<ContainerStatus>>) {
         if containers.is_none() {
             warn!("No container statuses found in pod {}.", pod);
             return;
@@ -77,20 +218,24 @@ impl Logger {
 
         // Iterate the containers of the pod.
         for container in containers.unwrap() {
+            if self.container.as_ref().is_some_and(|name| name != &container.name) {
+                continue;
+            }
             if container.state.is_none() {
                 warn!("No state found in container {} of {}.", container.name, pod);
                 continue;
             }
             let state = container.state.unwrap();
 
-            // If the container is running, then subscribe the log stream.
+            // If the container is running, then subscribe the log stream,
+            // and re-attach to it after it's restarted.
             if state.running.is_some_and(|s| s.started_at.is_some()) {
-                let key = &format!("{}-{}", pod, container.name);
-                if self.watches.contains_key(key) {
+                let key = (pod.to_string(), container.name.clone());
+                if self.watches.get(&key).is_some_and(|(restarts, _)| *restarts == container.restart_count) {
                     debug!("Skip container {} of {} because it's watching.", &container.name, pod);
                     continue;
                 }
-                self.subscribe(pod, &container.name).await;
+                self.subscribe(pod, actor, &container.name, container.restart_count).await;
             }
 
             // If the container is terminated, then unsubscribe the log stream.
@@ -102,64 +247,323 @@ impl Logger {
     }
 
     /// Subscribes the log stream of the container.
-    async fn subscribe(&mut self, pod: &str, container: &str) {
-        let key = format!("{}-{}", pod, container);
+    async fn subscribe(&mut self, pod: &str, actor: &str, container: &str, restarts: i32) {
+        self.unsubscribe(pod, container);
+        let prefix = self.prefixed.then(|| format!("{}/{}", actor, container));
 
-        let api = self.api.clone();
         let sender = self.sender.clone();
         let container = container.to_string();
         let pod = pod.to_string();
 
-        let task = tokio::spawn(async move {
-            Self::tail(api, sender, pod, container).await;
+        // Tell the client which container the following lines come from, with
+        // a stable color of the actor in 1..=6 (the ANSI colors from red to cyan)
+        // for telling the actors apart without escape codes in the lines.
+        let attached = json!({
+            "actor": actor,
+            "pod": pod,
+            "container": container,
+            "restarts": restarts,
+            "color": color(actor),
+        });
+        _ = sender.send(Message::Notice(Event::default().event("attached").data(attached.to_string()))).await;
+
+        let key = (pod.clone(), container.clone());
+        let tail = Tail {
+            api: self.api.clone(),
+            sender,
+            cursor: self.cursor.clone(),
+            pod,
+            container,
+            prefix,
+            since: self.since,
+        };
+        let task = tokio::spawn(tail.start(true));
+
+        self.watches.insert(key, (restarts, task));
+    }
+
+    /// Unsubscribes all the log streams of the pod, or of all the pods.
+    fn unsubscribe_all(&mut self, pod: Option<&str>) {
+        self.watches.retain(|(name, container), (_, task)| {
+            if pod.is_some_and(|pod| pod != name) {
+                return true;
+            }
+            info!("Unsubscribe the log stream of container {} in {}.", container, name);
+            task.abort();
+            false
         });
+    }
 
-        self.watches.insert(key, task);
+    /// Unsubscribes the log stream of the container.
+    fn unsubscribe(&mut self, pod: &str, container: &str) {
+        if let Some((_, task)) = self.watches.remove(&(pod.to_string(), container.to_string())) {
+            info!("Unsubscribe the log stream of container {} in {}.", container, pod);
+            task.abort();
+        }
     }
+}
 
+/// The log stream of a container.
+struct Tail {
+    api: Api<Pod>,                // The Kubernetes API client.
+    sender: Sender<Message>,      // The sender of the log stream.
+    cursor: Arc<Mutex<Cursor>>,   // The lines sent of every container, shared by the streams.
+    pod: String,                  // The pod of the container.
+    container: String,            // The name of the container.
+    prefix: Option<String>,       // The prefix of the lines, if any.
+    since: Option<DateTime<Utc>>, // The time to send the lines after, unless resumed.
+}
+
+impl Tail {
     /// Tails the log stream of the container.
-    async fn tail(api: Api<Pod>, sender: Sender<Event>, pod: String, container: String) {
+    ///
+    /// Every line is sent with the positions of all the containers as the event
+    /// id, so that clients can resume the stream of each from it after reconnecting.
+    async fn start(self, follow: bool) {
+        let key = format!("{}/{}", self.pod, self.container);
+        let position = self.cursor.lock().await.get(&key);
+        let since = position.map(|(time, _)| time).or(self.since);
         let params = LogParams {
-            container: Some(container.to_string()),
-            follow: true,
-            tail_lines: Some(100),
-            timestamps: false,
+            container: Some(self.container.clone()),
+            follow,
+            // Replay the recent lines only if there is no time to send the lines after.
+            tail_lines: if since.is_none() { Some(100) } else { None },
+            since_time: since,
+            timestamps: true,
             ..Default::default()
         };
 
-        match api.log_stream(&pod, &params).await {
-            Ok(stream) => {
-                info!("Start to receive the log stream of container {} in {}...", container, pod);
-                let mut lines = stream.lines();
-                while let Ok(Some(line)) = lines.try_next().await {
-                    _ = sender.send(Event::default().data(line)).await;
-                }
-            }
+        let stream = match self.api.log_stream(&self.pod, &params).await {
+            Ok(stream) => stream,
             Err(err) => {
-                let message =
-                    format!("Some error occurred while log stream for container {} in {}: {}.", container, pod, err);
+                let message = format!(
+                    "Some error occurred while log stream for container {} in {}: {}.",
+                    self.container, self.pod, err
+                );
                 error!("{}", message);
-                _ = sender.send(Event::default().data(message)).await;
+                _ = self.sender.send(Message::Notice(Event::default().data(message))).await;
+                return;
+            }
+        };
+
+        info!("Start to receive the log stream of container {} in {}...", self.container, self.pod);
+        let mut lines = stream.lines();
+        let mut repeated = 0;
+        while let Ok(Some(line)) = lines.try_next().await {
+            let (timestamp, message) = line.split_once(' ').unwrap_or(("", line.as_str()));
+
+            // The `sinceTime` is only precise to the second, skip the lines already sent,
+            // the ones at the same time as the last one sent are told apart by their count.
+            let time = DateTime::parse_from_rfc3339(timestamp).map(|t| t.with_timezone(&Utc)).ok();
+            if let (Some(time), Some((last, count))) = (time, position) {
+                if time < last {
+                    continue;
+                }
+                if time == last {
+                    repeated += 1;
+                    if repeated <= count {
+                        continue;
+                    }
+                }
+            }
+
+            let mut event = match &self.prefix {
+                Some(prefix) => Event::default().data(format!("{} | {}", prefix, message)),
+                None => Event::default().data(message),
+            };
+
+            // Hold the cursor until the line is sent, so the ids are sent in the order they advance.
+            let mut cursor = self.cursor.lock().await;
+            if let Some(time) = time {
+                cursor.advance(&key, time);
+                event = event.id(cursor.to_string());
+            }
+            if self.sender.send(Message::Line(event)).await.is_err() {
+                return;
             }
         }
     }
+}
+
+/// The positions of the lines sent of every container, keyed by `{pod}/{container}`.
+/// A position is the time of the last line sent, and the number of the lines sent
+/// at that time, as the lines of a busy container may share the same time.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Cursor(BTreeMap<String, (DateTime<Utc>, usize)>);
+
+impl Cursor {
+    fn get(&self, key: &str) -> Option<(DateTime<Utc>, usize)> {
+        self.0.get(key).copied()
+    }
 
-    /// Unsubscribes all the log streams.
-    fn unsubscribe_all(&mut self, pod: &str) {
-        for (key, task) in self.watches.drain() {
-            if key.starts_with(pod) {
-                info!("Unsubscribe the log stream of container {} in {}.", key, pod);
-                task.abort();
+    /// Moves the position of the container to the line sent at the given time.
+    fn advance(&mut self, key: &str, time: DateTime<Utc>) {
+        match self.0.get_mut(key) {
+            Some((last, count)) if *last == time => *count += 1,
+            _ => {
+                self.0.insert(key.to_string(), (time, 1));
             }
         }
     }
+}
 
-    /// Unsubscribes the log stream of the container.
-    fn unsubscribe(&mut self, pod: &str, container: &str) {
-        let key = &format!("{}-{}", pod, container);
-        if let Some(task) = self.watches.remove(key) {
-            info!("Unsubscribe the log stream of container {} in {}.", container, pod);
-            task.abort();
+/// Formats the cursor as the token like `web-0/app=2024-01-01T00:00:00.1Z#2,web-1/app=...#1`.
+impl Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let positions: Vec<String> = self
+            .0
+            .iter()
+            .map(|(key, (time, count))| {
+                format!("{}={}#{}", key, time.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true), count)
+            })
+            .collect();
+        write!(f, "{}", positions.join(","))
+    }
+}
+
+impl std::str::FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        let mut positions = BTreeMap::new();
+        for position in token.split(',').filter(|position| !position.is_empty()) {
+            let invalid = || format!("invalid position {}", position);
+            let (key, value) = position.split_once('=').ok_or_else(invalid)?;
+            let (time, count) = value.rsplit_once('#').ok_or_else(invalid)?;
+            let time = DateTime::parse_from_rfc3339(time).map_err(|_| invalid())?.with_timezone(&Utc);
+            let count = count.parse().map_err(|_| invalid())?;
+            positions.insert(key.to_string(), (time, count));
         }
+
+        Ok(Self(positions))
+    }
+}
+
+/// Returns the color of the actor in 1..=6, the same actor has the same color everywhere.
+fn color(actor: &str) -> u8 {
+    (actor.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32)) % 6) as u8 + 1
+}
+
+/// Parses the `since` of the log stream, either a duration like `30s`, `10m`,
+/// `2h` and `1d`, or an RFC 3339 time.
+pub fn since(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+
+    let (amount, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit())?);
+    let amount: i64 = amount.parse().ok()?;
+    let seconds = match unit {
+        "s" => amount,
+        "m" => amount * 60,
+        "h" => amount * 60 * 60,
+        "d" => amount * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(Utc::now() - chrono::Duration::seconds(seconds))
+}
+
+/// Returns the stream of the received events admitted by the limiter.
+pub fn stream(receiver: Receiver<Message>, mut limiter: RateLimiter) -> impl Stream<Item = Result<Event, Infallible>> {
+    let stream = ReceiverStream::new(receiver);
+    stream.flat_map(move |message| futures::stream::iter(limiter.admit(message))).map(Ok)
+}
+
+/// A fixed window limiter for the lines sent to a single client.
+///
+/// The lines exceeding the limit are dropped rather than buffered, otherwise
+/// a chatty actor would keep the client further and further behind. A notice
+/// with the number of dropped lines is sent when the next window opens.
+pub struct RateLimiter {
+    limit: u32,      // The maximum number of lines per window.
+    window: Instant, // The start of the current window.
+    admitted: u32,   // The number of lines admitted in the current window.
+    dropped: u64,    // The number of lines dropped since the last notice.
+}
+
+impl RateLimiter {
+    /// Creates a new limiter with the given lines per second.
+    pub fn new(limit: u32) -> Self {
+        Self { limit, window: Instant::now(), admitted: 0, dropped: 0 }
+    }
+
+    /// Returns the events to send for the given message, the notices are never dropped.
+    pub fn admit(&mut self, message: Message) -> Vec<Event> {
+        let event = match message {
+            Message::Line(event) => event,
+            Message::Notice(event) => return vec![event],
+        };
+
+        let mut events = vec![];
+
+        if self.window.elapsed() >= Duration::from_secs(1) {
+            self.window = Instant::now();
+            self.admitted = 0;
+
+            if self.dropped > 0 {
+                let message = format!("{} lines were dropped because the rate limit was exceeded.", self.dropped);
+                events.push(Event::default().event("dropped").data(message));
+                self.dropped = 0;
+            }
+        }
+
+        if self.admitted < self.limit {
+            self.admitted += 1;
+            events.push(event);
+        } else {
+            self.dropped += 1;
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_lines_only() {
+        let mut limiter = RateLimiter::new(1);
+        assert_eq!(limiter.admit(Message::Line(Event::default().data("first"))).len(), 1);
+        assert!(limiter.admit(Message::Line(Event::default().data("second"))).is_empty());
+
+        // The notices are sent even if the lines are dropped.
+        assert_eq!(limiter.admit(Message::Notice(Event::default().event("attached"))).len(), 1);
+        assert_eq!(limiter.dropped, 1);
+    }
+
+    #[test]
+    fn test_cursor_advance() {
+        let time = DateTime::parse_from_rfc3339("2024-01-01T00:00:00.5Z").unwrap().with_timezone(&Utc);
+        let mut cursor = Cursor::default();
+        cursor.advance("web-0/app", time);
+        cursor.advance("web-0/app", time);
+        cursor.advance("web-1/app", time);
+        assert_eq!(cursor.get("web-0/app"), Some((time, 2)));
+
+        let later = time + chrono::Duration::seconds(1);
+        cursor.advance("web-0/app", later);
+        assert_eq!(cursor.get("web-0/app"), Some((later, 1)));
+        assert_eq!(cursor.get("web-1/app"), Some((time, 1)));
+        assert_eq!(cursor.get("web-2/app"), None);
+    }
+
+    #[test]
+    fn test_cursor_token() {
+        let time = DateTime::parse_from_rfc3339("2024-01-01T00:00:00.123456789Z").unwrap().with_timezone(&Utc);
+        let mut cursor = Cursor::default();
+        cursor.advance("web-0/app", time);
+        cursor.advance("web-0/sidecar", time);
+        cursor.advance("web-0/sidecar", time);
+
+        let token = cursor.to_string();
+        assert_eq!(token, "web-0/app=2024-01-01T00:00:00.123456789Z#1,web-0/sidecar=2024-01-01T00:00:00.123456789Z#2");
+        assert_eq!(token.parse::<Cursor>().unwrap(), cursor);
+
+        assert_eq!("".parse::<Cursor>().unwrap(), Cursor::default());
+        assert!("2024-01-01T00:00:00Z".parse::<Cursor>().is_err());
+        assert!("web-0/app=2024-01-01T00:00:00Z#many".parse::<Cursor>().is_err());
     }
 }
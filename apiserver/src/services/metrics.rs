@@ -0,0 +1,95 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::error::Error;
+use amp_resources::{actor, metrics, playbook};
+use kube::ResourceExt;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::playbook::PlaybookService;
+use crate::services::Result;
+
+/// The live resource consumption of playbook, in total and by its actors.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlaybookMetrics {
+    /// Where the metrics are collected from, `metrics-server` or `prometheus`.
+    pub source: String,
+    /// The number of the pods measured.
+    pub pods: usize,
+    /// The cpu in cores.
+    pub cpu: f64,
+    /// The memory in bytes.
+    pub memory: f64,
+    pub actors: Vec<ActorMetrics>,
+}
+
+/// The live resource consumption of the pods of actor.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActorMetrics {
+    pub name: String,
+    /// The number of the pods measured.
+    pub pods: usize,
+    /// The cpu in cores.
+    pub cpu: f64,
+    /// The memory in bytes.
+    pub memory: f64,
+}
+
+pub struct MetricsService;
+
+impl MetricsService {
+    /// Collect the resource consumption of the actors of playbook, the
+    /// namespace may be shared with other playbooks, only its own actors
+    /// are counted there.
+    pub async fn playbook(ctx: Arc<Context>, id: Uuid) -> Result<PlaybookMetrics> {
+        let namespace = PlaybookService::namespace(&ctx, id).await?;
+        let client = PlaybookService::client(&ctx, id).await?;
+        let actors = match playbook::get(&ctx.k8s, &id.to_string()).await {
+            Ok(playbook) => actor::list_of(&client, &playbook).await,
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => actor::list(&client, &namespace).await,
+            Err(err) => return Err(ApiError::ResourceError(err)),
+        };
+        let actors = actors.map_err(ApiError::ResourceError)?;
+
+        let prometheus = ctx.config.prometheus_url.as_deref();
+        let mut usages = metrics::collect(&client, &namespace, prometheus).await.map_err(ApiError::ResourceError)?;
+
+        let mut total = metrics::Usage::default();
+        let mut details = vec![];
+        for actor in actors {
+            let usage = usages.remove(&actor.name_any()).unwrap_or_default();
+            total += usage;
+            details.push(ActorMetrics {
+                name: actor.name_any(),
+                pods: usage.pods,
+                cpu: usage.cpu,
+                memory: usage.memory,
+            });
+        }
+
+        Ok(PlaybookMetrics {
+            source: if prometheus.is_some() { "prometheus" } else { "metrics-server" }.into(),
+            pods: total.pods,
+            cpu: total.cpu,
+            memory: total.memory,
+            actors: details,
+        })
+    }
+}
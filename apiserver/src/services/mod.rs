@@ -13,7 +13,24 @@
 // limitations under the License.
 
 pub mod actor;
+pub mod admission;
+pub mod artifact;
+pub mod audit;
+pub mod cost;
+pub mod envset;
+pub mod forwarder;
 pub mod logger;
+pub mod metrics;
+pub mod operation;
+pub mod planner;
 pub mod playbook;
+pub mod quota;
+pub mod resource;
+pub mod revision;
+pub mod search;
+pub mod source;
+pub mod template;
+pub mod terminal;
+pub mod webhook;
 
 pub type Result<T, E = crate::errors::ApiError> = std::result::Result<T, E>;
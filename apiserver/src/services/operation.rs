@@ -0,0 +1,247 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_nats::jetstream::{self, kv};
+use futures::TryStreamExt;
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::{Api, Client};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::Result;
+
+/// The JetStream key-value bucket persisting the operations.
+const OPERATIONS_BUCKET: &str = "amp-operations";
+
+/// How long the operations are kept after their last update.
+const OPERATIONS_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A long-running operation, which is executed in background after the
+/// mutating call returned, poll it for the progress.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Operation {
+    pub id: String,
+    /// What the operation does, e.g. `delete_playbook`.
+    pub kind: String,
+    /// The resource the operation is applied to.
+    pub target: String,
+    pub status: OperationStatus,
+    pub steps: Vec<Step>,
+    /// The error of the failed step, if any.
+    pub error: Option<String>,
+    /// The apiserver instance running the operation.
+    #[serde(default)]
+    pub owner: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A step of operation, they are executed in order.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Step {
+    pub name: String,
+    pub status: OperationStatus,
+}
+
+impl Operation {
+    fn new(kind: &str, target: &str, steps: &[&str], owner: &str) -> Self {
+        let now = Utc::now();
+        Operation {
+            id: Uuid::new_v4().to_string(),
+            kind: kind.into(),
+            target: target.into(),
+            status: OperationStatus::Pending,
+            steps: steps.iter().map(|name| Step { name: name.to_string(), status: OperationStatus::Pending }).collect(),
+            error: None,
+            owner: owner.into(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Complete the running step and start the given one.
+    fn start(&mut self, name: &str) {
+        self.complete();
+        self.status = OperationStatus::Running;
+        if let Some(step) = self.steps.iter_mut().find(|step| step.name == name) {
+            step.status = OperationStatus::Running;
+        }
+    }
+
+    fn complete(&mut self) {
+        for step in self.steps.iter_mut().filter(|step| step.status == OperationStatus::Running) {
+            step.status = OperationStatus::Succeeded;
+        }
+    }
+
+    /// Finish the operation with the result of its last step.
+    fn finish(&mut self, result: Result<()>) {
+        match result {
+            Ok(()) => {
+                self.complete();
+                self.status = OperationStatus::Succeeded;
+            }
+            Err(err) => self.fail(err.to_string()),
+        }
+    }
+
+    fn fail(&mut self, message: String) {
+        for step in self.steps.iter_mut().filter(|step| step.status == OperationStatus::Running) {
+            step.status = OperationStatus::Failed;
+        }
+        self.status = OperationStatus::Failed;
+        self.error = Some(message);
+    }
+
+    fn finished(&self) -> bool {
+        matches!(self.status, OperationStatus::Succeeded | OperationStatus::Failed)
+    }
+}
+
+/// The repository of the operations, which are persisted in a JetStream
+/// key-value bucket, so they survive the restarts of apiserver.
+#[derive(Clone)]
+pub struct OperationRepository {
+    jetstream: jetstream::Context,
+    /// The name of this apiserver instance.
+    instance: String,
+}
+
+impl OperationRepository {
+    pub fn new(jetstream: jetstream::Context, instance: String) -> Self {
+        Self { jetstream, instance }
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Operation>> {
+        let entry = self.store().await?.get(id).await.map_err(|err| ApiError::NatsError(err.into()))?;
+
+        entry.map(|value| serde_json::from_slice(&value).map_err(|err| ApiError::NatsError(err.into()))).transpose()
+    }
+
+    pub async fn save(&self, operation: &mut Operation) -> Result<()> {
+        operation.updated_at = Utc::now();
+        let payload = serde_json::to_vec(operation).map_err(|err| ApiError::NatsError(err.into()))?;
+        self.store().await?.put(&operation.id, payload.into()).await.map_err(|err| ApiError::NatsError(err.into()))?;
+
+        Ok(())
+    }
+
+    /// Fail the operations which were still in progress when their apiserver
+    /// stopped, they will never be finished by anyone. The operations of the
+    /// other instances which are still running are left to them.
+    pub async fn interrupt(&self, client: &Client, namespace: &str) -> Result<()> {
+        let store = self.store().await?;
+        let keys = store.keys().await.map_err(|err| ApiError::NatsError(err.into()))?;
+        let keys: Vec<String> = keys.try_collect().await.map_err(|err| ApiError::NatsError(err.into()))?;
+
+        let pods: Api<Pod> = Api::namespaced(client.clone(), namespace);
+        for key in keys {
+            let Some(mut operation) = self.get(&key).await? else {
+                continue;
+            };
+            if operation.finished() {
+                continue;
+            }
+            // The operations started before they had an owner are orphaned as well.
+            let orphaned = operation.owner == self.instance
+                || operation.owner.is_empty()
+                || pods.get_opt(&operation.owner).await.map_err(ApiError::KubernetesError)?.is_none();
+            if orphaned {
+                operation.fail("Interrupted by the restart of apiserver".into());
+                self.save(&mut operation).await?;
+                info!("Interrupted operation {} of {}", operation.id, operation.kind);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn store(&self) -> Result<kv::Store> {
+        if let Ok(store) = self.jetstream.get_key_value(OPERATIONS_BUCKET).await {
+            return Ok(store);
+        }
+
+        let config =
+            kv::Config { bucket: OPERATIONS_BUCKET.into(), max_age: OPERATIONS_RETENTION, ..Default::default() };
+        self.jetstream.create_key_value(config).await.map_err(|err| ApiError::NatsError(err.into()))
+    }
+}
+
+/// Reports the progress of the running operation.
+#[derive(Clone)]
+pub struct Progress {
+    repository: OperationRepository,
+    operation: Arc<Mutex<Operation>>,
+}
+
+impl Progress {
+    /// Mark the previous step succeeded and start the given one.
+    pub async fn step(&self, name: &str) -> Result<()> {
+        let mut operation = self.operation.lock().await;
+        operation.start(name);
+
+        self.repository.save(&mut operation).await
+    }
+}
+
+pub struct OperationService;
+
+impl OperationService {
+    pub async fn get(ctx: Arc<Context>, id: Uuid) -> Result<Operation> {
+        ctx.operations.get(&id.to_string()).await?.ok_or(ApiError::NotFound)
+    }
+
+    /// Persist the operation and run it in background, returns it immediately.
+    pub async fn spawn<F, Fut>(ctx: Arc<Context>, kind: &str, target: &str, steps: &[&str], run: F) -> Result<Operation>
+    where
+        F: FnOnce(Arc<Context>, Progress) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let mut operation = Operation::new(kind, target, steps, &ctx.operations.instance);
+        ctx.operations.save(&mut operation).await?;
+
+        let progress =
+            Progress { repository: ctx.operations.clone(), operation: Arc::new(Mutex::new(operation.clone())) };
+        tokio::spawn(async move {
+            let result = run(ctx, progress.clone()).await;
+
+            let mut operation = progress.operation.lock().await;
+            operation.finish(result);
+            if let Err(err) = progress.repository.save(&mut operation).await {
+                error!("Failed to save the operation {}: {}", operation.id, err);
+            }
+        });
+
+        Ok(operation)
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use amp_common::resource::{Actor, ActorSpec, CharacterSpec, Playbook, Preface};
+use amp_common::schema::BuildMethod;
+use amp_resolver::preface::load;
+use amp_resources::containers::sidecar;
+use amp_resources::secret::Provider;
+use amp_resources::strategy::{self, Strategy};
+use amp_resources::{actor, cronjob, deployment, envset, helm, image, include, naming, policy, probe, service};
+use amp_resources::{statefulset, vars, volume, LAST_APPLIED_HASH_KEY};
+use k8s_openapi::api::core::v1::Namespace;
+use k8s_openapi::NamespaceResourceScope;
+use kube::core::ObjectMeta;
+use kube::{Api, Client, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::errors::ApiError;
+use crate::services::Result;
+
+/// The change that would be made to an object if the playbook was applied.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Create,
+    Update,
+    Unchanged,
+}
+
+/// A Kubernetes object that would be created or updated by the playbook.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlannedObject {
+    pub kind: String,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub action: Action,
+}
+
+/// The result of a dry-run, nothing of it has been applied to the cluster.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Plan {
+    /// The resolved characters, including all the partners.
+    pub characters: Vec<CharacterSpec>,
+    /// The actors rendered from the characters.
+    pub actors: Vec<ActorSpec>,
+    /// The objects that would be created or updated, compared with the cluster.
+    pub objects: Vec<PlannedObject>,
+}
+
+/// Resolve the preface of playbook with the ones it includes, and render
+/// everything it would create with its variables, without applying anything.
+/// The actors are named and placed by the namespace strategy of playbook, and
+/// the workloads of the existing ones are diffed against the live objects.
+pub async fn plan(
+    client: &Client,
+    config_namespace: &str,
+    secrets: &Provider,
+    playbook: &Playbook,
+    includes: &[Preface],
+    vars: &BTreeMap<String, String>,
+) -> Result<Plan> {
+    let credentials = secrets.read(client, config_namespace).await.map_err(ApiError::ResourceError)?;
+    let credentials = credentials.unwrap_or_default();
+    let policy = policy::load(client, config_namespace).await.map_err(ApiError::ResourceError)?;
+
+    // The starting characters of the included playbooks are merged after the one of playbook.
+    let character = load(client, &credentials, &playbook.spec.preface).await.map_err(ApiError::ResolveError)?;
+    let mut included = vec![];
+    for preface in includes {
+        included.push(load(client, &credentials, preface).await.map_err(ApiError::ResolveError)?);
+    }
+    let characters = include::merge(vec![character], included).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+    let characters = amp_resolver::partners(client, &credentials, characters).await.map_err(ApiError::ResolveError)?;
+    let characters = characters
+        .iter()
+        .map(|character| vars::substitute(character, vars))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(ApiError::ResourceError)?;
+    let actors = characters
+        .iter()
+        .map(|character| amp_resolver::to_actor(character, &credentials, &policy))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(ApiError::ResolveError)?;
+
+    let namespace = amp_resources::namespace::of(playbook);
+    let ns = Some(namespace.as_str());
+    let mut objects = vec![];
+
+    let api: Api<Namespace> = Api::all(client.clone());
+    let exists = api.get_opt(&namespace).await.map_err(ApiError::KubernetesError)?.is_some();
+    objects.push(object("Namespace", None, &namespace, if exists { Action::Unchanged } else { Action::Create }));
+
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+    for spec in &actors {
+        let mut rendered = actor::new(playbook, spec);
+        rendered.metadata.namespace = Some(namespace.clone());
+        let name = rendered.name_any();
+
+        let live = api.get_opt(&name).await.map_err(ApiError::KubernetesError)?;
+        let action = match &live {
+            Some(live) if actor::current(live, &rendered) => Action::Unchanged,
+            Some(_) => Action::Update,
+            None => Action::Create,
+        };
+        debug!("The planned action of actor {} is {:?}", name, action);
+        objects.push(object("Actor", ns, &name, action));
+
+        // The chart and the build are not rendered by the playbook, they follow the actor.
+        if helm::chart(&spec.character).is_some() {
+            objects.push(object("Job", ns, &helm::job_name(&name), action));
+            continue;
+        }
+        if spec.live || spec.source.is_some() {
+            let kind = match spec.character.build.clone().unwrap_or_default().method() {
+                BuildMethod::Dockerfile => "Job",
+                BuildMethod::Buildpacks => "Image",
+            };
+            objects.push(object(kind, ns, &naming::name(&[&name, "builder"]), action));
+        }
+
+        let Some(live) = live else {
+            // Nothing of the new actor exists yet.
+            objects.push(object(workload(&rendered)?, ns, &name, Action::Create));
+            if rendered.spec.has_services() {
+                objects.push(object("Service", ns, &naming::name(&[&name]), Action::Create));
+            }
+            continue;
+        };
+        // The workloads of the existing actor are rendered with the ones
+        // recorded by the controllers, e.g. the built image.
+        let mut annotations = live.annotations().clone();
+        annotations.extend(rendered.annotations().clone());
+        rendered.metadata = ObjectMeta { annotations: Some(annotations), ..live.metadata.clone() };
+        rendered.status = live.status.clone();
+        objects.extend(render(client, config_namespace, &rendered).await?);
+    }
+
+    Ok(Plan { characters, actors, objects })
+}
+
+/// Returns the kind of the workload of actor.
+fn workload(actor: &Actor) -> Result<&'static str> {
+    if cronjob::schedule(actor).map_err(ApiError::ResourceError)?.is_some() {
+        return Ok("CronJob");
+    }
+    match statefulset::stateful(actor) {
+        true => Ok("StatefulSet"),
+        false => Ok("Deployment"),
+    }
+}
+
+/// Render the workload and the Service of the existing actor the way the
+/// controllers do, and diff them against the live ones by their applied hashes.
+async fn render(client: &Client, config_namespace: &str, actor: &Actor) -> Result<Vec<PlannedObject>> {
+    let namespace = actor.namespace().unwrap_or_default();
+    let (env, shared_secrets) =
+        envset::preview(client, config_namespace, actor).await.map_err(ApiError::ResourceError)?;
+    let volumes = volume::volumes(actor).map_err(ApiError::ResourceError)?;
+    let containers = sidecar::containers(actor).map_err(ApiError::ResourceError)?;
+    let probes = probe::probes(actor).map_err(ApiError::ResourceError)?;
+
+    // The ports are inferred from the image only if the character doesn't declare any.
+    let declared = actor.spec.character.deploy.as_ref().and_then(|deploy| deploy.container_ports());
+    let ports = match declared.is_some_and(|ports| !ports.is_empty()) {
+        true => vec![],
+        false => image::inferred_of(actor).unwrap_or_default(),
+    };
+    let (pod, expected_hash) = deployment::render(actor, &ports, env, &shared_secrets, &volumes, &containers, &probes)
+        .map_err(ApiError::ResourceError)?;
+
+    let mut objects = vec![];
+    if let Some(schedule) = cronjob::schedule(actor).map_err(ApiError::ResourceError)? {
+        let expected_hash = amp_resources::hash(&(expected_hash, &schedule)).map_err(ApiError::ResourceError)?;
+        let resource = cronjob::new(actor, &schedule, pod, expected_hash).map_err(ApiError::ResourceError)?;
+        objects.push(diff(client, &namespace, "CronJob", &resource).await?);
+    } else if statefulset::stateful(actor) {
+        let claims = volume::claim_templates(&volumes);
+        let resource = statefulset::new(actor, pod, claims, expected_hash).map_err(ApiError::ResourceError)?;
+        objects.push(diff(client, &namespace, "StatefulSet", &resource).await?);
+    } else {
+        let strategy = strategy::strategy(actor).map_err(ApiError::ResourceError)?;
+        let expected_hash = match &strategy {
+            Some(strategy) => amp_resources::hash(&(expected_hash, strategy)).map_err(ApiError::ResourceError)?,
+            None => expected_hash,
+        };
+        let mut resource = deployment::new(actor, pod, expected_hash).map_err(ApiError::ResourceError)?;
+        // The blue/green actor is served by the Deployment of the active color.
+        if strategy == Some(Strategy::BlueGreen) {
+            resource.metadata.name = Some(strategy::serving(actor));
+        }
+        objects.push(diff(client, &namespace, "Deployment", &resource).await?);
+    }
+    if actor.spec.has_services() {
+        let resource = service::new(actor).map_err(ApiError::ResourceError)?;
+        objects.push(diff(client, &namespace, "Service", &resource).await?);
+    }
+
+    Ok(objects)
+}
+
+/// Compare the rendered object with the live one by the hashes they're applied from.
+async fn diff<K>(client: &Client, namespace: &str, kind: &str, rendered: &K) -> Result<PlannedObject>
+where
+    K: kube::Resource<Scope = NamespaceResourceScope, DynamicType = ()> + Clone + DeserializeOwned + Debug,
+{
+    let api: Api<K> = Api::namespaced(client.clone(), namespace);
+    let name = rendered.name_any();
+    let action = match api.get_opt(&name).await.map_err(ApiError::KubernetesError)? {
+        Some(live)
+            if live.annotations().get(LAST_APPLIED_HASH_KEY) == rendered.annotations().get(LAST_APPLIED_HASH_KEY) =>
+        {
+            Action::Unchanged
+        }
+        Some(_) => Action::Update,
+        None => Action::Create,
+    };
+    debug!("The planned action of {} {} is {:?}", kind, name, action);
+
+    Ok(object(kind, Some(namespace), &name, action))
+}
+
+fn object(kind: &str, namespace: Option<&str>, name: &str, action: Action) -> PlannedObject {
+    PlannedObject { kind: kind.into(), namespace: namespace.map(Into::into), name: name.into(), action }
+}
@@ -12,17 +12,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 
-use amp_common::resource::{Playbook, PlaybookSpec};
-use amp_resources::playbook;
+use amp_common::resource::{CharacterSpec, Playbook, PlaybookSpec, Preface};
+use amp_resources::bundle::{Bundle, Format};
+use amp_resources::containers::{lifecycle, sidecar};
+use amp_resources::error::Error;
+use amp_resources::hibernation::Reason;
+use amp_resources::kpack::reference;
+use amp_resources::uptime::Uptime;
+use amp_resources::{
+    actor, argocd, base, build, canary, cluster, cronjob, debug, detection, devcontainer, envset, export, exposure,
+    hibernation, image, include, job, namespace, naming, network, playbook, probe, quota, reload, rollback, sbom,
+    secret, signing, statefulset, strategy, telemetry, trash, uptime, vars, verification, volume,
+};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::{Client, ResourceExt};
+use tokio::time::{sleep, Instant};
 use uuid::Uuid;
 
 use crate::context::Context;
 use crate::errors::ApiError;
-use crate::requests::playbook::{CreatePlaybookRequest, UpdatePlaybookRequest};
+use crate::requests::playbook::{CreatePlaybookRequest, ImportPlaybookRequest, UpdatePlaybookRequest};
+use crate::services::operation::{Operation, OperationService};
+use crate::services::planner::{self, Plan};
+use crate::services::template::TemplateService;
 use crate::services::Result;
 
+/// How long to wait for the namespaces and actors of the deleted playbooks and actors to be removed.
+pub(crate) const CLEANUP_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
 pub struct PlaybookService;
 
 impl PlaybookService {
@@ -32,45 +53,562 @@ impl PlaybookService {
         Ok(playbook.spec)
     }
 
-    pub async fn list(ctx: Arc<Context>) -> Result<Vec<PlaybookSpec>> {
+    /// List the playbooks, or the deleted ones in the trash instead.
+    pub async fn list(ctx: Arc<Context>, deleted: bool) -> Result<Vec<PlaybookSpec>> {
         let resources = playbook::list(&ctx.k8s).await.map_err(ApiError::ResourceError)?;
+        let resources = resources.iter().filter(|playbook| trash::deleted(playbook).is_some() == deleted);
+
+        Ok(resources.map(|playbook| playbook.spec.clone()).collect())
+    }
+
+    /// Resume the hibernated or archived playbook, its actors are scaled back.
+    pub async fn start(ctx: Arc<Context>, id: Uuid) -> Result<()> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+        if trash::deleted(&playbook).is_some() {
+            return Err(ApiError::BadRequest("The playbook is deleted, restore it first".into()));
+        }
+
+        hibernation::wake(&ctx.k8s, &playbook, Reason::Requested).await.map_err(ApiError::ResourceError)
+    }
+
+    /// Hibernate the playbook, its actors are scaled to zero until it's used again.
+    pub async fn stop(ctx: Arc<Context>, id: Uuid) -> Result<()> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+        if hibernation::hibernated(&playbook) {
+            return Ok(());
+        }
+
+        hibernation::hibernate(&ctx.k8s, &playbook, Reason::Requested).await.map_err(ApiError::ResourceError)
+    }
+
+    /// Record the playbook is used now, and wake it up if it's hibernated.
+    pub async fn touch(ctx: Arc<Context>, id: Uuid) -> Result<()> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+
+        hibernation::touch(&ctx.k8s, &playbook).await.map_err(ApiError::ResourceError)
+    }
+
+    /// Returns the namespace of the playbook, the detached actors without a
+    /// playbook live in the `amp-{id}` namespace.
+    pub async fn namespace(ctx: &Context, id: Uuid) -> Result<String> {
+        match playbook::get(&ctx.k8s, &id.to_string()).await {
+            Ok(playbook) => Ok(namespace::of(&playbook)),
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => Ok(format!("amp-{}", id)),
+            Err(err) => Err(ApiError::ResourceError(err)),
+        }
+    }
+
+    /// Returns true if the namespace of the playbook is dedicated to it, so are
+    /// the `amp-{id}` namespaces of the detached actors.
+    pub async fn dedicated(ctx: &Context, id: Uuid) -> Result<bool> {
+        match playbook::get(&ctx.k8s, &id.to_string()).await {
+            Ok(playbook) => Ok(namespace::dedicated(&playbook)),
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => Ok(true),
+            Err(err) => Err(ApiError::ResourceError(err)),
+        }
+    }
+
+    /// Returns the client of the cluster the actors of playbook are deployed
+    /// to, the detached actors without a playbook live in the control plane.
+    pub async fn client(ctx: &Context, id: Uuid) -> Result<Client> {
+        match playbook::get(&ctx.k8s, &id.to_string()).await {
+            Ok(playbook) => {
+                cluster::client(&ctx.k8s, &ctx.config.namespace, &playbook).await.map_err(ApiError::ResourceError)
+            }
+            Err(Error::KubeError(kube::Error::Api(err))) if err.code == 404 => Ok(ctx.k8s.clone()),
+            Err(err) => Err(ApiError::ResourceError(err)),
+        }
+    }
+
+    /// Move the playbook into the trash, its actors are scaled to zero, and
+    /// it's purged after the retention window unless it's restored.
+    pub async fn delete(ctx: Arc<Context>, id: Uuid) -> Result<DateTime<Utc>> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+
+        trash::delete(&ctx.k8s, &playbook).await.map_err(ApiError::ResourceError)
+    }
+
+    /// Restore the deleted playbook from the trash, its actors are scaled back.
+    pub async fn restore(ctx: Arc<Context>, id: Uuid) -> Result<PlaybookSpec> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+        if trash::deleted(&playbook).is_none() {
+            return Err(ApiError::BadRequest("The playbook is not deleted".into()));
+        }
+        trash::restore(&ctx.k8s, &playbook).await.map_err(ApiError::ResourceError)?;
+
+        Ok(playbook.spec)
+    }
+
+    /// Delete the playbook permanently in background, its namespace and all the
+    /// resources in it are removed by the garbage collector, which may take minutes.
+    /// The existing and shared namespaces are kept, only the actors are removed.
+    pub async fn purge(ctx: Arc<Context>, id: Uuid) -> Result<Operation> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+        let namespace = namespace::of(&playbook);
+        let dedicated = namespace::dedicated(&playbook);
+
+        let steps = match dedicated {
+            true => ["delete_playbook", "delete_namespace"],
+            false => ["delete_playbook", "delete_actors"],
+        };
+        OperationService::spawn(ctx, "delete_playbook", &id.to_string(), &steps, move |ctx, progress| async move {
+            progress.step("delete_playbook").await?;
+            playbook::delete(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+
+            progress.step(steps[1]).await?;
+            let deadline = Instant::now() + CLEANUP_TIMEOUT;
+            loop {
+                let terminating = match dedicated {
+                    true => namespace::exists(&ctx.k8s, &namespace).await,
+                    false => actor::list_of(&ctx.k8s, &playbook).await.map(|actors| !actors.is_empty()),
+                };
+                if !terminating.map_err(ApiError::ResourceError)? {
+                    break;
+                }
+                if Instant::now() > deadline {
+                    return Err(ApiError::Timeout(format!(
+                        "{} of namespace {} is still terminating",
+                        steps[1], namespace
+                    )));
+                }
+                sleep(Duration::from_secs(5)).await;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn plan(ctx: Arc<Context>, id: Uuid) -> Result<Plan> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
 
-        Ok(resources.iter().map(|playbook| playbook.spec.clone()).collect())
+        let includes = include::of(&playbook).map_err(ApiError::ResourceError)?;
+        let vars = vars::of(&playbook).map_err(ApiError::ResourceError)?;
+        planner::plan(&ctx.k8s, &ctx.config.namespace, &ctx.secrets, &playbook, &includes, &vars).await
     }
 
-    pub async fn start(_ctx: Arc<Context>, _id: Uuid) -> Result<()> {
-        unimplemented!()
+    /// Returns the manifests exported for the playbook as a multi-document YAML.
+    pub async fn manifests(ctx: Arc<Context>, id: Uuid) -> Result<String> {
+        let manifests = export::get(&ctx.k8s, &ctx.config.namespace, &id.to_string()).await;
+
+        manifests.map_err(ApiError::ResourceError)?.ok_or(ApiError::NotFound)
+    }
+
+    /// Render the playbook of the create request without creating it.
+    pub async fn dry_run(ctx: Arc<Context>, req: &CreatePlaybookRequest) -> Result<Plan> {
+        let id = Uuid::new_v4().to_string();
+        let spec = PlaybookSpec { id: id.clone(), preface: req.preface.clone(), ..PlaybookSpec::default() };
+        let mut playbook = Playbook::new(&id, spec);
+        if let (Some(strategy), Some(name)) = (&req.namespace_strategy, &req.namespace) {
+            playbook.annotations_mut().insert(namespace::STRATEGY_ANNOTATION.into(), strategy.clone());
+            playbook.annotations_mut().insert(namespace::NAMESPACE_ANNOTATION.into(), name.clone());
+        }
+
+        let includes = Self::includes(ctx.clone(), req).await?;
+        let vars: BTreeMap<_, _> = req.vars.clone().unwrap_or_default().into_iter().collect();
+        planner::plan(&ctx.k8s, &ctx.config.namespace, &ctx.secrets, &playbook, &includes, &vars).await
     }
 
-    pub async fn stop(_ctx: Arc<Context>, _id: Uuid) -> Result<()> {
-        unimplemented!()
+    /// Export the playbook as a portable document, minus its secrets, the
+    /// values of the given names are exported as is.
+    pub async fn export(
+        ctx: Arc<Context>,
+        id: Uuid,
+        format: Option<&str>,
+        public: &[String],
+    ) -> Result<(Format, String)> {
+        let format = Self::format(format)?;
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+        let bundle = Bundle::of(&playbook, public).map_err(ApiError::ResourceError)?;
+
+        Ok((format, bundle.to_string(format).map_err(ApiError::ResourceError)?))
     }
 
-    pub async fn delete(ctx: Arc<Context>, id: Uuid) -> Result<()> {
-        playbook::delete(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+    /// Recreate the playbook from the exported document, along with its characters
+    /// and annotations, the secrets left out of the document are supplied by the request.
+    pub async fn import(ctx: Arc<Context>, req: &ImportPlaybookRequest) -> Result<PlaybookSpec> {
+        let format = Self::format(req.format.as_deref())?;
+        let bundle = Bundle::parse(&req.manifest, format).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+        let secrets = req.secrets.clone().unwrap_or_default();
+        let vars = bundle.vars(&secrets).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+
+        let create = CreatePlaybookRequest {
+            title: bundle.title,
+            description: bundle.description,
+            preface: bundle.preface,
+            vars: Some(vars.into_iter().collect()),
+            ..CreatePlaybookRequest::default()
+        };
+        Self::define(ctx, &create, bundle.characters, bundle.annotations).await
+    }
 
-        Ok(())
+    /// Parse the format of the exported document, the default is YAML.
+    fn format(format: Option<&str>) -> Result<Format> {
+        match format {
+            Some(format) => format.parse::<Format>().map_err(|err| ApiError::BadRequest(err.to_string())),
+            None => Ok(Format::default()),
+        }
     }
 
     pub async fn create(ctx: Arc<Context>, req: &CreatePlaybookRequest) -> Result<PlaybookSpec> {
+        Self::define(ctx, req, vec![], BTreeMap::new()).await
+    }
+
+    /// Create the playbook of the request, the characters are declared up front
+    /// if any, instead of being resolved from the preface. The given annotations
+    /// are applied first, the ones of the request take precedence over them.
+    async fn define(
+        ctx: Arc<Context>,
+        req: &CreatePlaybookRequest,
+        characters: Vec<CharacterSpec>,
+        annotations: BTreeMap<String, String>,
+    ) -> Result<PlaybookSpec> {
         let uuid = Uuid::new_v4();
-        let resource = Playbook::new(
+        let mut resource = Playbook::new(
             &uuid.to_string(),
             PlaybookSpec {
                 id: uuid.to_string(),
                 title: req.title.to_string(),
                 description: req.description.clone(),
                 preface: req.preface.clone(),
+                characters: Some(characters).filter(|characters| !characters.is_empty()),
                 ..PlaybookSpec::default()
             },
         );
+        resource.annotations_mut().extend(annotations);
+        // The idle timeout and the TTL of the playbook count from its creation.
+        let now = Utc::now().to_rfc3339();
+        resource.annotations_mut().insert(playbook::LAST_ACTIVITY_ANNOTATION.into(), now);
+        if let Some(mode) = req.signing.as_ref().or(ctx.config.image_signing.as_ref()) {
+            resource.annotations_mut().insert(signing::SIGNING_ANNOTATION.into(), mode.clone());
+        }
+        if let Some(ttl) = req.ttl {
+            resource.annotations_mut().insert(playbook::TTL_ANNOTATION.into(), ttl.to_string());
+        }
+        if let Some(schedule) = req.schedule.as_ref() {
+            let uptime = Uptime {
+                start: schedule.start.clone(),
+                stop: schedule.stop.clone(),
+                timezone: schedule.timezone.clone(),
+            };
+            uptime.validate().map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            let uptime = serde_json::to_string(&uptime).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(uptime::UPTIME_ANNOTATION.into(), uptime);
+        }
+        if let Some(vars) = req.vars.as_ref().filter(|vars| !vars.is_empty()) {
+            if let Some(name) = vars.keys().find(|name| !vars::valid(name)) {
+                return Err(ApiError::BadRequest(format!("Invalid variable name: {}", name)));
+            }
+            let vars: BTreeMap<_, _> = vars.iter().collect();
+            let vars = serde_json::to_string(&vars).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(vars::VARS_ANNOTATION.into(), vars);
+        }
+        let includes = Self::includes(ctx.clone(), req).await?;
+        if !includes.is_empty() {
+            let includes = serde_json::to_string(&includes).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(include::INCLUDES_ANNOTATION.into(), includes);
+        }
+        if let Some(bases) = req.bases.as_ref().filter(|bases| !bases.is_empty()) {
+            resource.annotations_mut().insert(base::BASES_ANNOTATION.into(), bases.join(","));
+        }
+        if let Some(sets) = req.env_sets.as_ref().filter(|sets| !sets.is_empty()) {
+            resource.annotations_mut().insert(envset::ENV_SETS_ANNOTATION.into(), sets.join(","));
+        }
+        for (character, sets) in req.character_env_sets.iter().flatten() {
+            let key = format!("{}.{}", envset::ENV_SETS_ANNOTATION, character);
+            resource.annotations_mut().insert(key, sets.join(","));
+        }
+        if let Some(network) = &req.network {
+            let network = serde_json::to_string(network).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            let parsed: network::Network =
+                serde_json::from_str(&network).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            parsed.validate().map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(network::NETWORK_ANNOTATION.into(), network);
+        }
+        if let Some(quota) = &req.quota {
+            let quota = serde_json::to_string(quota).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(quota::QUOTA_ANNOTATION.into(), quota);
+        }
+        for (character, volumes) in req.volumes.iter().flatten() {
+            let key = format!("{}.{}", volume::VOLUMES_ANNOTATION, character);
+            let volumes = serde_json::to_string(volumes).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, volumes);
+        }
+        for (character, workload) in req.workloads.iter().flatten() {
+            if !["deployment", "statefulset"].contains(&workload.to_lowercase().as_str()) {
+                return Err(ApiError::BadRequest(format!("Unknown workload {} of {}", workload, character)));
+            }
+            let key = format!("{}.{}", statefulset::WORKLOAD_ANNOTATION, character);
+            resource.annotations_mut().insert(key, workload.clone());
+        }
+        for (character, schedule) in req.schedules.iter().flatten() {
+            let key = format!("{}.{}", cronjob::SCHEDULE_ANNOTATION, character);
+            let schedule = serde_json::to_string(schedule).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, schedule);
+        }
+        for (character, containers) in req.containers.iter().flatten() {
+            let key = format!("{}.{}", sidecar::CONTAINERS_ANNOTATION, character);
+            let containers = serde_json::to_string(containers).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, containers);
+        }
+        for (character, probes) in req.probes.iter().flatten() {
+            let mut declared = [&probes.readiness, &probes.liveness, &probes.startup].into_iter().flatten();
+            if declared
+                .any(|p| [p.http.is_some(), p.tcp.is_some(), p.exec.is_some()].iter().filter(|h| **h).count() != 1)
+            {
+                return Err(ApiError::BadRequest(format!("The probes of {} need exactly one handler", character)));
+            }
+            let key = format!("{}.{}", probe::PROBES_ANNOTATION, character);
+            let probes = serde_json::to_string(probes).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, probes);
+        }
+        for (character, strategy) in req.strategies.iter().flatten() {
+            let key = format!("{}.{}", strategy::STRATEGY_ANNOTATION, character);
+            let strategy = serde_json::to_string(strategy).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, strategy);
+        }
+        for (character, canary) in req.canaries.iter().flatten() {
+            if canary.steps.is_empty() || canary.steps.iter().any(|step| !(1..=100).contains(&step.weight)) {
+                return Err(ApiError::BadRequest(format!("The canary steps of {} need weights in 1..=100", character)));
+            }
+            let key = format!("{}.{}", canary::CANARY_ANNOTATION, character);
+            let canary = serde_json::to_string(canary).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, canary);
+        }
+        for (character, verification) in req.verifications.iter().flatten() {
+            if verification.http.is_empty() && verification.job.is_none() {
+                return Err(ApiError::BadRequest(format!("The verification of {} has no checks", character)));
+            }
+            if verification.http.iter().any(|check| check.path.as_deref().is_some_and(|path| !path.starts_with('/'))) {
+                return Err(ApiError::BadRequest(format!("The verification paths of {} need a leading /", character)));
+            }
+            let key = format!("{}.{}", verification::VERIFICATION_ANNOTATION, character);
+            let verification =
+                serde_json::to_string(verification).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, verification);
+        }
+        for (character, policy) in req.rollbacks.iter().flatten() {
+            if policy.max_restarts.is_some_and(|restarts| restarts < 1) {
+                return Err(ApiError::BadRequest(format!(
+                    "The rollback restarts of {} need to be positive",
+                    character
+                )));
+            }
+            let key = format!("{}.{}", rollback::ROLLBACK_ANNOTATION, character);
+            let policy = serde_json::to_string(policy).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, policy);
+        }
+        for (character, exposure) in req.exposures.iter().flatten() {
+            if exposure.backend.as_deref().is_some_and(|backend| !["none", "ingress", "gateway"].contains(&backend)) {
+                return Err(ApiError::BadRequest(format!("Unknown exposure backend of {}", character)));
+            }
+            let key = format!("{}.{}", exposure::EXPOSURE_ANNOTATION, character);
+            let exposure = serde_json::to_string(exposure).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, exposure);
+        }
+        for (character, resources) in req.builds.iter().flatten() {
+            let tolerations = resources.tolerations.iter().map(|taint| build::toleration(taint));
+            let tolerations = tolerations.collect::<Result<Vec<_>, _>>();
+            let tolerations = tolerations.map_err(|err| ApiError::BadRequest(format!("{} of {}", err, character)))?;
+            let resources = build::BuildResources {
+                cpu_request: resources.cpu_request.clone(),
+                cpu_limit: resources.cpu_limit.clone(),
+                memory_request: resources.memory_request.clone(),
+                memory_limit: resources.memory_limit.clone(),
+                node_selector: resources.node_selector.clone().into_iter().collect(),
+                tolerations,
+                priority_class: resources.priority_class.clone(),
+                priority: resources.priority,
+                timeout: resources.timeout,
+                history_limit: None,
+            };
+            let key = format!("{}.{}", build::BUILD_RESOURCES_ANNOTATION, character);
+            let resources = serde_json::to_string(&resources).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, resources);
+        }
+        for (character, settings) in req.jobs.iter().flatten() {
+            let invalid =
+                settings.ttl_seconds_after_finished.is_some_and(|ttl| ttl < job::MIN_TTL_SECONDS_AFTER_FINISHED)
+                    || settings.backoff_limit.is_some_and(|limit| limit < 0)
+                    || settings.active_deadline_seconds.is_some_and(|deadline| deadline < 1);
+            if invalid {
+                return Err(ApiError::BadRequest(format!("Invalid job settings of {}", character)));
+            }
+            let key = format!("{}.{}", job::JOB_SETTINGS_ANNOTATION, character);
+            let settings = serde_json::to_string(settings).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, settings);
+        }
+        for (character, secrets) in req.image_pull_secrets.iter().flatten() {
+            if secrets.iter().any(|name| name.trim().is_empty() || name.contains(',')) {
+                return Err(ApiError::BadRequest(format!("Invalid image pull secrets of {}", character)));
+            }
+            let key = format!("{}.{}", secret::IMAGE_PULL_SECRETS_ANNOTATION, character);
+            resource.annotations_mut().insert(key, secrets.join(","));
+        }
+        for (character, builder) in req.builders.iter().flatten() {
+            let kind = builder.kind.as_deref().unwrap_or("ClusterBuilder");
+            if !["ClusterBuilder", "Builder"].contains(&kind) || (kind == "Builder" && !builder.buildpacks.is_empty()) {
+                return Err(ApiError::BadRequest(format!("Invalid kpack builder of {}", character)));
+            }
+            let key = format!("{}.{}", reference::BUILDER_ANNOTATION, character);
+            let builder = serde_json::to_string(builder).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, builder);
+        }
+        for (character, run_image) in req.run_images.iter().flatten() {
+            let key = format!("{}.{}", lifecycle::RUN_IMAGE_ANNOTATION, character);
+            resource.annotations_mut().insert(key, run_image.clone());
+        }
+        for (character, method) in req.build_methods.iter().flatten() {
+            if !detection::BUILD_METHODS.contains(&method.as_str()) {
+                return Err(ApiError::BadRequest(format!("Invalid build method {} of {}", method, character)));
+            }
+            let key = format!("{}.{}", detection::BUILD_METHOD_ANNOTATION, character);
+            resource.annotations_mut().insert(key, method.clone());
+        }
+        for (character, format) in req.sboms.iter().flatten() {
+            format.parse::<sbom::Format>().map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            let key = format!("{}.{}", sbom::SBOM_ANNOTATION, character);
+            resource.annotations_mut().insert(key, format.clone());
+        }
+        for (character, debugger) in req.debug.iter().flatten() {
+            // The characters resolved from the preface are only checked once resolved.
+            let declared = resource.spec.characters.as_ref();
+            if declared.is_some_and(|characters| characters.iter().all(|c| &c.meta.name != character)) {
+                return Err(ApiError::BadRequest(format!("Unknown debugged character {}", character)));
+            }
+            if !debug::LANGUAGES.contains(&debugger.language.as_str()) {
+                return Err(ApiError::BadRequest(format!("Unknown debugger language of {}", character)));
+            }
+            if debugger.language != "node" && debugger.command.is_empty() {
+                return Err(ApiError::BadRequest(format!("The debugger command of {} is required", character)));
+            }
+            let key = format!("{}.{}", debug::DEBUG_ANNOTATION, character);
+            let debugger = serde_json::to_string(debugger).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, debugger);
+        }
+        for (character, devcontainer) in req.devcontainers.iter().flatten() {
+            if devcontainer.port.is_some_and(|port| !(1..=65535).contains(&port)) {
+                return Err(ApiError::BadRequest(format!("Invalid SSH port of {}", character)));
+            }
+            if devcontainer.authorized_keys.iter().any(|key| key.trim().is_empty() || key.contains('\n')) {
+                return Err(ApiError::BadRequest(format!("The authorized keys of {} must be single lines", character)));
+            }
+            let key = format!("{}.{}", devcontainer::DEVCONTAINER_ANNOTATION, character);
+            let devcontainer =
+                serde_json::to_string(devcontainer).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, devcontainer);
+        }
+        for (character, hook) in req.reloads.iter().flatten() {
+            if !hook.path.starts_with('/') || hook.path == "/" {
+                return Err(ApiError::BadRequest(format!("The reload path of {} must be absolute", character)));
+            }
+            if hook.restart == !hook.command.is_empty() {
+                let message = format!("The reload hook of {} needs either a command or a restart", character);
+                return Err(ApiError::BadRequest(message));
+            }
+            let key = format!("{}.{}", reload::RELOAD_ANNOTATION, character);
+            let hook = serde_json::to_string(hook).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(key, hook);
+        }
+        for (character, digest) in req.image_digests.iter().flatten() {
+            let key = format!("{}.{}", image::DIGEST_ANNOTATION, character);
+            resource.annotations_mut().insert(key, digest.clone());
+        }
+        for character in req.verify_signatures.iter().flatten() {
+            let key = format!("{}.{}", image::VERIFY_SIGNATURE_ANNOTATION, character);
+            resource.annotations_mut().insert(key, "true".into());
+        }
+        if let Some(name) = req.cluster.as_ref().filter(|name| !name.is_empty()) {
+            let clusters = cluster::list(&ctx.k8s, &ctx.config.namespace).await.map_err(ApiError::ResourceError)?;
+            if !clusters.contains(name) {
+                return Err(ApiError::BadRequest(format!("Cluster {} is not registered", name)));
+            }
+            resource.annotations_mut().insert(cluster::CLUSTER_ANNOTATION.into(), name.clone());
+        }
+        if let Some(strategy) = req.namespace_strategy.as_ref().filter(|strategy| *strategy != "dedicated") {
+            if !namespace::STRATEGIES.contains(&strategy.as_str()) {
+                return Err(ApiError::BadRequest(format!("Invalid namespace strategy {}", strategy)));
+            }
+            let name = req.namespace.as_ref().filter(|name| !name.is_empty());
+            let name = name.ok_or_else(|| ApiError::BadRequest(format!("The {} namespace is required", strategy)))?;
+            if strategy == "existing" {
+                let client = cluster::client(&ctx.k8s, &ctx.config.namespace, &resource)
+                    .await
+                    .map_err(ApiError::ResourceError)?;
+                if !namespace::exists(&client, name).await.map_err(ApiError::ResourceError)? {
+                    return Err(ApiError::BadRequest(format!("Namespace {} was not found", name)));
+                }
+            }
+            resource.annotations_mut().insert(namespace::STRATEGY_ANNOTATION.into(), strategy.clone());
+            resource.annotations_mut().insert(namespace::NAMESPACE_ANNOTATION.into(), name.clone());
+        }
+        if let Some(export) = req.export.as_ref() {
+            if export.repo.as_ref().is_some_and(|repo| !repo.starts_with("https://")) {
+                return Err(ApiError::BadRequest("The export repository must be an HTTPS URL".into()));
+            }
+            let export = serde_json::to_string(export).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(export::EXPORT_ANNOTATION.into(), export);
+        }
+        if let Some(application) = req.argocd.as_ref() {
+            if !req.export.as_ref().is_some_and(|export| export.repo.is_some()) {
+                return Err(ApiError::BadRequest("The export repository is required by ArgoCD".into()));
+            }
+            let application =
+                serde_json::to_string(application).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            resource.annotations_mut().insert(argocd::ARGOCD_ANNOTATION.into(), application);
+        }
+        // Kubernetes rejects the annotation keys of the characters with too long or invalid names.
+        if let Some(key) = resource.annotations().keys().find(|key| !naming::annotation(key)) {
+            let message = format!("Invalid annotation key {}, the character name is too long or invalid", key);
+            return Err(ApiError::BadRequest(message));
+        }
+        // The reconciliations of the playbook and its actors are linked to the trace of this request.
+        if let Some(traceparent) = telemetry::inject() {
+            resource.annotations_mut().insert(telemetry::TRACE_CONTEXT_ANNOTATION.into(), traceparent);
+        }
 
         let playbook = playbook::create(&ctx.k8s, &resource).await.map_err(ApiError::ResourceError)?;
 
         Ok(playbook.spec)
     }
 
-    pub async fn update(_ctx: Arc<Context>, _id: Uuid, _req: &UpdatePlaybookRequest) -> Result<PlaybookSpec> {
-        unimplemented!()
+    /// Returns the prefaces of the playbooks included by the create request,
+    /// the templates are instantiated with their variables.
+    async fn includes(ctx: Arc<Context>, req: &CreatePlaybookRequest) -> Result<Vec<Preface>> {
+        let mut prefaces = vec![];
+        for include in req.includes.iter().flatten() {
+            let preface = match (&include.template, &include.preface) {
+                (Some(id), None) => TemplateService::render(ctx.clone(), *id, &include.variables).await?.preface,
+                (None, Some(preface)) => preface.clone(),
+                _ => return Err(ApiError::BadRequest("Include either a template or a preface".into())),
+            };
+            prefaces.push(preface);
+        }
+
+        Ok(prefaces)
+    }
+
+    /// Update the title, description and variables of the playbook. The
+    /// variables are replaced as a whole and rolled out to the actors
+    /// referencing them, they must define all the referenced ones.
+    pub async fn update(ctx: Arc<Context>, id: Uuid, req: &UpdatePlaybookRequest) -> Result<PlaybookSpec> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+
+        if let Some(vars) = req.vars.as_ref() {
+            if let Some(name) = vars.keys().find(|name| !vars::valid(name)) {
+                return Err(ApiError::BadRequest(format!("Invalid variable name: {}", name)));
+            }
+            let vars: BTreeMap<String, String> = vars.clone().into_iter().collect();
+            for character in playbook.spec.characters.iter().flatten() {
+                vars::substitute(character, &vars).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+            }
+            vars::replace(&ctx.k8s, &playbook, &vars).await.map_err(ApiError::ResourceError)?;
+        }
+
+        let (title, description) = (req.title.as_deref(), req.description.as_deref());
+        let playbook =
+            playbook::describe(&ctx.k8s, &playbook, title, description).await.map_err(ApiError::ResourceError)?;
+
+        Ok(playbook.spec)
     }
 }
@@ -0,0 +1,142 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The number of the tracked principals, the least recently used ones are
+/// evicted beyond it.
+const MAX_PRINCIPALS: usize = 10_000;
+
+/// The token buckets of the API callers, each principal (authenticated token
+/// or client address) may burst up to `burst` requests, and is refilled at
+/// `rate` requests per second.
+#[derive(Clone)]
+pub struct Quotas {
+    rate: f64,
+    burst: f64,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+#[derive(Default)]
+struct Buckets {
+    entries: HashMap<String, Bucket>,
+    recent: BTreeMap<u64, String>, // The principals ordered by their last use.
+    sequence: u64,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant, // The last time the tokens were refilled.
+    used: u64,         // The sequence of the last use, the key in the recent principals.
+}
+
+impl Buckets {
+    /// Returns the bucket of the principal marked as the most recently used,
+    /// the least recently used one is evicted if there are too many.
+    fn touch(&mut self, principal: &str, burst: f64, now: Instant) -> &mut Bucket {
+        self.sequence += 1;
+        let used = self.sequence;
+
+        match self.entries.get_mut(principal) {
+            Some(bucket) => {
+                self.recent.remove(&bucket.used);
+                bucket.used = used;
+            }
+            None => {
+                if self.entries.len() >= MAX_PRINCIPALS {
+                    if let Some((_, evicted)) = self.recent.pop_first() {
+                        self.entries.remove(&evicted);
+                    }
+                }
+                self.entries.insert(principal.to_string(), Bucket { tokens: burst, refilled: now, used });
+            }
+        }
+        self.recent.insert(used, principal.to_string());
+
+        self.entries.get_mut(principal).unwrap()
+    }
+}
+
+impl Quotas {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self { rate, burst: burst.max(1) as f64, buckets: Arc::new(Mutex::new(Buckets::default())) }
+    }
+
+    /// Returns true if the limiting is disabled.
+    pub fn disabled(&self) -> bool {
+        self.rate <= 0.0
+    }
+
+    /// Take a token of the principal, returns how long to wait for the next
+    /// token if there are none left.
+    pub fn acquire(&self, principal: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let bucket = buckets.touch(principal, self.burst, now);
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.refilled = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire() {
+        let quotas = Quotas::new(1.0, 2);
+        assert!(quotas.acquire("ip:10.0.0.1").is_ok());
+        assert!(quotas.acquire("ip:10.0.0.1").is_ok());
+
+        let wait = quotas.acquire("ip:10.0.0.1").unwrap_err();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_secs(1));
+
+        // The buckets are separated by the principals.
+        assert!(quotas.acquire("ip:10.0.0.2").is_ok());
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let quotas = Quotas::new(1.0, 1);
+        for i in 0..MAX_PRINCIPALS {
+            assert!(quotas.acquire(&format!("ip:{}", i)).is_ok());
+        }
+        assert!(quotas.acquire("ip:0").is_err());
+
+        // The principal used least recently is evicted, the size is kept.
+        assert!(quotas.acquire("token:new").is_ok());
+        let buckets = quotas.buckets.lock().unwrap();
+        assert_eq!(buckets.entries.len(), MAX_PRINCIPALS);
+        assert_eq!(buckets.recent.len(), MAX_PRINCIPALS);
+        assert!(!buckets.entries.contains_key("ip:1"));
+        assert!(buckets.entries.contains_key("ip:0"));
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert!(Quotas::new(0.0, 10).disabled());
+        assert!(!Quotas::new(5.0, 10).disabled());
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use amp_common::resource::Actor;
+use futures::stream::{self, BoxStream};
+use futures::{Stream, StreamExt};
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{Pod, Service};
+use k8s_openapi::NamespaceResourceScope;
+use kube::api::ListParams;
+use kube::runtime::watcher::{self, Result as WatchResult};
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::playbook::PlaybookService;
+use crate::services::Result;
+
+/// The label of the Kubernetes objects managed by Amphitheatre.
+const MANAGED_SELECTOR: &str = "app.kubernetes.io/managed-by=Amphitheatre";
+
+/// A Kubernetes object managed by Amphitheatre in the namespace of playbook.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct PlaybookResource {
+    pub kind: String,
+    pub name: String,
+    /// The full object, as returned by the Kubernetes API.
+    #[schema(value_type = Object)]
+    pub object: serde_json::Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceEventType {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A change of the objects in the namespace of playbook.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ResourceEvent {
+    #[serde(rename = "type")]
+    pub type_: ResourceEventType,
+    #[serde(flatten)]
+    pub resource: PlaybookResource,
+}
+
+/// The uid of the object, whether it's deleted, and the object itself.
+type Change = (String, bool, PlaybookResource);
+
+pub struct ResourceService;
+
+impl ResourceService {
+    /// List the objects managed by Amphitheatre in the namespace of playbook.
+    pub async fn list(ctx: Arc<Context>, id: Uuid) -> Result<Vec<PlaybookResource>> {
+        let namespace = PlaybookService::namespace(&ctx, id).await?;
+        let client = PlaybookService::client(&ctx, id).await?;
+
+        let mut resources = list::<Actor>(&client, &namespace, None).await?;
+        resources.extend(list::<Deployment>(&client, &namespace, Some(MANAGED_SELECTOR)).await?);
+        resources.extend(list::<StatefulSet>(&client, &namespace, Some(MANAGED_SELECTOR)).await?);
+        resources.extend(list::<Service>(&client, &namespace, Some(MANAGED_SELECTOR)).await?);
+        resources.extend(list::<Pod>(&client, &namespace, Some(MANAGED_SELECTOR)).await?);
+        resources.extend(list::<Job>(&client, &namespace, Some(MANAGED_SELECTOR)).await?);
+
+        Ok(resources)
+    }
+
+    /// Watch the objects managed by Amphitheatre in the namespace of playbook,
+    /// the existing objects are sent as added first.
+    pub fn watch(client: &Client, namespace: &str) -> impl Stream<Item = WatchResult<ResourceEvent>> {
+        let streams = vec![
+            watch::<Actor>(client, namespace, None),
+            watch::<Deployment>(client, namespace, Some(MANAGED_SELECTOR)),
+            watch::<StatefulSet>(client, namespace, Some(MANAGED_SELECTOR)),
+            watch::<Service>(client, namespace, Some(MANAGED_SELECTOR)),
+            watch::<Pod>(client, namespace, Some(MANAGED_SELECTOR)),
+            watch::<Job>(client, namespace, Some(MANAGED_SELECTOR)),
+        ];
+
+        // Tell the added objects from the modified ones by the seen uids.
+        let mut seen = HashSet::new();
+        stream::select_all(streams).map(move |result| {
+            result.map(|(uid, deleted, resource)| {
+                let type_ = match (deleted, seen.contains(&uid)) {
+                    (true, _) => ResourceEventType::Deleted,
+                    (false, true) => ResourceEventType::Modified,
+                    (false, false) => ResourceEventType::Added,
+                };
+                match deleted {
+                    true => seen.remove(&uid),
+                    false => seen.insert(uid),
+                };
+                ResourceEvent { type_, resource }
+            })
+        })
+    }
+}
+
+async fn list<K>(client: &Client, namespace: &str, selector: Option<&str>) -> Result<Vec<PlaybookResource>>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()> + Clone + DeserializeOwned + Debug + Serialize,
+{
+    let api: Api<K> = Api::namespaced(client.clone(), namespace);
+    let params = selector.map_or_else(ListParams::default, |selector| ListParams::default().labels(selector));
+    let objects = api.list(&params).await.map_err(ApiError::KubernetesError)?;
+
+    Ok(objects.items.iter().map(resource).collect())
+}
+
+/// Watch the objects of the kind, yields their uids, whether they are deleted, and themselves.
+/// The events are lost while the watch is restarted, so the objects re-listed on
+/// a restart are diffed with the known ones, the missing ones are deleted.
+fn watch<K>(client: &Client, namespace: &str, selector: Option<&str>) -> BoxStream<'static, WatchResult<Change>>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + DeserializeOwned
+        + Debug
+        + Serialize
+        + Send
+        + 'static,
+{
+    let api: Api<K> = Api::namespaced(client.clone(), namespace);
+    let config = selector.map_or_else(watcher::Config::default, |selector| watcher::Config::default().labels(selector));
+
+    let mut known: HashMap<String, PlaybookResource> = HashMap::new();
+    watcher(api, config)
+        .flat_map(move |event| {
+            let changes = match event {
+                Ok(watcher::Event::Applied(object)) => vec![change(&object, false)],
+                Ok(watcher::Event::Deleted(object)) => vec![change(&object, true)],
+                Ok(watcher::Event::Restarted(objects)) => {
+                    let listed: HashSet<String> =
+                        objects.iter().map(|object| object.uid().unwrap_or_default()).collect();
+                    let deleted = known.iter().filter(|(uid, _)| !listed.contains(*uid));
+                    let mut changes: Vec<Change> =
+                        deleted.map(|(uid, resource)| (uid.clone(), true, resource.clone())).collect();
+                    changes.extend(objects.iter().map(|object| change(object, false)));
+                    changes
+                }
+                Err(err) => return stream::iter(vec![Err(err)]),
+            };
+            for (uid, deleted, resource) in &changes {
+                match deleted {
+                    true => known.remove(uid),
+                    false => known.insert(uid.clone(), resource.clone()),
+                };
+            }
+            stream::iter(changes.into_iter().map(Ok).collect::<Vec<_>>())
+        })
+        .boxed()
+}
+
+fn change<K>(object: &K, deleted: bool) -> Change
+where
+    K: Resource<DynamicType = ()> + Serialize,
+{
+    (object.uid().unwrap_or_default(), deleted, resource(object))
+}
+
+fn resource<K>(object: &K) -> PlaybookResource
+where
+    K: Resource<DynamicType = ()> + Serialize,
+{
+    PlaybookResource {
+        kind: K::kind(&()).to_string(),
+        name: object.name_any(),
+        object: serde_json::to_value(object).unwrap_or_default(),
+    }
+}
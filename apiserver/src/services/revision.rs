@@ -0,0 +1,69 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_common::resource::CharacterSpec;
+use amp_resources::{playbook, revision};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::operation::{Operation, OperationService};
+use crate::services::Result;
+
+/// A snapshot of the characters of playbook, recorded once its actors are applied.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Revision {
+    pub revision: u64,
+    pub characters: Vec<CharacterSpec>,
+    /// The time the revision was recorded, in RFC 3339 format.
+    pub created_at: String,
+}
+
+impl From<revision::Revision> for Revision {
+    fn from(revision: revision::Revision) -> Self {
+        Revision { revision: revision.revision, characters: revision.characters, created_at: revision.created_at }
+    }
+}
+
+pub struct RevisionService;
+
+impl RevisionService {
+    pub async fn list(ctx: Arc<Context>, id: Uuid) -> Result<Vec<Revision>> {
+        let revisions = revision::list(&ctx.k8s, &ctx.config.namespace, &id.to_string()).await;
+
+        Ok(revisions.map_err(ApiError::ResourceError)?.into_iter().map(Revision::from).collect())
+    }
+
+    /// Roll back the playbook to the revision in background, the actors are
+    /// re-applied by the reconciliation of playbook, which records it as a new
+    /// revision, and the ones of the characters not in the revision are deleted.
+    pub async fn rollback(ctx: Arc<Context>, id: Uuid, rev: u64) -> Result<Operation> {
+        let playbook = playbook::get(&ctx.k8s, &id.to_string()).await.map_err(ApiError::ResourceError)?;
+        let revision = revision::get(&ctx.k8s, &ctx.config.namespace, &id.to_string(), rev).await;
+        let revision = revision.map_err(ApiError::ResourceError)?.ok_or(ApiError::NotFound)?;
+
+        let target = format!("{}/{}", id, rev);
+        OperationService::spawn(ctx, "rollback_playbook", &target, &["rollback"], move |ctx, progress| async move {
+            progress.step("rollback").await?;
+            revision::rollback(&ctx.k8s, &ctx.config.namespace, &playbook, &revision)
+                .await
+                .map_err(ApiError::ResourceError)
+        })
+        .await
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_common::resource::Playbook;
+use amp_resources::trash;
+use kube::api::ListParams;
+use kube::{Api, ResourceExt};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::requests::search::SearchQuery;
+use crate::services::Result;
+
+/// A playbook or an actor matching all the words of the search query.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResult {
+    /// `playbook` or `actor`.
+    pub kind: String,
+    /// The id and the title of playbook, or of the playbook of actor.
+    pub playbook: String,
+    pub title: String,
+    /// The name of actor, if it's an actor.
+    pub actor: Option<String>,
+    /// The fields the words are found in, e.g. `title`, `name` or `repository`.
+    pub matches: Vec<String>,
+    /// The relevance of result, the higher the better.
+    pub score: u32,
+}
+
+/// A searched field, along with its weight in the relevance.
+struct Field {
+    name: &'static str,
+    value: String,
+    weight: u32,
+}
+
+impl Field {
+    fn new(name: &'static str, value: &str, weight: u32) -> Self {
+        Self { name, value: value.to_lowercase(), weight }
+    }
+}
+
+pub struct SearchService;
+
+impl SearchService {
+    /// Search the playbooks and their actors, the most relevant first. The
+    /// playbooks are selected by their labels in the cluster, then matched in
+    /// place, the deleted ones in the trash are left out.
+    pub async fn search(ctx: Arc<Context>, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        let words: Vec<String> = query.q.split_whitespace().map(str::to_lowercase).collect();
+        if words.is_empty() {
+            return Err(ApiError::BadRequest("The search query is empty".into()));
+        }
+
+        let api: Api<Playbook> = Api::all(ctx.k8s.clone());
+        let params = match query.labels.as_deref().filter(|labels| !labels.is_empty()) {
+            Some(selector) => ListParams::default().labels(selector),
+            None => ListParams::default(),
+        };
+        let playbooks = api.list(&params).await.map_err(|err| match err {
+            kube::Error::Api(response) if response.code == 400 => ApiError::BadRequest(response.message),
+            err => ApiError::KubernetesError(err),
+        })?;
+
+        let mut results = vec![];
+        for playbook in playbooks.iter().filter(|playbook| trash::deleted(playbook).is_none()) {
+            let (id, title) = (&playbook.spec.id, &playbook.spec.title);
+            let labels: Vec<String> = playbook.labels().iter().map(|(key, value)| format!("{key}={value}")).collect();
+            let fields = [
+                Field::new("title", title, 3),
+                Field::new("description", playbook.spec.description.as_deref().unwrap_or_default(), 2),
+                Field::new("labels", &labels.join(" "), 1),
+            ];
+            if let Some((score, matches)) = rank(&words, &fields) {
+                results.push(SearchResult {
+                    kind: "playbook".into(),
+                    playbook: id.clone(),
+                    title: title.clone(),
+                    actor: None,
+                    matches,
+                    score,
+                });
+            }
+
+            for character in playbook.spec.characters.iter().flatten() {
+                let fields = [
+                    Field::new("name", &character.meta.name, 3),
+                    Field::new("repository", &character.meta.repository, 2),
+                ];
+                if let Some((score, matches)) = rank(&words, &fields) {
+                    results.push(SearchResult {
+                        kind: "actor".into(),
+                        playbook: id.clone(),
+                        title: title.clone(),
+                        actor: Some(character.meta.name.clone()),
+                        matches,
+                        score,
+                    });
+                }
+            }
+        }
+
+        results.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.title.cmp(&b.title)));
+        results.truncate(query.limit.unwrap_or(50).clamp(1, 200));
+
+        Ok(results)
+    }
+}
+
+/// Returns the relevance and the matched fields if each of the words is found
+/// in any of the fields, the relevance is the sum of the matched weights.
+fn rank(words: &[String], fields: &[Field]) -> Option<(u32, Vec<String>)> {
+    if !words.iter().all(|word| fields.iter().any(|field| field.value.contains(word))) {
+        return None;
+    }
+
+    let matched: Vec<&Field> =
+        fields.iter().filter(|field| words.iter().any(|word| field.value.contains(word))).collect();
+    let score = matched.iter().map(|field| field.weight).sum();
+
+    Some((score, matched.iter().map(|field| field.name.to_string()).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(query: &str) -> Vec<String> {
+        query.split_whitespace().map(str::to_lowercase).collect()
+    }
+
+    fn fields() -> [Field; 2] {
+        [Field::new("name", "Web-Frontend", 3), Field::new("repository", "https://github.com/amp/web", 2)]
+    }
+
+    #[test]
+    fn test_rank_sums_matched_weights() {
+        assert_eq!(rank(&words("web"), &fields()), Some((5, vec!["name".into(), "repository".into()])));
+        assert_eq!(rank(&words("FRONTEND"), &fields()), Some((3, vec!["name".into()])));
+        assert_eq!(rank(&words("github frontend"), &fields()), Some((5, vec!["name".into(), "repository".into()])));
+    }
+
+    #[test]
+    fn test_rank_requires_all_words() {
+        assert_eq!(rank(&words("web backend"), &fields()), None);
+        assert_eq!(rank(&words("api"), &fields()), None);
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::artifact::Kind;
+use amp_resources::upload;
+use serde::Serialize;
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::Result;
+
+/// The uploaded source, reference it by the repository of the character.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Upload {
+    /// The SHA-256 digest of the tarball.
+    pub id: String,
+    /// The repository of the character built from the source, e.g. `upload://{id}`.
+    pub repository: String,
+}
+
+pub struct SourceService;
+
+impl SourceService {
+    /// Store the tarball of the local source in the artifact store, it must
+    /// be an uncompressed tar archive.
+    pub async fn upload(ctx: Arc<Context>, tarball: &[u8]) -> Result<Upload> {
+        if !is_tar(tarball) {
+            return Err(ApiError::BadRequest("The source must be an uncompressed tar archive".into()));
+        }
+
+        let artifact = ctx.artifacts.put(Kind::Sources, tarball.to_vec()).await.map_err(ApiError::ResourceError)?;
+        info!("Uploaded the source {} of {} bytes", artifact.digest, artifact.size);
+
+        Ok(Upload { repository: upload::repository(&artifact.digest), id: artifact.digest })
+    }
+}
+
+/// Returns true if it starts with a POSIX (ustar) tar header.
+fn is_tar(tarball: &[u8]) -> bool {
+    tarball.len() >= 512 && &tarball[257..262] == b"ustar"
+}
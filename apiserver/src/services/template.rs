@@ -0,0 +1,135 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use amp_common::resource::{PlaybookSpec, Preface};
+use amp_resources::template;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::requests::playbook::CreatePlaybookRequest;
+use crate::requests::template::{CreateTemplateRequest, InstantiateTemplateRequest, UpdateTemplateRequest};
+use crate::services::playbook::PlaybookService;
+use crate::services::Result;
+
+/// A parameterized playbook, which can be instantiated to concrete playbooks.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Template {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub variables: Vec<Variable>,
+    pub preface: Preface,
+}
+
+/// A variable of template, it's required if there is no default value.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Variable {
+    pub name: String,
+    pub description: Option<String>,
+    pub default: Option<String>,
+}
+
+pub struct TemplateService;
+
+impl TemplateService {
+    pub async fn get(ctx: Arc<Context>, id: Uuid) -> Result<Template> {
+        let template = template::get(&ctx.k8s, &ctx.config.namespace, &id.to_string()).await;
+
+        template.map_err(ApiError::ResourceError)?.ok_or(ApiError::NotFound)
+    }
+
+    pub async fn list(ctx: Arc<Context>) -> Result<Vec<Template>> {
+        template::list(&ctx.k8s, &ctx.config.namespace).await.map_err(ApiError::ResourceError)
+    }
+
+    pub async fn create(ctx: Arc<Context>, req: &CreateTemplateRequest) -> Result<Template> {
+        let template = Template {
+            id: Uuid::new_v4().to_string(),
+            title: req.title.clone(),
+            description: req.description.clone(),
+            variables: req.variables.clone(),
+            preface: req.preface.clone(),
+        };
+        template::create(&ctx.k8s, &ctx.config.namespace, &template.id, &template)
+            .await
+            .map_err(ApiError::ResourceError)?;
+
+        Ok(template)
+    }
+
+    pub async fn update(ctx: Arc<Context>, id: Uuid, req: &UpdateTemplateRequest) -> Result<Template> {
+        let mut template = Self::get(ctx.clone(), id).await?;
+        if let Some(title) = &req.title {
+            template.title.clone_from(title);
+        }
+        if let Some(description) = &req.description {
+            template.description = Some(description.clone());
+        }
+        if let Some(variables) = &req.variables {
+            template.variables.clone_from(variables);
+        }
+        if let Some(preface) = &req.preface {
+            template.preface = preface.clone();
+        }
+        template::update(&ctx.k8s, &ctx.config.namespace, &template.id, &template)
+            .await
+            .map_err(ApiError::ResourceError)?;
+
+        Ok(template)
+    }
+
+    pub async fn delete(ctx: Arc<Context>, id: Uuid) -> Result<()> {
+        Self::get(ctx.clone(), id).await?;
+
+        template::delete(&ctx.k8s, &ctx.config.namespace, &id.to_string()).await.map_err(ApiError::ResourceError)
+    }
+
+    /// Substitute the variables of the template, and create a playbook with it.
+    pub async fn instantiate(ctx: Arc<Context>, id: Uuid, req: &InstantiateTemplateRequest) -> Result<PlaybookSpec> {
+        let rendered = Self::render(ctx.clone(), id, &req.variables).await?;
+        let req = CreatePlaybookRequest {
+            title: rendered.title,
+            description: rendered.description,
+            preface: rendered.preface,
+            ..CreatePlaybookRequest::default()
+        };
+
+        PlaybookService::create(ctx, &req).await
+    }
+
+    /// Substitute the variables of the template, the defaults are used for the missing ones.
+    /// Only the title, description and preface are rendered, not the variables themselves.
+    pub async fn render(ctx: Arc<Context>, id: Uuid, values: &HashMap<String, String>) -> Result<Template> {
+        let mut template = Self::get(ctx, id).await?;
+
+        let mut variables = HashMap::new();
+        for variable in &template.variables {
+            let value = values.get(&variable.name).or(variable.default.as_ref());
+            let value = value.ok_or_else(|| ApiError::BadRequest(format!("missing variable {}", variable.name)))?;
+            variables.insert(variable.name.clone(), value.clone());
+        }
+
+        let content = (template.title, template.description, template.preface);
+        let rendered = template::render(&content, &variables).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+        (template.title, template.description, template.preface) = rendered;
+
+        Ok(template)
+    }
+}
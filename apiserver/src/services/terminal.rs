@@ -0,0 +1,113 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::anyhow;
+use axum::extract::ws::{Message, WebSocket};
+use futures::SinkExt;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{AttachParams, TerminalSize};
+use kube::Api;
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, error, info};
+
+/// The control messages sent by the client in text frames.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Control {
+    /// The terminal of the client is resized.
+    Resize { width: u16, height: u16 },
+}
+
+/// Bridges the WebSocket connection to a process executed in the actor's container.
+///
+/// The binary frames are the stdin from the client and the stdout/stderr to the
+/// client, the text frames from the client are the JSON encoded [`Control`] messages.
+pub struct Terminal {
+    api: Api<Pod>,        // The Kubernetes API client.
+    actor: String,        // The name of the actor, as well as its container.
+    command: Vec<String>, // The command to execute.
+    tty: bool,            // Whether to allocate a TTY.
+}
+
+impl Terminal {
+    /// Creates a new terminal.
+    pub fn new(client: kube::Client, namespace: &str, actor: String, command: Vec<String>, tty: bool) -> Self {
+        let api: Api<Pod> = Api::namespaced(client, namespace);
+
+        Self { api, actor, command, tty }
+    }
+
+    /// Executes the command in the pod until it exits or the client closes the connection.
+    pub async fn open(self, pod: String, mut socket: WebSocket) {
+        info!("Opened terminal {:?} in {} of actor {}", self.command, pod, self.actor);
+
+        if let Err(err) = self.bridge(&pod, &mut socket).await {
+            error!("Terminal in {} of actor {} failed: {}", pod, self.actor, err);
+        }
+
+        // The socket may have been closed by the client already.
+        let _ = socket.send(Message::Close(None)).await;
+        info!("Closed terminal in {} of actor {}", pod, self.actor);
+    }
+
+    /// Copies the data between the WebSocket and the standard streams of the process.
+    async fn bridge(&self, pod: &str, socket: &mut WebSocket) -> anyhow::Result<()> {
+        let params = AttachParams::default().container(&self.actor).stdin(true).tty(self.tty).stderr(!self.tty);
+        let mut process = self.api.exec(pod, &self.command, &params).await?;
+
+        let mut stdin = process.stdin().ok_or_else(|| anyhow!("stdin is not attached"))?;
+        let mut stdout = process.stdout().ok_or_else(|| anyhow!("stdout is not attached"))?;
+        let mut stderr = process.stderr();
+        let mut resizer = process.terminal_size();
+        let status = process.take_status();
+
+        let (mut out, mut err) = (vec![0u8; 8192], vec![0u8; 8192]);
+        loop {
+            tokio::select! {
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Binary(data))) => stdin.write_all(&data).await?,
+                    Some(Ok(Message::Text(text))) => match serde_json::from_str::<Control>(&text) {
+                        Ok(Control::Resize { width, height }) => {
+                            if let Some(resizer) = resizer.as_mut() {
+                                resizer.send(TerminalSize { width, height }).await?;
+                            }
+                        }
+                        Err(err) => debug!("Ignore the invalid control message {}: {}", text, err),
+                    },
+                    Some(Ok(Message::Close(_))) | None => break,
+                    // The pings are answered by axum automatically.
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Err(err.into()),
+                },
+                read = stdout.read(&mut out) => match read? {
+                    0 => break,
+                    n => socket.send(Message::Binary(out[..n].to_vec())).await?,
+                },
+                read = async { stderr.as_mut().unwrap().read(&mut err).await }, if stderr.is_some() => match read? {
+                    0 => stderr = None,
+                    n => socket.send(Message::Binary(err[..n].to_vec())).await?,
+                },
+            }
+        }
+
+        drop(stdin);
+        process.abort();
+        if let Some(status) = status {
+            debug!("The process in {} exited with {:?}", pod, status.await);
+        }
+
+        Ok(())
+    }
+}
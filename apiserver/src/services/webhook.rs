@@ -0,0 +1,58 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::monorepo::{self, Push};
+use amp_resources::{actor, cluster};
+use kube::ResourceExt;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::context::Context;
+use crate::errors::ApiError;
+use crate::services::Result;
+
+pub struct WebhookService;
+
+impl WebhookService {
+    /// Request to rebuild the actors whose build contexts contain the files
+    /// changed by the push, returns their names in the form of `{namespace}/{name}`.
+    pub async fn push(ctx: Arc<Context>, payload: Value) -> Result<Vec<String>> {
+        let Some(push) = Push::parse(&payload) else {
+            debug!("Ignored the event which is not a push to a branch");
+            return Ok(vec![]);
+        };
+
+        // The actors of the workload clusters are requested in their own clusters.
+        let mut clients = vec![ctx.k8s.clone()];
+        for name in cluster::list(&ctx.k8s, &ctx.config.namespace).await.map_err(ApiError::ResourceError)? {
+            let client = cluster::connect(&ctx.k8s, &ctx.config.namespace, &name).await;
+            clients.push(client.map_err(ApiError::ResourceError)?);
+        }
+
+        let mut rebuilt = vec![];
+        for client in &clients {
+            for resource in actor::list_all(client).await.map_err(ApiError::ResourceError)? {
+                if !monorepo::affected(&push, &resource.spec) {
+                    continue;
+                }
+                monorepo::request(client, &resource, &push.rev).await.map_err(ApiError::ResourceError)?;
+                rebuilt.push(format!("{}/{}", resource.namespace().unwrap_or_default(), resource.name_any()));
+            }
+        }
+
+        Ok(rebuilt)
+    }
+}
@@ -15,33 +15,155 @@
 use amp_common::resource;
 use amp_common::schema;
 
-use utoipa::OpenApi;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::{handlers, requests};
+use crate::{handlers, requests, services};
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        handlers::actor::create,
         handlers::actor::detail,
+        handlers::actor::delete,
         handlers::actor::logs,
         handlers::actor::info,
         handlers::actor::stats,
+        handlers::actor::sync,
+        handlers::actor::trigger,
+        handlers::actor::scale,
+        handlers::actor::usage,
+        handlers::actor::sbom,
+        handlers::actor::builds,
+        handlers::actor::build_logs,
+        handlers::actor::promote,
+        handlers::actor::diff,
+        handlers::actor::forward,
+        handlers::actor::debug,
+        handlers::actor::ssh,
+        handlers::actor::exec,
         //
         handlers::playbook::list,
         handlers::playbook::create,
         handlers::playbook::detail,
         handlers::playbook::update,
         handlers::playbook::delete,
+        handlers::playbook::restore,
         handlers::playbook::start,
         handlers::playbook::stop,
         handlers::playbook::events,
+        handlers::playbook::logs,
+        handlers::playbook::resources,
+        handlers::playbook::metrics,
+        handlers::playbook::cost,
+        handlers::playbook::plan,
+        handlers::playbook::revisions,
+        handlers::playbook::rollback,
+        handlers::playbook::manifests,
+        handlers::playbook::export,
+        handlers::playbook::import,
         handlers::actor::list,
+        //
+        handlers::template::list,
+        handlers::template::create,
+        handlers::template::detail,
+        handlers::template::update,
+        handlers::template::delete,
+        handlers::template::instantiate,
+        //
+        handlers::envset::list,
+        handlers::envset::detail,
+        handlers::envset::apply,
+        handlers::envset::delete,
+        //
+        handlers::operation::detail,
+        //
+        handlers::audit::list,
+        //
+        handlers::search::search,
+        //
+        handlers::webhook::push,
+        //
+        handlers::source::upload,
+        //
+        handlers::artifact::download,
+        //
+        handlers::health::healthz,
+        handlers::health::readyz,
     ),
     components(
         schemas(
+            requests::actor::CreateActorRequest,
+            requests::actor::PromoteActorRequest,
+            requests::actor::ScaleActorRequest,
+            requests::envset::ApplyEnvSetRequest,
             requests::playbook::CreatePlaybookRequest,
+            requests::playbook::ImportPlaybookRequest,
+            requests::playbook::ArgoCd,
+            requests::playbook::BuildResources,
+            requests::playbook::Canary,
+            requests::playbook::CanaryStep,
+            requests::playbook::ContainerMount,
+            requests::playbook::ContainerSpec,
+            requests::playbook::Containers,
+            requests::playbook::Debugger,
+            requests::playbook::DevContainer,
+            requests::playbook::Endpoint,
+            requests::playbook::Export,
+            requests::playbook::Exposure,
+            requests::playbook::ExecProbe,
+            requests::playbook::HttpCheck,
+            requests::playbook::HttpProbe,
+            requests::playbook::Include,
+            requests::playbook::JobSettings,
+            requests::playbook::KpackBuilder,
+            requests::playbook::NamespaceQuota,
+            requests::playbook::Network,
+            requests::playbook::Probe,
+            requests::playbook::Probes,
+            requests::playbook::Reload,
+            requests::playbook::Rollback,
+            requests::playbook::Schedule,
+            requests::playbook::Strategy,
+            requests::playbook::TcpProbe,
+            requests::playbook::UptimeSchedule,
+            requests::playbook::Verification,
+            requests::playbook::VerificationJob,
+            requests::playbook::Volume,
+            requests::playbook::VolumeSource,
             requests::playbook::UpdatePlaybookRequest,
+            requests::template::CreateTemplateRequest,
+            requests::template::UpdateTemplateRequest,
+            requests::template::InstantiateTemplateRequest,
+            //
+            services::actor::ActorBuild,
+            services::actor::ActorDetail,
+            services::actor::ActorPromotion,
+            services::actor::ActorUsage,
+            services::actor::LiveStatus,
+            services::audit::AuditEvent,
+            services::audit::AuditPage,
+            services::cost::ActorCost,
+            services::cost::PlaybookCost,
+            services::envset::EnvSet,
+            services::envset::SecretRef,
+            services::metrics::ActorMetrics,
+            services::metrics::PlaybookMetrics,
+            services::operation::Operation,
+            services::operation::OperationStatus,
+            services::operation::Step,
+            services::planner::Action,
+            services::planner::Plan,
+            services::planner::PlannedObject,
+            services::resource::PlaybookResource,
+            services::resource::ResourceEvent,
+            services::resource::ResourceEventType,
+            services::revision::Revision,
+            services::search::SearchResult,
+            services::source::Upload,
+            services::template::Template,
+            services::template::Variable,
             //
             resource::ActorSpec,
             resource::CharacterSpec,
@@ -65,10 +187,32 @@ use crate::{handlers, requests};
     tags(
         (name = "Actors", description = "The Actors Service Handlers"),
         (name = "Playbooks", description = "The Playbooks Service Handlers"),
+        (name = "Templates", description = "The Templates Service Handlers"),
+        (name = "EnvSets", description = "The Env Sets Service Handlers"),
+        (name = "Operations", description = "The Operations Service Handlers"),
+        (name = "Audit", description = "The Audit Service Handlers"),
+        (name = "Search", description = "The Search Service Handlers"),
+        (name = "Webhooks", description = "The Webhooks of the Git Providers"),
+        (name = "Sources", description = "The Sources Service Handlers"),
+        (name = "Artifacts", description = "The Artifacts Service Handlers"),
+        (name = "Health", description = "The Health Service Handlers"),
     ),
+    modifiers(&SecurityAddon),
 )]
 struct ApiDoc;
 
+/// The bearer token required by the terminal, port forwarding, audit and artifact handlers.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            let scheme = HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build();
+            components.add_security_scheme("token", SecurityScheme::Http(scheme));
+        }
+    }
+}
+
 pub fn build() -> SwaggerUi {
     SwaggerUi::new("/swagger").url("/openapi.json", ApiDoc::openapi())
 }
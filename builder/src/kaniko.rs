@@ -17,7 +17,7 @@ use std::{sync::Arc, time::Duration};
 use crate::{errors::Error, Builder, Result};
 
 use amp_common::resource::Actor;
-use amp_resources::{containers::kaniko, job};
+use amp_resources::{build::BuildResources, containers::kaniko, job, naming};
 
 use async_trait::async_trait;
 use tracing::info;
@@ -26,11 +26,12 @@ use tracing::info;
 pub struct KanikoBuilder {
     k8s: Arc<kube::Client>,
     actor: Arc<Actor>,
+    resources: BuildResources,
 }
 
 impl KanikoBuilder {
-    pub fn new(k8s: Arc<kube::Client>, actor: Arc<Actor>) -> Self {
-        Self { k8s, actor }
+    pub fn new(k8s: Arc<kube::Client>, actor: Arc<Actor>, resources: BuildResources) -> Self {
+        Self { k8s, actor, resources }
     }
 }
 
@@ -42,8 +43,13 @@ impl Builder for KanikoBuilder {
     }
 
     async fn build(&self) -> Result<()> {
-        let name = format!("{}-builder", &self.actor.spec.name);
-        let pod = kaniko::pod(&self.actor).map_err(Error::ResourceError)?;
+        // The Job of the completed build may have been deleted after its TTL.
+        if job::built(&self.actor).map_err(Error::ResourceError)? {
+            return Ok(());
+        }
+
+        let name = naming::name(&[&self.actor.spec.name, "builder"]);
+        let pod = kaniko::pod(&self.actor, &self.resources).map_err(Error::ResourceError)?;
 
         // Build or update the build job
         match job::exists(&self.k8s, &self.actor).await.map_err(Error::ResourceError)? {
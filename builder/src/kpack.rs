@@ -18,12 +18,14 @@ use crate::{errors::Error, Builder, Result};
 
 use amp_common::{config::Credentials, resource::Actor};
 use amp_resources::{
+    build::BuildResources,
     kpack::{
-        cluster_builder, cluster_buildpack, cluster_store, encode_name, image, syncer,
+        builder_tag, cluster_builder, cluster_buildpack, cluster_store, encode_name, image,
+        reference::{self, BuilderRef},
+        syncer,
         types::{find_top_level_buildpacks, Buildpack, Group, Order},
-        BuildExt,
     },
-    volume,
+    naming, volume,
 };
 
 use async_trait::async_trait;
@@ -35,11 +37,26 @@ pub struct KpackBuilder {
     k8s: Arc<kube::Client>,
     credentials: Arc<RwLock<Credentials>>,
     actor: Arc<Actor>,
+    resources: BuildResources,
+    /// The builder referenced by the actor or the operator, the ClusterBuilder
+    /// of the character is managed if it's not set.
+    builder: Option<BuilderRef>,
 }
 
 impl KpackBuilder {
-    pub fn new(k8s: Arc<kube::Client>, actor: Arc<Actor>, credentials: Arc<RwLock<Credentials>>) -> Self {
-        Self { k8s, credentials, actor }
+    pub fn new(
+        k8s: Arc<kube::Client>,
+        actor: Arc<Actor>,
+        credentials: Arc<RwLock<Credentials>>,
+        resources: BuildResources,
+        builder: Option<BuilderRef>,
+    ) -> Self {
+        Self { k8s, credentials, actor, resources, builder }
+    }
+
+    /// Returns the builder to build with.
+    fn builder(&self) -> BuilderRef {
+        self.builder.clone().unwrap_or_else(|| reference::character(&self.actor.spec.character))
     }
 }
 
@@ -54,6 +71,15 @@ impl Builder for KpackBuilder {
             return Ok(Some(duration));
         }
 
+        // The referenced builder is not managed, wait for it to be ready only.
+        if let Some(builder) = self.builder.as_ref().filter(|builder| !builder.managed()) {
+            if !reference::ready(&self.k8s, &self.actor, builder).await.map_err(Error::ResourceError)? {
+                info!("The {} {} is not ready yet, wait for it", builder.kind, builder.name);
+                return Ok(Some(Duration::from_secs(5)));
+            }
+            return Ok(None);
+        }
+
         // Check if the buildpacks are ready
         if let Some(duration) = self.try_init_buildpack().await.map_err(Error::ResourceError)? {
             return Ok(Some(duration));
@@ -69,16 +95,20 @@ impl Builder for KpackBuilder {
 
     async fn build(&self) -> Result<()> {
         // Build or update the Image
-        let name = format!("{}-builder", &self.actor.spec.name);
+        let name = naming::name(&[&self.actor.spec.name, "builder"]);
         match image::exists(&self.k8s, &self.actor).await.map_err(Error::ResourceError)? {
             true => {
                 // Image already exists, update it if there are new changes
                 info!("Try to refresh an existing Image {}", name);
-                image::update(&self.k8s, &self.actor).await.map_err(Error::ResourceError)?;
+                image::update(&self.k8s, &self.actor, &self.builder(), &self.resources)
+                    .await
+                    .map_err(Error::ResourceError)?;
             }
             false => {
                 info!("Create new Image: {}", name);
-                image::create(&self.k8s, &self.actor).await.map_err(Error::ResourceError)?;
+                image::create(&self.k8s, &self.actor, &self.builder(), &self.resources)
+                    .await
+                    .map_err(Error::ResourceError)?;
             }
         }
 
@@ -105,12 +135,7 @@ impl KpackBuilder {
     }
 
     async fn try_init_buildpack(&self) -> Result<Option<Duration>, amp_resources::error::Error> {
-        let buildpacks = self.actor.spec.character.buildpacks();
-        if buildpacks.is_none() {
-            return Ok(None);
-        }
-
-        for buildpack in buildpacks.unwrap() {
+        for buildpack in &self.builder().buildpacks {
             if !cluster_buildpack::exists(&self.k8s, buildpack).await? {
                 cluster_buildpack::create(&self.k8s, buildpack).await?;
             }
@@ -124,7 +149,8 @@ impl KpackBuilder {
     }
 
     async fn try_init_builder(&self) -> Result<Option<Duration>, amp_resources::error::Error> {
-        if !cluster_builder::exists(&self.k8s, &self.actor).await? {
+        let builder = self.builder();
+        if !cluster_builder::exists(&self.k8s, &builder.name).await? {
             if !cluster_store::exists(&self.k8s, &self.actor).await? {
                 cluster_store::create(&self.k8s, &self.actor).await?;
             }
@@ -141,29 +167,28 @@ impl KpackBuilder {
                 order = find_top_level_buildpacks(&buildpacks);
             }
 
-            if let Some(buildpacks) = self.actor.spec.character.buildpacks() {
-                order.append(
-                    &mut buildpacks
-                        .iter()
-                        .map(|item| Order {
-                            group: vec![Group {
-                                name: Some(encode_name(item)),
-                                kind: Some("ClusterBuildpack".to_string()),
-                                ..Default::default()
-                            }],
-                        })
-                        .collect(),
-                );
-            }
+            order.append(
+                &mut builder
+                    .buildpacks
+                    .iter()
+                    .map(|item| Order {
+                        group: vec![Group {
+                            name: Some(encode_name(item)),
+                            kind: Some("ClusterBuildpack".to_string()),
+                            ..Default::default()
+                        }],
+                    })
+                    .collect(),
+            );
 
             debug!("The ClusterBuilder order: {:?}", order);
 
             let credentials = self.credentials.read().await;
-            let tag = self.actor.spec.character.builder_tag(&credentials)?;
-            cluster_builder::create(&self.k8s, &self.actor, &tag, order).await?;
+            let tag = builder_tag(&credentials, &builder.name)?;
+            cluster_builder::create(&self.k8s, &self.actor, &builder.name, &tag, order).await?;
         }
 
-        if !cluster_builder::ready(&self.k8s, &self.actor).await? {
+        if !cluster_builder::ready(&self.k8s, &builder.name).await? {
             return Ok(Some(Duration::from_secs(5))); // wait for the ClusterBuilder to be ready
         }
 
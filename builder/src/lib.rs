@@ -23,10 +23,14 @@ pub use kaniko::KanikoBuilder;
 mod kpack;
 pub use kpack::KpackBuilder;
 
+mod nixpacks;
+pub use nixpacks::NixpacksBuilder;
+
 pub mod errors;
 use errors::Result;
 
 use async_trait::async_trait;
+use tracing::instrument;
 
 /// Builder trait
 #[async_trait]
@@ -53,16 +57,19 @@ impl BuildDirector {
     }
 
     /// Prepare the build
+    #[instrument(name = "build.prepare", skip_all)]
     pub async fn prepare(&self) -> Result<Option<Duration>> {
         self.builder.prepare().await
     }
 
     /// Execute the build logic
+    #[instrument(name = "build.build", skip_all)]
     pub async fn build(&self) -> Result<()> {
         self.builder.build().await
     }
 
     /// Check if the build is completed
+    #[instrument(name = "build.completed", skip_all)]
     pub async fn completed(&self) -> Result<bool> {
         self.builder.completed().await
     }
@@ -76,6 +83,7 @@ mod tests {
     };
 
     use super::*;
+    use amp_resources::build::BuildResources;
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
@@ -86,7 +94,7 @@ mod tests {
         if let Ok(k8s) = k8s {
             let k8s = Arc::new(k8s);
             let actor = Arc::new(Actor::new("test", ActorSpec::default()));
-            let builder = LifecycleBuilder::new(k8s, actor);
+            let builder = LifecycleBuilder::new(k8s, actor, BuildResources::default());
             let _ = BuildDirector::new(Box::new(builder));
         }
     }
@@ -98,7 +106,19 @@ mod tests {
         if let Ok(k8s) = k8s {
             let k8s = Arc::new(k8s);
             let actor = Arc::new(Actor::new("test", ActorSpec::default()));
-            let builder = KanikoBuilder::new(k8s, actor);
+            let builder = KanikoBuilder::new(k8s, actor, BuildResources::default());
+            let _ = BuildDirector::new(Box::new(builder));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_director_nixpacks() {
+        // only run this test in k8s environment
+        let k8s = kube::Client::try_default().await;
+        if let Ok(k8s) = k8s {
+            let k8s = Arc::new(k8s);
+            let actor = Arc::new(Actor::new("test", ActorSpec::default()));
+            let builder = NixpacksBuilder::new(k8s, actor, BuildResources::default());
             let _ = BuildDirector::new(Box::new(builder));
         }
     }
@@ -110,7 +130,13 @@ mod tests {
         if let Ok(k8s) = k8s {
             let k8s = Arc::new(k8s);
             let actor = Arc::new(Actor::new("test", ActorSpec::default()));
-            let builder = KpackBuilder::new(k8s, actor, Arc::new(RwLock::new(Credentials::default())));
+            let builder = KpackBuilder::new(
+                k8s,
+                actor,
+                Arc::new(RwLock::new(Credentials::default())),
+                BuildResources::default(),
+                None,
+            );
             let _ = BuildDirector::new(Box::new(builder));
         }
     }
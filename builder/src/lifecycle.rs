@@ -17,7 +17,11 @@ use std::{sync::Arc, time::Duration};
 use crate::{errors::Error, Builder, Result};
 
 use amp_common::resource::Actor;
-use amp_resources::{containers::lifecycle, job};
+use amp_resources::{
+    build::{self, BuildResources},
+    containers::lifecycle,
+    job, naming,
+};
 
 use async_trait::async_trait;
 use tracing::info;
@@ -26,11 +30,12 @@ use tracing::info;
 pub struct LifecycleBuilder {
     k8s: Arc<kube::Client>,
     actor: Arc<Actor>,
+    resources: BuildResources,
 }
 
 impl LifecycleBuilder {
-    pub fn new(k8s: Arc<kube::Client>, actor: Arc<Actor>) -> Self {
-        Self { k8s, actor }
+    pub fn new(k8s: Arc<kube::Client>, actor: Arc<Actor>, resources: BuildResources) -> Self {
+        Self { k8s, actor, resources }
     }
 }
 
@@ -38,12 +43,20 @@ impl LifecycleBuilder {
 impl Builder for LifecycleBuilder {
     // initialize the some resources before building
     async fn prepare(&self) -> Result<Option<Duration>> {
+        // Apply the order of the explicit buildpacks mounted into the build pod
+        build::apply_order(&self.k8s, &self.actor).await.map_err(Error::ResourceError)?;
+
         Ok(None) // No need to wait
     }
 
     async fn build(&self) -> Result<()> {
-        let name = format!("{}-builder", &self.actor.spec.name);
-        let pod = lifecycle::pod(&self.actor).map_err(Error::ResourceError)?;
+        // The Job of the completed build may have been deleted after its TTL.
+        if job::built(&self.actor).map_err(Error::ResourceError)? {
+            return Ok(());
+        }
+
+        let name = naming::name(&[&self.actor.spec.name, "builder"]);
+        let pod = lifecycle::pod(&self.actor, &self.resources).map_err(Error::ResourceError)?;
 
         // Build or update the build job
         match job::exists(&self.k8s, &self.actor).await.map_err(Error::ResourceError)? {
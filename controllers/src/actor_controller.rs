@@ -16,14 +16,15 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use amp_common::resource::Actor;
+use amp_resources::{cache, telemetry};
 use amp_workflow::Workflow;
 use futures::{future, StreamExt};
 use kube::api::ListParams;
 use kube::runtime::controller::Action;
 use kube::runtime::finalizer::{finalizer, Event};
-use kube::runtime::{watcher, Controller};
-use kube::{Api, ResourceExt};
-use tracing::{error, info};
+use kube::runtime::Controller;
+use kube::{Api, Resource, ResourceExt};
+use tracing::{error, info, instrument, Span};
 
 use crate::context::Context;
 use crate::errors::{Error, Result};
@@ -40,14 +41,18 @@ pub async fn new(ctx: &Arc<Context>) {
         std::process::exit(1);
     }
 
-    Controller::new(api, watcher::Config::default())
-        .run(reconcile, error_policy, ctx.clone())
-        .for_each(|_| future::ready(()))
-        .await
+    // Share the cached actors with the resource helpers.
+    let controller = Controller::new(api, ctx.config.watcher()).with_config(ctx.config.controller());
+    cache::install_actors(controller.store());
+
+    controller.run(reconcile, error_policy, ctx.clone()).for_each(|_| future::ready(())).await
 }
 
 /// The reconciler that will be called when either object change
+#[instrument(skip_all, fields(actor = %actor.name_any(), namespace = %actor.namespace().unwrap_or_default()))]
 pub async fn reconcile(actor: Arc<Actor>, ctx: Arc<Context>) -> Result<Action> {
+    telemetry::link(&Span::current(), actor.as_ref());
+
     let ns = actor.namespace().unwrap(); // actor is namespace scoped
     let api: Api<Actor> = Api::namespaced(ctx.k8s.clone(), &ns);
 
@@ -56,13 +61,28 @@ pub async fn reconcile(actor: Arc<Actor>, ctx: Arc<Context>) -> Result<Action> {
             k8s: Arc::new(ctx.k8s.clone()),
             jetstream: ctx.jetstream.clone(),
             credentials: ctx.credentials.clone(),
+            policy: ctx.policy.clone(),
+            namespace: ctx.config.namespace.clone(),
+            quota: ctx.config.quota(),
+            exposure: ctx.exposure.clone(),
+            build: ctx.build.clone(),
+            build_queue: ctx.build_queue.clone(),
+            kpack_builder: ctx.kpack_builder.clone(),
+            requeue: ctx.config.requeue_interval(),
+            images: ctx.images.clone(),
+            artifacts: ctx.artifacts.clone(),
+            retention: ctx.retention.clone(),
             object: actor.clone(),
         },
         Box::new(amp_workflow::actor::InitialState),
     );
 
+    // User-initiated changes jump ahead of the periodic resyncs.
+    let _permit = ctx.actor_scheduler.admit(actor.as_ref()).await;
+
     // Reconcile the actor custom resource.
-    finalizer(&api, FINALIZER_NAME, actor, |event| async {
+    let object = actor.clone();
+    let action = finalizer(&api, FINALIZER_NAME, actor, |event| async {
         match event {
             Event::Apply(actor) => {
                 info!("Apply actor {}", actor.name_any());
@@ -79,12 +99,20 @@ pub async fn reconcile(actor: Arc<Actor>, ctx: Arc<Context>) -> Result<Action> {
         workflow.run().await.map_err(Error::WorkflowError)
     })
     .await
-    .map_err(|e| Error::FinalizerError(Box::new(e)))
+    .map_err(|e| Error::FinalizerError(Box::new(e)))?;
+
+    if object.meta().deletion_timestamp.is_some() {
+        ctx.actor_scheduler.forget(object.as_ref());
+    } else {
+        ctx.actor_scheduler.reconciled(object.as_ref());
+    }
+
+    Ok(action)
 }
 
 /// an error handler that will be called when the reconciler fails with access to both the
 /// object that caused the failure and the actual error
-pub fn error_policy(_: Arc<Actor>, error: &Error, _ctx: Arc<Context>) -> Action {
+pub fn error_policy(_: Arc<Actor>, error: &Error, ctx: Arc<Context>) -> Action {
     error!("reconcile failed: {:?}", error);
-    Action::requeue(Duration::from_secs(60))
+    Action::requeue(Duration::from_secs(ctx.config.error_requeue_interval))
 }
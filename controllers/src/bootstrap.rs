@@ -0,0 +1,68 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use amp_common::resource::{Actor, Character, Playbook};
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::api::{Patch, PatchParams};
+use kube::runtime::wait::{await_condition, conditions};
+use kube::{Api, CustomResourceExt, ResourceExt};
+use tracing::{debug, info};
+
+use crate::context::Context;
+
+/// How long to wait for the applied CRDs to be established.
+const ESTABLISH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Install the missing CRDs and upgrade the outdated ones, then wait for
+/// them to be established, so the controllers can watch them.
+pub async fn run(ctx: &Arc<Context>) -> anyhow::Result<()> {
+    let api: Api<CustomResourceDefinition> = Api::all(ctx.k8s.clone());
+
+    for crd in [Playbook::crd(), Actor::crd(), Character::crd()] {
+        let name = crd.name_any();
+        let installed = api.get_opt(&name).await?;
+        if installed.as_ref().is_some_and(|installed| current(installed, &crd)) {
+            debug!("The CRD {} is up to date", name);
+        } else {
+            let params = PatchParams::apply("amp-controllers").force();
+            api.patch(&name, &params, &Patch::Apply(&crd)).await?;
+            match installed {
+                Some(_) => info!("Upgraded the CRD {}", name),
+                None => info!("Installed the CRD {}", name),
+            }
+        }
+
+        let established = await_condition(api.clone(), &name, conditions::is_crd_established());
+        tokio::time::timeout(ESTABLISH_TIMEOUT, established)
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out waiting for the CRD {} to be established", name))??;
+    }
+
+    Ok(())
+}
+
+/// Returns true if the installed CRD serves the same versions and schemas as the desired one.
+fn current(installed: &CustomResourceDefinition, desired: &CustomResourceDefinition) -> bool {
+    let versions = |crd: &CustomResourceDefinition| -> Vec<_> {
+        let versions = crd.spec.versions.iter();
+        versions
+            .map(|version| (version.name.clone(), version.served, version.storage, version.schema.clone()))
+            .collect()
+    };
+
+    versions(installed) == versions(desired)
+}
@@ -0,0 +1,46 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::cache;
+use futures::{future, StreamExt};
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::batch::v1::Job;
+use kube::runtime::{reflector, watcher, WatchStreamExt};
+use kube::Api;
+
+use crate::context::Context;
+
+/// Only the objects managed by Amphitheatre are cached.
+const MANAGED_LABEL: &str = "app.kubernetes.io/managed-by=Amphitheatre";
+
+/// Reflect the jobs and deployments of the actors into the stores shared with
+/// the resource helpers, so they don't ask the API server on every reconcile.
+pub async fn new(ctx: &Arc<Context>) {
+    let config = watcher::Config::default().labels(MANAGED_LABEL);
+
+    let (reader, writer) = reflector::store();
+    cache::install_jobs(reader);
+    let jobs = reflector(writer, watcher(Api::<Job>::all(ctx.k8s.clone()), config.clone()));
+
+    let (reader, writer) = reflector::store();
+    cache::install_deployments(reader);
+    let deployments = reflector(writer, watcher(Api::<Deployment>::all(ctx.k8s.clone()), config));
+
+    tokio::join!(
+        jobs.applied_objects().for_each(|_| future::ready(())),
+        deployments.applied_objects().for_each(|_| future::ready(())),
+    );
+}
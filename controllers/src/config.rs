@@ -12,6 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use amp_resources::artifact::{Lifecycle, Store};
+use amp_resources::build::{self, BuildResources};
+use amp_resources::exposure::{Backend, Exposure};
+use amp_resources::job::JobSettings;
+use amp_resources::kpack::reference::BuilderRef;
+use amp_resources::quota::Quota;
+use amp_resources::retention::Retention;
+use amp_resources::secret::{ExternalSecret, Provider, Vault};
+use kube::runtime::{controller, watcher};
+
 /// The configuration parameters for the application.
 ///
 /// These can either be passed on the command line, or pulled from environment variables.
@@ -49,4 +62,456 @@ pub struct Config {
     /// Persistent Volume access mode, the default is `ReadWriteOnce`.
     #[clap(long, env = "AMP_PV_ACCESS_MODE", default_value = "ReadWriteOnce")]
     pub pv_access_mode: String,
+
+    /// The maximum number of concurrent background reconciliations (periodic
+    /// resyncs and status-only changes) per controller, the default is `4`.
+    #[clap(long, env = "AMP_BACKGROUND_RECONCILE_CONCURRENCY", default_value = "4")]
+    pub background_reconcile_concurrency: usize,
+
+    /// The maximum number of concurrent reconciliations per controller,
+    /// `0` is unlimited, the default is `0`.
+    #[clap(long, env = "AMP_RECONCILE_CONCURRENCY", default_value = "0")]
+    pub reconcile_concurrency: u16,
+
+    /// Reconcile the playbooks and actors again after this many seconds even
+    /// if they are not changed, `0` waits for the changes, the default is `0`.
+    #[clap(long, env = "AMP_REQUEUE_INTERVAL", default_value = "0")]
+    pub requeue_interval: u64,
+
+    /// Retry the failed reconciliations after this many seconds, the default is `60`.
+    #[clap(long, env = "AMP_ERROR_REQUEUE_INTERVAL", default_value = "60")]
+    pub error_requeue_interval: u64,
+
+    /// Install the missing CRDs and upgrade the outdated ones on startup,
+    /// disable it if they are managed elsewhere, the default is `true`.
+    #[clap(long, env = "AMP_INSTALL_CRDS", default_value = "true", action = clap::ArgAction::Set)]
+    pub install_crds: bool,
+
+    /// The port of the `/healthz` and `/readyz` endpoints, the default is `8080`.
+    #[clap(long, env = "AMP_HEALTH_PORT", default_value = "8080")]
+    pub health_port: u16,
+
+    /// Only watch the playbooks and actors matching the label selector,
+    /// e.g. `amphitheatre.app/shard=a`, all of them are watched if it's not set.
+    #[clap(long, env = "AMP_WATCH_LABEL_SELECTOR")]
+    pub watch_label_selector: Option<String>,
+
+    /// How long in seconds before the TTL elapses to warn that the playbook
+    /// is expiring, the default is `3600`.
+    #[clap(long, env = "AMP_TTL_WARNING_BEFORE", default_value = "3600")]
+    pub ttl_warning_before: i64,
+
+    /// How long in seconds the deleted playbooks are kept in the trash before
+    /// they are purged, they can be restored in between, the default is 7 days.
+    #[clap(long, env = "AMP_TRASH_RETENTION", default_value = "604800")]
+    pub trash_retention: i64,
+
+    /// The URL of Prometheus the requests served by the ingresses of the
+    /// playbooks are queried from, e.g. `http://prometheus.monitoring.svc:9090`,
+    /// they're recorded as the activities of the playbooks if it's set.
+    #[clap(long, env = "AMP_PROMETHEUS_URL")]
+    pub prometheus_url: Option<String>,
+
+    /// How long in days the audit events are retained, it should be the
+    /// same as the apiserver's, the default is `90`.
+    #[clap(long, env = "AMP_AUDIT_RETENTION_DAYS", default_value = "90")]
+    pub audit_retention_days: u64,
+
+    /// The total cpu of the pods in a playbook namespace, e.g. `8`,
+    /// unlimited if it's not set.
+    #[clap(long, env = "AMP_QUOTA_CPU")]
+    pub quota_cpu: Option<String>,
+
+    /// The total memory of the pods in a playbook namespace, e.g. `16Gi`,
+    /// unlimited if it's not set.
+    #[clap(long, env = "AMP_QUOTA_MEMORY")]
+    pub quota_memory: Option<String>,
+
+    /// The maximum number of pods in a playbook namespace,
+    /// unlimited if it's not set.
+    #[clap(long, env = "AMP_QUOTA_PODS")]
+    pub quota_pods: Option<String>,
+
+    /// The total storage of the volumes in a playbook namespace, e.g. `50Gi`,
+    /// unlimited if it's not set.
+    #[clap(long, env = "AMP_QUOTA_STORAGE")]
+    pub quota_storage: Option<String>,
+
+    /// The cpu limit of the containers without their own, e.g. `500m`,
+    /// required if `AMP_QUOTA_CPU` is set.
+    #[clap(long, env = "AMP_DEFAULT_CPU_LIMIT")]
+    pub default_cpu_limit: Option<String>,
+
+    /// The memory limit of the containers without their own, e.g. `512Mi`,
+    /// required if `AMP_QUOTA_MEMORY` is set.
+    #[clap(long, env = "AMP_DEFAULT_MEMORY_LIMIT")]
+    pub default_memory_limit: Option<String>,
+
+    /// The provider of the registry and repository credentials, `kubernetes`,
+    /// `vault` or `external-secrets`, the default is `kubernetes`.
+    #[clap(long, env = "AMP_SECRETS_PROVIDER", default_value = "kubernetes")]
+    pub secrets_provider: String,
+
+    /// How often in seconds the credentials are refreshed from the provider,
+    /// the default is `300`.
+    #[clap(long, env = "AMP_SECRETS_REFRESH_INTERVAL", default_value = "300")]
+    pub secrets_refresh_interval: u64,
+
+    /// How often in seconds the tokens of the cloud registries (ECR, GCR and
+    /// ACR) are minted again, `0` disables the refreshing, the default is `1800`
+    /// as the GCR tokens expire in an hour.
+    #[clap(long, env = "AMP_REGISTRY_TOKEN_REFRESH_INTERVAL", default_value = "1800")]
+    pub registry_token_refresh_interval: u64,
+
+    /// The address of Vault, required by the `vault` provider.
+    #[clap(long, env = "AMP_VAULT_ADDR")]
+    pub vault_addr: Option<String>,
+
+    /// The role of the Vault Kubernetes auth method, the default is `amp-controllers`.
+    #[clap(long, env = "AMP_VAULT_ROLE", default_value = "amp-controllers")]
+    pub vault_role: String,
+
+    /// The path of the Vault secret holding the credentials,
+    /// the default is `secret/data/amphitheatre`.
+    #[clap(long, env = "AMP_VAULT_PATH", default_value = "secret/data/amphitheatre")]
+    pub vault_path: String,
+
+    /// The name of the secret store, required by the `external-secrets` provider.
+    #[clap(long, env = "AMP_EXTERNAL_SECRETS_STORE")]
+    pub external_secrets_store: Option<String>,
+
+    /// The kind of the secret store, `SecretStore` or `ClusterSecretStore`,
+    /// the default is `ClusterSecretStore`.
+    #[clap(long, env = "AMP_EXTERNAL_SECRETS_STORE_KIND", default_value = "ClusterSecretStore")]
+    pub external_secrets_store_kind: String,
+
+    /// The key of the credentials in the secret store, the default is `amphitheatre`.
+    #[clap(long, env = "AMP_EXTERNAL_SECRETS_KEY", default_value = "amphitheatre")]
+    pub external_secrets_key: String,
+
+    /// How the actors are exposed outside of the cluster, `none`, `ingress`
+    /// or `gateway` (Gateway API), the default is `none`.
+    #[clap(long, env = "AMP_EXPOSURE_BACKEND", default_value = "none")]
+    pub exposure_backend: String,
+
+    /// The hostname template of the exposed actors, the `{character}`,
+    /// `{playbook}` and `{namespace}` variables are replaced, e.g.
+    /// `{character}-{playbook}.apps.example.com`, the actors are not exposed
+    /// if it's not set.
+    #[clap(long, env = "AMP_EXPOSURE_HOSTNAME")]
+    pub exposure_hostname: Option<String>,
+
+    /// The Secret holding the TLS certificate of the exposed actors, in their namespaces.
+    #[clap(long, env = "AMP_EXPOSURE_TLS_SECRET")]
+    pub exposure_tls_secret: Option<String>,
+
+    /// The IngressClass of the Ingresses, the default class of cluster if it's not set.
+    #[clap(long, env = "AMP_INGRESS_CLASS")]
+    pub ingress_class: Option<String>,
+
+    /// The namespace of the ingress controller, e.g. `ingress-nginx`, it's
+    /// allowed to reach the actors isolated by the network section of playbooks.
+    #[clap(long, env = "AMP_INGRESS_NAMESPACE")]
+    pub ingress_namespace: Option<String>,
+
+    /// The shared Gateway the HTTPRoutes are attached to, in the form of
+    /// `{namespace}/{name}`, required by the `gateway` backend unless the
+    /// actors have their own TLS certificates.
+    #[clap(long, env = "AMP_GATEWAY")]
+    pub gateway: Option<String>,
+
+    /// The GatewayClass of the Gateways created for the actors with their own TLS certificates.
+    #[clap(long, env = "AMP_GATEWAY_CLASS")]
+    pub gateway_class: Option<String>,
+
+    /// The ClusterIssuer of cert-manager issuing the TLS certificates of the
+    /// exposed actors without their own TLS secrets, the actors are served
+    /// over HTTP only if it's not set.
+    #[clap(long, env = "AMP_CERT_ISSUER")]
+    pub cert_issuer: Option<String>,
+
+    /// The cpu requested by the build pods, e.g. `500m`.
+    #[clap(long, env = "AMP_BUILD_CPU_REQUEST")]
+    pub build_cpu_request: Option<String>,
+
+    /// The cpu limit of the build pods, e.g. `2`.
+    #[clap(long, env = "AMP_BUILD_CPU_LIMIT")]
+    pub build_cpu_limit: Option<String>,
+
+    /// The memory requested by the build pods, e.g. `1Gi`.
+    #[clap(long, env = "AMP_BUILD_MEMORY_REQUEST")]
+    pub build_memory_request: Option<String>,
+
+    /// The memory limit of the build pods, e.g. `4Gi`.
+    #[clap(long, env = "AMP_BUILD_MEMORY_LIMIT")]
+    pub build_memory_limit: Option<String>,
+
+    /// The labels of the nodes the build pods are scheduled to, separated
+    /// by commas, e.g. `pool=builds,kubernetes.io/arch=amd64`.
+    #[clap(long, env = "AMP_BUILD_NODE_SELECTOR")]
+    pub build_node_selector: Option<String>,
+
+    /// The taints tolerated by the build pods in the form of `kubectl taint`,
+    /// separated by commas, e.g. `dedicated=builds:NoSchedule`.
+    #[clap(long, env = "AMP_BUILD_TOLERATIONS")]
+    pub build_tolerations: Option<String>,
+
+    /// The PriorityClass of the build pods.
+    #[clap(long, env = "AMP_BUILD_PRIORITY_CLASS")]
+    pub build_priority_class: Option<String>,
+
+    /// The maximum number of concurrent builds across all the playbooks,
+    /// the excess builds are queued, `0` is unlimited, the default is `0`.
+    #[clap(long, env = "AMP_BUILD_CONCURRENCY", default_value = "0")]
+    pub build_concurrency: usize,
+
+    /// Kill the builds not completed in this many seconds, the actors can
+    /// override it, unlimited if it's not set.
+    #[clap(long, env = "AMP_BUILD_TIMEOUT")]
+    pub build_timeout: Option<i64>,
+
+    /// Delete the finished Jobs of the actors and their pods after this many
+    /// seconds, at least `60` so that they're seen completed, the actors can
+    /// override it, they're kept if it's not set.
+    #[clap(long, env = "AMP_JOB_TTL_SECONDS_AFTER_FINISHED")]
+    pub job_ttl_seconds_after_finished: Option<i32>,
+
+    /// The number of retries before the Jobs of the actors are marked failed,
+    /// the actors can override it, the default is `0`.
+    #[clap(long, env = "AMP_JOB_BACKOFF_LIMIT", default_value = "0")]
+    pub job_backoff_limit: i32,
+
+    /// Kill the Jobs of the actors still active after this many seconds,
+    /// the actors can override it, unlimited if it's not set.
+    #[clap(long, env = "AMP_JOB_ACTIVE_DEADLINE_SECONDS")]
+    pub job_active_deadline_seconds: Option<i64>,
+
+    /// The download API of the uploaded sources in the artifact store of the
+    /// apiserver, the default is `http://amp-apiserver.amp-system.svc:8170/v1/artifacts/sources`.
+    #[clap(long, env = "AMP_SOURCES_URL", default_value = amp_resources::upload::DEFAULT_SOURCES_URL)]
+    pub sources_url: String,
+
+    /// The bearer token of the apiserver, the build pods download the
+    /// uploaded sources with the signatures of it, as the artifacts require it.
+    #[clap(long, env = "AMP_AUTH_TOKEN")]
+    pub auth_token: Option<String>,
+
+    /// The default kpack builder of the actors built with Buildpacks, in the
+    /// form of `{kind}/{name}` or `{name}` for a ClusterBuilder, the builder
+    /// of each character is created if it's not set.
+    #[clap(long, env = "AMP_KPACK_BUILDER")]
+    pub kpack_builder: Option<String>,
+
+    /// How long the existence of an image in the registry is cached in
+    /// seconds, `0` disables the cache, the default is `300`.
+    #[clap(long, env = "AMP_IMAGE_CACHE_TTL", default_value = "300")]
+    pub image_cache_ttl: u64,
+
+    /// The format of the logs, `text` or `json`, the default is `text`.
+    #[clap(long, env = "AMP_LOG_FORMAT", default_value = "text")]
+    pub log_format: String,
+
+    /// The log level of each module, e.g. `info,amp_workflow=debug,kube=warn`,
+    /// it's overridden at runtime by the `amp-logging` ConfigMap, `RUST_LOG`
+    /// is used if it's not set, and the default is `info`.
+    #[clap(long, env = "AMP_LOG_FILTER")]
+    pub log_filter: Option<String>,
+
+    /// The OTLP gRPC endpoint the traces are exported to, e.g.
+    /// `http://otel-collector:4317`, they are not exported if it's not set.
+    #[clap(long, env = "AMP_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// The index of the character hub, an HTTP(S) URL or an OCI repository,
+    /// e.g. `oci://ghcr.io/amphitheatre-app/hub`, the qualified names of the
+    /// `hub` registry, e.g. `amphitheatre/redis@1.2.0`, are fetched from it.
+    #[clap(long, env = "AMP_HUB_URL")]
+    pub hub_url: Option<String>,
+
+    /// The backend of the artifact store the build logs are archived to,
+    /// `pvc`, `s3` or `gcs`, the default is `pvc`.
+    #[clap(long, env = "AMP_ARTIFACT_BACKEND", default_value = "pvc")]
+    pub artifact_backend: String,
+
+    /// The directory of the artifacts for the `pvc` backend, it's the mount of a
+    /// `ReadWriteMany` volume shared with the apiserver, or the bucket for the
+    /// `s3` and `gcs` backends, the default is `/var/lib/amp/artifacts`.
+    #[clap(long, env = "AMP_ARTIFACT_LOCATION", default_value = "/var/lib/amp/artifacts")]
+    pub artifact_location: String,
+
+    /// The endpoint of the S3 compatible service, e.g. MinIO, it's AWS S3 if not set.
+    #[clap(long, env = "AMP_ARTIFACT_S3_ENDPOINT")]
+    pub artifact_s3_endpoint: Option<String>,
+
+    /// The region of the S3 bucket, the default is `us-east-1`.
+    #[clap(long, env = "AMP_ARTIFACT_S3_REGION", default_value = "us-east-1")]
+    pub artifact_s3_region: String,
+
+    /// The number of the latest builds kept for each actor, the older base image
+    /// build Jobs and finished build pods are deleted, and the kpack Images keep
+    /// as many successful and failed Builds, `0` disables the collection, the default is `5`.
+    #[clap(long, env = "AMP_BUILD_HISTORY_LIMIT", default_value = "5")]
+    pub build_history_limit: usize,
+
+    /// How often in seconds the stale build resources and registry tags are collected, the default is `3600`.
+    #[clap(long, env = "AMP_GC_INTERVAL", default_value = "3600")]
+    pub gc_interval: u64,
+
+    /// The API the stale tags are deleted from the default registry by,
+    /// `distribution` (Docker Registry HTTP API V2) or `harbor`, the
+    /// default is `distribution`.
+    #[clap(long, env = "AMP_REGISTRY_RETENTION_API", default_value = "distribution")]
+    pub registry_retention_api: String,
+
+    /// The number of the latest tags kept for each repository of the actors,
+    /// the deployed ones are always kept, `0` disables the pruning, the default is `0`.
+    #[clap(long, env = "AMP_REGISTRY_RETENTION_KEEP", default_value = "0")]
+    pub registry_retention_keep: usize,
+
+    /// Keep the tags pushed in this many days as well, `0` ignores the age, the default is `0`.
+    #[clap(long, env = "AMP_REGISTRY_RETENTION_DAYS", default_value = "0")]
+    pub registry_retention_days: u64,
+}
+
+impl Config {
+    /// Returns the default quota of the playbook namespaces.
+    pub fn quota(&self) -> Quota {
+        Quota {
+            cpu: self.quota_cpu.clone(),
+            memory: self.quota_memory.clone(),
+            pods: self.quota_pods.clone(),
+            storage: self.quota_storage.clone(),
+            default_cpu: self.default_cpu_limit.clone(),
+            default_memory: self.default_memory_limit.clone(),
+        }
+    }
+
+    /// Returns how the actors are exposed by default.
+    pub fn exposure(&self) -> anyhow::Result<Exposure> {
+        let backend = match self.exposure_backend.as_str() {
+            "none" => Backend::None,
+            "ingress" => Backend::Ingress,
+            "gateway" => Backend::Gateway,
+            backend => return Err(anyhow::anyhow!("Unknown exposure backend: {}", backend)),
+        };
+
+        Ok(Exposure {
+            backend,
+            hostname: self.exposure_hostname.clone(),
+            tls_secret: self.exposure_tls_secret.clone(),
+            ingress_class: self.ingress_class.clone(),
+            ingress_namespace: self.ingress_namespace.clone(),
+            gateway: self.gateway.clone(),
+            gateway_class: self.gateway_class.clone(),
+            issuer: self.cert_issuer.clone(),
+        })
+    }
+
+    /// Returns the artifact store, the expired artifacts are deleted by the apiserver.
+    pub fn artifacts(&self) -> anyhow::Result<Store> {
+        let (backend, location) = (&self.artifact_backend, &self.artifact_location);
+        let endpoint = self.artifact_s3_endpoint.as_deref();
+
+        Ok(Store::open(backend, location, endpoint, &self.artifact_s3_region, Lifecycle::default())?)
+    }
+
+    /// Returns the retention of the tags of the default registry, None if the pruning is disabled.
+    pub fn retention(&self) -> anyhow::Result<Option<Retention>> {
+        if self.registry_retention_keep == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(Retention {
+            api: self.registry_retention_api.parse()?,
+            keep: self.registry_retention_keep,
+            max_age: (self.registry_retention_days > 0)
+                .then(|| Duration::from_secs(self.registry_retention_days * 24 * 60 * 60)),
+        }))
+    }
+
+    /// Returns the watcher config of the playbook and actor controllers.
+    pub fn watcher(&self) -> watcher::Config {
+        match &self.watch_label_selector {
+            Some(selector) => watcher::Config::default().labels(selector),
+            None => watcher::Config::default(),
+        }
+    }
+
+    /// Returns the config of the playbook and actor controllers.
+    pub fn controller(&self) -> controller::Config {
+        controller::Config::default().concurrency(self.reconcile_concurrency)
+    }
+
+    /// Returns the interval to reconcile the unchanged objects again, if any.
+    pub fn requeue_interval(&self) -> Option<Duration> {
+        (self.requeue_interval > 0).then(|| Duration::from_secs(self.requeue_interval))
+    }
+
+    /// Returns the default resources and placement of the build pods.
+    pub fn build(&self) -> anyhow::Result<BuildResources> {
+        let items = |value: &Option<String>| {
+            let value = value.as_deref().unwrap_or_default();
+            value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from).collect::<Vec<_>>()
+        };
+        let mut node_selector = BTreeMap::new();
+        for label in items(&self.build_node_selector) {
+            let (key, value) =
+                label.split_once('=').ok_or_else(|| anyhow::anyhow!("Invalid build node selector: {}", label))?;
+            node_selector.insert(key.to_string(), value.to_string());
+        }
+        let tolerations = items(&self.build_tolerations).iter().map(|taint| build::toleration(taint));
+        let tolerations = tolerations.collect::<Result<Vec<_>, _>>()?;
+
+        Ok(BuildResources {
+            cpu_request: self.build_cpu_request.clone(),
+            cpu_limit: self.build_cpu_limit.clone(),
+            memory_request: self.build_memory_request.clone(),
+            memory_limit: self.build_memory_limit.clone(),
+            node_selector,
+            tolerations,
+            priority_class: self.build_priority_class.clone(),
+            priority: None,
+            timeout: self.build_timeout,
+            history_limit: (self.build_history_limit > 0).then_some(self.build_history_limit as i64),
+        })
+    }
+
+    /// Returns the default settings of the Jobs of actors.
+    pub fn job(&self) -> JobSettings {
+        JobSettings {
+            ttl_seconds_after_finished: self.job_ttl_seconds_after_finished,
+            backoff_limit: Some(self.job_backoff_limit),
+            active_deadline_seconds: self.job_active_deadline_seconds,
+        }
+    }
+
+    /// Returns the default kpack builder, if any.
+    pub fn kpack_builder(&self) -> Option<BuilderRef> {
+        let value = self.kpack_builder.as_deref().filter(|value| !value.is_empty())?;
+        let (kind, name) = value.split_once('/').unwrap_or(("ClusterBuilder", value));
+
+        Some(BuilderRef { name: name.to_string(), kind: kind.to_string(), buildpacks: vec![] })
+    }
+
+    /// Returns the provider of the credentials.
+    pub fn secrets_provider(&self) -> anyhow::Result<Provider> {
+        match self.secrets_provider.as_str() {
+            "kubernetes" => Ok(Provider::Kubernetes),
+            "vault" => {
+                let addr = self.vault_addr.as_deref().ok_or_else(|| anyhow::anyhow!("AMP_VAULT_ADDR is required"))?;
+                Ok(Provider::Vault(Vault::new(addr, &self.vault_role, &self.vault_path)))
+            }
+            "external-secrets" => {
+                let store = self.external_secrets_store.clone();
+                let store = store.ok_or_else(|| anyhow::anyhow!("AMP_EXTERNAL_SECRETS_STORE is required"))?;
+                Ok(Provider::ExternalSecrets(ExternalSecret {
+                    store,
+                    store_kind: self.external_secrets_store_kind.clone(),
+                    key: self.external_secrets_key.clone(),
+                    refresh_interval: self.secrets_refresh_interval,
+                }))
+            }
+            provider => Err(anyhow::anyhow!("Unknown secrets provider: {}", provider)),
+        }
+    }
 }
@@ -12,14 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 use amp_common::config::Credentials;
-use amp_resources::credential;
+use amp_resources::artifact::Store;
+use amp_resources::audit::Publisher;
+use amp_resources::build::BuildResources;
+use amp_resources::exposure::Exposure;
+use amp_resources::kpack::reference::BuilderRef;
+use amp_resources::policy::{self, RegistryPolicy};
+use amp_resources::retention::Retention;
+use amp_resources::secret::Provider;
+use amp_workflow::{BuildQueue, ImageCache};
 use async_nats::jetstream;
 use tokio::sync::RwLock;
 
 use crate::config::Config;
+use crate::priority::Scheduler;
 
 /// The core type through which handler functions can access common API state.
 ///
@@ -32,27 +43,67 @@ use crate::config::Config;
 pub struct Context {
     pub k8s: kube::Client,
     pub credentials: Arc<RwLock<Credentials>>,
+    /// The servers of the registries whose passwords are minted tokens, instead of static ones.
+    pub minted: Arc<RwLock<BTreeSet<String>>>,
+    pub secrets: Provider,
+    pub exposure: Exposure,
+    pub build: BuildResources,
+    pub build_queue: Arc<BuildQueue>,
+    pub kpack_builder: Option<BuilderRef>,
+    pub images: Arc<ImageCache>,
+    pub artifacts: Store,
+    pub retention: Option<Retention>,
+    pub policy: Arc<RwLock<RegistryPolicy>>,
     pub config: Arc<Config>,
+    pub nats: async_nats::Client,
     pub jetstream: Arc<jetstream::Context>,
+    pub audit: Publisher,
+    pub actor_scheduler: Scheduler,
+    pub playbook_scheduler: Scheduler,
 }
 
 impl Context {
     pub async fn new(config: Config) -> anyhow::Result<Context> {
         let k8s = kube::Client::try_default().await?;
-        let credentials = credential::load(&k8s, &config.namespace).await?;
+        let secrets = config.secrets_provider()?;
+        let exposure = config.exposure()?;
+        let build = config.build()?;
+        let kpack_builder = config.kpack_builder();
+        let artifacts = config.artifacts()?;
+        let retention = config.retention()?;
+        let credentials = secrets.load(&k8s, &config.namespace).await?;
         let credentials = RwLock::new(credentials.unwrap_or_default());
+        let policy = policy::load(&k8s, &config.namespace).await?;
 
         // Connect to NATS and create a JetStream instance.
         let client = async_nats::connect(&config.nats_url)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to connect to NATS: {}, {}", &config.nats_url, e))?;
-        let jetstream = jetstream::new(client);
+        let jetstream = jetstream::new(client.clone());
+        let retention = Duration::from_secs(config.audit_retention_days * 24 * 60 * 60);
+        let audit = Publisher::new(jetstream.clone(), retention);
+
+        let concurrency = config.background_reconcile_concurrency;
 
         Ok(Context {
             k8s,
             credentials: Arc::new(credentials),
+            minted: Arc::new(RwLock::new(BTreeSet::new())),
+            secrets,
+            exposure,
+            build,
+            build_queue: Arc::new(BuildQueue::new(config.build_concurrency)),
+            kpack_builder,
+            images: Arc::new(ImageCache::new(Duration::from_secs(config.image_cache_ttl))),
+            artifacts,
+            retention,
+            policy: Arc::new(RwLock::new(policy)),
             config: Arc::new(config),
+            nats: client,
             jetstream: Arc::new(jetstream),
+            audit,
+            actor_scheduler: Scheduler::new(concurrency),
+            playbook_scheduler: Scheduler::new(concurrency),
         })
     }
 }
@@ -12,12 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 use amp_common::config::Credentials;
-use amp_resources::credential;
+use amp_resources::secret::{Provider, Vault, CREDENTIALS_SECRET};
+use amp_resources::{credential, registry};
 use futures::{StreamExt, TryStreamExt};
-use k8s_openapi::api::core::v1::Secret;
+use k8s_openapi::api::core::v1::{Namespace, Secret};
+use kube::api::ListParams;
 use kube::runtime::{watcher, WatchStreamExt};
 use kube::{Api, ResourceExt};
 use tracing::{debug, error, info};
@@ -25,11 +29,20 @@ use tracing::{debug, error, info};
 use crate::context::Context;
 
 pub async fn new(ctx: &Arc<Context>) {
+    match &ctx.secrets {
+        Provider::Vault(vault) => refresh(ctx, vault).await,
+        // The Secret is synced by the External Secrets Operator, watch it as well.
+        Provider::Kubernetes | Provider::ExternalSecrets(_) => watch(ctx).await,
+    }
+}
+
+/// Watch the `amp-credentials` Secret in the Amphitheatre namespace.
+async fn watch(ctx: &Arc<Context>) {
     let namespace = ctx.config.namespace.clone();
     debug!("namespace = {}", namespace);
 
     let api = Api::<Secret>::namespaced(ctx.k8s.clone(), &namespace);
-    let config = watcher::Config::default().fields("metadata.name=amp-credentials");
+    let config = watcher::Config::default().fields(&format!("metadata.name={}", CREDENTIALS_SECRET));
     let mut obs = watcher(api, config).applied_objects().boxed();
 
     loop {
@@ -49,6 +62,31 @@ pub async fn new(ctx: &Arc<Context>) {
     }
 }
 
+/// Refresh the credentials from Vault periodically, the loaded ones are applied
+/// first. They're refreshed until applied, if they failed to be.
+async fn refresh(ctx: &Arc<Context>, vault: &Vault) {
+    match sync(ctx, &ctx.credentials.read().await).await {
+        Ok(()) => vault.applied().await,
+        Err(err) => error!("Apply the credentials from Vault failed: {}", err.to_string()),
+    }
+
+    let interval = Duration::from_secs(ctx.config.secrets_refresh_interval.max(1));
+    loop {
+        tokio::time::sleep(interval).await;
+        match vault.refresh().await {
+            Ok(Some(credentials)) => {
+                info!("The credentials in Vault were rotated, apply them");
+                match apply(ctx, credentials).await {
+                    Ok(()) => vault.applied().await,
+                    Err(err) => error!("Apply the credentials from Vault failed: {}", err.to_string()),
+                }
+            }
+            Ok(None) => debug!("The credentials in Vault are not changed"),
+            Err(err) => error!("Refresh the credentials from Vault failed: {}", err.to_string()),
+        }
+    }
+}
+
 // This function lets the app handle an added/modified secret from k8s.
 async fn handle(ctx: &Arc<Context>, secret: &Secret) -> anyhow::Result<()> {
     info!("Handle an added/modified secret from k8s: {}", secret.name_any());
@@ -57,14 +95,50 @@ async fn handle(ctx: &Arc<Context>, secret: &Secret) -> anyhow::Result<()> {
         if let Some(content) = data.get("credentials") {
             let content = std::str::from_utf8(&content.0)?;
             let value: Credentials = toml::from_str(content)?;
-            let mut credentials = ctx.credentials.write().await;
-            *credentials = value;
+            apply(ctx, value).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the credentials and sync them into the namespaces using them.
+async fn apply(ctx: &Arc<Context>, mut value: Credentials) -> anyhow::Result<()> {
+    // The new credentials don't carry the tokens of the cloud registries, mint them again.
+    let mut minted = BTreeSet::new();
+    if ctx.config.registry_token_refresh_interval > 0 {
+        let tokens = registry::refresh(&value, &minted).await;
+        registry::merge(&mut value, &tokens);
+        minted = tokens.into_keys().collect();
+    }
+    *ctx.credentials.write().await = value;
+    *ctx.minted.write().await = minted;
+    sync(ctx, &ctx.credentials.read().await).await?;
+    info!("The latest credentials has been successfully applied!");
+
+    Ok(())
+}
 
-            // Refresh the credentials under the amp platform's own namespace.
-            debug!("Refresh the credentials under the amp platform's own namespace.");
-            credential::sync(&ctx.k8s, &ctx.config.namespace, &ctx.config.service_account_name, &credentials).await?;
+/// Sync the credentials into the Amphitheatre namespace and the namespaces
+/// of playbooks, so the rotated credentials take effect without waiting for
+/// new namespaces.
+pub(crate) async fn sync(ctx: &Arc<Context>, credentials: &Credentials) -> anyhow::Result<()> {
+    // Refresh the credentials under the amp platform's own namespace.
+    debug!("Refresh the credentials under the amp platform's own namespace.");
+    credential::sync(&ctx.k8s, &ctx.config.namespace, &ctx.config.service_account_name, credentials).await?;
 
-            info!("The latest credentials has been successfully applied!");
+    // Refresh the credentials under the namespaces of playbooks.
+    let api = Api::<Namespace>::all(ctx.k8s.clone());
+    let namespaces = api.list(&ListParams::default().labels("syncer.amphitheatre.app/sync=true")).await?;
+    for ns in namespaces.items {
+        if ns.status.as_ref().and_then(|status| status.phase.as_deref()) == Some("Terminating") {
+            continue;
+        }
+        if let Err(err) = credential::sync(&ctx.k8s, &ns.name_any(), "default", credentials).await {
+            error!("Refresh the credentials under namespace {} failed: {}", ns.name_any(), err.to_string());
+        }
+        if let Err(err) = credential::sync_builder(&ctx.k8s, &ns.name_any(), credentials).await {
+            error!("Refresh the builder credentials under namespace {} failed: {}", ns.name_any(), err.to_string());
         }
     }
 
@@ -0,0 +1,85 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use amp_resources::{actor, cluster, gc, retention};
+use k8s_openapi::chrono;
+use tracing::{error, info};
+
+use crate::context::Context;
+
+/// The namespaces of detached actors younger than this are not collected.
+const DETACHED_NAMESPACE_GRACE_MINUTES: i64 = 10;
+
+/// Delete the build resources beyond the history limit of each actor
+/// periodically, i.e. the base image build Jobs and the finished build
+/// pods, the kpack Images limit their Builds themselves. Also delete the
+/// leaked namespaces of the detached actors, and prune the stale tags of
+/// the repositories of the actors.
+pub async fn new(ctx: &Arc<Context>) {
+    let keep = ctx.config.build_history_limit;
+
+    info!("GC controller is running...");
+    let mut interval = tokio::time::interval(Duration::from_secs(ctx.config.gc_interval.max(60)));
+    loop {
+        interval.tick().await;
+        if keep > 0 {
+            collect(ctx, keep).await;
+        }
+        match gc::namespaces(&ctx.k8s, chrono::Duration::minutes(DETACHED_NAMESPACE_GRACE_MINUTES)).await {
+            Ok(deleted) if deleted > 0 => info!("Deleted {} leaked namespaces of detached actors", deleted),
+            Ok(_) => {}
+            Err(err) => error!("Collect the leaked namespaces of detached actors failed: {}", err.to_string()),
+        }
+        if let Err(err) = prune(ctx).await {
+            error!("Prune the stale registry tags failed: {}", err.to_string());
+        }
+    }
+}
+
+async fn collect(ctx: &Arc<Context>, keep: usize) {
+    match gc::jobs(&ctx.k8s, keep).await {
+        Ok(deleted) if deleted > 0 => info!("Deleted {} stale base image build Jobs", deleted),
+        Ok(_) => {}
+        Err(err) => error!("Collect the stale base image build Jobs failed: {}", err.to_string()),
+    }
+    match gc::pods(&ctx.k8s, keep).await {
+        Ok(deleted) if deleted > 0 => info!("Deleted {} stale build pods", deleted),
+        Ok(_) => {}
+        Err(err) => error!("Collect the stale build pods failed: {}", err.to_string()),
+    }
+}
+
+/// Prune the stale tags of the repositories of all the actors, the deployed ones are kept.
+/// The actors of the workload clusters share the repositories, so nothing is pruned
+/// unless the actors of every registered cluster are listed.
+async fn prune(ctx: &Arc<Context>) -> anyhow::Result<()> {
+    let Some(retention) = &ctx.retention else {
+        return Ok(());
+    };
+
+    let mut actors = actor::list_all(&ctx.k8s).await?;
+    for name in cluster::list(&ctx.k8s, &ctx.config.namespace).await? {
+        let client = cluster::connect(&ctx.k8s, &ctx.config.namespace, &name).await?;
+        actors.extend(actor::list_all(&client).await?);
+    }
+    let deleted = retention::prune(&*ctx.credentials.read().await, retention, &actors, &actors).await?;
+    if deleted > 0 {
+        info!("Deleted {} stale images from the registry", deleted);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_common::resource::Actor;
+use amp_resources::audit::{AuditEvent, Publisher};
+use amp_resources::healing::{self, Action, HealingPolicy, Remediation};
+use amp_resources::{debug, devcontainer, playbook, statefulset, strategy};
+use chrono::Utc;
+use futures::{future, StreamExt};
+use kube::runtime::events::{Event, EventType, Recorder, Reporter};
+use kube::Client;
+use kube::{
+    runtime::{reflector, watcher, WatchStreamExt},
+    Api, Resource, ResourceExt,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::context::Context;
+
+/// Watch the running actors and remediate the unhealthy ones by their self-healing policies.
+pub async fn new(ctx: &Arc<Context>) {
+    let client = ctx.k8s.clone();
+    let audit = ctx.audit.clone();
+    let api = Api::<Actor>::all(client.clone());
+    let config = watcher::Config::default();
+    let (reader, writer) = reflector::store();
+    let rf = reflector(writer, watcher(api, config));
+
+    tokio::spawn(async move {
+        if let Err(e) = reader.wait_until_ready().await {
+            error!("Failed to wait until ready: {:?}", e);
+            return;
+        }
+        info!("Healing controller is running...");
+        loop {
+            for actor in reader.state() {
+                if !actor.status.as_ref().is_some_and(|status| status.running()) {
+                    continue;
+                }
+                // The probes fail while the debugger is paused at a breakpoint.
+                if debug::debugging(&actor) {
+                    continue;
+                }
+                // The dev container does not run the application to be healed.
+                if devcontainer::developing(&actor) {
+                    continue;
+                }
+                let policy = match healing::policy(&actor) {
+                    Ok(Some(policy)) => policy,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        error!("Invalid healing policy of actor {}: {}", actor.name_any(), err);
+                        continue;
+                    }
+                };
+                if let Err(err) = handle(actor.as_ref(), &policy, &client, &audit).await {
+                    error!("Handle healing of actor failed: {}", err.to_string());
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        }
+    });
+
+    rf.applied_objects().for_each(|_| future::ready(())).await;
+}
+
+async fn handle(actor: &Actor, policy: &HealingPolicy, client: &Client, audit: &Publisher) -> anyhow::Result<()> {
+    let pods = healing::observe(client, actor).await?;
+    let history = healing::history(actor);
+    let owner = actor.owner_references().iter().find(|owner| owner.kind == "Playbook");
+
+    let stateful = statefulset::stateful(actor);
+    let workload = if stateful { statefulset::name(actor) } else { strategy::serving(actor) };
+    let Some(remediation) = healing::decide(policy, &workload, stateful, &pods) else {
+        if healing::recovered(&history, &pods) {
+            recover(actor, client, owner.map(|owner| owner.name.as_str())).await?;
+        }
+        return Ok(());
+    };
+    if !healing::due(&history, Utc::now()) {
+        debug!("Back off the remediation of actor {}: {}", actor.name_any(), remediation.reason);
+        return Ok(());
+    }
+
+    healing::remediate(client, actor, &remediation).await?;
+    report(client, actor, &remediation, audit).await;
+
+    // Escalate to the playbook if the remediations don't help.
+    let mut remediations = vec![remediation];
+    let mut history = history;
+    history.extend(remediations.clone());
+    if let (true, Some(owner)) = (healing::escalate(policy, &history), owner) {
+        let playbook = playbook::get(client, &owner.name).await?;
+        let message = format!(
+            "Actor {} is still unhealthy after {} remediations",
+            actor.name_any(),
+            policy.escalate_after.unwrap_or_default()
+        );
+        warn!("{}, mark playbook {} degraded", message, playbook.name_any());
+        playbook::upsert_condition(client, &playbook, healing::degraded(message.clone())).await?;
+
+        let escalation = Remediation {
+            action: Action::Escalate,
+            target: playbook.name_any(),
+            reason: message,
+            time: Utc::now().to_rfc3339(),
+        };
+        report(client, actor, &escalation, audit).await;
+        remediations.push(escalation);
+    }
+
+    healing::record(client, actor, remediations).await?;
+
+    Ok(())
+}
+
+/// Record the recovery of actor, and clear the degraded condition of its
+/// playbook if it was marked by the escalation of actor.
+async fn recover(actor: &Actor, client: &Client, playbook: Option<&str>) -> anyhow::Result<()> {
+    if let Some(name) = playbook {
+        let playbook = playbook::get(client, name).await?;
+        if healing::degraded_by(&playbook, actor) {
+            playbook::remove_condition(client, &playbook, healing::DEGRADED_CONDITION_TYPE).await?;
+        }
+    }
+
+    let recovery = Remediation {
+        action: Action::Recover,
+        target: actor.name_any(),
+        reason: format!("Actor {} is healthy again", actor.name_any()),
+        time: Utc::now().to_rfc3339(),
+    };
+    info!("{} after the remediations", recovery.reason);
+    healing::record(client, actor, vec![recovery]).await?;
+
+    Ok(())
+}
+
+/// Report the remediation as an event of actor and in the audit log, the
+/// remediation has been taken already, so the failures are logged only.
+async fn report(client: &Client, actor: &Actor, remediation: &Remediation, audit: &Publisher) {
+    let reporter = Reporter { controller: "amp-controllers".into(), instance: None };
+    let recorder = Recorder::new(client.clone(), reporter, actor.object_ref(&()));
+    let event = Event {
+        type_: EventType::Warning,
+        reason: "Remediated".into(),
+        note: Some(remediation.reason.clone()),
+        action: format!("{:?}", remediation.action),
+        secondary: None,
+    };
+    if let Err(err) = recorder.publish(event).await {
+        error!("Failed to publish the remediation event of actor {}: {}", actor.name_any(), err);
+    }
+
+    // The same shape as the audit events of the API calls.
+    let playbook = actor.namespace().unwrap_or_default();
+    let method = serde_json::to_value(remediation.action).ok();
+    let event = AuditEvent {
+        id: 0,
+        actor: "amp-controllers".into(),
+        source: None,
+        method: method.as_ref().and_then(|value| value.as_str()).unwrap_or_default().into(),
+        path: format!("/v1/actors/{}/{}", playbook.trim_start_matches("amp-"), actor.name_any()),
+        status: 200,
+        digest: String::new(),
+        timestamp: Utc::now(),
+    };
+    if let Err(err) = audit.record(&event).await {
+        error!("Failed to record the remediation of actor {} in the audit log: {}", actor.name_any(), err);
+    }
+}
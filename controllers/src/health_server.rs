@@ -0,0 +1,67 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use amp_resources::{cache, health};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use tracing::{error, info};
+
+use crate::context::Context;
+
+/// Serve the liveness and readiness probes of the controllers.
+pub async fn new(ctx: &Arc<Context>) {
+    let app = Router::new().route("/healthz", get(healthz)).route("/readyz", get(readyz)).with_state(ctx.clone());
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], ctx.config.health_port));
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Failed to bind the health server on {}: {}", addr, err);
+            return;
+        }
+    };
+    info!("Health server is listening on {}", addr);
+
+    if let Err(err) = axum::serve(listener, app).await {
+        error!("Health server error: {}", err);
+    }
+}
+
+/// The process is alive as long as it serves the requests.
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Ready once Kubernetes is reachable, the CRDs are installed and the informers are synced.
+async fn readyz(State(ctx): State<Arc<Context>>) -> (StatusCode, Json<Vec<health::Check>>) {
+    let informers = match cache::synced() {
+        true => Ok(()),
+        false => Err("The informers are not synced yet".to_string()),
+    };
+    let checks = vec![
+        health::kubernetes(&ctx.k8s).await,
+        health::crds(&ctx.k8s).await,
+        health::Check::new("informers", informers),
+    ];
+
+    match health::ready(&checks) {
+        true => (StatusCode::OK, Json(checks)),
+        false => (StatusCode::SERVICE_UNAVAILABLE, Json(checks)),
+    }
+}
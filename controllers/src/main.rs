@@ -15,50 +15,85 @@
 #![allow(clippy::enum_variant_names)]
 use std::sync::Arc;
 
+use amp_resources::logging;
 use clap::Parser;
-use tracing::metadata::LevelFilter;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::EnvFilter;
 
+mod bootstrap;
 mod config;
 mod context;
 mod errors;
+mod priority;
 
 use crate::config::Config;
 use crate::context::Context;
 
 mod actor_controller;
+mod cache_watcher;
 mod credentials_watcher;
+mod gc_controller;
+mod healing_controller;
+mod health_server;
 mod namespace_watcher;
 mod playbook_controller;
+mod policy_watcher;
+mod reload_controller;
 mod timeout_controller;
+mod token_refresher;
+mod usage_controller;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Enable tracing.
-    tracing_subscriber::registry()
-        .with(EnvFilter::builder().with_default_directive(LevelFilter::INFO.into()).from_env_lossy())
-        .with(tracing_subscriber::fmt::layer().with_file(false).with_target(false))
-        .init();
-
     // This returns an error if the `.env` file doesn't exist, but that's not what we want
     // since we're not going to use a `.env` file if we deploy this application.
     dotenv::dotenv().ok();
 
     // Parse our configuration from the environment.
     // This will exit with a help message if something is wrong.
+    let config = Config::parse();
+
+    // Enable logging, and export the spans if the OTLP endpoint is set.
+    let endpoint = config.otlp_endpoint.as_deref();
+    let filter = logging::directives(config.log_filter.as_deref());
+    logging::init("amp-controllers", &config.log_format, &filter, endpoint)?;
+
+    // Fetch the qualified characters of the hub registry from the hub index.
+    if let Some(url) = &config.hub_url {
+        amp_resolver::hub::configure(url)?;
+    }
+
+    // Set the default lifecycle of the Jobs created for the actors.
+    amp_resources::job::configure(config.job());
+
+    // The build pods download the uploaded sources from the apiserver.
+    amp_resources::upload::configure(&config.sources_url, config.auth_token.clone());
+
     // Then, initialize the shared context.
-    let ctx = Arc::new(Context::new(Config::parse()).await?);
+    let ctx = Arc::new(Context::new(config).await?);
+
+    // Install or upgrade the CRDs before watching them.
+    if ctx.config.install_crds {
+        bootstrap::run(&ctx).await?;
+    }
 
     // Creates the controllers and waits on multiple concurrent branches,
     // returning when **the first** branch completes and cancelling the remaining branches.
     tokio::select! {
         _ = playbook_controller::new(&ctx) => tracing::warn!("playbook controller exited"),
         _ = actor_controller::new(&ctx) => tracing::warn!("actor controller exited"),
+        _ = cache_watcher::new(&ctx) => tracing::warn!("cache watcher exited"),
         _ = credentials_watcher::new(&ctx) => tracing::warn!("credentials watcher exited"),
         _ = namespace_watcher::new(&ctx) => tracing::warn!("namespace watcher exited"),
-        _ = timeout_controller::new(&ctx) => tracing::warn!("timeout controller exited")
+        _ = policy_watcher::new(&ctx) => tracing::warn!("policy watcher exited"),
+        _ = timeout_controller::new(&ctx) => tracing::warn!("timeout controller exited"),
+        _ = token_refresher::new(&ctx) => tracing::warn!("token refresher exited"),
+        _ = usage_controller::new(&ctx) => tracing::warn!("usage controller exited"),
+        _ = healing_controller::new(&ctx) => tracing::warn!("healing controller exited"),
+        _ = reload_controller::new(&ctx) => tracing::warn!("reload controller exited"),
+        _ = gc_controller::new(&ctx) => tracing::warn!("gc controller exited"),
+        _ = health_server::new(&ctx) => tracing::warn!("health server exited"),
+        _ = logging::watch(ctx.k8s.clone(), &ctx.config.namespace, &filter) => {
+            tracing::warn!("logging watcher exited")
+        }
     }
 
     Ok(())
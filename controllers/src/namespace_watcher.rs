@@ -59,6 +59,7 @@ async fn handle(ctx: &Arc<Context>, ns: &Namespace) -> anyhow::Result<()> {
     // Inject dependent credentials for this namespace.
     let credentials = ctx.credentials.read().await;
     credential::sync(&ctx.k8s, &ns.name_any(), "default", &credentials).await?;
+    credential::sync_builder(&ctx.k8s, &ns.name_any(), &credentials).await?;
 
     Ok(())
 }
@@ -16,15 +16,16 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use amp_common::resource::Playbook;
+use amp_resources::telemetry;
 
 use amp_workflow::Workflow;
 use futures::{future, StreamExt};
 use kube::api::ListParams;
 use kube::runtime::controller::Action;
 use kube::runtime::finalizer::{finalizer, Event};
-use kube::runtime::{watcher, Controller};
-use kube::{Api, ResourceExt};
-use tracing::{error, info};
+use kube::runtime::Controller;
+use kube::{Api, Resource, ResourceExt};
+use tracing::{error, info, instrument, Span};
 
 use crate::context::Context;
 use crate::errors::{Error, Result};
@@ -41,14 +42,18 @@ pub async fn new(ctx: &Arc<Context>) {
         std::process::exit(1);
     }
 
-    Controller::new(api, watcher::Config::default())
+    Controller::new(api, ctx.config.watcher())
+        .with_config(ctx.config.controller())
         .run(reconcile, error_policy, ctx.clone())
         .for_each(|_| future::ready(()))
         .await
 }
 
 /// The reconciler that will be called when either object change
+#[instrument(skip_all, fields(playbook = %playbook.name_any()))]
 pub async fn reconcile(playbook: Arc<Playbook>, ctx: Arc<Context>) -> Result<Action> {
+    telemetry::link(&Span::current(), playbook.as_ref());
+
     let api: Api<Playbook> = Api::all(ctx.k8s.clone());
 
     let mut workflow = Workflow::new(
@@ -56,13 +61,28 @@ pub async fn reconcile(playbook: Arc<Playbook>, ctx: Arc<Context>) -> Result<Act
             k8s: Arc::new(ctx.k8s.clone()),
             jetstream: ctx.jetstream.clone(),
             credentials: ctx.credentials.clone(),
+            policy: ctx.policy.clone(),
+            namespace: ctx.config.namespace.clone(),
+            quota: ctx.config.quota(),
+            exposure: ctx.exposure.clone(),
+            build: ctx.build.clone(),
+            build_queue: ctx.build_queue.clone(),
+            kpack_builder: ctx.kpack_builder.clone(),
+            requeue: ctx.config.requeue_interval(),
+            images: ctx.images.clone(),
+            artifacts: ctx.artifacts.clone(),
+            retention: ctx.retention.clone(),
             object: playbook.clone(),
         },
         Box::new(amp_workflow::playbook::InitialState),
     );
 
+    // User-initiated changes jump ahead of the periodic resyncs.
+    let _permit = ctx.playbook_scheduler.admit(playbook.as_ref()).await;
+
     // Reconcile the playbook custom resource.
-    finalizer(&api, FINALIZER_NAME, playbook, |event| async {
+    let object = playbook.clone();
+    let action = finalizer(&api, FINALIZER_NAME, playbook, |event| async {
         match event {
             Event::Apply(playbook) => {
                 info!("Apply playbook {}", playbook.name_any());
@@ -79,12 +99,20 @@ pub async fn reconcile(playbook: Arc<Playbook>, ctx: Arc<Context>) -> Result<Act
         workflow.run().await.map_err(Error::WorkflowError)
     })
     .await
-    .map_err(|e| Error::FinalizerError(Box::new(e)))
+    .map_err(|e| Error::FinalizerError(Box::new(e)))?;
+
+    if object.meta().deletion_timestamp.is_some() {
+        ctx.playbook_scheduler.forget(object.as_ref());
+    } else {
+        ctx.playbook_scheduler.reconciled(object.as_ref());
+    }
+
+    Ok(action)
 }
 
 /// an error handler that will be called when the reconciler fails with access to both the
 /// object that caused the failure and the actual error
-pub fn error_policy(_: Arc<Playbook>, error: &Error, _: Arc<Context>) -> Action {
+pub fn error_policy(_: Arc<Playbook>, error: &Error, ctx: Arc<Context>) -> Action {
     error!("reconcile failed: {:?}", error);
-    Action::requeue(Duration::from_secs(60))
+    Action::requeue(Duration::from_secs(ctx.config.error_requeue_interval))
 }
@@ -0,0 +1,64 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::policy::{self, RegistryPolicy, POLICY_CONFIG_MAP};
+use futures::{StreamExt, TryStreamExt};
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::runtime::watcher::{self, Event};
+use kube::Api;
+use tracing::{error, info};
+
+use crate::context::Context;
+
+pub async fn new(ctx: &Arc<Context>) {
+    let api = Api::<ConfigMap>::namespaced(ctx.k8s.clone(), &ctx.config.namespace);
+    let config = watcher::Config::default().fields(&format!("metadata.name={}", POLICY_CONFIG_MAP));
+    let mut obs = watcher(api, config).boxed();
+
+    loop {
+        let event = obs.try_next().await;
+        match event {
+            Ok(Some(event)) => {
+                if let Err(err) = handle(ctx, event).await {
+                    error!("Handle registry policy failed: {}", err.to_string());
+                }
+            }
+            Ok(None) => continue,
+            Err(err) => {
+                error!("Resolve registry policy stream failed: {}", err.to_string());
+                continue;
+            }
+        }
+    }
+}
+
+// This function lets the app handle the added/modified/deleted registry policy from k8s.
+async fn handle(ctx: &Arc<Context>, event: Event<ConfigMap>) -> anyhow::Result<()> {
+    let value = match event {
+        Event::Applied(config_map) => policy::from(&config_map)?,
+        Event::Deleted(_) => RegistryPolicy::default(),
+        Event::Restarted(config_maps) => match config_maps.first() {
+            Some(config_map) => policy::from(config_map)?,
+            None => RegistryPolicy::default(),
+        },
+    };
+
+    let mut policy = ctx.policy.write().await;
+    *policy = value;
+    info!("The latest registry policy has been successfully applied: {:?}", policy.allow);
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use amp_resources::REQUESTED_AT_ANNOTATION;
+use kube::{Resource, ResourceExt};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::debug;
+
+/// The priority of a reconciliation.
+#[derive(Debug, PartialEq)]
+pub enum Priority {
+    /// Triggered by the user, the object is new, its spec has changed or
+    /// an operation has been requested.
+    Interactive,
+    /// Periodic resyncs and status-only changes.
+    Background,
+}
+
+/// The generation and requested-at annotation of the last reconciled object.
+type Fingerprint = (Option<i64>, Option<String>);
+
+/// Prioritizes the user-initiated reconciliations over the background ones.
+///
+/// The interactive reconciliations run immediately, while the background ones
+/// have to wait for a limited number of permits, so they can not saturate the
+/// controllers and the Kubernetes API under heavy background load.
+pub struct Scheduler {
+    background: Semaphore,
+    reconciled: Mutex<HashMap<String, Fingerprint>>,
+}
+
+impl Scheduler {
+    pub fn new(background_concurrency: usize) -> Self {
+        Self { background: Semaphore::new(background_concurrency), reconciled: Mutex::new(HashMap::new()) }
+    }
+
+    /// Classify the reconciliation of the object by comparing it with the last reconciled one.
+    pub fn classify<K: Resource<DynamicType = ()>>(&self, object: &K) -> Priority {
+        let reconciled = self.reconciled.lock().unwrap();
+        match reconciled.get(&key(object)) {
+            Some(fingerprint) if fingerprint == &fingerprint_of(object) => Priority::Background,
+            _ => Priority::Interactive,
+        }
+    }
+
+    /// Wait for the turn of the reconciliation, the returned permit must be held until it's finished.
+    pub async fn admit<K: Resource<DynamicType = ()>>(&self, object: &K) -> Option<SemaphorePermit<'_>> {
+        match self.classify(object) {
+            Priority::Interactive => {
+                debug!("Reconcile {} {} with interactive priority", K::kind(&()), object.name_any());
+                None
+            }
+            Priority::Background => self.background.acquire().await.ok(),
+        }
+    }
+
+    /// Record the object as reconciled, the following reconciliations of it
+    /// will be in background until the user changes it again.
+    pub fn reconciled<K: Resource<DynamicType = ()>>(&self, object: &K) {
+        self.reconciled.lock().unwrap().insert(key(object), fingerprint_of(object));
+    }
+
+    /// Forget the deleted object.
+    pub fn forget<K: Resource<DynamicType = ()>>(&self, object: &K) {
+        self.reconciled.lock().unwrap().remove(&key(object));
+    }
+}
+
+#[inline]
+fn key<K: Resource<DynamicType = ()>>(object: &K) -> String {
+    format!("{}/{}/{}", K::kind(&()), object.namespace().unwrap_or_default(), object.name_any())
+}
+
+#[inline]
+fn fingerprint_of<K: Resource>(object: &K) -> Fingerprint {
+    (object.meta().generation, object.annotations().get(REQUESTED_AT_ANNOTATION).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use amp_common::resource::{Actor, ActorSpec};
+
+    use super::*;
+
+    fn actor(generation: i64) -> Actor {
+        let mut actor = Actor::new("test", ActorSpec::default());
+        actor.meta_mut().namespace = Some("amp-test".into());
+        actor.meta_mut().generation = Some(generation);
+        actor
+    }
+
+    #[test]
+    fn test_classify_new_and_changed_objects() {
+        let scheduler = Scheduler::new(1);
+        let object = actor(1);
+        assert_eq!(scheduler.classify(&object), Priority::Interactive);
+
+        scheduler.reconciled(&object);
+        assert_eq!(scheduler.classify(&object), Priority::Background);
+        assert_eq!(scheduler.classify(&actor(2)), Priority::Interactive);
+    }
+
+    #[test]
+    fn test_classify_requested_objects() {
+        let scheduler = Scheduler::new(1);
+        let mut object = actor(1);
+        scheduler.reconciled(&object);
+
+        object.annotations_mut().insert(REQUESTED_AT_ANNOTATION.into(), "2024-01-01T00:00:00Z".into());
+        assert_eq!(scheduler.classify(&object), Priority::Interactive);
+
+        scheduler.reconciled(&object);
+        assert_eq!(scheduler.classify(&object), Priority::Background);
+
+        scheduler.forget(&object);
+        assert_eq!(scheduler.classify(&object), Priority::Interactive);
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use amp_resources::{actor, cluster, namespace, playbook, reload};
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::AttachParams;
+use kube::Api;
+use tokio::io::AsyncReadExt;
+use tracing::{error, info};
+
+use crate::context::Context;
+
+/// Run the reload hooks of the live actors once the files are synced into their pods.
+pub async fn new(ctx: &Arc<Context>) {
+    let subject = format!("{}.>", reload::SYNCED_SUBJECT);
+    let mut subscriber = match ctx.nats.subscribe(subject).await {
+        Ok(subscriber) => subscriber,
+        Err(err) => {
+            error!("Failed to subscribe the synced events: {}", err);
+            return;
+        }
+    };
+    info!("Reload controller is running...");
+
+    while let Some(message) = subscriber.next().await {
+        let Some((playbook, character)) =
+            message.subject.strip_prefix(&format!("{}.", reload::SYNCED_SUBJECT)).and_then(|s| s.split_once('.'))
+        else {
+            continue;
+        };
+        let pod = String::from_utf8_lossy(&message.payload).to_string();
+
+        // The hooks of different pods are run concurrently.
+        let (ctx, playbook, character) = (ctx.clone(), playbook.to_string(), character.to_string());
+        tokio::spawn(async move {
+            if let Err(err) = handle(&ctx, &playbook, &character, &pod).await {
+                error!("Failed to run the reload hook of actor {} in pod {}: {}", character, pod, err);
+            }
+        });
+    }
+}
+
+async fn handle(ctx: &Context, playbook: &str, character: &str, pod: &str) -> anyhow::Result<()> {
+    let playbook = playbook::get(&ctx.k8s, playbook).await?;
+    let client = cluster::client(&ctx.k8s, &ctx.config.namespace, &playbook).await?;
+    let namespace = namespace::of(&playbook);
+    let actor = actor::get(&client, &namespace, &namespace::actor_name(&playbook, character)).await?;
+    let Some(hook) = reload::hook(&actor)? else {
+        return Ok(());
+    };
+
+    let api: Api<Pod> = Api::namespaced(client, &namespace);
+    let params = AttachParams::default().container(character).stdout(false);
+    let mut process = api.exec(pod, hook.command(), &params).await?;
+    let status = process.take_status().ok_or_else(|| anyhow::anyhow!("the status of hook is taken"))?;
+
+    let mut stderr = String::new();
+    if let Some(mut reader) = process.stderr() {
+        reader.read_to_string(&mut stderr).await?;
+    }
+    let status = status.await;
+    process.join().await?;
+
+    match status.and_then(|status| status.status).as_deref() {
+        Some("Success") => info!("Ran the reload hook of actor {} in pod {}", character, pod),
+        _ => anyhow::bail!("the hook {:?} failed: {}", hook.command(), stderr.trim()),
+    }
+
+    Ok(())
+}
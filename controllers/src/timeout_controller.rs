@@ -12,21 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use amp_common::resource::Playbook;
-use amp_resources::playbook::delete;
+use amp_resources::hibernation::{Reason, HIBERNATED_CONDITION_TYPE};
+use amp_resources::playbook::LAST_ACTIVITY_ANNOTATION;
+use amp_resources::playbook::{delete, ARCHIVED_ANNOTATION, EXPIRY_WARNED_ANNOTATION, IDLE_TIMEOUT_ANNOTATION};
+use amp_resources::uptime::Action;
+use amp_resources::workspace::{self, WorkspacePolicy};
+use amp_resources::{actor, hibernation, metrics, namespace, playbook, trash, uptime};
 use chrono::{DateTime, Duration, TimeDelta, Utc};
 use futures::{future, StreamExt};
+use kube::runtime::events::{Event, EventType, Recorder, Reporter};
 use kube::Client;
 use kube::{
     runtime::{reflector, watcher, WatchStreamExt},
-    Api,
+    Api, Resource, ResourceExt,
 };
 use tracing::{error, info, warn};
 
 use crate::context::Context;
 
+/// How often the playbooks are checked.
+const INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
 /// The strategy is to evaluate the execution status of the playbook.
 enum Strategy {
     /// Handling the expiration of the playbook.
@@ -50,6 +60,10 @@ impl From<DateTime<Utc>> for Strategy {
 
 pub async fn new(ctx: &Arc<Context>) {
     let client = ctx.k8s.clone();
+    let namespace = ctx.config.namespace.clone();
+    let warning = ctx.config.ttl_warning_before;
+    let retention = ctx.config.trash_retention;
+    let prometheus = ctx.config.prometheus_url.clone();
     let api = Api::<Playbook>::all(client.clone());
     let config = watcher::Config::default();
     let (reader, writer) = reflector::store();
@@ -62,46 +76,182 @@ pub async fn new(ctx: &Arc<Context>) {
         }
         info!("Timeout controller is running...");
         loop {
+            // Reload the workspace policies every round, so changes apply without restarting.
+            let policies = workspace::load(&client, &namespace).await.unwrap_or_else(|err| {
+                error!("Load workspace policies failed: {}", err.to_string());
+                HashMap::new()
+            });
+
             for p in reader.state() {
-                if let Err(err) = handle(p.as_ref(), &client).await {
-                    error!("Delete playbook failed: {}", err.to_string());
+                // The playbook used in this round is not idle, it's checked again in the next one.
+                if let Some(url) = &prometheus {
+                    match traffic(&client, url, p.as_ref()).await {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(err) => warn!("Failed to record the traffic of playbook {}: {}", p.name_any(), err),
+                    }
+                }
+
+                let policy = policies.get(&workspace::of(&p)).cloned().unwrap_or_default();
+                if let Err(err) = handle(p.as_ref(), &policy, &client, warning, retention).await {
+                    error!("Handle playbook failed: {}", err.to_string());
                 }
             }
-            tokio::time::sleep(std::time::Duration::from_secs(5 * 60)).await;
+            tokio::time::sleep(INTERVAL).await;
         }
     });
 
     rf.applied_objects().for_each(|_| future::ready(())).await;
 }
 
-async fn handle(playbook: &Playbook, client: &Client) -> anyhow::Result<()> {
-    if let Some(ttl) = playbook
-        .metadata
-        .annotations
-        .as_ref()
-        .and_then(|annotations| annotations.get("ttl"))
-        .and_then(|ttl_str| ttl_str.parse::<i64>().ok())
-    {
-        if let Some(timestamp) = playbook.metadata.creation_timestamp.as_ref() {
-            let expiration_time = timestamp.0 + Duration::seconds(ttl);
-            let stratege = Strategy::from(expiration_time);
-            match stratege {
-                Strategy::Expired => {
-                    if let Some(name) = &playbook.metadata.name {
-                        delete(client, name).await?;
-                    }
-                }
-                Strategy::Remain(time) => {
-                    if time == Duration::days(3) {
-                        send_message().await;
-                    }
-                }
+async fn handle(
+    playbook: &Playbook,
+    policy: &WorkspacePolicy,
+    client: &Client,
+    warning: i64,
+    retention: i64,
+) -> anyhow::Result<()> {
+    let annotations = playbook.annotations();
+    let activity = last_activity(playbook);
+
+    // Purge the deleted playbook once its retention window in the trash elapses.
+    if let Some(deleted) = trash::deleted(playbook) {
+        if let Strategy::Expired = Strategy::from(deleted + Duration::seconds(retention)) {
+            info!("Purge the deleted playbook {}", playbook.name_any());
+            delete(client, &playbook.name_any()).await?;
+        }
+        return Ok(());
+    }
+
+    // Delete the expired playbook, and its namespace is deleted along with it as the owner.
+    if let (Some(ttl), Some(activity)) = (policy.ttl(playbook::ttl(playbook)), activity) {
+        let expiration = activity + Duration::seconds(ttl);
+        match Strategy::from(expiration) {
+            Strategy::Expired => {
+                info!("Delete the expired playbook {}", playbook.name_any());
+                delete(client, &playbook.name_any()).await?;
+                return Ok(());
+            }
+            Strategy::Remain(time) if time <= Duration::seconds(warning) => {
+                warn_expiring(client, playbook, expiration).await?;
+            }
+            Strategy::Remain(_) => {}
+        }
+    }
+
+    // The archived playbooks have been scaled to zero already.
+    if annotations.contains_key(ARCHIVED_ANNOTATION) {
+        return Ok(());
+    }
+
+    // Start or stop the playbook by its uptime schedule when the time comes.
+    if let Some((action, time)) = uptime::pending(playbook, Utc::now())? {
+        match action {
+            Action::Start if hibernation::hibernated(playbook) => {
+                hibernation::wake(client, playbook, Reason::Scheduled).await?;
+            }
+            Action::Stop if !hibernation::hibernated(playbook) => {
+                hibernation::hibernate(client, playbook, Reason::Scheduled).await?;
             }
+            _ => {}
+        }
+        uptime::applied(client, playbook, time).await?;
+        return Ok(());
+    }
+
+    let Some(last_activity) = activity else {
+        return Ok(());
+    };
+
+    if let Some(archive_after) = policy.archive_after {
+        if let Strategy::Expired = Strategy::from(last_activity + Duration::seconds(archive_after)) {
+            info!("Archive the idle playbook {}", playbook.name_any());
+            hibernation::scale_to_zero(client, playbook).await?;
+
+            let annotations = BTreeMap::from([(ARCHIVED_ANNOTATION.to_string(), Utc::now().to_rfc3339())]);
+            playbook::annotate(client, playbook, annotations).await?;
+            return Ok(());
+        }
+    }
+
+    // The hibernated playbooks are woken up by the apiserver on the next use.
+    if hibernation::hibernated(playbook) {
+        return Ok(());
+    }
+
+    let requested = annotations.get(IDLE_TIMEOUT_ANNOTATION).and_then(|value| value.parse::<i64>().ok());
+    if let Some(idle_timeout) = policy.idle_timeout(requested) {
+        if let Strategy::Expired = Strategy::from(last_activity + Duration::seconds(idle_timeout)) {
+            info!("Hibernate the idle playbook {}", playbook.name_any());
+            hibernation::hibernate(client, playbook, Reason::Activity).await?;
         }
     }
+
     Ok(())
 }
 
-async fn send_message() {
-    warn!("Email sending functionality is not implemented yet");
+/// Record the activity of the playbook if its ingresses served any requests
+/// since the last round, as the traffic to its actors doesn't go through the
+/// apiserver. The hibernated playbook is woken up by it as well. Returns
+/// true if it's recorded.
+async fn traffic(client: &Client, url: &str, playbook: &Playbook) -> anyhow::Result<bool> {
+    if trash::deleted(playbook).is_some() || playbook.annotations().contains_key(ARCHIVED_ANNOTATION) {
+        return Ok(false);
+    }
+    let actors: Vec<String> = actor::list_of(client, playbook).await?.iter().map(|actor| actor.name_any()).collect();
+    if actors.is_empty() {
+        return Ok(false);
+    }
+
+    let requests = metrics::requests(url, &namespace::of(playbook), &actors, INTERVAL).await?;
+    if requests < 1.0 {
+        return Ok(false);
+    }
+    hibernation::touch(client, playbook).await?;
+
+    Ok(true)
+}
+
+/// Returns the last time the playbook was used, either recorded in the annotation
+/// or the latest status transition, falling back to the creation time.
+fn last_activity(playbook: &Playbook) -> Option<DateTime<Utc>> {
+    let recorded = playbook
+        .annotations()
+        .get(LAST_ACTIVITY_ANNOTATION)
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|time| time.with_timezone(&Utc));
+    let transitioned = playbook.status.as_ref().and_then(|status| {
+        // The hibernation itself is not an activity of the playbook.
+        let conditions = status.conditions.iter().filter(|c| c.type_ != HIBERNATED_CONDITION_TYPE);
+        conditions.map(|condition| condition.last_transition_time.0).max()
+    });
+    let created = playbook.metadata.creation_timestamp.as_ref().map(|time| time.0);
+
+    [recorded, transitioned, created].into_iter().flatten().max()
+}
+
+/// Emit a warning event that the playbook is expiring, once per expiration time,
+/// it will be warned again if the expiration is postponed by new activities.
+async fn warn_expiring(client: &Client, playbook: &Playbook, expiration: DateTime<Utc>) -> anyhow::Result<()> {
+    let expiration = expiration.to_rfc3339();
+    if playbook.annotations().get(EXPIRY_WARNED_ANNOTATION) == Some(&expiration) {
+        return Ok(());
+    }
+
+    let reporter = Reporter { controller: "amp-controllers".into(), instance: None };
+    let recorder = Recorder::new(client.clone(), reporter, playbook.object_ref(&()));
+    let event = Event {
+        type_: EventType::Warning,
+        reason: "Expiring".into(),
+        note: Some(format!("The playbook will be deleted at {} unless it's used", expiration)),
+        action: "Reaping".into(),
+        secondary: None,
+    };
+    recorder.publish(event).await?;
+    warn!("The playbook {} will be expired at {}", playbook.name_any(), expiration);
+
+    let annotations = BTreeMap::from([(EXPIRY_WARNED_ANNOTATION.to_string(), expiration)]);
+    playbook::annotate(client, playbook, annotations).await?;
+
+    Ok(())
 }
@@ -0,0 +1,59 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use amp_resources::registry;
+use futures::future;
+use tracing::{error, info};
+
+use crate::context::Context;
+use crate::credentials_watcher;
+
+/// Mint new tokens for the cloud registries periodically before they expire,
+/// e.g. the ECR tokens expire every 12 hours, and sync them into the
+/// docker-registry Secrets of all the namespaces.
+pub async fn new(ctx: &Arc<Context>) {
+    if ctx.config.registry_token_refresh_interval == 0 {
+        info!("Registry token refresher is disabled");
+        return future::pending().await;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(ctx.config.registry_token_refresh_interval));
+    loop {
+        interval.tick().await;
+        if let Err(err) = refresh(ctx).await {
+            error!("Refresh the registry tokens failed: {}", err.to_string());
+        }
+    }
+}
+
+async fn refresh(ctx: &Arc<Context>) -> anyhow::Result<()> {
+    // Mint outside of the lock, the credentials are read by the reconcilers meanwhile.
+    let credentials = ctx.credentials.read().await.clone();
+    let minted = ctx.minted.read().await.clone();
+    let tokens = registry::refresh(&credentials, &minted).await;
+    if tokens.is_empty() {
+        return Ok(());
+    }
+
+    registry::merge(&mut *ctx.credentials.write().await, &tokens);
+    ctx.minted.write().await.extend(tokens.keys().cloned());
+
+    credentials_watcher::sync(ctx, &ctx.credentials.read().await).await?;
+    info!("The tokens of {} registries have been refreshed", tokens.len());
+
+    Ok(())
+}
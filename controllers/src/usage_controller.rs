@@ -0,0 +1,161 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use amp_common::resource::Actor;
+use amp_resources::usage::{self, Budget, Usage};
+use amp_resources::workspace::{self, WorkspacePolicy};
+use amp_resources::{deployment, namespace, playbook};
+use chrono::Utc;
+use futures::{future, StreamExt};
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::runtime::events::{Event, EventType, Recorder, Reporter};
+use kube::Client;
+use kube::{
+    runtime::{reflector, watcher, WatchStreamExt},
+    Api, Resource, ResourceExt,
+};
+use tracing::{error, info, warn};
+
+use crate::context::Context;
+
+/// Meter the runtime of the actors periodically, and alert the exceeded budgets.
+pub async fn new(ctx: &Arc<Context>) {
+    let client = ctx.k8s.clone();
+    let namespace = ctx.config.namespace.clone();
+    let api = Api::<Actor>::all(client.clone());
+    let config = watcher::Config::default();
+    let (reader, writer) = reflector::store();
+    let rf = reflector(writer, watcher(api, config));
+
+    tokio::spawn(async move {
+        if let Err(e) = reader.wait_until_ready().await {
+            error!("Failed to wait until ready: {:?}", e);
+            return;
+        }
+        info!("Usage controller is running...");
+        loop {
+            // Reload the workspace policies every round, so the budget changes apply without restarting.
+            let policies = workspace::load(&client, &namespace).await.unwrap_or_else(|err| {
+                error!("Load workspace policies failed: {}", err.to_string());
+                HashMap::new()
+            });
+            let workspaces = workspaces(&client).await.unwrap_or_else(|err| {
+                error!("Load workspaces of playbooks failed: {}", err.to_string());
+                HashMap::new()
+            });
+
+            // The usages are loaded once per namespace, instead of once per actor.
+            let mut usages = HashMap::new();
+            for actor in reader.state() {
+                let Some(namespace) = actor.namespace() else {
+                    continue;
+                };
+                let workspace = workspaces.get(&namespace).cloned();
+                let workspace = workspace.unwrap_or_else(|| workspace::DEFAULT_WORKSPACE.to_string());
+                let policy = policies.get(&workspace).cloned().unwrap_or_default();
+                if !usages.contains_key(&namespace) {
+                    match usage::load(&client, &namespace).await {
+                        Ok(loaded) => usages.insert(namespace.clone(), loaded),
+                        Err(err) => {
+                            error!("Load usages of namespace {} failed: {}", namespace, err.to_string());
+                            continue;
+                        }
+                    };
+                }
+                let current = usage::of(&usages[&namespace], actor.as_ref(), Utc::now());
+                if let Err(err) = handle(actor.as_ref(), current, &policy, &client).await {
+                    error!("Handle usage of actor failed: {}", err.to_string());
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(5 * 60)).await;
+        }
+    });
+
+    rf.applied_objects().for_each(|_| future::ready(())).await;
+}
+
+/// Returns the workspaces of the playbooks, keyed by their namespaces.
+async fn workspaces(client: &Client) -> anyhow::Result<HashMap<String, String>> {
+    let playbooks = playbook::list(client).await?;
+
+    Ok(playbooks.iter().map(|p| (namespace::of(p), workspace::of(p))).collect())
+}
+
+async fn handle(actor: &Actor, mut usage: Usage, policy: &WorkspacePolicy, client: &Client) -> anyhow::Result<()> {
+    let now = Utc::now();
+    usage.add_runtime(replicas(client, actor).await?, now);
+
+    let exceeded = usage.exceeded(policy);
+    for budget in &exceeded {
+        if !usage.alerted.contains(budget) {
+            alert(client, actor, *budget, policy).await?;
+            usage.alerted.push(*budget);
+        }
+    }
+
+    // Only the new builds are paused, the running workloads are kept.
+    let paused = policy.pause_builds_over_budget && exceeded.contains(&Budget::BuildMinutes);
+    if paused && !usage::builds_paused(actor) {
+        warn!("Pause the builds of actor {} as its build minutes budget is exceeded", actor.name_any());
+    }
+    usage::save(client, actor, &usage, paused).await?;
+
+    Ok(())
+}
+
+/// Returns the number of ready replicas of actor.
+async fn replicas(client: &Client, actor: &Actor) -> anyhow::Result<i32> {
+    let Some(namespace) = actor.namespace() else {
+        return Ok(0);
+    };
+    let api: Api<Deployment> = Api::namespaced(client.clone(), &namespace);
+    let deployment = api.get_opt(&deployment::name(actor)).await?;
+
+    Ok(deployment.and_then(|d| d.status).and_then(|status| status.ready_replicas).unwrap_or_default())
+}
+
+/// Emit a warning event that the budget of actor is exceeded, once per month.
+async fn alert(client: &Client, actor: &Actor, budget: Budget, policy: &WorkspacePolicy) -> anyhow::Result<()> {
+    let note = match budget {
+        Budget::BuildMinutes => {
+            format!(
+                "The build minutes budget {} of this month is exceeded",
+                policy.build_minutes_budget.unwrap_or_default()
+            )
+        }
+        Budget::RuntimeHours => {
+            format!(
+                "The runtime hours budget {} of this month is exceeded",
+                policy.runtime_hours_budget.unwrap_or_default()
+            )
+        }
+    };
+
+    let reporter = Reporter { controller: "amp-controllers".into(), instance: None };
+    let recorder = Recorder::new(client.clone(), reporter, actor.object_ref(&()));
+    let event = Event {
+        type_: EventType::Warning,
+        reason: "BudgetExceeded".into(),
+        note: Some(note.clone()),
+        action: "Metering".into(),
+        secondary: None,
+    };
+    recorder.publish(event).await?;
+    warn!("{} by actor {}", note, actor.name_any());
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_resources::rbac::{cluster_role, cluster_role_binding, Component};
+use clap::Parser;
+
+/// Generate the least privilege RBAC manifests for installing Amphitheatre.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Names of the components, separated by comma, `controllers` and `apiserver`.
+    /// If not specified, generate for all components.
+    #[arg(short, long)]
+    components: Option<String>,
+    /// The namespace that Amphitheatre is installed in.
+    #[arg(short, long, default_value = "amp-system")]
+    namespace: String,
+    /// The name of the ServiceAccount bound to the roles.
+    #[arg(short, long, default_value = "default")]
+    service_account: String,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let components: Vec<Component> = match &args.components {
+        None => Component::ALL.to_vec(),
+        Some(names) => names.split(',').map(parse).collect(),
+    };
+
+    for component in components {
+        println!("{}\n---\n", serde_yaml::to_string(&cluster_role(component)).unwrap());
+        let binding = cluster_role_binding(component, &args.namespace, &args.service_account);
+        println!("{}\n---\n", serde_yaml::to_string(&binding).unwrap());
+    }
+}
+
+/// Parse the component name, exit if it's not valid.
+fn parse(name: &str) -> Component {
+    match name.trim() {
+        "controllers" => Component::Controllers,
+        "apiserver" => Component::ApiServer,
+        name => {
+            eprintln!("The given component is not valid: {}", name);
+            std::process::exit(1);
+        }
+    }
+}
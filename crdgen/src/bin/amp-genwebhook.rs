@@ -0,0 +1,52 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+
+use amp_resources::policy::webhook;
+use clap::Parser;
+
+/// Generate the ValidatingWebhookConfiguration enforcing the registry policy on the actors.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// The namespace that Amphitheatre is installed in.
+    #[arg(short, long, default_value = "amp-system")]
+    namespace: String,
+    /// The name of the Service of apiserver, it must serve TLS.
+    #[arg(short, long, default_value = "amp-apiserver")]
+    service: String,
+    /// The port of the Service of apiserver.
+    #[arg(short, long, default_value_t = 443)]
+    port: i32,
+    /// The path of the PEM encoded CA bundle signing the certificate of apiserver,
+    /// if not specified, it's expected to be injected, e.g. by cert-manager.
+    #[arg(short, long)]
+    ca_bundle: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let ca_bundle = args.ca_bundle.as_ref().map(|path| match fs::read(path) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("Failed to read the CA bundle {}: {}", path, err);
+            std::process::exit(1);
+        }
+    });
+
+    let configuration = webhook(&args.namespace, &args.service, args.port, ca_bundle);
+    println!("{}", serde_yaml::to_string(&configuration).unwrap());
+}
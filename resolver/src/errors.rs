@@ -61,6 +61,18 @@ pub enum ResolveError {
 
     #[error("NameNotSet")]
     NameNotSet,
+
+    #[error("ImageNotAllowed: {0}")]
+    ImageNotAllowed(String),
+
+    #[error("ReferenceNotFound: {0}")]
+    ReferenceNotFound(String),
+
+    #[error("InvalidHubAddress: {0}")]
+    InvalidHubAddress(String),
+
+    #[error("HubNotConfigured")]
+    HubNotConfigured,
 }
 
 pub type Result<T, E = ResolveError> = std::result::Result<T, E>;
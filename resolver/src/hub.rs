@@ -0,0 +1,162 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use amp_common::config::Credentials;
+use amp_common::resource::CharacterSpec;
+use amp_common::schema::Character;
+use amp_resources::image;
+use tracing::{debug, info};
+
+use crate::errors::{ResolveError, Result};
+
+/// The media type of the characters published to the OCI hub.
+pub const CHARACTER_MEDIA_TYPE: &str = "application/vnd.amphitheatre.character.v1+toml";
+
+/// How long to wait for the manifest served by the HTTP index.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the fetched characters are cached, the republished ones are fetched again after it.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The hub index configured by the operator.
+static HUB: OnceLock<Hub> = OnceLock::new();
+
+/// The index the characters are fetched from by their qualified names,
+/// e.g. `amphitheatre/redis@1.2.0`. The fetched characters are cached along
+/// with the time they're fetched.
+struct Hub {
+    index: Index,
+    client: reqwest::Client,
+    cache: RwLock<HashMap<String, (CharacterSpec, Instant)>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Index {
+    /// The manifests are served at `{url}/{org}/{name}/{version}/amp.toml`.
+    Http(String),
+    /// The manifests are pushed as the artifacts `{repository}/{org}/{name}:{version}`.
+    Oci(String),
+}
+
+/// Configure the hub index, an HTTP(S) URL or an OCI repository prefixed by
+/// `oci://`, e.g. `oci://ghcr.io/amphitheatre-app/hub`.
+pub fn configure(url: &str) -> Result<()> {
+    let index = index(url)?;
+    let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build();
+    let client = client.map_err(|e| ResolveError::FetchingError(e.to_string()))?;
+
+    HUB.set(Hub { index, client, cache: RwLock::new(HashMap::new()) }).ok();
+    info!("Configured the character hub: {}", url);
+
+    Ok(())
+}
+
+/// Parse the address of the hub index.
+fn index(url: &str) -> Result<Index> {
+    let url = url.trim_end_matches('/');
+    match url.split_once("://") {
+        Some(("oci", repository)) if !repository.is_empty() => Ok(Index::Oci(repository.to_string())),
+        Some(("http" | "https", host)) if !host.is_empty() => Ok(Index::Http(url.to_string())),
+        _ => Err(ResolveError::InvalidHubAddress(url.to_string())),
+    }
+}
+
+/// Returns true if the name is qualified by its organization, e.g.
+/// `amphitheatre/redis`, which is fetched from the hub index instead of the
+/// characters registered in the cluster.
+pub fn qualified(name: &str) -> bool {
+    name.contains('/')
+}
+
+/// Split the version from the name in the form of `{org}/{name}@{version}`,
+/// the given version is used if the name doesn't have one.
+pub fn parse<'a>(name: &'a str, version: &'a str) -> (&'a str, &'a str) {
+    match name.split_once('@') {
+        Some((name, version)) => (name, version),
+        None => (name, version),
+    }
+}
+
+/// Load the character from the hub index, or from the cache if it's fetched before.
+pub async fn load(credentials: &Credentials, name: &str, version: &str) -> Result<CharacterSpec> {
+    let hub = HUB.get().ok_or(ResolveError::HubNotConfigured)?;
+    let (name, version) = parse(name, version);
+    let key = format!("{}@{}", name, version);
+
+    let cached = hub.cache.read().ok().and_then(|cache| cache.get(&key).cloned());
+    if let Some((character, _)) = cached.filter(|(_, fetched)| fetched.elapsed() < CACHE_TTL) {
+        debug!("Loaded character {} from the hub cache", key);
+        return Ok(character);
+    }
+
+    let data = match &hub.index {
+        Index::Http(url) => {
+            let url = format!("{}/{}/{}/amp.toml", url, name, version);
+            debug!("Fetching character {} from {}", key, url);
+            let response = hub.client.get(&url).send().await;
+            let response = response.map_err(|e| ResolveError::FetchingError(e.to_string()))?;
+            let response = response.error_for_status().map_err(|e| ResolveError::FetchingError(e.to_string()))?;
+            response.bytes().await.map_err(|e| ResolveError::FetchingError(e.to_string()))?.to_vec()
+        }
+        Index::Oci(repository) => {
+            let reference = format!("{}/{}:{}", repository, name, version);
+            debug!("Pulling character {} from {}", key, reference);
+            image::artifact(&reference, CHARACTER_MEDIA_TYPE, credentials).await.map_err(ResolveError::ResourceError)?
+        }
+    };
+    let data = std::str::from_utf8(&data).map_err(ResolveError::ConvertBytesError)?;
+    let manifest: Character = toml::from_str(data).map_err(ResolveError::TomlParseFailed)?;
+    let character = CharacterSpec::from(&manifest);
+
+    if let Ok(mut cache) = hub.cache.write() {
+        cache.insert(key, (character.clone(), Instant::now()));
+    }
+
+    Ok(character)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qualified() {
+        assert!(qualified("amphitheatre/redis"));
+        assert!(qualified("amphitheatre/redis@1.2.0"));
+        assert!(!qualified("redis"));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("amphitheatre/redis@1.2.0", "1.0.0"), ("amphitheatre/redis", "1.2.0"));
+        assert_eq!(parse("amphitheatre/redis", "1.0.0"), ("amphitheatre/redis", "1.0.0"));
+    }
+
+    #[test]
+    fn test_index() {
+        let oci = index("oci://ghcr.io/amphitheatre-app/hub/").unwrap();
+        assert_eq!(oci, Index::Oci("ghcr.io/amphitheatre-app/hub".into()));
+        let http = index("https://hub.amphitheatre.app").unwrap();
+        assert_eq!(http, Index::Http("https://hub.amphitheatre.app".into()));
+
+        assert!(index("oci://").is_err());
+        assert!(index("ftp://hub.amphitheatre.app").is_err());
+        assert!(index("hub.amphitheatre.app").is_err());
+        assert!(configure("hub.amphitheatre.app").is_err());
+    }
+}
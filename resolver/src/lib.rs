@@ -12,19 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use amp_common::resource::CharacterSpec;
+use amp_common::resource::{CharacterSpec, Partner, Preface};
 use amp_common::schema::{Character, GitReference};
-use amp_common::scm::client::Client as ScmClient;
 use amp_common::{config::Credentials, resource::ActorSpec};
-use amp_resources::character;
+use amp_resources::detection::{self, Decision};
+use amp_resources::policy::RegistryPolicy;
+use amp_resources::{character, helm, monorepo, upload};
 use errors::{ResolveError, Result};
 use kube::Client as KubeClient;
+use remote::Remote;
 use tracing::debug;
 
 pub mod errors;
+pub mod hub;
 pub mod partner;
 pub mod patches;
 pub mod preface;
+pub mod remote;
 pub mod utils;
 
 const CATALOG_REPO_URL: &str = "https://github.com/amphitheatre-app/catalog.git";
@@ -42,21 +46,40 @@ pub fn load_from_catalog(credentials: &Credentials, name: &str, version: &str) -
 
 /// Load manifest from remote VCS (like github) and return the actor spec.
 pub fn load_from_source(credentials: &Credentials, reference: &GitReference) -> Result<CharacterSpec> {
-    let client = ScmClient::init(credentials, &reference.repo).map_err(ResolveError::SCMError)?;
+    let client = Remote::open(credentials, &reference.repo)?;
 
     let reference = patches::source(&client, reference)?;
-    let path = reference.path.clone().unwrap_or(".amp.toml".into());
+    let path = manifest(reference.path.as_deref());
     let repo = utils::repo(&reference.repo)?;
 
-    let content = client
-        .contents()
-        .find(&repo, &path, &reference.rev())
-        .map_err(|e| ResolveError::FetchingError(e.to_string()))?;
-    let data = std::str::from_utf8(&content.data).map_err(ResolveError::ConvertBytesError)?;
+    let content = client.read(&repo, &path, &reference.rev())?;
+    let data = std::str::from_utf8(&content).map_err(ResolveError::ConvertBytesError)?;
     debug!("The `.amp.toml` content of {} is:\n{:?}", repo, data);
 
     let manifest: Character = toml::from_str(data).map_err(ResolveError::TomlParseFailed)?;
-    Ok(CharacterSpec::from(&manifest))
+    let mut character = CharacterSpec::from(&manifest);
+
+    // The character in a directory of monorepo is built from that directory,
+    // unless its manifest declares the build context.
+    let directory = path.rsplit_once('/').map(|(directory, _)| directory);
+    if let Some(directory) =
+        directory.filter(|_| utils::repo(&character.meta.repository).is_ok_and(|other| other == repo))
+    {
+        let build = character.build.get_or_insert_with(Default::default);
+        build.context.get_or_insert_with(|| directory.to_string());
+    }
+
+    Ok(character)
+}
+
+/// Returns the path of manifest in the repository, the path may be the
+/// directory of the character in a monorepo, which holds its `.amp.toml`.
+fn manifest(path: Option<&str>) -> String {
+    match path.map(|path| path.trim_start_matches("./").trim_matches('/')) {
+        None | Some("") | Some(".") => ".amp.toml".into(),
+        Some(path) if path.ends_with(".toml") => path.into(),
+        Some(path) => format!("{}/.amp.toml", path),
+    }
 }
 
 /// Load manifest from Kubernetes cluster and return the actor spec.
@@ -65,18 +88,91 @@ pub async fn load_from_cluster(client: &KubeClient, name: &str) -> Result<Charac
     Ok(character.spec)
 }
 
-/// Read Character manifest and return the actor spec.
-pub fn to_actor(character: &CharacterSpec, credentials: &Credentials) -> Result<ActorSpec> {
+/// Read Character manifest and return the actor spec,
+/// the image must be allowed by the registry policy.
+pub fn to_actor(character: &CharacterSpec, credentials: &Credentials, policy: &RegistryPolicy) -> Result<ActorSpec> {
+    let actor = render(character, credentials)?;
+    policy.check(&actor).map_err(|_| ResolveError::ImageNotAllowed(actor.image.clone()))?;
+
+    Ok(actor)
+}
+
+/// Validate the source reference of the character exists in the SCM before
+/// it's added to a playbook, so a bad reference fails the resolution of its
+/// partner instead of the build later.
+pub fn validate(character: &CharacterSpec, credentials: &Credentials) -> Result<()> {
+    let actor = ActorSpec::from(character);
+
+    // Nothing will be built from the source in SCM.
+    let uploaded = upload::id(&character.meta.repository).is_some();
+    if helm::chart(character).is_some() || !actor.image.is_empty() || actor.live || uploaded {
+        return Ok(());
+    }
+
+    let source = actor.source.as_ref().ok_or(ResolveError::SourceNotSet)?;
+    let client = Remote::open(credentials, &source.repo)?;
+    patches::source(&client, source)?;
+
+    Ok(())
+}
+
+/// Detect how to build the actor from the files in the build context of its repository.
+pub fn detect(credentials: &Credentials, actor: &ActorSpec) -> Result<Decision> {
+    // The live actors and the uploaded sources are from the local workspace, there is no repository to inspect.
+    if actor.live || upload::of(actor).is_some() {
+        return Ok(Decision::Unrecognized);
+    }
+    let Some(source) = actor.source.as_ref() else {
+        return Ok(detection::decide(false, &[]));
+    };
+    let client = Remote::open(credentials, &source.repo)?;
+    let repo = utils::repo(&source.repo)?;
+    let context = monorepo::context(actor);
+
+    let mut files = vec![];
+    for file in detection::CANDIDATES {
+        let path = match &context {
+            Some(context) => format!("{}/{}", context, file),
+            None => file.to_string(),
+        };
+        if client.read(&repo, &path, &source.rev()).is_ok() {
+            files.push(*file);
+        }
+    }
+    debug!("Found the files {:?} in the repository {}", files, repo);
+
+    Ok(detection::decide(true, &files))
+}
+
+fn render(character: &CharacterSpec, credentials: &Credentials) -> Result<ActorSpec> {
     let repo = &character.meta.repository;
-    let client = ScmClient::init(credentials, repo).map_err(ResolveError::SCMError)?;
+
+    // The chart characters are deployed by Helm, there is nothing to build or sync.
+    if helm::chart(character).is_some() {
+        let mut actor = ActorSpec::from(character);
+        actor.image.clone_from(repo);
+        actor.source = None;
+        actor.live = false;
+        return Ok(actor);
+    }
 
     let mut actor = ActorSpec::from(character);
 
-    // Return the actor if the image is already set.
+    // Return the actor if the image is already set, e.g. a published image
+    // without a repository, there is nothing to solve.
     if !actor.image.is_empty() {
         return Ok(actor);
     }
 
+    // The uploaded source is built as it is, it's tagged with its id.
+    if let Some(id) = upload::id(repo).filter(|_| !actor.live) {
+        actor.image = patches::image(credentials, &actor, &id[..id.len().min(12)])?;
+        actor.source = Some(GitReference { repo: repo.clone(), rev: Some(id.to_string()), ..GitReference::default() });
+        return Ok(actor);
+    }
+
+    let client = Remote::open(credentials, repo)?;
+
     // Patch the source and image if the actor is not live.
     // it will be build with the builders later, so these must be valid.
     if !actor.live {
@@ -93,3 +189,36 @@ pub fn to_actor(character: &CharacterSpec, credentials: &Credentials) -> Result<
 
     Ok(actor)
 }
+
+/// Resolve the preface and all of its partners recursively,
+/// returns the characters in the order they were resolved.
+pub async fn resolve(client: &KubeClient, credentials: &Credentials, preface: &Preface) -> Result<Vec<CharacterSpec>> {
+    let characters = vec![preface::load(client, credentials, preface).await?];
+
+    partners(client, credentials, characters).await
+}
+
+/// Resolve all the partners of the characters recursively, the partners
+/// declared by the characters already are skipped, returns the characters
+/// followed by the partners in the order they were resolved.
+pub async fn partners(
+    client: &KubeClient,
+    credentials: &Credentials,
+    mut characters: Vec<CharacterSpec>,
+) -> Result<Vec<CharacterSpec>> {
+    let mut index = 0;
+    while index < characters.len() {
+        let partners: Vec<(String, Partner)> =
+            characters[index].partners.iter().flatten().map(|(k, v)| (k.clone(), v.clone())).collect();
+        for (name, partner) in partners {
+            if characters.iter().any(|character| character.meta.name == name) {
+                continue;
+            }
+            debug!("Resolving the partner {} of {}", name, characters[index].meta.name);
+            characters.push(partner::load(client, credentials, &name, &partner).await?);
+        }
+        index += 1;
+    }
+
+    Ok(characters)
+}
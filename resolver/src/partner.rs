@@ -14,7 +14,7 @@
 
 use crate::{
     errors::{ResolveError, Result},
-    load_from_catalog, load_from_cluster, load_from_source,
+    hub, load_from_catalog, load_from_cluster, load_from_source,
 };
 use amp_common::{
     config::Credentials,
@@ -34,6 +34,7 @@ pub async fn load(
             let registry = p.registry.clone().unwrap_or_else(|| "catalog".to_string());
             return match registry.as_str() {
                 "catalog" => load_from_catalog(credentials, name, &p.version),
+                "hub" if hub::qualified(name) => hub::load(credentials, name, &p.version).await,
                 "hub" => load_from_cluster(client, name).await,
                 x => Err(ResolveError::UnknownCharacterRegistry(x.to_string())),
             };
@@ -15,21 +15,32 @@
 use amp_common::config::{Credential, Credentials};
 use amp_common::resource::ActorSpec;
 use amp_common::schema::GitReference;
-use amp_common::scm::client::Client;
+use semver::{Version, VersionReq};
 use tracing::debug;
 
 use crate::errors::{ResolveError, Result};
+use crate::remote::Remote;
 use crate::utils;
 
-pub fn source(client: &Client, source: &GitReference) -> Result<GitReference> {
+pub fn source(client: &Remote, source: &GitReference) -> Result<GitReference> {
     let mut actual = source.clone();
+    let repo = utils::repo(&actual.repo)?;
 
-    // Return it if revision was provided.
-    if actual.rev.is_some() {
+    // Return it if revision was provided and it does exist.
+    if let Some(rev) = &actual.rev {
+        commit(client, &repo, rev)?;
+        return Ok(actual);
+    }
+
+    // Resolve the semver range of tag to the highest version matching it,
+    // the tag is replaced by the version, e.g. `^1.2` to `v1.4.0`.
+    if let Some(range) = actual.tag.as_deref().and_then(range) {
+        let tag = highest(client, &repo, &range)?;
+        actual.rev = Some(commit(client, &repo, &tag)?);
+        actual.tag = Some(tag);
         return Ok(actual);
     }
 
-    let repo = utils::repo(&actual.repo)?;
     let reference: String;
 
     debug!("the repo name parsed from repository address is: {}", repo);
@@ -42,8 +53,8 @@ pub fn source(client: &Client, source: &GitReference) -> Result<GitReference> {
     } else if let Some(branch) = &actual.branch {
         reference = branch.to_string();
     } else {
-        let repository = client.repositories().find(&repo).map_err(|e| ResolveError::FetchingError(e.to_string()))?;
-        reference = repository.unwrap().branch;
+        let branch = client.default_branch(&repo)?;
+        reference = branch.ok_or_else(|| ResolveError::ReferenceNotFound(actual.repo.clone()))?;
 
         // Save it for other purposes,
         // such as a reference value when re-modifying
@@ -51,12 +62,39 @@ pub fn source(client: &Client, source: &GitReference) -> Result<GitReference> {
     }
 
     // Get its real latest revision according to the reference
-    let commit = client.git().find_commit(&repo, &reference).unwrap();
-    actual.rev = Some(commit.unwrap().sha);
+    actual.rev = Some(commit(client, &repo, &reference)?);
 
     Ok(actual)
 }
 
+/// Returns the semver range of the tag, e.g. `^1.2` or `>=1.0, <2`, None if
+/// it's the name or the exact version of a tag, e.g. `v1.2.0`.
+pub fn range(tag: &str) -> Option<VersionReq> {
+    if Version::parse(tag.trim_start_matches('v')).is_ok() {
+        return None;
+    }
+
+    VersionReq::parse(tag).ok()
+}
+
+/// Returns the highest tag of the repository matching the range, the tags
+/// are versions optionally prefixed by `v`, the others are ignored.
+fn highest(client: &Remote, repo: &str, range: &VersionReq) -> Result<String> {
+    let tags = client.tags(repo)?;
+    let versions = tags.into_iter().filter_map(|tag| Some((Version::parse(tag.trim_start_matches('v')).ok()?, tag)));
+    let highest = versions.filter(|(version, _)| range.matches(version)).max_by(|(a, _), (b, _)| a.cmp(b));
+    debug!("The highest tag of {} matching {} is {:?}", repo, range, highest);
+
+    highest.map(|(_, tag)| tag).ok_or_else(|| ResolveError::ReferenceNotFound(format!("{}@{}", repo, range)))
+}
+
+/// Returns the sha of the commit the reference (branch, tag or revision) points to.
+fn commit(client: &Remote, repo: &str, reference: &str) -> Result<String> {
+    let commit = client.commit(repo, reference)?;
+
+    commit.ok_or_else(|| ResolveError::ReferenceNotFound(format!("{}@{}", repo, reference)))
+}
+
 pub fn image(credentials: &Credentials, spec: &ActorSpec, tag: &str) -> Result<String> {
     // Generate image name based on the current registry and character name & revision
     if let Some(credential) = credentials.default_registry() {
@@ -0,0 +1,227 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use amp_common::config::{Credential, Credentials};
+use amp_common::scm::client::Client as ScmClient;
+use amp_resources::git;
+use tracing::debug;
+
+use crate::errors::{ResolveError, Result};
+
+/// The sequence of the working directories of the SSH remotes in this process.
+static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+/// The repository the characters are resolved from. It's read from the API
+/// of its provider over HTTPS, or cloned over SSH with the private key if
+/// there are only the SSH credentials of its host, as the keys can't
+/// authenticate the APIs.
+pub enum Remote {
+    Scm(ScmClient),
+    Ssh(SshRemote),
+}
+
+impl Remote {
+    /// Open the repository with the credentials of its host.
+    pub fn open(credentials: &Credentials, repo: &str) -> Result<Self> {
+        let host = git::endpoint(repo).map(|(_, host)| host).unwrap_or_default();
+        let same_host = |server: &str| git::endpoint(server).is_some_and(|(_, other)| other == host);
+        let repositories = credentials.repositories.as_deref().unwrap_or_default();
+
+        let key = repositories.iter().find(|credential| git::ssh(&credential.server) && same_host(&credential.server));
+        let token =
+            repositories.iter().any(|credential| !git::ssh(&credential.server) && same_host(&credential.server));
+        if let (true, Some(credential), false) = (git::ssh(repo), key, token) {
+            return SshRemote::new(repo, &host, &credential.token_any()).map(Remote::Ssh);
+        }
+
+        let mut credentials = credentials.clone();
+        if let Some(repositories) = credentials.repositories.as_mut() {
+            repositories.retain(|credential| !git::ssh(&credential.server));
+        }
+        ScmClient::init(&credentials, &git::https(repo)).map(Remote::Scm).map_err(ResolveError::SCMError)
+    }
+
+    /// Returns the content of the file at the revision, `repo` is the
+    /// `{owner}/{name}` of the repository for the provider APIs.
+    pub fn read(&self, repo: &str, path: &str, rev: &str) -> Result<Vec<u8>> {
+        match self {
+            Remote::Scm(client) => {
+                let content = client.contents().find(repo, path, rev);
+                content.map(|content| content.data).map_err(|e| ResolveError::FetchingError(e.to_string()))
+            }
+            Remote::Ssh(remote) => remote.read(path, rev),
+        }
+    }
+
+    /// Returns the sha of the commit the reference (branch, tag or revision) points to.
+    pub fn commit(&self, repo: &str, reference: &str) -> Result<Option<String>> {
+        match self {
+            Remote::Scm(client) => {
+                let commit = client.git().find_commit(repo, reference);
+                commit
+                    .map(|commit| commit.map(|commit| commit.sha))
+                    .map_err(|e| ResolveError::FetchingError(e.to_string()))
+            }
+            Remote::Ssh(remote) => remote.commit(reference),
+        }
+    }
+
+    /// Returns the names of the tags of the repository.
+    pub fn tags(&self, repo: &str) -> Result<Vec<String>> {
+        match self {
+            Remote::Scm(client) => {
+                let tags = client.git().list_tags(repo, Default::default());
+                let tags = tags.map_err(|e| ResolveError::FetchingError(e.to_string()))?;
+                Ok(tags.into_iter().map(|tag| tag.name).collect())
+            }
+            Remote::Ssh(remote) => remote.tags(),
+        }
+    }
+
+    /// Returns the default branch of the repository, None if it's not found.
+    pub fn default_branch(&self, repo: &str) -> Result<Option<String>> {
+        match self {
+            Remote::Scm(client) => {
+                let repository = client.repositories().find(repo);
+                let repository = repository.map_err(|e| ResolveError::FetchingError(e.to_string()))?;
+                Ok(repository.map(|repository| repository.branch))
+            }
+            Remote::Ssh(remote) => remote.default_branch(),
+        }
+    }
+}
+
+/// The repository read by the git client over SSH, the host is verified with
+/// its well-known keys, the private key and the fetched objects are kept in
+/// a working directory removed along with it.
+pub struct SshRemote {
+    url: String,
+    directory: PathBuf,
+}
+
+impl SshRemote {
+    fn new(url: &str, host: &str, private_key: &str) -> Result<Self> {
+        let known_hosts = git::known_hosts(host)
+            .ok_or_else(|| ResolveError::FetchingError(format!("the host key of {} is unknown", host)))?;
+
+        let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        let directory = std::env::temp_dir().join(format!("amp-resolver-{}-{}", std::process::id(), sequence));
+        let remote = SshRemote { url: url.to_string(), directory };
+
+        let key = remote.directory.join("ssh");
+        let write = || -> std::io::Result<()> {
+            fs::create_dir_all(&remote.directory)?;
+            fs::write(&key, format!("{}\n", private_key.trim_end()))?;
+            fs::set_permissions(&key, fs::Permissions::from_mode(0o600))?;
+            fs::write(remote.directory.join("known_hosts"), format!("{}\n", known_hosts))
+        };
+        write().map_err(|e| ResolveError::FetchingError(e.to_string()))?;
+        remote.git(&["init", "--quiet", "--bare", "repo.git"])?;
+
+        Ok(remote)
+    }
+
+    fn read(&self, path: &str, rev: &str) -> Result<Vec<u8>> {
+        // The revision is fetched once, the files in it are read locally.
+        let object = format!("{}^{{commit}}", rev);
+        if self.git(&["--git-dir", "repo.git", "rev-parse", "--verify", "--quiet", &object]).is_err() {
+            self.git(&["--git-dir", "repo.git", "fetch", "--quiet", "--depth", "1", &self.url, rev])?;
+            return self.git(&["--git-dir", "repo.git", "show", &format!("FETCH_HEAD:{}", path)]);
+        }
+
+        self.git(&["--git-dir", "repo.git", "show", &format!("{}:{}", rev, path)])
+    }
+
+    fn commit(&self, reference: &str) -> Result<Option<String>> {
+        if reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(Some(reference.to_string()));
+        }
+
+        // The annotated tags are peeled to the commits they point to.
+        let refs = self.ls_remote(&[], &[reference])?;
+        let candidates = [
+            format!("refs/tags/{}^{{}}", reference),
+            format!("refs/tags/{}", reference),
+            format!("refs/heads/{}", reference),
+            reference.to_string(),
+        ];
+        let sha = candidates.iter().find_map(|name| refs.iter().find(|(_, r)| r == name).map(|(sha, _)| sha.clone()));
+
+        Ok(sha)
+    }
+
+    fn tags(&self) -> Result<Vec<String>> {
+        let refs = self.ls_remote(&["--tags", "--refs"], &[])?;
+        Ok(refs.into_iter().filter_map(|(_, name)| name.strip_prefix("refs/tags/").map(String::from)).collect())
+    }
+
+    fn default_branch(&self) -> Result<Option<String>> {
+        let output = self.git(&["ls-remote", "--symref", &self.url, "HEAD"])?;
+        let output = String::from_utf8_lossy(&output);
+        let branch = output.lines().find_map(|line| {
+            let target = line.strip_prefix("ref: ")?.split_whitespace().next()?;
+            target.strip_prefix("refs/heads/").map(String::from)
+        });
+
+        Ok(branch)
+    }
+
+    /// List the references of the remote matching the patterns, as the pairs of sha and name.
+    fn ls_remote(&self, options: &[&str], patterns: &[&str]) -> Result<Vec<(String, String)>> {
+        let arguments = [&["ls-remote"][..], options, &[self.url.as_str()][..], patterns].concat();
+        let output = self.git(&arguments)?;
+        let refs = String::from_utf8_lossy(&output)
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(sha, name)| (sha.to_string(), name.to_string()))
+            .collect();
+
+        Ok(refs)
+    }
+
+    /// Run the git command in the working directory, returns its stdout.
+    fn git(&self, args: &[&str]) -> Result<Vec<u8>> {
+        let ssh = format!(
+            "ssh -i {} -o IdentitiesOnly=yes -o StrictHostKeyChecking=yes -o UserKnownHostsFile={}",
+            self.directory.join("ssh").display(),
+            self.directory.join("known_hosts").display(),
+        );
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&self.directory)
+            .env("GIT_SSH_COMMAND", ssh)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .map_err(|e| ResolveError::FetchingError(e.to_string()))?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            debug!("Failed to run git {:?} for {}: {}", args, self.url, message);
+            return Err(ResolveError::FetchingError(message));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+impl Drop for SshRemote {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
@@ -12,13 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use amp_resources::git;
 use url::Url;
 
 use crate::errors::{ResolveError, Result};
 
-/// Resolve the repo from the URL.
+/// Resolve the repo from the URL, over HTTPS or SSH.
 pub fn repo(url: &str) -> Result<String> {
-    let url = Url::parse(url).map_err(ResolveError::InvalidRepoAddress)?;
+    let url = Url::parse(&git::https(url)).map_err(ResolveError::InvalidRepoAddress)?;
     let mut repo = url.path().replace(".git", "");
     repo = repo.trim_start_matches('/').to_string();
 
@@ -12,31 +12,82 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::argocd::ARGOCD_ANNOTATION;
+use super::base::{self, BASES_ANNOTATION, BASE_ANNOTATION};
+use super::build::BUILD_RESOURCES_ANNOTATION;
+use super::cache;
+use super::canary::CANARY_ANNOTATION;
+use super::cluster;
+use super::containers::lifecycle::RUN_IMAGE_ANNOTATION;
+use super::containers::sidecar::CONTAINERS_ANNOTATION;
+use super::cronjob::SCHEDULE_ANNOTATION;
+use super::debug::DEBUG_ANNOTATION;
+use super::detection::BUILD_METHOD_ANNOTATION;
+use super::devcontainer::DEVCONTAINER_ANNOTATION;
+use super::discovery::{self, SIBLINGS_ANNOTATION};
+use super::envset::ENV_SETS_ANNOTATION;
 use super::error::{Error, Result};
+use super::exposure::EXPOSURE_ANNOTATION;
+use super::healing::HEALING_ANNOTATION;
+use super::image::{DIGEST_ANNOTATION, PORTS_INFERRED_CONDITION_TYPE, VERIFY_SIGNATURE_ANNOTATION};
+use super::job::JOB_SETTINGS_ANNOTATION;
+use super::kpack::reference::BUILDER_ANNOTATION;
+use super::namespace;
+use super::probe::PROBES_ANNOTATION;
+use super::promotion::PROMOTED_ANNOTATION;
+use super::reload::RELOAD_ANNOTATION;
+use super::rollback::ROLLBACK_ANNOTATION;
+use super::sbom::SBOM_ANNOTATION;
+use super::secret::IMAGE_PULL_SECRETS_ANNOTATION;
+use super::signing::SIGNING_ANNOTATION;
+use super::statefulset::WORKLOAD_ANNOTATION;
+use super::strategy::STRATEGY_ANNOTATION;
+use super::telemetry::TRACE_CONTEXT_ANNOTATION;
+use super::verification::VERIFICATION_ANNOTATION;
+use super::volume::VOLUMES_ANNOTATION;
+use super::workspace::WORKSPACE_LABEL;
 
-use amp_common::resource::{Actor, ActorSpec, ActorState, Playbook};
+use std::collections::BTreeMap;
+
+use amp_common::resource::{Actor, ActorSpec, ActorState, Playbook, Preface};
 use k8s_metrics::v1beta1::PodMetrics;
-use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
-use kube::api::{ListParams, Patch, PatchParams, PostParams};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, OwnerReference};
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
 use kube::{Api, Client, Resource, ResourceExt};
 use serde_json::json;
 use tracing::{debug, error, info};
 
-pub async fn exists(client: &Client, playbook: &Playbook, name: &str) -> Result<bool> {
-    let namespace = playbook.spec.namespace();
+/// The label of the actors and namespaces deployed without a playbook.
+pub const DETACHED_LABEL: &str = "amphitheatre.app/detached";
+
+/// The annotation of detached actor holding the preface to resolve its spec from.
+pub const PREFACE_ANNOTATION: &str = "amphitheatre.app/preface";
+
+/// The label of the actors holding the name of their playbook, which tells
+/// them apart from the actors of other playbooks in a shared namespace.
+pub const PLAYBOOK_LABEL: &str = "amphitheatre.app/playbook";
+
+/// Returns the labels of the pods of actor besides the selected ones, the
+/// pods of the playbooks sharing a namespace are told apart by them.
+pub fn pod_labels(actor: &Actor) -> BTreeMap<String, String> {
+    let playbook = actor.labels().get(PLAYBOOK_LABEL);
+    playbook.map(|playbook| (PLAYBOOK_LABEL.to_string(), playbook.clone())).into_iter().collect()
+}
+
+/// Returns true if the actor of the character in playbook exists.
+pub async fn exists(client: &Client, playbook: &Playbook, character: &str) -> Result<bool> {
+    let namespace = namespace::of(playbook);
+    let name = namespace::actor_name(playbook, character);
     let api: Api<Actor> = Api::namespaced(client.clone(), namespace.as_str());
 
-    Ok(api.get_opt(name).await.map_err(Error::KubeError)?.is_some())
+    Ok(api.get_opt(&name).await.map_err(Error::KubeError)?.is_some())
 }
 
 pub async fn create(client: &Client, playbook: &Playbook, spec: &ActorSpec) -> Result<Actor> {
-    let namespace = playbook.spec.namespace();
+    let namespace = namespace::of(playbook);
     let api: Api<Actor> = Api::namespaced(client.clone(), namespace.as_str());
 
-    let name = spec.name.clone();
-    let mut resource = Actor::new(&name, spec.clone());
-    resource.owner_references_mut().push(playbook.controller_owner_ref(&()).unwrap());
-
+    let resource = new(playbook, spec);
     let actor = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
     info!("Created Actor: {}", actor.name_any());
 
@@ -46,20 +97,26 @@ pub async fn create(client: &Client, playbook: &Playbook, spec: &ActorSpec) -> R
 }
 
 pub async fn update(client: &Client, playbook: &Playbook, spec: &ActorSpec) -> Result<Actor> {
-    let namespace = playbook.spec.namespace();
+    let namespace = namespace::of(playbook);
     let api: Api<Actor> = Api::namespaced(client.clone(), namespace.as_str());
 
-    let name = spec.name.clone();
+    let resource = new(playbook, spec);
+    let name = resource.name_any();
+
+    // The playbook is reconciled again on the changes, so it's safe to skip the stale cached actor.
+    if let Some(actor) = cache::actor(&namespace, &name).filter(|actor| current(actor, &resource)) {
+        debug!("The cached Actor {} is already up-to-date", &name);
+        return Ok((*actor).clone());
+    }
+
     let mut actor = api.get(&name).await.map_err(Error::KubeError)?;
-    debug!("The Actor {} already exists", &spec.name);
+    debug!("The Actor {} already exists", &name);
 
-    if &actor.spec == spec {
-        debug!("The Actor {} is already up-to-date", &spec.name);
+    if current(&actor, &resource) {
+        debug!("The Actor {} is already up-to-date", &name);
         return Ok(actor);
     }
 
-    let mut resource = Actor::new(&name, spec.clone());
-    resource.owner_references_mut().push(playbook.controller_owner_ref(&()).unwrap());
     debug!("The updating Actor resource:\n {:?}\n", resource);
 
     let params = &PatchParams::apply("amp-controllers").force();
@@ -70,12 +127,153 @@ pub async fn update(client: &Client, playbook: &Playbook, spec: &ActorSpec) -> R
     Ok(actor)
 }
 
+/// Create a detached actor in its own namespace, the spec will be resolved from the preface later.
+pub async fn create_detached(client: &Client, namespace: &str, name: &str, preface: &Preface) -> Result<Actor> {
+    let api: Api<Actor> = Api::namespaced(client.clone(), namespace);
+
+    let mut resource = Actor::new(name, ActorSpec { name: name.into(), ..Default::default() });
+    resource.labels_mut().insert(DETACHED_LABEL.into(), "true".into());
+    let preface = serde_json::to_string(preface).map_err(Error::SerializationError)?;
+    resource.annotations_mut().insert(PREFACE_ANNOTATION.into(), preface);
+
+    let actor = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+    info!("Created detached Actor: {}", actor.name_any());
+
+    // Patch this actor as initial Pending status
+    patch_status(client, &actor, ActorState::pending()).await?;
+    Ok(actor)
+}
+
+/// Returns true if the actor is deployed without a playbook.
+pub fn detached(actor: &Actor) -> bool {
+    actor.labels().get(DETACHED_LABEL).is_some_and(|v| v == "true")
+}
+
+/// Returns the preface of the detached actor.
+pub fn preface(actor: &Actor) -> Result<Option<Preface>> {
+    actor
+        .annotations()
+        .get(PREFACE_ANNOTATION)
+        .map(|value| serde_json::from_str(value).map_err(Error::SerializationError))
+        .transpose()
+}
+
+/// Replace the spec of actor, e.g. with the resolved one of detached actor.
+pub async fn replace_spec(client: &Client, actor: &Actor, spec: &ActorSpec) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let patch = json!({ "spec": spec });
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Replaced the spec of Actor {}", actor.name_any());
+
+    Ok(())
+}
+
+pub async fn delete(client: &Client, namespace: &str, name: &str) -> Result<()> {
+    let api: Api<Actor> = Api::namespaced(client.clone(), namespace);
+    api.delete(name, &DeleteParams::default()).await.map_err(Error::KubeError)?;
+    info!("Deleted Actor {}", name);
+
+    Ok(())
+}
+
+/// Build the actor of the character in playbook, it's named after the
+/// character unless the namespace is shared with other playbooks.
+pub fn new(playbook: &Playbook, spec: &ActorSpec) -> Actor {
+    let name = namespace::actor_name(playbook, &spec.name);
+    let mut resource = Actor::new(&name, ActorSpec { name: name.clone(), ..spec.clone() });
+    resource.owner_references_mut().extend(owner(playbook));
+    resource.labels_mut().insert(PLAYBOOK_LABEL.into(), playbook.name_any());
+    inherit_annotations(playbook, &spec.name, &mut resource);
+
+    let siblings = discovery::siblings(playbook, &spec.name);
+    if let Ok(siblings) = serde_json::to_string(&siblings) {
+        resource.annotations_mut().insert(SIBLINGS_ANNOTATION.into(), siblings);
+    }
+
+    resource
+}
+
+/// Returns true if the actor is up-to-date with the built one, the siblings
+/// are compared as well, as they change with the other characters.
+pub fn current(actor: &Actor, resource: &Actor) -> bool {
+    actor.spec == resource.spec
+        && actor.annotations().get(SIBLINGS_ANNOTATION) == resource.annotations().get(SIBLINGS_ANNOTATION)
+}
+
+/// Returns the owner reference of the actors of playbook, the actors in the
+/// workload clusters are not owned by it and deleted with their namespace.
+fn owner(playbook: &Playbook) -> Option<OwnerReference> {
+    playbook.controller_owner_ref(&()).filter(|_| !cluster::remote(playbook))
+}
+
+/// Inherit the annotations configuring the actors and the workspace from the playbook.
+fn inherit_annotations(playbook: &Playbook, character: &str, actor: &mut Actor) {
+    for key in [SIGNING_ANNOTATION, HEALING_ANNOTATION, ARGOCD_ANNOTATION, TRACE_CONTEXT_ANNOTATION] {
+        if let Some(value) = playbook.annotations().get(key) {
+            actor.annotations_mut().insert(key.into(), value.clone());
+        }
+    }
+    // The env sets of the character are applied after the ones of playbook.
+    let key = format!("{}.{}", ENV_SETS_ANNOTATION, character);
+    let sets = [playbook.annotations().get(ENV_SETS_ANNOTATION), playbook.annotations().get(&key)];
+    let sets: Vec<&str> = sets.into_iter().flatten().map(String::as_str).collect();
+    if !sets.is_empty() {
+        actor.annotations_mut().insert(ENV_SETS_ANNOTATION.into(), sets.join(","));
+    }
+    let keys = [
+        VOLUMES_ANNOTATION,
+        WORKLOAD_ANNOTATION,
+        SCHEDULE_ANNOTATION,
+        CONTAINERS_ANNOTATION,
+        PROBES_ANNOTATION,
+        STRATEGY_ANNOTATION,
+        CANARY_ANNOTATION,
+        VERIFICATION_ANNOTATION,
+        ROLLBACK_ANNOTATION,
+        EXPOSURE_ANNOTATION,
+        BUILD_RESOURCES_ANNOTATION,
+        JOB_SETTINGS_ANNOTATION,
+        BUILDER_ANNOTATION,
+        RUN_IMAGE_ANNOTATION,
+        BUILD_METHOD_ANNOTATION,
+        SBOM_ANNOTATION,
+        DIGEST_ANNOTATION,
+        IMAGE_PULL_SECRETS_ANNOTATION,
+        VERIFY_SIGNATURE_ANNOTATION,
+        DEBUG_ANNOTATION,
+        DEVCONTAINER_ANNOTATION,
+        PROMOTED_ANNOTATION,
+        RELOAD_ANNOTATION,
+    ];
+    for key in keys {
+        if let Some(value) = playbook.annotations().get(&format!("{}.{}", key, character)) {
+            actor.annotations_mut().insert(key.into(), value.clone());
+        }
+    }
+    if let Some(workspace) = playbook.labels().get(WORKSPACE_LABEL) {
+        actor.labels_mut().insert(WORKSPACE_LABEL.into(), workspace.clone());
+    }
+    if let Some(dockerfile) =
+        playbook.annotations().get(BASES_ANNOTATION).and_then(|v| base::dockerfile(v, &actor.spec))
+    {
+        actor.annotations_mut().insert(BASE_ANNOTATION.into(), dockerfile);
+    }
+}
+
 pub async fn patch_status(client: &Client, actor: &Actor, condition: Condition) -> Result<()> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
 
     let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
 
-    let status = json!({ "status": { "conditions": vec![condition.clone()] }});
+    // The state transition replaces the conditions, except the ports inferred from the image.
+    let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    let mut conditions: Vec<Condition> =
+        conditions.iter().filter(|c| c.type_ == PORTS_INFERRED_CONDITION_TYPE).cloned().collect();
+    conditions.push(condition.clone());
+
+    let status = json!({ "status": { "conditions": conditions }});
     let actor = api
         .patch_status(actor.name_any().as_str(), &PatchParams::default(), &Patch::Merge(&status))
         .await
@@ -86,6 +284,45 @@ pub async fn patch_status(client: &Client, actor: &Actor, condition: Condition)
     Ok(())
 }
 
+/// Add or replace the condition of the same type, the other conditions are kept.
+pub async fn upsert_condition(client: &Client, actor: &Actor, condition: Condition) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let mut conditions = actor.status.as_ref().map(|status| status.conditions.clone()).unwrap_or_default();
+    conditions.retain(|c| c.type_ != condition.type_);
+    conditions.push(condition.clone());
+
+    let status = json!({ "status": { "conditions": conditions }});
+    api.patch_status(actor.name_any().as_str(), &PatchParams::default(), &Patch::Merge(&status))
+        .await
+        .map_err(Error::KubeError)?;
+
+    info!("Upserted condition {:?} with reason {:?} for Actor {}", condition.type_, condition.reason, actor.name_any());
+
+    Ok(())
+}
+
+/// Remove the condition of the given type, if the actor has it.
+pub async fn remove_condition(client: &Client, actor: &Actor, type_: &str) -> Result<()> {
+    let mut conditions = actor.status.as_ref().map(|status| status.conditions.clone()).unwrap_or_default();
+    if !conditions.iter().any(|c| c.type_ == type_) {
+        return Ok(());
+    }
+    conditions.retain(|c| c.type_ != type_);
+
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+    let status = json!({ "status": { "conditions": conditions }});
+    api.patch_status(actor.name_any().as_str(), &PatchParams::default(), &Patch::Merge(&status))
+        .await
+        .map_err(Error::KubeError)?;
+
+    info!("Removed condition {:?} from Actor {}", type_, actor.name_any());
+
+    Ok(())
+}
+
 pub async fn metrics(client: &Client, namespace: &str, name: &str) -> Result<PodMetrics> {
     let api: Api<PodMetrics> = Api::namespaced(client.clone(), namespace);
     let params = ListParams::default().labels(&format!("amphitheatre.app/character={}", name)).limit(1);
@@ -119,3 +356,22 @@ pub async fn list(client: &Client, namespace: &str) -> Result<Vec<Actor>> {
 
     Ok(actors.items)
 }
+
+/// List the actors of all the namespaces.
+pub async fn list_all(client: &Client) -> Result<Vec<Actor>> {
+    let api: Api<Actor> = Api::all(client.clone());
+    let actors = api.list(&ListParams::default()).await.map_err(Error::KubeError)?;
+
+    Ok(actors.items)
+}
+
+/// List the actors of playbook, leaving out the ones of other playbooks sharing its namespace.
+pub async fn list_of(client: &Client, playbook: &Playbook) -> Result<Vec<Actor>> {
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace::of(playbook));
+    let mut actors = api.list(&ListParams::default()).await.map_err(Error::KubeError)?.items;
+    if !namespace::dedicated(playbook) {
+        actors.retain(|actor| actor.labels().get(PLAYBOOK_LABEL) == Some(&playbook.name_any()));
+    }
+
+    Ok(actors)
+}
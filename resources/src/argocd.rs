@@ -0,0 +1,258 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::{Actor, Playbook};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::api::{Patch, PatchParams};
+use kube::core::{DynamicObject, GroupVersionKind};
+use kube::discovery::ApiResource;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_value, json};
+use tracing::{debug, info};
+
+use crate::actor::PLAYBOOK_LABEL;
+use crate::error::{Error, Result};
+use crate::export::Export;
+use crate::{namespace, naming};
+
+/// The annotation of playbook deploying its actors by an ArgoCD Application
+/// as a JSON document, e.g. `{"namespace": "argocd", "project": "default"}`.
+/// The Application syncs the manifests exported to the repository, so the
+/// export is required, and the actors are only built by Amphitheatre.
+pub const ARGOCD_ANNOTATION: &str = "amphitheatre.app/argocd";
+
+/// The condition type of playbook reporting the health of its Application.
+pub const APPLICATION_CONDITION_TYPE: &str = "ApplicationHealthy";
+
+/// The finalizer of ArgoCD deleting the synced resources along with the Application.
+const RESOURCES_FINALIZER: &str = "resources-finalizer.argocd.argoproj.io";
+
+const IN_CLUSTER_SERVER: &str = "https://kubernetes.default.svc";
+
+/// The ArgoCD Application of playbook.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct ArgoCd {
+    /// The namespace ArgoCD is installed in, the default is `argocd`.
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// The ArgoCD project of the Application, the default is `default`.
+    #[serde(default = "default_project")]
+    pub project: String,
+    /// The name of the cluster registered in ArgoCD to deploy to, the
+    /// cluster ArgoCD runs in if not set.
+    pub destination: Option<String>,
+    /// Sync the changes of the manifests automatically, pruning the deleted resources.
+    #[serde(default = "default_auto_sync")]
+    pub auto_sync: bool,
+}
+
+fn default_namespace() -> String {
+    "argocd".to_string()
+}
+
+fn default_project() -> String {
+    "default".to_string()
+}
+
+fn default_auto_sync() -> bool {
+    true
+}
+
+/// The health and sync status of the Application.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Health {
+    /// `Healthy`, `Progressing`, `Degraded`, `Suspended`, `Missing` or `Unknown`.
+    pub health: String,
+    /// `Synced`, `OutOfSync` or `Unknown`.
+    pub sync: String,
+    pub message: Option<String>,
+}
+
+/// Returns the ArgoCD Application of playbook, if any.
+pub fn of(playbook: &Playbook) -> Result<Option<ArgoCd>> {
+    match playbook.annotations().get(ARGOCD_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Returns true if the workloads of actor are deployed by ArgoCD instead.
+pub fn managed(actor: &Actor) -> bool {
+    actor.annotations().contains_key(ARGOCD_ANNOTATION)
+}
+
+/// Create or update the Application syncing the exported manifests of playbook.
+pub async fn apply(client: &Client, playbook: &Playbook, argocd: &ArgoCd, export: &Export) -> Result<()> {
+    let resource = new(playbook, argocd, export)?;
+    let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), &argocd.namespace, &api_resource());
+
+    let params = &PatchParams::apply("amp-controllers").force();
+    api.patch(&name(&playbook.name_any()), params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+    debug!("Applied the Application of playbook {}", playbook.name_any());
+
+    Ok(())
+}
+
+/// Returns the health of the Application of playbook, None if it's not reported yet.
+pub async fn health(client: &Client, playbook: &Playbook, argocd: &ArgoCd) -> Result<Option<Health>> {
+    let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), &argocd.namespace, &api_resource());
+    let Some(application) = api.get_opt(&name(&playbook.name_any())).await.map_err(Error::KubeError)? else {
+        return Ok(None);
+    };
+
+    Ok(from(&application.data))
+}
+
+/// Build the condition reporting the health of the Application.
+pub fn condition(health: &Health) -> Condition {
+    let status = match health.health.as_str() {
+        "Healthy" if health.sync == "Synced" => "True",
+        _ => "False",
+    };
+    let message = match &health.message {
+        Some(message) => format!("{} and {}: {}", health.health, health.sync, message),
+        None => format!("{} and {}", health.health, health.sync),
+    };
+
+    Condition {
+        type_: APPLICATION_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: health.health.clone(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// Record the health of the Application in the conditions of playbook, if it has changed.
+pub async fn track(client: &Client, playbook: &Playbook, health: &Health) -> Result<()> {
+    let condition = condition(health);
+    let current = playbook.status.as_ref().and_then(|status| {
+        status.conditions.iter().find(|c| c.type_ == APPLICATION_CONDITION_TYPE).map(|c| &c.message)
+    });
+    if current == Some(&condition.message) {
+        return Ok(());
+    }
+
+    info!("The Application of playbook {} is {}", playbook.name_any(), condition.message);
+    crate::playbook::upsert_condition(client, playbook, condition).await
+}
+
+fn new(playbook: &Playbook, argocd: &ArgoCd, export: &Export) -> Result<DynamicObject> {
+    let repo = export.repo.as_ref().ok_or_else(|| Error::InvalidArgoCd("the export repository is required".into()))?;
+    let destination = match &argocd.destination {
+        Some(name) => json!({ "name": name, "namespace": namespace::of(playbook) }),
+        None => json!({ "server": IN_CLUSTER_SERVER, "namespace": namespace::of(playbook) }),
+    };
+    let sync_policy = match argocd.auto_sync {
+        true => json!({ "automated": { "prune": true, "selfHeal": true } }),
+        false => json!({}),
+    };
+
+    from_value(json!({
+        "apiVersion": "argoproj.io/v1alpha1",
+        "kind": "Application",
+        "metadata": {
+            "name": name(&playbook.name_any()),
+            "labels": {
+                PLAYBOOK_LABEL: playbook.name_any(),
+                "app.kubernetes.io/managed-by": "Amphitheatre",
+            },
+            // The synced resources are deleted along with the playbook.
+            "finalizers": [RESOURCES_FINALIZER],
+            "ownerReferences": playbook.controller_owner_ref(&()).map(|owner| vec![owner]),
+        },
+        "spec": {
+            "project": argocd.project,
+            "source": {
+                "repoURL": repo,
+                "targetRevision": export.branch,
+                "path": export.directory(&playbook.name_any()),
+            },
+            "destination": destination,
+            "syncPolicy": sync_policy,
+        }
+    }))
+    .map_err(Error::SerializationError)
+}
+
+fn from(data: &serde_json::Value) -> Option<Health> {
+    let health = data.pointer("/status/health/status")?.as_str()?;
+    let sync = data.pointer("/status/sync/status").and_then(|v| v.as_str()).unwrap_or("Unknown");
+    let message = data.pointer("/status/health/message").and_then(|v| v.as_str());
+
+    Some(Health { health: health.into(), sync: sync.into(), message: message.map(String::from) })
+}
+
+#[inline]
+fn api_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk("argoproj.io", "v1alpha1", "Application"))
+}
+
+#[inline]
+fn name(playbook: &str) -> String {
+    naming::name(&["amp", playbook])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::PlaybookSpec;
+
+    fn playbook() -> Playbook {
+        let mut playbook = Playbook::new("demo", PlaybookSpec { id: "demo".into(), ..Default::default() });
+        playbook.annotations_mut().insert(ARGOCD_ANNOTATION.into(), r#"{"project": "apps"}"#.into());
+        playbook
+    }
+
+    #[test]
+    fn test_parse_argocd() {
+        let argocd = of(&playbook()).unwrap().unwrap();
+        assert_eq!(argocd.namespace, "argocd");
+        assert_eq!(argocd.project, "apps");
+        assert!(argocd.auto_sync);
+        assert_eq!(of(&Playbook::new("demo", PlaybookSpec::default())).unwrap(), None);
+    }
+
+    #[test]
+    fn test_new_application() {
+        let playbook = playbook();
+        let argocd = of(&playbook).unwrap().unwrap();
+        assert!(new(&playbook, &argocd, &Export::default()).is_err());
+
+        let export =
+            Export { repo: Some("https://github.com/org/deploy".into()), branch: "main".into(), ..Default::default() };
+        let application = new(&playbook, &argocd, &export).unwrap();
+        assert_eq!(application.name_any(), "amp-demo");
+        assert_eq!(application.data["spec"]["source"]["path"], "demo");
+        assert_eq!(application.data["spec"]["destination"]["namespace"], "amp-demo");
+        assert_eq!(application.data["spec"]["destination"]["server"], IN_CLUSTER_SERVER);
+    }
+
+    #[test]
+    fn test_health_condition() {
+        let data = json!({"status": {"health": {"status": "Healthy"}, "sync": {"status": "Synced"}}});
+        let health = from(&data).unwrap();
+        assert_eq!(condition(&health).status, "True");
+
+        let data = json!({"status": {"health": {"status": "Degraded", "message": "web crashed"}}});
+        let condition = condition(&from(&data).unwrap());
+        assert_eq!(condition.status, "False");
+        assert_eq!(condition.message, "Degraded and Unknown: web crashed");
+        assert_eq!(from(&json!({})), None);
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use k8s_openapi::chrono::{DateTime, Utc};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use super::{artifact_error, Backend, Object};
+use crate::error::Result;
+use crate::registry::GCP_TOKEN_URL;
+
+const STORAGE_URL: &str = "https://storage.googleapis.com/storage/v1";
+const UPLOAD_URL: &str = "https://storage.googleapis.com/upload/storage/v1";
+
+/// The artifacts stored in a bucket of Google Cloud Storage, the requests
+/// are authorized by the access token of the workload identity.
+pub struct Gcs {
+    http: reqwest::Client,
+    bucket: String,
+    /// The access token and when it should be renewed.
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+#[derive(Deserialize)]
+struct Token {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Objects {
+    #[serde(default)]
+    items: Vec<Item>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    name: String,
+    updated: DateTime<Utc>,
+}
+
+impl Gcs {
+    pub fn new(bucket: &str) -> Self {
+        Self { http: reqwest::Client::new(), bucket: bucket.into(), token: Mutex::new(None) }
+    }
+
+    /// Returns the cached access token, it's renewed a minute before it expires.
+    async fn token(&self) -> Result<String> {
+        let mut cache = self.token.lock().await;
+        if let Some((token, renew_at)) = cache.as_ref() {
+            if Instant::now() < *renew_at {
+                return Ok(token.clone());
+            }
+        }
+
+        let response = self.http.get(GCP_TOKEN_URL).header("Metadata-Flavor", "Google").send().await;
+        let response = response.map_err(artifact_error)?.error_for_status().map_err(artifact_error)?;
+        let token: Token = response.json().await.map_err(artifact_error)?;
+        let renew_at = Instant::now() + Duration::from_secs(token.expires_in.saturating_sub(60));
+        *cache = Some((token.access_token.clone(), renew_at));
+
+        Ok(token.access_token)
+    }
+
+    async fn authorize(&self, request: RequestBuilder) -> Result<RequestBuilder> {
+        Ok(request.bearer_auth(self.token().await?))
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        format!("{}/b/{}/o/{}", STORAGE_URL, self.bucket, key.replace('/', "%2F"))
+    }
+}
+
+#[async_trait]
+impl Backend for Gcs {
+    async fn put(&self, key: &str, content: Vec<u8>) -> Result<()> {
+        let url = format!("{}/b/{}/o", UPLOAD_URL, self.bucket);
+        let request = self.http.post(url).query(&[("uploadType", "media"), ("name", key)]).body(content);
+        let response = self.authorize(request).await?.send().await.map_err(artifact_error)?;
+        response.error_for_status().map_err(artifact_error)?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let request = self.http.get(self.object_url(key)).query(&[("alt", "media")]);
+        let response = self.authorize(request).await?.send().await.map_err(artifact_error)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let content = response.error_for_status().map_err(artifact_error)?.bytes().await.map_err(artifact_error)?;
+        Ok(Some(content.to_vec()))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let request = self.http.delete(self.object_url(key));
+        let response = self.authorize(request).await?.send().await.map_err(artifact_error)?;
+        if response.status() != StatusCode::NOT_FOUND {
+            response.error_for_status().map_err(artifact_error)?;
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>> {
+        let url = format!("{}/b/{}/o", STORAGE_URL, self.bucket);
+        let mut objects = vec![];
+        let mut page: Option<String> = None;
+        loop {
+            let mut request = self.http.get(&url).query(&[("prefix", prefix)]);
+            if let Some(page) = &page {
+                request = request.query(&[("pageToken", page)]);
+            }
+            let response = self.authorize(request).await?.send().await.map_err(artifact_error)?;
+            let response = response.error_for_status().map_err(artifact_error)?;
+            let list: Objects = response.json().await.map_err(artifact_error)?;
+
+            objects.extend(list.items.into_iter().map(|item| Object { key: item.name, modified: item.updated }));
+            page = list.next_page_token;
+            if page.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+}
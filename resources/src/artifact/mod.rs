@@ -0,0 +1,278 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use k8s_openapi::chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::error::{Error, Result};
+use crate::{hex, hmac};
+
+mod gcs;
+mod pvc;
+mod s3;
+
+pub use self::gcs::Gcs;
+pub use self::pvc::Pvc;
+pub use self::s3::S3;
+
+/// The kinds of the artifacts, each kind is kept under its own prefix and expires on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    /// The tarballs of the sources uploaded from local.
+    Sources,
+    /// The archived logs of the build pods.
+    BuildLogs,
+    /// The generated SBOM documents.
+    Sboms,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Sources, Kind::BuildLogs, Kind::Sboms];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Sources => "sources",
+            Kind::BuildLogs => "build-logs",
+            Kind::Sboms => "sboms",
+        }
+    }
+
+    /// Returns the media type of the artifacts of the kind.
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Kind::Sources => "application/x-tar",
+            Kind::BuildLogs => "text/plain; charset=utf-8",
+            Kind::Sboms => "application/json",
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Kind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Kind::ALL.into_iter().find(|kind| kind.as_str() == s).ok_or_else(|| Error::UnknownArtifactKind(s.into()))
+    }
+}
+
+/// An artifact stored by the SHA-256 digest of its content.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Artifact {
+    pub kind: Kind,
+    pub digest: String,
+    pub size: usize,
+}
+
+/// An object listed from the backend.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Object {
+    pub key: String,
+    /// When the object was stored, the expiry is counted from it.
+    pub modified: DateTime<Utc>,
+}
+
+/// The storage of the artifacts, the keys are relative paths, e.g. `sboms/{digest}`.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// Store the content, the existing object of the key is replaced.
+    async fn put(&self, key: &str, content: Vec<u8>) -> Result<()>;
+
+    /// Returns the content of the key, None if it doesn't exist.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Delete the object of the key, it's not an error if it doesn't exist.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// List the objects whose keys start with the prefix.
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>>;
+}
+
+/// How long the artifacts of each kind are retained, they are kept forever if not set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lifecycle {
+    pub sources: Option<Duration>,
+    pub build_logs: Option<Duration>,
+    pub sboms: Option<Duration>,
+}
+
+impl Lifecycle {
+    /// Build the lifecycle from the retention in days, `0` keeps the artifacts forever.
+    pub fn days(sources: u64, build_logs: u64, sboms: u64) -> Self {
+        let days = |days: u64| (days > 0).then(|| Duration::from_secs(days * 24 * 60 * 60));
+        Self { sources: days(sources), build_logs: days(build_logs), sboms: days(sboms) }
+    }
+
+    pub fn max_age(&self, kind: Kind) -> Option<Duration> {
+        match kind {
+            Kind::Sources => self.sources,
+            Kind::BuildLogs => self.build_logs,
+            Kind::Sboms => self.sboms,
+        }
+    }
+}
+
+/// The content-addressed store of the artifacts, the same content is stored once.
+#[derive(Clone)]
+pub struct Store {
+    backend: Arc<dyn Backend>,
+    lifecycle: Lifecycle,
+}
+
+impl Store {
+    pub fn new(backend: Arc<dyn Backend>, lifecycle: Lifecycle) -> Self {
+        Self { backend, lifecycle }
+    }
+
+    /// Open the store of the backend, `pvc` at the directory, `s3` or `gcs`
+    /// in the bucket. The S3 endpoint defaults to AWS in the region, set it for
+    /// the compatible services such as MinIO.
+    pub fn open(
+        backend: &str,
+        location: &str,
+        endpoint: Option<&str>,
+        region: &str,
+        lifecycle: Lifecycle,
+    ) -> Result<Self> {
+        let backend: Arc<dyn Backend> = match backend {
+            "pvc" => Arc::new(Pvc::new(location)),
+            "s3" => Arc::new(S3::new(location, endpoint, region)?),
+            "gcs" => Arc::new(Gcs::new(location)),
+            backend => return Err(Error::UnknownArtifactBackend(backend.into())),
+        };
+
+        Ok(Self::new(backend, lifecycle))
+    }
+
+    /// Store the content and returns the artifact addressed by its digest,
+    /// storing the same content again refreshes its expiry.
+    pub async fn put(&self, kind: Kind, content: Vec<u8>) -> Result<Artifact> {
+        let digest = digest(&content);
+        let size = content.len();
+        self.backend.put(&key(kind, &digest), content).await?;
+        debug!("Stored the artifact {}/{} of {} bytes", kind, digest, size);
+
+        Ok(Artifact { kind, digest, size })
+    }
+
+    /// Returns the content of the artifact, None if it doesn't exist or has expired.
+    pub async fn get(&self, kind: Kind, digest: &str) -> Result<Option<Vec<u8>>> {
+        if !valid(digest) {
+            return Err(Error::InvalidArtifactDigest(digest.into()));
+        }
+
+        self.backend.get(&key(kind, digest)).await
+    }
+
+    /// Delete the artifacts older than the retention of their kinds, returns
+    /// the number of the deleted ones.
+    pub async fn expire(&self) -> Result<usize> {
+        let now = Utc::now();
+        let mut deleted = 0;
+        for kind in Kind::ALL {
+            let Some(max_age) = self.lifecycle.max_age(kind) else {
+                continue;
+            };
+            for object in self.backend.list(&format!("{}/", kind)).await? {
+                if expired(&object, max_age, now) {
+                    self.backend.delete(&object.key).await?;
+                    deleted += 1;
+                }
+            }
+        }
+        info!("Deleted {} expired artifacts", deleted);
+
+        Ok(deleted)
+    }
+}
+
+#[inline]
+/// Returns the SHA-256 digest of the content, the artifacts are addressed by it.
+pub fn digest(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Returns the signature granting the download of the artifact, signed with
+/// the token of the apiserver, so the pods can download it without the token.
+pub fn signature(key: &str, kind: Kind, digest: &str) -> String {
+    hex(&hmac(key.as_bytes(), format!("{}/{}", kind, digest).as_bytes()))
+}
+
+fn key(kind: Kind, digest: &str) -> String {
+    format!("{}/{}", kind, digest)
+}
+
+/// Returns true if it's a SHA-256 digest in hex, so it can't escape the prefix of its kind.
+fn valid(digest: &str) -> bool {
+    digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn expired(object: &Object, max_age: Duration, now: DateTime<Utc>) -> bool {
+    (now - object.modified).to_std().is_ok_and(|age| age > max_age)
+}
+
+/// Wrap the error of backend.
+pub(crate) fn artifact_error(err: impl Into<anyhow::Error>) -> Error {
+    Error::ArtifactError(err.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use k8s_openapi::chrono::TimeDelta;
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!("build-logs".parse::<Kind>().unwrap(), Kind::BuildLogs);
+        assert_eq!(Kind::Sboms.to_string(), "sboms");
+        assert!("logs".parse::<Kind>().is_err());
+    }
+
+    #[test]
+    fn test_valid_digest() {
+        assert!(valid(&format!("{:x}", Sha256::digest(b"source"))));
+        assert!(!valid("../../etc/passwd"));
+        assert!(!valid(&"A".repeat(64)));
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        let lifecycle = Lifecycle::days(7, 0, 90);
+        assert_eq!(lifecycle.max_age(Kind::Sources), Some(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(lifecycle.max_age(Kind::BuildLogs), None);
+    }
+
+    #[test]
+    fn test_expired() {
+        let now = Utc::now();
+        let object = Object { key: "sources/x".into(), modified: now - TimeDelta::days(8) };
+        assert!(expired(&object, Duration::from_secs(7 * 24 * 60 * 60), now));
+        assert!(!expired(&object, Duration::from_secs(9 * 24 * 60 * 60), now));
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use k8s_openapi::chrono::{DateTime, Utc};
+use tokio::fs;
+
+use super::{artifact_error, Backend, Object};
+use crate::error::Result;
+
+/// The artifacts stored in a directory, it's the mount of a PersistentVolumeClaim
+/// shared by the apiserver and the controllers, so it must be `ReadWriteMany`.
+pub struct Pvc {
+    root: PathBuf,
+}
+
+impl Pvc {
+    pub fn new(root: &str) -> Self {
+        Self { root: PathBuf::from(root) }
+    }
+}
+
+#[async_trait]
+impl Backend for Pvc {
+    async fn put(&self, key: &str, content: Vec<u8>) -> Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.map_err(artifact_error)?;
+        }
+
+        // Write to a temporary file first, the readers never see the partial content.
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, content).await.map_err(artifact_error)?;
+        fs::rename(&temporary, &path).await.map_err(artifact_error)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.root.join(key)).await {
+            Ok(content) => Ok(Some(content)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(artifact_error(err)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match fs::remove_file(self.root.join(key)).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(artifact_error(err)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The keys are `{kind}/{digest}`, so the prefix is a directory.
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>> {
+        let mut entries = match fs::read_dir(self.root.join(prefix)).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(artifact_error(err)),
+        };
+
+        let mut objects = vec![];
+        while let Some(entry) = entries.next_entry().await.map_err(artifact_error)? {
+            let metadata = entry.metadata().await.map_err(artifact_error)?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let modified: DateTime<Utc> = metadata.modified().map_err(artifact_error)?.into();
+            let key = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            objects.push(Object { key, modified });
+        }
+
+        Ok(objects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pvc() {
+        let root = std::env::temp_dir().join(format!("amp-artifacts-{}", std::process::id()));
+        let pvc = Pvc::new(root.to_str().unwrap());
+
+        pvc.put("sboms/abc", b"{}".to_vec()).await.unwrap();
+        assert_eq!(pvc.get("sboms/abc").await.unwrap(), Some(b"{}".to_vec()));
+        assert_eq!(pvc.get("sboms/def").await.unwrap(), None);
+
+        let objects = pvc.list("sboms/").await.unwrap();
+        assert_eq!(objects.iter().map(|o| o.key.as_str()).collect::<Vec<_>>(), vec!["sboms/abc"]);
+        assert!(pvc.list("sources/").await.unwrap().is_empty());
+
+        pvc.delete("sboms/abc").await.unwrap();
+        pvc.delete("sboms/abc").await.unwrap();
+        assert_eq!(pvc.get("sboms/abc").await.unwrap(), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use k8s_openapi::chrono::{DateTime, Utc};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use super::{artifact_error, Backend, Object};
+use crate::error::{Error, Result};
+use crate::{hex, hmac};
+
+/// The artifacts stored in a bucket of S3 or a compatible service such as
+/// MinIO, the requests are signed with the Signature Version 4 by the keys
+/// of the `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` env vars.
+pub struct S3 {
+    http: reqwest::Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl S3 {
+    pub fn new(bucket: &str, endpoint: Option<&str>, region: &str) -> Result<Self> {
+        let endpoint = match endpoint {
+            Some(endpoint) => endpoint.to_string(),
+            None => format!("https://s3.{}.amazonaws.com", region),
+        };
+        let env = |key: &str| std::env::var(key).map_err(|_| artifact_error(anyhow::anyhow!("{} is not set", key)));
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            endpoint: Url::parse(&endpoint).map_err(Error::UrlParseError)?,
+            bucket: bucket.into(),
+            region: region.into(),
+            access_key: env("AWS_ACCESS_KEY_ID")?,
+            secret_key: env("AWS_SECRET_ACCESS_KEY")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+
+    /// Build the signed request of the path-style URL, the query is sorted by the names.
+    fn request(&self, method: Method, key: &str, query: &[(&str, &str)], payload: &[u8]) -> RequestBuilder {
+        let now = Utc::now();
+        let datetime = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let path = match key {
+            "" => format!("/{}", self.bucket),
+            key => format!("/{}/{}", self.bucket, key),
+        };
+        let query = query.iter().map(|(k, v)| format!("{}={}", encode(k), encode(v))).collect::<Vec<_>>().join("&");
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{}:{}", self.endpoint.host_str().unwrap_or_default(), port),
+            None => self.endpoint.host_str().unwrap_or_default().to_string(),
+        };
+        let hash = format!("{:x}", Sha256::digest(payload));
+
+        let mut headers =
+            vec![("host", host), ("x-amz-content-sha256", hash.clone()), ("x-amz-date", datetime.clone())];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers.iter().map(|(k, v)| format!("{}:{}\n", k, v)).collect();
+        let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+        let canonical_request =
+            format!("{}\n{}\n{}\n{}\n{}\n{}", method, path, query, canonical_headers, signed_headers, hash);
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign =
+            format!("AWS4-HMAC-SHA256\n{}\n{}\n{:x}", datetime, scope, Sha256::digest(canonical_request.as_bytes()));
+        let key = signing_key(&self.secret_key, &date, &self.region, "s3");
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+        url.set_query((!query.is_empty()).then_some(query.as_str()));
+
+        let mut request = self.http.request(method, url).header("Authorization", authorization);
+        for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+        request
+    }
+}
+
+#[async_trait]
+impl Backend for S3 {
+    async fn put(&self, key: &str, content: Vec<u8>) -> Result<()> {
+        let request = self.request(Method::PUT, key, &[], &content).body(content);
+        request.send().await.map_err(artifact_error)?.error_for_status().map_err(artifact_error)?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let response = self.request(Method::GET, key, &[], &[]).send().await.map_err(artifact_error)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let content = response.error_for_status().map_err(artifact_error)?.bytes().await.map_err(artifact_error)?;
+        Ok(Some(content.to_vec()))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let response = self.request(Method::DELETE, key, &[], &[]).send().await.map_err(artifact_error)?;
+        if response.status() != StatusCode::NOT_FOUND {
+            response.error_for_status().map_err(artifact_error)?;
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<Object>> {
+        let mut objects = vec![];
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.as_str()));
+            }
+            query.extend([("list-type", "2"), ("prefix", prefix)]);
+
+            let response = self.request(Method::GET, "", &query, &[]).send().await.map_err(artifact_error)?;
+            let body = response.error_for_status().map_err(artifact_error)?.text().await.map_err(artifact_error)?;
+            objects.extend(contents(&body)?);
+
+            token = match element(&body, "IsTruncated") {
+                Some("true") => element(&body, "NextContinuationToken").map(unescape),
+                _ => None,
+            };
+            if token.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+}
+
+/// Parse the objects of the `ListObjectsV2` response.
+fn contents(body: &str) -> Result<Vec<Object>> {
+    let mut objects = vec![];
+    for content in body.split("<Contents>").skip(1) {
+        let key = element(content, "Key").map(unescape);
+        let modified = element(content, "LastModified").map(DateTime::parse_from_rfc3339);
+        if let (Some(key), Some(modified)) = (key, modified) {
+            objects.push(Object { key, modified: modified.map_err(artifact_error)?.with_timezone(&Utc) });
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Returns the text of the first element of the name, the responses are simple enough to not need an XML parser.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", name))? + start;
+
+    Some(&xml[start..end])
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// Percent-encode all but the unreserved characters, as required by the canonical query.
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_key() {
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex(&key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("sources/"), "sources%2F");
+        assert_eq!(encode("a b~"), "a%20b~");
+    }
+
+    #[test]
+    fn test_contents() {
+        let body = r#"<ListBucketResult><IsTruncated>true</IsTruncated>
+            <Contents><Key>sboms/abc</Key><LastModified>2024-06-01T10:00:00.000Z</LastModified></Contents>
+            <NextContinuationToken>1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=</NextContinuationToken>
+            </ListBucketResult>"#;
+
+        let objects = contents(body).unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].key, "sboms/abc");
+        assert_eq!(objects[0].modified.to_rfc3339(), "2024-06-01T10:00:00+00:00");
+        assert_eq!(element(body, "IsTruncated"), Some("true"));
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_nats::jetstream::{self, stream};
+use k8s_openapi::chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+use crate::error::{Error, Result};
+
+/// The JetStream stream persisting the audit events.
+pub const AUDIT_STREAM: &str = "amp-audit";
+pub const AUDIT_SUBJECT: &str = "amp.audit";
+
+/// A mutating call, made through the API or by the controllers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct AuditEvent {
+    /// The sequence of the event, it's assigned when recorded.
+    #[serde(default)]
+    pub id: u64,
+    /// Who made the call, the fingerprint of the bearer token, `anonymous`
+    /// or the name of the controller.
+    pub actor: String,
+    /// The forwarded client address, if any.
+    pub source: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    /// The SHA-256 digest of the request payload read by the handler.
+    pub digest: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Publish the audit events to the JetStream stream, which is created on the
+/// first record and expires the events after the retention period.
+#[derive(Clone)]
+pub struct Publisher {
+    jetstream: jetstream::Context,
+    retention: Duration,
+    /// The stream is created once, instead of on every record.
+    stream: Arc<OnceCell<stream::Stream>>,
+}
+
+impl Publisher {
+    pub fn new(jetstream: jetstream::Context, retention: Duration) -> Self {
+        Self { jetstream, retention, stream: Arc::new(OnceCell::new()) }
+    }
+
+    /// Record the event and wait until it's persisted.
+    pub async fn record(&self, event: &AuditEvent) -> Result<()> {
+        self.stream().await?;
+
+        let payload = serde_json::to_vec(event).map_err(Error::SerializationError)?;
+        let ack = self.jetstream.publish(AUDIT_SUBJECT, payload.into()).await;
+        ack.map_err(|err| Error::AuditError(err.into()))?.await.map_err(|err| Error::AuditError(err.into()))?;
+
+        Ok(())
+    }
+
+    /// Returns the stream of the audit events, it's created if not exists.
+    pub async fn stream(&self) -> Result<&stream::Stream> {
+        let config = stream::Config {
+            name: AUDIT_STREAM.into(),
+            subjects: vec![AUDIT_SUBJECT.into()],
+            max_age: self.retention,
+            ..Default::default()
+        };
+        let stream = self.stream.get_or_try_init(|| self.jetstream.get_or_create_stream(config)).await;
+        stream.map_err(|err| Error::AuditError(err.into()))
+    }
+}
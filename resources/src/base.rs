@@ -0,0 +1,215 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::{Actor, ActorSpec};
+use k8s_openapi::api::batch::v1::Job;
+use kube::api::PostParams;
+use kube::{Api, Client, ResourceExt};
+use tracing::{debug, info};
+
+use crate::build::BuildResources;
+use crate::containers::kaniko;
+use crate::error::{Error, Result};
+use crate::{hash, job};
+
+/// The annotation of playbook declaring the shared base images of the monorepos,
+/// separated by commas, e.g. `https://github.com/org/monorepo#docker/base.Dockerfile`.
+pub const BASES_ANNOTATION: &str = "amphitheatre.app/base-images";
+
+/// The annotation of actor pointing to the Dockerfile of the shared base image,
+/// relative to the root of the repository.
+pub const BASE_ANNOTATION: &str = "amphitheatre.app/base-dockerfile";
+
+/// The prefix of the names of the base image build Jobs.
+const JOB_PREFIX: &str = "amp-base-";
+
+/// The build argument passing the base image to the Dockerfile of actor,
+/// which should build `FROM ${AMP_BASE_IMAGE}`.
+pub const BASE_IMAGE_BUILD_ARG: &str = "AMP_BASE_IMAGE";
+
+/// The base image shared by the actors from the same repository, it's built
+/// once per commit and all the dependent actors build from it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Base {
+    pub dockerfile: String,
+    pub image: String,
+}
+
+/// Returns the Dockerfile of the base image declared by the playbook for the actor's repository.
+pub fn dockerfile(declarations: &str, spec: &ActorSpec) -> Option<String> {
+    let source = spec.source.as_ref()?;
+    declarations
+        .split(',')
+        .filter_map(|declaration| declaration.trim().rsplit_once('#'))
+        .find(|(repo, _)| *repo == source.repo)
+        .map(|(_, dockerfile)| dockerfile.to_string())
+}
+
+/// Returns the base image of the actor if it's declared, the live actors
+/// are synced from local without a commit, so they never share one. The
+/// image is tagged with the revision, so an empty one is rejected.
+pub fn of(actor: &Actor) -> Result<Option<Base>> {
+    let Some(dockerfile) = actor.annotations().get(BASE_ANNOTATION) else {
+        return Ok(None);
+    };
+    let Some(source) = actor.spec.source.as_ref().filter(|_| !actor.spec.live) else {
+        return Ok(None);
+    };
+
+    let rev = source.rev();
+    if rev.is_empty() {
+        return Err(Error::InvalidBase(format!("no revision of {} to tag the base image with", source.repo)));
+    }
+
+    // The same repository and Dockerfile share the same image name,
+    // and it's pushed next to the actor's image.
+    let digest = hash(&(&source.repo, dockerfile))?;
+    let repository = actor.spec.image.rsplit_once('/').map_or("", |(repository, _)| repository);
+    let image = format!("{}/amp-base-{}:{}", repository, &digest[..12], rev);
+    let image = image.trim_start_matches('/').to_string();
+
+    Ok(Some(Base { dockerfile: dockerfile.clone(), image }))
+}
+
+/// Build the base image unless it's being built or has been built by another actor.
+pub async fn build(client: &Client, actor: &Actor, base: &Base, resources: &BuildResources) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(base)?;
+
+    if api.get_opt(&name).await.map_err(Error::KubeError)?.is_some() {
+        debug!("The base image {} is built by Job {} already", base.image, name);
+        return Ok(());
+    }
+
+    // Build it as an actor with the base Dockerfile from the root of the repository.
+    let mut builder = actor.clone();
+    builder.annotations_mut().remove(BASE_ANNOTATION);
+    builder.spec.image.clone_from(&base.image);
+    let mut build = builder.spec.character.build.clone().unwrap_or_default();
+    build.context = None;
+    if let Some(source) = builder.spec.source.as_mut() {
+        source.path = None;
+    }
+    build.args = None;
+    build.dockerfile.get_or_insert_with(Default::default).dockerfile.clone_from(&base.dockerfile);
+    builder.spec.character.build = Some(build);
+
+    let resource = job::new(actor, name, kaniko::pod(&builder, resources)?)?;
+    match api.create(&PostParams::default(), &resource).await {
+        Ok(job) => info!("Created base image build Job: {}", job.name_any()),
+        // Another actor of the same repository created it at the same time.
+        Err(kube::Error::Api(err)) if err.code == 409 => {}
+        Err(err) => return Err(Error::KubeError(err)),
+    }
+
+    Ok(())
+}
+
+/// Check if the base image is built, returns an error if the build failed,
+/// and the failed Job is deleted so the next reconciliation builds it again.
+pub async fn completed(client: &Client, actor: &Actor, base: &Base) -> Result<bool> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(base)?;
+
+    let Some(status) = api.get_opt(&name).await.map_err(Error::KubeError)?.and_then(|job| job.status) else {
+        debug!("Not found the status of base image build Job {}", name);
+        return Ok(false);
+    };
+    if status.failed >= Some(1) {
+        info!("The base image build Job {} failed, delete it for retrying", name);
+        job::replace(&api, &name).await?;
+        return Err(Error::BaseBuildFailed(name));
+    }
+
+    Ok(status.succeeded >= Some(1))
+}
+
+/// The name of the build job is derived from the image, so it's shared per commit.
+#[inline]
+fn job_name(base: &Base) -> Result<String> {
+    Ok(format!("{}{}", JOB_PREFIX, &hash(&base.image)?[..12]))
+}
+
+/// Returns true if the Job builds a base image.
+pub(crate) fn is_job(name: &str) -> bool {
+    name.starts_with(JOB_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::schema::GitReference;
+
+    fn actor(name: &str, repo: &str) -> Actor {
+        let source = GitReference { repo: repo.into(), rev: Some("abc123".into()), ..Default::default() };
+        let spec = ActorSpec {
+            name: name.into(),
+            image: format!("index.docker.io/amp/{}:abc123", name),
+            source: Some(source),
+            ..Default::default()
+        };
+        let mut actor = Actor::new(name, spec);
+        actor.annotations_mut().insert(BASE_ANNOTATION.into(), "Dockerfile.base".into());
+        actor
+    }
+
+    #[test]
+    fn test_base_of_actor() {
+        let base = of(&actor("web", "https://github.com/amp/monorepo")).unwrap().unwrap();
+
+        assert_eq!(base.dockerfile, "Dockerfile.base");
+        assert!(base.image.starts_with("index.docker.io/amp/amp-base-"));
+        assert!(base.image.ends_with(":abc123"));
+    }
+
+    #[test]
+    fn test_base_shared_by_repository() {
+        let web = of(&actor("web", "https://github.com/amp/monorepo")).unwrap();
+        let api = of(&actor("api", "https://github.com/amp/monorepo")).unwrap();
+        let other = of(&actor("api", "https://github.com/amp/other")).unwrap();
+
+        assert_eq!(web, api);
+        assert_ne!(web, other);
+    }
+
+    #[test]
+    fn test_dockerfile_of_repository() {
+        let declarations =
+            "https://github.com/amp/monorepo#Dockerfile.base, https://github.com/amp/other#base/Dockerfile";
+        let spec = actor("web", "https://github.com/amp/other").spec;
+        assert_eq!(dockerfile(declarations, &spec), Some("base/Dockerfile".into()));
+
+        let spec = actor("web", "https://github.com/amp/unknown").spec;
+        assert_eq!(dockerfile(declarations, &spec), None);
+    }
+
+    #[test]
+    fn test_base_without_revision() {
+        let mut actor = actor("web", "https://github.com/amp/monorepo");
+        actor.spec.source.as_mut().unwrap().rev = Some(String::new());
+
+        assert!(matches!(of(&actor), Err(Error::InvalidBase(_))));
+    }
+
+    #[test]
+    fn test_no_base_without_annotation() {
+        let mut actor = actor("web", "https://github.com/amp/monorepo");
+        actor.annotations_mut().remove(BASE_ANNOTATION);
+
+        assert_eq!(of(&actor).unwrap(), None);
+    }
+}
@@ -0,0 +1,871 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{ConfigMap, Pod, PodSpec, ResourceRequirements, Toleration};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::{DateTime, Duration, Utc};
+use kube::api::{DeleteParams, ListParams, LogParams, Patch, PatchParams, PostParams, PropagationPolicy};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+
+use crate::artifact::{Artifact, Kind, Store};
+use crate::containers::lifecycle;
+use crate::error::{Error, Result};
+use crate::{monorepo, naming, upload};
+
+/// The annotation of actor overriding the default resources and placement of
+/// its build pods as a JSON document, e.g. `{"cpu_limit": "4", "node_selector": {"pool": "builds"}}`.
+pub const BUILD_RESOURCES_ANNOTATION: &str = "amphitheatre.app/build-resources";
+
+/// The condition type of actor reporting its build is waiting for a slot.
+pub const BUILD_QUEUED_CONDITION_TYPE: &str = "BuildQueued";
+
+/// The annotation of actor holding the position of its build in the queue,
+/// it's removed once the build is admitted.
+pub const BUILD_POSITION_ANNOTATION: &str = "amphitheatre.app/build-position";
+
+/// The condition type of actor reporting its build failed, e.g. timed out.
+pub const BUILD_FAILED_CONDITION_TYPE: &str = "BuildFailed";
+
+/// The condition type of actor reporting its build pod can't make progress.
+pub const BUILD_STUCK_CONDITION_TYPE: &str = "BuildStuck";
+
+/// The ConfigMap in the namespace of the actors recording their recent builds as
+/// JSON lists keyed by their names, the latest last. They're not recorded in the
+/// actors, so the snapshots of the running builds don't trigger their reconciliations.
+pub const BUILDS_CONFIG_MAP: &str = "amp-builds";
+
+/// The number of the recent builds recorded on actor, the older ones are dropped.
+const MAX_BUILDS: usize = 20;
+
+/// How often the logs of the running build are archived, in seconds.
+const SNAPSHOT_INTERVAL: i64 = 60;
+
+/// The name of the container running the build in the build pods.
+const BUILDER_CONTAINER: &str = "builder";
+
+/// The label of the build pods of the kpack Images.
+const KPACK_IMAGE_LABEL: &str = "image.kpack.io/image";
+
+/// How long a build pod may wait for a node before it's reported stuck.
+const UNSCHEDULABLE_GRACE: i64 = 5 * 60;
+
+/// The resources and node placement of the build pods, in Kubernetes quantities.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct BuildResources {
+    pub cpu_request: Option<String>,
+    pub cpu_limit: Option<String>,
+    pub memory_request: Option<String>,
+    pub memory_limit: Option<String>,
+    /// The labels of the nodes the build pods are scheduled to.
+    #[serde(default)]
+    pub node_selector: BTreeMap<String, String>,
+    /// The taints of the nodes tolerated by the build pods.
+    #[serde(default)]
+    pub tolerations: Vec<Toleration>,
+    /// The PriorityClass of the build pods.
+    pub priority_class: Option<String>,
+    /// The priority of the builds waiting in the queue, the higher ones go first.
+    pub priority: Option<i32>,
+    /// Kill the build if it's not completed in this many seconds.
+    pub timeout: Option<i64>,
+    /// The number of the latest successful and failed kpack Builds kept for the actor.
+    pub history_limit: Option<i64>,
+}
+
+impl BuildResources {
+    /// Returns the resources with the fields set in the overrides replaced,
+    /// the node selectors are merged and the tolerations are replaced if any.
+    pub fn merge(&self, overrides: &BuildResources) -> BuildResources {
+        let mut node_selector = self.node_selector.clone();
+        node_selector.extend(overrides.node_selector.clone());
+        let tolerations =
+            if overrides.tolerations.is_empty() { self.tolerations.clone() } else { overrides.tolerations.clone() };
+
+        BuildResources {
+            cpu_request: overrides.cpu_request.clone().or_else(|| self.cpu_request.clone()),
+            cpu_limit: overrides.cpu_limit.clone().or_else(|| self.cpu_limit.clone()),
+            memory_request: overrides.memory_request.clone().or_else(|| self.memory_request.clone()),
+            memory_limit: overrides.memory_limit.clone().or_else(|| self.memory_limit.clone()),
+            node_selector,
+            tolerations,
+            priority_class: overrides.priority_class.clone().or_else(|| self.priority_class.clone()),
+            priority: overrides.priority.or(self.priority),
+            timeout: overrides.timeout.or(self.timeout),
+            history_limit: overrides.history_limit.or(self.history_limit),
+        }
+    }
+
+    /// Returns the resource requirements of the builder container, if any is configured.
+    pub fn requirements(&self) -> Option<ResourceRequirements> {
+        let quantities = |cpu: &Option<String>, memory: &Option<String>| {
+            let mut quantities = BTreeMap::new();
+            if let Some(cpu) = cpu {
+                quantities.insert("cpu".to_string(), Quantity(cpu.clone()));
+            }
+            if let Some(memory) = memory {
+                quantities.insert("memory".to_string(), Quantity(memory.clone()));
+            }
+            Some(quantities).filter(|quantities| !quantities.is_empty())
+        };
+        let requests = quantities(&self.cpu_request, &self.memory_request);
+        let limits = quantities(&self.cpu_limit, &self.memory_limit);
+        if requests.is_none() && limits.is_none() {
+            return None;
+        }
+
+        Some(ResourceRequirements { requests, limits, ..Default::default() })
+    }
+
+    /// Set the resources of the builder container and the placement of the build pod.
+    pub fn apply(&self, pod: &mut PodSpec) {
+        if let Some(requirements) = self.requirements() {
+            for container in pod.containers.iter_mut().filter(|c| c.name == BUILDER_CONTAINER) {
+                container.resources = Some(requirements.clone());
+            }
+        }
+        if !self.node_selector.is_empty() {
+            pod.node_selector = Some(self.node_selector.clone());
+        }
+        if !self.tolerations.is_empty() {
+            pod.tolerations = Some(self.tolerations.clone());
+        }
+        if let Some(class) = &self.priority_class {
+            pod.priority_class_name = Some(class.clone());
+        }
+    }
+
+    /// Set the resources and placement of the kpack build, kpack assigns
+    /// the priority classes of its builds itself.
+    pub fn apply_kpack(&self, build: &mut Value) -> Result<()> {
+        if let Some(requirements) = self.requirements() {
+            build["resources"] = serde_json::to_value(requirements).map_err(Error::SerializationError)?;
+        }
+        if !self.node_selector.is_empty() {
+            build["nodeSelector"] = json!(self.node_selector);
+        }
+        if !self.tolerations.is_empty() {
+            build["tolerations"] = serde_json::to_value(&self.tolerations).map_err(Error::SerializationError)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the build resources of the actor, the defaults overridden by its own.
+pub fn of(actor: &Actor, defaults: &BuildResources) -> Result<BuildResources> {
+    match actor.annotations().get(BUILD_RESOURCES_ANNOTATION) {
+        Some(content) => {
+            let overrides: BuildResources = serde_json::from_str(content).map_err(Error::SerializationError)?;
+            Ok(defaults.merge(&overrides))
+        }
+        None => Ok(defaults.clone()),
+    }
+}
+
+/// Build the condition reporting the position of the build in the queue,
+/// or that it's admitted if the position is `None`.
+pub fn queued(position: Option<usize>) -> Condition {
+    let (status, reason, message) = match position {
+        Some(position) => ("True", "Queued", format!("Position {} in the build queue", position)),
+        None => ("False", "Admitted", "The build is admitted".to_string()),
+    };
+
+    Condition {
+        type_: BUILD_QUEUED_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: reason.into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// Returns the position of the build of actor in the queue, if it's queued.
+pub fn position(actor: &Actor) -> Option<usize> {
+    actor.annotations().get(BUILD_POSITION_ANNOTATION).and_then(|position| position.parse().ok())
+}
+
+/// Record the position of the build of actor in the queue, or remove it once the build is admitted.
+pub async fn place(client: &Client, actor: &Actor, position: Option<usize>) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let position = position.map(|position| position.to_string());
+    let patch = json!({"metadata": {"annotations": { BUILD_POSITION_ANNOTATION: position }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    debug!("Placed the build of actor {} at {:?} in the queue", actor.name_any(), position);
+
+    Ok(())
+}
+
+/// The reason the build can't complete.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Failure {
+    /// The build is not completed in the given seconds.
+    Timeout(i64),
+    /// The image of a build container can't be pulled.
+    ImagePull(String),
+    /// The build pod can't be scheduled to any node.
+    Unschedulable(String),
+}
+
+impl Failure {
+    /// Returns true if the build is killed for it, the other failures may recover by themselves.
+    pub fn fatal(&self) -> bool {
+        matches!(self, Failure::Timeout(_))
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Failure::Timeout(_) => "Timeout",
+            Failure::ImagePull(_) => "ImagePullBackOff",
+            Failure::Unschedulable(_) => "Unschedulable",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Failure::Timeout(seconds) => format!("The build is not completed in {} seconds", seconds),
+            Failure::ImagePull(message) => format!("The build image can't be pulled: {}", message),
+            Failure::Unschedulable(message) => format!("The build pod can't be scheduled: {}", message),
+        }
+    }
+
+    /// Build the condition reporting the failure, `BuildFailed` for the fatal ones or `BuildStuck`.
+    pub fn condition(&self, generation: Option<i64>) -> Condition {
+        let type_ = if self.fatal() { BUILD_FAILED_CONDITION_TYPE } else { BUILD_STUCK_CONDITION_TYPE };
+        Condition {
+            type_: type_.into(),
+            status: "True".into(),
+            reason: self.reason().into(),
+            message: self.message(),
+            last_transition_time: Time(Utc::now()),
+            observed_generation: generation,
+        }
+    }
+}
+
+/// Diagnose the build pod, the unfinished pods running longer than the timeout
+/// are timed out, the pull failures are reported at once, and the pending pods
+/// after a grace period as the cluster may be scaling up.
+pub fn diagnose(pod: &Pod, timeout: Option<i64>, now: DateTime<Utc>) -> Option<Failure> {
+    let status = pod.status.clone().unwrap_or_default();
+    if matches!(status.phase.as_deref(), Some("Succeeded" | "Failed")) {
+        return None;
+    }
+
+    if let Some(timeout) = timeout {
+        let created = pod.creation_timestamp().map(|time| time.0);
+        if created.is_some_and(|created| now - created > Duration::seconds(timeout)) {
+            return Some(Failure::Timeout(timeout));
+        }
+    }
+
+    let containers = status.init_container_statuses.iter().chain(status.container_statuses.iter()).flatten();
+    for container in containers {
+        let waiting = container.state.as_ref().and_then(|state| state.waiting.as_ref());
+        if let Some(waiting) = waiting {
+            if matches!(waiting.reason.as_deref(), Some("ImagePullBackOff" | "ErrImagePull" | "InvalidImageName")) {
+                let message = waiting.message.clone().unwrap_or_else(|| container.image.clone());
+                return Some(Failure::ImagePull(message));
+            }
+        }
+    }
+
+    let scheduled = status.conditions.iter().flatten().find(|c| c.type_ == "PodScheduled" && c.status == "False");
+    if let Some(condition) = scheduled.filter(|c| c.reason.as_deref() == Some("Unschedulable")) {
+        let since = condition.last_transition_time.as_ref().map(|time| time.0).unwrap_or(now);
+        if now - since > Duration::seconds(UNSCHEDULABLE_GRACE) {
+            return Some(Failure::Unschedulable(condition.message.clone().unwrap_or_default()));
+        }
+    }
+
+    None
+}
+
+/// Apply the ConfigMap holding the order of the explicit buildpacks of actor
+/// for the Buildpacks lifecycle, nothing is applied if the builder's order is used.
+pub async fn apply_order(client: &Client, actor: &Actor) -> Result<()> {
+    let Some(resource) = lifecycle::order_config_map(actor) else {
+        return Ok(());
+    };
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), &namespace);
+
+    let name = lifecycle::order_name(actor);
+    let params = &PatchParams::apply("amp-controllers").force();
+    api.patch(&name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+    info!("Applied the buildpacks order {} of actor {}", name, actor.name_any());
+
+    Ok(())
+}
+
+/// Inspect the build pods of actor, either of the build Job or of the kpack Image.
+pub async fn inspect(client: &Client, actor: &Actor, timeout: Option<i64>) -> Result<Option<Failure>> {
+    let now = Utc::now();
+    let failure = pods(client, actor).await?.iter().find_map(|pod| diagnose(pod, timeout, now));
+    debug!("Inspected the build pods of actor {}: {:?}", actor.name_any(), failure);
+
+    Ok(failure)
+}
+
+/// Kill the build of actor, the build Job is deleted along with its pods,
+/// and the pods of kpack Builds are deleted so the Builds fail.
+pub async fn kill(client: &Client, actor: &Actor) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let name = naming::name(&[&actor.spec.name, "builder"]);
+
+    let api: Api<Job> = Api::namespaced(client.clone(), &namespace);
+    let params = DeleteParams { propagation_policy: Some(PropagationPolicy::Background), ..Default::default() };
+    match api.delete(&name, &params).await {
+        Ok(_) => info!("Deleted the build Job {}", name),
+        Err(kube::Error::Api(err)) if err.code == 404 => {}
+        Err(err) => return Err(Error::KubeError(err)),
+    }
+
+    let api: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    let params = ListParams::default().labels(&format!("{}={}", KPACK_IMAGE_LABEL, name));
+    for pod in api.list(&params).await.map_err(Error::KubeError)?.items {
+        match api.delete(&pod.name_any(), &DeleteParams::default()).await {
+            Ok(_) => info!("Deleted the build pod {}", pod.name_any()),
+            Err(kube::Error::Api(err)) if err.code == 404 => {}
+            Err(err) => return Err(Error::KubeError(err)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the keys of the actors (`{namespace}/{name}`) whose build Jobs are
+/// running in the cluster, they hold the build slots across the restarts.
+pub async fn running(client: &Client) -> Result<Vec<String>> {
+    let api: Api<Job> = Api::all(client.clone());
+    let params = ListParams::default().labels("app.kubernetes.io/managed-by=Amphitheatre");
+    let jobs = api.list(&params).await.map_err(Error::KubeError)?;
+
+    let keys = jobs
+        .items
+        .iter()
+        .filter(|job| job.status.as_ref().and_then(|status| status.active).unwrap_or_default() > 0)
+        .filter_map(|job| {
+            // The other Jobs of actor, e.g. the signing, are not builds.
+            let character = job.labels().get("amphitheatre.app/character")?;
+            if job.name_any() != naming::name(&[character, "builder"]) {
+                return None;
+            }
+            let owner = job.owner_references().iter().find(|owner| owner.kind == "Actor")?;
+            Some(format!("{}/{}", job.namespace()?, owner.name))
+        })
+        .collect();
+
+    Ok(keys)
+}
+
+/// Archive the logs of the latest build pod of actor to the artifact store, so
+/// they outlive the pod, and record the finished build.
+pub async fn archive(
+    client: &Client,
+    store: &Store,
+    actor: &Actor,
+    builder: &str,
+    status: Status,
+    digest: Option<&str>,
+) -> Result<Option<Artifact>> {
+    let Some(pod) = latest(client, actor).await? else {
+        return Ok(None);
+    };
+
+    let update = |record: &mut Record| {
+        record.builder = Some(builder.into());
+        record.status = status;
+        record.digest = digest.map(String::from);
+        record.finished = Some(Utc::now());
+    };
+    save(client, store, actor, &pod, update).await
+}
+
+/// Archive the logs of the running build every once in a while, so they're
+/// kept even if its pod is gone before the build finishes.
+pub async fn snapshot(client: &Client, store: &Store, actor: &Actor, builder: &str) -> Result<Option<Artifact>> {
+    let Some(pod) = latest(client, actor).await? else {
+        return Ok(None);
+    };
+    let records = records(client, actor).await?;
+    let archived = records.iter().find(|r| r.id == pod.name_any()).and_then(|r| r.archived);
+    if archived.is_some_and(|time| Utc::now() - time < Duration::seconds(SNAPSHOT_INTERVAL)) {
+        return Ok(None);
+    }
+
+    save(client, store, actor, &pod, |record| record.builder = Some(builder.into())).await
+}
+
+/// Returns the latest build pod of actor.
+async fn latest(client: &Client, actor: &Actor) -> Result<Option<Pod>> {
+    let pods = pods(client, actor).await?;
+    Ok(pods.into_iter().max_by_key(|pod| pod.creation_timestamp().map(|time| time.0)))
+}
+
+/// Store the logs of all the containers of the build pod, and record them in
+/// the build of the pod updated by the caller. The build is recorded even if
+/// its logs can't be stored, e.g. the artifact store is unavailable.
+async fn save(
+    client: &Client,
+    store: &Store,
+    actor: &Actor,
+    pod: &Pod,
+    update: impl FnOnce(&mut Record),
+) -> Result<Option<Artifact>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    let spec = pod.spec.clone().unwrap_or_default();
+    let containers = spec.init_containers.unwrap_or_default().into_iter().chain(spec.containers);
+
+    let mut content = String::new();
+    for container in containers {
+        let params = LogParams { container: Some(container.name.clone()), ..Default::default() };
+        match api.logs(&pod.name_any(), &params).await {
+            Ok(logs) => content.push_str(&format!("==> {} <==\n{}\n", container.name, logs)),
+            // The containers after the failed one have never started.
+            Err(err) => debug!("Skipped the logs of container {} of pod {}: {}", container.name, pod.name_any(), err),
+        }
+    }
+
+    let artifact = match store.put(Kind::BuildLogs, content.into_bytes()).await {
+        Ok(artifact) => {
+            info!("Archived the build logs of actor {} as {}", actor.name_any(), artifact.digest);
+            Some(artifact)
+        }
+        Err(err) => {
+            warn!("Failed to archive the build logs of actor {}: {}", actor.name_any(), err);
+            None
+        }
+    };
+
+    let mut records = records(client, actor).await?;
+    let mut record = match records.iter().find(|r| r.id == pod.name_any()) {
+        Some(record) => record.clone(),
+        None => Record::new(actor, pod),
+    };
+    if let Some(artifact) = &artifact {
+        record.logs = Some(artifact.digest.clone());
+    }
+    record.archived = Some(Utc::now());
+    update(&mut record);
+    upsert(&mut records, record);
+    write(client, actor, &records).await?;
+
+    Ok(artifact)
+}
+
+/// The status of a build.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum Status {
+    #[default]
+    Running,
+    Succeeded,
+    Failed,
+    /// It never finished, e.g. retried by another pod or superseded by a newer build.
+    Interrupted,
+}
+
+/// What a build is triggered by.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// The commit pushed to the branch, notified by the webhook.
+    Push,
+    /// The files synced from local to the live actor.
+    Sync,
+    /// The source uploaded from local.
+    Upload,
+    /// The actor is created or its spec is changed.
+    Spec,
+}
+
+impl Trigger {
+    /// Returns what the current build of actor is triggered by.
+    pub fn of(actor: &Actor) -> Self {
+        let rev = actor.spec.source.as_ref().and_then(|source| source.rev.as_ref());
+        if actor.spec.live {
+            Trigger::Sync
+        } else if upload::of(&actor.spec).is_some() {
+            Trigger::Upload
+        } else if rev.is_some() && actor.annotations().get(monorepo::PUSHED_ANNOTATION) == rev {
+            Trigger::Push
+        } else {
+            Trigger::Spec
+        }
+    }
+}
+
+/// A build of actor, identified by the name of its build pod, so the retries
+/// of a build are recorded on their own.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Record {
+    pub id: String,
+    #[serde(default)]
+    pub status: Status,
+    /// The builder, `kaniko`, `lifecycle`, `nixpacks` or `kpack`.
+    pub builder: Option<String>,
+    pub trigger: Option<Trigger>,
+    /// The commit built from, unknown for the live and uploaded sources.
+    pub revision: Option<String>,
+    /// The digest of the built image, once succeeded.
+    pub digest: Option<String>,
+    pub started: Option<DateTime<Utc>>,
+    pub finished: Option<DateTime<Utc>>,
+    /// The digest of the archived logs, a snapshot while the build is running.
+    pub logs: Option<String>,
+    /// When the logs were archived last time.
+    pub archived: Option<DateTime<Utc>>,
+}
+
+impl Record {
+    /// Start recording the build of the pod.
+    fn new(actor: &Actor, pod: &Pod) -> Self {
+        let source = actor.spec.source.as_ref().filter(|_| !actor.spec.live);
+        Self {
+            id: pod.name_any(),
+            trigger: Some(Trigger::of(actor)),
+            revision: source.and_then(|source| source.rev.clone()),
+            started: pod.creation_timestamp().map(|time| time.0),
+            ..Default::default()
+        }
+    }
+
+    /// Returns how long the build took, or has taken so far if it's running.
+    pub fn duration(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let finished = match self.status {
+            Status::Running => now,
+            _ => self.finished?,
+        };
+        Some(finished - self.started?)
+    }
+}
+
+/// Returns the recent builds of actor from its namespace, the latest last.
+pub async fn records(client: &Client, actor: &Actor) -> Result<Vec<Record>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), &namespace);
+    let config_map = api.get_opt(BUILDS_CONFIG_MAP).await.map_err(Error::KubeError)?;
+
+    match config_map.and_then(|config_map| config_map.data).and_then(|mut data| data.remove(&actor.name_any())) {
+        Some(content) => parse(&content),
+        None => Ok(vec![]),
+    }
+}
+
+fn parse(content: &str) -> Result<Vec<Record>> {
+    serde_json::from_str(content).map_err(Error::SerializationError)
+}
+
+/// Save the recent builds of actor into the ConfigMap of its namespace.
+async fn write(client: &Client, actor: &Actor, records: &[Record]) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), &namespace);
+    let content = serde_json::to_string(records).map_err(Error::SerializationError)?;
+
+    let patch = json!({ "data": { actor.name_any(): content } });
+    match api.patch(BUILDS_CONFIG_MAP, &PatchParams::default(), &Patch::Merge(&patch)).await {
+        Err(kube::Error::Api(err)) if err.code == 404 => {
+            let resource = ConfigMap {
+                metadata: ObjectMeta {
+                    name: Some(BUILDS_CONFIG_MAP.into()),
+                    labels: Some(BTreeMap::from([("app.kubernetes.io/managed-by".into(), "Amphitheatre".into())])),
+                    ..Default::default()
+                },
+                data: Some(BTreeMap::from([(actor.name_any(), content)])),
+                ..Default::default()
+            };
+            api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+        }
+        result => {
+            result.map_err(Error::KubeError)?;
+        }
+    }
+    debug!("Saved the builds of actor {}", actor.name_any());
+
+    Ok(())
+}
+
+/// Replace the record of the same build, or append it and drop the oldest
+/// ones, the earlier builds still running are interrupted by the new one.
+fn upsert(records: &mut Vec<Record>, record: Record) {
+    match records.iter_mut().find(|r| r.id == record.id) {
+        Some(existing) => *existing = record,
+        None => {
+            for existing in records.iter_mut().filter(|r| r.status == Status::Running) {
+                existing.status = Status::Interrupted;
+            }
+            records.push(record);
+        }
+    }
+    if records.len() > MAX_BUILDS {
+        records.drain(..records.len() - MAX_BUILDS);
+    }
+}
+
+/// Returns true if the build of actor failed for its current generation, it's not retried until the spec changes.
+pub fn failed(actor: &Actor) -> bool {
+    let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    conditions.iter().any(|c| {
+        c.type_ == BUILD_FAILED_CONDITION_TYPE && c.status == "True" && c.observed_generation == actor.meta().generation
+    })
+}
+
+async fn pods(client: &Client, actor: &Actor) -> Result<Vec<Pod>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    let name = naming::name(&[&actor.spec.name, "builder"]);
+
+    let mut pods = vec![];
+    for selector in [format!("job-name={}", name), format!("{}={}", KPACK_IMAGE_LABEL, name)] {
+        pods.extend(api.list(&ListParams::default().labels(&selector)).await.map_err(Error::KubeError)?.items);
+    }
+
+    Ok(pods)
+}
+
+/// Parse the toleration of a taint in the form of `kubectl taint`, i.e.
+/// `key=value:Effect`, `key:Effect` or `key`, the latter tolerating all the effects.
+pub fn toleration(taint: &str) -> Result<Toleration> {
+    let (taint, effect) = match taint.split_once(':') {
+        Some((taint, effect)) => (taint, Some(effect)),
+        None => (taint, None),
+    };
+    if let Some(effect) = effect.filter(|e| !["NoSchedule", "PreferNoSchedule", "NoExecute"].contains(e)) {
+        return Err(Error::InvalidToleration(format!("unknown effect {}", effect)));
+    }
+    let (key, value) = match taint.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (taint, None),
+    };
+    if key.is_empty() {
+        return Err(Error::InvalidToleration(format!("missing key of {}", taint)));
+    }
+
+    Ok(Toleration {
+        key: Some(key.into()),
+        operator: Some(if value.is_some() { "Equal" } else { "Exists" }.into()),
+        value: value.map(String::from),
+        effect: effect.map(String::from),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+    use amp_common::schema::GitReference;
+    use k8s_openapi::api::core::v1::Container;
+
+    #[test]
+    fn test_merge_overrides() {
+        let defaults = BuildResources {
+            cpu_limit: Some("2".into()),
+            memory_limit: Some("4Gi".into()),
+            node_selector: BTreeMap::from([("pool".into(), "builds".into())]),
+            tolerations: vec![toleration("builds:NoSchedule").unwrap()],
+            ..Default::default()
+        };
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(of(&actor, &defaults).unwrap(), defaults);
+
+        let overrides = r#"{"cpu_limit": "8", "node_selector": {"arch": "arm64"}, "priority_class": "low"}"#;
+        actor.annotations_mut().insert(BUILD_RESOURCES_ANNOTATION.into(), overrides.into());
+        let merged = of(&actor, &defaults).unwrap();
+        assert_eq!(merged.cpu_limit, Some("8".into()));
+        assert_eq!(merged.memory_limit, Some("4Gi".into()));
+        assert_eq!(merged.node_selector.len(), 2);
+        assert_eq!(merged.tolerations, defaults.tolerations);
+        assert_eq!(merged.priority_class, Some("low".into()));
+    }
+
+    #[test]
+    fn test_apply_to_builder() {
+        let resources = BuildResources {
+            cpu_request: Some("500m".into()),
+            memory_limit: Some("2Gi".into()),
+            priority_class: Some("low".into()),
+            ..Default::default()
+        };
+        let mut pod = PodSpec {
+            init_containers: Some(vec![Container { name: "syncer".into(), ..Default::default() }]),
+            containers: vec![Container { name: "builder".into(), ..Default::default() }],
+            ..Default::default()
+        };
+        resources.apply(&mut pod);
+
+        let requirements = pod.containers[0].resources.clone().unwrap();
+        assert_eq!(requirements.requests.unwrap()["cpu"], Quantity("500m".into()));
+        assert_eq!(requirements.limits.unwrap()["memory"], Quantity("2Gi".into()));
+        assert_eq!(pod.init_containers.unwrap()[0].resources, None);
+        assert_eq!(pod.priority_class_name, Some("low".into()));
+        assert_eq!(pod.node_selector, None);
+
+        let mut pod = PodSpec::default();
+        BuildResources::default().apply(&mut pod);
+        assert_eq!(pod, PodSpec::default());
+    }
+
+    #[test]
+    fn test_queue_position() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(position(&actor), None);
+
+        actor.annotations_mut().insert(BUILD_POSITION_ANNOTATION.into(), "3".into());
+        assert_eq!(position(&actor), Some(3));
+        assert_eq!(queued(position(&actor)).message, "Position 3 in the build queue");
+        assert_eq!(queued(None).status, "False");
+    }
+
+    #[test]
+    fn test_diagnose_timeout() {
+        use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+
+        let now = Utc::now();
+        let created = Time(now - Duration::seconds(120));
+        let pod = Pod {
+            metadata: ObjectMeta { creation_timestamp: Some(created), ..Default::default() },
+            ..Default::default()
+        };
+        assert_eq!(diagnose(&pod, Some(60), now), Some(Failure::Timeout(60)));
+        assert_eq!(diagnose(&pod, Some(600), now), None);
+        assert_eq!(diagnose(&pod, None, now), None);
+    }
+
+    #[test]
+    fn test_diagnose_stuck() {
+        use k8s_openapi::api::core::v1::{
+            ContainerState, ContainerStateWaiting, ContainerStatus, PodCondition, PodStatus,
+        };
+
+        let now = Utc::now();
+        let waiting = ContainerStateWaiting { reason: Some("ImagePullBackOff".into()), message: None };
+        let container = ContainerStatus {
+            image: "gcr.io/kaniko-project/executor:v0".into(),
+            state: Some(ContainerState { waiting: Some(waiting), ..Default::default() }),
+            ..Default::default()
+        };
+        let status = PodStatus { init_container_statuses: Some(vec![container]), ..Default::default() };
+        let pod = Pod { status: Some(status), ..Default::default() };
+        let failure = diagnose(&pod, None, now).unwrap();
+        assert_eq!(failure, Failure::ImagePull("gcr.io/kaniko-project/executor:v0".into()));
+        assert!(!failure.fatal());
+
+        let condition = |minutes: i64| PodCondition {
+            type_: "PodScheduled".into(),
+            status: "False".into(),
+            reason: Some("Unschedulable".into()),
+            message: Some("0/3 nodes are available".into()),
+            last_transition_time: Some(Time(now - Duration::minutes(minutes))),
+            ..Default::default()
+        };
+        let pending = |minutes| Pod {
+            status: Some(PodStatus {
+                phase: Some("Pending".into()),
+                conditions: Some(vec![condition(minutes)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(diagnose(&pending(1), None, now), None);
+        assert_eq!(diagnose(&pending(10), None, now), Some(Failure::Unschedulable("0/3 nodes are available".into())));
+    }
+
+    #[test]
+    fn test_upsert_records() {
+        let record = |id: &str, status: Status| Record { id: id.into(), status, ..Default::default() };
+        let mut records = vec![];
+        upsert(&mut records, record("web-builder-a", Status::Running));
+        upsert(&mut records, record("web-builder-b", Status::Running));
+        assert_eq!(
+            records,
+            vec![record("web-builder-a", Status::Interrupted), record("web-builder-b", Status::Running)]
+        );
+
+        upsert(&mut records, record("web-builder-b", Status::Succeeded));
+        assert_eq!(records[1], record("web-builder-b", Status::Succeeded));
+
+        for i in 0..MAX_BUILDS {
+            upsert(&mut records, record(&format!("web-builder-{}", i), Status::Failed));
+        }
+        assert_eq!(records.len(), MAX_BUILDS);
+        assert_eq!(records[0].id, "web-builder-0");
+    }
+
+    #[test]
+    fn test_parse_records() {
+        assert_eq!(parse("[]").unwrap(), vec![]);
+
+        let content = r#"[{"id": "web-builder-x7k2p", "logs": "abc", "archived": "2026-10-15T08:00:00Z"}]"#;
+        let parsed = parse(content).unwrap();
+        assert_eq!(parsed[0].id, "web-builder-x7k2p");
+        assert_eq!(parsed[0].status, Status::Running);
+        assert_eq!(parsed[0].logs.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_record_duration() {
+        let started = "2026-10-15T08:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut record = Record { started: Some(started), ..Default::default() };
+        assert_eq!(record.duration(started + Duration::seconds(30)), Some(Duration::seconds(30)));
+
+        record.status = Status::Succeeded;
+        assert_eq!(record.duration(started + Duration::seconds(30)), None);
+        record.finished = Some(started + Duration::seconds(90));
+        assert_eq!(record.duration(started + Duration::seconds(300)), Some(Duration::seconds(90)));
+    }
+
+    #[test]
+    fn test_trigger() {
+        let source =
+            GitReference { repo: "https://github.com/amp/web".into(), rev: Some("1111".into()), ..Default::default() };
+        let mut actor = Actor::new("web", ActorSpec { source: Some(source), ..Default::default() });
+        assert_eq!(Trigger::of(&actor), Trigger::Spec);
+
+        actor.annotations_mut().insert(monorepo::PUSHED_ANNOTATION.into(), "1111".into());
+        assert_eq!(Trigger::of(&actor), Trigger::Push);
+
+        actor.spec.live = true;
+        assert_eq!(Trigger::of(&actor), Trigger::Sync);
+    }
+
+    #[test]
+    fn test_toleration() {
+        let parsed = toleration("dedicated=builds:NoSchedule").unwrap();
+        assert_eq!(parsed.key, Some("dedicated".into()));
+        assert_eq!(parsed.operator, Some("Equal".into()));
+        assert_eq!(parsed.value, Some("builds".into()));
+        assert_eq!(parsed.effect, Some("NoSchedule".into()));
+
+        let parsed = toleration("spot").unwrap();
+        assert_eq!(parsed.operator, Some("Exists".into()));
+        assert_eq!(parsed.effect, None);
+
+        assert!(toleration("spot:Never").is_err());
+        assert!(toleration("=builds").is_err());
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+
+use amp_common::resource::{CharacterSpec, Playbook, Preface};
+use kube::ResourceExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::containers::{lifecycle, sidecar};
+use crate::error::{Error, Result};
+use crate::kpack::reference;
+use crate::{
+    argocd, base, build, canary, cronjob, debug, detection, devcontainer, envset, export, exposure, healing, image,
+    include, job, network, playbook, probe, quota, reload, rollback, sbom, secret, signing, statefulset, strategy,
+    uptime, vars, verification, volume,
+};
+
+/// The version of the bundle format, the bundles of a newer version are rejected.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// The annotations of playbook configuring it and its characters, they are
+/// exported along with the characters, the per-character ones are suffixed
+/// by the name of character. The annotations bound to the cluster or the
+/// state of the playbook, e.g. its namespace and activity, are left out.
+const PORTABLE_ANNOTATIONS: [&str; 34] = [
+    argocd::ARGOCD_ANNOTATION,
+    base::BASES_ANNOTATION,
+    build::BUILD_RESOURCES_ANNOTATION,
+    canary::CANARY_ANNOTATION,
+    cronjob::SCHEDULE_ANNOTATION,
+    debug::DEBUG_ANNOTATION,
+    detection::BUILD_METHOD_ANNOTATION,
+    devcontainer::DEVCONTAINER_ANNOTATION,
+    envset::ENV_SETS_ANNOTATION,
+    export::EXPORT_ANNOTATION,
+    exposure::EXPOSURE_ANNOTATION,
+    healing::HEALING_ANNOTATION,
+    image::DIGEST_ANNOTATION,
+    image::VERIFY_SIGNATURE_ANNOTATION,
+    include::INCLUDES_ANNOTATION,
+    job::JOB_SETTINGS_ANNOTATION,
+    lifecycle::RUN_IMAGE_ANNOTATION,
+    network::NETWORK_ANNOTATION,
+    playbook::IDLE_TIMEOUT_ANNOTATION,
+    playbook::TTL_ANNOTATION,
+    probe::PROBES_ANNOTATION,
+    quota::QUOTA_ANNOTATION,
+    reference::BUILDER_ANNOTATION,
+    reload::RELOAD_ANNOTATION,
+    rollback::ROLLBACK_ANNOTATION,
+    sbom::SBOM_ANNOTATION,
+    secret::IMAGE_PULL_SECRETS_ANNOTATION,
+    sidecar::CONTAINERS_ANNOTATION,
+    signing::SIGNING_ANNOTATION,
+    statefulset::WORKLOAD_ANNOTATION,
+    strategy::STRATEGY_ANNOTATION,
+    uptime::UPTIME_ANNOTATION,
+    verification::VERIFICATION_ANNOTATION,
+    volume::VOLUMES_ANNOTATION,
+];
+
+/// The fields holding the environment variables and the arguments, their
+/// values are taken as secrets unless declared public.
+const SECRET_FIELDS: [&str; 2] = ["env", "args"];
+
+/// The format of the bundle document.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Format::Yaml => "application/yaml",
+            Format::Toml => "application/toml",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "toml" => Ok(Format::Toml),
+            _ => Err(Error::InvalidBundle(format!("unknown format {}", s))),
+        }
+    }
+}
+
+/// The portable document of a playbook, along with its characters and
+/// variables, to recreate it in another cluster or share it with teammates.
+///
+/// The secrets are never exported: the values of the variables and of the
+/// environment variables and arguments of the characters and their sidecars
+/// are all taken as secrets, unless declared public on export. The secret
+/// variables are listed by their names, and the other secret values are
+/// replaced by references to the variables named after their paths, e.g.
+/// `${vars.WEB_BUILD_ENV_NPM_TOKEN}`, so they are supplied on import.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Bundle {
+    pub version: u32,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The names of the secret variables to be supplied on import.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+    pub preface: Preface,
+    /// The resolved characters, they are deployed as is instead of being resolved again.
+    #[serde(default)]
+    pub characters: Vec<CharacterSpec>,
+    /// The annotations configuring the playbook and its characters.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Bundle {
+    /// Export the playbook, minus its secrets, the variables, environment
+    /// variables and arguments of the given names are exported as is.
+    pub fn of(playbook: &Playbook, public: &[String]) -> Result<Self> {
+        let public = |name: &str| public.iter().any(|p| p == name);
+        let (vars, hidden): (BTreeMap<_, _>, _) = vars::of(playbook)?.into_iter().partition(|(name, _)| public(name));
+        let mut secrets: BTreeSet<String> = hidden.into_keys().collect();
+
+        let mut characters = vec![];
+        for character in playbook.spec.characters.iter().flatten() {
+            let mut value = serde_json::to_value(character).map_err(Error::SerializationError)?;
+            scrub(&mut value, &[character.meta.name.as_str()], &public, &mut secrets);
+            characters.push(serde_json::from_value(value).map_err(Error::SerializationError)?);
+        }
+
+        let mut annotations = BTreeMap::new();
+        for (key, value) in playbook.annotations().iter().filter(|(key, _)| portable(key)) {
+            let mut value = value.clone();
+            // The sidecars declare their environment variables as well.
+            if let Some(character) = key.strip_prefix(&format!("{}.", sidecar::CONTAINERS_ANNOTATION)) {
+                let mut containers: Value = serde_json::from_str(&value).map_err(Error::SerializationError)?;
+                scrub(&mut containers, &[character, "containers"], &public, &mut secrets);
+                value = containers.to_string();
+            }
+            annotations.insert(key.clone(), value);
+        }
+
+        Ok(Self {
+            version: BUNDLE_VERSION,
+            title: playbook.spec.title.clone(),
+            description: playbook.spec.description.clone(),
+            secrets: secrets.into_iter().collect(),
+            vars,
+            preface: playbook.spec.preface.clone(),
+            characters,
+            annotations,
+        })
+    }
+
+    /// Parse the bundle from the document in the given format.
+    pub fn parse(content: &str, format: Format) -> Result<Self> {
+        let bundle: Self = match format {
+            Format::Yaml => serde_yaml::from_str(content).map_err(Error::YamlSerializationError)?,
+            Format::Toml => toml::from_str(content).map_err(Error::TomlDeserializeError)?,
+        };
+        if bundle.version > BUNDLE_VERSION {
+            return Err(Error::InvalidBundle(format!("unsupported version {}", bundle.version)));
+        }
+
+        Ok(bundle)
+    }
+
+    /// Serialize the bundle to a document in the given format.
+    pub fn to_string(&self, format: Format) -> Result<String> {
+        match format {
+            Format::Yaml => serde_yaml::to_string(self).map_err(Error::YamlSerializationError),
+            Format::Toml => toml::to_string(self).map_err(Error::TomlSerializationError),
+        }
+    }
+
+    /// Returns the variables of bundle along with the supplied secrets, it
+    /// fails if any of the secrets is missing.
+    pub fn vars(&self, secrets: &HashMap<String, String>) -> Result<BTreeMap<String, String>> {
+        let missing: Vec<String> = self.secrets.iter().filter(|name| !secrets.contains_key(*name)).cloned().collect();
+        if !missing.is_empty() {
+            return Err(Error::InvalidBundle(format!("missing secrets {}", missing.join(", "))));
+        }
+
+        let mut vars = self.vars.clone();
+        vars.extend(secrets.iter().map(|(name, value)| (name.clone(), value.clone())));
+
+        Ok(vars)
+    }
+}
+
+/// Returns true if the annotation of playbook is exported, along with the per-character ones.
+fn portable(key: &str) -> bool {
+    PORTABLE_ANNOTATIONS.iter().any(|a| key == *a || key.strip_prefix(a).is_some_and(|k| k.starts_with('.')))
+}
+
+/// Replace the values of the environment variables and arguments in the value
+/// by references to the secret variables named after their paths, unless they
+/// are public, or are references to a single variable already.
+fn scrub(value: &mut Value, path: &[&str], public: &dyn Fn(&str) -> bool, secrets: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                let path = [path, &[name.as_str()][..]].concat();
+                match SECRET_FIELDS.contains(&name.as_str()) {
+                    true => hide(field, &path, public, secrets),
+                    false => scrub(field, &path, public, secrets),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                scrub(item, &[path, &[i.to_string().as_str()][..]].concat(), public, secrets);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Hide the values of the environment variables or arguments, keyed by their
+/// names in a map, or by their positions in a list.
+fn hide(value: &mut Value, path: &[&str], public: &dyn Fn(&str) -> bool, secrets: &mut BTreeSet<String>) {
+    let entries: Vec<(String, &mut Value)> = match value {
+        Value::Object(fields) => fields.iter_mut().map(|(name, value)| (name.clone(), value)).collect(),
+        Value::Array(items) => items.iter_mut().enumerate().map(|(i, value)| (i.to_string(), value)).collect(),
+        _ => return,
+    };
+
+    for (key, value) in entries {
+        let name = variable(&[path, &[key.as_str()][..]].concat());
+        let Value::String(text) = value else {
+            continue;
+        };
+        if public(&key) || public(&name) || reference(text) {
+            continue;
+        }
+        *text = format!("{}{}}}", vars::PREFIX, name);
+        secrets.insert(name);
+    }
+}
+
+/// Returns true if the text is only a reference to a single variable.
+fn reference(text: &str) -> bool {
+    text.strip_prefix(vars::PREFIX)
+        .and_then(|rest| rest.strip_suffix('}'))
+        .is_some_and(|name| !name.is_empty() && !name.contains('}'))
+}
+
+/// Build the name of the variable from the path, e.g. `WEB_BUILD_ENV_NPM_TOKEN`.
+fn variable(path: &[&str]) -> String {
+    let name = path.join("_").to_ascii_uppercase();
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::PlaybookSpec;
+    use serde_json::json;
+
+    #[test]
+    fn test_export_without_secrets() {
+        let mut character = CharacterSpec::default();
+        character.meta.name = "web".into();
+        let env = [("API_URL", "${vars.API_URL}"), ("DATABASE_URL", "postgres://u:p@db"), ("PORT", "8080")];
+        let mut spec = serde_json::to_value(&character).unwrap();
+        spec["deploy"] = json!({"env": env.into_iter().collect::<BTreeMap<_, _>>()});
+        spec["build"] = json!({"env": {"NPM_TOKEN": "npm_abc"}});
+        let character: CharacterSpec = serde_json::from_value(spec).unwrap();
+
+        let spec = PlaybookSpec { title: "demo".into(), characters: Some(vec![character]), ..Default::default() };
+        let mut playbook = Playbook::new("demo", spec);
+        let vars = r#"{"API_URL": "https://api.example.com", "API_TOKEN": "abc"}"#;
+        playbook.annotations_mut().insert(vars::VARS_ANNOTATION.into(), vars.into());
+        let containers = r#"{"sidecars": [{"name": "proxy", "image": "envoy", "env": {"ADMIN": "s3cret"}}]}"#;
+        playbook.annotations_mut().insert(format!("{}.web", sidecar::CONTAINERS_ANNOTATION), containers.into());
+        playbook.annotations_mut().insert(format!("{}.web", statefulset::WORKLOAD_ANNOTATION), "statefulset".into());
+        playbook.annotations_mut().insert(playbook::LAST_ACTIVITY_ANNOTATION.into(), "2024-01-01T00:00:00Z".into());
+
+        let bundle = Bundle::of(&playbook, &["API_URL".into(), "PORT".into()]).unwrap();
+        assert_eq!(
+            bundle.secrets,
+            vec![
+                "API_TOKEN",
+                "WEB_BUILD_ENV_NPM_TOKEN",
+                "WEB_CONTAINERS_SIDECARS_0_ENV_ADMIN",
+                "WEB_DEPLOY_ENV_DATABASE_URL"
+            ]
+        );
+        assert_eq!(bundle.vars, BTreeMap::from([("API_URL".into(), "https://api.example.com".into())]));
+
+        let spec = serde_json::to_value(&bundle.characters[0]).unwrap();
+        assert_eq!(spec["deploy"]["env"]["DATABASE_URL"], "${vars.WEB_DEPLOY_ENV_DATABASE_URL}");
+        assert_eq!(spec["deploy"]["env"]["API_URL"], "${vars.API_URL}");
+        assert_eq!(spec["deploy"]["env"]["PORT"], "8080");
+        assert_eq!(spec["build"]["env"]["NPM_TOKEN"], "${vars.WEB_BUILD_ENV_NPM_TOKEN}");
+
+        assert_eq!(bundle.annotations.len(), 2);
+        assert_eq!(bundle.annotations[&format!("{}.web", statefulset::WORKLOAD_ANNOTATION)], "statefulset");
+
+        for format in [Format::Yaml, Format::Toml] {
+            let content = bundle.to_string(format).unwrap();
+            for secret in ["postgres://", "npm_abc", "s3cret", "\"abc\""] {
+                assert!(!content.contains(secret), "{} is exported", secret);
+            }
+            let parsed = Bundle::parse(&content, format).unwrap();
+            assert_eq!(parsed.secrets, bundle.secrets);
+            assert_eq!(parsed.annotations, bundle.annotations);
+            assert_eq!(parsed.characters[0].meta.name, "web");
+        }
+
+        assert!(bundle.vars(&HashMap::new()).is_err());
+        let secrets: HashMap<String, String> = bundle.secrets.iter().map(|name| (name.clone(), "x".into())).collect();
+        let vars = bundle.vars(&secrets).unwrap();
+        assert_eq!(vars.len(), 5);
+        assert_eq!(vars["API_TOKEN"], "x");
+    }
+
+    #[test]
+    fn test_hide_args() {
+        let mut value = json!({"build": {"args": ["--token=abc", "${vars.TAG}"]}});
+        let mut secrets = BTreeSet::new();
+        scrub(&mut value, &["web"], &|_| false, &mut secrets);
+
+        assert_eq!(value["build"]["args"], json!(["${vars.WEB_BUILD_ARGS_0}", "${vars.TAG}"]));
+        assert_eq!(secrets, BTreeSet::from(["WEB_BUILD_ARGS_0".to_string()]));
+
+        assert!(reference("${vars.TAG}"));
+        assert!(!reference("${vars.HOST}:${vars.PORT}"));
+        assert!(!reference("https://${vars.HOST}"));
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("YAML".parse::<Format>().unwrap(), Format::Yaml);
+        assert_eq!("toml".parse::<Format>().unwrap(), Format::Toml);
+        assert!("json".parse::<Format>().is_err());
+    }
+}
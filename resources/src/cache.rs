@@ -0,0 +1,122 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, RwLock};
+
+use amp_common::resource::Actor;
+use futures::FutureExt;
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::batch::v1::Job;
+use kube::runtime::reflector::{ObjectRef, Store};
+use kube::Resource;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref STORES: RwLock<Stores> = RwLock::new(Stores::default());
+}
+
+/// The reflector stores installed by the controllers, the helpers look up
+/// the objects in them before asking the API server. The stores may lag
+/// behind the API server, so only the lookups tolerating it use them, the
+/// existence of the objects is always checked with the API server.
+#[derive(Default)]
+struct Stores {
+    actors: Option<Store<Actor>>,
+    jobs: Option<Store<Job>>,
+    deployments: Option<Store<Deployment>>,
+}
+
+pub fn install_actors(store: Store<Actor>) {
+    STORES.write().unwrap().actors = Some(store);
+}
+
+pub fn install_jobs(store: Store<Job>) {
+    STORES.write().unwrap().jobs = Some(store);
+}
+
+pub fn install_deployments(store: Store<Deployment>) {
+    STORES.write().unwrap().deployments = Some(store);
+}
+
+/// Returns the cached actor, `None` if it's not cached or the store is not installed.
+pub fn actor(namespace: &str, name: &str) -> Option<Arc<Actor>> {
+    get(&STORES.read().unwrap().actors, namespace, name)
+}
+
+/// Returns the cached job, `None` if it's not cached or the store is not installed.
+pub fn job(namespace: &str, name: &str) -> Option<Arc<Job>> {
+    get(&STORES.read().unwrap().jobs, namespace, name)
+}
+
+/// Returns the cached deployment, `None` if it's not cached or the store is not installed.
+pub fn deployment(namespace: &str, name: &str) -> Option<Arc<Deployment>> {
+    get(&STORES.read().unwrap().deployments, namespace, name)
+}
+
+/// Returns true once all the stores are installed and have received their
+/// initial lists, i.e. the informers of the controllers are synced.
+pub fn synced() -> bool {
+    let stores = STORES.read().unwrap();
+    ready(&stores.actors) && ready(&stores.jobs) && ready(&stores.deployments)
+}
+
+fn ready<K>(store: &Option<Store<K>>) -> bool
+where
+    K: Resource<DynamicType = ()> + Clone + 'static,
+{
+    let ready = store.as_ref().map(|store| store.wait_until_ready().now_or_never());
+    matches!(ready, Some(Some(Ok(()))))
+}
+
+fn get<K>(store: &Option<Store<K>>, namespace: &str, name: &str) -> Option<Arc<K>>
+where
+    K: Resource<DynamicType = ()> + Clone + 'static,
+{
+    store.as_ref()?.get(&ObjectRef::new(name).within(namespace))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+    use kube::runtime::reflector::store;
+    use kube::runtime::watcher;
+
+    #[test]
+    fn test_cached_actor() {
+        assert!(get::<Actor>(&None, "amp-test", "web").is_none());
+
+        let (reader, mut writer) = store::<Actor>();
+        let mut actor = Actor::new("web", ActorSpec::default());
+        actor.meta_mut().namespace = Some("amp-test".into());
+        writer.apply_watcher_event(&watcher::Event::Applied(actor));
+
+        let store = Some(reader);
+        assert!(get(&store, "amp-test", "web").is_some());
+        assert!(get(&store, "amp-other", "web").is_none());
+    }
+
+    #[test]
+    fn test_ready() {
+        assert!(!ready::<Actor>(&None));
+
+        let (reader, mut writer) = store::<Actor>();
+        let store = Some(reader);
+        assert!(!ready(&store));
+
+        writer.apply_watcher_event(&watcher::Event::Restarted(vec![]));
+        assert!(ready(&store));
+    }
+}
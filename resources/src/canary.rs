@@ -0,0 +1,324 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::apps::v1::{Deployment, ReplicaSet};
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::api::{ListParams, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::error::{Error, Result};
+use crate::{deployment, naming};
+
+/// The annotation of actor holding its canary rollout as a JSON document, e.g.
+/// `{"steps": [{"weight": 10, "pause": 300}, {"weight": 50, "pause": 600}]}`.
+/// The playbook declares the canary of a character with the annotation
+/// suffixed by its name, e.g. `amphitheatre.app/canary.web`.
+pub const CANARY_ANNOTATION: &str = "amphitheatre.app/canary";
+
+/// The annotation of actor recording the progress of its canary rollout.
+pub const CANARY_STATUS_ANNOTATION: &str = "amphitheatre.app/canary-status";
+
+/// The label of the pods of the canary Deployment.
+pub const TRACK_LABEL: &str = "amphitheatre.app/track";
+
+/// The condition type of actor reporting its canary rollout.
+pub const CANARY_CONDITION_TYPE: &str = "Canary";
+
+/// The canary rollout of actor, the updates are rolled out to a part of the
+/// traffic step by step, and rolled back if the canary pods are unhealthy.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Canary {
+    pub steps: Vec<Step>,
+    /// Roll back if the canary pods are not ready in this many seconds of a step.
+    #[serde(default = "default_progress_deadline")]
+    pub progress_deadline: u64,
+    /// Roll back if the containers of the canary pods restarted more times than this.
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: i32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Step {
+    /// The percentage of the traffic served by the canary pods, it is split
+    /// by the Ingress or HTTPRoute if the actor is exposed by the weighted
+    /// backends, or approximated by their share of the replicas behind the Service.
+    pub weight: i32,
+    /// The seconds to observe the ready canary pods before the next step.
+    #[serde(default)]
+    pub pause: u64,
+}
+
+fn default_progress_deadline() -> u64 {
+    600
+}
+
+fn default_max_restarts() -> i32 {
+    3
+}
+
+/// The progress of the canary rollout of a version.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Status {
+    /// The hash of the version rolled out.
+    pub hash: String,
+    pub phase: Phase,
+    /// The index of the current step.
+    pub step: usize,
+    /// The start time of the current step, in RFC 3339 format.
+    pub started_at: String,
+    /// The replicas of the stable Deployment before the rollout.
+    pub replicas: i32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Progressing,
+    Promoted,
+    RolledBack,
+}
+
+/// What to do next with the canary rollout.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decision {
+    /// Check again after the duration.
+    Wait(Duration),
+    /// Move on to the next step.
+    Advance,
+    /// All the steps passed, update the stable Deployment with the canary.
+    Promote,
+    /// Delete the canary and keep the stable Deployment, with the reason.
+    Rollback(String),
+}
+
+/// Returns the canary rollout of actor, None if not declared.
+pub fn canary(actor: &Actor) -> Result<Option<Canary>> {
+    match actor.annotations().get(CANARY_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Returns the progress of the canary rollout of actor, if any.
+pub fn status(actor: &Actor) -> Option<Status> {
+    let content = actor.annotations().get(CANARY_STATUS_ANNOTATION);
+    content.and_then(|content| serde_json::from_str(content).ok())
+}
+
+/// Returns the weight of the canary pods in the step in progress, None if
+/// there is no canary rollout in progress.
+pub fn weight(actor: &Actor) -> Option<i32> {
+    let status = status(actor).filter(|status| status.phase == Phase::Progressing)?;
+    let canary = canary(actor).ok().flatten()?;
+    canary.steps.get(status.step).map(|step| step.weight)
+}
+
+/// Record the progress of the canary rollout of actor.
+pub async fn record(client: &Client, actor: &Actor, status: &Status) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let content = serde_json::to_string(status).map_err(Error::SerializationError)?;
+    let annotations = BTreeMap::from([(CANARY_STATUS_ANNOTATION.to_string(), content)]);
+    let patch = json!({"metadata": { "annotations": annotations }});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+/// Decide the next move of the canary rollout by the health of the canary pods.
+pub fn decide(canary: &Canary, status: &Status, ready: bool, restarts: i32, now: DateTime<Utc>) -> Decision {
+    if restarts > canary.max_restarts {
+        return Decision::Rollback(format!("The canary pods restarted {} times", restarts));
+    }
+
+    let started = DateTime::parse_from_rfc3339(&status.started_at).map(|t| t.with_timezone(&Utc)).unwrap_or(now);
+    let elapsed = (now - started).to_std().unwrap_or_default();
+    if !ready {
+        if elapsed.as_secs() > canary.progress_deadline {
+            let reason = format!("The canary pods are not ready in {} seconds", canary.progress_deadline);
+            return Decision::Rollback(reason);
+        }
+        return Decision::Wait(Duration::from_secs(10));
+    }
+
+    let pause = Duration::from_secs(canary.steps.get(status.step).map(|step| step.pause).unwrap_or_default());
+    if elapsed < pause {
+        // Keep checking the health of the canary pods while pausing.
+        return Decision::Wait((pause - elapsed).min(Duration::from_secs(30)));
+    }
+
+    match status.step + 1 >= canary.steps.len() {
+        true => Decision::Promote,
+        false => Decision::Advance,
+    }
+}
+
+/// Split the replicas between the stable and canary Deployments by the
+/// weight, the canary has one replica at least, and the stable keeps one
+/// until the canary takes all the traffic.
+pub fn split(replicas: i32, weight: i32) -> (i32, i32) {
+    let replicas = replicas.max(1);
+    if weight >= 100 {
+        return (0, replicas);
+    }
+
+    let canary = ((replicas * weight.max(0) + 99) / 100).clamp(1, replicas);
+    ((replicas - canary).max(1), canary)
+}
+
+/// Returns the name of the canary Deployment of actor.
+pub fn name(actor: &Actor) -> String {
+    naming::name(&[&actor.name_any(), "canary"])
+}
+
+/// Turn the Deployment of actor into its canary, with the given replicas.
+pub fn paint(actor: &Actor, deployment: &mut Deployment, replicas: i32) {
+    let labels = BTreeMap::from([(TRACK_LABEL.to_string(), "canary".to_string())]);
+
+    deployment.metadata.name = Some(name(actor));
+    deployment.metadata.labels.get_or_insert_with(BTreeMap::new).extend(labels.clone());
+    if let Some(spec) = deployment.spec.as_mut() {
+        spec.replicas = Some(replicas);
+        spec.selector.match_labels.get_or_insert_with(BTreeMap::new).extend(labels.clone());
+        let metadata = spec.template.metadata.get_or_insert_with(Default::default);
+        metadata.labels.get_or_insert_with(BTreeMap::new).extend(labels);
+    }
+}
+
+/// Returns the labels selecting the canary pods.
+pub fn selector(actor: &Actor) -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("amphitheatre.app/character".into(), actor.name_any()),
+        ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+        (TRACK_LABEL.into(), "canary".into()),
+    ])
+}
+
+/// Returns the labels selecting the stable pods, by the template hash of the
+/// newest ReplicaSet of the stable Deployment, as the canary pods match its
+/// labels as well. None if the ReplicaSet is not found.
+pub async fn stable_selector(client: &Client, actor: &Actor) -> Result<Option<BTreeMap<String, String>>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<ReplicaSet> = Api::namespaced(client.clone(), &namespace);
+    let labels = format!("amphitheatre.app/character={}", actor.name_any());
+    let replicasets = api.list(&ListParams::default().labels(&labels)).await.map_err(Error::KubeError)?;
+
+    let owner = deployment::name(actor);
+    let revision = |replicaset: &ReplicaSet| -> u64 {
+        let revision = replicaset.annotations().get("deployment.kubernetes.io/revision");
+        revision.and_then(|revision| revision.parse().ok()).unwrap_or_default()
+    };
+    let newest = replicasets
+        .items
+        .into_iter()
+        .filter(|replicaset| replicaset.owner_references().iter().any(|owner_ref| owner_ref.name == owner))
+        .max_by_key(revision);
+    let hash = newest.and_then(|replicaset| replicaset.labels().get("pod-template-hash").cloned());
+
+    Ok(hash.map(|hash| {
+        BTreeMap::from([
+            ("amphitheatre.app/character".into(), actor.name_any()),
+            ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+            ("pod-template-hash".into(), hash),
+        ])
+    }))
+}
+
+/// Returns the total restarts of the containers of the canary pods.
+pub async fn restarts(client: &Client, actor: &Actor) -> Result<i32> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    let labels = format!("amphitheatre.app/character={},{}=canary", actor.name_any(), TRACK_LABEL);
+    let pods = api.list(&ListParams::default().labels(&labels)).await.map_err(Error::KubeError)?;
+
+    let containers = pods.items.into_iter().filter_map(|pod| pod.status?.container_statuses).flatten();
+    Ok(containers.map(|container| container.restart_count).sum())
+}
+
+/// Build the condition reporting the canary rollout of actor.
+pub fn condition(phase: Phase, message: String) -> Condition {
+    let (status, reason) = match phase {
+        Phase::Progressing => ("True", "Progressing"),
+        Phase::Promoted => ("False", "Promoted"),
+        Phase::RolledBack => ("False", "RolledBack"),
+    };
+    Condition {
+        type_: CANARY_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: reason.into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canary() -> Canary {
+        Canary {
+            steps: vec![Step { weight: 10, pause: 60 }, Step { weight: 50, pause: 0 }],
+            progress_deadline: 300,
+            max_restarts: 2,
+        }
+    }
+
+    fn status(step: usize, started_at: DateTime<Utc>) -> Status {
+        Status { hash: "v2".into(), phase: Phase::Progressing, step, started_at: started_at.to_rfc3339(), replicas: 4 }
+    }
+
+    #[test]
+    fn test_parse_canary() {
+        let parsed: Canary = serde_json::from_str(r#"{"steps": [{"weight": 20}]}"#).unwrap();
+        assert_eq!(parsed.steps, vec![Step { weight: 20, pause: 0 }]);
+        assert_eq!(parsed.progress_deadline, 600);
+        assert_eq!(parsed.max_restarts, 3);
+    }
+
+    #[test]
+    fn test_decide() {
+        let now = Utc::now();
+        let started = now - k8s_openapi::chrono::Duration::seconds(30);
+
+        assert_eq!(decide(&canary(), &status(0, started), true, 0, now), Decision::Wait(Duration::from_secs(30)));
+        assert_eq!(decide(&canary(), &status(0, started), false, 0, now), Decision::Wait(Duration::from_secs(10)));
+        assert!(matches!(decide(&canary(), &status(0, started), true, 3, now), Decision::Rollback(_)));
+
+        let started = now - k8s_openapi::chrono::Duration::seconds(120);
+        assert_eq!(decide(&canary(), &status(0, started), true, 0, now), Decision::Advance);
+        assert_eq!(decide(&canary(), &status(1, started), true, 0, now), Decision::Promote);
+
+        let started = now - k8s_openapi::chrono::Duration::seconds(600);
+        assert!(matches!(decide(&canary(), &status(1, started), false, 0, now), Decision::Rollback(_)));
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(10, 10), (9, 1));
+        assert_eq!(split(4, 50), (2, 2));
+        assert_eq!(split(1, 10), (1, 1));
+        assert_eq!(split(3, 100), (0, 3));
+        assert_eq!(split(0, 20), (1, 1));
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use amp_common::resource::Playbook;
+use k8s_openapi::api::core::v1::Secret;
+use kube::api::ListParams;
+use kube::config::{KubeConfigOptions, Kubeconfig};
+use kube::{Api, Client, Config, ResourceExt};
+use lazy_static::lazy_static;
+use tracing::info;
+
+use crate::error::{Error, Result};
+
+/// The annotation of playbook naming the workload cluster its namespace and
+/// actors are created in, they are created in the control plane if not set.
+/// The workload cluster runs its own controllers to reconcile the actors.
+pub const CLUSTER_ANNOTATION: &str = "amphitheatre.app/cluster";
+
+/// The label of the Secrets in the Amphitheatre namespace registering the
+/// workload clusters, the value is the name of cluster.
+pub const CLUSTER_LABEL: &str = "amphitheatre.app/cluster";
+
+/// The key of the Secret holding the kubeconfig of the workload cluster.
+const KUBECONFIG_KEY: &str = "kubeconfig";
+
+lazy_static! {
+    /// The clients of the workload clusters, keyed by the name of cluster
+    /// along with the resource version of its Secret, so the rotated
+    /// kubeconfigs are picked up.
+    static ref CLIENTS: RwLock<HashMap<String, (String, Client)>> = RwLock::new(HashMap::new());
+}
+
+/// Returns the name of the workload cluster of playbook, if any.
+pub fn name(playbook: &Playbook) -> Option<&str> {
+    playbook.annotations().get(CLUSTER_ANNOTATION).map(String::as_str).filter(|name| !name.is_empty())
+}
+
+/// Returns true if the playbook is deployed to a remote workload cluster, the
+/// objects there can't be owned by the playbook in the control plane.
+pub fn remote(playbook: &Playbook) -> bool {
+    name(playbook).is_some()
+}
+
+/// List the names of the registered workload clusters.
+pub async fn list(client: &Client, namespace: &str) -> Result<Vec<String>> {
+    let api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let secrets = api.list(&ListParams::default().labels(CLUSTER_LABEL)).await.map_err(Error::KubeError)?;
+
+    Ok(secrets.items.iter().filter_map(|secret| secret.labels().get(CLUSTER_LABEL).cloned()).collect())
+}
+
+/// Returns the client of the workload cluster of playbook, or the given
+/// client of the control plane if the playbook is not deployed remotely.
+pub async fn client(client: &Client, namespace: &str, playbook: &Playbook) -> Result<Client> {
+    match self::name(playbook) {
+        Some(name) => connect(client, namespace, name).await,
+        None => Ok(client.clone()),
+    }
+}
+
+/// Returns the client of the registered workload cluster of the given name.
+pub async fn connect(client: &Client, namespace: &str, name: &str) -> Result<Client> {
+    let api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let params = ListParams::default().labels(&format!("{}={}", CLUSTER_LABEL, name));
+    let secret = api.list(&params).await.map_err(Error::KubeError)?.items.into_iter().next();
+    let secret = secret.ok_or_else(|| Error::ClusterNotFound(name.to_string()))?;
+
+    let version = secret.resource_version().unwrap_or_default();
+    if let Some((cached, client)) = CLIENTS.read().unwrap().get(name) {
+        if cached == &version {
+            return Ok(client.clone());
+        }
+    }
+
+    let kubeconfig = secret.data.as_ref().and_then(|data| data.get(KUBECONFIG_KEY));
+    let kubeconfig = kubeconfig.ok_or(Error::MissingObjectKey(".data.kubeconfig"))?;
+    let kubeconfig = String::from_utf8_lossy(&kubeconfig.0);
+    let kubeconfig = Kubeconfig::from_yaml(&kubeconfig).map_err(Error::KubeconfigError)?;
+    let config = Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default())
+        .await
+        .map_err(Error::KubeconfigError)?;
+    let remote = Client::try_from(config).map_err(Error::KubeError)?;
+    info!("Created the client of workload cluster {}", name);
+
+    CLIENTS.write().unwrap().insert(name.to_string(), (version, remote.clone()));
+
+    Ok(remote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::PlaybookSpec;
+
+    #[test]
+    fn test_name() {
+        let mut playbook = Playbook::new("test", PlaybookSpec::default());
+        assert_eq!(name(&playbook), None);
+        assert!(!remote(&playbook));
+
+        playbook.annotations_mut().insert(CLUSTER_ANNOTATION.into(), "".into());
+        assert!(!remote(&playbook));
+
+        playbook.annotations_mut().insert(CLUSTER_ANNOTATION.into(), "edge".into());
+        assert_eq!(name(&playbook), Some("edge"));
+        assert!(remote(&playbook));
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use k8s_openapi::api::core::v1::{
+    Container, EnvVar, EnvVarSource, KeyToPath, PodSpec, ProjectedVolumeSource, SecretKeySelector, SecretVolumeSource,
+    ServiceAccountTokenProjection, Volume, VolumeMount, VolumeProjection,
+};
+
+use super::{docker_config_mount, docker_config_volume};
+use crate::signing::{Mode, COSIGN_KEY_SECRET_NAME};
+
+const DEFAULT_COSIGN_IMAGE: &str = "gcr.io/projectsigstore/cosign:v2.4.0";
+
+const COSIGN_KEY_DIR: &str = "/cosign";
+const OIDC_TOKEN_DIR: &str = "/var/run/sigstore/cosign";
+
+/// Build the pod spec which signs the image (pinned by its digest) with cosign,
+/// the signature is pushed next to the image, tagged by its digest.
+pub fn pod(image: &str, mode: &Mode) -> PodSpec {
+    PodSpec {
+        containers: vec![signer(image, mode)],
+        restart_policy: Some("Never".into()),
+        volumes: Some(vec![docker_config_volume(), credential_volume(mode)]),
+        ..Default::default()
+    }
+}
+
+/// Build and return the cosign container signing the image.
+pub fn signer(image: &str, mode: &Mode) -> Container {
+    let mut env = vec![docker_config_env()];
+    let mut arguments = vec!["sign".to_string(), "--yes".into()];
+
+    match mode {
+        Mode::Key => {
+            arguments.push(format!("--key={}/cosign.key", COSIGN_KEY_DIR));
+            env.push(EnvVar {
+                name: "COSIGN_PASSWORD".into(),
+                value_from: Some(EnvVarSource {
+                    secret_key_ref: Some(SecretKeySelector {
+                        name: Some(COSIGN_KEY_SECRET_NAME.into()),
+                        key: "cosign.password".into(),
+                        optional: Some(true),
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+        }
+        Mode::Keyless => arguments.push(format!("--identity-token={}/oidc-token", OIDC_TOKEN_DIR)),
+    }
+    arguments.push(image.into());
+
+    Container {
+        name: "cosign".into(),
+        image: Some(DEFAULT_COSIGN_IMAGE.into()),
+        image_pull_policy: Some("IfNotPresent".into()),
+        args: Some(arguments),
+        env: Some(env),
+        volume_mounts: Some(vec![docker_config_mount(), credential_mount(mode)]),
+        ..Default::default()
+    }
+}
+
+/// The volume providing the signing credential, the private key stored
+/// in Secret, or the projected OIDC token of the ServiceAccount.
+fn credential_volume(mode: &Mode) -> Volume {
+    match mode {
+        Mode::Key => Volume {
+            name: "cosign-credential".into(),
+            secret: Some(SecretVolumeSource {
+                secret_name: Some(COSIGN_KEY_SECRET_NAME.into()),
+                items: Some(vec![KeyToPath {
+                    key: "cosign.key".into(),
+                    path: "cosign.key".into(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        Mode::Keyless => Volume {
+            name: "cosign-credential".into(),
+            projected: Some(ProjectedVolumeSource {
+                sources: Some(vec![VolumeProjection {
+                    service_account_token: Some(ServiceAccountTokenProjection {
+                        audience: Some("sigstore".into()),
+                        expiration_seconds: Some(600),
+                        path: "oidc-token".into(),
+                    }),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    }
+}
+
+#[inline]
+fn credential_mount(mode: &Mode) -> VolumeMount {
+    let mount_path = match mode {
+        Mode::Key => COSIGN_KEY_DIR,
+        Mode::Keyless => OIDC_TOKEN_DIR,
+    };
+
+    VolumeMount { name: "cosign-credential".into(), mount_path: mount_path.into(), ..Default::default() }
+}
+
+#[inline]
+fn docker_config_env() -> EnvVar {
+    EnvVar { name: "DOCKER_CONFIG".into(), value: Some("/.docker".into()), ..Default::default() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signer_container_with_key() {
+        let container = signer("test@sha256:abc", &Mode::Key);
+
+        assert_eq!(container.name, "cosign");
+        assert_eq!(
+            container.args,
+            Some(vec!["sign".into(), "--yes".into(), "--key=/cosign/cosign.key".into(), "test@sha256:abc".into()])
+        );
+        assert_eq!(container.volume_mounts.unwrap()[1].mount_path, "/cosign");
+    }
+
+    #[test]
+    fn test_signer_container_keyless() {
+        let container = signer("test:v1", &Mode::Keyless);
+
+        assert_eq!(
+            container.args,
+            Some(vec![
+                "sign".into(),
+                "--yes".into(),
+                "--identity-token=/var/run/sigstore/cosign/oidc-token".into(),
+                "test:v1".into()
+            ])
+        );
+        assert_eq!(container.env.unwrap().len(), 1);
+    }
+}
@@ -25,7 +25,7 @@ use super::workspace_mount;
 /// information about what's included in the default Linux image, see the
 /// [devcontainers/images](https://github.com/devcontainers/images/tree/main/src/universal)
 /// repository.
-const DEFAULT_DEVCONTAINER_IMAGE: &str = "mcr.microsoft.com/devcontainers/universal:linux";
+pub const DEFAULT_DEVCONTAINER_IMAGE: &str = "mcr.microsoft.com/devcontainers/universal:linux";
 
 /// Build and return the container spec for the devcontainer.
 pub fn container(_spec: &ActorSpec) -> Container {
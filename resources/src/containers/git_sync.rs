@@ -13,19 +13,25 @@
 // limitations under the License.
 
 use super::{workspace_mount, WORKSPACE_DIR};
-use crate::args;
+use crate::{args, git};
 use amp_common::resource::Actor;
-use k8s_openapi::api::core::v1::{Container, VolumeMount};
+use k8s_openapi::api::core::v1::{Container, EnvVar, EnvVarSource, KeyToPath, SecretKeySelector};
+use k8s_openapi::api::core::v1::{SecretVolumeSource, Volume, VolumeMount};
 
 const DEFAULT_GIT_SYNC_IMAGE: &str = "registry.k8s.io/git-sync/git-sync:v4.0.0";
 
+/// The directory of the SSH key and the known hosts of the repository.
+const SSH_KEY_DIR: &str = "/etc/git-secret";
+const SSH_KEY_FILE: &str = "/etc/git-secret/ssh";
+const SSH_KNOWN_HOSTS_FILE: &str = "/etc/git-secret/known_hosts";
+
 /// Build and return the container spec for the git-sync.
 pub fn container(actor: &Actor) -> Container {
     let source = actor.spec.source.as_ref().unwrap();
 
     // Parse the arguments for the container
     let revision = source.rev();
-    let arguments = vec![
+    let mut arguments = vec![
         ("depth", "1"),
         ("one-time", "true"),
         ("ref", &revision),
@@ -34,16 +40,84 @@ pub fn container(actor: &Actor) -> Container {
         ("link", WORKSPACE_DIR),
     ];
 
+    // Authenticate with the credential of the repository synced into the
+    // namespace, the Secret is optional as the repository may be public.
+    let mut env = None;
+    let mut volume_mounts = vec![workspace_mount(), source_mount()];
+    if let Some(name) = git::secret_name(&source.repo) {
+        if git::ssh(&source.repo) {
+            arguments.extend([
+                ("ssh-key-file", SSH_KEY_FILE),
+                ("ssh-known-hosts", "true"),
+                ("ssh-known-hosts-file", SSH_KNOWN_HOSTS_FILE),
+            ]);
+            volume_mounts.push(ssh_key_mount());
+        } else {
+            env = Some(vec![
+                secret_env("GITSYNC_USERNAME", &name, "username"),
+                secret_env("GITSYNC_PASSWORD", &name, "password"),
+            ]);
+        }
+    }
+
     Container {
         name: "syncer".to_string(),
         image: Some(DEFAULT_GIT_SYNC_IMAGE.to_string()),
         image_pull_policy: Some("IfNotPresent".to_string()),
         args: Some(args(&arguments, 2)),
-        volume_mounts: Some(vec![workspace_mount(), source_mount()]),
+        env,
+        volume_mounts: Some(volume_mounts),
         ..Default::default()
     }
 }
 
+/// The volume of the SSH key of the repository, if it's cloned over SSH. The
+/// host is verified with the `known_hosts` of the Secret, the clone fails
+/// if it's missing, the well-known hosts are filled in when it's synced.
+pub fn ssh_key_volume(actor: &Actor) -> Option<Volume> {
+    let repo = &actor.spec.source.as_ref()?.repo;
+    let name = git::secret_name(repo).filter(|_| git::ssh(repo))?;
+
+    // ssh only refuses the keys readable by others when they're owned by the
+    // current user, git-sync runs as a non-root user, so it's readable by all.
+    let items = vec![
+        KeyToPath { key: "ssh-privatekey".into(), path: "ssh".into(), mode: None },
+        KeyToPath { key: "known_hosts".into(), path: "known_hosts".into(), mode: None },
+    ];
+    Some(Volume {
+        name: "git-secret".into(),
+        secret: Some(SecretVolumeSource {
+            secret_name: Some(name),
+            items: Some(items),
+            default_mode: Some(0o444),
+            optional: Some(true),
+        }),
+        ..Default::default()
+    })
+}
+
+#[inline]
+fn ssh_key_mount() -> VolumeMount {
+    VolumeMount {
+        name: "git-secret".into(),
+        mount_path: SSH_KEY_DIR.into(),
+        read_only: Some(true),
+        ..Default::default()
+    }
+}
+
+fn secret_env(name: &str, secret: &str, key: &str) -> EnvVar {
+    let selector = SecretKeySelector { name: Some(secret.into()), key: key.into(), optional: Some(true) };
+    let source = EnvVarSource { secret_key_ref: Some(selector), ..Default::default() };
+    EnvVar { name: name.into(), value: None, value_from: Some(source) }
+}
+
+/// volume for /src based on k8s emptyDir
+#[inline]
+pub fn source_volume() -> Volume {
+    Volume { name: "src".to_string(), empty_dir: Some(Default::default()), ..Default::default() }
+}
+
 /// volume mount for /src
 #[inline]
 pub fn source_mount() -> VolumeMount {
@@ -78,4 +152,32 @@ mod tests {
         assert_eq!(container.image, Some(DEFAULT_GIT_SYNC_IMAGE.to_string()));
         assert_eq!(container.image_pull_policy, Some("IfNotPresent".to_string()));
     }
+
+    #[test]
+    fn test_authenticate_with_repository_credential() {
+        let source = |repo: &str| amp_common::schema::GitReference { repo: repo.into(), ..Default::default() };
+        let spec = ActorSpec {
+            source: Some(source("https://github.com/amphitheatre-app/amp-example-go")),
+            ..Default::default()
+        };
+        let actor = Actor::new("test", spec);
+        let env = container(&actor).env.unwrap();
+        let selector = env[1].value_from.as_ref().unwrap().secret_key_ref.as_ref().unwrap();
+        assert_eq!(env[1].name, "GITSYNC_PASSWORD");
+        assert_eq!(selector.name.as_deref(), Some("amp-repo-credentials-https-github.com"));
+        assert!(ssh_key_volume(&actor).is_none());
+
+        let spec = ActorSpec {
+            source: Some(source("git@github.com:amphitheatre-app/amp-example-go.git")),
+            ..Default::default()
+        };
+        let actor = Actor::new("test", spec);
+        let container = container(&actor);
+        assert!(container.env.is_none());
+        let args = container.args.unwrap();
+        assert!(args.contains(&format!("--ssh-key-file={}", SSH_KEY_FILE)));
+        assert!(args.contains(&"--ssh-known-hosts=true".to_string()));
+        let volume = ssh_key_volume(&actor).unwrap();
+        assert_eq!(volume.secret.unwrap().secret_name.as_deref(), Some("amp-repo-credentials-ssh-github.com"));
+    }
 }
@@ -0,0 +1,179 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::{Container, EnvVar, PodSpec};
+use serde_json::{Map, Value};
+
+use crate::error::{Error, Result};
+use crate::helm::{Chart, HELM_SERVICE_ACCOUNT};
+
+const DEFAULT_HELM_IMAGE: &str = "alpine/helm:3.15.4";
+
+/// The values file written from the `HELM_VALUES` variable before running helm,
+/// as the values passed with `--set` break on the commas in them.
+const VALUES_FILE: &str = "/tmp/values.json";
+
+/// Build the pod spec which installs or upgrades the chart release of the actor.
+pub fn pod(actor: &Actor, namespace: &str, chart: &Chart) -> Result<PodSpec> {
+    let pairs = actor.spec.character.deploy.as_ref().and_then(|deploy| deploy.env.clone()).unwrap_or_default();
+    let values = values(&pairs.into_iter().collect());
+
+    Ok(PodSpec {
+        containers: vec![container(&actor.spec.name, namespace, chart, &values)?],
+        service_account_name: Some(HELM_SERVICE_ACCOUNT.into()),
+        restart_policy: Some("Never".into()),
+        ..Default::default()
+    })
+}
+
+/// Build the pod spec which uninstalls the chart release of the actor.
+pub fn uninstall(release: &str, namespace: &str) -> PodSpec {
+    let arguments = vec![
+        "uninstall".to_string(),
+        release.into(),
+        format!("--namespace={}", namespace),
+        "--wait".into(),
+        "--ignore-not-found".into(),
+    ];
+
+    PodSpec {
+        containers: vec![Container {
+            name: "helm".into(),
+            image: Some(DEFAULT_HELM_IMAGE.into()),
+            image_pull_policy: Some("IfNotPresent".into()),
+            args: Some(arguments),
+            ..Default::default()
+        }],
+        service_account_name: Some(HELM_SERVICE_ACCOUNT.into()),
+        restart_policy: Some("Never".into()),
+        ..Default::default()
+    }
+}
+
+/// Build and return the helm container, the values are passed with a values file.
+pub fn container(release: &str, namespace: &str, chart: &Chart, values: &Value) -> Result<Container> {
+    let mut arguments = vec!["upgrade".to_string(), "--install".into(), release.into(), chart.name.clone()];
+    arguments.push(format!("--namespace={}", namespace));
+    if let Some(version) = &chart.version {
+        arguments.push(format!("--version={}", version));
+    }
+    arguments.push(format!("--values={}", VALUES_FILE));
+    arguments.extend(["--wait".into(), "--atomic".into(), "--timeout=10m".into()]);
+
+    let script = format!("printf '%s' \"$HELM_VALUES\" > {} && exec helm \"$@\"", VALUES_FILE);
+    let values = serde_json::to_string(values).map_err(Error::SerializationError)?;
+
+    Ok(Container {
+        name: "helm".into(),
+        image: Some(DEFAULT_HELM_IMAGE.into()),
+        image_pull_policy: Some("IfNotPresent".into()),
+        command: Some(vec!["/bin/sh".into(), "-c".into(), script, "helm".into()]),
+        args: Some(arguments),
+        env: Some(vec![EnvVar { name: "HELM_VALUES".into(), value: Some(values), ..Default::default() }]),
+        ..Default::default()
+    })
+}
+
+/// Build the chart values from the keys in the `--set` style, e.g. `auth.enabled=false`,
+/// the booleans and integers are typed like helm does.
+pub fn values(pairs: &BTreeMap<String, String>) -> Value {
+    let mut values = Map::new();
+    for (key, value) in pairs {
+        let mut parts: Vec<&str> = key.split('.').collect();
+        let last = parts.pop().unwrap_or_default();
+
+        let mut node = &mut values;
+        for part in parts {
+            let entry = node.entry(part).or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            node = entry.as_object_mut().unwrap();
+        }
+        node.insert(last.into(), scalar(value));
+    }
+
+    Value::Object(values)
+}
+
+fn scalar(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => value.parse::<i64>().map(Value::from).unwrap_or_else(|_| Value::String(value.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_helm_container() {
+        let chart =
+            Chart { name: "oci://registry-1.docker.io/bitnamicharts/redis".into(), version: Some("19.6.4".into()) };
+        let values = json!({"auth": {"enabled": false}});
+        let container = container("cache", "amp-test", &chart, &values).unwrap();
+
+        assert_eq!(container.name, "helm");
+        assert_eq!(
+            container.args,
+            Some(vec![
+                "upgrade".into(),
+                "--install".into(),
+                "cache".into(),
+                "oci://registry-1.docker.io/bitnamicharts/redis".into(),
+                "--namespace=amp-test".into(),
+                "--version=19.6.4".into(),
+                "--values=/tmp/values.json".into(),
+                "--wait".into(),
+                "--atomic".into(),
+                "--timeout=10m".into(),
+            ])
+        );
+        assert_eq!(container.env.unwrap()[0].value, Some(r#"{"auth":{"enabled":false}}"#.into()));
+    }
+
+    #[test]
+    fn test_values() {
+        let pairs = BTreeMap::from([
+            ("auth.enabled".into(), "false".into()),
+            ("auth.password".into(), "a,b=c".into()),
+            ("replica.replicaCount".into(), "3".into()),
+            ("architecture".into(), "standalone".into()),
+        ]);
+
+        assert_eq!(
+            values(&pairs),
+            json!({
+                "architecture": "standalone",
+                "auth": {"enabled": false, "password": "a,b=c"},
+                "replica": {"replicaCount": 3},
+            })
+        );
+    }
+
+    #[test]
+    fn test_uninstall_pod() {
+        let pod = uninstall("cache", "amp-test");
+
+        assert_eq!(pod.service_account_name, Some(HELM_SERVICE_ACCOUNT.into()));
+        assert_eq!(pod.containers[0].args.as_ref().unwrap()[..2], ["uninstall".to_string(), "cache".into()]);
+    }
+}
@@ -15,41 +15,57 @@
 use std::path::PathBuf;
 
 use super::{docker_config_volume, git_sync, syncer, workspace_mount, workspace_volume, WORKSPACE_DIR};
-use crate::args;
+use crate::build::BuildResources;
 use crate::error::Result;
+use crate::service_account::BUILDER_SERVICE_ACCOUNT;
+use crate::{args, base, monorepo, upload};
 
 use amp_common::resource::{Actor, ActorSpec};
-use k8s_openapi::api::core::v1::{Container, PodSpec, Volume, VolumeMount};
+use k8s_openapi::api::core::v1::{Container, PodSpec, VolumeMount};
 
 const DEFAULT_KANIKO_IMAGE: &str = "gcr.io/kaniko-project/executor:v1.15.0";
 
-pub fn pod(actor: &Actor) -> Result<PodSpec> {
+pub fn pod(actor: &Actor, resources: &BuildResources) -> Result<PodSpec> {
     // Choose the syncer for source code synchronization
     let syncer: Container;
     let mut volumes = vec![docker_config_volume(), workspace_volume()];
     if actor.spec.live {
         syncer = syncer::container(actor, &None)?;
+    } else if let Some(id) = upload::of(&actor.spec) {
+        syncer = syncer::upload(actor, id, &None)?;
     } else {
         syncer = git_sync::container(actor);
-        volumes.push(git_source_volume());
+        volumes.push(git_sync::source_volume());
+        volumes.extend(git_sync::ssh_key_volume(actor));
     }
 
-    Ok(PodSpec {
+    // Build from the shared base image if declared.
+    let mut container = container(&actor.spec);
+    if let Some(base) = base::of(actor)? {
+        let arg = format!("--build-arg={}={}", base::BASE_IMAGE_BUILD_ARG, base.image);
+        container.args.get_or_insert_with(Vec::new).push(arg);
+    }
+
+    let mut pod = PodSpec {
         init_containers: Some(vec![syncer]),
-        containers: vec![container(&actor.spec)],
+        containers: vec![container],
         restart_policy: Some("Never".into()),
+        service_account_name: Some(BUILDER_SERVICE_ACCOUNT.into()),
         volumes: Some(volumes),
         ..Default::default()
-    })
+    };
+    resources.apply(&mut pod);
+
+    Ok(pod)
 }
 
 /// Build and return the container spec for the kaniko pod
 pub fn container(spec: &ActorSpec) -> Container {
     let build = spec.character.build.clone().unwrap_or_default();
 
-    // Set the working directory to context.
+    // Set the working directory to context, scoped to the path of source in a monorepo.
     let mut workdir = PathBuf::from(WORKSPACE_DIR);
-    if let Some(context) = &build.context {
+    if let Some(context) = monorepo::context(spec) {
         workdir.push(context);
     }
 
@@ -99,10 +115,6 @@ fn docker_config_mount() -> VolumeMount {
     VolumeMount { name: "docker-config".into(), mount_path: "/kaniko/.docker".into(), ..Default::default() }
 }
 
-fn git_source_volume() -> Volume {
-    Volume { name: "src".to_string(), empty_dir: Some(Default::default()), ..Default::default() }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
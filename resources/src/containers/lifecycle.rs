@@ -12,43 +12,92 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
+
 use amp_common::resource::{Actor, ActorSpec};
-use k8s_openapi::api::core::v1::SecurityContext;
+use k8s_openapi::api::core::v1::{ConfigMap, ConfigMapVolumeSource, SecurityContext, Volume};
 use k8s_openapi::api::core::v1::{Container, EnvVar, PodSpec, VolumeMount};
+use kube::core::ObjectMeta;
+use kube::{Resource, ResourceExt};
 
 use super::{docker_config_volume, git_sync, syncer, workspace_mount, workspace_volume, WORKSPACE_DIR};
-use crate::args;
+use crate::build::BuildResources;
+use crate::{args, monorepo, naming, upload};
 
 use crate::error::Result;
+use crate::service_account::BUILDER_SERVICE_ACCOUNT;
+
+/// The annotation of actor overriding the run image of the builder, like
+/// `[io.buildpacks.run] image` of `project.toml`.
+pub const RUN_IMAGE_ANNOTATION: &str = "amphitheatre.app/run-image";
 
 const DEFAULT_RUN_AS_GROUP: i64 = 1000;
 const DEFAULT_RUN_AS_USER: i64 = 1001;
 
-pub fn pod(actor: &Actor) -> Result<PodSpec> {
+/// The directory of the order of the explicit buildpacks.
+const ORDER_DIR: &str = "/amp/order";
+const ORDER_KEY: &str = "order.toml";
+
+pub fn pod(actor: &Actor, resources: &BuildResources) -> Result<PodSpec> {
     // Get SecurityContext for the container
     let build = actor.spec.character.build.clone().unwrap_or_default();
     let builder = build.buildpacks.clone().unwrap_or_default().builder;
     let security_context = security_context(&builder);
 
     // Choose the syncer for source code synchronization
-    let syncer =
-        if actor.spec.live { syncer::container(actor, &security_context)? } else { git_sync::container(actor) };
+    let syncer = match upload::of(&actor.spec) {
+        _ if actor.spec.live => syncer::container(actor, &security_context)?,
+        Some(id) => syncer::upload(actor, id, &security_context)?,
+        None => git_sync::container(actor),
+    };
 
-    Ok(PodSpec {
+    let mut container = container(&actor.spec, &security_context);
+    let mut volumes = vec![workspace_volume(), docker_config_volume()];
+    if !actor.spec.live {
+        volumes.extend(git_sync::ssh_key_volume(actor));
+    }
+    let mut flags = vec![];
+
+    // Detect with the explicit buildpacks only, instead of the order of builder.
+    if order(actor).is_some() {
+        flags.push(format!("-order={}/{}", ORDER_DIR, ORDER_KEY));
+        volumes.push(order_volume(actor));
+        let mount = VolumeMount { name: "order".into(), mount_path: ORDER_DIR.into(), ..Default::default() };
+        container.volume_mounts.get_or_insert_with(Vec::new).push(mount);
+    }
+    if let Some(image) = actor.annotations().get(RUN_IMAGE_ANNOTATION) {
+        flags.push(format!("-run-image={}", image));
+    }
+    // The flags go before the image, which is the last argument.
+    if let Some(args) = container.args.as_mut() {
+        let image = args.pop();
+        args.extend(flags);
+        args.extend(image);
+    }
+
+    let mut pod = PodSpec {
         init_containers: Some(vec![syncer]),
-        containers: vec![container(&actor.spec, &security_context)],
+        containers: vec![container],
         restart_policy: Some("Never".into()),
-        volumes: Some(vec![workspace_volume(), docker_config_volume()]),
+        service_account_name: Some(BUILDER_SERVICE_ACCOUNT.into()),
+        volumes: Some(volumes),
         ..Default::default()
-    })
+    };
+    resources.apply(&mut pod);
+
+    Ok(pod)
 }
 
 /// Build and return the container spec for the buildpacks container
 pub fn container(spec: &ActorSpec, security_context: &Option<SecurityContext>) -> Container {
     let build = spec.character.build.clone().unwrap_or_default();
 
-    // Parse the arguments for the container
-    let arguments = vec![("app", WORKSPACE_DIR)];
+    // Parse the arguments for the container, the app is scoped to the path of source in a monorepo.
+    let app = match monorepo::context(spec) {
+        Some(context) => format!("{}/{}", WORKSPACE_DIR, context),
+        None => WORKSPACE_DIR.to_string(),
+    };
+    let arguments = vec![("app", app.as_str())];
     let mut arguments = args(&arguments, 1);
     if let Some(args) = &build.args {
         arguments.extend(args.clone());
@@ -76,6 +125,58 @@ pub fn container(spec: &ActorSpec, security_context: &Option<SecurityContext>) -
     }
 }
 
+/// Returns the `order.toml` of the explicit buildpacks of actor, if any, like
+/// `[[io.buildpacks.group]]` of `project.toml`. The buildpacks are referenced
+/// by `{id}@{version}` or `urn:cnb:builder:{id}`, they must be in the builder.
+pub fn order(actor: &Actor) -> Option<String> {
+    let build = actor.spec.character.build.as_ref()?;
+    let buildpacks = build.buildpacks.as_ref()?.buildpacks.as_ref().filter(|buildpacks| !buildpacks.is_empty())?;
+
+    let mut content = String::from("[[order]]\n");
+    for buildpack in buildpacks {
+        let buildpack = buildpack.trim_start_matches("urn:cnb:builder:").trim_start_matches("urn:cnb:registry:");
+        content.push_str("\n[[order.group]]\n");
+        match buildpack.split_once('@') {
+            Some((id, version)) => content.push_str(&format!("id = {:?}\nversion = {:?}\n", id, version)),
+            None => content.push_str(&format!("id = {:?}\n", buildpack)),
+        }
+    }
+
+    Some(content)
+}
+
+/// Build the ConfigMap holding the `order.toml` of the explicit buildpacks, if any.
+pub fn order_config_map(actor: &Actor) -> Option<ConfigMap> {
+    let content = order(actor)?;
+
+    Some(ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(order_name(actor)),
+            owner_references: actor.controller_owner_ref(&()).map(|owner| vec![owner]),
+            labels: Some(BTreeMap::from([
+                ("amphitheatre.app/character".into(), actor.spec.name.clone()),
+                ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+            ])),
+            ..Default::default()
+        },
+        data: Some(BTreeMap::from([(ORDER_KEY.into(), content)])),
+        ..Default::default()
+    })
+}
+
+#[inline]
+pub fn order_name(actor: &Actor) -> String {
+    naming::name(&[&actor.spec.name, "builder-order"])
+}
+
+fn order_volume(actor: &Actor) -> Volume {
+    Volume {
+        name: "order".into(),
+        config_map: Some(ConfigMapVolumeSource { name: Some(order_name(actor)), ..Default::default() }),
+        ..Default::default()
+    }
+}
+
 /// Build and return the volume mount for the docker config
 #[inline]
 pub fn docker_config_mount() -> VolumeMount {
@@ -140,6 +241,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_order() {
+        use amp_common::schema::{Build, BuildpacksConfig};
+
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(order(&actor), None);
+
+        let buildpacks = vec!["paketo-buildpacks/go@4.0.0".into(), "urn:cnb:builder:paketo-buildpacks/procfile".into()];
+        let config = BuildpacksConfig { builder: "paketobuildpacks/builder:base".into(), buildpacks: Some(buildpacks) };
+        actor.spec.character.build = Some(Build { buildpacks: Some(config), ..Default::default() });
+        assert_eq!(
+            order(&actor).unwrap(),
+            "[[order]]\n\n[[order.group]]\nid = \"paketo-buildpacks/go\"\nversion = \"4.0.0\"\n\n\
+             [[order.group]]\nid = \"paketo-buildpacks/procfile\"\n"
+        );
+    }
+
+    #[test]
+    fn test_pod_with_order_and_run_image() {
+        use amp_common::schema::{Build, BuildpacksConfig, GitReference};
+
+        let source = Some(GitReference {
+            repo: "https://github.com/amphitheatre-app/amp-example-go".into(),
+            ..Default::default()
+        });
+        let spec = ActorSpec { name: "web".into(), image: "web:latest".into(), source, ..Default::default() };
+        let mut actor = Actor::new("web", spec);
+        let config = BuildpacksConfig { builder: "paketobuildpacks/builder:base".into(), buildpacks: Some(vec![]) };
+        actor.spec.character.build = Some(Build { buildpacks: Some(config), ..Default::default() });
+        let pod = pod(&actor, &BuildResources::default()).unwrap();
+        assert_eq!(pod.containers[0].args, Some(vec!["-app=/workspace".into(), "web:latest".into()]));
+        assert_eq!(pod.volumes.unwrap().len(), 2);
+
+        let config =
+            BuildpacksConfig { builder: "paketobuildpacks/builder:base".into(), buildpacks: Some(vec!["go".into()]) };
+        actor.spec.character.build = Some(Build { buildpacks: Some(config), ..Default::default() });
+        actor.annotations_mut().insert(RUN_IMAGE_ANNOTATION.into(), "paketobuildpacks/run:tiny".into());
+        let pod = pod(&actor, &BuildResources::default()).unwrap();
+        assert_eq!(
+            pod.containers[0].args,
+            Some(vec![
+                "-app=/workspace".into(),
+                "-order=/amp/order/order.toml".into(),
+                "-run-image=paketobuildpacks/run:tiny".into(),
+                "web:latest".into(),
+            ])
+        );
+        assert!(pod.volumes.unwrap().iter().any(|volume| volume.name == "order"));
+    }
+
     #[test]
     fn test_docker_config_mount() {
         let mount = docker_config_mount();
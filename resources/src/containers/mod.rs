@@ -13,15 +13,22 @@
 // limitations under the License.
 
 pub mod application;
+pub mod cosign;
 pub mod devcontainer;
 pub mod git_sync;
+pub mod helm;
 pub mod kaniko;
 pub mod lifecycle;
+pub mod nixpacks;
+pub mod sbom;
+pub mod sidecar;
 pub mod syncer;
 
 use k8s_openapi::api::core::v1::{KeyToPath, SecretVolumeSource, Volume, VolumeMount};
 
-const WORKSPACE_DIR: &str = "/workspace";
+use crate::secret::REGISTRY_SECRET;
+
+pub(crate) const WORKSPACE_DIR: &str = "/workspace";
 
 /// volume for /workspace based on k8s emptyDir
 #[inline]
@@ -41,7 +48,7 @@ pub fn docker_config_volume() -> Volume {
     Volume {
         name: "docker-config".to_string(),
         secret: Some(SecretVolumeSource {
-            secret_name: Some("amp-registry-credentials".into()),
+            secret_name: Some(REGISTRY_SECRET.into()),
             items: Some(vec![KeyToPath {
                 key: ".dockerconfigjson".into(),
                 path: "config.json".into(),
@@ -53,6 +60,12 @@ pub fn docker_config_volume() -> Volume {
     }
 }
 
+/// volume mount for docker config
+#[inline]
+pub fn docker_config_mount() -> VolumeMount {
+    VolumeMount { name: "docker-config".into(), mount_path: "/.docker".into(), ..Default::default() }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
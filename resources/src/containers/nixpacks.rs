@@ -0,0 +1,112 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use amp_common::resource::{Actor, ActorSpec};
+use k8s_openapi::api::core::v1::{Container, PodSpec};
+
+use super::{kaniko, workspace_mount, WORKSPACE_DIR};
+use crate::build::BuildResources;
+use crate::error::Result;
+use crate::monorepo;
+
+const DEFAULT_NIXPACKS_IMAGE: &str = "ghcr.io/amphitheatre-app/nixpacks:v1.29.1";
+
+/// Build the pod generating the Dockerfile with Nixpacks before building it
+/// with Kaniko, as Nixpacks needs a Docker daemon to build the image itself.
+pub fn pod(actor: &Actor, resources: &BuildResources) -> Result<PodSpec> {
+    let mut pod = kaniko::pod(actor, resources)?;
+    pod.init_containers.get_or_insert_with(Vec::new).push(container(&actor.spec));
+
+    let dockerfile = format!("--dockerfile={}/.nixpacks/Dockerfile", workdir(&actor.spec));
+    for container in pod.containers.iter_mut().filter(|container| container.name == "builder") {
+        let args = container.args.get_or_insert_with(Vec::new);
+        args.retain(|arg| !arg.starts_with("--dockerfile="));
+        args.push(dockerfile.clone());
+    }
+
+    Ok(pod)
+}
+
+/// Build and return the container spec generating the Dockerfile into `.nixpacks`.
+pub fn container(spec: &ActorSpec) -> Container {
+    let build = spec.character.build.clone().unwrap_or_default();
+    let workdir = workdir(spec);
+
+    let mut arguments = vec!["build".to_string(), workdir.clone(), "--out".into(), workdir];
+    for env in build.env().unwrap_or_default() {
+        if let Some(value) = env.value {
+            arguments.extend(["--env".into(), format!("{}={}", env.name, value)]);
+        }
+    }
+
+    Container {
+        name: "nixpacks".to_string(),
+        image: Some(DEFAULT_NIXPACKS_IMAGE.into()),
+        image_pull_policy: Some("IfNotPresent".into()),
+        command: Some(vec!["nixpacks".into()]),
+        args: Some(arguments),
+        volume_mounts: Some(vec![workspace_mount()]),
+        ..Default::default()
+    }
+}
+
+/// Returns the build context in the workspace.
+fn workdir(spec: &ActorSpec) -> String {
+    let mut workdir = PathBuf::from(WORKSPACE_DIR);
+    if let Some(context) = monorepo::context(spec) {
+        workdir.push(context);
+    }
+
+    workdir.to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nixpacks_container() {
+        let spec = ActorSpec { name: "test".into(), image: "test".into(), ..Default::default() };
+
+        let container = container(&spec);
+
+        assert_eq!(container.name, "nixpacks");
+        assert_eq!(container.image, Some(DEFAULT_NIXPACKS_IMAGE.into()));
+        assert_eq!(
+            container.args,
+            Some(vec!["build".into(), "/workspace".into(), "--out".into(), "/workspace".into()])
+        );
+    }
+
+    #[test]
+    fn test_nixpacks_pod() {
+        use amp_common::schema::GitReference;
+
+        let source = Some(GitReference {
+            repo: "https://github.com/amphitheatre-app/amp-example-go".into(),
+            ..Default::default()
+        });
+        let spec = ActorSpec { name: "test".into(), image: "test".into(), source, ..Default::default() };
+        let actor = Actor::new("test", spec);
+
+        let pod = pod(&actor, &BuildResources::default()).unwrap();
+
+        let init_containers = pod.init_containers.unwrap();
+        assert_eq!(init_containers.last().map(|container| container.name.as_str()), Some("nixpacks"));
+        let args = pod.containers[0].args.clone().unwrap();
+        assert!(args.contains(&"--dockerfile=/workspace/.nixpacks/Dockerfile".to_string()));
+    }
+}
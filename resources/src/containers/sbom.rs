@@ -0,0 +1,130 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::{Container, EnvVar, PodSpec};
+
+use super::{docker_config_mount, docker_config_volume, workspace_mount, workspace_volume, WORKSPACE_DIR};
+use crate::args;
+use crate::sbom::Format;
+
+const DEFAULT_SYFT_IMAGE: &str = "anchore/syft:v1.11.1";
+const DEFAULT_ORAS_IMAGE: &str = "ghcr.io/oras-project/oras:v1.2.0";
+const DEFAULT_READER_IMAGE: &str = "busybox:1.36";
+
+/// The name of the container printing the generated SBOM file, its logs are the
+/// SBOM document only, the diagnostics of syft never end up in them.
+pub const READER_CONTAINER_NAME: &str = "reader";
+
+const SBOM_FILENAME: &str = "sbom.json";
+
+/// Build the pod spec which generates the SBOM of the actor's image with syft,
+/// attaches it to the image as an OCI referrer artifact with oras, and prints it.
+pub fn pod(actor: &Actor, format: &Format) -> PodSpec {
+    PodSpec {
+        init_containers: Some(vec![generator(&actor.spec.image, format), attacher(&actor.spec.image, format)]),
+        containers: vec![reader()],
+        restart_policy: Some("Never".into()),
+        volumes: Some(vec![workspace_volume(), docker_config_volume()]),
+        ..Default::default()
+    }
+}
+
+/// Build and return the syft container, the SBOM is written to the workspace.
+pub fn generator(image: &str, format: &Format) -> Container {
+    let output = format!("{}={}/{}", format.syft_output(), WORKSPACE_DIR, SBOM_FILENAME);
+    let arguments = vec![image.to_string(), "--quiet".into(), "-o".into(), output];
+
+    Container {
+        name: "syft".into(),
+        image: Some(DEFAULT_SYFT_IMAGE.into()),
+        image_pull_policy: Some("IfNotPresent".into()),
+        args: Some(arguments),
+        env: Some(vec![EnvVar { name: "DOCKER_CONFIG".into(), value: Some("/.docker".into()), ..Default::default() }]),
+        volume_mounts: Some(vec![workspace_mount(), docker_config_mount()]),
+        ..Default::default()
+    }
+}
+
+/// Build and return the oras container attaching the SBOM to the image.
+pub fn attacher(image: &str, format: &Format) -> Container {
+    let mut arguments = vec!["attach".to_string()];
+    arguments.extend(args(&[("artifact-type", format.media_type()), ("registry-config", "/.docker/config.json")], 2));
+    arguments.push(image.into());
+    arguments.push(format!("{}:{}", SBOM_FILENAME, format.media_type()));
+
+    Container {
+        name: "oras".into(),
+        image: Some(DEFAULT_ORAS_IMAGE.into()),
+        image_pull_policy: Some("IfNotPresent".into()),
+        args: Some(arguments),
+        working_dir: Some(WORKSPACE_DIR.into()),
+        volume_mounts: Some(vec![workspace_mount(), docker_config_mount()]),
+        ..Default::default()
+    }
+}
+
+/// Build and return the container printing the SBOM file of the workspace.
+pub fn reader() -> Container {
+    Container {
+        name: READER_CONTAINER_NAME.into(),
+        image: Some(DEFAULT_READER_IMAGE.into()),
+        image_pull_policy: Some("IfNotPresent".into()),
+        command: Some(vec!["cat".into(), format!("{}/{}", WORKSPACE_DIR, SBOM_FILENAME)]),
+        volume_mounts: Some(vec![workspace_mount()]),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator_container() {
+        let container = generator("test:v1", &Format::CycloneDx);
+
+        assert_eq!(container.name, "syft");
+        assert_eq!(container.image, Some(DEFAULT_SYFT_IMAGE.into()));
+        assert_eq!(
+            container.args,
+            Some(vec!["test:v1".into(), "--quiet".into(), "-o".into(), "cyclonedx-json=/workspace/sbom.json".into()])
+        );
+    }
+
+    #[test]
+    fn test_reader_container() {
+        let container = reader();
+
+        assert_eq!(container.name, READER_CONTAINER_NAME);
+        assert_eq!(container.command, Some(vec!["cat".into(), "/workspace/sbom.json".into()]));
+    }
+
+    #[test]
+    fn test_attacher_container() {
+        let container = attacher("test:v1", &Format::Spdx);
+
+        assert_eq!(container.name, "oras");
+        assert_eq!(
+            container.args,
+            Some(vec![
+                "attach".into(),
+                "--artifact-type=application/spdx+json".into(),
+                "--registry-config=/.docker/config.json".into(),
+                "test:v1".into(),
+                "sbom.json:application/spdx+json".into(),
+            ])
+        );
+    }
+}
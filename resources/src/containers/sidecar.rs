@@ -0,0 +1,142 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::{Container, EnvVar, VolumeMount};
+use kube::ResourceExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The annotation of actor holding its init containers and sidecars as a
+/// JSON document. The playbook declares them for a character with the
+/// annotation suffixed by its name, e.g. `amphitheatre.app/containers.web`.
+pub const CONTAINERS_ANNOTATION: &str = "amphitheatre.app/containers";
+
+/// The containers running along with the application container of actor.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Containers {
+    /// Run to completion in order before the application starts, e.g. the migrations.
+    #[serde(default)]
+    pub init_containers: Vec<ContainerSpec>,
+    /// Run alongside the application for the lifetime of the pod, e.g. log
+    /// shippers and proxies. They are started before the init containers, and
+    /// don't keep the scheduled tasks from completing.
+    #[serde(default)]
+    pub sidecars: Vec<ContainerSpec>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct ContainerSpec {
+    pub name: String,
+    pub image: String,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Mount the volumes of actor by their names.
+    #[serde(default)]
+    pub mounts: Vec<Mount>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Mount {
+    pub volume: String,
+    pub mount_path: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl Containers {
+    pub fn is_empty(&self) -> bool {
+        self.init_containers.is_empty() && self.sidecars.is_empty()
+    }
+
+    /// Build the init containers of pod, the sidecars are the native ones
+    /// restarting always, which requires Kubernetes 1.29 or later.
+    pub fn build(&self) -> Vec<Container> {
+        let sidecars =
+            self.sidecars.iter().map(|spec| Container { restart_policy: Some("Always".into()), ..spec.container() });
+
+        sidecars.chain(self.init_containers.iter().map(ContainerSpec::container)).collect()
+    }
+}
+
+impl ContainerSpec {
+    pub fn container(&self) -> Container {
+        let env = self.env.iter().map(|(name, value)| EnvVar {
+            name: name.clone(),
+            value: Some(value.clone()),
+            value_from: None,
+        });
+        let mounts = self.mounts.iter().map(|mount| VolumeMount {
+            name: mount.volume.clone(),
+            mount_path: mount.mount_path.clone(),
+            read_only: Some(mount.read_only),
+            ..Default::default()
+        });
+
+        Container {
+            name: self.name.clone(),
+            image: Some(self.image.clone()),
+            command: (!self.command.is_empty()).then(|| self.command.clone()),
+            args: (!self.args.is_empty()).then(|| self.args.clone()),
+            env: Some(env.collect()),
+            volume_mounts: Some(mounts.collect()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns the init containers and sidecars of actor.
+pub fn containers(actor: &Actor) -> Result<Containers> {
+    match actor.annotations().get(CONTAINERS_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map_err(Error::SerializationError),
+        None => Ok(Containers::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_build_containers() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert!(containers(&actor).unwrap().is_empty());
+
+        actor.annotations_mut().insert(
+            CONTAINERS_ANNOTATION.into(),
+            r#"{"init_containers": [{"name": "migrate", "image": "web:latest", "command": ["./migrate"]}],
+                "sidecars": [{"name": "logs", "image": "fluent-bit",
+                              "mounts": [{"volume": "logs", "mount_path": "/var/log/app", "read_only": true}]}]}"#
+                .into(),
+        );
+        let built = containers(&actor).unwrap().build();
+
+        assert_eq!(built.len(), 2);
+        assert_eq!(built[0].name, "logs");
+        assert_eq!(built[0].restart_policy.as_deref(), Some("Always"));
+        assert_eq!(built[0].volume_mounts.as_ref().unwrap()[0].mount_path, "/var/log/app");
+        assert_eq!(built[1].name, "migrate");
+        assert_eq!(built[1].restart_policy, None);
+        assert_eq!(built[1].command, Some(vec!["./migrate".to_string()]));
+    }
+}
@@ -15,10 +15,10 @@
 use std::path::PathBuf;
 
 use super::{workspace_mount, WORKSPACE_DIR};
-use crate::args;
 use crate::error::{Error, Result};
+use crate::{args, upload};
 use amp_common::resource::Actor;
-use k8s_openapi::api::core::v1::{Container, SecurityContext};
+use k8s_openapi::api::core::v1::{Container, SecurityContext, VolumeMount};
 use kube::ResourceExt;
 use lazy_static::lazy_static;
 
@@ -64,6 +64,47 @@ pub fn container(actor: &Actor, security_context: &Option<SecurityContext>) -> R
     })
 }
 
+/// Build and return the syncer running next to the application container of
+/// the live actor, it syncs the files into the shared volume mounted at the
+/// workspace and reports the synced events for the reload hooks.
+pub fn sidecar(actor: &Actor, volume: &str) -> Result<Container> {
+    let mut container = container(actor, &None)?;
+    container.name = "amp-syncer".into();
+    container.args = Some(args(
+        &[
+            ("nats-url", "nats://amp-nats.amp-system.svc:4222"),
+            ("workspace", WORKSPACE_DIR),
+            ("playbook", owner_reference(actor)?.as_str()),
+            ("actor", actor.spec.name.as_str()),
+            ("once", "false"),
+            ("notify", "true"),
+        ],
+        2,
+    ));
+    container.volume_mounts =
+        Some(vec![VolumeMount { name: volume.into(), mount_path: WORKSPACE_DIR.into(), ..Default::default() }]);
+
+    Ok(container)
+}
+
+/// Build and return the syncer unpacking the uploaded source into the
+/// workspace of the build pod, it exits once unpacked.
+pub fn upload(actor: &Actor, id: &str, security_context: &Option<SecurityContext>) -> Result<Container> {
+    let mut container = container(actor, security_context)?;
+    container.args = Some(args(
+        &[
+            ("nats-url", "nats://amp-nats.amp-system.svc:4222"),
+            ("workspace", WORKSPACE_DIR),
+            ("playbook", owner_reference(actor)?.as_str()),
+            ("actor", actor.spec.name.as_str()),
+            ("upload", upload::url(id).as_str()),
+        ],
+        2,
+    ));
+
+    Ok(container)
+}
+
 /// Get the playbook name from the owner reference.
 #[inline]
 fn owner_reference(actor: &Actor) -> Result<String> {
@@ -0,0 +1,200 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use k8s_openapi::api::core::v1::{ConfigMap, PodSpec};
+use kube::{Api, Client};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::error::{Error, Result};
+use super::metrics::quantity;
+use super::usage::Usage;
+
+/// The name of the ConfigMap holding the unit prices as a TOML document under
+/// the `prices` key, e.g. synced from the pricing data of the cloud, they
+/// take precedence over the configured ones.
+const PRICES_CONFIG_MAP: &str = "amp-prices";
+
+/// The average number of hours in a month.
+pub const HOURS_PER_MONTH: f64 = 730.0;
+
+/// The number of bytes in a GiB.
+const GIB: f64 = 1073741824.0;
+
+/// The unit prices the costs are estimated with, the resources without a
+/// price are free.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Prices {
+    /// The currency of the prices, e.g. `USD`.
+    pub currency: Option<String>,
+    /// The price of a cpu core requested for an hour.
+    pub cpu_hour: Option<f64>,
+    /// The price of a GiB of memory requested for an hour.
+    pub memory_gib_hour: Option<f64>,
+    /// The price of a build minute.
+    pub build_minute: Option<f64>,
+}
+
+impl Prices {
+    /// Merge the other prices into these, the set ones of the other take precedence.
+    pub fn merge(self, other: Prices) -> Prices {
+        Prices {
+            currency: other.currency.or(self.currency),
+            cpu_hour: other.cpu_hour.or(self.cpu_hour),
+            memory_gib_hour: other.memory_gib_hour.or(self.memory_gib_hour),
+            build_minute: other.build_minute.or(self.build_minute),
+        }
+    }
+
+    /// Returns the price of a pod requesting the resources for an hour.
+    pub fn pod_hour(&self, requests: &Requests) -> f64 {
+        requests.cpu * self.cpu_hour.unwrap_or_default()
+            + requests.memory / GIB * self.memory_gib_hour.unwrap_or_default()
+    }
+}
+
+/// The resources requested by a pod, the cpu in cores and the memory in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Requests {
+    pub cpu: f64,
+    pub memory: f64,
+}
+
+/// The estimated cost of actor.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Cost {
+    /// The cost of the running pods per hour.
+    pub hourly: f64,
+    /// The cost of a month if the pods keep running as now.
+    pub monthly: f64,
+    /// The cost of the runtime and the builds in the current month so far.
+    pub month_to_date: f64,
+}
+
+/// Load the unit prices from the ConfigMap in the namespace, over the defaults.
+pub async fn load(client: &Client, namespace: &str, defaults: Prices) -> Result<Prices> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let Some(config_map) = api.get_opt(PRICES_CONFIG_MAP).await.map_err(Error::KubeError)? else {
+        debug!("The {} was not found, the configured prices applied.", PRICES_CONFIG_MAP);
+        return Ok(defaults);
+    };
+
+    match config_map.data.unwrap_or_default().get("prices") {
+        Some(content) => Ok(defaults.merge(toml::from_str(content).map_err(Error::TomlDeserializeError)?)),
+        None => Ok(defaults),
+    }
+}
+
+/// Returns the resources requested by the pod, the limits are requested if
+/// the requests are not set, as Kubernetes does.
+pub fn requests(pod: &PodSpec) -> Requests {
+    let mut requests = Requests::default();
+    for container in &pod.containers {
+        let Some(resources) = &container.resources else {
+            continue;
+        };
+        let get = |name: &str| {
+            let requested = resources.requests.as_ref().and_then(|r| r.get(name));
+            let value = requested.or_else(|| resources.limits.as_ref().and_then(|l| l.get(name)));
+            value.and_then(|q| quantity(&q.0)).unwrap_or_default()
+        };
+        requests.cpu += get("cpu");
+        requests.memory += get("memory");
+    }
+
+    requests
+}
+
+/// Estimate the cost of actor running the replicas of the pods requesting
+/// the resources, along with its usage of the current month.
+pub fn estimate(prices: &Prices, requests: &Requests, replicas: i32, usage: &Usage) -> Cost {
+    let pod_hour = prices.pod_hour(requests);
+    let hourly = pod_hour * replicas.max(0) as f64;
+
+    Cost {
+        hourly,
+        monthly: hourly * HOURS_PER_MONTH,
+        month_to_date: pod_hour * usage.runtime_hours + prices.build_minute.unwrap_or_default() * usage.build_minutes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::api::core::v1::{Container, ResourceRequirements};
+    use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+
+    fn container(requests: &[(&str, &str)], limits: &[(&str, &str)]) -> Container {
+        let items = |items: &[(&str, &str)]| {
+            let items: BTreeMap<String, Quantity> =
+                items.iter().map(|(k, v)| (k.to_string(), Quantity(v.to_string()))).collect();
+            (!items.is_empty()).then_some(items)
+        };
+        Container {
+            resources: Some(ResourceRequirements {
+                requests: items(requests),
+                limits: items(limits),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let defaults = Prices { currency: Some("USD".into()), cpu_hour: Some(0.04), ..Default::default() };
+        let prices = defaults.merge(Prices { cpu_hour: Some(0.03), build_minute: Some(0.01), ..Default::default() });
+
+        assert_eq!(prices.currency.as_deref(), Some("USD"));
+        assert_eq!(prices.cpu_hour, Some(0.03));
+        assert_eq!(prices.memory_gib_hour, None);
+        assert_eq!(prices.build_minute, Some(0.01));
+    }
+
+    #[test]
+    fn test_requests() {
+        let pod = PodSpec {
+            containers: vec![
+                container(&[("cpu", "500m"), ("memory", "1Gi")], &[("cpu", "1")]),
+                container(&[], &[("cpu", "250m"), ("memory", "512Mi")]),
+                Container::default(),
+            ],
+            ..Default::default()
+        };
+
+        let requests = requests(&pod);
+        assert!((requests.cpu - 0.75).abs() < 1e-9);
+        assert_eq!(requests.memory, 1.5 * GIB);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let prices = Prices {
+            cpu_hour: Some(0.04),
+            memory_gib_hour: Some(0.01),
+            build_minute: Some(0.005),
+            ..Default::default()
+        };
+        let requests = Requests { cpu: 0.5, memory: 2.0 * GIB };
+        let usage = Usage { runtime_hours: 100.0, build_minutes: 60.0, ..Default::default() };
+
+        let cost = estimate(&prices, &requests, 2, &usage);
+        assert!((cost.hourly - 0.08).abs() < 1e-9);
+        assert!((cost.monthly - 0.08 * HOURS_PER_MONTH).abs() < 1e-9);
+        assert!((cost.month_to_date - (0.04 * 100.0 + 0.3)).abs() < 1e-9);
+    }
+}
@@ -24,10 +24,11 @@ use crate::{secret, service_account};
 pub async fn sync(client: &Client, namespace: &str, name: &str, credentials: &Credentials) -> Result<()> {
     let mut secrets = vec![];
 
-    // Patch the image pull secrets to service account
-    info!("Patch the image pull secrets to Service Account {}", name);
+    // The image pull secrets are set on the pods explicitly instead of the
+    // service account, so the unrelated workloads can't pull with them.
+    // Remove the ones patched into the service account previously.
     secrets.extend(sync_registry_credentials(client, namespace, credentials).await?);
-    service_account::patch(client, namespace, name, &secrets, false, true).await?;
+    service_account::revoke_image_pull_secrets(client, namespace, name, &secrets).await?;
 
     // Patch the secrets to service account
     info!("Patch the secrets to Service Account {}", name);
@@ -39,6 +40,20 @@ pub async fn sync(client: &Client, namespace: &str, name: &str, credentials: &Cr
     Ok(())
 }
 
+/// Sync the credentials into the dedicated ServiceAccount of the builds, which
+/// is permitted to read these credentials only.
+pub async fn sync_builder(client: &Client, namespace: &str, credentials: &Credentials) -> Result<()> {
+    let mut secrets = sync_registry_credentials(client, namespace, credentials).await?;
+    secrets.extend(sync_repository_credentials(client, namespace, credentials).await?);
+
+    info!("Patch the secrets to Service Account {}", service_account::BUILDER_SERVICE_ACCOUNT);
+    let names: Vec<String> = secrets.iter().map(|secret| secret.name_any()).collect();
+    service_account::builder(client, namespace, &names).await?;
+    service_account::patch(client, namespace, service_account::BUILDER_SERVICE_ACCOUNT, &secrets, true, true).await?;
+
+    Ok(())
+}
+
 /// Sync Docker registry credentials.
 async fn sync_registry_credentials(client: &Client, namespace: &str, credentials: &Credentials) -> Result<Vec<Secret>> {
     let mut secrets = vec![];
@@ -75,7 +90,7 @@ async fn sync_repository_credentials(
 /// Load the credentials from the Kubernetes secret.
 /// FIXME: return the error instead of None
 pub async fn load(client: &Client, namespace: &str) -> Result<Option<Credentials>> {
-    let result = secret::get_opt(client, namespace, "amp-credentials").await?;
+    let result = secret::get_opt(client, namespace, secret::CREDENTIALS_SECRET).await?;
     if result.is_none() {
         debug!("the amp-credentials was not found.");
         return Ok(None);
@@ -0,0 +1,266 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::batch::v1::{CronJob, CronJobSpec, Job, JobSpec, JobTemplateSpec};
+use k8s_openapi::api::core::v1::{PodSpec, PodTemplateSpec};
+use k8s_openapi::chrono::Utc;
+use kube::api::{DeleteParams, Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+
+use super::error::{Error, Result};
+use super::{actor, naming, LAST_APPLIED_HASH_KEY, REQUESTED_AT_ANNOTATION};
+
+/// The annotation of actor holding its schedule as a JSON document, the
+/// actor is deployed as a CronJob instead of a Deployment if it's set. The
+/// playbook schedules a character with the annotation suffixed by its name,
+/// e.g. `amphitheatre.app/schedule.cleanup`.
+pub const SCHEDULE_ANNOTATION: &str = "amphitheatre.app/schedule";
+
+/// The annotation of actor recording the time of the latest manual run requested.
+pub const TRIGGER_ANNOTATION: &str = "amphitheatre.app/trigger";
+
+/// The annotation of actor recording the latest manual run requested that has been run.
+pub const TRIGGERED_ANNOTATION: &str = "amphitheatre.app/triggered";
+
+/// The schedule of actor running as a scheduled task, e.g.
+/// `{"cron": "*/30 * * * *", "concurrency_policy": "Forbid"}`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Schedule {
+    /// The schedule in Cron format.
+    pub cron: String,
+    /// `Allow`, `Forbid` or `Replace` the concurrent runs, the default is `Forbid`.
+    pub concurrency_policy: Option<String>,
+    /// The number of the successful jobs kept, the default is `3`.
+    pub successful_jobs_history_limit: Option<i32>,
+    /// The number of the failed jobs kept, the default is `1`.
+    pub failed_jobs_history_limit: Option<i32>,
+    /// Suspend the subsequent runs, the manual runs are still allowed.
+    #[serde(default)]
+    pub suspend: bool,
+}
+
+/// Returns the schedule of actor, if any.
+pub fn schedule(actor: &Actor) -> Result<Option<Schedule>> {
+    match actor.annotations().get(SCHEDULE_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Request a manual run of the scheduled actor, it's run by the controller
+/// when the actor is reconciled. Returns the time of the request.
+pub async fn request(client: &Client, actor: &Actor) -> Result<String> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let time = Utc::now().to_rfc3339();
+    let patch = json!({"metadata": { "annotations": { TRIGGER_ANNOTATION: time, REQUESTED_AT_ANNOTATION: time }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Requested a manual run of actor {}", actor.name_any());
+
+    Ok(time)
+}
+
+/// Returns the manual run requested but not run yet, if any.
+pub fn requested(actor: &Actor) -> Option<String> {
+    let requested = actor.annotations().get(TRIGGER_ANNOTATION)?;
+    (actor.annotations().get(TRIGGERED_ANNOTATION) != Some(requested)).then(|| requested.clone())
+}
+
+/// Record the manual run requested has been run.
+pub async fn triggered(client: &Client, actor: &Actor, requested: &str) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let patch = json!({"metadata": { "annotations": { TRIGGERED_ANNOTATION: requested }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+pub async fn exists(client: &Client, namespace: &str, name: &str) -> Result<bool> {
+    let api: Api<CronJob> = Api::namespaced(client.clone(), namespace);
+    Ok(api.get_opt(name).await.map_err(Error::KubeError)?.is_some())
+}
+
+/// Create or update the CronJob if the hash is changed.
+pub async fn apply(client: &Client, namespace: &str, resource: CronJob, expected_hash: String) -> Result<CronJob> {
+    let api: Api<CronJob> = Api::namespaced(client.clone(), namespace);
+    let name = resource.name_any();
+
+    if let Some(cronjob) = api.get_opt(&name).await.map_err(Error::KubeError)? {
+        if cronjob.annotations().get(LAST_APPLIED_HASH_KEY) == Some(&expected_hash) {
+            debug!("The CronJob {} is already up-to-date", name);
+            return Ok(cronjob);
+        }
+    }
+
+    let params = &PatchParams::apply("amp-controllers").force();
+    let cronjob = api.patch(&name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+    info!("Applied CronJob: {}", cronjob.name_any());
+
+    Ok(cronjob)
+}
+
+pub async fn delete(client: &Client, namespace: &str, name: &str) -> Result<()> {
+    let api: Api<CronJob> = Api::namespaced(client.clone(), namespace);
+    api.delete(name, &DeleteParams::default()).await.map_err(Error::KubeError)?;
+    info!("Deleted CronJob {}", name);
+
+    Ok(())
+}
+
+/// Suspend or resume the subsequent runs of the CronJob, e.g. while its playbook is hibernated.
+pub async fn suspend(client: &Client, namespace: &str, name: &str, suspend: bool) -> Result<()> {
+    let api: Api<CronJob> = Api::namespaced(client.clone(), namespace);
+
+    let patch = json!({"spec": { "suspend": suspend }});
+    api.patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Set the suspension of CronJob {} to {}", name, suspend);
+
+    Ok(())
+}
+
+/// Run the CronJob once now, the same as `kubectl create job --from=cronjob/{name}`.
+pub async fn trigger(client: &Client, namespace: &str, name: &str) -> Result<Job> {
+    let api: Api<CronJob> = Api::namespaced(client.clone(), namespace);
+    let cronjob = api.get(name).await.map_err(Error::KubeError)?;
+
+    let resource = manual_job(&cronjob)?;
+    let jobs: Api<Job> = Api::namespaced(client.clone(), namespace);
+    let job = jobs.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+    info!("Triggered Job {} of CronJob {}", job.name_any(), name);
+
+    Ok(job)
+}
+
+fn manual_job(cronjob: &CronJob) -> Result<Job> {
+    let template = cronjob.spec.as_ref().map(|spec| spec.job_template.clone());
+    let template = template.ok_or_else(|| Error::MissingObjectKey(".spec.jobTemplate"))?;
+    let suffix = Utc::now().timestamp().to_string();
+
+    let mut metadata = template.metadata.unwrap_or_default();
+    metadata.name = Some(naming::name(&[&cronjob.name_any(), "manual", &suffix]));
+    metadata.owner_references = cronjob.controller_owner_ref(&()).map(|owner| vec![owner]);
+    metadata
+        .annotations
+        .get_or_insert_with(BTreeMap::new)
+        .insert("cronjob.kubernetes.io/instantiate".into(), "manual".into());
+
+    Ok(Job { metadata, spec: template.spec, ..Default::default() })
+}
+
+/// Returns the name of the CronJob of actor.
+pub fn name(actor: &Actor) -> String {
+    naming::name(&[&actor.name_any()])
+}
+
+/// Build the CronJob of actor, the pods are restarted on failure.
+pub fn new(actor: &Actor, schedule: &Schedule, mut pod: PodSpec, hash: String) -> Result<CronJob> {
+    let name = name(actor);
+
+    let owner_reference = actor.controller_owner_ref(&()).unwrap();
+    let labels = BTreeMap::from([
+        ("amphitheatre.app/character".into(), actor.name_any()),
+        ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+    ]);
+    let annotations = BTreeMap::from([(LAST_APPLIED_HASH_KEY.into(), hash)]);
+    let metadata = ObjectMeta {
+        name: Some(name),
+        owner_references: Some(vec![owner_reference]),
+        labels: Some(labels.clone()),
+        annotations: Some(annotations),
+        ..Default::default()
+    };
+
+    pod.restart_policy = Some("OnFailure".into());
+    let mut pod_labels = labels.clone();
+    pod_labels.extend(actor::pod_labels(actor));
+    let template = JobTemplateSpec {
+        metadata: Some(ObjectMeta { labels: Some(labels), ..Default::default() }),
+        spec: Some(JobSpec {
+            template: PodTemplateSpec {
+                metadata: Some(ObjectMeta { labels: Some(pod_labels), ..Default::default() }),
+                spec: Some(pod),
+            },
+            ..Default::default()
+        }),
+    };
+
+    let spec = CronJobSpec {
+        schedule: schedule.cron.clone(),
+        concurrency_policy: Some(schedule.concurrency_policy.clone().unwrap_or_else(|| "Forbid".into())),
+        successful_jobs_history_limit: Some(schedule.successful_jobs_history_limit.unwrap_or(3)),
+        failed_jobs_history_limit: Some(schedule.failed_jobs_history_limit.unwrap_or(1)),
+        suspend: Some(schedule.suspend),
+        job_template: template,
+        ..Default::default()
+    };
+
+    Ok(CronJob { metadata, spec: Some(spec), ..Default::default() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_parse_schedule() {
+        let mut actor = Actor::new("cleanup", ActorSpec::default());
+        assert_eq!(schedule(&actor).unwrap(), None);
+
+        actor.annotations_mut().insert(SCHEDULE_ANNOTATION.into(), r#"{"cron": "*/30 * * * *"}"#.into());
+        let parsed = schedule(&actor).unwrap().unwrap();
+        assert_eq!(parsed.cron, "*/30 * * * *");
+        assert_eq!(parsed.concurrency_policy, None);
+        assert!(!parsed.suspend);
+    }
+
+    #[test]
+    fn test_requested() {
+        let mut actor = Actor::new("cleanup", ActorSpec::default());
+        assert_eq!(requested(&actor), None);
+
+        actor.annotations_mut().insert(TRIGGER_ANNOTATION.into(), "2024-09-01T00:00:00Z".into());
+        assert_eq!(requested(&actor), Some("2024-09-01T00:00:00Z".into()));
+
+        actor.annotations_mut().insert(TRIGGERED_ANNOTATION.into(), "2024-09-01T00:00:00Z".into());
+        assert_eq!(requested(&actor), None);
+    }
+
+    #[test]
+    fn test_manual_job() {
+        let mut cronjob = CronJob {
+            metadata: ObjectMeta { name: Some("cleanup".into()), ..Default::default() },
+            spec: Some(CronJobSpec { schedule: "@daily".into(), ..Default::default() }),
+            ..Default::default()
+        };
+        cronjob.spec.as_mut().unwrap().job_template.metadata =
+            Some(ObjectMeta { labels: Some(BTreeMap::from([("app".into(), "cleanup".into())])), ..Default::default() });
+
+        let job = manual_job(&cronjob).unwrap();
+        assert!(job.name_any().starts_with("cleanup-manual-"));
+        assert_eq!(job.labels().get("app").map(String::as_str), Some("cleanup"));
+        assert_eq!(job.annotations().get("cronjob.kubernetes.io/instantiate").map(String::as_str), Some("manual"));
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::{Actor, Playbook};
+use k8s_openapi::api::core::v1::{
+    Capabilities, Container, ContainerPort, EnvVar, PodSpec, SecurityContext, Volume, VolumeMount,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::ResourceExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The annotation of actor running it under the debugger of its language as
+/// a JSON document, e.g. `{"language": "go", "command": ["/app/server"]}`.
+/// The playbook switches on the debugging of a character with the annotation
+/// suffixed by its name, e.g. `amphitheatre.app/debug.web`.
+pub const DEBUG_ANNOTATION: &str = "amphitheatre.app/debug";
+
+/// The languages with a debugger.
+pub const LANGUAGES: &[&str] = &["go", "python", "node"];
+
+/// The condition type of playbook reporting whether the debugged characters are in it.
+pub const DEBUGGERS_CONDITION_TYPE: &str = "DebuggersAttached";
+
+/// The name of the container port the debugger listens on.
+pub const DEBUG_PORT_NAME: &str = "debug";
+
+/// The directory the debuggers are installed into by the init container.
+const DEBUGGER_DIR: &str = "/amp-debug";
+
+const DEFAULT_DELVE_IMAGE: &str = "golang:1.22.5-alpine";
+const DELVE_PACKAGE: &str = "github.com/go-delve/delve/cmd/dlv";
+const DELVE_VERSION: &str = "v1.23.0";
+const DEBUGPY_VERSION: &str = "1.8.2";
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// Debugged by Delve, the binary is run by `dlv exec`.
+    Go,
+    /// Debugged by debugpy, the script is run by `python -m debugpy`.
+    Python,
+    /// Debugged by the inspector of Node.js, enabled by `NODE_OPTIONS`.
+    Node,
+}
+
+/// The debugger attached to the application container of actor.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Debugger {
+    pub language: Language,
+    /// The command of the application run under the debugger, the binary and
+    /// its arguments of Go, or the script and its arguments of Python. The
+    /// command of image is kept for Node.
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// The port the debugger listens on, the default one of the debugger if not set.
+    pub port: Option<i32>,
+}
+
+impl Debugger {
+    /// Returns the port the debugger listens on.
+    pub fn port(&self) -> i32 {
+        self.port.unwrap_or(match self.language {
+            Language::Go => 2345,
+            Language::Python => 5678,
+            Language::Node => 9229,
+        })
+    }
+
+    /// Attach the debugger to the application container of the pod. The
+    /// liveness and startup probes are removed, so the container is not
+    /// restarted while it's paused at a breakpoint.
+    pub fn apply(&self, pod: &mut PodSpec) {
+        let Some(container) = pod.containers.first_mut() else {
+            return;
+        };
+        let port = self.port();
+
+        container.liveness_probe = None;
+        container.startup_probe = None;
+        container.ports.get_or_insert_with(Vec::new).push(ContainerPort {
+            name: Some(DEBUG_PORT_NAME.into()),
+            container_port: port,
+            protocol: Some("TCP".into()),
+            ..Default::default()
+        });
+
+        let (init, command, vars) = match self.language {
+            Language::Go => {
+                let mut command = vec![
+                    format!("{}/dlv", DEBUGGER_DIR),
+                    "exec".into(),
+                    "--headless".into(),
+                    format!("--listen=:{}", port),
+                    "--api-version=2".into(),
+                    "--accept-multiclient".into(),
+                    "--continue".into(),
+                ];
+                command.extend(self.command.first().cloned());
+                if self.command.len() > 1 {
+                    command.push("--".into());
+                    command.extend(self.command[1..].iter().cloned());
+                }
+                // Delve attaches to the process by ptrace.
+                let capabilities = Capabilities { add: Some(vec!["SYS_PTRACE".into()]), drop: None };
+                let context = container.security_context.get_or_insert_with(SecurityContext::default);
+                context.capabilities = Some(capabilities);
+
+                let script =
+                    format!("go install {}@{} && cp /go/bin/dlv {}/", DELVE_PACKAGE, DELVE_VERSION, DEBUGGER_DIR);
+                (Some(installer(DEFAULT_DELVE_IMAGE, script, &[("CGO_ENABLED", "0")])), Some(command), vec![])
+            }
+            Language::Python => {
+                let listen = format!("0.0.0.0:{}", port);
+                let mut command = vec!["python".into(), "-m".into(), "debugpy".into(), "--listen".into(), listen];
+                command.extend(self.command.iter().cloned());
+
+                // debugpy is installed by the interpreter of the application
+                // image, so that it's built for the same Python and platform.
+                let script = format!(
+                    "python -m pip install --no-cache-dir --target {} debugpy=={}",
+                    DEBUGGER_DIR, DEBUGPY_VERSION
+                );
+                let vars = vec![env("PYTHONPATH", DEBUGGER_DIR)];
+                let image = container.image.clone().unwrap_or_default();
+                (Some(installer(&image, script, &[])), Some(command), vars)
+            }
+            Language::Node => {
+                let vars = vec![env("NODE_OPTIONS", &format!("--inspect=0.0.0.0:{}", port))];
+                (None, None, vars)
+            }
+        };
+
+        if command.is_some() {
+            container.command = command;
+            container.args = None;
+        }
+        let declared = container.env.get_or_insert_with(Vec::new);
+        declared.retain(|var| vars.iter().all(|v| v.name != var.name));
+        declared.extend(vars);
+
+        if let Some(installer) = init {
+            container.volume_mounts.get_or_insert_with(Vec::new).push(mount());
+            pod.volumes.get_or_insert_with(Vec::new).push(Volume {
+                name: "amp-debug".into(),
+                empty_dir: Some(Default::default()),
+                ..Default::default()
+            });
+            pod.init_containers.get_or_insert_with(Vec::new).push(installer);
+        }
+    }
+}
+
+/// Returns the debugger of actor, if it's debugging.
+pub fn debugger(actor: &Actor) -> Result<Option<Debugger>> {
+    let Some(content) = actor.annotations().get(DEBUG_ANNOTATION) else {
+        return Ok(None);
+    };
+    let debugger: Debugger = serde_json::from_str(content).map_err(Error::SerializationError)?;
+    if debugger.language != Language::Node && debugger.command.is_empty() {
+        return Err(Error::InvalidDebugger(format!("the command is required by {:?}", debugger.language)));
+    }
+
+    Ok(Some(debugger))
+}
+
+/// Returns the characters debugged by the annotations of playbook which are
+/// not in it, the annotations of them are never inherited by any actor.
+pub fn unknown(playbook: &Playbook) -> Vec<String> {
+    let prefix = format!("{}.", DEBUG_ANNOTATION);
+    let characters: Vec<&str> = playbook.spec.characters.iter().flatten().map(|c| c.meta.name.as_str()).collect();
+    playbook
+        .annotations()
+        .keys()
+        .filter_map(|key| key.strip_prefix(&prefix))
+        .filter(|name| !characters.contains(name))
+        .map(String::from)
+        .collect()
+}
+
+pub fn condition(unknown: &[String]) -> Condition {
+    let (status, reason, message) = match unknown.is_empty() {
+        true => ("True", "Attached", String::new()),
+        false => ("False", "UnknownCharacters", format!("Not in the playbook: {}", unknown.join(", "))),
+    };
+
+    Condition {
+        type_: DEBUGGERS_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: reason.into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// Returns true if the actor is running under the debugger.
+pub fn debugging(actor: &Actor) -> bool {
+    actor.annotations().contains_key(DEBUG_ANNOTATION)
+}
+
+/// Build the init container installing the debugger into the shared directory.
+fn installer(image: &str, script: String, env: &[(&str, &str)]) -> Container {
+    Container {
+        name: "amp-debugger".into(),
+        image: Some(image.into()),
+        command: Some(vec!["/bin/sh".into(), "-c".into(), script]),
+        env: Some(env.iter().map(|(name, value)| self::env(name, value)).collect()),
+        volume_mounts: Some(vec![mount()]),
+        ..Default::default()
+    }
+}
+
+fn mount() -> VolumeMount {
+    VolumeMount { name: "amp-debug".into(), mount_path: DEBUGGER_DIR.into(), ..Default::default() }
+}
+
+fn env(name: &str, value: &str) -> EnvVar {
+    EnvVar { name: name.into(), value: Some(value.into()), value_from: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::{ActorSpec, CharacterSpec, PlaybookSpec};
+    use k8s_openapi::api::core::v1::Probe;
+
+    fn pod() -> PodSpec {
+        let container = Container {
+            name: "web".into(),
+            liveness_probe: Some(Probe::default()),
+            readiness_probe: Some(Probe::default()),
+            ..Default::default()
+        };
+        PodSpec { containers: vec![container], ..Default::default() }
+    }
+
+    #[test]
+    fn test_parse_debugger() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(debugger(&actor).unwrap(), None);
+        assert!(!debugging(&actor));
+
+        actor.annotations_mut().insert(DEBUG_ANNOTATION.into(), r#"{"language": "node"}"#.into());
+        let parsed = debugger(&actor).unwrap().unwrap();
+        assert_eq!(parsed.language, Language::Node);
+        assert_eq!(parsed.port(), 9229);
+
+        actor.annotations_mut().insert(DEBUG_ANNOTATION.into(), r#"{"language": "go"}"#.into());
+        assert!(debugger(&actor).is_err());
+    }
+
+    #[test]
+    fn test_apply_go_debugger() {
+        let debugger =
+            Debugger { language: Language::Go, command: vec!["/app/server".into(), "-v".into()], port: None };
+        let mut pod = pod();
+        debugger.apply(&mut pod);
+
+        let container = &pod.containers[0];
+        let command = container.command.as_ref().unwrap();
+        assert_eq!(command[0], "/amp-debug/dlv");
+        assert!(command.contains(&"--listen=:2345".to_string()));
+        assert_eq!(command[command.len() - 3..], ["/app/server", "--", "-v"]);
+        assert_eq!(container.liveness_probe, None);
+        assert!(container.readiness_probe.is_some());
+        assert_eq!(container.ports.as_ref().unwrap()[0].container_port, 2345);
+        assert_eq!(pod.init_containers.as_ref().unwrap()[0].name, "amp-debugger");
+        assert_eq!(pod.volumes.as_ref().unwrap()[0].name, "amp-debug");
+    }
+
+    #[test]
+    fn test_apply_node_debugger() {
+        let debugger = Debugger { language: Language::Node, command: vec![], port: Some(9230) };
+        let mut pod = pod();
+        debugger.apply(&mut pod);
+
+        let container = &pod.containers[0];
+        assert_eq!(container.command, None);
+        assert_eq!(container.env.as_ref().unwrap()[0].value.as_deref(), Some("--inspect=0.0.0.0:9230"));
+        assert_eq!(pod.init_containers, None);
+    }
+
+    #[test]
+    fn test_unknown() {
+        let mut character = CharacterSpec::default();
+        character.meta.name = "web".into();
+        let spec = PlaybookSpec { characters: Some(vec![character]), ..Default::default() };
+        let mut playbook = Playbook::new("test", spec);
+        assert!(unknown(&playbook).is_empty());
+
+        let content = r#"{"language": "node"}"#;
+        playbook.annotations_mut().insert(format!("{}.web", DEBUG_ANNOTATION), content.into());
+        playbook.annotations_mut().insert(format!("{}.api", DEBUG_ANNOTATION), content.into());
+        assert_eq!(unknown(&playbook), vec!["api".to_string()]);
+        assert_eq!(condition(&unknown(&playbook)).status, "False");
+    }
+}
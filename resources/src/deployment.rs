@@ -16,15 +16,30 @@ use std::collections::BTreeMap;
 
 use amp_common::resource::Actor;
 use k8s_openapi::api::apps::v1::{Deployment, DeploymentSpec};
-use k8s_openapi::api::core::v1::{PodSpec, PodTemplateSpec};
+use k8s_openapi::api::core::v1::{EnvVar, PodSpec, PodTemplateSpec, Probe, TCPSocketAction};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
-use kube::api::{Patch, PatchParams, PostParams};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use k8s_openapi::ByteString;
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
 use kube::core::ObjectMeta;
 use kube::{Api, Client, Resource, ResourceExt};
+use serde_json::json;
 use tracing::{debug, info};
 
+use super::actor;
+use super::cache;
+use super::containers::application;
+use super::containers::sidecar::Containers;
+use super::debug;
+use super::devcontainer;
 use super::error::{Error, Result};
-use super::{hash, LAST_APPLIED_HASH_KEY};
+use super::image::{self, ExposedPort};
+use super::probe::Probes;
+use super::reload;
+use super::replicas;
+use super::secret;
+use super::volume::{self, Volume};
+use super::{hash, naming, LAST_APPLIED_HASH_KEY};
 
 pub async fn exists(client: &Client, namespace: &str, name: &str) -> Result<bool> {
     let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
@@ -47,6 +62,14 @@ pub async fn update(
     expected_hash: String,
 ) -> Result<Deployment> {
     let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+
+    // The actor is reconciled again on the changes, so it's safe to skip the stale cached Deployment.
+    let cached = cache::deployment(namespace, name);
+    if let Some(deployment) = cached.filter(|d| d.annotations().get(LAST_APPLIED_HASH_KEY) == Some(&expected_hash)) {
+        debug!("The cached Deployment {} is already up-to-date", name);
+        return Ok((*deployment).clone());
+    }
+
     let mut deployment = api.get(name).await.map_err(Error::KubeError)?;
     debug!("The Deployment {} already exists", name);
 
@@ -66,8 +89,70 @@ pub async fn update(
     Ok(deployment)
 }
 
-pub fn new(actor: &Actor, pod: PodSpec) -> Result<Deployment> {
-    let name = actor.name_any();
+/// Returns the hash of the spec last applied to the Deployment, None if it doesn't exist.
+pub async fn applied_hash(client: &Client, namespace: &str, name: &str) -> Result<Option<String>> {
+    let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    let deployment = api.get_opt(name).await.map_err(Error::KubeError)?;
+
+    Ok(deployment.map(|d| d.annotations().get(LAST_APPLIED_HASH_KEY).cloned().unwrap_or_default()))
+}
+
+pub async fn delete(client: &Client, namespace: &str, name: &str) -> Result<()> {
+    let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    api.delete(name, &DeleteParams::default()).await.map_err(Error::KubeError)?;
+    info!("Deleted Deployment {}", name);
+
+    Ok(())
+}
+
+/// List all the Deployments managed by Amphitheatre in the namespace.
+pub async fn list(client: &Client, namespace: &str) -> Result<Vec<Deployment>> {
+    let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    let params = ListParams::default().labels("app.kubernetes.io/managed-by=Amphitheatre");
+    let deployments = api.list(&params).await.map_err(Error::KubeError)?;
+
+    Ok(deployments.items)
+}
+
+/// Scale the Deployment to the given number of replicas.
+pub async fn scale(client: &Client, namespace: &str, name: &str, replicas: i32) -> Result<Deployment> {
+    let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+
+    let patch = json!({"spec": { "replicas": replicas }});
+    let deployment = api.patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Scaled Deployment {} to {} replicas", deployment.name_any(), replicas);
+
+    Ok(deployment)
+}
+
+/// Set the image of the container of Deployment, it is kept until the
+/// Deployment is updated by a new spec of actor.
+pub async fn set_image(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+    container: &str,
+    image: &str,
+) -> Result<Deployment> {
+    let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+
+    let patch = json!({"spec": { "template": { "spec": { "containers": [{ "name": container, "image": image }] } } }});
+    let params = PatchParams::default();
+    let deployment = api.patch(name, &params, &Patch::Strategic(&patch)).await.map_err(Error::KubeError)?;
+    info!("Set the image of Deployment {} to {}", deployment.name_any(), image);
+
+    Ok(deployment)
+}
+
+/// Returns the name of the Deployment of actor, the same as its Service.
+pub fn name(actor: &Actor) -> String {
+    naming::name(&[&actor.name_any()])
+}
+
+/// Build the Deployment of actor, the hash identifies the applied spec and
+/// the resolved env sets to detect the changes on update.
+pub fn new(actor: &Actor, pod: PodSpec, hash: String) -> Result<Deployment> {
+    let name = self::name(actor);
 
     // Build the metadata for the deployment
     let owner_reference = actor.controller_owner_ref(&()).unwrap();
@@ -75,7 +160,7 @@ pub fn new(actor: &Actor, pod: PodSpec) -> Result<Deployment> {
         ("amphitheatre.app/character".into(), name.clone()),
         ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
     ]);
-    let annotations = BTreeMap::from([(LAST_APPLIED_HASH_KEY.into(), hash(&actor.spec)?)]);
+    let annotations = BTreeMap::from([(LAST_APPLIED_HASH_KEY.into(), hash)]);
     let metadata = ObjectMeta {
         name: Some(name),
         owner_references: Some(vec![owner_reference]),
@@ -85,10 +170,13 @@ pub fn new(actor: &Actor, pod: PodSpec) -> Result<Deployment> {
     };
 
     // Build the spec for the deployment
+    let mut pod_labels = labels.clone();
+    pod_labels.extend(actor::pod_labels(actor));
     let spec = DeploymentSpec {
+        replicas: replicas::desired(actor),
         selector: LabelSelector { match_labels: Some(labels.clone()), ..Default::default() },
         template: PodTemplateSpec {
-            metadata: Some(ObjectMeta { labels: Some(labels.clone()), ..Default::default() }),
+            metadata: Some(ObjectMeta { labels: Some(pod_labels), ..Default::default() }),
             spec: Some(pod),
         },
         ..Default::default()
@@ -97,3 +185,132 @@ pub fn new(actor: &Actor, pod: PodSpec) -> Result<Deployment> {
     // Build and return the deployment resource
     Ok(Deployment { metadata, spec: Some(spec), ..Default::default() })
 }
+
+/// Returns the hash of the spec of actor and the resources of its pods, only
+/// the declared ones are hashed to keep the hashes of the existing Deployments.
+pub fn digest(
+    actor: &Actor,
+    env: &[EnvVar],
+    volumes: &[Volume],
+    containers: &Containers,
+    probes: &Probes,
+) -> Result<String> {
+    match (env.is_empty(), volumes.is_empty(), containers.is_empty(), probes.is_empty()) {
+        (true, true, true, true) => hash(&actor.spec),
+        (false, true, true, true) => hash(&(&actor.spec, env)),
+        (_, _, true, true) => hash(&(&actor.spec, env, volumes)),
+        (_, _, _, true) => hash(&(&actor.spec, env, volumes, containers)),
+        _ => hash(&(&actor.spec, env, volumes, containers, probes)),
+    }
+}
+
+/// Render the pod of actor along with the hash of everything it's built from,
+/// the changes of any of them are rolled out. The dev container takes the place
+/// of the application container, so the debugger and the reload hook don't
+/// apply to it.
+#[allow(clippy::too_many_arguments)]
+pub fn render(
+    actor: &Actor,
+    ports: &[ExposedPort],
+    env: Vec<EnvVar>,
+    shared_secrets: &BTreeMap<String, ByteString>,
+    volumes: &[Volume],
+    containers: &Containers,
+    probes: &Probes,
+) -> Result<(PodSpec, String)> {
+    let expected_hash = digest(actor, &env, volumes, containers, probes)?;
+
+    // The env vars only reference the copied secret values, rotating them is a change as well.
+    let expected_hash = match shared_secrets.is_empty() {
+        true => expected_hash,
+        false => hash(&(expected_hash, shared_secrets))?,
+    };
+    let mut pod = self::pod(actor, ports, env, volumes, containers, probes);
+
+    // The built image is deployed by its digest, a rebuild of the same tag is a change as well.
+    let image = image::deployed(actor);
+    let expected_hash = match image != actor.spec.image {
+        true => hash(&(expected_hash, image))?,
+        false => expected_hash,
+    };
+
+    // The image pull secrets of its own are not a part of the spec, changing them is a change as well.
+    let secrets = secret::pull_secrets(actor);
+    let expected_hash = match secrets.is_empty() {
+        true => expected_hash,
+        false => hash(&(expected_hash, secrets))?,
+    };
+
+    // Neither is the dev container.
+    let devcontainer = devcontainer::devcontainer(actor)?;
+    let expected_hash = match &devcontainer {
+        Some(devcontainer) => {
+            devcontainer.apply(actor, &mut pod)?;
+            hash(&(expected_hash, devcontainer))?
+        }
+        None => expected_hash,
+    };
+
+    // The debugger is not a part of the spec, switching it is a change as well.
+    let expected_hash = match debug::debugger(actor)?.filter(|_| devcontainer.is_none()) {
+        Some(debugger) => {
+            debugger.apply(&mut pod);
+            hash(&(expected_hash, debugger))?
+        }
+        None => expected_hash,
+    };
+
+    // The files of the live actor are synced into its pods for the reload hook.
+    let expected_hash = match reload::hook(actor)?.filter(|_| actor.spec.live && devcontainer.is_none()) {
+        Some(hook) => {
+            hook.apply(actor, &mut pod)?;
+            hash(&(expected_hash, hook))?
+        }
+        None => expected_hash,
+    };
+
+    Ok((pod, expected_hash))
+}
+
+/// Build the pod of actor, with the env vars of the env sets, its volumes,
+/// sidecars and probes, and the ports inferred from the image if not declared.
+pub fn pod(
+    actor: &Actor,
+    ports: &[ExposedPort],
+    env: Vec<EnvVar>,
+    volumes: &[Volume],
+    containers: &Containers,
+    probes: &Probes,
+) -> PodSpec {
+    let mut container = application::container(&actor.spec);
+    container.image = Some(image::deployed(actor));
+
+    // The env vars declared by the character override the ones of the env sets.
+    let declared = container.env.get_or_insert_with(Vec::new);
+    let shared: Vec<EnvVar> = env.into_iter().filter(|var| declared.iter().all(|d| d.name != var.name)).collect();
+    declared.extend(shared);
+
+    // Default the container ports and readiness probe to the inferred ports.
+    if let Some(port) = ports.first() {
+        container.ports = Some(ports.iter().map(|port| port.container_port()).collect());
+        if port.protocol == "TCP" {
+            container.readiness_probe = Some(Probe {
+                tcp_socket: Some(TCPSocketAction { port: IntOrString::Int(port.port), ..Default::default() }),
+                ..Default::default()
+            });
+        }
+    }
+    probes.apply(&mut container);
+
+    let mut pod = PodSpec { image_pull_secrets: Some(secret::image_pull_secrets(actor)), ..Default::default() };
+    if !volumes.is_empty() {
+        let (volumes, mounts) = volume::mounts(actor, volumes);
+        container.volume_mounts.get_or_insert_with(Vec::new).extend(mounts);
+        pod.volumes = Some(volumes);
+    }
+    if !containers.is_empty() {
+        pod.init_containers = Some(containers.build());
+    }
+
+    PodSpec { containers: vec![container], ..pod }
+}
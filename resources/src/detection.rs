@@ -0,0 +1,182 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::Actor;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::{Client, Resource, ResourceExt};
+
+use crate::actor;
+use crate::error::{Error, Result};
+
+/// The annotation of actor choosing its builder over the build method of its
+/// spec, `auto` detects it from the files of its repository, and `nixpacks`
+/// builds with the language detection of Nixpacks.
+pub const BUILD_METHOD_ANNOTATION: &str = "amphitheatre.app/build-method";
+
+/// The values of the build method annotation.
+pub const BUILD_METHODS: &[&str] = &["auto", "nixpacks"];
+
+/// The condition type of actor recording the detected build method in its reason.
+pub const BUILD_METHOD_CONDITION_TYPE: &str = "BuildMethodDetected";
+
+/// The files looked up in the build context of the repository, the ones
+/// after `Dockerfile` are recognized by the Buildpacks.
+pub const CANDIDATES: &[&str] = &[
+    "Dockerfile",
+    "project.toml",
+    "Procfile",
+    "go.mod",
+    "package.json",
+    "pom.xml",
+    "build.gradle",
+    "requirements.txt",
+    "pyproject.toml",
+    "Gemfile",
+    "composer.json",
+    "Cargo.toml",
+];
+
+/// How the actor is built, detected from its repository.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decision {
+    /// Build with the Dockerfile by Kaniko.
+    Dockerfile,
+    /// Build with the Buildpacks lifecycle.
+    Buildpacks,
+    /// Deploy the prebuilt image, there is no repository to build.
+    Skipped,
+    /// None of the files is recognized, built by the method of its spec.
+    Unrecognized,
+}
+
+impl Decision {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Decision::Dockerfile => "Dockerfile",
+            Decision::Buildpacks => "Buildpacks",
+            Decision::Skipped => "Skipped",
+            Decision::Unrecognized => "Unrecognized",
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            Decision::Dockerfile => "Found a Dockerfile, built with Kaniko",
+            Decision::Buildpacks => "Found a Buildpacks compatible project, built with the Buildpacks lifecycle",
+            Decision::Skipped => "Deployed the prebuilt image without a repository",
+            Decision::Unrecognized => "None of the files is recognized, built by the method of its spec",
+        }
+    }
+}
+
+/// Returns true if the builder of actor is detected from its repository.
+pub fn auto(actor: &Actor) -> bool {
+    actor.annotations().get(BUILD_METHOD_ANNOTATION).is_some_and(|value| value == "auto")
+}
+
+/// Returns true if the actor is built with Nixpacks.
+pub fn nixpacks(actor: &Actor) -> bool {
+    actor.annotations().get(BUILD_METHOD_ANNOTATION).is_some_and(|value| value == "nixpacks")
+}
+
+/// Decide how to build the actor from the candidate files found in its repository.
+pub fn decide(source: bool, files: &[&str]) -> Decision {
+    if !source {
+        return Decision::Skipped;
+    }
+    if files.contains(&"Dockerfile") {
+        return Decision::Dockerfile;
+    }
+    if files.iter().any(|file| CANDIDATES.contains(file)) {
+        return Decision::Buildpacks;
+    }
+
+    Decision::Unrecognized
+}
+
+/// Returns the decision recorded for the current generation of actor, if any.
+pub fn decided(actor: &Actor) -> Option<Decision> {
+    let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    decision(conditions, actor.meta().generation)
+}
+
+fn decision(conditions: &[Condition], generation: Option<i64>) -> Option<Decision> {
+    let condition = conditions.iter().find(|c| c.type_ == BUILD_METHOD_CONDITION_TYPE)?;
+    if condition.observed_generation != generation {
+        return None;
+    }
+
+    let decisions = [Decision::Dockerfile, Decision::Buildpacks, Decision::Skipped, Decision::Unrecognized];
+    decisions.into_iter().find(|decision| decision.reason() == condition.reason)
+}
+
+/// Record the decision in the status of actor, it's read again as the
+/// conditions may have been replaced by a state transition.
+pub async fn record(client: &Client, actor: &Actor, decision: Decision) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let actor = actor::get(client, &namespace, &actor.name_any()).await?;
+
+    actor::upsert_condition(client, &actor, condition(decision, actor.meta().generation)).await
+}
+
+fn condition(decision: Decision, generation: Option<i64>) -> Condition {
+    Condition {
+        type_: BUILD_METHOD_CONDITION_TYPE.into(),
+        status: "True".into(),
+        reason: decision.reason().into(),
+        message: decision.message().into(),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: generation,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_decide() {
+        assert_eq!(decide(false, &[]), Decision::Skipped);
+        assert_eq!(decide(true, &["Dockerfile", "go.mod"]), Decision::Dockerfile);
+        assert_eq!(decide(true, &["project.toml"]), Decision::Buildpacks);
+        assert_eq!(decide(true, &["package.json"]), Decision::Buildpacks);
+        assert_eq!(decide(true, &[]), Decision::Unrecognized);
+    }
+
+    #[test]
+    fn test_decision() {
+        assert_eq!(decision(&[], Some(2)), None);
+
+        let conditions = vec![condition(Decision::Buildpacks, Some(2))];
+        assert_eq!(decision(&conditions, Some(2)), Some(Decision::Buildpacks));
+        assert_eq!(decision(&conditions, Some(3)), None);
+    }
+
+    #[test]
+    fn test_auto() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert!(!auto(&actor));
+
+        actor.annotations_mut().insert(BUILD_METHOD_ANNOTATION.into(), "auto".into());
+        assert!(auto(&actor));
+        assert!(!nixpacks(&actor));
+
+        actor.annotations_mut().insert(BUILD_METHOD_ANNOTATION.into(), "nixpacks".into());
+        assert!(!auto(&actor));
+        assert!(nixpacks(&actor));
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::{
+    ContainerPort, EnvVar, PodSpec, Probe, Secret, SecretVolumeSource, TCPSocketAction, Volume, VolumeMount,
+};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::api::PostParams;
+use kube::core::ObjectMeta;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use ssh_key::rand_core::OsRng;
+use ssh_key::{Algorithm, LineEnding, PrivateKey};
+use tracing::{debug, info};
+
+use crate::containers::devcontainer::DEFAULT_DEVCONTAINER_IMAGE;
+use crate::containers::{git_sync, syncer, workspace_mount, workspace_volume, WORKSPACE_DIR};
+use crate::error::{Error, Result};
+use crate::{naming, upload};
+
+/// The annotation of actor running it as a long-lived development container
+/// as a JSON document, e.g. `{"image": "rust:1.78", "authorized_keys": ["ssh-ed25519 AAAA..."]}`.
+/// The playbook switches on the dev container of a character with the
+/// annotation suffixed by its name, e.g. `amphitheatre.app/devcontainer.web`.
+pub const DEVCONTAINER_ANNOTATION: &str = "amphitheatre.app/devcontainer";
+
+/// The name of the container port the SSH server listens on.
+pub const SSH_PORT_NAME: &str = "ssh";
+
+/// Not the privileged port 22, the SSH server may be run by a non-root user.
+const DEFAULT_SSH_PORT: i32 = 2222;
+
+/// The suffix of the Secret keeping the SSH host key of the dev container, so
+/// that its identity survives the restarts of the pods.
+const HOST_KEYS_SUFFIX: &str = "ssh-host-keys";
+
+/// The name of the SSH host key in the Secret.
+const HOST_KEY: &str = "ssh_host_ed25519_key";
+
+/// The directory the Secret of the SSH host key is mounted at.
+const HOST_KEYS_DIR: &str = "/etc/amp/ssh";
+
+/// Authorize the keys and start the SSH server of the image in the background,
+/// it's not installed on every start. The host key is copied out of the Secret,
+/// as the SSH server refuses the keys readable by others. The container exits if
+/// the SSH server is missing or fails to start, it's ready once it's listening.
+const SSH_SCRIPT: &str = r#"SUDO=$(command -v sudo || true)
+SSHD=$(command -v sshd || echo /usr/sbin/sshd)
+if [ ! -x "$SSHD" ]; then
+  echo "The SSH server is not found, install openssh-server in the image" >&2
+  exit 1
+fi
+mkdir -p "$HOME/.ssh" && chmod 700 "$HOME/.ssh"
+printf '%s\n' "$AMP_AUTHORIZED_KEYS" > "$HOME/.ssh/authorized_keys" && chmod 600 "$HOME/.ssh/authorized_keys"
+HOST_KEY="$HOME/.ssh/ssh_host_ed25519_key"
+cp "$AMP_SSH_HOST_KEY" "$HOST_KEY" && chmod 600 "$HOST_KEY"
+$SUDO mkdir -p /run/sshd
+$SUDO "$SSHD" -p "$AMP_SSH_PORT" -h "$HOST_KEY" -o PasswordAuthentication=no || {
+  echo "Failed to start the SSH server" >&2
+  exit 1
+}
+exec sleep infinity
+"#;
+
+/// The development container run in place of the application of actor.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct DevContainer {
+    /// The image with the toolchain, the universal image of the dev containers if not set.
+    pub image: Option<String>,
+    /// The public keys allowed to log in by SSH, the SSH server of the image is not started without them.
+    #[serde(default)]
+    pub authorized_keys: Vec<String>,
+    /// The port the SSH server listens on, `2222` if not set.
+    pub port: Option<i32>,
+}
+
+impl DevContainer {
+    /// Returns the port the SSH server listens on.
+    pub fn port(&self) -> i32 {
+        self.port.unwrap_or(DEFAULT_SSH_PORT)
+    }
+
+    /// Returns true if the SSH server is started in the dev container.
+    pub fn ssh(&self) -> bool {
+        !self.authorized_keys.is_empty()
+    }
+
+    /// Replace the application container of the pod with the dev container,
+    /// it sleeps instead of running the application, and the probes are
+    /// removed. The source is mounted at the workspace, kept in sync by the
+    /// syncer for the live actor, or cloned once from its repository.
+    pub fn apply(&self, actor: &Actor, pod: &mut PodSpec) -> Result<()> {
+        let mut volumes = vec![workspace_volume()];
+        let (mut init, mut sidecar) = (None, None);
+        if actor.spec.live {
+            sidecar = Some(syncer::sidecar(actor, &workspace_volume().name)?);
+        } else if let Some(id) = upload::of(&actor.spec) {
+            init = Some(syncer::upload(actor, id, &None)?);
+        } else if actor.spec.source.is_some() {
+            init = Some(git_sync::container(actor));
+            volumes.push(git_sync::source_volume());
+            volumes.extend(git_sync::ssh_key_volume(actor));
+        }
+
+        let Some(container) = pod.containers.first_mut() else {
+            return Ok(());
+        };
+        container.image = Some(self.image.clone().unwrap_or_else(|| DEFAULT_DEVCONTAINER_IMAGE.into()));
+        container.args = None;
+        container.liveness_probe = None;
+        container.readiness_probe = None;
+        container.startup_probe = None;
+        container.working_dir = Some(WORKSPACE_DIR.into());
+        container.volume_mounts.get_or_insert_with(Vec::new).push(workspace_mount());
+
+        if self.ssh() {
+            container.command = Some(vec!["/bin/sh".into(), "-c".into(), SSH_SCRIPT.into()]);
+            let vars = [
+                ("AMP_SSH_PORT", self.port().to_string()),
+                ("AMP_AUTHORIZED_KEYS", self.authorized_keys.join("\n")),
+                ("AMP_SSH_HOST_KEY", format!("{}/{}", HOST_KEYS_DIR, HOST_KEY)),
+            ];
+            let declared = container.env.get_or_insert_with(Vec::new);
+            declared.retain(|var| vars.iter().all(|(name, _)| name != &var.name));
+            declared.extend(vars.map(|(name, value)| EnvVar {
+                name: name.into(),
+                value: Some(value),
+                value_from: None,
+            }));
+            container.ports.get_or_insert_with(Vec::new).push(ContainerPort {
+                name: Some(SSH_PORT_NAME.into()),
+                container_port: self.port(),
+                protocol: Some("TCP".into()),
+                ..Default::default()
+            });
+            container.readiness_probe = Some(Probe {
+                tcp_socket: Some(TCPSocketAction { port: IntOrString::Int(self.port()), ..Default::default() }),
+                period_seconds: Some(5),
+                ..Default::default()
+            });
+            container.volume_mounts.get_or_insert_with(Vec::new).push(VolumeMount {
+                name: HOST_KEYS_SUFFIX.into(),
+                mount_path: HOST_KEYS_DIR.into(),
+                read_only: Some(true),
+                ..Default::default()
+            });
+            volumes.push(Volume {
+                name: HOST_KEYS_SUFFIX.into(),
+                secret: Some(SecretVolumeSource {
+                    secret_name: Some(host_keys_name(actor)),
+                    default_mode: Some(0o444),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+        } else {
+            container.command = Some(vec!["sleep".into(), "infinity".into()]);
+        }
+
+        pod.volumes.get_or_insert_with(Vec::new).extend(volumes);
+        if let Some(init) = init {
+            pod.init_containers.get_or_insert_with(Vec::new).push(init);
+        }
+        pod.containers.extend(sidecar);
+
+        Ok(())
+    }
+}
+
+/// Returns the dev container of actor, if it's run as one.
+pub fn devcontainer(actor: &Actor) -> Result<Option<DevContainer>> {
+    let Some(content) = actor.annotations().get(DEVCONTAINER_ANNOTATION) else {
+        return Ok(None);
+    };
+    let devcontainer: DevContainer = serde_json::from_str(content).map_err(Error::SerializationError)?;
+    if !(1..=65535).contains(&devcontainer.port()) {
+        return Err(Error::InvalidDevContainer(format!("invalid SSH port {}", devcontainer.port())));
+    }
+    if devcontainer.authorized_keys.iter().any(|key| key.trim().is_empty() || key.contains('\n')) {
+        return Err(Error::InvalidDevContainer("the authorized keys must be single lines".into()));
+    }
+
+    Ok(Some(devcontainer))
+}
+
+/// Returns the name of the Secret keeping the SSH host key of actor.
+fn host_keys_name(actor: &Actor) -> String {
+    naming::name(&[&actor.name_any(), HOST_KEYS_SUFFIX])
+}
+
+/// Create the Secret of the SSH host key of actor if it's not created yet,
+/// it's kept as long as the actor, and deleted along with it.
+pub async fn host_keys(client: &Client, actor: &Actor) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Secret> = Api::namespaced(client.clone(), &namespace);
+    let name = host_keys_name(actor);
+    if api.get_opt(&name).await.map_err(Error::KubeError)?.is_some() {
+        return Ok(());
+    }
+
+    let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519);
+    let key = key.map_err(|err| Error::InvalidDevContainer(err.to_string()))?;
+    let private = key.to_openssh(LineEnding::LF).map_err(|err| Error::InvalidDevContainer(err.to_string()))?;
+    let public = key.public_key().to_openssh().map_err(|err| Error::InvalidDevContainer(err.to_string()))?;
+    let resource = Secret {
+        metadata: ObjectMeta {
+            name: Some(name.clone()),
+            owner_references: actor.controller_owner_ref(&()).map(|owner| vec![owner]),
+            ..Default::default()
+        },
+        string_data: Some(BTreeMap::from([
+            (HOST_KEY.to_string(), private.to_string()),
+            (format!("{}.pub", HOST_KEY), public),
+        ])),
+        ..Default::default()
+    };
+
+    match api.create(&PostParams::default(), &resource).await {
+        Ok(_) => info!("Created the SSH host key of actor {}", actor.name_any()),
+        Err(kube::Error::Api(err)) if err.code == 409 => debug!("The SSH host key {} already exists", name),
+        Err(err) => return Err(Error::KubeError(err)),
+    }
+
+    Ok(())
+}
+
+/// Returns true if the actor is run as a dev container.
+pub fn developing(actor: &Actor) -> bool {
+    actor.annotations().contains_key(DEVCONTAINER_ANNOTATION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+    use k8s_openapi::api::core::v1::Container;
+
+    fn pod() -> PodSpec {
+        let container = Container {
+            name: "web".into(),
+            image: Some("web:latest".into()),
+            args: Some(vec!["--port=8080".into()]),
+            liveness_probe: Some(Probe::default()),
+            readiness_probe: Some(Probe::default()),
+            ..Default::default()
+        };
+        PodSpec { containers: vec![container], ..Default::default() }
+    }
+
+    #[test]
+    fn test_parse_devcontainer() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(devcontainer(&actor).unwrap(), None);
+        assert!(!developing(&actor));
+
+        actor.annotations_mut().insert(DEVCONTAINER_ANNOTATION.into(), r#"{"image": "rust:1.78"}"#.into());
+        let parsed = devcontainer(&actor).unwrap().unwrap();
+        assert_eq!(parsed.image.as_deref(), Some("rust:1.78"));
+        assert_eq!(parsed.port(), 2222);
+        assert!(!parsed.ssh());
+
+        actor.annotations_mut().insert(DEVCONTAINER_ANNOTATION.into(), r#"{"port": 70000}"#.into());
+        assert!(devcontainer(&actor).is_err());
+
+        let content = r#"{"authorized_keys": ["ssh-ed25519 AAAA\nssh-rsa BBBB"]}"#;
+        actor.annotations_mut().insert(DEVCONTAINER_ANNOTATION.into(), content.into());
+        assert!(devcontainer(&actor).is_err());
+    }
+
+    #[test]
+    fn test_apply_sleeping_devcontainer() {
+        let actor = Actor::new("web", ActorSpec::default());
+        let mut pod = pod();
+        DevContainer::default().apply(&actor, &mut pod).unwrap();
+
+        let container = &pod.containers[0];
+        assert_eq!(container.image.as_deref(), Some(DEFAULT_DEVCONTAINER_IMAGE));
+        assert_eq!(container.command, Some(vec!["sleep".to_string(), "infinity".to_string()]));
+        assert_eq!(container.args, None);
+        assert_eq!((&container.liveness_probe, &container.readiness_probe), (&None, &None));
+        assert_eq!(container.working_dir.as_deref(), Some("/workspace"));
+        assert_eq!(container.ports, None);
+        assert_eq!(pod.volumes.as_ref().unwrap()[0].name, "workspace");
+        assert_eq!(pod.init_containers, None);
+    }
+
+    #[test]
+    fn test_apply_ssh_devcontainer() {
+        let actor = Actor::new("web", ActorSpec::default());
+        let devcontainer = DevContainer {
+            image: Some("rust:1.78".into()),
+            authorized_keys: vec!["ssh-ed25519 AAAA".into(), "ssh-rsa BBBB".into()],
+            port: None,
+        };
+        let mut pod = pod();
+        devcontainer.apply(&actor, &mut pod).unwrap();
+
+        let container = &pod.containers[0];
+        assert_eq!(container.image.as_deref(), Some("rust:1.78"));
+        assert_eq!(container.command.as_ref().unwrap()[0], "/bin/sh");
+        let env = container.env.as_ref().unwrap();
+        assert_eq!(env[0].value.as_deref(), Some("2222"));
+        assert_eq!(env[1].value.as_deref(), Some("ssh-ed25519 AAAA\nssh-rsa BBBB"));
+        assert_eq!(env[2].value.as_deref(), Some("/etc/amp/ssh/ssh_host_ed25519_key"));
+        let port = &container.ports.as_ref().unwrap()[0];
+        assert_eq!((port.name.as_deref(), port.container_port), (Some(SSH_PORT_NAME), 2222));
+        let probe = container.readiness_probe.as_ref().unwrap().tcp_socket.as_ref().unwrap();
+        assert_eq!(probe.port, IntOrString::Int(2222));
+
+        let volume = pod.volumes.as_ref().unwrap().iter().find(|volume| volume.name == HOST_KEYS_SUFFIX).unwrap();
+        assert_eq!(volume.secret.as_ref().unwrap().secret_name.as_deref(), Some("web-ssh-host-keys"));
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::{Actor, ActorSpec, Playbook};
+use k8s_openapi::api::core::v1::{EnvVar, ServicePort};
+use kube::{Client, ResourceExt};
+use tracing::debug;
+
+use crate::actor::{self, PLAYBOOK_LABEL};
+use crate::error::{Error, Result};
+use crate::{namespace, naming, service};
+
+/// The annotation of actor holding the declared ports of the other characters
+/// of its playbook keyed by the names of their actors, as a JSON document.
+/// It's set from the playbook, so the addresses of the siblings don't depend
+/// on the order the actors are created in.
+pub const SIBLINGS_ANNOTATION: &str = "amphitheatre.app/siblings";
+
+/// Returns the declared ports of the other characters of playbook, keyed by
+/// the names of their actors.
+pub fn siblings(playbook: &Playbook, character: &str) -> BTreeMap<String, Vec<ServicePort>> {
+    let characters = playbook.spec.characters.iter().flatten().filter(|c| c.meta.name != character);
+    characters
+        .map(|c| {
+            let deploy = ActorSpec::from(c).character.deploy;
+            let ports = deploy.and_then(|deploy| deploy.service_ports()).unwrap_or_default();
+            (namespace::actor_name(playbook, &c.meta.name), ports)
+        })
+        .collect()
+}
+
+/// Resolve the env vars describing the in-namespace addresses of the sibling
+/// actors of the same playbook, so the characters can find each other
+/// without hardcoding their names. The siblings are the characters of the
+/// playbook, their ports inferred from the images are added once known.
+pub async fn resolve(client: &Client, actor: &Actor) -> Result<Vec<EnvVar>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let playbook = actor.labels().get(PLAYBOOK_LABEL);
+
+    let mut actors = actor::list(client, &namespace).await?;
+    actors.retain(|sibling| sibling.name_any() != actor.name_any() && sibling.labels().get(PLAYBOOK_LABEL) == playbook);
+
+    // The actors created before the siblings were recorded fall back to the existing ones.
+    let declared = actor.annotations().get(SIBLINGS_ANNOTATION);
+    let declared: Option<BTreeMap<String, Vec<ServicePort>>> =
+        declared.and_then(|content| serde_json::from_str(content).ok());
+    let siblings: Vec<(String, Vec<ServicePort>)> = match declared {
+        Some(declared) => declared
+            .into_iter()
+            .map(|(name, ports)| {
+                let found = actors.iter().find(|sibling| sibling.name_any() == name).map(service::ports);
+                (name, found.filter(|found| !found.is_empty()).unwrap_or(ports))
+            })
+            .collect(),
+        None => actors.iter().map(|sibling| (sibling.name_any(), service::ports(sibling))).collect(),
+    };
+
+    let env = env(&namespace, &siblings);
+    debug!("Resolved the addresses of the siblings of actor {}: {:?}", actor.name_any(), env);
+
+    Ok(env)
+}
+
+/// Build the env vars of the Services of the siblings in the namespace, in the
+/// form of the Kubernetes service links, e.g. `BACKEND_SERVICE_HOST` and
+/// `BACKEND_SERVICE_PORT` for the first port, and `BACKEND_SERVICE_PORT_HTTP`
+/// for each named port. The siblings without any port are left out.
+pub fn env(namespace: &str, siblings: &[(String, Vec<ServicePort>)]) -> Vec<EnvVar> {
+    let mut env = vec![];
+    for (name, ports) in siblings {
+        let Some(first) = ports.first() else {
+            continue;
+        };
+
+        let prefix = format!("{}_SERVICE", normalize(name));
+        let host = format!("{}.{}.svc", naming::name(&[name]), namespace);
+        env.push(var(format!("{}_HOST", prefix), host));
+        env.push(var(format!("{}_PORT", prefix), first.port.to_string()));
+        for port in ports {
+            if let Some(name) = &port.name {
+                env.push(var(format!("{}_PORT_{}", prefix, normalize(name)), port.port.to_string()));
+            }
+        }
+    }
+
+    env
+}
+
+/// Returns the name in the form of an env var name, e.g. `BACKEND_API` of `backend-api`.
+fn normalize(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect()
+}
+
+#[inline]
+fn var(name: String, value: String) -> EnvVar {
+    EnvVar { name, value: Some(value), value_from: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::image;
+
+    #[test]
+    fn test_env() {
+        let mut backend = Actor::new("backend-api", ActorSpec::default());
+        image::infer(&mut backend, &["8080/tcp"]);
+        let worker = Actor::new("worker", ActorSpec::default());
+
+        let siblings = [backend, worker].map(|sibling| (sibling.name_any(), service::ports(&sibling)));
+        let env = env("amp-playbook", &siblings);
+        let env: Vec<(String, String)> = env.into_iter().map(|var| (var.name, var.value.unwrap())).collect();
+        assert_eq!(
+            env,
+            vec![
+                ("BACKEND_API_SERVICE_HOST".into(), "backend-api.amp-playbook.svc".into()),
+                ("BACKEND_API_SERVICE_PORT".into(), "8080".into()),
+                ("BACKEND_API_SERVICE_PORT_TCP_8080".into(), "8080".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("backend-api"), "BACKEND_API");
+        assert_eq!(normalize("web.v2"), "WEB_V2");
+    }
+}
@@ -0,0 +1,359 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::{ConfigMap, EnvVar, EnvVarSource, Secret, SecretKeySelector};
+use k8s_openapi::ByteString;
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::error::{Error, Result};
+use crate::workspace::{DEFAULT_WORKSPACE, WORKSPACE_LABEL};
+use crate::{discovery, naming};
+
+/// The annotation of playbook or actor referencing the env sets by name,
+/// separated by commas. The playbook references the env sets of a single
+/// character with the annotation suffixed by its name, e.g.
+/// `amphitheatre.app/env-sets.web`.
+pub const ENV_SETS_ANNOTATION: &str = "amphitheatre.app/env-sets";
+
+/// The label of the ConfigMaps holding the env sets, the value is the name of env set.
+pub const ENV_SET_LABEL: &str = "amphitheatre.app/env-set";
+
+/// The label of the Secrets in the Amphitheatre namespace which can be
+/// referenced by the env sets, the value must be `true`. The other Secrets,
+/// e.g. the credentials of Amphitheatre itself, are never copied out.
+pub const SHARED_SECRET_LABEL: &str = "amphitheatre.app/env-set-shared";
+
+/// The key of the ConfigMap holding the env set as a JSON document.
+const ENV_SET_KEY: &str = "envset";
+
+/// The prefix of the Secret of actor holding the values of its secret refs.
+const ENV_SETS_SECRET: &str = "amp-env-sets";
+
+/// A named collection of env vars shared by the playbooks of a workspace.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct EnvSet {
+    pub name: String,
+    /// The workspace of the env set, only its playbooks can reference it.
+    #[serde(default = "default_workspace")]
+    pub workspace: String,
+    /// The plain env vars.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The env vars read from the shared Secrets in the Amphitheatre namespace.
+    #[serde(default)]
+    pub secrets: BTreeMap<String, SecretRef>,
+}
+
+/// The key of a Secret in the Amphitheatre namespace.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct SecretRef {
+    pub name: String,
+    pub key: String,
+}
+
+fn default_workspace() -> String {
+    DEFAULT_WORKSPACE.to_string()
+}
+
+/// Create or replace the env set in a ConfigMap of the given namespace,
+/// the referenced Secrets must be shared with the env sets.
+pub async fn apply(client: &Client, namespace: &str, set: &EnvSet) -> Result<()> {
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    for reference in set.secrets.values() {
+        shared(&secrets, &reference.name).await?;
+    }
+
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+
+    let name = name(&set.workspace, &set.name);
+    let content = serde_json::to_string(set).map_err(Error::SerializationError)?;
+    let resource = ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(name.clone()),
+            labels: Some(BTreeMap::from([
+                (ENV_SET_LABEL.into(), set.name.clone()),
+                (WORKSPACE_LABEL.into(), set.workspace.clone()),
+                ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+            ])),
+            ..Default::default()
+        },
+        data: Some(BTreeMap::from([(ENV_SET_KEY.into(), content)])),
+        ..Default::default()
+    };
+
+    let params = &PatchParams::apply("amp-apiserver");
+    api.patch(&name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+    info!("Applied env set {} of workspace {}", set.name, set.workspace);
+
+    Ok(())
+}
+
+/// Get the env set of the workspace from the given namespace.
+pub async fn get(client: &Client, namespace: &str, workspace: &str, name: &str) -> Result<Option<EnvSet>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+
+    match api.get_opt(&self::name(workspace, name)).await.map_err(Error::KubeError)? {
+        Some(config_map) => from(&config_map).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// List the env sets of the workspace in the given namespace.
+pub async fn list(client: &Client, namespace: &str, workspace: &str) -> Result<Vec<EnvSet>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let params = ListParams::default().labels(&format!("{},{}={}", ENV_SET_LABEL, WORKSPACE_LABEL, workspace));
+
+    api.list(&params).await.map_err(Error::KubeError)?.items.iter().map(from).collect()
+}
+
+/// Delete the env set of the workspace, the deployed actors keep their env vars until redeployed.
+pub async fn delete(client: &Client, namespace: &str, workspace: &str, name: &str) -> Result<()> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    api.delete(&self::name(workspace, name), &DeleteParams::default()).await.map_err(Error::KubeError)?;
+    info!("Deleted env set {} of workspace {}", name, workspace);
+
+    Ok(())
+}
+
+/// Returns the names of the env sets referenced by the actor, in order.
+pub fn names(actor: &Actor) -> Vec<String> {
+    let value = actor.annotations().get(ENV_SETS_ANNOTATION).map(String::as_str).unwrap_or_default();
+    value.split(',').map(str::trim).filter(|name| !name.is_empty()).map(String::from).collect()
+}
+
+/// Resolve the env vars of the env sets referenced by the actor, the later
+/// sets override the earlier ones. The values of the secret refs are copied
+/// into a Secret next to the actor, as pods can only read the Secrets of
+/// their own namespace, and returned along with the env vars, so that the
+/// rotation of them can be rolled out as well.
+pub async fn resolve(
+    client: &Client,
+    namespace: &str,
+    actor: &Actor,
+) -> Result<(Vec<EnvVar>, BTreeMap<String, ByteString>)> {
+    let (env, data) = lookup(client, namespace, actor).await?;
+    if !data.is_empty() {
+        copy_secret(client, actor, data.clone()).await?;
+    }
+
+    Ok((env, data))
+}
+
+/// Resolve the env vars of the env sets referenced by the actor, without
+/// copying the values of the secret refs.
+async fn lookup(
+    client: &Client,
+    namespace: &str,
+    actor: &Actor,
+) -> Result<(Vec<EnvVar>, BTreeMap<String, ByteString>)> {
+    let names = names(actor);
+    if names.is_empty() {
+        return Ok((vec![], BTreeMap::new()));
+    }
+
+    let workspace = actor.labels().get(WORKSPACE_LABEL).cloned().unwrap_or_else(default_workspace);
+    let mut sets = vec![];
+    for name in &names {
+        let set = get(client, namespace, &workspace, name).await?;
+        sets.push(set.ok_or_else(|| Error::EnvSetNotFound(name.clone()))?);
+    }
+
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let secret_name = self::secret_name(actor);
+    let mut env = BTreeMap::new();
+    let mut data = BTreeMap::new();
+    for set in sets {
+        for (key, value) in set.env {
+            env.insert(key, EnvVar { name: String::new(), value: Some(value), value_from: None });
+        }
+        for (key, reference) in set.secrets {
+            let secret = shared(&secrets, &reference.name).await?;
+            let value = secret.data.and_then(|mut data| data.remove(&reference.key));
+            let value = value.ok_or_else(|| Error::MissingObjectKey(".data"))?;
+            let copied = secret_key(&set.name, &key);
+            data.insert(copied.clone(), value);
+
+            let selector = SecretKeySelector { name: Some(secret_name.clone()), key: copied, optional: None };
+            let source = EnvVarSource { secret_key_ref: Some(selector), ..Default::default() };
+            env.insert(key, EnvVar { name: String::new(), value: None, value_from: Some(source) });
+        }
+    }
+
+    let env: Vec<EnvVar> = env.into_iter().map(|(name, var)| EnvVar { name, ..var }).collect();
+    debug!("Resolved env sets {:?} of actor {}: {:?}", names, actor.name_any(), env);
+
+    Ok((env, data))
+}
+
+/// Resolve the env vars deployed along with the actor: the addresses of the
+/// sibling actors, overridden by the env vars of its env sets. The copied
+/// secret values are returned as well, the same as [`resolve`].
+pub async fn environment(
+    client: &Client,
+    namespace: &str,
+    actor: &Actor,
+) -> Result<(Vec<EnvVar>, BTreeMap<String, ByteString>)> {
+    let (shared, secrets) = resolve(client, namespace, actor).await?;
+    let env = siblings(client, actor, shared).await?;
+
+    Ok((env, secrets))
+}
+
+/// Resolve the env vars the same as [`environment`] for a dry-run, nothing
+/// is copied into the namespace of actor.
+pub async fn preview(
+    client: &Client,
+    namespace: &str,
+    actor: &Actor,
+) -> Result<(Vec<EnvVar>, BTreeMap<String, ByteString>)> {
+    let (shared, secrets) = lookup(client, namespace, actor).await?;
+    let env = siblings(client, actor, shared).await?;
+
+    Ok((env, secrets))
+}
+
+/// Returns the addresses of the sibling actors overridden by the shared env vars.
+async fn siblings(client: &Client, actor: &Actor, shared: Vec<EnvVar>) -> Result<Vec<EnvVar>> {
+    let mut env = discovery::resolve(client, actor).await?;
+    env.retain(|var| shared.iter().all(|s| s.name != var.name));
+    env.extend(shared);
+
+    Ok(env)
+}
+
+/// Get the Secret, it must be labelled as shared with the env sets.
+async fn shared(api: &Api<Secret>, name: &str) -> Result<Secret> {
+    let secret = api.get_opt(name).await.map_err(Error::KubeError)?;
+    secret.filter(is_shared).ok_or_else(|| Error::SecretNotShared(name.to_string()))
+}
+
+#[inline]
+fn is_shared(secret: &Secret) -> bool {
+    secret.labels().get(SHARED_SECRET_LABEL).is_some_and(|value| value == "true")
+}
+
+/// Copy the secret values into the Secret of actor in its namespace, the
+/// values of the env sets it doesn't reference anymore are removed.
+async fn copy_secret(client: &Client, actor: &Actor, data: BTreeMap<String, ByteString>) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Secret> = Api::namespaced(client.clone(), &namespace);
+    let name = secret_name(actor);
+
+    match api.get_opt(&name).await.map_err(Error::KubeError)? {
+        Some(mut secret) => {
+            if secret.data.as_ref() == Some(&data) {
+                return Ok(());
+            }
+            secret.data = Some(data);
+            api.replace(&name, &PostParams::default(), &secret).await.map_err(Error::KubeError)?;
+            debug!("Replaced the secret values of the env sets of actor {}", actor.name_any());
+        }
+        None => {
+            let resource = Secret {
+                metadata: ObjectMeta {
+                    name: Some(name.clone()),
+                    owner_references: actor.controller_owner_ref(&()).map(|owner| vec![owner]),
+                    ..Default::default()
+                },
+                data: Some(data),
+                ..Default::default()
+            };
+            api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+            debug!("Copied the secret values of the env sets of actor {}", actor.name_any());
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the name of the Secret of actor holding the values of its secret refs.
+#[inline]
+fn secret_name(actor: &Actor) -> String {
+    naming::name(&[ENV_SETS_SECRET, &actor.name_any()])
+}
+
+/// Returns the key of the secret value of the env var in the Secret of actor,
+/// namespaced by the env set, the env sets may declare the same env vars.
+#[inline]
+fn secret_key(set: &str, key: &str) -> String {
+    format!("{}.{}", set, key)
+}
+
+fn from(config_map: &ConfigMap) -> Result<EnvSet> {
+    let content = config_map.data.as_ref().and_then(|data| data.get(ENV_SET_KEY));
+    let content = content.ok_or(Error::MissingObjectKey(".data.envset"))?;
+
+    serde_json::from_str(content).map_err(Error::SerializationError)
+}
+
+#[inline]
+fn name(workspace: &str, name: &str) -> String {
+    naming::name(&["amp-envset", workspace, name])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_names() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert!(names(&actor).is_empty());
+
+        actor.annotations_mut().insert(ENV_SETS_ANNOTATION.into(), "telemetry, shared-keys,".into());
+        assert_eq!(names(&actor), vec!["telemetry".to_string(), "shared-keys".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_env_set() {
+        let set: EnvSet = serde_json::from_str(
+            r#"{"name": "telemetry", "env": {"OTEL_ENDPOINT": "http://otel:4317"},
+                "secrets": {"API_KEY": {"name": "shared", "key": "api-key"}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(set.workspace, DEFAULT_WORKSPACE);
+        assert_eq!(set.env["OTEL_ENDPOINT"], "http://otel:4317");
+        assert_eq!(set.secrets["API_KEY"], SecretRef { name: "shared".into(), key: "api-key".into() });
+    }
+
+    #[test]
+    fn test_secret_key() {
+        let actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(secret_name(&actor), "amp-env-sets-web");
+        assert_eq!(secret_key("telemetry", "API_KEY"), "telemetry.API_KEY");
+        assert_ne!(secret_key("telemetry", "API_KEY"), secret_key("payments", "API_KEY"));
+    }
+
+    #[test]
+    fn test_is_shared() {
+        let mut secret = Secret::default();
+        assert!(!is_shared(&secret));
+
+        secret.labels_mut().insert(SHARED_SECRET_LABEL.into(), "false".into());
+        assert!(!is_shared(&secret));
+
+        secret.labels_mut().insert(SHARED_SECRET_LABEL.into(), "true".into());
+        assert!(is_shared(&secret));
+    }
+}
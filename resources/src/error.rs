@@ -34,6 +34,9 @@ pub enum Error {
     #[error("Metrics not available at the moment")]
     MetricsNotAvailable,
 
+    #[error("MetricsError: {0}")]
+    MetricsError(#[source] anyhow::Error),
+
     #[error("Unknown Syncer: {0}")]
     UnknownSyncer(String),
 
@@ -54,6 +57,144 @@ pub enum Error {
 
     #[error("ClusterStoreNotReady")]
     ClusterStoreNotReady,
+
+    #[error("Unknown SBOM Format: {0}")]
+    UnknownSbomFormat(String),
+
+    #[error("Unknown Signing Mode: {0}")]
+    UnknownSigningMode(String),
+
+    #[error("Signing Job {0} failed")]
+    SigningFailed(String),
+
+    #[error("Invalid Exposed Port: {0}")]
+    InvalidExposedPort(String),
+
+    #[error("ImageInspectError: {0}")]
+    ImageInspectError(#[source] anyhow::Error),
+
+    #[error("Helm release Job {0} failed")]
+    HelmReleaseFailed(String),
+
+    #[error("TomlDeserializeError: {0}")]
+    TomlDeserializeError(#[source] toml::de::Error),
+
+    #[error("TomlSerializationError: {0}")]
+    TomlSerializationError(#[source] toml::ser::Error),
+
+    #[error("Base image build Job {0} failed")]
+    BaseBuildFailed(String),
+
+    #[error("Invalid Base Image: {0}")]
+    InvalidBase(String),
+
+    #[error("Image {0} is not allowed by the registry policy")]
+    ImageNotAllowed(String),
+
+    #[error("Env set {0} was not found")]
+    EnvSetNotFound(String),
+
+    #[error("Secret {0} is not shared with the env sets")]
+    SecretNotShared(String),
+
+    #[error("Secret {0} is neither owned by nor labelled for the playbook")]
+    VolumeSecretNotAllowed(String),
+
+    #[error("SecretProviderError: {0}")]
+    SecretProviderError(#[source] anyhow::Error),
+
+    #[error("RegistryTokenError: {0}")]
+    RegistryTokenError(#[source] anyhow::Error),
+
+    #[error("Invalid Exposure: {0}")]
+    InvalidExposure(String),
+
+    #[error("Invalid Toleration: {0}")]
+    InvalidToleration(String),
+
+    #[error("Invalid Builder: {0}")]
+    InvalidBuilder(String),
+
+    #[error("Namespace {0} was not found")]
+    NamespaceNotFound(String),
+
+    #[error("Cluster {0} is not registered")]
+    ClusterNotFound(String),
+
+    #[error("KubeconfigError: {0}")]
+    KubeconfigError(#[source] kube::config::KubeconfigError),
+
+    #[error("Invalid ArgoCD Application: {0}")]
+    InvalidArgoCd(String),
+
+    #[error("Invalid Playbook Bundle: {0}")]
+    InvalidBundle(String),
+
+    #[error("Invalid Debugger: {0}")]
+    InvalidDebugger(String),
+
+    #[error("Invalid Dev Container: {0}")]
+    InvalidDevContainer(String),
+
+    #[error("Invalid Promotion: {0}")]
+    InvalidPromotion(String),
+
+    #[error("Invalid Reload Hook: {0}")]
+    InvalidReloadHook(String),
+
+    #[error("Invalid Repository Address: {0}")]
+    InvalidRepositoryAddress(String),
+
+    #[error("Invalid Log Filter: {0}")]
+    InvalidLogFilter(#[source] tracing_subscriber::filter::ParseError),
+
+    #[error("Unknown Log Format: {0}")]
+    InvalidLogFormat(String),
+
+    #[error("LogReloadError: {0}")]
+    LogReloadError(#[source] tracing_subscriber::reload::Error),
+
+    #[error("TelemetryError: {0}")]
+    TelemetryError(#[source] opentelemetry::trace::TraceError),
+
+    #[error("YamlSerializationError: {0}")]
+    YamlSerializationError(#[source] serde_yaml::Error),
+
+    #[error("Unknown Artifact Backend: {0}")]
+    UnknownArtifactBackend(String),
+
+    #[error("Unknown Artifact Kind: {0}")]
+    UnknownArtifactKind(String),
+
+    #[error("Invalid Artifact Digest: {0}")]
+    InvalidArtifactDigest(String),
+
+    #[error("ArtifactError: {0}")]
+    ArtifactError(#[source] anyhow::Error),
+
+    #[error("Unknown Registry API: {0}")]
+    UnknownRegistryApi(String),
+
+    #[error("RetentionError: {0}")]
+    RetentionError(#[source] anyhow::Error),
+
+    #[error("AuditError: {0}")]
+    AuditError(#[source] async_nats::Error),
+
+    #[error("Invalid Variable Reference: {0}")]
+    InvalidVariable(String),
+
+    #[error("Variable {0} is not defined")]
+    UndefinedVariable(String),
+
+    #[error("Character {0} is declared more than once by the included playbooks")]
+    CharacterConflict(String),
+
+    #[error("Invalid Uptime Schedule: {0}")]
+    InvalidUptime(String),
+
+    #[error("Invalid Network: {0}")]
+    InvalidNetwork(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,388 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::{Actor, Playbook};
+use amp_common::schema::BuildMethod;
+use k8s_openapi::api::batch::v1::{Job, JobSpec};
+use k8s_openapi::api::core::v1::{
+    ConfigMap, ConfigMapVolumeSource, Container, EnvVar, EnvVarSource, PodSpec, PodTemplateSpec, SecretKeySelector,
+    Volume, VolumeMount,
+};
+use kube::api::{Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::actor::PLAYBOOK_LABEL;
+use crate::build::BuildResources;
+use crate::containers::sidecar;
+use crate::error::{Error, Result};
+use crate::exposure::{self, Backend, Exposure};
+use crate::kpack::reference::{self, BuilderRef};
+use crate::{
+    cronjob, deployment, detection, hash, helm, image, kpack, naming, probe, service, statefulset, upload, volume,
+};
+
+/// The annotation of playbook exporting the manifests of its actors as a JSON
+/// document, e.g. `{"repo": "https://github.com/org/deploy", "branch": "main", "path": "apps/{playbook}"}`.
+/// The manifests are only kept in a ConfigMap if the repository is not set.
+pub const EXPORT_ANNOTATION: &str = "amphitheatre.app/export";
+
+/// The key of the ConfigMap holding the manifests as a multi-document YAML.
+const MANIFESTS_KEY: &str = "manifests.yaml";
+
+const DEFAULT_GIT_IMAGE: &str = "alpine/git:2.45.2";
+
+/// Where the manifests of playbook are committed for the GitOps review flow.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Export {
+    /// The HTTPS URL of the Git repository.
+    pub repo: Option<String>,
+    #[serde(default = "default_branch")]
+    pub branch: String,
+    /// The directory of the manifests in the repository, the `{playbook}`
+    /// variable is replaced, defaults to the name of playbook.
+    pub path: Option<String>,
+    /// The Secret in the Amphitheatre namespace holding the `username` and
+    /// `password` of the repository.
+    pub secret: Option<String>,
+}
+
+fn default_branch() -> String {
+    "main".to_string()
+}
+
+impl Export {
+    /// Returns the directory of the manifests of the playbook in the repository.
+    pub fn directory(&self, playbook: &str) -> String {
+        let path = self.path.as_deref().unwrap_or("{playbook}");
+        path.replace("{playbook}", playbook).trim_matches('/').to_string()
+    }
+}
+
+/// Returns the export of playbook, if any.
+pub fn of(playbook: &Playbook) -> Result<Option<Export>> {
+    match playbook.annotations().get(EXPORT_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Render the resources the workflow applies for the actor: its kpack Image,
+/// workload, Service and Ingress. The workload is selected the same as on
+/// deploy, with the env vars resolved by [`crate::envset::environment`], as the env
+/// sets copy their Secrets into the namespace of actor. The Helm charts are
+/// not rendered, so they can't be deployed from the manifests.
+pub fn render(
+    actor: &Actor,
+    exposure: &Exposure,
+    builder: Option<&BuilderRef>,
+    resources: &BuildResources,
+    env: &[EnvVar],
+) -> Result<Vec<Value>> {
+    let mut objects = vec![];
+    if helm::chart(&actor.spec.character).is_some() {
+        return Ok(objects);
+    }
+
+    // The Buildpacks are built by kpack unless the builder is detected, Nixpacks or the source is uploaded.
+    let method = actor.spec.character.build.clone().unwrap_or_default().method();
+    if matches!(method, BuildMethod::Buildpacks)
+        && !image::prebuilt(&actor.spec)
+        && !detection::auto(actor)
+        && !detection::nixpacks(actor)
+        && upload::of(&actor.spec).is_none()
+    {
+        let builder = reference::of(actor, builder)?.unwrap_or_else(|| reference::character(&actor.spec.character));
+        objects.push(to_value(&kpack::image::new(actor, &builder, resources)?)?);
+    }
+
+    let volumes = volume::volumes(actor)?;
+    let containers = sidecar::containers(actor)?;
+    let probes = probe::probes(actor)?;
+    let digest = deployment::digest(actor, env, &volumes, &containers, &probes)?;
+    let mut pod = deployment::pod(actor, &image::inferred(actor), env.to_vec(), &volumes, &containers, &probes);
+    if let Some(schedule) = cronjob::schedule(actor)? {
+        // The tasks are not serving, so they are never ready.
+        for container in pod.containers.iter_mut() {
+            container.readiness_probe = None;
+            container.startup_probe = None;
+        }
+        let digest = hash(&(digest, &schedule))?;
+        objects.push(to_value(&cronjob::new(actor, &schedule, pod, digest)?)?);
+    } else if statefulset::stateful(actor) {
+        // The PVC volumes are mounted from the claim templates instead.
+        if let Some(volumes) = pod.volumes.as_mut() {
+            volumes.retain(|volume| volume.persistent_volume_claim.is_none());
+        }
+        objects.push(to_value(&statefulset::headless_service(actor))?);
+        objects.push(to_value(&statefulset::new(actor, pod, volume::claim_templates(&volumes), digest)?)?);
+    } else {
+        objects.push(to_value(&deployment::new(actor, pod, digest)?)?);
+    }
+
+    if actor.spec.has_services() || !image::inferred(actor).is_empty() {
+        let service = service::new(actor)?;
+        let port = service.spec.as_ref().and_then(|spec| spec.ports.as_ref()).and_then(|ports| ports.first());
+        let host = exposure.hostname.as_deref().map(|template| exposure::hostname(template, actor));
+        objects.push(to_value(&service)?);
+        if let (Backend::Ingress, Some(host), Some(port)) = (exposure.backend, host, port) {
+            let ingress = exposure::ingress(actor, exposure, &service.name_any(), &host, port.port);
+            objects.push(to_value(&ingress)?);
+        }
+    }
+
+    // The manifests are applied by the GitOps tools, not owned by the actors.
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    for object in objects.iter_mut() {
+        if let Some(metadata) = object.get_mut("metadata").and_then(Value::as_object_mut) {
+            metadata.remove("ownerReferences");
+            metadata.insert("namespace".into(), Value::String(namespace.clone()));
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Serialize the objects into a multi-document YAML.
+pub fn to_yaml(objects: &[Value]) -> Result<String> {
+    let documents = objects
+        .iter()
+        .map(|object| serde_yaml::to_string(object).map_err(Error::YamlSerializationError))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(documents.join("---\n"))
+}
+
+/// Get the manifests exported for the playbook from the given namespace.
+pub async fn get(client: &Client, namespace: &str, playbook: &str) -> Result<Option<String>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let config_map = api.get_opt(&name(playbook)).await.map_err(Error::KubeError)?;
+
+    Ok(config_map.and_then(|config_map| config_map.data).and_then(|mut data| data.remove(MANIFESTS_KEY)))
+}
+
+/// Save the manifests of playbook in a ConfigMap of the given namespace,
+/// returns true if they have changed since the last export.
+pub async fn save(client: &Client, namespace: &str, playbook: &Playbook, manifests: &str) -> Result<bool> {
+    if get(client, namespace, &playbook.name_any()).await?.as_deref() == Some(manifests) {
+        debug!("The manifests of playbook {} are already up-to-date", playbook.name_any());
+        return Ok(false);
+    }
+
+    let resource = ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(name(&playbook.name_any())),
+            labels: Some(BTreeMap::from([
+                (PLAYBOOK_LABEL.into(), playbook.name_any()),
+                ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+            ])),
+            owner_references: playbook.controller_owner_ref(&()).map(|owner| vec![owner]),
+            ..Default::default()
+        },
+        data: Some(BTreeMap::from([(MANIFESTS_KEY.into(), manifests.to_string())])),
+        ..Default::default()
+    };
+
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let params = &PatchParams::apply("amp-controllers").force();
+    api.patch(&name(&playbook.name_any()), params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+    info!("Exported the manifests of playbook {}", playbook.name_any());
+
+    Ok(true)
+}
+
+/// Commit the saved manifests of playbook to the repository by a Job, the
+/// Job is named after the manifests, so each version is committed once.
+pub async fn commit(
+    client: &Client,
+    namespace: &str,
+    playbook: &Playbook,
+    export: &Export,
+    manifests: &str,
+) -> Result<()> {
+    let Some(repo) = export.repo.as_ref() else {
+        return Ok(());
+    };
+
+    let name = naming::name(&["amp-export", &playbook.name_any(), &hash(&manifests)?[..12]]);
+    let resource = job(playbook, export, repo, &name);
+
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace);
+    match api.create(&PostParams::default(), &resource).await {
+        Ok(job) => info!("Created export Job {} of playbook {}", job.name_any(), playbook.name_any()),
+        Err(kube::Error::Api(err)) if err.code == 409 => {}
+        Err(err) => return Err(Error::KubeError(err)),
+    }
+
+    Ok(())
+}
+
+/// Build the Job cloning the branch of repository, replacing the manifests
+/// in the directory of playbook and pushing them if they have changed.
+fn job(playbook: &Playbook, export: &Export, repo: &str, name: &str) -> Job {
+    let script = r#"set -e
+git config --global credential.helper '!f() { echo "username=${GIT_USERNAME}"; echo "password=${GIT_PASSWORD}"; }; f'
+git clone --depth 1 --branch "${GIT_BRANCH}" "${GIT_REPO}" /repo || \
+  (git init /repo && git -C /repo checkout -b "${GIT_BRANCH}" && git -C /repo remote add origin "${GIT_REPO}")
+mkdir -p "/repo/${EXPORT_PATH}"
+cp /manifests/manifests.yaml "/repo/${EXPORT_PATH}/manifests.yaml"
+cd /repo && git add -A
+git diff --cached --quiet && exit 0
+git -c user.name=Amphitheatre -c user.email=noreply@amphitheatre.app commit -m "Export the manifests of ${PLAYBOOK}"
+git push origin "${GIT_BRANCH}""#;
+
+    let credential = |key: &str| {
+        let secret = export.secret.as_ref()?;
+        let selector = SecretKeySelector { name: Some(secret.clone()), key: key.into(), optional: Some(true) };
+        Some(EnvVarSource { secret_key_ref: Some(selector), ..Default::default() })
+    };
+    let var = |name: &str, value: String| EnvVar { name: name.into(), value: Some(value), value_from: None };
+    let env = vec![
+        var("GIT_REPO", repo.to_string()),
+        var("GIT_BRANCH", export.branch.clone()),
+        var("EXPORT_PATH", export.directory(&playbook.name_any())),
+        var("PLAYBOOK", playbook.name_any()),
+        EnvVar { name: "GIT_USERNAME".into(), value: None, value_from: credential("username") },
+        EnvVar { name: "GIT_PASSWORD".into(), value: None, value_from: credential("password") },
+    ];
+
+    let container = Container {
+        name: "export".into(),
+        image: Some(DEFAULT_GIT_IMAGE.into()),
+        command: Some(vec!["/bin/sh".into(), "-c".into(), script.into()]),
+        env: Some(env),
+        volume_mounts: Some(vec![VolumeMount {
+            name: "manifests".into(),
+            mount_path: "/manifests".into(),
+            read_only: Some(true),
+            ..Default::default()
+        }]),
+        ..Default::default()
+    };
+    let volume = Volume {
+        name: "manifests".into(),
+        config_map: Some(ConfigMapVolumeSource { name: Some(self::name(&playbook.name_any())), ..Default::default() }),
+        ..Default::default()
+    };
+
+    let labels = BTreeMap::from([
+        (PLAYBOOK_LABEL.into(), playbook.name_any()),
+        ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+    ]);
+    Job {
+        metadata: ObjectMeta {
+            name: Some(name.into()),
+            labels: Some(labels),
+            owner_references: playbook.controller_owner_ref(&()).map(|owner| vec![owner]),
+            ..Default::default()
+        },
+        spec: Some(JobSpec {
+            backoff_limit: Some(2),
+            ttl_seconds_after_finished: Some(24 * 60 * 60),
+            template: PodTemplateSpec {
+                spec: Some(PodSpec {
+                    containers: vec![container],
+                    volumes: Some(vec![volume]),
+                    restart_policy: Some("Never".into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn to_value<T: Serialize>(resource: &T) -> Result<Value> {
+    serde_json::to_value(resource).map_err(Error::SerializationError)
+}
+
+#[inline]
+fn name(playbook: &str) -> String {
+    naming::name(&["amp-manifests", playbook])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    fn actor() -> Actor {
+        let spec = ActorSpec { name: "web".into(), image: "nginx:1.27".into(), ..Default::default() };
+        let mut actor = Actor::new("web", spec);
+        actor.metadata.namespace = Some("amp-demo".into());
+        actor.metadata.uid = Some("uid".into());
+        image::infer(&mut actor, &["8080/tcp"]);
+        actor
+    }
+
+    #[test]
+    fn test_parse_export() {
+        let mut playbook = Playbook::new("demo", Default::default());
+        assert_eq!(of(&playbook).unwrap(), None);
+
+        let content = r#"{"repo": "https://github.com/org/deploy", "path": "/apps/{playbook}/"}"#;
+        playbook.annotations_mut().insert(EXPORT_ANNOTATION.into(), content.into());
+        let export = of(&playbook).unwrap().unwrap();
+        assert_eq!(export.branch, "main");
+        assert_eq!(export.directory("demo"), "apps/demo");
+        assert_eq!(Export::default().directory("demo"), "demo");
+    }
+
+    #[test]
+    fn test_render_manifests() {
+        let exposure = Exposure {
+            backend: Backend::Ingress,
+            hostname: Some("{character}.example.com".into()),
+            ..Default::default()
+        };
+        let objects = render(&actor(), &exposure, None, &BuildResources::default(), &[]).unwrap();
+
+        let kinds: Vec<&str> = objects.iter().filter_map(|object| object["kind"].as_str()).collect();
+        assert_eq!(kinds, vec!["Deployment", "Service", "Ingress"]);
+        for object in &objects {
+            assert_eq!(object["metadata"]["namespace"], "amp-demo");
+            assert!(object["metadata"].get("ownerReferences").is_none());
+        }
+
+        let objects = render(&actor(), &Exposure::default(), None, &BuildResources::default(), &[]).unwrap();
+        assert_eq!(objects.len(), 2);
+    }
+
+    #[test]
+    fn test_render_workload_with_env() {
+        let env = vec![EnvVar { name: "DB_HOST".into(), value: Some("db.amp-demo".into()), value_from: None }];
+        let mut actor = actor();
+        actor.annotations_mut().insert(statefulset::WORKLOAD_ANNOTATION.into(), "statefulset".into());
+        let objects = render(&actor, &Exposure::default(), None, &BuildResources::default(), &env).unwrap();
+
+        let kinds: Vec<&str> = objects.iter().filter_map(|object| object["kind"].as_str()).collect();
+        assert_eq!(kinds, vec!["Service", "StatefulSet", "Service"]);
+        let container = &objects[1]["spec"]["template"]["spec"]["containers"][0];
+        assert!(container["env"].as_array().unwrap().iter().any(|var| var["name"] == "DB_HOST"));
+    }
+
+    #[test]
+    fn test_to_yaml() {
+        let objects = vec![serde_json::json!({"kind": "Service"}), serde_json::json!({"kind": "Ingress"})];
+        assert_eq!(to_yaml(&objects).unwrap(), "kind: Service\n---\nkind: Ingress\n");
+    }
+}
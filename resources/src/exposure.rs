@@ -0,0 +1,541 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::{Secret, Service, ServiceSpec};
+use k8s_openapi::api::networking::v1::{
+    HTTPIngressPath, HTTPIngressRuleValue, Ingress, IngressBackend, IngressRule, IngressServiceBackend, IngressSpec,
+    IngressTLS, ServiceBackendPort,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::core::{DynamicObject, GroupVersionKind, ObjectMeta};
+use kube::discovery::ApiResource;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_value, json};
+use tracing::info;
+
+use crate::error::{Error, Result};
+use crate::{canary, naming, probe};
+
+/// The annotation of actor overriding how it is exposed as a JSON document,
+/// e.g. `{"backend": "gateway", "hostname": "api.example.com"}`. The playbook
+/// declares the exposure of a character with the annotation suffixed by its
+/// name, e.g. `amphitheatre.app/exposure.web`.
+pub const EXPOSURE_ANNOTATION: &str = "amphitheatre.app/exposure";
+
+/// The condition type of actor reporting its URL outside of the cluster.
+pub const EXPOSED_CONDITION_TYPE: &str = "Exposed";
+
+/// How the actors are exposed outside of the cluster, configured by the
+/// operator and overridden per actor.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Exposure {
+    pub backend: Backend,
+    /// The hostname template of the actors, the `{character}`, `{playbook}`
+    /// and `{namespace}` variables are replaced, e.g. `{character}-{playbook}.apps.example.com`.
+    pub hostname: Option<String>,
+    /// The Secret holding the TLS certificate of the hostname, in the namespace of actor.
+    pub tls_secret: Option<String>,
+    /// The IngressClass of the Ingresses.
+    pub ingress_class: Option<String>,
+    /// The namespace of the ingress controller, its traffic to the isolated actors is allowed.
+    pub ingress_namespace: Option<String>,
+    /// The shared Gateway the HTTPRoutes are attached to, in the form of `{namespace}/{name}`.
+    pub gateway: Option<String>,
+    /// The GatewayClass of the Gateways created for the actors with their own TLS certificates.
+    pub gateway_class: Option<String>,
+    /// The ClusterIssuer of cert-manager issuing the TLS certificates of the
+    /// actors without their own TLS secrets.
+    pub issuer: Option<String>,
+}
+
+/// The annotations of ingress-nginx routing a part of the traffic to the canary Ingress.
+const NGINX_CANARY_ANNOTATION: &str = "nginx.ingress.kubernetes.io/canary";
+const NGINX_CANARY_WEIGHT_ANNOTATION: &str = "nginx.ingress.kubernetes.io/canary-weight";
+
+/// The URL of the exposed actor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exposed {
+    pub url: String,
+    /// The TLS certificate is not issued yet, the URL is served over HTTP only.
+    pub pending: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Only reachable inside the cluster by its Service.
+    #[default]
+    None,
+    Ingress,
+    /// The HTTPRoute of Gateway API.
+    Gateway,
+}
+
+/// The overrides of the exposure of actor.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Override {
+    pub backend: Option<Backend>,
+    pub hostname: Option<String>,
+    pub tls_secret: Option<String>,
+}
+
+impl Exposure {
+    /// Returns the exposure of actor, the defaults overridden by its annotation.
+    pub fn of(&self, actor: &Actor) -> Result<Exposure> {
+        let Some(content) = actor.annotations().get(EXPOSURE_ANNOTATION) else {
+            return Ok(self.clone());
+        };
+        let overrides: Override = serde_json::from_str(content).map_err(Error::SerializationError)?;
+
+        Ok(Exposure {
+            backend: overrides.backend.unwrap_or(self.backend),
+            hostname: overrides.hostname.or_else(|| self.hostname.clone()),
+            tls_secret: overrides.tls_secret.or_else(|| self.tls_secret.clone()),
+            ..self.clone()
+        })
+    }
+
+    /// Returns true if the backend splits the traffic by weights, the
+    /// HTTPRoute of Gateway API, or the Ingress of ingress-nginx.
+    pub fn weighted(&self) -> bool {
+        match self.backend {
+            Backend::None => false,
+            Backend::Ingress => self.ingress_class.as_deref().is_some_and(|class| class.contains("nginx")),
+            Backend::Gateway => true,
+        }
+    }
+
+    /// Returns the namespace the external traffic comes from, the one of the
+    /// ingress controller, or the one of the shared Gateway.
+    pub fn ingress_namespace(&self) -> Option<&str> {
+        let gateway =
+            self.gateway.as_deref().and_then(|gateway| gateway.split_once('/')).map(|(namespace, _)| namespace);
+        self.ingress_namespace.as_deref().or(gateway)
+    }
+}
+
+/// Render the hostname template for actor.
+pub fn hostname(template: &str, actor: &Actor) -> String {
+    let namespace = actor.namespace().unwrap_or_default();
+    let playbook = actor.owner_references().iter().find(|owner| owner.kind == "Playbook").map(|owner| &owner.name);
+
+    template
+        .replace("{character}", &actor.name_any())
+        .replace("{playbook}", playbook.unwrap_or(&namespace))
+        .replace("{namespace}", &namespace)
+}
+
+/// Expose the Service of actor by the backend, the resources of the other
+/// backends are deleted in case the backend is changed. Returns the URL of
+/// actor, None if it's only reachable inside the cluster.
+pub async fn apply(client: &Client, actor: &Actor, exposure: &Exposure, service: &Service) -> Result<Option<Exposed>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let name = service.name_any();
+    let port = service.spec.as_ref().and_then(|spec| spec.ports.as_ref()).and_then(|ports| ports.first());
+    let host = exposure.hostname.as_deref().map(|template| hostname(template, actor));
+
+    let backend = match (host.as_ref(), port) {
+        (Some(_), Some(_)) => exposure.backend,
+        _ => Backend::None,
+    };
+
+    // Issue the certificate of the hostname by cert-manager if the actor has no TLS secret.
+    let mut exposure = exposure.clone();
+    match (&host, &exposure.issuer) {
+        (Some(host), Some(issuer)) if backend != Backend::None && exposure.tls_secret.is_none() => {
+            let secret = naming::name(&[&name, "tls"]);
+            let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), &namespace, &certificate_resource());
+            let resource = certificate(actor, &name, host, issuer, &secret)?;
+            let params = &PatchParams::apply("amp-controllers").force();
+            api.patch(&name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+            exposure.tls_secret = Some(secret);
+        }
+        _ => delete(client, &namespace, &name, &certificate_resource()).await?,
+    }
+    let exposure = &exposure;
+    if backend != Backend::Ingress {
+        delete(client, &namespace, &name, &ingress_resource()).await?;
+    }
+    if backend != Backend::Gateway {
+        delete(client, &namespace, &name, &http_route_resource()).await?;
+    }
+    if backend != Backend::Gateway || exposure.tls_secret.is_none() {
+        delete(client, &namespace, &name, &gateway_resource()).await?;
+    }
+
+    // Split the traffic between the stable and canary pods during the canary rollout.
+    let split = split(client, actor, service, backend != Backend::None && exposure.weighted()).await?;
+    if split.is_none() || backend != Backend::Ingress {
+        delete(client, &namespace, &naming::name(&[&name, "canary"]), &ingress_resource()).await?;
+    }
+
+    let (Some(host), Some(port)) = (host, port) else {
+        return Ok(None);
+    };
+    let params = &PatchParams::apply("amp-controllers").force();
+    match backend {
+        Backend::None => {}
+        Backend::Ingress => {
+            let api: Api<Ingress> = Api::namespaced(client.clone(), &namespace);
+            let mut resource = ingress(actor, exposure, &name, &host, port.port);
+            if let Some(split) = &split {
+                // The main Ingress serves the stable pods, the canary one takes the weight of traffic.
+                route(&mut resource, &split.stable);
+                let mut canary = ingress(actor, exposure, &split.canary, &host, port.port);
+                canary.annotations_mut().extend([
+                    (NGINX_CANARY_ANNOTATION.to_string(), "true".to_string()),
+                    (NGINX_CANARY_WEIGHT_ANNOTATION.to_string(), split.weight.to_string()),
+                ]);
+                api.patch(&split.canary, params, &Patch::Apply(&canary)).await.map_err(Error::KubeError)?;
+            }
+            api.patch(&name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+            info!("Exposed actor {} at {} by Ingress", actor.name_any(), host);
+        }
+        Backend::Gateway => {
+            // The TLS certificates are referenced by the listeners of Gateway,
+            // so the actor with its own certificate has its own Gateway.
+            let parent = match &exposure.tls_secret {
+                Some(secret) => {
+                    let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), &namespace, &gateway_resource());
+                    let resource = gateway(actor, exposure, &name, &host, secret)?;
+                    api.patch(&name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+                    json!({ "name": name })
+                }
+                None => {
+                    let gateway = exposure
+                        .gateway
+                        .as_deref()
+                        .ok_or_else(|| Error::InvalidExposure("A shared gateway or a TLS secret is required".into()))?;
+                    let (gateway_namespace, gateway_name) = gateway.split_once('/').unwrap_or((&namespace, gateway));
+                    json!({ "name": gateway_name, "namespace": gateway_namespace })
+                }
+            };
+
+            let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), &namespace, &http_route_resource());
+            let resource = http_route(actor, &name, &host, port.port, parent, split.as_ref())?;
+            api.patch(&name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+            info!("Exposed actor {} at {} by HTTPRoute", actor.name_any(), host);
+        }
+    }
+    if backend == Backend::None {
+        return Ok(None);
+    }
+
+    // Wait for the TLS secret before reporting the HTTPS URL.
+    let Some(secret) = &exposure.tls_secret else {
+        return Ok(Some(Exposed { url: format!("http://{}", host), pending: false }));
+    };
+    let api: Api<Secret> = Api::namespaced(client.clone(), &namespace);
+    match api.get_opt(secret).await.map_err(Error::KubeError)? {
+        Some(_) => Ok(Some(Exposed { url: format!("https://{}", host), pending: false })),
+        None => Ok(Some(Exposed { url: format!("http://{}", host), pending: true })),
+    }
+}
+
+/// Build the condition reporting the URL of the exposed actor.
+pub fn condition(exposed: &Exposed) -> Condition {
+    let (status, reason) = match exposed.pending {
+        true => ("False", "CertificatePending"),
+        false => ("True", "Exposed"),
+    };
+
+    Condition {
+        type_: EXPOSED_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: reason.into(),
+        message: exposed.url.clone(),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// The Services of the stable and canary pods, and the weight of the canary.
+struct Split {
+    stable: String,
+    canary: String,
+    weight: i32,
+}
+
+/// Apply the Services of the stable and canary pods if the canary rollout of
+/// actor is in progress and the traffic is split by weights, or delete them.
+/// The canary takes no traffic until its pods are ready.
+async fn split(client: &Client, actor: &Actor, service: &Service, weighted: bool) -> Result<Option<Split>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let name = service.name_any();
+    let (stable, canary) = (naming::name(&[&name, "stable"]), naming::name(&[&name, "canary"]));
+
+    let weight = canary::weight(actor).filter(|_| weighted);
+    let selector = match weight {
+        Some(_) => canary::stable_selector(client, actor).await?,
+        None => None,
+    };
+    let (Some(weight), Some(selector)) = (weight, selector) else {
+        for name in [&stable, &canary] {
+            delete(client, &namespace, name, &ApiResource::erase::<Service>(&())).await?;
+        }
+        return Ok(None);
+    };
+
+    let api: Api<Service> = Api::namespaced(client.clone(), &namespace);
+    let params = &PatchParams::apply("amp-controllers").force();
+    let ports = service.spec.as_ref().and_then(|spec| spec.ports.clone());
+    for (name, selector) in [(&stable, selector), (&canary, canary::selector(actor))] {
+        let resource = Service {
+            metadata: metadata(actor, name),
+            spec: Some(ServiceSpec { selector: Some(selector), ports: ports.clone(), ..Default::default() }),
+            ..Default::default()
+        };
+        api.patch(name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+    }
+
+    let replicas = probe::replicas(client, &namespace, &canary::name(actor)).await?;
+    let ready = replicas.is_some_and(|(ready, desired)| ready > 0 && ready >= desired);
+    Ok(Some(Split { stable, canary, weight: if ready { weight.clamp(0, 100) } else { 0 } }))
+}
+
+async fn delete(client: &Client, namespace: &str, name: &str, resource: &ApiResource) -> Result<()> {
+    let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace, resource);
+
+    // The CRDs of Gateway API and cert-manager may be not installed at all.
+    match api.get_opt(name).await {
+        Ok(Some(_)) => {
+            api.delete(name, &DeleteParams::default()).await.map_err(Error::KubeError)?;
+            info!("Deleted {} {}", resource.kind, name);
+        }
+        Ok(None) => {}
+        Err(kube::Error::Api(err)) if err.code == 404 => {}
+        Err(err) => return Err(Error::KubeError(err)),
+    }
+
+    Ok(())
+}
+
+fn metadata(actor: &Actor, name: &str) -> ObjectMeta {
+    ObjectMeta {
+        name: Some(name.into()),
+        owner_references: Some(vec![actor.controller_owner_ref(&()).unwrap()]),
+        labels: Some(BTreeMap::from([
+            ("amphitheatre.app/character".into(), actor.name_any()),
+            ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+        ])),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn ingress(actor: &Actor, exposure: &Exposure, service: &str, host: &str, port: i32) -> Ingress {
+    let backend = IngressBackend {
+        service: Some(IngressServiceBackend {
+            name: service.into(),
+            port: Some(ServiceBackendPort { number: Some(port), name: None }),
+        }),
+        resource: None,
+    };
+    let path = HTTPIngressPath { path: Some("/".into()), path_type: "Prefix".into(), backend };
+
+    Ingress {
+        metadata: metadata(actor, service),
+        spec: Some(IngressSpec {
+            ingress_class_name: exposure.ingress_class.clone(),
+            rules: Some(vec![IngressRule {
+                host: Some(host.into()),
+                http: Some(HTTPIngressRuleValue { paths: vec![path] }),
+            }]),
+            tls: exposure
+                .tls_secret
+                .as_ref()
+                .map(|secret| vec![IngressTLS { hosts: Some(vec![host.into()]), secret_name: Some(secret.clone()) }]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Point the paths of the Ingress to the Service.
+fn route(ingress: &mut Ingress, service: &str) {
+    let rules = ingress.spec.iter_mut().flat_map(|spec| spec.rules.iter_mut().flatten());
+    for path in rules.flat_map(|rule| rule.http.iter_mut().flat_map(|http| http.paths.iter_mut())) {
+        if let Some(backend) = path.backend.service.as_mut() {
+            backend.name = service.into();
+        }
+    }
+}
+
+fn certificate(actor: &Actor, name: &str, host: &str, issuer: &str, secret: &str) -> Result<DynamicObject> {
+    from_value(json!({
+        "apiVersion": "cert-manager.io/v1",
+        "kind": "Certificate",
+        "metadata": metadata(actor, name),
+        "spec": {
+            "secretName": secret,
+            "dnsNames": [host],
+            "issuerRef": { "kind": "ClusterIssuer", "name": issuer },
+        },
+    }))
+    .map_err(Error::SerializationError)
+}
+
+fn gateway(actor: &Actor, exposure: &Exposure, name: &str, host: &str, secret: &str) -> Result<DynamicObject> {
+    let class = exposure.gateway_class.as_deref();
+    let class = class.ok_or_else(|| Error::InvalidExposure("A gateway class is required by the TLS secret".into()))?;
+
+    from_value(json!({
+        "apiVersion": "gateway.networking.k8s.io/v1",
+        "kind": "Gateway",
+        "metadata": metadata(actor, name),
+        "spec": {
+            "gatewayClassName": class,
+            "listeners": [{
+                "name": "https",
+                "protocol": "HTTPS",
+                "port": 443,
+                "hostname": host,
+                "tls": {
+                    "mode": "Terminate",
+                    "certificateRefs": [{ "kind": "Secret", "name": secret }],
+                },
+            }],
+        },
+    }))
+    .map_err(Error::SerializationError)
+}
+
+fn http_route(
+    actor: &Actor,
+    service: &str,
+    host: &str,
+    port: i32,
+    parent: serde_json::Value,
+    split: Option<&Split>,
+) -> Result<DynamicObject> {
+    let backends = match split {
+        Some(split) => json!([
+            { "name": split.stable, "port": port, "weight": 100 - split.weight },
+            { "name": split.canary, "port": port, "weight": split.weight },
+        ]),
+        None => json!([{ "name": service, "port": port }]),
+    };
+
+    from_value(json!({
+        "apiVersion": "gateway.networking.k8s.io/v1",
+        "kind": "HTTPRoute",
+        "metadata": metadata(actor, service),
+        "spec": {
+            "parentRefs": [parent],
+            "hostnames": [host],
+            "rules": [{
+                "matches": [{ "path": { "type": "PathPrefix", "value": "/" } }],
+                "backendRefs": backends,
+            }],
+        },
+    }))
+    .map_err(Error::SerializationError)
+}
+
+#[inline]
+fn ingress_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk("networking.k8s.io", "v1", "Ingress"))
+}
+
+#[inline]
+fn certificate_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk("cert-manager.io", "v1", "Certificate"))
+}
+
+#[inline]
+fn gateway_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk("gateway.networking.k8s.io", "v1", "Gateway"))
+}
+
+#[inline]
+fn http_route_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk("gateway.networking.k8s.io", "v1", "HTTPRoute"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+
+    fn actor() -> Actor {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        actor.metadata.namespace = Some("amp-demo".into());
+        actor.metadata.uid = Some("uid".into());
+        actor.owner_references_mut().push(OwnerReference {
+            kind: "Playbook".into(),
+            name: "demo".into(),
+            ..Default::default()
+        });
+        actor
+    }
+
+    #[test]
+    fn test_hostname() {
+        let actor = actor();
+        assert_eq!(hostname("{character}-{playbook}.apps.example.com", &actor), "web-demo.apps.example.com");
+        assert_eq!(hostname("{character}.{namespace}.local", &actor), "web.amp-demo.local");
+    }
+
+    #[test]
+    fn test_override_exposure() {
+        let defaults = Exposure {
+            backend: Backend::Ingress,
+            hostname: Some("{character}.example.com".into()),
+            ingress_class: Some("nginx".into()),
+            ..Default::default()
+        };
+        let mut actor = actor();
+        assert_eq!(defaults.of(&actor).unwrap(), defaults);
+
+        let content = r#"{"backend": "gateway", "tls_secret": "web-tls"}"#;
+        actor.annotations_mut().insert(EXPOSURE_ANNOTATION.into(), content.into());
+        let exposure = defaults.of(&actor).unwrap();
+        assert_eq!(exposure.backend, Backend::Gateway);
+        assert_eq!(exposure.hostname, defaults.hostname);
+        assert_eq!(exposure.tls_secret.as_deref(), Some("web-tls"));
+    }
+
+    #[test]
+    fn test_exposed_condition() {
+        let condition = condition(&Exposed { url: "http://web.example.com".into(), pending: true });
+        assert_eq!(condition.status, "False");
+        assert_eq!(condition.reason, "CertificatePending");
+        assert_eq!(condition.message, "http://web.example.com");
+    }
+
+    #[test]
+    fn test_http_route() {
+        let parent = json!({ "name": "shared", "namespace": "gateways" });
+        let route = http_route(&actor(), "web", "web.example.com", 8080, parent.clone(), None).unwrap();
+
+        assert_eq!(route.data["spec"]["hostnames"][0], "web.example.com");
+        assert_eq!(route.data["spec"]["parentRefs"][0]["namespace"], "gateways");
+        assert_eq!(route.data["spec"]["rules"][0]["backendRefs"][0]["port"], 8080);
+
+        let split = Split { stable: "web-stable".into(), canary: "web-canary".into(), weight: 20 };
+        let route = http_route(&actor(), "web", "web.example.com", 8080, parent, Some(&split)).unwrap();
+        let backends = &route.data["spec"]["rules"][0]["backendRefs"];
+        assert_eq!(backends[0]["name"], "web-stable");
+        assert_eq!(backends[0]["weight"], 80);
+        assert_eq!(backends[1]["name"], "web-canary");
+        assert_eq!(backends[1]["weight"], 20);
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{Namespace, Pod};
+use k8s_openapi::chrono::{Duration, Utc};
+use kube::api::{DeleteParams, ListParams, PropagationPolicy};
+use kube::{Api, Client, ResourceExt};
+use tracing::{debug, info};
+
+use crate::actor::DETACHED_LABEL;
+use crate::base;
+use crate::error::{Error, Result};
+
+/// The label of the resources managed by Amphitheatre.
+const MANAGED_LABEL: &str = "app.kubernetes.io/managed-by=Amphitheatre";
+
+/// The label of the pods naming the Job they are created by.
+const JOB_NAME_LABEL: &str = "job-name";
+
+/// The label of the Jobs and their pods naming the character they are created for.
+const CHARACTER_LABEL: &str = "amphitheatre.app/character";
+
+/// Delete the completed base image build Jobs beyond the latest ones of each
+/// actor, they are created once per commit. Returns the number of the deleted Jobs.
+pub async fn jobs(client: &Client, keep: usize) -> Result<usize> {
+    let api: Api<Job> = Api::all(client.clone());
+    let jobs = api.list(&ListParams::default().labels(MANAGED_LABEL)).await.map_err(Error::KubeError)?.items;
+
+    let jobs = jobs.into_iter().filter(|job| base::is_job(&job.name_any()) && completed(job)).filter_map(|job| {
+        let character = job.labels().get(CHARACTER_LABEL)?;
+        let order = created(&job).unwrap_or_default();
+        Some((format!("{}/{}", job.namespace()?, character), order, job))
+    });
+
+    let mut deleted = 0;
+    for job in stale(jobs.collect(), keep) {
+        let api: Api<Job> = Api::namespaced(client.clone(), &job.namespace().unwrap_or_default());
+        if delete(&api, &job.name_any()).await? {
+            info!("Deleted the stale base image build Job {}", job.name_any());
+            deleted += 1;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Delete the finished pods of the build Jobs beyond the latest ones of each actor,
+/// the failed attempts are kept by their Jobs, and the pods of the replaced Jobs may
+/// be left behind. Returns the number of the deleted pods.
+pub async fn pods(client: &Client, keep: usize) -> Result<usize> {
+    let api: Api<Pod> = Api::all(client.clone());
+    let params = ListParams::default().labels(&format!("{},{}", MANAGED_LABEL, JOB_NAME_LABEL));
+    let pods = api.list(&params).await.map_err(Error::KubeError)?.items;
+
+    let pods = pods.into_iter().filter(finished).filter_map(|pod| {
+        let character = pod.labels().get(CHARACTER_LABEL)?;
+        let order = created(&pod).unwrap_or_default();
+        Some((format!("{}/{}", pod.namespace()?, character), order, pod))
+    });
+
+    let mut deleted = 0;
+    for pod in stale(pods.collect(), keep) {
+        let api: Api<Pod> = Api::namespaced(client.clone(), &pod.namespace().unwrap_or_default());
+        if delete(&api, &pod.name_any()).await? {
+            info!("Deleted the stale build pod {}", pod.name_any());
+            deleted += 1;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Delete the namespaces of the detached actors which have no actors left, e.g.
+/// the actor failed to be created, or its cleanup failed after it's deleted.
+/// The namespaces younger than the grace period are kept, their actors may
+/// be being created. Returns the number of the deleted namespaces.
+pub async fn namespaces(client: &Client, grace: Duration) -> Result<usize> {
+    let api: Api<Namespace> = Api::all(client.clone());
+    let params = ListParams::default().labels(&format!("{}=true", DETACHED_LABEL));
+    let namespaces = api.list(&params).await.map_err(Error::KubeError)?.items;
+
+    let mut deleted = 0;
+    for namespace in namespaces {
+        let young = namespace.creation_timestamp().map_or(true, |time| Utc::now() - time.0 < grace);
+        if young || namespace.metadata.deletion_timestamp.is_some() {
+            continue;
+        }
+
+        let actors: Api<Actor> = Api::namespaced(client.clone(), &namespace.name_any());
+        if !actors.list(&ListParams::default().limit(1)).await.map_err(Error::KubeError)?.items.is_empty() {
+            continue;
+        }
+        if delete(&api, &namespace.name_any()).await? {
+            info!("Deleted the leaked namespace {} of detached actor", namespace.name_any());
+            deleted += 1;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Returns the objects beyond the latest `keep` ones of each group, the
+/// objects are given with their groups and orders, the greater is later.
+fn stale<T>(objects: Vec<(String, i64, T)>, keep: usize) -> Vec<T> {
+    let mut groups: BTreeMap<String, Vec<(i64, T)>> = BTreeMap::new();
+    for (group, order, object) in objects {
+        groups.entry(group).or_default().push((order, object));
+    }
+
+    let mut stale = vec![];
+    for (_, mut objects) in groups {
+        objects.sort_by_key(|(order, _)| std::cmp::Reverse(*order));
+        stale.extend(objects.into_iter().skip(keep).map(|(_, object)| object));
+    }
+
+    stale
+}
+
+/// Delete the object with its dependents, returns false if it is gone already.
+async fn delete<K>(api: &Api<K>, name: &str) -> Result<bool>
+where
+    K: kube::Resource + Clone + serde::de::DeserializeOwned + std::fmt::Debug,
+{
+    let params = DeleteParams { propagation_policy: Some(PropagationPolicy::Background), ..Default::default() };
+    match api.delete(name, &params).await {
+        Ok(_) => Ok(true),
+        Err(kube::Error::Api(err)) if err.code == 404 => {
+            debug!("The stale {} is gone already", name);
+            Ok(false)
+        }
+        Err(err) => Err(Error::KubeError(err)),
+    }
+}
+
+fn completed(job: &Job) -> bool {
+    job.status.as_ref().is_some_and(|status| status.succeeded >= Some(1) || status.failed >= Some(1))
+}
+
+fn finished(pod: &Pod) -> bool {
+    let phase = pod.status.as_ref().and_then(|status| status.phase.as_deref());
+    matches!(phase, Some("Succeeded") | Some("Failed"))
+}
+
+fn created<K: ResourceExt>(object: &K) -> Option<i64> {
+    object.creation_timestamp().map(|time| time.0.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_objects() {
+        let objects = vec![
+            ("web".to_string(), 1, "web-1"),
+            ("web".to_string(), 3, "web-3"),
+            ("api".to_string(), 1, "api-1"),
+            ("web".to_string(), 2, "web-2"),
+            ("web".to_string(), 4, "web-4"),
+        ];
+
+        assert_eq!(stale(objects.clone(), 2), vec!["web-2", "web-1"]);
+        assert_eq!(stale(objects.clone(), 0).len(), 5);
+        assert!(stale(objects, 5).is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::config::{Credential, Scheme};
+use url::Url;
+
+/// The annotation of the repository Secrets telling kpack which git server they authenticate.
+pub const KPACK_GIT_ANNOTATION: &str = "kpack.io/git";
+
+/// The prefix of the Secrets holding the repository credentials.
+const SECRET_PREFIX: &str = "amp-repo-credentials";
+
+/// The username of the tokens of the unknown hosts, e.g. self-hosted GitLab
+/// or Gitea, GitLab accepts it and Gitea authenticates the owner of token.
+const DEFAULT_USERNAME: &str = "oauth2";
+
+/// The host keys of the well-known providers, the repositories of the other
+/// hosts are verified with the `known_hosts` of their Secrets.
+const KNOWN_HOSTS: [(&str, &str); 3] = [
+    ("github.com", "github.com ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl"),
+    ("gitlab.com", "gitlab.com ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAfuCHKVTjquxvt6CM6tdG4SLp1Btn/nOeHHE5UOzRdf"),
+    ("bitbucket.org", "bitbucket.org ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIIazEu89wgQZ4bqs3d63QSMzYVa0MuJ2e2gKTKqu+UUO"),
+];
+
+/// The hosting providers of the repositories, the tokens are sent as the
+/// passwords of their own usernames when cloning over HTTPS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Provider {
+    GitHub,
+    GitLab,
+    Bitbucket,
+}
+
+impl Provider {
+    /// Detect the provider from the host, None if it's unknown, e.g. a
+    /// self-hosted server on a custom domain.
+    pub fn of(host: &str) -> Option<Self> {
+        match host {
+            host if host.contains("github") => Some(Provider::GitHub),
+            host if host.contains("gitlab") => Some(Provider::GitLab),
+            host if host.contains("bitbucket") => Some(Provider::Bitbucket),
+            _ => None,
+        }
+    }
+
+    /// The username of the tokens.
+    pub fn username(&self) -> &'static str {
+        match self {
+            Provider::GitHub => "x-access-token",
+            Provider::GitLab => "oauth2",
+            Provider::Bitbucket => "x-token-auth",
+        }
+    }
+}
+
+/// How the git clients authenticate with a repository credential, along
+/// with the host keys of the SSH server if it's well-known.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Auth {
+    Basic { username: String, password: String },
+    Ssh { private_key: String, known_hosts: Option<String> },
+}
+
+/// Returns the scheme and host of the repository, the SCP-like addresses
+/// such as `git@github.com:org/repo.git` are taken as `ssh`.
+pub fn endpoint(repo: &str) -> Option<(String, String)> {
+    if let Ok(url) = Url::parse(repo) {
+        return url.host_str().map(|host| (url.scheme().to_string(), host.to_lowercase()));
+    }
+
+    let (address, _) = repo.split_once(':')?;
+    let host = address.rsplit('@').next().filter(|host| !host.is_empty())?;
+    Some(("ssh".into(), host.to_lowercase()))
+}
+
+/// Returns true if the repository is cloned over SSH.
+pub fn ssh(repo: &str) -> bool {
+    endpoint(repo).is_some_and(|(scheme, _)| scheme == "ssh")
+}
+
+/// Returns the HTTPS address of the repository, the APIs of the providers
+/// are always over HTTPS, even if the repository is cloned over SSH.
+pub fn https(repo: &str) -> String {
+    match endpoint(repo) {
+        Some((scheme, host)) if scheme == "ssh" => {
+            let path = match Url::parse(repo) {
+                Ok(url) => url.path().to_string(),
+                Err(_) => repo.split_once(':').map(|(_, path)| path.to_string()).unwrap_or_default(),
+            };
+            format!("https://{}/{}", host, path.trim_start_matches('/'))
+        }
+        _ => repo.to_string(),
+    }
+}
+
+/// Returns the name of the Secret holding the credential of the repository,
+/// the credentials of the same host over HTTPS and SSH are kept apart.
+pub fn secret_name(repo: &str) -> Option<String> {
+    endpoint(repo).map(|(scheme, host)| format!("{}-{}-{}", SECRET_PREFIX, scheme, host))
+}
+
+/// Returns the git server of the `kpack.io/git` annotation, which kpack
+/// matches with the prefix of the source URL of Image.
+pub fn kpack_server(repo: &str) -> Option<String> {
+    endpoint(repo).map(|(scheme, host)| match scheme.as_str() {
+        "ssh" => format!("git@{}", host),
+        _ => format!("{}://{}", scheme, host),
+    })
+}
+
+/// Returns how to authenticate with the credential of the server. The
+/// credentials of the `ssh://` servers hold the private keys in their tokens,
+/// and the tokens of the others are sent as the passwords over HTTPS.
+pub fn auth(server: &str, credential: &impl Credential) -> Option<Auth> {
+    if ssh(server) {
+        let known_hosts = endpoint(server).and_then(|(_, host)| known_hosts(&host)).map(String::from);
+        return Some(Auth::Ssh { private_key: credential.token_any(), known_hosts });
+    }
+
+    match credential.scheme() {
+        Scheme::Basic => Some(Auth::Basic { username: credential.username_any(), password: credential.password_any() }),
+        Scheme::Bearer => {
+            let (_, host) = endpoint(server)?;
+            let username = Some(credential.username_any()).filter(|username| !username.is_empty());
+            let username = username.unwrap_or_else(|| {
+                Provider::of(&host).map_or(DEFAULT_USERNAME, |provider| provider.username()).to_string()
+            });
+            Some(Auth::Basic { username, password: credential.token_any() })
+        }
+        Scheme::Unknown => None,
+    }
+}
+
+/// Returns the host keys of the well-known host, in the format of `known_hosts`.
+pub fn known_hosts(host: &str) -> Option<&'static str> {
+    KNOWN_HOSTS.iter().find(|(known, _)| *known == host).map(|(_, keys)| *keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint() {
+        let https = Some(("https".to_string(), "github.com".to_string()));
+        let ssh = Some(("ssh".to_string(), "github.com".to_string()));
+        assert_eq!(endpoint("https://GitHub.com/amphitheatre-app/amp-example-go"), https);
+        assert_eq!(endpoint("git@github.com:amphitheatre-app/amp-example-go.git"), ssh);
+        assert_eq!(endpoint("ssh://git@github.com/amphitheatre-app/amp-example-go.git"), ssh);
+        assert_eq!(endpoint("amp-example-go"), None);
+    }
+
+    #[test]
+    fn test_https() {
+        let expected = "https://github.com/amphitheatre-app/amp-example-go.git";
+        assert_eq!(https("git@github.com:amphitheatre-app/amp-example-go.git"), expected);
+        assert_eq!(https("ssh://git@github.com/amphitheatre-app/amp-example-go.git"), expected);
+        assert_eq!(https(expected), expected);
+    }
+
+    #[test]
+    fn test_secret_name_and_kpack_server() {
+        let repo = "git@gitlab.com:amphitheatre-app/amp-example-go.git";
+        assert_eq!(secret_name(repo).unwrap(), "amp-repo-credentials-ssh-gitlab.com");
+        assert_eq!(kpack_server(repo).unwrap(), "git@gitlab.com");
+
+        let repo = "https://gitlab.com/amphitheatre-app/amp-example-go";
+        assert_eq!(secret_name(repo).unwrap(), "amp-repo-credentials-https-gitlab.com");
+        assert_eq!(kpack_server(repo).unwrap(), "https://gitlab.com");
+    }
+
+    #[test]
+    fn test_provider() {
+        assert_eq!(Provider::of("github.com").map(|p| p.username()), Some("x-access-token"));
+        assert_eq!(Provider::of("gitlab.example.com").map(|p| p.username()), Some("oauth2"));
+        assert_eq!(Provider::of("bitbucket.org").map(|p| p.username()), Some("x-token-auth"));
+        assert_eq!(Provider::of("git.example.com"), None);
+    }
+
+    #[test]
+    fn test_known_hosts() {
+        assert!(known_hosts("github.com").unwrap().starts_with("github.com ssh-ed25519 "));
+        assert_eq!(known_hosts("git.example.com"), None);
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use amp_common::resource::{Actor, Playbook};
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_openapi::api::core::v1::{Event, Pod};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::error::{Error, Result};
+
+/// The annotation of actor holding its self-healing policy as a JSON document,
+/// e.g. `{"restart_after": 3, "recreate_after": 5, "escalate_after": 3}`.
+pub const HEALING_ANNOTATION: &str = "amphitheatre.app/healing";
+
+/// The annotation of actor recording the history of the remediations as a JSON document.
+pub const REMEDIATIONS_ANNOTATION: &str = "amphitheatre.app/remediations";
+
+/// The condition type of playbook reporting its actors can't be healed.
+pub const DEGRADED_CONDITION_TYPE: &str = "Degraded";
+
+/// The number of the latest remediations kept in the history.
+const HISTORY_LIMIT: usize = 20;
+
+/// The delay after the first remediation before the next one, it doubles
+/// with each remediation until the actor recovers.
+const BACKOFF: Duration = Duration::from_secs(60);
+
+/// The maximum delay between the remediations.
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// The self-healing policy of actor, the remediations are disabled if not set.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct HealingPolicy {
+    /// Restart the pod after its probes failed this many times.
+    pub restart_after: Option<i32>,
+    /// Recreate the pods of deployment after a container crashed this many times in a loop.
+    pub recreate_after: Option<i32>,
+    /// Mark the playbook degraded after this many remediations without recovery.
+    pub escalate_after: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    RestartPod,
+    RecreateDeployment,
+    RecreateStatefulSet,
+    Escalate,
+    /// The pods are healthy again after the remediations.
+    Recover,
+}
+
+/// A remediation taken on the actor.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Remediation {
+    pub action: Action,
+    /// The name of the pod, workload or playbook remediated.
+    pub target: String,
+    pub reason: String,
+    /// The time of the remediation, in RFC 3339 format.
+    pub time: String,
+}
+
+/// The health of a pod of actor observed by the watcher.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PodHealth {
+    pub name: String,
+    /// The number of the failed probes reported in the events of pod.
+    pub probe_failures: i32,
+    /// The number of restarts of the containers in crash loop back-off.
+    pub crash_loops: i32,
+}
+
+/// Returns the self-healing policy of actor, if any.
+pub fn policy(actor: &Actor) -> Result<Option<HealingPolicy>> {
+    match actor.annotations().get(HEALING_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Returns the history of the remediations taken on actor, ordered from the oldest.
+pub fn history(actor: &Actor) -> Vec<Remediation> {
+    let content = actor.annotations().get(REMEDIATIONS_ANNOTATION);
+    content.and_then(|content| serde_json::from_str(content).ok()).unwrap_or_default()
+}
+
+/// Decide the remediation for the pods of actor, the crash loops are
+/// remediated first as restarting a single pod doesn't help them. The pods
+/// of the workload are recreated, the Deployment or the StatefulSet if stateful.
+pub fn decide(policy: &HealingPolicy, workload: &str, stateful: bool, pods: &[PodHealth]) -> Option<Remediation> {
+    let time = Utc::now().to_rfc3339();
+
+    if let Some(threshold) = policy.recreate_after {
+        if let Some(pod) = pods.iter().find(|pod| pod.crash_loops >= threshold) {
+            let reason = format!("Pod {} crashed {} times in a loop", pod.name, pod.crash_loops);
+            let action = if stateful { Action::RecreateStatefulSet } else { Action::RecreateDeployment };
+            return Some(Remediation { action, target: workload.into(), reason, time });
+        }
+    }
+
+    if let Some(threshold) = policy.restart_after {
+        if let Some(pod) = pods.iter().find(|pod| pod.probe_failures >= threshold) {
+            let reason = format!("Pod {} failed its probes {} times", pod.name, pod.probe_failures);
+            return Some(Remediation { action: Action::RestartPod, target: pod.name.clone(), reason, time });
+        }
+    }
+
+    None
+}
+
+/// Returns true if the remediations since the last escalation or recovery reached the threshold.
+pub fn escalate(policy: &HealingPolicy, history: &[Remediation]) -> bool {
+    let Some(threshold) = policy.escalate_after else {
+        return false;
+    };
+    let remediations = history
+        .iter()
+        .rev()
+        .take_while(|remediation| !matches!(remediation.action, Action::Escalate | Action::Recover))
+        .count();
+
+    remediations >= threshold
+}
+
+/// Returns the remediations of the pods since the last recovery, ordered from the latest.
+fn unrecovered(history: &[Remediation]) -> impl Iterator<Item = &Remediation> {
+    let since = history.iter().rev().take_while(|remediation| remediation.action != Action::Recover);
+    since.filter(|remediation| remediation.action != Action::Escalate)
+}
+
+/// Returns true if the backoff after the last remediation has passed, it
+/// doubles with each remediation since the last recovery.
+pub fn due(history: &[Remediation], now: DateTime<Utc>) -> bool {
+    let remediations: Vec<&Remediation> = unrecovered(history).collect();
+    let Some(last) = remediations.first() else {
+        return true;
+    };
+    let Ok(time) = DateTime::parse_from_rfc3339(&last.time) else {
+        return true;
+    };
+
+    let exponent = (remediations.len() - 1).min(10) as u32;
+    let backoff = BACKOFF.saturating_mul(2u32.pow(exponent)).min(MAX_BACKOFF);
+    (now - time.with_timezone(&Utc)).to_std().unwrap_or_default() >= backoff
+}
+
+/// Returns true if the pods are healthy again after the remediations.
+pub fn recovered(history: &[Remediation], pods: &[PodHealth]) -> bool {
+    let healthy = !pods.is_empty() && pods.iter().all(|pod| pod.probe_failures == 0 && pod.crash_loops == 0);
+    healthy && unrecovered(history).next().is_some()
+}
+
+/// Returns true if the playbook is marked degraded by the escalation of actor.
+pub fn degraded_by(playbook: &Playbook, actor: &Actor) -> bool {
+    let conditions = playbook.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    let prefix = format!("Actor {} ", actor.name_any());
+    conditions.iter().any(|condition| {
+        condition.type_ == DEGRADED_CONDITION_TYPE
+            && condition.reason == "RemediationsExhausted"
+            && condition.message.starts_with(&prefix)
+    })
+}
+
+/// Observe the health of the pods of actor from their statuses and events.
+pub async fn observe(client: &Client, actor: &Actor) -> Result<Vec<PodHealth>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    let params = ListParams::default().labels(&format!("amphitheatre.app/character={}", actor.name_any()));
+    let pods = api.list(&params).await.map_err(Error::KubeError)?;
+
+    let events: Api<Event> = Api::namespaced(client.clone(), &namespace);
+    let mut healths = vec![];
+    for pod in pods.items {
+        let name = pod.name_any();
+        let containers = pod.status.and_then(|status| status.container_statuses).unwrap_or_default();
+        let crash_loops = containers
+            .iter()
+            .filter(|c| {
+                let waiting = c.state.as_ref().and_then(|state| state.waiting.as_ref());
+                waiting.and_then(|waiting| waiting.reason.as_deref()) == Some("CrashLoopBackOff")
+            })
+            .map(|c| c.restart_count)
+            .max()
+            .unwrap_or_default();
+
+        let params = ListParams::default().fields(&format!("involvedObject.name={},reason=Unhealthy", name));
+        let unhealthy = events.list(&params).await.map_err(Error::KubeError)?;
+        let probe_failures = unhealthy.items.iter().map(|event| event.count.unwrap_or(1)).sum();
+
+        healths.push(PodHealth { name, probe_failures, crash_loops });
+    }
+    debug!("The health of pods of actor {}: {:?}", actor.name_any(), healths);
+
+    Ok(healths)
+}
+
+/// Take the remediation on the pod or deployment of actor.
+pub async fn remediate(client: &Client, actor: &Actor, remediation: &Remediation) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+
+    match remediation.action {
+        Action::RestartPod => {
+            let api: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+            api.delete(&remediation.target, &DeleteParams::default()).await.map_err(Error::KubeError)?;
+        }
+        Action::RecreateDeployment | Action::RecreateStatefulSet => {
+            // Same as `kubectl rollout restart`, all the pods are recreated.
+            let patch = json!({"spec": {"template": {"metadata": {"annotations": {
+                "kubectl.kubernetes.io/restartedAt": remediation.time,
+            }}}}});
+            let (name, params, patch) = (&remediation.target, &PatchParams::default(), &Patch::Merge(&patch));
+            match remediation.action {
+                Action::RecreateStatefulSet => {
+                    let api: Api<StatefulSet> = Api::namespaced(client.clone(), &namespace);
+                    api.patch(name, params, patch).await.map_err(Error::KubeError)?;
+                }
+                _ => {
+                    let api: Api<Deployment> = Api::namespaced(client.clone(), &namespace);
+                    api.patch(name, params, patch).await.map_err(Error::KubeError)?;
+                }
+            }
+        }
+        Action::Escalate | Action::Recover => {}
+    }
+    info!("Remediated actor {} with {:?}: {}", actor.name_any(), remediation.action, remediation.reason);
+
+    Ok(())
+}
+
+/// Append the remediations to the history of actor, only the latest ones are kept.
+pub async fn record(client: &Client, actor: &Actor, remediations: Vec<Remediation>) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let mut history = history(actor);
+    history.extend(remediations);
+    let skipped = history.len().saturating_sub(HISTORY_LIMIT);
+    let content = serde_json::to_string(&history[skipped..]).map_err(Error::SerializationError)?;
+
+    let annotations = BTreeMap::from([(REMEDIATIONS_ANNOTATION.to_string(), content)]);
+    let patch = json!({"metadata": { "annotations": annotations }});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+/// Build the condition reporting the playbook is degraded.
+pub fn degraded(message: String) -> Condition {
+    Condition {
+        type_: DEGRADED_CONDITION_TYPE.into(),
+        status: "True".into(),
+        reason: "RemediationsExhausted".into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod(name: &str, probe_failures: i32, crash_loops: i32) -> PodHealth {
+        PodHealth { name: name.into(), probe_failures, crash_loops }
+    }
+
+    fn remediation(action: Action) -> Remediation {
+        Remediation { action, target: "web".into(), reason: String::new(), time: String::new() }
+    }
+
+    fn remediated(action: Action, minutes_ago: i64) -> Remediation {
+        let time = Utc::now() - k8s_openapi::chrono::Duration::minutes(minutes_ago);
+        Remediation { time: time.to_rfc3339(), ..remediation(action) }
+    }
+
+    #[test]
+    fn test_decide_restart_pod() {
+        let policy = HealingPolicy { restart_after: Some(3), ..Default::default() };
+
+        let remediation = decide(&policy, "web", false, &[pod("web-1", 1, 0), pod("web-2", 3, 0)]).unwrap();
+        assert_eq!(remediation.action, Action::RestartPod);
+        assert_eq!(remediation.target, "web-2");
+
+        assert_eq!(decide(&policy, "web", false, &[pod("web-1", 2, 10)]), None);
+    }
+
+    #[test]
+    fn test_decide_crash_loops_first() {
+        let policy = HealingPolicy { restart_after: Some(3), recreate_after: Some(5), ..Default::default() };
+
+        let remediation = decide(&policy, "web", false, &[pod("web-1", 3, 0), pod("web-2", 0, 5)]).unwrap();
+        assert_eq!(remediation.action, Action::RecreateDeployment);
+        assert_eq!(remediation.target, "web");
+
+        let remediation = decide(&policy, "db", true, &[pod("db-0", 0, 5)]).unwrap();
+        assert_eq!(remediation.action, Action::RecreateStatefulSet);
+        assert_eq!(remediation.target, "db");
+    }
+
+    #[test]
+    fn test_due() {
+        let now = Utc::now();
+        assert!(due(&[], now));
+        assert!(!due(&[remediated(Action::RestartPod, 0)], now));
+        assert!(due(&[remediated(Action::RestartPod, 2)], now));
+
+        // The backoff doubles with each remediation since the last recovery.
+        let history = [remediated(Action::RestartPod, 10), remediated(Action::RestartPod, 3)];
+        assert!(due(&history, now));
+        let history = [remediated(Action::RestartPod, 10), remediated(Action::RestartPod, 1)];
+        assert!(!due(&history, now));
+
+        let history = [remediated(Action::RestartPod, 10), remediated(Action::Recover, 5)];
+        assert!(due(&history, now));
+    }
+
+    #[test]
+    fn test_recovered() {
+        let history = vec![remediation(Action::RestartPod)];
+        assert!(recovered(&history, &[pod("web-1", 0, 0)]));
+        assert!(!recovered(&history, &[pod("web-1", 1, 0)]));
+        assert!(!recovered(&history, &[]));
+        assert!(!recovered(&[], &[pod("web-1", 0, 0)]));
+        assert!(!recovered(&[remediation(Action::RestartPod), remediation(Action::Recover)], &[pod("web-1", 0, 0)]));
+    }
+
+    #[test]
+    fn test_escalate() {
+        let policy = HealingPolicy { escalate_after: Some(2), ..Default::default() };
+        let mut history = vec![remediation(Action::RestartPod)];
+        assert!(!escalate(&policy, &history));
+
+        history.push(remediation(Action::RecreateDeployment));
+        assert!(escalate(&policy, &history));
+
+        history.push(remediation(Action::Escalate));
+        assert!(!escalate(&policy, &history));
+        assert!(!escalate(&HealingPolicy::default(), &history));
+
+        history.extend([remediation(Action::RestartPod), remediation(Action::Recover)]);
+        history.push(remediation(Action::RestartPod));
+        assert!(!escalate(&policy, &history));
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let mut actor = Actor::new("web", Default::default());
+        assert_eq!(policy(&actor).unwrap(), None);
+
+        actor.annotations_mut().insert(HEALING_ANNOTATION.into(), r#"{"restart_after": 3}"#.into());
+        let parsed = policy(&actor).unwrap().unwrap();
+        assert_eq!(parsed.restart_after, Some(3));
+        assert_eq!(parsed.escalate_after, None);
+    }
+}
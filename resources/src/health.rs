@@ -0,0 +1,109 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::{Api, Client};
+use serde::Serialize;
+
+/// The CustomResourceDefinitions the controllers and apiserver depend on.
+pub const CRDS: &[&str] = &["actors.amphitheatre.app", "characters.amphitheatre.app", "playbooks.amphitheatre.app"];
+
+/// The result of a readiness check, reported by the `/readyz` endpoints.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub ready: bool,
+    /// Why the check failed, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Check {
+    pub fn new(name: &str, result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Check { name: name.into(), ready: true, message: None },
+            Err(message) => Check { name: name.into(), ready: false, message: Some(message) },
+        }
+    }
+}
+
+/// Check the API server of Kubernetes is reachable.
+pub async fn kubernetes(client: &Client) -> Check {
+    let result = client.apiserver_version().await.map(|_| ()).map_err(|err| err.to_string());
+    Check::new("kubernetes", result)
+}
+
+/// Check the CustomResourceDefinitions are installed and established.
+pub async fn crds(client: &Client) -> Check {
+    let api: Api<CustomResourceDefinition> = Api::all(client.clone());
+
+    let mut missing = vec![];
+    for name in CRDS {
+        match api.get_opt(name).await {
+            Ok(Some(crd)) if established(&crd) => {}
+            Ok(_) => missing.push(*name),
+            Err(err) => return Check::new("crds", Err(err.to_string())),
+        }
+    }
+
+    let result = match missing.is_empty() {
+        true => Ok(()),
+        false => Err(format!("CustomResourceDefinitions not established: {}", missing.join(", "))),
+    };
+    Check::new("crds", result)
+}
+
+/// Returns true if all the checks are ready.
+pub fn ready(checks: &[Check]) -> bool {
+    checks.iter().all(|check| check.ready)
+}
+
+fn established(crd: &CustomResourceDefinition) -> bool {
+    let conditions = crd.status.as_ref().and_then(|status| status.conditions.as_ref());
+    let mut conditions = conditions.into_iter().flatten();
+    conditions.any(|condition| condition.type_ == "Established" && condition.status == "True")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready() {
+        let checks = vec![Check::new("kubernetes", Ok(())), Check::new("crds", Ok(()))];
+        assert!(ready(&checks));
+        assert!(checks[0].message.is_none());
+
+        let checks = vec![Check::new("kubernetes", Ok(())), Check::new("crds", Err("missing".into()))];
+        assert!(!ready(&checks));
+        assert_eq!(checks[1].message.as_deref(), Some("missing"));
+    }
+
+    #[test]
+    fn test_established() {
+        let mut crd: CustomResourceDefinition = serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "actors.amphitheatre.app"},
+            "spec": {"group": "amphitheatre.app", "names": {"kind": "Actor", "plural": "actors"},
+                     "scope": "Namespaced", "versions": []},
+        }))
+        .unwrap();
+        assert!(!established(&crd));
+
+        crd.status = serde_json::from_value(serde_json::json!({
+            "conditions": [{"type": "Established", "status": "True"}],
+        }))
+        .unwrap();
+        assert!(established(&crd));
+    }
+}
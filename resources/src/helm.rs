@@ -0,0 +1,266 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Debug;
+
+use amp_common::resource::{Actor, CharacterSpec};
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::ServiceAccount;
+use k8s_openapi::api::rbac::v1::{RoleBinding, RoleRef, Subject};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::api::{ListParams, Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::containers::helm;
+use crate::error::{Error, Result};
+use crate::{hash, job, naming, rbac, LAST_APPLIED_HASH_KEY};
+
+/// The condition type of actor tracking the status of its chart release.
+pub const RELEASE_CONDITION_TYPE: &str = "HelmRelease";
+
+/// Delete the finished uninstall Job after this many seconds.
+const UNINSTALL_JOB_TTL: i32 = 300;
+
+/// The annotation of the workloads of the chart release recording their
+/// replicas before they were scaled to zero, they're scaled back to them.
+const HIBERNATED_REPLICAS_ANNOTATION: &str = "amphitheatre.app/hibernated-replicas";
+
+/// The dedicated ServiceAccount the release Jobs run under in the namespaces of
+/// playbooks, the actor pods run under the `default` one.
+pub const HELM_SERVICE_ACCOUNT: &str = "amp-helm";
+
+/// The Helm chart of a character, the charts are referenced by the repository of
+/// character in the native OCI form of Helm, e.g. `oci://registry-1.docker.io/bitnamicharts/redis@19.6.4`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chart {
+    /// The OCI chart reference.
+    pub name: String,
+    /// The chart version, the latest if not set.
+    pub version: Option<String>,
+}
+
+/// Returns the chart if the character is deployed by Helm.
+pub fn chart(character: &CharacterSpec) -> Option<Chart> {
+    parse(&character.meta.repository)
+}
+
+fn parse(repository: &str) -> Option<Chart> {
+    if !repository.starts_with("oci://") {
+        return None;
+    }
+
+    match repository.rsplit_once('@') {
+        Some((reference, version)) if !version.contains('/') => {
+            Some(Chart { name: reference.into(), version: Some(version.into()) })
+        }
+        _ => Some(Chart { name: repository.into(), version: None }),
+    }
+}
+
+/// Install or upgrade the chart release of the actor, the previous
+/// release job will be replaced if the actor has changed.
+pub async fn install(client: &Client, actor: &Actor, chart: &Chart) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(&actor.spec.name);
+
+    let expected_hash = hash(&actor.spec)?;
+    if let Some(job) = api.get_opt(&name).await.map_err(Error::KubeError)? {
+        if job.annotations().get(LAST_APPLIED_HASH_KEY) == Some(&expected_hash) {
+            debug!("The release Job {} is already up-to-date", name);
+            return Ok(());
+        }
+
+        job::replace(&api, &name).await?;
+        info!("Deleted the outdated release Job {}", name);
+    }
+
+    authorize(client, &namespace).await?;
+
+    let resource = job::new(actor, name, helm::pod(actor, &namespace, chart)?)?;
+    let job = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+    info!("Created release Job: {}", job.name_any());
+
+    Ok(())
+}
+
+/// Check if the chart release of the actor is deployed, returns an error if it failed.
+pub async fn released(client: &Client, actor: &Actor) -> Result<bool> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(&actor.spec.name);
+
+    let Some(status) = api.get_opt(&name).await.map_err(Error::KubeError)?.and_then(|job| job.status) else {
+        debug!("Not found the status of release Job {}", name);
+        return Ok(false);
+    };
+    if status.failed >= Some(1) {
+        return Err(Error::HelmReleaseFailed(name));
+    }
+
+    Ok(status.succeeded >= Some(1))
+}
+
+/// Build the condition of the chart release.
+pub fn condition(released: bool, reason: &str, message: Option<String>) -> Condition {
+    Condition {
+        type_: RELEASE_CONDITION_TYPE.into(),
+        status: if released { "True".into() } else { "False".into() },
+        reason: reason.into(),
+        message: message.unwrap_or_default(),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// Uninstall the chart release of the removed actor. The uninstall Job is not owned
+/// by the actor, so it outlives it, and it's deleted a while after it's finished.
+pub async fn uninstall(client: &Client, actor: &Actor) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = naming::name(&[&actor.spec.name, "helm", "uninstall"]);
+
+    authorize(client, &namespace).await?;
+
+    let mut resource = job::new(actor, name, helm::uninstall(&actor.spec.name, &namespace))?;
+    resource.metadata.owner_references = None;
+    if let Some(spec) = resource.spec.as_mut() {
+        spec.ttl_seconds_after_finished = Some(UNINSTALL_JOB_TTL);
+    }
+    match api.create(&PostParams::default(), &resource).await {
+        Ok(job) => info!("Created uninstall Job: {}", job.name_any()),
+        // The release is being uninstalled already.
+        Err(kube::Error::Api(err)) if err.code == 409 => {}
+        Err(err) => return Err(Error::KubeError(err)),
+    }
+
+    Ok(())
+}
+
+/// Scale the Deployments and StatefulSets of the chart release of actor to
+/// zero, or back to their recorded replicas, e.g. as its playbook is hibernated.
+pub async fn scale(client: &Client, actor: &Actor, hibernate: bool) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let selector = format!("app.kubernetes.io/managed-by=Helm,app.kubernetes.io/instance={}", actor.spec.name);
+
+    let api: Api<Deployment> = Api::namespaced(client.clone(), &namespace);
+    resize(&api, &selector, hibernate, |d| d.spec.as_ref().and_then(|spec| spec.replicas)).await?;
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), &namespace);
+    resize(&api, &selector, hibernate, |s| s.spec.as_ref().and_then(|spec| spec.replicas)).await?;
+
+    Ok(())
+}
+
+async fn resize<K>(api: &Api<K>, selector: &str, hibernate: bool, replicas: fn(&K) -> Option<i32>) -> Result<()>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    for workload in api.list(&ListParams::default().labels(selector)).await.map_err(Error::KubeError)?.items {
+        let (recorded, replicas) = match (hibernate, workload.annotations().get(HIBERNATED_REPLICAS_ANNOTATION)) {
+            (true, None) => (Some(replicas(&workload).unwrap_or(1).to_string()), 0),
+            (false, Some(previous)) => (None, previous.parse::<i32>().unwrap_or(1)),
+            // It's scaled to zero already, or it's not scaled by the hibernation.
+            _ => continue,
+        };
+        let patch = json!({
+            "metadata": { "annotations": { HIBERNATED_REPLICAS_ANNOTATION: recorded }},
+            "spec": { "replicas": replicas },
+        });
+        api.patch(&workload.name_any(), &PatchParams::default(), &Patch::Merge(&patch))
+            .await
+            .map_err(Error::KubeError)?;
+        info!("Scaled the workload {} of the chart release {}", workload.name_any(), selector);
+    }
+
+    Ok(())
+}
+
+/// Grant the `admin` ClusterRole of the namespace to the dedicated helm ServiceAccount
+/// which runs the release Jobs, so the chart can create any namespaced resources.
+async fn authorize(client: &Client, namespace: &str) -> Result<()> {
+    let api: Api<ServiceAccount> = Api::namespaced(client.clone(), namespace);
+    if api.get_opt(HELM_SERVICE_ACCOUNT).await.map_err(Error::KubeError)?.is_none() {
+        let resource = ServiceAccount {
+            metadata: ObjectMeta {
+                name: Some(HELM_SERVICE_ACCOUNT.into()),
+                labels: Some(rbac::labels()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        match api.create(&PostParams::default(), &resource).await {
+            Ok(account) => info!("Created ServiceAccount {} in namespace {}", account.name_any(), namespace),
+            Err(kube::Error::Api(err)) if err.code == 409 => {}
+            Err(err) => return Err(Error::KubeError(err)),
+        }
+    }
+
+    let api: Api<RoleBinding> = Api::namespaced(client.clone(), namespace);
+    let resource = RoleBinding {
+        metadata: ObjectMeta {
+            name: Some(HELM_SERVICE_ACCOUNT.into()),
+            labels: Some(rbac::labels()),
+            ..Default::default()
+        },
+        role_ref: RoleRef {
+            api_group: "rbac.authorization.k8s.io".into(),
+            kind: "ClusterRole".into(),
+            name: "admin".into(),
+        },
+        subjects: Some(vec![Subject {
+            kind: "ServiceAccount".into(),
+            name: HELM_SERVICE_ACCOUNT.into(),
+            namespace: Some(namespace.into()),
+            ..Default::default()
+        }]),
+    };
+
+    let params = &PatchParams::apply("amp-controllers").force();
+    api.patch(HELM_SERVICE_ACCOUNT, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+/// The name of the job which installs the chart of actor.
+#[inline]
+pub fn job_name(actor: &str) -> String {
+    naming::name(&[actor, "helm"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chart() {
+        assert_eq!(
+            parse("oci://registry-1.docker.io/bitnamicharts/redis@19.6.4"),
+            Some(Chart {
+                name: "oci://registry-1.docker.io/bitnamicharts/redis".into(),
+                version: Some("19.6.4".into())
+            })
+        );
+        assert_eq!(
+            parse("oci://registry-1.docker.io/bitnamicharts/postgresql"),
+            Some(Chart { name: "oci://registry-1.docker.io/bitnamicharts/postgresql".into(), version: None })
+        );
+        assert_eq!(parse("https://github.com/amphitheatre-app/amp-example-go"), None);
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use amp_common::resource::Playbook;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::{DateTime, Duration, Utc};
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+use tracing::{debug, info};
+
+use super::error::{Error, Result};
+use super::playbook::{ARCHIVED_ANNOTATION, LAST_ACTIVITY_ANNOTATION};
+use super::{
+    actor, cronjob, deployment, helm, namespace, playbook, replicas, statefulset, trash, REQUESTED_AT_ANNOTATION,
+};
+
+/// The annotation recording the time the playbook was hibernated, in RFC 3339
+/// format, its actors are scaled to zero until it's woken up.
+pub const HIBERNATED_ANNOTATION: &str = "amphitheatre.app/hibernated";
+
+/// The condition type of playbook reporting whether it's hibernated.
+pub const HIBERNATED_CONDITION_TYPE: &str = "Hibernated";
+
+/// The activities within this many seconds of the recorded one are not
+/// recorded again, to avoid patching the playbook on every request.
+const ACTIVITY_RESOLUTION: i64 = 60;
+
+/// Why the playbook is hibernated or woken up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reason {
+    /// It's idle, or used again.
+    Activity,
+    /// It's out of or in its uptime schedule.
+    Scheduled,
+    /// It's stopped or started explicitly.
+    Requested,
+    /// It's deleted into the trash, or restored from it.
+    Deleted,
+}
+
+/// Returns true if the playbook is hibernated.
+pub fn hibernated(playbook: &Playbook) -> bool {
+    playbook.annotations().contains_key(HIBERNATED_ANNOTATION)
+}
+
+/// Returns true if the activity at the given time should be recorded, the
+/// recorded one is older than the resolution.
+pub fn outdated(playbook: &Playbook, now: DateTime<Utc>) -> bool {
+    let recorded = playbook
+        .annotations()
+        .get(LAST_ACTIVITY_ANNOTATION)
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|time| time.with_timezone(&Utc));
+
+    recorded.map_or(true, |recorded| now - recorded >= Duration::seconds(ACTIVITY_RESOLUTION))
+}
+
+/// Record the playbook is used now, and wake it up if it's hibernated.
+pub async fn touch(client: &Client, playbook: &Playbook) -> Result<()> {
+    // The deleted playbook is only woken up by restoring it from the trash.
+    if trash::deleted(playbook).is_some() {
+        return Ok(());
+    }
+    if hibernated(playbook) {
+        return wake(client, playbook, Reason::Activity).await;
+    }
+
+    let now = Utc::now();
+    if outdated(playbook, now) {
+        let api: Api<Playbook> = Api::all(client.clone());
+        let patch = json!({"metadata": { "annotations": { LAST_ACTIVITY_ANNOTATION: now.to_rfc3339() }}});
+        api.patch(&playbook.name_any(), &PatchParams::default(), &Patch::Merge(&patch))
+            .await
+            .map_err(Error::KubeError)?;
+        debug!("Recorded the activity of playbook {}", playbook.name_any());
+    }
+
+    Ok(())
+}
+
+/// Hibernate the playbook, its actors are scaled to zero until it's woken up.
+pub async fn hibernate(client: &Client, playbook: &Playbook, reason: Reason) -> Result<()> {
+    scale_to_zero(client, playbook).await?;
+
+    let api: Api<Playbook> = Api::all(client.clone());
+    let now = Utc::now().to_rfc3339();
+    let patch = json!({"metadata": { "annotations": { HIBERNATED_ANNOTATION: now, REQUESTED_AT_ANNOTATION: now }}});
+    api.patch(&playbook.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    playbook::upsert_condition(client, playbook, condition(true, reason)).await?;
+    info!("Hibernated the playbook {} ({:?})", playbook.name_any(), reason);
+
+    Ok(())
+}
+
+/// Wake the hibernated or archived playbook up, its actors are scaled back to
+/// their desired replicas, and it's recorded as used now.
+pub async fn wake(client: &Client, playbook: &Playbook, reason: Reason) -> Result<()> {
+    for actor in actor::list_of(client, playbook).await? {
+        if helm::chart(&actor.spec.character).is_some() {
+            helm::scale(client, &actor, false).await?;
+            continue;
+        }
+        if let Some(schedule) = cronjob::schedule(&actor)? {
+            let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+            if cronjob::exists(client, &namespace, &cronjob::name(&actor)).await? {
+                cronjob::suspend(client, &namespace, &cronjob::name(&actor), schedule.suspend).await?;
+            }
+            continue;
+        }
+        replicas::restore(client, &actor).await?;
+    }
+
+    let api: Api<Playbook> = Api::all(client.clone());
+    let now = Utc::now().to_rfc3339();
+    let patch = json!({"metadata": { "annotations": {
+        HIBERNATED_ANNOTATION: null,
+        ARCHIVED_ANNOTATION: null,
+        LAST_ACTIVITY_ANNOTATION: now,
+        REQUESTED_AT_ANNOTATION: now,
+    }}});
+    api.patch(&playbook.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    playbook::upsert_condition(client, playbook, condition(false, reason)).await?;
+    info!("Woke the playbook {} up ({:?})", playbook.name_any(), reason);
+
+    Ok(())
+}
+
+/// Scale all the actors of the playbook to zero replicas, the workloads of
+/// their Helm charts are scaled as well, and their CronJobs are suspended.
+pub async fn scale_to_zero(client: &Client, playbook: &Playbook) -> Result<()> {
+    let namespace = namespace::of(playbook);
+    let list = actor::list_of(client, playbook).await?;
+    for actor in &list {
+        if helm::chart(&actor.spec.character).is_some() {
+            helm::scale(client, actor, true).await?;
+        } else if cronjob::schedule(actor)?.is_some()
+            && cronjob::exists(client, &namespace, &cronjob::name(actor)).await?
+        {
+            info!("Suspend the idle actor {} in {}", actor.name_any(), namespace);
+            cronjob::suspend(client, &namespace, &cronjob::name(actor), true).await?;
+        }
+    }
+
+    // The namespace may be shared with other playbooks, only their own actors are scaled there.
+    let actors: HashSet<String> = list.iter().map(|a| a.name_any()).collect();
+    let owned = |name: String| namespace::dedicated(playbook) || actors.contains(&name);
+    for deployment in deployment::list(client, &namespace).await? {
+        if !owned(deployment.name_any()) {
+            continue;
+        }
+        if deployment.spec.as_ref().and_then(|spec| spec.replicas) != Some(0) {
+            info!("Scale the idle actor {} in {} to zero", deployment.name_any(), namespace);
+            deployment::scale(client, &namespace, &deployment.name_any(), 0).await?;
+        }
+    }
+    for statefulset in statefulset::list(client, &namespace).await? {
+        if !owned(statefulset.name_any()) {
+            continue;
+        }
+        if statefulset.spec.as_ref().and_then(|spec| spec.replicas) != Some(0) {
+            info!("Scale the idle actor {} in {} to zero", statefulset.name_any(), namespace);
+            statefulset::scale(client, &namespace, &statefulset.name_any(), 0).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the condition reporting whether the playbook is hibernated.
+pub fn condition(hibernated: bool, reason: Reason) -> Condition {
+    let (reason, message) = match (hibernated, reason) {
+        (true, Reason::Activity) => ("Idle", "The actors are scaled to zero as the playbook is idle"),
+        (true, Reason::Scheduled) => ("Scheduled", "The actors are scaled to zero out of the uptime schedule"),
+        (true, Reason::Requested) => ("Stopped", "The actors are scaled to zero as the playbook is stopped"),
+        (false, Reason::Activity) => ("Used", "The actors are scaled back as the playbook is used"),
+        (false, Reason::Scheduled) => ("Scheduled", "The actors are scaled back in the uptime schedule"),
+        (false, Reason::Requested) => ("Started", "The actors are scaled back as the playbook is started"),
+        (true, Reason::Deleted) => ("Deleted", "The actors are scaled to zero as the playbook is deleted"),
+        (false, Reason::Deleted) => ("Restored", "The actors are scaled back as the playbook is restored"),
+    };
+    let status = if hibernated { "True" } else { "False" };
+
+    Condition {
+        type_: HIBERNATED_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: reason.into(),
+        message: message.into(),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::PlaybookSpec;
+
+    #[test]
+    fn test_outdated() {
+        let now = Utc::now();
+        let mut playbook = Playbook::new("test", PlaybookSpec::default());
+        assert!(outdated(&playbook, now));
+
+        let recorded = (now - Duration::seconds(30)).to_rfc3339();
+        playbook.annotations_mut().insert(LAST_ACTIVITY_ANNOTATION.into(), recorded);
+        assert!(!outdated(&playbook, now));
+
+        let recorded = (now - Duration::seconds(ACTIVITY_RESOLUTION)).to_rfc3339();
+        playbook.annotations_mut().insert(LAST_ACTIVITY_ANNOTATION.into(), recorded);
+        assert!(outdated(&playbook, now));
+    }
+
+    #[test]
+    fn test_condition() {
+        let hibernated = condition(true, Reason::Activity);
+        assert_eq!(hibernated.type_, HIBERNATED_CONDITION_TYPE);
+        assert_eq!((hibernated.status.as_str(), hibernated.reason.as_str()), ("True", "Idle"));
+
+        let woken = condition(false, Reason::Scheduled);
+        assert_eq!((woken.status.as_str(), woken.reason.as_str()), ("False", "Scheduled"));
+    }
+}
@@ -0,0 +1,369 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use amp_common::config::{Credential, Credentials};
+use amp_common::resource::{Actor, ActorSpec};
+use k8s_openapi::api::core::v1::{ContainerPort, ServicePort};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client, Resource, ResourceExt};
+use oci_distribution::secrets::RegistryAuth;
+use oci_distribution::Reference;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::error::{Error, Result};
+use crate::{actor, helm};
+
+/// The condition type of actor recording the ports inferred from its image in its message as
+/// a JSON document, e.g. `{"image": "harbor.amp.io/web@sha256:...", "ports": ["8080/tcp"]}`.
+pub const PORTS_INFERRED_CONDITION_TYPE: &str = "PortsInferred";
+
+/// The annotation of actor pinning the digest its prebuilt image must resolve to, e.g. `sha256:...`.
+pub const DIGEST_ANNOTATION: &str = "amphitheatre.app/image-digest";
+
+/// The annotation of actor requiring its prebuilt image to be signed by cosign, the value is `true`.
+pub const VERIFY_SIGNATURE_ANNOTATION: &str = "amphitheatre.app/verify-signature";
+
+/// The annotation of actor recording the digest of its built image as a JSON document,
+/// e.g. `{"image": "harbor.amp.io/web:abc123", "digest": "sha256:..."}`.
+pub const BUILT_DIGEST_ANNOTATION: &str = "amphitheatre.app/built-digest";
+
+/// The condition type of actor reporting the registry of its image can't be reached,
+/// it's distinct from the `Degraded` of the self-healing, which is about the pods.
+pub const REGISTRY_AVAILABLE_CONDITION_TYPE: &str = "RegistryAvailable";
+
+/// The ports inferred from the deployed image of actor.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+struct Inferred {
+    image: String,
+    ports: Vec<String>,
+}
+
+/// The digest the image of actor resolved to after its build.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+struct Built {
+    image: String,
+    digest: String,
+}
+
+/// A port exposed by the image, e.g. `8080/tcp`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExposedPort {
+    pub port: i32,
+    pub protocol: String,
+}
+
+impl ExposedPort {
+    pub fn container_port(&self) -> ContainerPort {
+        ContainerPort { container_port: self.port, protocol: Some(self.protocol.clone()), ..Default::default() }
+    }
+
+    pub fn service_port(&self) -> ServicePort {
+        ServicePort {
+            name: Some(format!("{}-{}", self.protocol.to_lowercase(), self.port)),
+            port: self.port,
+            protocol: Some(self.protocol.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+impl FromStr for ExposedPort {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (port, protocol) = s.trim().split_once('/').unwrap_or((s.trim(), "tcp"));
+        let port = port.parse().map_err(|_| Error::InvalidExposedPort(s.to_string()))?;
+
+        Ok(ExposedPort { port, protocol: protocol.to_uppercase() })
+    }
+}
+
+impl Display for ExposedPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.port, self.protocol.to_lowercase())
+    }
+}
+
+/// Inspect the `EXPOSE` ports from the config blob of the image.
+pub async fn exposed_ports(image: &str, credentials: &Credentials) -> Result<Vec<ExposedPort>> {
+    let reference: Reference = image.parse().map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))?;
+    let client = oci_distribution::Client::default();
+
+    let auth = auth(&reference, credentials);
+    let (_, _, config) = client
+        .pull_manifest_and_config(&reference, &auth)
+        .await
+        .map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))?;
+
+    parse(&config)
+}
+
+/// Parse the exposed ports from the image config.
+fn parse(config: &str) -> Result<Vec<ExposedPort>> {
+    let config: Value = serde_json::from_str(config).map_err(Error::SerializationError)?;
+    let Some(ports) = config.pointer("/config/ExposedPorts").and_then(|v| v.as_object()) else {
+        return Ok(vec![]);
+    };
+
+    let mut ports = ports.keys().map(|key| key.parse()).collect::<Result<Vec<ExposedPort>>>()?;
+    ports.sort_by_key(|p| p.port);
+
+    Ok(ports)
+}
+
+/// Returns true if the actor is a published image without a repository, there is nothing to build.
+pub fn prebuilt(spec: &ActorSpec) -> bool {
+    !spec.image.is_empty() && !spec.live && spec.source.is_none() && helm::chart(&spec.character).is_none()
+}
+
+/// Verify the image of actor resolves to its pinned digest, and it's signed
+/// if required, returns the reason if the verification failed.
+pub async fn verify(actor: &Actor, credentials: &Credentials) -> Result<Option<String>> {
+    let pinned = actor.annotations().get(DIGEST_ANNOTATION);
+    let signed = actor.annotations().get(VERIFY_SIGNATURE_ANNOTATION).is_some_and(|value| value == "true");
+    if pinned.is_none() && !signed {
+        return Ok(None);
+    }
+
+    let image = &actor.spec.image;
+    let reference: Reference = image.parse().map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))?;
+    let mut client = oci_distribution::Client::default();
+    let auth = auth(&reference, credentials);
+    let digest = client
+        .fetch_manifest_digest(&reference, &auth)
+        .await
+        .map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))?;
+
+    if let Some(pinned) = pinned.filter(|pinned| **pinned != digest) {
+        return Ok(Some(format!("The digest {} of image {} is not the pinned {}", digest, image, pinned)));
+    }
+    // The signature is pushed by cosign next to the image, tagged by its digest.
+    // See signing::sign for the signing side.
+    if signed && client.fetch_manifest_digest(&signature(&reference, &digest), &auth).await.is_err() {
+        return Ok(Some(format!("The image {} is not signed", image)));
+    }
+
+    Ok(None)
+}
+
+/// Pull the content of the artifact with a single layer of the media type,
+/// e.g. a manifest pushed by `oras push`.
+pub async fn artifact(reference: &str, media_type: &str, credentials: &Credentials) -> Result<Vec<u8>> {
+    let reference: Reference = reference.parse().map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))?;
+    let client = oci_distribution::Client::default();
+
+    let auth = auth(&reference, credentials);
+    let data = client
+        .pull(&reference, &auth, vec![media_type])
+        .await
+        .map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))?;
+
+    let layer = data.layers.into_iter().next();
+    layer
+        .map(|layer| layer.data)
+        .ok_or_else(|| Error::ImageInspectError(anyhow::anyhow!("{} has no layers", reference)))
+}
+
+/// Returns the reference of the cosign signature of the image digest, it's
+/// pushed next to the image, tagged by the digest, e.g. `sha256-abc.sig`.
+pub(crate) fn signature(reference: &Reference, digest: &str) -> Reference {
+    let tag = format!("{}.sig", digest.replace(':', "-"));
+    Reference::with_tag(reference.registry().into(), reference.repository().into(), tag)
+}
+
+/// Resolve the digest of the image from its registry.
+pub async fn digest(image: &str, credentials: &Credentials) -> Result<String> {
+    let reference: Reference = image.parse().map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))?;
+    let mut client = oci_distribution::Client::default();
+    let auth = auth(&reference, credentials);
+
+    client.fetch_manifest_digest(&reference, &auth).await.map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))
+}
+
+/// Record the digest of the built image of actor, it is deployed by the digest.
+pub async fn pin(client: &Client, actor: &Actor, digest: &str) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), namespace.as_str());
+
+    let built = Built { image: actor.spec.image.clone(), digest: digest.to_string() };
+    let value = serde_json::to_string(&built).map_err(Error::SerializationError)?;
+    let annotations = BTreeMap::from([(BUILT_DIGEST_ANNOTATION, value.as_str())]);
+    let patch = json!({ "metadata": { "annotations": annotations } });
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Pinned the image {} of Actor {} to {}", actor.spec.image, actor.name_any(), digest);
+
+    Ok(())
+}
+
+/// Returns the image of actor to deploy, it is referenced by the digest
+/// recorded after its build, as the tag may be overwritten in the registry.
+pub fn deployed(actor: &Actor) -> String {
+    let built = actor.annotations().get(BUILT_DIGEST_ANNOTATION);
+    let built = built.and_then(|content| serde_json::from_str::<Built>(content).ok());
+    match built.filter(|built| built.image == actor.spec.image) {
+        Some(built) => pinned(&built.image, &built.digest),
+        None => actor.spec.image.clone(),
+    }
+}
+
+/// Returns the digest of the built image of actor, if it's built from its current image.
+pub fn built(actor: &Actor) -> Option<String> {
+    let built = serde_json::from_str::<Built>(actor.annotations().get(BUILT_DIGEST_ANNOTATION)?).ok()?;
+    (built.image == actor.spec.image).then_some(built.digest)
+}
+
+pub(crate) fn pinned(image: &str, digest: &str) -> String {
+    match image.parse::<Reference>() {
+        Ok(reference) => {
+            Reference::with_digest(reference.registry().into(), reference.repository().into(), digest.into()).whole()
+        }
+        Err(_) => image.to_string(),
+    }
+}
+
+/// Use the default registry credential if the image is hosted on it.
+fn auth(reference: &Reference, credentials: &Credentials) -> RegistryAuth {
+    if let Some(credential) = credentials.default_registry() {
+        let server = credential.server.trim_start_matches("https://").trim_end_matches('/');
+        if reference.resolve_registry() == server || reference.registry() == server {
+            return RegistryAuth::Basic(credential.username_any(), credential.password_any());
+        }
+    }
+
+    RegistryAuth::Anonymous
+}
+
+/// Returns the ports inferred from the image of actor, empty if they're
+/// not inferred from its currently deployed image.
+pub fn inferred(actor: &Actor) -> Vec<ExposedPort> {
+    inferred_of(actor).unwrap_or_default()
+}
+
+/// Returns the ports inferred from the currently deployed image of actor, None if
+/// they're never inferred, or inferred from a previous image or a previous build.
+pub fn inferred_of(actor: &Actor) -> Option<Vec<ExposedPort>> {
+    let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    let condition = conditions.iter().find(|c| c.type_ == PORTS_INFERRED_CONDITION_TYPE)?;
+    let inferred = serde_json::from_str::<Inferred>(&condition.message).ok()?;
+    (inferred.image == deployed(actor)).then(|| inferred.ports.iter().filter_map(|port| port.parse().ok()).collect())
+}
+
+/// Record the ports inferred from the currently deployed image in the status of actor.
+pub async fn record(client: &Client, actor: &Actor, ports: &[ExposedPort]) -> Result<()> {
+    actor::upsert_condition(client, actor, condition(actor, ports)?).await?;
+    info!("Recorded the inferred ports {:?} of Actor {}", ports, actor.name_any());
+
+    Ok(())
+}
+
+fn condition(actor: &Actor, ports: &[ExposedPort]) -> Result<Condition> {
+    let inferred = Inferred { image: deployed(actor), ports: ports.iter().map(|p| p.to_string()).collect() };
+
+    Ok(Condition {
+        type_: PORTS_INFERRED_CONDITION_TYPE.into(),
+        status: "True".into(),
+        reason: "Inferred".into(),
+        message: serde_json::to_string(&inferred).map_err(Error::SerializationError)?,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: actor.meta().generation,
+    })
+}
+
+/// Set the ports inferred from the currently deployed image of actor, for the tests.
+#[cfg(test)]
+pub(crate) fn infer(actor: &mut Actor, ports: &[&str]) {
+    let ports: Vec<ExposedPort> = ports.iter().map(|port| port.parse().unwrap()).collect();
+    let conditions = vec![condition(actor, &ports).unwrap()];
+    actor.status = Some(serde_json::from_value(json!({ "conditions": conditions })).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_exposed_port() {
+        assert_eq!("8080/tcp".parse::<ExposedPort>().unwrap(), ExposedPort { port: 8080, protocol: "TCP".into() });
+        assert_eq!("53/udp".parse::<ExposedPort>().unwrap().protocol, "UDP");
+        assert_eq!("3000".parse::<ExposedPort>().unwrap().to_string(), "3000/tcp");
+        assert!("http/tcp".parse::<ExposedPort>().is_err());
+    }
+
+    #[test]
+    fn test_parse_image_config() {
+        let config = r#"{"config":{"ExposedPorts":{"9090/tcp":{},"8080/tcp":{}}}}"#;
+        let ports = parse(config).unwrap();
+        assert_eq!(ports.iter().map(|p| p.port).collect::<Vec<i32>>(), vec![8080, 9090]);
+
+        assert!(parse(r#"{"config":{}}"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_signature_reference() {
+        let reference: Reference = "ghcr.io/amphitheatre-app/web:v1".parse().unwrap();
+        let signature = signature(&reference, "sha256:abc");
+
+        assert_eq!(signature.whole(), "ghcr.io/amphitheatre-app/web:sha256-abc.sig");
+    }
+
+    #[test]
+    fn test_deployed_image() {
+        let spec = ActorSpec { image: "harbor.amp.io/amp/web:abc123".into(), ..Default::default() };
+        let mut actor = Actor::new("web", spec);
+        assert_eq!(deployed(&actor), "harbor.amp.io/amp/web:abc123");
+
+        let built = r#"{"image": "harbor.amp.io/amp/web:abc123", "digest": "sha256:def"}"#;
+        actor.annotations_mut().insert(BUILT_DIGEST_ANNOTATION.into(), built.into());
+        assert_eq!(deployed(&actor), "harbor.amp.io/amp/web@sha256:def");
+
+        // The digest of the previous image is not used.
+        actor.spec.image = "harbor.amp.io/amp/web:456789".into();
+        assert_eq!(deployed(&actor), "harbor.amp.io/amp/web:456789");
+    }
+
+    #[test]
+    fn test_prebuilt() {
+        let spec = ActorSpec { image: "nginx:1.27".into(), ..Default::default() };
+        assert!(prebuilt(&spec));
+
+        assert!(!prebuilt(&ActorSpec { live: true, ..spec.clone() }));
+        assert!(!prebuilt(&ActorSpec::default()));
+    }
+
+    #[test]
+    fn test_inferred_ports() {
+        let mut actor = Actor::new("test", ActorSpec { image: "web:v1".into(), ..Default::default() });
+        assert!(inferred(&actor).is_empty());
+        assert_eq!(inferred_of(&actor), None);
+
+        infer(&mut actor, &["8080/tcp", "53/udp"]);
+        assert_eq!(inferred(&actor).len(), 2);
+
+        // The ports are inferred again once the image is changed or rebuilt.
+        actor.spec.image = "web:v2".into();
+        assert_eq!(inferred_of(&actor), None);
+        actor.spec.image = "web:v1".into();
+        let content = r#"{"image": "web:v1", "digest": "sha256:abc"}"#;
+        actor.annotations_mut().insert(BUILT_DIGEST_ANNOTATION.into(), content.into());
+        assert_eq!(inferred_of(&actor), None);
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use amp_common::resource::{CharacterSpec, Playbook, Preface};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::ResourceExt;
+
+use crate::error::{Error, Result};
+
+/// The annotation of the playbook holding the prefaces of the included
+/// playbooks in JSON, their characters are merged into the playbook, e.g. a
+/// base infrastructure playbook included by a feature playbook.
+pub const INCLUDES_ANNOTATION: &str = "amphitheatre.app/includes";
+
+/// The type of the condition reporting the included playbooks failed to merge.
+pub const INCLUDED_CONDITION_TYPE: &str = "Included";
+
+/// Returns the prefaces of the playbooks included by the playbook, if any.
+pub fn of(playbook: &Playbook) -> Result<Vec<Preface>> {
+    match playbook.annotations().get(INCLUDES_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map_err(Error::SerializationError),
+        None => Ok(vec![]),
+    }
+}
+
+/// Merge the characters of the included playbooks after the ones of the
+/// playbook, returns an error if any of the names is declared more than once.
+pub fn merge(characters: Vec<CharacterSpec>, included: Vec<CharacterSpec>) -> Result<Vec<CharacterSpec>> {
+    let mut names = HashSet::new();
+    let mut merged = Vec::with_capacity(characters.len() + included.len());
+    for character in characters.into_iter().chain(included) {
+        if !names.insert(character.meta.name.clone()) {
+            return Err(Error::CharacterConflict(character.meta.name));
+        }
+        merged.push(character);
+    }
+
+    Ok(merged)
+}
+
+/// Build the condition reporting the included playbooks failed to merge.
+pub fn failed(message: String) -> Condition {
+    Condition {
+        type_: INCLUDED_CONDITION_TYPE.into(),
+        status: "False".into(),
+        reason: "IncludeFailed".into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(name: &str) -> CharacterSpec {
+        let mut character = CharacterSpec::default();
+        character.meta.name = name.into();
+        character
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = merge(vec![character("web")], vec![character("postgres"), character("redis")]).unwrap();
+        let names: Vec<&str> = merged.iter().map(|c| c.meta.name.as_str()).collect();
+        assert_eq!(names, vec!["web", "postgres", "redis"]);
+
+        let conflict = merge(vec![character("web")], vec![character("postgres"), character("web")]);
+        assert!(matches!(conflict, Err(Error::CharacterConflict(name)) if name == "web"));
+    }
+}
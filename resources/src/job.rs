@@ -13,22 +13,87 @@
 // limitations under the License.
 
 use std::collections::BTreeMap;
+use std::sync::OnceLock;
 
 use amp_common::resource::Actor;
 use k8s_openapi::api::batch::v1::{Job, JobSpec};
 use k8s_openapi::api::core::v1::{PodSpec, PodTemplateSpec};
-use kube::api::{Patch, PatchParams, PostParams};
+use kube::api::{DeleteParams, Patch, PatchParams, PostParams, PropagationPolicy};
 use kube::core::ObjectMeta;
 use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::error::{Error, Result};
-use crate::{hash, LAST_APPLIED_HASH_KEY};
+use crate::{cache, hash, naming, secret, LAST_APPLIED_HASH_KEY};
+
+/// The annotation of the actor holding its own job settings in JSON,
+/// overriding the defaults configured by the operator.
+pub const JOB_SETTINGS_ANNOTATION: &str = "amphitheatre.app/job-settings";
+
+/// The annotation of the actor holding the spec hash of its last completed
+/// build, the finished Job may be deleted before it's seen completed.
+pub const BUILT_ANNOTATION: &str = "amphitheatre.app/built";
+
+/// The minimum seconds to keep the finished Jobs, so that the controller can
+/// see them completed before they're deleted.
+pub const MIN_TTL_SECONDS_AFTER_FINISHED: i32 = 60;
+
+/// The job settings configured by the operator.
+static DEFAULTS: OnceLock<JobSettings> = OnceLock::new();
+
+/// The lifecycle of the Jobs created for the actors, e.g. the builds.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct JobSettings {
+    /// Delete the finished Job and its pods after this many seconds, they're kept if it's not set.
+    pub ttl_seconds_after_finished: Option<i32>,
+    /// The number of retries before the Job is marked failed, 0 if it's not set.
+    pub backoff_limit: Option<i32>,
+    /// Kill the Job if it's still active after this many seconds, unlimited if it's not set.
+    pub active_deadline_seconds: Option<i64>,
+}
+
+impl JobSettings {
+    /// Returns the settings with the fields set in the overrides replaced.
+    pub fn merge(&self, overrides: &JobSettings) -> JobSettings {
+        JobSettings {
+            ttl_seconds_after_finished: overrides.ttl_seconds_after_finished.or(self.ttl_seconds_after_finished),
+            backoff_limit: overrides.backoff_limit.or(self.backoff_limit),
+            active_deadline_seconds: overrides.active_deadline_seconds.or(self.active_deadline_seconds),
+        }
+    }
+
+    /// Set the lifecycle of the Job.
+    pub fn apply(&self, spec: &mut JobSpec) {
+        spec.ttl_seconds_after_finished =
+            self.ttl_seconds_after_finished.map(|ttl| ttl.max(MIN_TTL_SECONDS_AFTER_FINISHED));
+        spec.backoff_limit = Some(self.backoff_limit.unwrap_or(0));
+        spec.active_deadline_seconds = self.active_deadline_seconds;
+    }
+}
+
+/// Configure the default job settings of the actors.
+pub fn configure(defaults: JobSettings) {
+    tracing::info!("Configured the job settings: {:?}", defaults);
+    DEFAULTS.set(defaults).ok();
+}
+
+/// Returns the job settings of the actor, the defaults overridden by its own.
+pub fn settings(actor: &Actor) -> Result<JobSettings> {
+    let defaults = DEFAULTS.get().cloned().unwrap_or_default();
+    match actor.annotations().get(JOB_SETTINGS_ANNOTATION) {
+        Some(content) => {
+            let overrides: JobSettings = serde_json::from_str(content).map_err(Error::SerializationError)?;
+            Ok(defaults.merge(&overrides))
+        }
+        None => Ok(defaults),
+    }
+}
 
 pub async fn exists(client: &Client, actor: &Actor) -> Result<bool> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
-    let name = format!("{}-builder", actor.spec.name);
-
+    let name = naming::name(&[&actor.spec.name, "builder"]);
     Ok(api.get_opt(&name).await.map_err(Error::KubeError)?.is_some())
 }
 
@@ -36,7 +101,7 @@ pub async fn create(client: &Client, actor: &Actor, pod: PodSpec) -> Result<Job>
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
 
-    let resource = new(actor, pod)?;
+    let resource = new(actor, naming::name(&[&actor.spec.name, "builder"]), pod)?;
     let job = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
     tracing::info!("Created Job: {}", job.name_any());
 
@@ -46,16 +111,24 @@ pub async fn create(client: &Client, actor: &Actor, pod: PodSpec) -> Result<Job>
 pub async fn update(client: &Client, actor: &Actor, pod: PodSpec) -> Result<Job> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
-    let name = format!("{}-builder", actor.spec.name);
+    let name = naming::name(&[&actor.spec.name, "builder"]);
+
+    let expected_hash = hash(&actor.spec)?;
+
+    // The actor is reconciled again on the changes, so it's safe to skip the stale cached Job.
+    let cached = cache::job(&namespace, &name);
+    if let Some(job) = cached.filter(|job| job.annotations().get(LAST_APPLIED_HASH_KEY) == Some(&expected_hash)) {
+        tracing::debug!("The cached Job {} is already up-to-date", &name);
+        return Ok((*job).clone());
+    }
 
     let mut job = api.get(&name).await.map_err(Error::KubeError)?;
     tracing::debug!("The Job {} already exists", &name);
 
-    let expected_hash = hash(&actor.spec)?;
     let found_hash: String = job.annotations().get(LAST_APPLIED_HASH_KEY).map_or("".into(), |v| v.into());
 
     if found_hash != expected_hash {
-        let resource = new(actor, pod)?;
+        let resource = new(actor, name.clone(), pod)?;
         tracing::debug!("The updating Job resource:\n {:?}\n", resource);
 
         job = api
@@ -69,9 +142,17 @@ pub async fn update(client: &Client, actor: &Actor, pod: PodSpec) -> Result<Job>
     Ok(job)
 }
 
-/// Create a Job for build images
-fn new(actor: &Actor, pod: PodSpec) -> Result<Job> {
-    let name = format!("{}-builder", actor.spec.name);
+/// Delete the outdated Job with the given name, so that it can be created again
+/// from the new pod template, the pod template of Job is immutable.
+pub(crate) async fn replace(api: &Api<Job>, name: &str) -> Result<()> {
+    let params = DeleteParams { propagation_policy: Some(PropagationPolicy::Background), ..Default::default() };
+    api.delete(name, &params).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+/// Create a Job with the given name for the actor
+pub(crate) fn new(actor: &Actor, name: String, mut pod: PodSpec) -> Result<Job> {
     let owner_reference = actor.controller_owner_ref(&()).unwrap();
     let annotations = BTreeMap::from([(LAST_APPLIED_HASH_KEY.into(), hash(&actor.spec)?)]);
     let labels = BTreeMap::from([
@@ -79,22 +160,25 @@ fn new(actor: &Actor, pod: PodSpec) -> Result<Job> {
         ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
     ]);
 
+    pod.image_pull_secrets = Some(secret::image_pull_secrets(actor));
+    let mut spec = JobSpec {
+        template: PodTemplateSpec {
+            metadata: Some(ObjectMeta { labels: Some(labels.clone()), ..Default::default() }),
+            spec: Some(pod),
+        },
+        ..Default::default()
+    };
+    settings(actor)?.apply(&mut spec);
+
     Ok(Job {
         metadata: ObjectMeta {
             name: Some(name),
             owner_references: Some(vec![owner_reference]),
-            labels: Some(labels.clone()),
+            labels: Some(labels),
             annotations: Some(annotations),
             ..Default::default()
         },
-        spec: Some(JobSpec {
-            backoff_limit: Some(0),
-            template: PodTemplateSpec {
-                metadata: Some(ObjectMeta { labels: Some(labels), ..Default::default() }),
-                spec: Some(pod),
-            },
-            ..Default::default()
-        }),
+        spec: Some(spec),
         ..Default::default()
     })
 }
@@ -104,13 +188,73 @@ pub async fn completed(client: &Client, actor: &Actor) -> Result<bool> {
 
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
-    let name = format!("{}-builder", actor.spec.name);
-
-    if let Ok(Some(job)) = api.get_opt(&name).await {
-        tracing::debug!("Found Job {}", &name);
-        Ok(job.status.map_or(false, |s| s.succeeded >= Some(1)))
-    } else {
-        tracing::debug!("Not found Job {}", &name);
-        Ok(false)
+    let name = naming::name(&[&actor.spec.name, "builder"]);
+
+    match api.get_opt(&name).await.map_err(Error::KubeError)? {
+        Some(job) => {
+            tracing::debug!("Found Job {}", &name);
+            let succeeded = job.status.map_or(false, |s| s.succeeded >= Some(1));
+            if succeeded && !built(actor)? {
+                record(client, actor).await?;
+            }
+            Ok(succeeded)
+        }
+        None => {
+            // The finished Job may have been deleted after its TTL.
+            tracing::debug!("Not found Job {}", &name);
+            built(actor)
+        }
+    }
+}
+
+/// Returns true if the build of the current spec of actor has completed.
+pub fn built(actor: &Actor) -> Result<bool> {
+    let expected = hash(&actor.spec)?;
+    Ok(actor.annotations().get(BUILT_ANNOTATION).is_some_and(|found| *found == expected))
+}
+
+/// Record the completed build of the current spec of actor.
+async fn record(client: &Client, actor: &Actor) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+    let patch = json!({"metadata": { "annotations": { BUILT_ANNOTATION: hash(&actor.spec)? }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let defaults = JobSettings {
+            ttl_seconds_after_finished: Some(3600),
+            backoff_limit: Some(0),
+            active_deadline_seconds: Some(1800),
+        };
+        let overrides = JobSettings { backoff_limit: Some(2), ..Default::default() };
+
+        let settings = defaults.merge(&overrides);
+        assert_eq!(settings.ttl_seconds_after_finished, Some(3600));
+        assert_eq!(settings.backoff_limit, Some(2));
+        assert_eq!(settings.active_deadline_seconds, Some(1800));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut spec = JobSpec::default();
+        JobSettings::default().apply(&mut spec);
+        assert_eq!(spec.backoff_limit, Some(0));
+        assert_eq!(spec.ttl_seconds_after_finished, None);
+
+        let settings = JobSettings { ttl_seconds_after_finished: Some(3600), ..Default::default() };
+        settings.apply(&mut spec);
+        assert_eq!(spec.ttl_seconds_after_finished, Some(3600));
+
+        let settings = JobSettings { ttl_seconds_after_finished: Some(0), ..Default::default() };
+        settings.apply(&mut spec);
+        assert_eq!(spec.ttl_seconds_after_finished, Some(MIN_TTL_SECONDS_AFTER_FINISHED));
     }
 }
@@ -12,15 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::error::{Error, Result};
-use amp_common::{
-    config::{Credential, Credentials},
-    resource::CharacterSpec,
-    schema::BuildpacksConfig,
-};
+use crate::error::Result;
+use amp_common::{config::Credentials, resource::CharacterSpec, schema::BuildpacksConfig};
 use sha2::{Digest, Sha256};
 
 use super::encode_name;
+use crate::naming;
 
 pub trait BuildExt {
     fn builder_name(&self) -> String;
@@ -59,16 +56,7 @@ impl BuildExt for CharacterSpec {
     /// Returns the tag of the builder image
     fn builder_tag(&self, credentials: &Credentials) -> Result<String> {
         // Generate image name based on the current registry and character name & revision
-        if let Some(credential) = credentials.default_registry() {
-            let mut registry = credential.server.as_str();
-            if registry.eq("https://index.docker.io/v1/") {
-                registry = "index.docker.io";
-            }
-
-            Ok(format!("{}/{}/{}", registry, credential.username_any(), self.builder_name()))
-        } else {
-            Err(Error::NotFoundRegistries)
-        }
+        super::builder_tag(credentials, &self.builder_name())
     }
 
     /// Returns the name of the ClusterBuilder
@@ -87,7 +75,7 @@ impl BuildExt for CharacterSpec {
 
     /// Returns the name of the PVC
     fn pvc_name(&self) -> String {
-        format!("{}-pvc", self.meta.name)
+        naming::name(&[&self.meta.name, "pvc"])
     }
 
     /// Returns the buildpacks config
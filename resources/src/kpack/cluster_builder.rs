@@ -26,35 +26,33 @@ use kube::{Api, Client, ResourceExt};
 use serde_json::{from_value, json};
 use tracing::{debug, info};
 
-pub async fn exists(client: &Client, actor: &Actor) -> Result<bool> {
+pub async fn exists(client: &Client, name: &str) -> Result<bool> {
     let api: Api<DynamicObject> = Api::all_with(client.clone(), &api_resource());
-    let name = actor.spec.character.builder_name();
 
-    Ok(api.get_opt(&name).await.map_err(Error::KubeError)?.is_some())
+    Ok(api.get_opt(name).await.map_err(Error::KubeError)?.is_some())
 }
 
-pub async fn create(client: &Client, actor: &Actor, tag: &str, order: Vec<Order>) -> Result<DynamicObject> {
+pub async fn create(client: &Client, actor: &Actor, name: &str, tag: &str, order: Vec<Order>) -> Result<DynamicObject> {
     let api: Api<DynamicObject> = Api::all_with(client.clone(), &api_resource());
 
-    let resource = new(actor, tag, order).await?;
+    let resource = new(actor, name, tag, order).await?;
     let builder = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
     info!("Created ClusterBuilder: {}", builder.name_any());
 
     Ok(builder)
 }
 
-pub async fn update(client: &Client, actor: &Actor, tag: &str, order: Vec<Order>) -> Result<DynamicObject> {
+pub async fn update(client: &Client, actor: &Actor, name: &str, tag: &str, order: Vec<Order>) -> Result<DynamicObject> {
     let api: Api<DynamicObject> = Api::all_with(client.clone(), &api_resource());
 
-    let name = actor.spec.character.builder_name();
-    let mut builder = api.get(&name).await.map_err(Error::KubeError)?;
+    let mut builder = api.get(name).await.map_err(Error::KubeError)?;
     debug!("The ClusterBuilder \"{}\" already exists", name);
 
-    let resource = new(actor, tag, order).await?;
+    let resource = new(actor, name, tag, order).await?;
     if builder.data.pointer("/spec") != resource.data.pointer("/spec") {
         debug!("The updating ClusterBuilder resource:\n {:?}\n", resource);
         builder = api
-            .patch(&name, &PatchParams::apply("amp-controllers").force(), &Patch::Apply(&resource))
+            .patch(name, &PatchParams::apply("amp-controllers").force(), &Patch::Apply(&resource))
             .await
             .map_err(Error::KubeError)?;
 
@@ -69,13 +67,12 @@ fn api_resource() -> ApiResource {
     ApiResource::from_gvk(&GroupVersionKind::gvk("kpack.io", "v1alpha2", "ClusterBuilder"))
 }
 
-async fn new(actor: &Actor, tag: &str, order: Vec<Order>) -> Result<DynamicObject> {
-    let name = actor.spec.character.builder_name();
+async fn new(actor: &Actor, name: &str, tag: &str, order: Vec<Order>) -> Result<DynamicObject> {
     let resource = from_value(json!({
         "apiVersion": "kpack.io/v1alpha2",
         "kind": "ClusterBuilder",
         "metadata": {
-            "name": name.clone(),
+            "name": name,
             "labels": {
                 "amphitheatre.app/character": actor.spec.name.clone(),
                 "app.kubernetes.io/managed-by": "Amphitheatre",
@@ -104,14 +101,13 @@ async fn new(actor: &Actor, tag: &str, order: Vec<Order>) -> Result<DynamicObjec
     Ok(resource)
 }
 
-pub async fn ready(client: &Client, actor: &Actor) -> Result<bool> {
-    let name = actor.spec.character.builder_name();
+pub async fn ready(client: &Client, name: &str) -> Result<bool> {
     debug!("Check if the ClusterBuilder {} is ready", name);
 
     let api: Api<DynamicObject> = Api::all_with(client.clone(), &api_resource());
 
-    if let Some(builder) = api.get_opt(&name).await.map_err(Error::KubeError)? {
-        debug!("Found ClusterBuilder {}", &name);
+    if let Some(builder) = api.get_opt(name).await.map_err(Error::KubeError)? {
+        debug!("Found ClusterBuilder {}", name);
         debug!("The ClusterBuilder data is: {:?}", builder.data);
 
         if let Some(conditions) = builder.data.pointer("/status/conditions") {
@@ -121,6 +117,6 @@ pub async fn ready(client: &Client, actor: &Actor) -> Result<bool> {
         }
     }
 
-    debug!("Not found ClusterBuilder {}", &name);
+    debug!("Not found ClusterBuilder {}", name);
     Ok(false)
 }
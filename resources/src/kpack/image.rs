@@ -21,37 +21,51 @@ use kube::{Api, Client, Resource, ResourceExt};
 use serde_json::{from_value, json};
 use tracing::{debug, info};
 
+use crate::build::BuildResources;
 use crate::error::{Error, Result};
+use crate::kpack::reference::BuilderRef;
 use crate::kpack::BuildExt;
+use crate::service_account::BUILDER_SERVICE_ACCOUNT;
+use crate::{monorepo, naming};
 
 pub async fn exists(client: &Client, actor: &Actor) -> Result<bool> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace.as_str(), &api_resource());
-    let name = format!("{}-builder", actor.spec.name);
+    let name = naming::name(&[&actor.spec.name, "builder"]);
 
     Ok(api.get_opt(&name).await.map_err(Error::KubeError)?.is_some())
 }
 
-pub async fn create(client: &Client, actor: &Actor) -> Result<DynamicObject> {
+pub async fn create(
+    client: &Client,
+    actor: &Actor,
+    builder: &BuilderRef,
+    resources: &BuildResources,
+) -> Result<DynamicObject> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace.as_str(), &api_resource());
 
-    let resource = new(actor)?;
+    let resource = new(actor, builder, resources)?;
     let image = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
     info!("Created Image: {}", image.name_any());
 
     Ok(image)
 }
 
-pub async fn update(client: &Client, actor: &Actor) -> Result<DynamicObject> {
+pub async fn update(
+    client: &Client,
+    actor: &Actor,
+    builder: &BuilderRef,
+    resources: &BuildResources,
+) -> Result<DynamicObject> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace.as_str(), &api_resource());
 
-    let name = format!("{}-builder", actor.spec.name);
+    let name = naming::name(&[&actor.spec.name, "builder"]);
     let mut image = api.get(&name).await.map_err(Error::KubeError)?;
     debug!("The Image \"{}\" already exists", name);
 
-    let resource = new(actor)?;
+    let resource = new(actor, builder, resources)?;
     if image.data.pointer("/spec") != resource.data.pointer("/spec") {
         debug!("The updating Image resource:\n {:?}\n", resource);
         image = api
@@ -69,8 +83,8 @@ fn api_resource() -> ApiResource {
     ApiResource::from_gvk(&GroupVersionKind::gvk("kpack.io", "v1alpha2", "Image"))
 }
 
-fn new(actor: &Actor) -> Result<DynamicObject> {
-    let name = format!("{}-builder", actor.spec.name);
+pub(crate) fn new(actor: &Actor, builder: &BuilderRef, resources: &BuildResources) -> Result<DynamicObject> {
+    let name = naming::name(&[&actor.spec.name, "builder"]);
     let owner_reference = actor.controller_owner_ref(&()).unwrap();
 
     // Build the source based on the build strategy
@@ -88,7 +102,7 @@ fn new(actor: &Actor) -> Result<DynamicObject> {
                 "url": source.repo,
                 "revision": source.rev(),
             },
-            "subPath": source.path.as_deref().unwrap_or_default(),
+            "subPath": monorepo::context(&actor.spec).unwrap_or_default(),
         })
     };
 
@@ -98,6 +112,27 @@ fn new(actor: &Actor) -> Result<DynamicObject> {
     if let Some(env) = actor.spec.character.build.as_ref().and_then(|build| build.env.as_ref()) {
         build["env"] = env.iter().map(|(name, value)| json!({"name": name, "value": value})).collect();
     }
+    resources.apply_kpack(&mut build)?;
+
+    let mut spec = json!({
+        "build": build,
+        "builder": {
+            "name": builder.name,
+            "kind": builder.kind,
+        },
+        "cache": {
+            "volume": {}
+        },
+        "serviceAccountName": BUILDER_SERVICE_ACCOUNT,
+        "source": source,
+        "tag": actor.spec.image,
+    });
+
+    // kpack deletes the Builds beyond the history limits along with their pods.
+    if let Some(limit) = resources.history_limit {
+        spec["successBuildHistoryLimit"] = json!(limit);
+        spec["failedBuildHistoryLimit"] = json!(limit);
+    }
 
     let resource = from_value(json!({
         "apiVersion": "kpack.io/v1alpha2",
@@ -110,18 +145,7 @@ fn new(actor: &Actor) -> Result<DynamicObject> {
             "name": name.clone(),
             "ownerReferences": vec![owner_reference],
         },
-        "spec": {
-            "build": build,
-            "builder": {
-                "name": actor.spec.character.builder_name(),
-                "kind": "ClusterBuilder",
-            },
-            "cache": {
-                "volume": {}
-            },
-            "source": source,
-            "tag": actor.spec.image,
-        }
+        "spec": spec,
     }))
     .map_err(Error::SerializationError)?;
 
@@ -133,7 +157,7 @@ pub async fn completed(client: &Client, actor: &Actor) -> Result<bool> {
 
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace.as_str(), &api_resource());
-    let name = format!("{}-builder", actor.spec.name);
+    let name = naming::name(&[&actor.spec.name, "builder"]);
 
     if let Some(image) = api.get_opt(&name).await.map_err(Error::KubeError)? {
         debug!("Found Image {}", &name);
@@ -12,6 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use amp_common::config::{Credential, Credentials};
+
+use crate::error::{Error, Result};
+
 mod build_ext;
 pub use self::build_ext::BuildExt;
 
@@ -19,6 +23,7 @@ pub mod cluster_builder;
 pub mod cluster_buildpack;
 pub mod cluster_store;
 pub mod image;
+pub mod reference;
 pub mod syncer;
 pub mod types;
 
@@ -32,6 +37,17 @@ pub fn encode_name(image: &str) -> String {
     image.replace(['.', '/'], "-").to_lowercase()
 }
 
+/// Returns the tag of the builder image in the default registry.
+pub fn builder_tag(credentials: &Credentials, name: &str) -> Result<String> {
+    let credential = credentials.default_registry().ok_or(Error::NotFoundRegistries)?;
+    let mut registry = credential.server.as_str();
+    if registry.eq("https://index.docker.io/v1/") {
+        registry = "index.docker.io";
+    }
+
+    Ok(format!("{}/{}/{}", registry, credential.username_any(), name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::encode_name;
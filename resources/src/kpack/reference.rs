@@ -0,0 +1,140 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::{Actor, CharacterSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use kube::core::{DynamicObject, GroupVersionKind};
+use kube::discovery::ApiResource;
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::{cluster_builder, BuildExt};
+use crate::error::{Error, Result};
+
+/// The annotation of actor referencing the kpack builder to build with as a
+/// JSON document, e.g. `{"name": "paketo-java", "kind": "ClusterBuilder"}`.
+pub const BUILDER_ANNOTATION: &str = "amphitheatre.app/builder";
+
+const CLUSTER_BUILDER_KIND: &str = "ClusterBuilder";
+const BUILDER_KIND: &str = "Builder";
+
+/// The reference to a kpack builder, either a ClusterBuilder or a Builder in
+/// the namespace of actor.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct BuilderRef {
+    pub name: String,
+    #[serde(default = "default_kind")]
+    pub kind: String,
+    /// The buildpacks of the builder, the ClusterBuilder is managed by
+    /// Amphitheatre with them if set, otherwise it must exist already.
+    #[serde(default)]
+    pub buildpacks: Vec<String>,
+}
+
+impl BuilderRef {
+    /// Returns true if the builder is created and updated by Amphitheatre.
+    pub fn managed(&self) -> bool {
+        !self.buildpacks.is_empty()
+    }
+}
+
+fn default_kind() -> String {
+    CLUSTER_BUILDER_KIND.to_string()
+}
+
+/// Returns the builder referenced by actor, or the default of the operator,
+/// the ClusterBuilder of the character is used if neither is set.
+pub fn of(actor: &Actor, default: Option<&BuilderRef>) -> Result<Option<BuilderRef>> {
+    let reference: BuilderRef = match actor.annotations().get(BUILDER_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map_err(Error::SerializationError)?,
+        None => match default {
+            Some(default) => default.clone(),
+            None => return Ok(None),
+        },
+    };
+
+    if ![CLUSTER_BUILDER_KIND, BUILDER_KIND].contains(&reference.kind.as_str()) {
+        return Err(Error::InvalidBuilder(format!("unknown kind {}", reference.kind)));
+    }
+    if reference.managed() && reference.kind != CLUSTER_BUILDER_KIND {
+        return Err(Error::InvalidBuilder(format!("only the ClusterBuilders are managed, not {}", reference.name)));
+    }
+    debug!("The builder of actor {}: {:?}", actor.name_any(), reference);
+
+    Ok(Some(reference))
+}
+
+/// Returns the ClusterBuilder managed for the character, by its builder and buildpacks.
+pub fn character(character: &CharacterSpec) -> BuilderRef {
+    let buildpacks = character.buildpacks().cloned().unwrap_or_default();
+    BuilderRef { name: character.builder_name(), kind: default_kind(), buildpacks }
+}
+
+/// Check if the referenced builder is ready.
+pub async fn ready(client: &Client, actor: &Actor, reference: &BuilderRef) -> Result<bool> {
+    if reference.kind == CLUSTER_BUILDER_KIND {
+        return cluster_builder::ready(client, &reference.name).await;
+    }
+
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let resource = ApiResource::from_gvk(&GroupVersionKind::gvk("kpack.io", "v1alpha2", BUILDER_KIND));
+    let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), &namespace, &resource);
+
+    let Some(builder) = api.get_opt(&reference.name).await.map_err(Error::KubeError)? else {
+        debug!("Not found Builder {}", reference.name);
+        return Ok(false);
+    };
+    let conditions = builder.data.pointer("/status/conditions").cloned().unwrap_or_default();
+    let conditions: Vec<Condition> = serde_json::from_value(conditions).unwrap_or_default();
+
+    Ok(conditions.iter().any(|condition| condition.type_ == "Ready" && condition.status == "True"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_default_builder() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(of(&actor, None).unwrap(), None);
+
+        let reference = character(&actor.spec.character);
+        assert_eq!(reference.name, "default-cluster-builder");
+        assert_eq!(reference.kind, CLUSTER_BUILDER_KIND);
+
+        let default = BuilderRef { name: "paketo-base".into(), kind: default_kind(), buildpacks: vec![] };
+        assert_eq!(of(&actor, Some(&default)).unwrap(), Some(default.clone()));
+
+        actor.annotations_mut().insert(BUILDER_ANNOTATION.into(), r#"{"name": "go", "kind": "Builder"}"#.into());
+        let reference = of(&actor, Some(&default)).unwrap().unwrap();
+        assert_eq!(reference.name, "go");
+        assert_eq!(reference.kind, BUILDER_KIND);
+        assert!(!reference.managed());
+    }
+
+    #[test]
+    fn test_invalid_builder() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        actor.annotations_mut().insert(BUILDER_ANNOTATION.into(), r#"{"name": "go", "kind": "Stack"}"#.into());
+        assert!(of(&actor, None).is_err());
+
+        let content = r#"{"name": "go", "kind": "Builder", "buildpacks": ["paketo-buildpacks/go"]}"#;
+        actor.annotations_mut().insert(BUILDER_ANNOTATION.into(), content.into());
+        assert!(of(&actor, None).is_err());
+    }
+}
@@ -23,14 +23,14 @@ use tracing::{debug, info};
 
 use crate::containers::syncer;
 use crate::error::{Error, Result};
-use crate::{hash, LAST_APPLIED_HASH_KEY};
+use crate::{hash, naming, LAST_APPLIED_HASH_KEY};
 
 use super::BuildExt;
 
 pub async fn exists(client: &Client, actor: &Actor) -> Result<bool> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Pod> = Api::namespaced(client.clone(), namespace.as_str());
-    let name = format!("{}-syncer", actor.spec.name);
+    let name = naming::name(&[&actor.spec.name, "syncer"]);
 
     Ok(api.get_opt(&name).await.map_err(Error::KubeError)?.is_some())
 }
@@ -49,7 +49,7 @@ pub async fn create(client: &Client, actor: &Actor) -> Result<Pod> {
 pub async fn update(client: &Client, actor: &Actor) -> Result<Pod> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Pod> = Api::namespaced(client.clone(), namespace.as_str());
-    let name = format!("{}-syncer", actor.spec.name);
+    let name = naming::name(&[&actor.spec.name, "syncer"]);
 
     let mut pod = api.get(&name).await.map_err(Error::KubeError)?;
     debug!("The Pod {} already exists", &name);
@@ -74,7 +74,7 @@ pub async fn update(client: &Client, actor: &Actor) -> Result<Pod> {
 
 /// Create a Syncer Pod for build images
 fn new(actor: &Actor) -> Result<Pod> {
-    let name = format!("{}-syncer", actor.spec.name);
+    let name = naming::name(&[&actor.spec.name, "syncer"]);
     let owner_reference = actor.controller_owner_ref(&()).unwrap();
     let annotations = BTreeMap::from([(LAST_APPLIED_HASH_KEY.into(), hash(&actor.spec)?)]);
     let labels = BTreeMap::from([
@@ -112,7 +112,7 @@ pub async fn ready(client: &Client, actor: &Actor) -> Result<bool> {
 
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Pod> = Api::namespaced(client.clone(), namespace.as_str());
-    let name = format!("{}-syncer", actor.spec.name);
+    let name = naming::name(&[&actor.spec.name, "syncer"]);
 
     let result = api.get_opt(&name).await.map_err(Error::KubeError)?;
     if result.is_none() {
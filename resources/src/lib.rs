@@ -19,21 +19,82 @@ use sha2::{Digest, Sha256};
 use self::error::{Error, Result};
 
 pub mod actor;
+pub mod argocd;
+pub mod artifact;
+pub mod audit;
+pub mod base;
+pub mod build;
+pub mod bundle;
+pub mod cache;
+pub mod canary;
 pub mod character;
+pub mod cluster;
 pub mod containers;
+pub mod cost;
 pub mod credential;
+pub mod cronjob;
+pub mod debug;
 pub mod deployment;
+pub mod detection;
+pub mod devcontainer;
+pub mod discovery;
+pub mod envset;
 pub mod error;
+pub mod export;
+pub mod exposure;
+pub mod gc;
+pub mod git;
+pub mod healing;
+pub mod health;
+pub mod helm;
+pub mod hibernation;
+pub mod image;
+pub mod include;
 pub mod job;
 pub mod kpack;
+pub mod logging;
+pub mod metrics;
+pub mod monorepo;
 pub mod namespace;
+pub mod naming;
+pub mod network;
 pub mod playbook;
+pub mod policy;
+pub mod probe;
+pub mod promotion;
+pub mod quota;
+pub mod rbac;
+pub mod registry;
+pub mod reload;
+pub mod replicas;
+pub mod retention;
+pub mod revision;
+pub mod rollback;
+pub mod sbom;
 pub mod secret;
 pub mod service;
 pub mod service_account;
+pub mod signing;
+pub mod statefulset;
+pub mod strategy;
+pub mod telemetry;
+pub mod template;
+pub mod trash;
+pub mod upload;
+pub mod uptime;
+pub mod usage;
+pub mod vars;
+pub mod verification;
+pub mod version;
 pub mod volume;
+pub mod workspace;
 
-const LAST_APPLIED_HASH_KEY: &str = "amphitheatre.app/last-applied-hash";
+/// The annotation of the objects holding the hash of what they were applied from.
+pub const LAST_APPLIED_HASH_KEY: &str = "amphitheatre.app/last-applied-hash";
+
+/// The annotation bumped by the user-initiated operations (e.g. scale, trigger,
+/// hibernate) which do not change the spec, so the generation is not bumped either.
+pub const REQUESTED_AT_ANNOTATION: &str = "amphitheatre.app/requested-at";
 
 pub fn hash<T>(resource: &T) -> Result<String>
 where
@@ -45,6 +106,24 @@ where
     Ok(format!("{:x}", hash))
 }
 
+/// HMAC-SHA256 (RFC 2104).
+pub fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut block = [0u8; 64];
+    match key.len() > block.len() {
+        true => block[..32].copy_from_slice(&Sha256::digest(key)),
+        false => block[..key.len()].copy_from_slice(key),
+    }
+
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<_>>();
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(data).finalize();
+    Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize().to_vec()
+}
+
+/// Returns the lowercase hex encoding of the bytes.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 /// Returns a list of arguments in one-dash or two-dash style.
 #[inline]
 pub fn args(args: &[(&str, &str)], dash: i8) -> Vec<String> {
@@ -52,3 +131,14 @@ pub fn args(args: &[(&str, &str)], dash: i8) -> Vec<String> {
         .map(|(key, value)| if dash == 1 { format!("-{}={}", key, value) } else { format!("--{}={}", key, value) })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac() {
+        let digest = hmac(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(hex(&digest), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+}
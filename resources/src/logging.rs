@@ -0,0 +1,154 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use futures::{StreamExt, TryStreamExt};
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::runtime::watcher::{self, Event};
+use kube::{Api, Client};
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+use crate::error::{Error, Result};
+use crate::telemetry;
+
+/// The ConfigMap in the Amphitheatre namespace overriding the log filter at
+/// runtime, e.g. `filter: info,amp_workflow=debug`, the configured filter is
+/// restored once it's deleted.
+pub const LOGGING_CONFIG_MAP: &str = "amp-logging";
+
+/// The key of the ConfigMap holding the filter directives.
+const FILTER_KEY: &str = "filter";
+
+/// The filter directives used if neither the configured ones nor `RUST_LOG` are set.
+const DEFAULT_FILTER: &str = "info";
+
+/// How long to wait before watching the logging ConfigMap again once the watch is ended.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The handle replacing the filter of the installed subscriber.
+static HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Returns the configured filter directives, or the ones of `RUST_LOG` if not configured.
+pub fn directives(configured: Option<&str>) -> String {
+    let directives = configured.map(String::from).or_else(|| std::env::var("RUST_LOG").ok());
+    directives.filter(|directives| !directives.trim().is_empty()).unwrap_or_else(|| DEFAULT_FILTER.to_string())
+}
+
+/// Install the global subscriber logging in the `text` or `json` format
+/// filtered by the directives, e.g. `info,kube=warn`. The spans are exported
+/// to the OTLP collector as well if the endpoint is set.
+pub fn init(service: &str, format: &str, filter: &str, endpoint: Option<&str>) -> Result<()> {
+    let (filter, handle) = reload::Layer::new(EnvFilter::try_new(filter).map_err(Error::InvalidLogFilter)?);
+
+    // The fields of the reconcile and request spans, e.g. the names of the
+    // playbook and actor, are logged along with every event in them.
+    let format = match format {
+        "text" => fmt::layer().with_file(false).with_target(false).boxed(),
+        "json" => fmt::layer().json().with_current_span(false).with_span_list(true).boxed(),
+        _ => return Err(Error::InvalidLogFormat(format.into())),
+    };
+
+    tracing_subscriber::registry().with(filter).with(format).with(telemetry::layer(service, endpoint)?).init();
+    HANDLE.set(handle).ok();
+
+    Ok(())
+}
+
+/// Replace the filter of the installed subscriber.
+pub fn reload(directives: &str) -> Result<()> {
+    let filter = EnvFilter::try_new(directives).map_err(Error::InvalidLogFilter)?;
+    if let Some(handle) = HANDLE.get() {
+        handle.reload(filter).map_err(Error::LogReloadError)?;
+        info!("Reloaded the log filter: {}", directives);
+    }
+
+    Ok(())
+}
+
+/// Watch the logging ConfigMap and reload the filter once it's changed.
+pub async fn watch(client: Client, namespace: &str, default: &str) {
+    let api = Api::<ConfigMap>::namespaced(client, namespace);
+    let config = watcher::Config::default().fields(&format!("metadata.name={}", LOGGING_CONFIG_MAP));
+    let mut obs = watcher(api.clone(), config.clone()).boxed();
+
+    loop {
+        match obs.try_next().await {
+            Ok(Some(event)) => {
+                if let Err(err) = reload(&filter(&event, default)) {
+                    error!("Reload the log filter failed: {}", err);
+                }
+            }
+            // The ended stream yields nothing anymore, watch it again after a while.
+            Ok(None) => {
+                warn!("The logging config stream is ended, watch it again");
+                sleep(RETRY_INTERVAL).await;
+                obs = watcher(api.clone(), config.clone()).boxed();
+            }
+            Err(err) => {
+                error!("Resolve logging config stream failed: {}", err);
+                continue;
+            }
+        }
+    }
+}
+
+/// Returns the filter directives of the event, or the default ones if the ConfigMap is deleted.
+fn filter(event: &Event<ConfigMap>, default: &str) -> String {
+    let config_map = match event {
+        Event::Applied(config_map) => Some(config_map),
+        Event::Deleted(_) => None,
+        Event::Restarted(config_maps) => config_maps.first(),
+    };
+    let directives = config_map.and_then(|config_map| config_map.data.as_ref()?.get(FILTER_KEY).cloned());
+
+    directives.filter(|directives| !directives.trim().is_empty()).unwrap_or_else(|| default.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    fn config_map(filter: &str) -> ConfigMap {
+        ConfigMap { data: Some(BTreeMap::from([(FILTER_KEY.into(), filter.into())])), ..Default::default() }
+    }
+
+    #[test]
+    fn test_filter_of_event() {
+        let event = Event::Applied(config_map("info,amp_workflow=debug"));
+        assert_eq!(filter(&event, "info"), "info,amp_workflow=debug");
+
+        assert_eq!(filter(&Event::Deleted(config_map("debug")), "info"), "info");
+        assert_eq!(filter(&Event::Restarted(vec![]), "info"), "info");
+        assert_eq!(filter(&Event::Applied(config_map(" ")), "info"), "info");
+    }
+
+    #[test]
+    fn test_configured_directives() {
+        assert_eq!(directives(Some("info,amp_workflow=debug")), "info,amp_workflow=debug");
+    }
+
+    #[test]
+    fn test_invalid_filter() {
+        assert!(reload("info,amp_workflow=nope").is_err());
+        assert!(reload("info,amp_workflow=debug").is_ok());
+    }
+}
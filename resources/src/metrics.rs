@@ -0,0 +1,245 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::AddAssign;
+use std::time::Duration;
+
+use k8s_metrics::v1beta1::PodMetrics;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::ListParams;
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::error::{Error, Result};
+
+/// The label of the pods holding the name of their actor.
+const CHARACTER_LABEL: &str = "amphitheatre.app/character";
+
+/// The suffixes of the quantities and their multipliers, the binary ones first.
+const SUFFIXES: &[(&str, f64)] = &[
+    ("Ki", 1024.0),
+    ("Mi", 1048576.0),
+    ("Gi", 1073741824.0),
+    ("Ti", 1099511627776.0),
+    ("Pi", 1125899906842624.0),
+    ("Ei", 1152921504606846976.0),
+    ("n", 1e-9),
+    ("u", 1e-6),
+    ("m", 1e-3),
+    ("k", 1e3),
+    ("M", 1e6),
+    ("G", 1e9),
+    ("T", 1e12),
+    ("P", 1e15),
+    ("E", 1e18),
+];
+
+/// The resource consumption of a group of pods.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Usage {
+    /// The number of the pods measured.
+    pub pods: usize,
+    /// The cpu in cores.
+    pub cpu: f64,
+    /// The memory in bytes.
+    pub memory: f64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.pods += other.pods;
+        self.cpu += other.cpu;
+        self.memory += other.memory;
+    }
+}
+
+/// Collect the resource consumption of the pods of each actor in the namespace,
+/// from Prometheus if its URL is given, or the metrics-server otherwise.
+pub async fn collect(client: &Client, namespace: &str, prometheus: Option<&str>) -> Result<BTreeMap<String, Usage>> {
+    let pods = match prometheus {
+        Some(url) => query(url, namespace).await?,
+        None => server(client, namespace).await?,
+    };
+
+    let api: Api<Pod> = Api::namespaced(client.clone(), namespace);
+    let params = ListParams::default().labels(CHARACTER_LABEL);
+    let owners = api.list(&params).await.map_err(Error::KubeError)?;
+    let owners: HashMap<String, String> = owners
+        .items
+        .iter()
+        .filter_map(|pod| Some((pod.name_any(), pod.labels().get(CHARACTER_LABEL)?.clone())))
+        .collect();
+
+    Ok(aggregate(pods, &owners))
+}
+
+/// Sum up the consumption of the pods by their actors, the pods of others are skipped.
+pub fn aggregate(pods: HashMap<String, Usage>, owners: &HashMap<String, String>) -> BTreeMap<String, Usage> {
+    let mut actors: BTreeMap<String, Usage> = BTreeMap::new();
+    for (pod, usage) in pods {
+        if let Some(actor) = owners.get(&pod) {
+            *actors.entry(actor.clone()).or_default() += usage;
+        }
+    }
+
+    actors
+}
+
+/// Parse the quantity into a number, e.g. `250m` into `0.25` and `64Mi` into `67108864`.
+pub fn quantity(value: &str) -> Option<f64> {
+    let value = value.trim();
+    for (suffix, multiplier) in SUFFIXES {
+        if let Some(number) = value.strip_suffix(suffix) {
+            return number.parse::<f64>().ok().map(|n| n * multiplier);
+        }
+    }
+
+    value.parse().ok()
+}
+
+/// Read the consumption of the pods in the namespace from the metrics-server.
+async fn server(client: &Client, namespace: &str) -> Result<HashMap<String, Usage>> {
+    let api: Api<PodMetrics> = Api::namespaced(client.clone(), namespace);
+    let metrics = match api.list(&ListParams::default().labels(CHARACTER_LABEL)).await {
+        Ok(metrics) => metrics,
+        Err(kube::Error::Api(err)) if err.code == 404 => return Err(Error::MetricsNotAvailable),
+        Err(err) => return Err(Error::KubeError(err)),
+    };
+
+    let mut pods = HashMap::new();
+    for pod in metrics.items {
+        let mut usage = Usage { pods: 1, ..Default::default() };
+        for container in &pod.containers {
+            usage.cpu += quantity(&container.usage.cpu.0).unwrap_or_default();
+            usage.memory += quantity(&container.usage.memory.0).unwrap_or_default();
+        }
+        pods.insert(pod.name_any(), usage);
+    }
+
+    Ok(pods)
+}
+
+#[derive(Deserialize)]
+struct QueryResponse {
+    data: QueryData,
+}
+
+#[derive(Deserialize)]
+struct QueryData {
+    result: Vec<Sample>,
+}
+
+#[derive(Deserialize)]
+struct Sample {
+    metric: HashMap<String, String>,
+    /// The time and the value of the sample.
+    value: (f64, String),
+}
+
+/// Read the consumption of the pods in the namespace from Prometheus, the
+/// cpu is averaged over the last 5 minutes.
+async fn query(url: &str, namespace: &str) -> Result<HashMap<String, Usage>> {
+    let selector = format!(r#"namespace="{}",container!="",container!="POD""#, namespace);
+    let cpu = format!("sum by (pod) (rate(container_cpu_usage_seconds_total{{{}}}[5m]))", selector);
+    let memory = format!("sum by (pod) (container_memory_working_set_bytes{{{}}})", selector);
+
+    let mut pods: HashMap<String, Usage> = HashMap::new();
+    for (pod, value) in instant(url, &cpu, "pod").await? {
+        pods.entry(pod).or_insert(Usage { pods: 1, ..Default::default() }).cpu = value;
+    }
+    for (pod, value) in instant(url, &memory, "pod").await? {
+        pods.entry(pod).or_insert(Usage { pods: 1, ..Default::default() }).memory = value;
+    }
+
+    Ok(pods)
+}
+
+/// Returns the number of the requests served by the ingresses of the actors
+/// in the namespace in the last window, from the metrics of ingress-nginx.
+pub async fn requests(url: &str, namespace: &str, actors: &[String], window: Duration) -> Result<f64> {
+    // The names of actors are DNS labels, there are no special characters of regex in them.
+    let selector = format!(r#"exported_namespace="{}",ingress=~"({})(-canary)?""#, namespace, actors.join("|"));
+    let query = format!(
+        "sum by (exported_namespace) (increase(nginx_ingress_controller_requests{{{}}}[{}s]))",
+        selector,
+        window.as_secs()
+    );
+
+    Ok(instant(url, &query, "exported_namespace").await?.into_iter().map(|(_, value)| value).sum())
+}
+
+/// Run the instant query of Prometheus, returns the value by the label, e.g. of each pod.
+async fn instant(url: &str, query: &str, label: &str) -> Result<Vec<(String, f64)>> {
+    let http = reqwest::Client::new();
+    let url = format!("{}/api/v1/query", url.trim_end_matches('/'));
+    debug!("Querying Prometheus {}: {}", url, query);
+
+    let response = http
+        .get(&url)
+        .query(&[("query", query)])
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| Error::MetricsError(err.into()))?;
+    let response: QueryResponse = response.json().await.map_err(|err| Error::MetricsError(err.into()))?;
+
+    Ok(response
+        .data
+        .result
+        .into_iter()
+        .filter_map(|sample| Some((sample.metric.get(label)?.clone(), sample.value.1.parse().ok()?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantity() {
+        assert_eq!(quantity("2"), Some(2.0));
+        assert!((quantity("250m").unwrap() - 0.25).abs() < 1e-9);
+        assert!((quantity("1500000n").unwrap() - 0.0015).abs() < 1e-9);
+        assert_eq!(quantity("64Mi"), Some(67108864.0));
+        assert_eq!(quantity("1G"), Some(1e9));
+        assert_eq!(quantity("1e3"), Some(1000.0));
+        assert_eq!(quantity("invalid"), None);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let usage = |cpu, memory| Usage { pods: 1, cpu, memory };
+        let pods = HashMap::from([
+            ("web-1".into(), usage(0.1, 100.0)),
+            ("web-2".into(), usage(0.2, 200.0)),
+            ("api-1".into(), usage(0.5, 50.0)),
+            ("builder".into(), usage(1.0, 1000.0)),
+        ]);
+        let owners = HashMap::from([
+            ("web-1".into(), "web".into()),
+            ("web-2".into(), "web".into()),
+            ("api-1".into(), "api".into()),
+        ]);
+
+        let actors = aggregate(pods, &owners);
+        assert_eq!(actors.len(), 2);
+        assert_eq!(actors["web"].pods, 2);
+        assert!((actors["web"].cpu - 0.3).abs() < 1e-9);
+        assert_eq!(actors["web"].memory, 300.0);
+        assert_eq!(actors["api"], usage(0.5, 50.0));
+    }
+}
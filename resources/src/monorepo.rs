@@ -0,0 +1,195 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::{Actor, ActorSpec};
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::error::{Error, Result};
+use crate::git;
+
+/// The annotation of actor recording the commit pushed to its branch which
+/// changed its files, the actor is rebuilt from it by the controllers.
+pub const PUSHED_ANNOTATION: &str = "amphitheatre.app/pushed";
+
+/// A push to a branch of repository, from the webhooks of the providers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Push {
+    /// The HTTPS address of the repository.
+    pub repo: String,
+    pub branch: String,
+    /// The commit the branch points to after the push.
+    pub rev: String,
+    /// The files added, modified or removed by the commits, None if they are
+    /// not all listed, e.g. a force push or too many commits.
+    pub files: Option<Vec<String>>,
+}
+
+impl Push {
+    /// Parse the push event of GitHub, GitLab or Gitea, returns None if it's
+    /// not a push to a branch, or the branch is deleted.
+    pub fn parse(payload: &Value) -> Option<Push> {
+        let branch = payload["ref"].as_str()?.strip_prefix("refs/heads/")?;
+        let rev = payload["after"].as_str().filter(|rev| !rev.trim_start_matches('0').is_empty())?;
+        let repo = ["clone_url", "git_http_url", "html_url"]
+            .iter()
+            .find_map(|key| payload["repository"][key].as_str().or_else(|| payload["project"][key].as_str()))?;
+
+        let commits = payload["commits"].as_array().filter(|commits| !commits.is_empty());
+        let total = payload["total_commits_count"].as_u64();
+        let commits = commits.filter(|commits| total.map_or(true, |total| total as usize == commits.len()));
+        let files = commits.map(|commits| {
+            let mut files = vec![];
+            for commit in commits {
+                for key in ["added", "modified", "removed"] {
+                    let paths = commit[key].as_array().into_iter().flatten().filter_map(Value::as_str);
+                    files.extend(paths.map(String::from));
+                }
+            }
+            files
+        });
+
+        Some(Push { repo: git::https(repo), branch: branch.into(), rev: rev.into(), files })
+    }
+}
+
+/// Returns the build context of actor relative to the root of its repository,
+/// the path of source joined with the context of build, None if it's the root.
+pub fn context(spec: &ActorSpec) -> Option<String> {
+    let path = spec.source.as_ref().and_then(|source| source.path.as_deref());
+    let context = spec.character.build.as_ref().and_then(|build| build.context.as_deref());
+    let parts: Vec<&str> = [path, context]
+        .into_iter()
+        .flatten()
+        .map(|part| part.trim_start_matches("./").trim_matches('/'))
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Returns true if the actor is built from the branch pushed, and its build
+/// context contains any file changed by the push. The actors pinned to a tag
+/// or without a source are never rebuilt by the pushes.
+pub fn affected(push: &Push, spec: &ActorSpec) -> bool {
+    let Some(source) = spec.source.as_ref().filter(|_| !spec.live) else {
+        return false;
+    };
+    if normalize(&source.repo) != normalize(&push.repo)
+        || source.branch.as_deref() != Some(push.branch.as_str())
+        || source.tag.is_some()
+        || source.rev.as_deref() == Some(push.rev.as_str())
+    {
+        return false;
+    }
+
+    match (&push.files, context(spec)) {
+        (Some(files), Some(context)) => files.iter().any(|file| file.starts_with(&format!("{}/", context))),
+        _ => true,
+    }
+}
+
+/// Request to rebuild the actor from the pushed commit.
+pub async fn request(client: &Client, actor: &Actor, rev: &str) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let patch = json!({"metadata": { "annotations": { PUSHED_ANNOTATION: rev }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Requested to rebuild actor {} from the pushed commit {}", actor.name_any(), rev);
+
+    Ok(())
+}
+
+/// Returns the pushed commit the actor is not rebuilt from yet, if any.
+pub fn pending(actor: &Actor) -> Option<String> {
+    let pushed = actor.annotations().get(PUSHED_ANNOTATION)?;
+    let rev = actor.spec.source.as_ref().and_then(|source| source.rev.as_ref());
+    (rev != Some(pushed)).then(|| pushed.clone())
+}
+
+/// The repositories are the same regardless of the protocol and the `.git` suffix.
+fn normalize(repo: &str) -> String {
+    git::https(repo).trim_end_matches('/').trim_end_matches(".git").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::schema::GitReference;
+
+    fn spec(path: Option<&str>) -> ActorSpec {
+        let source = GitReference {
+            repo: "git@github.com:amp/monorepo.git".into(),
+            branch: Some("main".into()),
+            rev: Some("1111".into()),
+            path: path.map(String::from),
+            ..Default::default()
+        };
+        ActorSpec { image: "registry/amp/web:1111".into(), source: Some(source), ..Default::default() }
+    }
+
+    fn push(files: Option<&[&str]>) -> Push {
+        Push {
+            repo: "https://github.com/amp/monorepo".into(),
+            branch: "main".into(),
+            rev: "2222".into(),
+            files: files.map(|files| files.iter().map(|file| file.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn test_parse_push() {
+        let payload = json!({
+            "ref": "refs/heads/main",
+            "after": "2222",
+            "repository": {"clone_url": "https://github.com/amp/monorepo.git"},
+            "commits": [{"added": ["web/index.js"], "modified": ["api/main.go"], "removed": []}],
+        });
+        let push = Push::parse(&payload).unwrap();
+        assert_eq!(push.repo, "https://github.com/amp/monorepo.git");
+        assert_eq!(push.files, Some(vec!["web/index.js".to_string(), "api/main.go".to_string()]));
+
+        let deleted = json!({"ref": "refs/heads/main", "after": "0000", "repository": payload["repository"]});
+        assert_eq!(Push::parse(&deleted), None);
+        let tag = json!({"ref": "refs/tags/v1.0.0", "after": "2222", "repository": payload["repository"]});
+        assert_eq!(Push::parse(&tag), None);
+    }
+
+    #[test]
+    fn test_context() {
+        assert_eq!(context(&spec(None)), None);
+        assert_eq!(context(&spec(Some("./services/web/"))), Some("services/web".to_string()));
+
+        let mut spec = spec(Some("services/web"));
+        spec.character.build = Some(amp_common::schema::Build { context: Some("app".into()), ..Default::default() });
+        assert_eq!(context(&spec), Some("services/web/app".to_string()));
+    }
+
+    #[test]
+    fn test_affected() {
+        assert!(affected(&push(Some(&["services/web/index.js"])), &spec(Some("services/web"))));
+        assert!(!affected(&push(Some(&["services/api/main.go"])), &spec(Some("services/web"))));
+        assert!(!affected(&push(Some(&["services/webapp/index.js"])), &spec(Some("services/web"))));
+        assert!(affected(&push(None), &spec(Some("services/web"))));
+        assert!(affected(&push(Some(&["README.md"])), &spec(None)));
+
+        let mut pinned = spec(None);
+        pinned.source.as_mut().unwrap().tag = Some("v1.0.0".into());
+        assert!(!affected(&push(None), &pinned));
+    }
+}
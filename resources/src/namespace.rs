@@ -16,16 +16,83 @@ use std::collections::BTreeMap;
 
 use amp_common::resource::Playbook;
 use k8s_openapi::api::core::v1::Namespace;
-use kube::api::{Patch, PatchParams};
+use kube::api::{DeleteParams, Patch, PatchParams};
 use kube::core::ObjectMeta;
 use kube::{Api, Client, Resource, ResourceExt};
 use tracing::info;
 
+use super::actor::DETACHED_LABEL;
+use super::cluster;
 use super::error::{Error, Result};
+use super::naming;
 
+/// The annotation of playbook choosing the strategy of its namespace, one of
+/// [`STRATEGIES`], the default is `dedicated`.
+pub const STRATEGY_ANNOTATION: &str = "amphitheatre.app/namespace-strategy";
+
+/// The annotation of playbook naming the namespace of the `existing` and `shared` strategies.
+pub const NAMESPACE_ANNOTATION: &str = "amphitheatre.app/namespace";
+
+pub const STRATEGIES: &[&str] = &["dedicated", "existing", "shared"];
+
+/// Where the actors of playbook are deployed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Strategy {
+    /// The `amp-{id}` namespace owned by the playbook, deleted along with it.
+    Dedicated,
+    /// The namespace provided by the user, it must exist and is never deleted.
+    Existing(String),
+    /// The namespace shared by the playbooks of a team, created if missing and
+    /// never deleted, the names of actors are prefixed by their playbook.
+    Shared(String),
+}
+
+/// Returns the namespace strategy of playbook, it falls back to `dedicated`
+/// if the strategy is unknown or its namespace is not set.
+pub fn strategy(playbook: &Playbook) -> Strategy {
+    let name = playbook.annotations().get(NAMESPACE_ANNOTATION).filter(|name| !name.is_empty()).cloned();
+    match (playbook.annotations().get(STRATEGY_ANNOTATION).map(String::as_str), name) {
+        (Some("existing"), Some(name)) => Strategy::Existing(name),
+        (Some("shared"), Some(name)) => Strategy::Shared(name),
+        _ => Strategy::Dedicated,
+    }
+}
+
+/// Returns true if the namespace of playbook is dedicated to and owned by it.
+pub fn dedicated(playbook: &Playbook) -> bool {
+    strategy(playbook) == Strategy::Dedicated
+}
+
+/// Returns the name of the namespace the actors of playbook are deployed in.
+pub fn of(playbook: &Playbook) -> String {
+    match strategy(playbook) {
+        Strategy::Dedicated => playbook.spec.namespace(),
+        Strategy::Existing(name) | Strategy::Shared(name) => name,
+    }
+}
+
+/// Returns the name of the actor of character, it's prefixed by the id of
+/// playbook in a shared namespace to avoid the conflicts with other playbooks.
+pub fn actor_name(playbook: &Playbook, character: &str) -> String {
+    match strategy(playbook) {
+        Strategy::Shared(_) => {
+            let prefix = playbook.spec.id.split('-').next().unwrap_or_default();
+            naming::name(&[prefix, character])
+        }
+        _ => character.to_string(),
+    }
+}
+
+/// Prepare the namespace of playbook by its strategy, the dedicated and shared
+/// namespaces are created if missing, the existing one must exist. All of them
+/// are labelled, so the credentials are synced into them.
 pub async fn create(client: &Client, playbook: &Playbook) -> Result<Namespace> {
     let api: Api<Namespace> = Api::all(client.clone());
-    let name = playbook.spec.namespace();
+    let name = of(playbook);
+
+    if matches!(strategy(playbook), Strategy::Existing(_)) && !exists(client, &name).await? {
+        return Err(Error::NamespaceNotFound(name));
+    }
 
     let resource = new(playbook);
     let params = &PatchParams::apply("amp-controllers").force();
@@ -35,21 +102,92 @@ pub async fn create(client: &Client, playbook: &Playbook) -> Result<Namespace> {
     Ok(namespace)
 }
 
+/// Create the namespace of a detached actor, which is not owned by any playbook.
+pub async fn create_detached(client: &Client, name: &str) -> Result<Namespace> {
+    let api: Api<Namespace> = Api::all(client.clone());
+
+    let mut resource =
+        Namespace { metadata: ObjectMeta { name: Some(name.into()), ..Default::default() }, ..Default::default() };
+    resource.labels_mut().extend(labels());
+    resource.labels_mut().insert(DETACHED_LABEL.into(), "true".into());
+
+    let params = &PatchParams::apply("amp-controllers").force();
+    let namespace = api.patch(name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+
+    info!("Added namespace: {}", namespace.name_any());
+    Ok(namespace)
+}
+
+pub async fn exists(client: &Client, name: &str) -> Result<bool> {
+    let api: Api<Namespace> = Api::all(client.clone());
+    Ok(api.get_opt(name).await.map_err(Error::KubeError)?.is_some())
+}
+
+pub async fn delete(client: &Client, name: &str) -> Result<()> {
+    let api: Api<Namespace> = Api::all(client.clone());
+    api.delete(name, &DeleteParams::default()).await.map_err(Error::KubeError)?;
+
+    info!("Deleted namespace: {}", name);
+    Ok(())
+}
+
 fn new(playbook: &Playbook) -> Namespace {
-    let name = playbook.spec.namespace();
-    let owner_reference = playbook.controller_owner_ref(&()).unwrap();
-    let labels = BTreeMap::from([
-        ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
-        ("syncer.amphitheatre.app/sync".into(), "true".into()),
-    ]);
+    let name = of(playbook);
+    // The namespaces in the workload clusters are deleted with the playbook
+    // explicitly, and the shared ones outlive their playbooks.
+    let owner_reference =
+        playbook.controller_owner_ref(&()).filter(|_| !cluster::remote(playbook) && dedicated(playbook));
 
     Namespace {
         metadata: ObjectMeta {
             name: Some(name.clone()),
-            owner_references: Some(vec![owner_reference]),
-            labels: Some(labels),
+            owner_references: owner_reference.map(|owner| vec![owner]),
+            labels: Some(labels()),
             ..ObjectMeta::default()
         },
         ..Namespace::default()
     }
 }
+
+#[inline]
+fn labels() -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+        ("syncer.amphitheatre.app/sync".into(), "true".into()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::PlaybookSpec;
+
+    fn playbook(annotations: &[(&str, &str)]) -> Playbook {
+        let id = "9f3e2c1a-5b7d-4e6f-8a9b-0c1d2e3f4a5b";
+        let mut playbook = Playbook::new(id, PlaybookSpec { id: id.into(), ..Default::default() });
+        for (key, value) in annotations {
+            playbook.annotations_mut().insert(key.to_string(), value.to_string());
+        }
+        playbook
+    }
+
+    #[test]
+    fn test_strategy() {
+        assert_eq!(strategy(&playbook(&[])), Strategy::Dedicated);
+        assert_eq!(strategy(&playbook(&[(STRATEGY_ANNOTATION, "shared")])), Strategy::Dedicated);
+
+        let existing = playbook(&[(STRATEGY_ANNOTATION, "existing"), (NAMESPACE_ANNOTATION, "team-a")]);
+        assert_eq!(strategy(&existing), Strategy::Existing("team-a".into()));
+        assert_eq!(of(&existing), "team-a");
+        assert!(!dedicated(&existing));
+    }
+
+    #[test]
+    fn test_actor_name() {
+        assert_eq!(actor_name(&playbook(&[]), "web"), "web");
+
+        let shared = playbook(&[(STRATEGY_ANNOTATION, "shared"), (NAMESPACE_ANNOTATION, "team-a")]);
+        assert_eq!(actor_name(&shared, "web"), "9f3e2c1a-web");
+    }
+}
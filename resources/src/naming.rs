@@ -0,0 +1,144 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sha2::{Digest, Sha256};
+
+/// The maximum length of the RFC 1123 label names.
+pub const MAX_LENGTH: usize = 63;
+
+/// The length of the hash suffix appended to the altered names.
+const HASH_LENGTH: usize = 8;
+
+/// Returns an RFC 1123 label name joining the parts with `-`, e.g. `{actor}-builder`.
+///
+/// The name is returned as-is if it's valid already, so the existing resources
+/// are still found by it. Otherwise it's lowercased, the invalid characters are
+/// replaced and it's truncated, then a stable short hash of the original name is
+/// appended, so two different names never collide after being altered. The hash
+/// is prefixed with `a-` if nothing is left, as the Service names start with a letter.
+pub fn name(parts: &[&str]) -> String {
+    let original = parts.join("-");
+    if valid(&original) {
+        return original;
+    }
+
+    let hash = format!("{:x}", Sha256::digest(original.as_bytes()));
+    let mut prefix: String = sanitize(&original).chars().take(MAX_LENGTH - HASH_LENGTH - 1).collect();
+    prefix.truncate(prefix.trim_end_matches('-').len());
+
+    match prefix.is_empty() {
+        true => format!("a-{}", &hash[..HASH_LENGTH]),
+        false => format!("{}-{}", prefix, &hash[..HASH_LENGTH]),
+    }
+}
+
+/// Returns true if the name is a valid RFC 1123 label.
+pub fn valid(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_LENGTH
+        && name.bytes().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+/// Returns true if the key is a valid annotation key, the name part after its prefix
+/// is at most 63 characters, e.g. the one of `amphitheatre.app/probes.{character}`.
+pub fn annotation(key: &str) -> bool {
+    let name = key.rsplit_once('/').map_or(key, |(_, name)| name);
+    !name.is_empty()
+        && name.len() <= MAX_LENGTH
+        && name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_' || c == b'.')
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+/// Lowercase the name, and replace the runs of invalid characters with a single `-`.
+fn sanitize(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for c in name.chars().map(|c| c.to_ascii_lowercase()) {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            result.push(c);
+        } else if !result.is_empty() && !result.ends_with('-') {
+            result.push('-');
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_name_unchanged() {
+        assert_eq!(name(&["web", "builder"]), "web-builder");
+        assert_eq!(name(&["gcr-io-paketo-buildpacks-builder"]), "gcr-io-paketo-buildpacks-builder");
+    }
+
+    #[test]
+    fn test_long_name_bounded() {
+        let long = "a".repeat(80);
+        let result = name(&[&long, "builder"]);
+
+        assert!(valid(&result));
+        assert_eq!(result.len(), MAX_LENGTH);
+        assert_eq!(result, name(&[&long, "builder"]));
+    }
+
+    #[test]
+    fn test_truncated_names_not_collide() {
+        let prefix = "a".repeat(70);
+
+        assert_ne!(name(&[&prefix, "x-builder"]), name(&[&prefix, "y-builder"]));
+    }
+
+    #[test]
+    fn test_invalid_characters_replaced() {
+        let result = name(&["My_App.v2", "builder"]);
+
+        assert!(valid(&result));
+        assert!(result.starts_with("my-app-v2-builder-"));
+        assert_ne!(result, name(&["my-app-v2", "builder"]));
+    }
+
+    #[test]
+    fn test_all_invalid_characters_replaced() {
+        let result = name(&["_", "."]);
+
+        assert!(valid(&result));
+        assert!(result.starts_with("a-"));
+        assert_eq!(result.len(), HASH_LENGTH + 2);
+        assert_ne!(result, name(&["__"]));
+    }
+
+    #[test]
+    fn test_valid() {
+        assert!(valid("web-1"));
+        assert!(!valid(""));
+        assert!(!valid("-web"));
+        assert!(!valid("web-"));
+        assert!(!valid("Web"));
+        assert!(!valid(&"a".repeat(64)));
+    }
+
+    #[test]
+    fn test_annotation() {
+        assert!(annotation("amphitheatre.app/probes.web"));
+        assert!(annotation(&format!("amphitheatre.app/probes.{}", "a".repeat(56))));
+        assert!(!annotation(&format!("amphitheatre.app/image-pull-secrets.{}", "a".repeat(45))));
+        assert!(!annotation("amphitheatre.app/probes.web-"));
+        assert!(!annotation("amphitheatre.app/"));
+    }
+}
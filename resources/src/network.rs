@@ -0,0 +1,263 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use amp_common::resource::Playbook;
+use k8s_openapi::api::networking::v1::{
+    IPBlock, NetworkPolicy, NetworkPolicyEgressRule, NetworkPolicyIngressRule, NetworkPolicyPeer, NetworkPolicyPort,
+    NetworkPolicySpec,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{LabelSelector, LabelSelectorRequirement};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::api::{Patch, PatchParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::{Error, Result};
+use crate::namespace;
+
+/// The annotation of playbook holding its network section as a JSON document,
+/// e.g. `{"egress": [{"cidr": "10.0.0.0/8", "ports": [5432]}]}`. The actors
+/// are isolated only if it's set.
+pub const NETWORK_ANNOTATION: &str = "amphitheatre.app/network";
+
+/// The label of the pods of actors, the builders are never isolated.
+const CHARACTER_LABEL: &str = "amphitheatre.app/character";
+
+/// The label set on every namespace by Kubernetes, the value is its name.
+const NAMESPACE_NAME_LABEL: &str = "kubernetes.io/metadata.name";
+
+/// The network section of playbook, the actors can only talk to each other,
+/// the DNS, and the declared external endpoints.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Network {
+    /// The external endpoints the actors are allowed to connect to.
+    #[serde(default)]
+    pub egress: Vec<Endpoint>,
+}
+
+/// An external endpoint, all ports are allowed if none is declared.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Endpoint {
+    pub cidr: String,
+    #[serde(default)]
+    pub ports: Vec<i32>,
+}
+
+impl Network {
+    /// Check the endpoints are IP blocks in the CIDR notation, e.g. `10.0.0.0/8`,
+    /// with the valid ports, the API server rejects the malformed policies.
+    pub fn validate(&self) -> Result<()> {
+        for endpoint in &self.egress {
+            if !cidr(&endpoint.cidr) {
+                return Err(Error::InvalidNetwork(format!("invalid egress cidr {}", endpoint.cidr)));
+            }
+            if let Some(port) = endpoint.ports.iter().find(|port| !(1..=65535).contains(*port)) {
+                return Err(Error::InvalidNetwork(format!("invalid egress port {} of {}", port, endpoint.cidr)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if the value is an IPv4 or IPv6 block in the CIDR notation.
+fn cidr(value: &str) -> bool {
+    let Some((address, prefix)) = value.split_once('/') else {
+        return false;
+    };
+    let Ok(prefix) = prefix.parse::<u8>() else {
+        return false;
+    };
+
+    match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => prefix <= 32,
+        Ok(IpAddr::V6(_)) => prefix <= 128,
+        Err(_) => false,
+    }
+}
+
+/// Returns the network section of playbook, if any.
+pub fn of(playbook: &Playbook) -> Result<Option<Network>> {
+    match playbook.annotations().get(NETWORK_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Apply the NetworkPolicies isolating the actors in the namespace of playbook,
+/// the traffic from and to the system namespaces, e.g. the one of Amphitheatre
+/// and the one of the ingress controller, is allowed.
+pub async fn apply(client: &Client, playbook: &Playbook, network: &Network, system: &[String]) -> Result<()> {
+    let namespace = namespace::of(playbook);
+    let api: Api<NetworkPolicy> = Api::namespaced(client.clone(), &namespace);
+
+    let params = &PatchParams::apply("amp-controllers").force();
+    for policy in policies(network, system) {
+        let name = policy.name_any();
+        api.patch(&name, params, &Patch::Apply(&policy)).await.map_err(Error::KubeError)?;
+        info!("Applied NetworkPolicy {} in namespace {}", name, namespace);
+    }
+
+    Ok(())
+}
+
+/// Build the default-deny policy, and the rules allowing the traffic in the
+/// playbook, to the DNS, from and to the system namespaces, and to the
+/// declared external endpoints.
+pub fn policies(network: &Network, system: &[String]) -> Vec<NetworkPolicy> {
+    let mut policies = vec![
+        new("amp-default-deny", vec![], vec![]),
+        new(
+            "amp-allow-playbook",
+            vec![NetworkPolicyIngressRule { from: Some(vec![same_namespace()]), ports: None }],
+            vec![
+                NetworkPolicyEgressRule { to: Some(vec![same_namespace()]), ports: None },
+                NetworkPolicyEgressRule {
+                    to: Some(vec![NetworkPolicyPeer {
+                        namespace_selector: Some(LabelSelector::default()),
+                        ..Default::default()
+                    }]),
+                    ports: Some(vec![port("UDP", 53), port("TCP", 53)]),
+                },
+            ],
+        ),
+    ];
+
+    if !system.is_empty() {
+        let peer = NetworkPolicyPeer {
+            namespace_selector: Some(LabelSelector {
+                match_expressions: Some(vec![LabelSelectorRequirement {
+                    key: NAMESPACE_NAME_LABEL.into(),
+                    operator: "In".into(),
+                    values: Some(system.to_vec()),
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        policies.push(new(
+            "amp-allow-system",
+            vec![NetworkPolicyIngressRule { from: Some(vec![peer.clone()]), ports: None }],
+            vec![NetworkPolicyEgressRule { to: Some(vec![peer]), ports: None }],
+        ));
+    }
+
+    if !network.egress.is_empty() {
+        let rules = network.egress.iter().map(|endpoint| NetworkPolicyEgressRule {
+            to: Some(vec![NetworkPolicyPeer {
+                ip_block: Some(IPBlock { cidr: endpoint.cidr.clone(), except: None }),
+                ..Default::default()
+            }]),
+            ports: (!endpoint.ports.is_empty())
+                .then(|| endpoint.ports.iter().map(|number| port("TCP", *number)).collect()),
+        });
+        policies.push(new("amp-allow-egress", vec![], rules.collect()));
+    }
+
+    policies
+}
+
+fn new(name: &str, ingress: Vec<NetworkPolicyIngressRule>, egress: Vec<NetworkPolicyEgressRule>) -> NetworkPolicy {
+    let selector = LabelSelector {
+        match_expressions: Some(vec![LabelSelectorRequirement {
+            key: CHARACTER_LABEL.into(),
+            operator: "Exists".into(),
+            values: None,
+        }]),
+        ..Default::default()
+    };
+
+    NetworkPolicy {
+        metadata: ObjectMeta {
+            name: Some(name.into()),
+            labels: Some(BTreeMap::from([("app.kubernetes.io/managed-by".into(), "Amphitheatre".into())])),
+            ..Default::default()
+        },
+        spec: Some(NetworkPolicySpec {
+            pod_selector: selector,
+            policy_types: Some(vec!["Ingress".into(), "Egress".into()]),
+            ingress: (!ingress.is_empty()).then_some(ingress),
+            egress: (!egress.is_empty()).then_some(egress),
+        }),
+    }
+}
+
+fn same_namespace() -> NetworkPolicyPeer {
+    NetworkPolicyPeer { pod_selector: Some(LabelSelector::default()), ..Default::default() }
+}
+
+fn port(protocol: &str, number: i32) -> NetworkPolicyPort {
+    NetworkPolicyPort { protocol: Some(protocol.into()), port: Some(IntOrString::Int(number)), end_port: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies_without_egress() {
+        let policies = policies(&Network::default(), &[]);
+        let names: Vec<String> = policies.iter().map(|policy| policy.name_any()).collect();
+        assert_eq!(names, vec!["amp-default-deny", "amp-allow-playbook"]);
+
+        let deny = policies[0].spec.as_ref().unwrap();
+        assert_eq!(deny.ingress, None);
+        assert_eq!(deny.egress, None);
+    }
+
+    #[test]
+    fn test_policies_with_egress() {
+        let network: Network =
+            serde_json::from_str(r#"{"egress": [{"cidr": "10.0.0.0/8", "ports": [5432]}, {"cidr": "0.0.0.0/0"}]}"#)
+                .unwrap();
+        let policies = policies(&network, &[]);
+        assert_eq!(policies.len(), 3);
+
+        let egress = policies[2].spec.as_ref().unwrap().egress.as_ref().unwrap();
+        assert_eq!(egress[0].ports.as_ref().unwrap()[0].port, Some(IntOrString::Int(5432)));
+        assert_eq!(egress[1].ports, None);
+    }
+
+    #[test]
+    fn test_policies_with_system() {
+        let system = vec!["amp-system".to_string(), "ingress-nginx".to_string()];
+        let policies = policies(&Network::default(), &system);
+        assert_eq!(policies[2].name_any(), "amp-allow-system");
+
+        let spec = policies[2].spec.as_ref().unwrap();
+        let peer = &spec.ingress.as_ref().unwrap()[0].from.as_ref().unwrap()[0];
+        let requirement = &peer.namespace_selector.as_ref().unwrap().match_expressions.as_ref().unwrap()[0];
+        assert_eq!(requirement.values, Some(system));
+        assert_eq!(spec.egress.as_ref().unwrap()[0].to.as_ref().unwrap()[0], *peer);
+    }
+
+    #[test]
+    fn test_validate() {
+        let network = |cidr: &str, ports: Vec<i32>| Network { egress: vec![Endpoint { cidr: cidr.into(), ports }] };
+        assert!(network("10.0.0.0/8", vec![5432]).validate().is_ok());
+        assert!(network("0.0.0.0/0", vec![]).validate().is_ok());
+        assert!(network("fd00::/8", vec![]).validate().is_ok());
+
+        assert!(network("10.0.0.0", vec![]).validate().is_err());
+        assert!(network("10.0.0.0/33", vec![]).validate().is_err());
+        assert!(network("db.example.com/32", vec![]).validate().is_err());
+        assert!(network("10.0.0.0/8", vec![0]).validate().is_err());
+        assert!(network("10.0.0.0/8", vec![65536]).validate().is_err());
+    }
+}
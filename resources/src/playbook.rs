@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 use amp_common::resource::{CharacterSpec, Playbook, PlaybookState};
@@ -19,7 +20,8 @@ use amp_common::resource::{CharacterSpec, Playbook, PlaybookState};
 use k8s_openapi::apiextensions_apiserver as server;
 use server::pkg::apis::apiextensions::v1::CustomResourceDefinition;
 
-use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
 use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
 use kube::core::ObjectList;
 use kube::{Api, Client, CustomResourceExt, ResourceExt};
@@ -30,6 +32,29 @@ use tracing::{debug, info};
 
 use super::error::{Error, Result};
 
+/// The annotation recording the last time the playbook was used, in RFC 3339 format.
+pub const LAST_ACTIVITY_ANNOTATION: &str = "amphitheatre.app/last-activity";
+
+/// The annotation overriding the idle timeout (in seconds) of the playbook.
+pub const IDLE_TIMEOUT_ANNOTATION: &str = "amphitheatre.app/idle-timeout";
+
+/// The annotation overriding the TTL (in seconds) of the playbook, it's deleted
+/// once the TTL elapses since the last activity.
+pub const TTL_ANNOTATION: &str = "amphitheatre.app/ttl";
+
+/// The annotation of the TTL before it was renamed to `TTL_ANNOTATION`, the
+/// existing playbooks are still annotated with it.
+const LEGACY_TTL_ANNOTATION: &str = "ttl";
+
+/// The annotation recording the expiration time that has been warned about, in RFC 3339 format.
+pub const EXPIRY_WARNED_ANNOTATION: &str = "amphitheatre.app/expiry-warned";
+
+/// The annotation recording the time the playbook was archived, in RFC 3339 format.
+pub const ARCHIVED_ANNOTATION: &str = "amphitheatre.app/archived";
+
+/// The condition type of playbook reporting whether all the partners are resolved.
+pub const PARTNERS_RESOLVED_CONDITION_TYPE: &str = "PartnersResolved";
+
 pub async fn install(client: &Client) -> Result<()> {
     let api: Api<CustomResourceDefinition> = Api::all(client.clone());
     let crd = Playbook::crd();
@@ -82,20 +107,25 @@ pub async fn create(client: &Client, playbook: &Playbook) -> Result<Playbook> {
 }
 
 pub async fn add(client: &Client, playbook: &Playbook, character: CharacterSpec) -> Result<()> {
+    extend(client, playbook, vec![character]).await
+}
+
+/// Add the characters to the playbook at once, after the existing ones.
+pub async fn extend(client: &Client, playbook: &Playbook, added: Vec<CharacterSpec>) -> Result<()> {
     let api: Api<Playbook> = Api::all(client.clone());
-    let character_name = character.meta.name.clone();
+    let names: Vec<String> = added.iter().map(|character| character.meta.name.clone()).collect();
 
     let mut characters: Vec<CharacterSpec> = vec![];
     if let Some(items) = &playbook.spec.characters {
         characters.clone_from(items);
     }
-    characters.push(character);
+    characters.extend(added);
 
     let params = &PatchParams::apply("amp-controllers");
     let patch = json!({"spec": { "characters": characters }});
     let playbook = api.patch(&playbook.name_any(), params, &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
 
-    info!("Added character {:?} to {}", character_name, playbook.name_any());
+    info!("Added characters {:?} to {}", names, playbook.name_any());
 
     Ok(())
 }
@@ -113,6 +143,106 @@ pub async fn patch_status(client: &Client, playbook: &Playbook, condition: Condi
     Ok(())
 }
 
+/// Add or replace the condition of the same type, the other conditions are kept.
+pub async fn upsert_condition(client: &Client, playbook: &Playbook, condition: Condition) -> Result<()> {
+    let api: Api<Playbook> = Api::all(client.clone());
+
+    let mut conditions = playbook.status.as_ref().map(|status| status.conditions.clone()).unwrap_or_default();
+    conditions.retain(|c| c.type_ != condition.type_);
+    conditions.push(condition.clone());
+
+    let status = json!({ "status": { "conditions": conditions }});
+    api.patch_status(playbook.name_any().as_str(), &PatchParams::default(), &Patch::Merge(&status))
+        .await
+        .map_err(Error::KubeError)?;
+    info!(
+        "Upserted condition {:?} with reason {:?} for Playbook {}",
+        condition.type_,
+        condition.reason,
+        playbook.name_any()
+    );
+
+    Ok(())
+}
+
+/// Remove the condition of the given type, if the playbook has it.
+pub async fn remove_condition(client: &Client, playbook: &Playbook, type_: &str) -> Result<()> {
+    let mut conditions = playbook.status.as_ref().map(|status| status.conditions.clone()).unwrap_or_default();
+    if !conditions.iter().any(|c| c.type_ == type_) {
+        return Ok(());
+    }
+    conditions.retain(|c| c.type_ != type_);
+
+    let api: Api<Playbook> = Api::all(client.clone());
+    let status = json!({ "status": { "conditions": conditions }});
+    api.patch_status(playbook.name_any().as_str(), &PatchParams::default(), &Patch::Merge(&status))
+        .await
+        .map_err(Error::KubeError)?;
+    info!("Removed condition {:?} from Playbook {}", type_, playbook.name_any());
+
+    Ok(())
+}
+
+/// Read the requested TTL (in seconds) of playbook, falling back to the legacy annotation.
+pub fn ttl(playbook: &Playbook) -> Option<i64> {
+    let annotations = playbook.annotations();
+    let value = annotations.get(TTL_ANNOTATION).or_else(|| annotations.get(LEGACY_TTL_ANNOTATION))?;
+    value.parse().ok()
+}
+
+/// Build the condition reporting the partners failed to resolve, the message
+/// lists the errors of each partner, or all are resolved if there are none.
+pub fn partners_resolved(errors: &[(String, String)]) -> Condition {
+    let (status, reason) = match errors.is_empty() {
+        true => ("True", "Resolved"),
+        false => ("False", "ResolveFailed"),
+    };
+    let message = errors.iter().map(|(name, error)| format!("{}: {}", name, error)).collect::<Vec<_>>().join("; ");
+
+    Condition {
+        type_: PARTNERS_RESOLVED_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: reason.into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// Add or overwrite the annotations of the playbook.
+pub async fn annotate(client: &Client, playbook: &Playbook, annotations: BTreeMap<String, String>) -> Result<()> {
+    let api: Api<Playbook> = Api::all(client.clone());
+
+    let patch = json!({"metadata": { "annotations": annotations }});
+    let playbook = api
+        .patch(&playbook.name_any(), &PatchParams::default(), &Patch::Merge(&patch))
+        .await
+        .map_err(Error::KubeError)?;
+    debug!("Annotated playbook {} with {:?}", playbook.name_any(), annotations);
+
+    Ok(())
+}
+
+/// Update the title and description of the playbook, the ones not given are kept.
+pub async fn describe(
+    client: &Client,
+    playbook: &Playbook,
+    title: Option<&str>,
+    description: Option<&str>,
+) -> Result<Playbook> {
+    let api: Api<Playbook> = Api::all(client.clone());
+
+    let mut spec = serde_json::Map::new();
+    if let Some(title) = title {
+        spec.insert("title".into(), json!(title));
+    }
+    if let Some(description) = description {
+        spec.insert("description".into(), json!(description));
+    }
+    let patch = json!({ "spec": spec });
+    api.patch(&playbook.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)
+}
+
 /// List all playbooks
 pub async fn list(client: &Client) -> Result<ObjectList<Playbook>> {
     let api: Api<Playbook> = Api::all(client.clone());
@@ -0,0 +1,266 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::{Actor, ActorSpec};
+use k8s_openapi::api::admissionregistration::v1::{
+    RuleWithOperations, ServiceReference, ValidatingWebhook, ValidatingWebhookConfiguration, WebhookClientConfig,
+};
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, ObjectMeta, Time};
+use k8s_openapi::chrono::Utc;
+use k8s_openapi::ByteString;
+use kube::{Api, Client};
+use oci_distribution::Reference;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::containers::sidecar::Containers;
+use crate::error::{Error, Result};
+use crate::{actor, helm, rbac};
+
+/// The name of the ConfigMap holding the registry policy of the cluster.
+pub const POLICY_CONFIG_MAP: &str = "amp-registry-policy";
+
+/// The key of the ConfigMap holding the policy as a TOML document.
+const POLICY_KEY: &str = "policy";
+
+/// The name of the ValidatingWebhookConfiguration enforcing the registry policy.
+pub const WEBHOOK_NAME: &str = "amp-registry-policy";
+
+/// The path of the admission webhook of the apiserver validating the actors.
+const WEBHOOK_PATH: &str = "/v1/admission/actors";
+
+/// The condition type of actor reporting the policy violations.
+pub const REJECTED_CONDITION_TYPE: &str = "Rejected";
+
+/// The cluster policy restricting which registries the actor images may be
+/// pulled from or pushed to, e.g.
+///
+/// ```toml
+/// allow = ["ghcr.io/amphitheatre-app/**", "docker.io/library/*"]
+/// ```
+///
+/// The patterns are matched against `registry/repository` of the image, `*`
+/// matches within a path segment and `**` matches across segments. Docker Hub
+/// images are normalized to `docker.io`, and everything is allowed if the
+/// allow-list is empty.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct RegistryPolicy {
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+impl RegistryPolicy {
+    /// Parse the policy from the TOML document.
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(Error::TomlDeserializeError)
+    }
+
+    /// Returns true if the image is allowed by this policy.
+    pub fn allows(&self, image: &str) -> bool {
+        if self.allow.is_empty() {
+            return true;
+        }
+
+        let Ok(reference) = image.parse::<Reference>() else {
+            return false;
+        };
+        let name = format!("{}/{}", reference.registry(), reference.repository());
+        self.allow.iter().any(|pattern| matches(pattern.as_bytes(), name.as_bytes()))
+    }
+
+    /// Check the image of actor, the Helm charts are not images so they are always allowed.
+    pub fn check(&self, spec: &ActorSpec) -> Result<()> {
+        if spec.image.is_empty() || helm::chart(&spec.character).is_some() || self.allows(&spec.image) {
+            return Ok(());
+        }
+
+        Err(Error::ImageNotAllowed(spec.image.clone()))
+    }
+
+    /// Check the images of the init containers and sidecars running along with the actor.
+    pub fn check_containers(&self, containers: &Containers) -> Result<()> {
+        let specs = containers.init_containers.iter().chain(containers.sidecars.iter());
+        match specs.map(|spec| &spec.image).find(|image| !self.allows(image)) {
+            Some(image) => Err(Error::ImageNotAllowed(image.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Load the registry policy from the ConfigMap in the given namespace.
+pub async fn load(client: &Client, namespace: &str) -> Result<RegistryPolicy> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+
+    let Some(config_map) = api.get_opt(POLICY_CONFIG_MAP).await.map_err(Error::KubeError)? else {
+        debug!("The {} was not found, all registries are allowed.", POLICY_CONFIG_MAP);
+        return Ok(RegistryPolicy::default());
+    };
+
+    from(&config_map)
+}
+
+/// Read the registry policy from the ConfigMap.
+pub fn from(config_map: &ConfigMap) -> Result<RegistryPolicy> {
+    match config_map.data.as_ref().and_then(|data| data.get(POLICY_KEY)) {
+        Some(content) => RegistryPolicy::parse(content),
+        None => Ok(RegistryPolicy::default()),
+    }
+}
+
+/// Build the condition reporting the policy violation.
+pub fn condition(message: String) -> Condition {
+    Condition {
+        type_: REJECTED_CONDITION_TYPE.into(),
+        status: "True".into(),
+        reason: "RegistryNotAllowed".into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// Report the policy violation in the conditions of actor, unless it's already reported.
+pub async fn reject(client: &Client, actor: &Actor, message: String) -> Result<()> {
+    let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    if conditions.iter().any(|c| c.type_ == REJECTED_CONDITION_TYPE && c.message == message) {
+        return Ok(());
+    }
+
+    actor::upsert_condition(client, actor, condition(message)).await
+}
+
+/// Clear the reported policy violation of actor once its images are allowed.
+pub async fn accept(client: &Client, actor: &Actor) -> Result<()> {
+    actor::remove_condition(client, actor, REJECTED_CONDITION_TYPE).await
+}
+
+/// Build the ValidatingWebhookConfiguration calling the admission webhook of the
+/// apiserver behind the given Service, the API server only calls the webhooks
+/// over TLS, so the Service must serve the certificate signed by `ca_bundle`.
+pub fn webhook(
+    namespace: &str,
+    service: &str,
+    port: i32,
+    ca_bundle: Option<Vec<u8>>,
+) -> ValidatingWebhookConfiguration {
+    ValidatingWebhookConfiguration {
+        metadata: ObjectMeta { name: Some(WEBHOOK_NAME.into()), labels: Some(rbac::labels()), ..Default::default() },
+        webhooks: Some(vec![ValidatingWebhook {
+            name: "actors.amphitheatre.app".into(),
+            admission_review_versions: vec!["v1".into()],
+            client_config: WebhookClientConfig {
+                service: Some(ServiceReference {
+                    namespace: namespace.into(),
+                    name: service.into(),
+                    path: Some(WEBHOOK_PATH.into()),
+                    port: Some(port),
+                }),
+                ca_bundle: ca_bundle.map(ByteString),
+                ..Default::default()
+            },
+            rules: Some(vec![RuleWithOperations {
+                api_groups: Some(vec!["amphitheatre.app".into()]),
+                api_versions: Some(vec!["*".into()]),
+                operations: Some(vec!["CREATE".into(), "UPDATE".into()]),
+                resources: Some(vec!["actors".into()]),
+                scope: Some("Namespaced".into()),
+            }]),
+            // The controllers check the policy as well, so the actors are not admitted
+            // unchecked if the apiserver is unavailable, and nothing is blocked either.
+            failure_policy: Some("Ignore".into()),
+            side_effects: "None".into(),
+            timeout_seconds: Some(10),
+            ..Default::default()
+        }]),
+    }
+}
+
+/// Glob matching, `*` matches anything except `/`, and `**` matches anything.
+fn matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=name.len()).any(|i| matches(rest, &name[i..])),
+        [b'*', rest @ ..] => {
+            let segment = name.iter().position(|c| *c == b'/').unwrap_or(name.len());
+            (0..=segment).any(|i| matches(rest, &name[i..]))
+        }
+        [c, rest @ ..] => name.first() == Some(c) && matches(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::containers::sidecar::ContainerSpec;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(b"ghcr.io/amp/*", b"ghcr.io/amp/app"));
+        assert!(!matches(b"ghcr.io/amp/*", b"ghcr.io/amp/team/app"));
+        assert!(matches(b"ghcr.io/amp/**", b"ghcr.io/amp/team/app"));
+        assert!(matches(b"*.amazonaws.com/**", b"1234.dkr.ecr.amazonaws.com/app"));
+        assert!(!matches(b"ghcr.io/amp/*", b"ghcr.io/other/app"));
+    }
+
+    #[test]
+    fn test_allows() {
+        let policy = RegistryPolicy { allow: vec!["ghcr.io/amphitheatre-app/*".into(), "docker.io/library/*".into()] };
+
+        assert!(policy.allows("ghcr.io/amphitheatre-app/amp-syncer:v0.9.0"));
+        assert!(policy.allows("nginx:latest"));
+        assert!(!policy.allows("quay.io/amphitheatre-app/app:v1"));
+        assert!(!policy.allows("ghcr.io/someone/app:v1"));
+    }
+
+    #[test]
+    fn test_check_containers() {
+        let policy = RegistryPolicy { allow: vec!["docker.io/library/*".into()] };
+        let spec = |image: &str| ContainerSpec { name: "sidecar".into(), image: image.into(), ..Default::default() };
+
+        let allowed = Containers { init_containers: vec![spec("busybox")], sidecars: vec![spec("nginx:latest")] };
+        assert!(policy.check_containers(&allowed).is_ok());
+
+        let denied =
+            Containers { init_containers: vec![spec("busybox")], sidecars: vec![spec("quay.io/someone/proxy")] };
+        let err = policy.check_containers(&denied).unwrap_err();
+        assert!(matches!(err, Error::ImageNotAllowed(image) if image == "quay.io/someone/proxy"));
+    }
+
+    #[test]
+    fn test_allows_everything_without_patterns() {
+        assert!(RegistryPolicy::default().allows("quay.io/anyone/app:v1"));
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let policy = RegistryPolicy::parse(r#"allow = ["ghcr.io/**"]"#).unwrap();
+        assert_eq!(policy.allow, vec!["ghcr.io/**".to_string()]);
+
+        assert_eq!(RegistryPolicy::parse("").unwrap(), RegistryPolicy::default());
+    }
+
+    #[test]
+    fn test_webhook() {
+        let configuration = webhook("amp-system", "amp-apiserver", 443, None);
+        let webhook = &configuration.webhooks.unwrap()[0];
+
+        let service = webhook.client_config.service.as_ref().unwrap();
+        assert_eq!(service.path.as_deref(), Some(WEBHOOK_PATH));
+        assert_eq!(service.namespace, "amp-system");
+        let rule = &webhook.rules.as_ref().unwrap()[0];
+        assert_eq!(rule.resources, Some(vec!["actors".to_string()]));
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_openapi::api::core::v1::{Container, ExecAction, HTTPGetAction, Probe as ContainerProbe, TCPSocketAction};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use k8s_openapi::chrono::Utc;
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The annotation of actor holding its health probes as a JSON document, e.g.
+/// `{"readiness": {"http": {"path": "/healthz", "port": 8080}, "period_seconds": 5}}`.
+/// The playbook declares the probes of a character with the annotation
+/// suffixed by its name, e.g. `amphitheatre.app/probes.web`.
+pub const PROBES_ANNOTATION: &str = "amphitheatre.app/probes";
+
+/// The condition type of actor reporting whether its pods are ready.
+pub const READY_CONDITION_TYPE: &str = "Ready";
+
+/// The health probes of the application container.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Probes {
+    /// Receive the traffic only after it succeeds.
+    pub readiness: Option<Probe>,
+    /// Restart the container after it fails.
+    pub liveness: Option<Probe>,
+    /// Hold the other probes until it succeeds, for the slow starting containers.
+    pub startup: Option<Probe>,
+}
+
+/// A probe checking the container by one of the handlers, with the thresholds.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Probe {
+    pub http: Option<HttpProbe>,
+    pub tcp: Option<TcpProbe>,
+    pub exec: Option<ExecProbe>,
+    pub initial_delay_seconds: Option<i32>,
+    pub period_seconds: Option<i32>,
+    pub timeout_seconds: Option<i32>,
+    pub failure_threshold: Option<i32>,
+    pub success_threshold: Option<i32>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct HttpProbe {
+    #[serde(default = "default_path")]
+    pub path: String,
+    pub port: i32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct TcpProbe {
+    pub port: i32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct ExecProbe {
+    pub command: Vec<String>,
+}
+
+fn default_path() -> String {
+    "/".into()
+}
+
+impl Probes {
+    pub fn is_empty(&self) -> bool {
+        self.readiness.is_none() && self.liveness.is_none() && self.startup.is_none()
+    }
+
+    /// Apply the probes to the container, the declared ones replace the defaults.
+    pub fn apply(&self, container: &mut Container) {
+        if let Some(probe) = &self.readiness {
+            container.readiness_probe = Some(probe.build());
+        }
+        if let Some(probe) = &self.liveness {
+            container.liveness_probe = Some(probe.build());
+        }
+        if let Some(probe) = &self.startup {
+            container.startup_probe = Some(probe.build());
+        }
+    }
+}
+
+impl Probe {
+    pub fn build(&self) -> ContainerProbe {
+        ContainerProbe {
+            http_get: self.http.as_ref().map(|http| HTTPGetAction {
+                path: Some(http.path.clone()),
+                port: IntOrString::Int(http.port),
+                ..Default::default()
+            }),
+            tcp_socket: self
+                .tcp
+                .as_ref()
+                .map(|tcp| TCPSocketAction { port: IntOrString::Int(tcp.port), ..Default::default() }),
+            exec: self.exec.as_ref().map(|exec| ExecAction { command: Some(exec.command.clone()) }),
+            initial_delay_seconds: self.initial_delay_seconds,
+            period_seconds: self.period_seconds,
+            timeout_seconds: self.timeout_seconds,
+            failure_threshold: self.failure_threshold,
+            success_threshold: self.success_threshold,
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns the health probes of actor.
+pub fn probes(actor: &Actor) -> Result<Probes> {
+    match actor.annotations().get(PROBES_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map_err(Error::SerializationError),
+        None => Ok(Probes::default()),
+    }
+}
+
+/// Returns the number of the ready replicas and the desired ones of the
+/// Deployment or StatefulSet of actor, None if neither is deployed yet.
+pub async fn replicas(client: &Client, namespace: &str, name: &str) -> Result<Option<(i32, i32)>> {
+    let api: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    if let Some(deployment) = api.get_opt(name).await.map_err(Error::KubeError)? {
+        let desired = deployment.spec.and_then(|spec| spec.replicas).unwrap_or(1);
+        let ready = deployment.status.and_then(|status| status.ready_replicas).unwrap_or_default();
+        return Ok(Some((ready, desired)));
+    }
+
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), namespace);
+    if let Some(statefulset) = api.get_opt(name).await.map_err(Error::KubeError)? {
+        let desired = statefulset.spec.and_then(|spec| spec.replicas).unwrap_or(1);
+        let ready = statefulset.status.and_then(|status| status.ready_replicas).unwrap_or_default();
+        return Ok(Some((ready, desired)));
+    }
+
+    Ok(None)
+}
+
+/// Build the condition reporting whether the pods of actor are ready.
+pub fn ready(ready: i32, desired: i32) -> Condition {
+    let (status, reason) = match ready >= desired {
+        true => ("True", "PodsReady"),
+        false => ("False", "Progressing"),
+    };
+
+    Condition {
+        type_: READY_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: reason.into(),
+        message: format!("{} of {} replicas are ready", ready, desired),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_apply_probes() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert!(probes(&actor).unwrap().is_empty());
+
+        actor.annotations_mut().insert(
+            PROBES_ANNOTATION.into(),
+            r#"{"readiness": {"http": {"port": 8080}, "period_seconds": 5},
+                "liveness": {"exec": {"command": ["cat", "/tmp/healthy"]}, "failure_threshold": 3}}"#
+                .into(),
+        );
+        let mut container = Container {
+            readiness_probe: Some(ContainerProbe { tcp_socket: Some(Default::default()), ..Default::default() }),
+            ..Default::default()
+        };
+        probes(&actor).unwrap().apply(&mut container);
+
+        let readiness = container.readiness_probe.unwrap();
+        assert_eq!(readiness.http_get.unwrap().path.as_deref(), Some("/"));
+        assert_eq!(readiness.tcp_socket, None);
+        assert_eq!(readiness.period_seconds, Some(5));
+        assert_eq!(container.liveness_probe.unwrap().failure_threshold, Some(3));
+        assert_eq!(container.startup_probe, None);
+    }
+
+    #[test]
+    fn test_ready_condition() {
+        assert_eq!(ready(2, 2).status, "True");
+        assert_eq!(ready(0, 0).status, "True");
+
+        let condition = ready(1, 2);
+        assert_eq!(condition.status, "False");
+        assert_eq!(condition.reason, "Progressing");
+        assert_eq!(condition.message, "1 of 2 replicas are ready");
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::{Actor, ActorSpec, Playbook};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::{Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::actor::PLAYBOOK_LABEL;
+use crate::build::{Record, Status};
+use crate::error::{Error, Result};
+use crate::image::{self, DIGEST_ANNOTATION};
+use crate::playbook;
+
+/// The annotation of actor recording the image promoted to it from another
+/// playbook as a JSON document, it's deployed as is instead of being built.
+/// The playbook records the promotion of a character with the annotation
+/// suffixed by its name, e.g. `amphitheatre.app/promoted.web`.
+pub const PROMOTED_ANNOTATION: &str = "amphitheatre.app/promoted";
+
+/// The image built in a playbook and promoted to another one, along with its provenance.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Promotion {
+    /// The image referenced by its digest, e.g. `harbor.amp.io/web@sha256:...`.
+    pub image: String,
+    pub digest: String,
+    /// The playbook and the actor the image was built by.
+    pub playbook: String,
+    pub actor: String,
+    /// The commit the image was built from, if known.
+    pub revision: Option<String>,
+    pub promoted_at: DateTime<Utc>,
+}
+
+impl Promotion {
+    /// Promote the built image of actor, returns an error if it's not built yet.
+    /// The revision is looked up in the recent builds of actor.
+    pub fn of(actor: &Actor, records: &[Record]) -> Result<Self> {
+        let digest = image::built(actor)
+            .ok_or_else(|| Error::InvalidPromotion(format!("actor {} has no built image", actor.name_any())))?;
+        let built = records.iter().rev().find(|r| r.status == Status::Succeeded && r.digest.as_ref() == Some(&digest));
+
+        Ok(Self {
+            image: image::deployed(actor),
+            playbook: actor.labels().get(PLAYBOOK_LABEL).cloned().unwrap_or_default(),
+            actor: actor.spec.name.clone(),
+            revision: built.and_then(|record| record.revision.clone()),
+            promoted_at: Utc::now(),
+            digest,
+        })
+    }
+}
+
+/// Returns the promotion of the character in playbook, if any.
+pub fn of(playbook: &Playbook, character: &str) -> Result<Option<Promotion>> {
+    let Some(content) = playbook.annotations().get(&format!("{}.{}", PROMOTED_ANNOTATION, character)) else {
+        return Ok(None);
+    };
+
+    serde_json::from_str(content).map(Some).map_err(Error::SerializationError)
+}
+
+/// Deploy the promoted image of the character as a prebuilt one, it's neither
+/// built from the source nor synced from local.
+pub fn apply(playbook: &Playbook, spec: &mut ActorSpec) -> Result<()> {
+    if let Some(promotion) = of(playbook, &spec.name)? {
+        spec.image = promotion.image;
+        spec.source = None;
+        spec.live = false;
+    }
+
+    Ok(())
+}
+
+/// Promote the built image of actor to the character of playbook, its digest
+/// is pinned, so the image is verified against it before it's deployed. The
+/// character is added to playbook from the actor if it's not declared yet.
+pub async fn promote(
+    client: &Client,
+    playbook: &Playbook,
+    character: &str,
+    actor: &Actor,
+    records: &[Record],
+) -> Result<Promotion> {
+    let promotion = Promotion::of(actor, records)?;
+    let content = serde_json::to_string(&promotion).map_err(Error::SerializationError)?;
+    let annotations = BTreeMap::from([
+        (format!("{}.{}", PROMOTED_ANNOTATION, character), content),
+        (format!("{}.{}", DIGEST_ANNOTATION, character), promotion.digest.clone()),
+    ]);
+    playbook::annotate(client, playbook, annotations).await?;
+    info!("Promoted {} to {} of playbook {}", promotion.image, character, playbook.name_any());
+
+    let characters = playbook.spec.characters.as_deref().unwrap_or_default();
+    if characters.iter().all(|c| c.meta.name != character) {
+        let mut spec = actor.spec.character.clone();
+        spec.meta.name = character.into();
+        playbook::add(client, playbook, spec).await?;
+    }
+
+    Ok(promotion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::PlaybookSpec;
+    use amp_common::schema::GitReference;
+
+    #[test]
+    fn test_apply_promotion() {
+        let mut playbook = Playbook::new("production", PlaybookSpec::default());
+        let source = GitReference { repo: "https://github.com/amp/web".into(), ..Default::default() };
+        let mut spec = ActorSpec { name: "web".into(), image: "harbor.amp.io/web:abc".into(), ..Default::default() };
+        spec.source = Some(source);
+        spec.live = true;
+
+        apply(&playbook, &mut spec).unwrap();
+        assert_eq!(spec.image, "harbor.amp.io/web:abc");
+
+        let promotion = Promotion {
+            image: "harbor.amp.io/web@sha256:1234".into(),
+            digest: "sha256:1234".into(),
+            playbook: "staging".into(),
+            actor: "web".into(),
+            revision: Some("1111".into()),
+            promoted_at: Utc::now(),
+        };
+        let content = serde_json::to_string(&promotion).unwrap();
+        playbook.annotations_mut().insert(format!("{}.web", PROMOTED_ANNOTATION), content);
+        assert_eq!(of(&playbook, "web").unwrap(), Some(promotion));
+        assert_eq!(of(&playbook, "api").unwrap(), None);
+
+        apply(&playbook, &mut spec).unwrap();
+        assert_eq!(spec.image, "harbor.amp.io/web@sha256:1234");
+        assert!(spec.source.is_none() && !spec.live);
+    }
+}
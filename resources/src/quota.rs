@@ -0,0 +1,193 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Playbook;
+use k8s_openapi::api::core::v1::{LimitRange, LimitRangeItem, LimitRangeSpec, ResourceQuota, ResourceQuotaSpec};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kube::api::{Patch, PatchParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::{Error, Result};
+use crate::namespace;
+
+/// The annotation of playbook overriding the default quota as a JSON document,
+/// e.g. `{"cpu": "8", "memory": "16Gi"}`.
+pub const QUOTA_ANNOTATION: &str = "amphitheatre.app/quota";
+
+const RESOURCE_QUOTA_NAME: &str = "amp-quota";
+const LIMIT_RANGE_NAME: &str = "amp-limits";
+
+/// The resources of the namespace of playbook, in Kubernetes quantities.
+/// Set the default limits along with the cpu and memory totals, otherwise
+/// the pods without their own limits are rejected.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Quota {
+    /// The total cpu requested and limited by all pods.
+    pub cpu: Option<String>,
+    /// The total memory requested and limited by all pods.
+    pub memory: Option<String>,
+    /// The maximum number of pods.
+    pub pods: Option<String>,
+    /// The total storage requested by all persistent volume claims.
+    pub storage: Option<String>,
+    /// The cpu limit of the containers without their own.
+    pub default_cpu: Option<String>,
+    /// The memory limit of the containers without their own.
+    pub default_memory: Option<String>,
+}
+
+impl Quota {
+    /// Returns the quota with the fields set in the overrides replaced.
+    pub fn merge(&self, overrides: &Quota) -> Quota {
+        Quota {
+            cpu: overrides.cpu.clone().or_else(|| self.cpu.clone()),
+            memory: overrides.memory.clone().or_else(|| self.memory.clone()),
+            pods: overrides.pods.clone().or_else(|| self.pods.clone()),
+            storage: overrides.storage.clone().or_else(|| self.storage.clone()),
+            default_cpu: overrides.default_cpu.clone().or_else(|| self.default_cpu.clone()),
+            default_memory: overrides.default_memory.clone().or_else(|| self.default_memory.clone()),
+        }
+    }
+}
+
+/// Returns the quota of the playbook, the defaults overridden by its own.
+pub fn of(playbook: &Playbook, defaults: &Quota) -> Result<Quota> {
+    match playbook.annotations().get(QUOTA_ANNOTATION) {
+        Some(content) => {
+            let overrides: Quota = serde_json::from_str(content).map_err(Error::SerializationError)?;
+            Ok(defaults.merge(&overrides))
+        }
+        None => Ok(defaults.clone()),
+    }
+}
+
+/// Apply the ResourceQuota and LimitRange to the namespace of playbook, if any is configured.
+pub async fn apply(client: &Client, playbook: &Playbook, quota: &Quota) -> Result<()> {
+    let namespace = namespace::of(playbook);
+    let params = &PatchParams::apply("amp-controllers").force();
+
+    if let Some(resource) = resource_quota(quota) {
+        let api: Api<ResourceQuota> = Api::namespaced(client.clone(), &namespace);
+        api.patch(RESOURCE_QUOTA_NAME, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+        info!("Applied ResourceQuota in namespace {}", namespace);
+    }
+
+    if let Some(resource) = limit_range(quota) {
+        let api: Api<LimitRange> = Api::namespaced(client.clone(), &namespace);
+        api.patch(LIMIT_RANGE_NAME, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+        info!("Applied LimitRange in namespace {}", namespace);
+    }
+
+    Ok(())
+}
+
+pub fn resource_quota(quota: &Quota) -> Option<ResourceQuota> {
+    let mut hard = BTreeMap::new();
+    if let Some(cpu) = &quota.cpu {
+        hard.insert("requests.cpu".into(), Quantity(cpu.clone()));
+        hard.insert("limits.cpu".into(), Quantity(cpu.clone()));
+    }
+    if let Some(memory) = &quota.memory {
+        hard.insert("requests.memory".into(), Quantity(memory.clone()));
+        hard.insert("limits.memory".into(), Quantity(memory.clone()));
+    }
+    if let Some(pods) = &quota.pods {
+        hard.insert("pods".into(), Quantity(pods.clone()));
+    }
+    if let Some(storage) = &quota.storage {
+        hard.insert("requests.storage".into(), Quantity(storage.clone()));
+    }
+    if hard.is_empty() {
+        return None;
+    }
+
+    Some(ResourceQuota {
+        metadata: metadata(RESOURCE_QUOTA_NAME),
+        spec: Some(ResourceQuotaSpec { hard: Some(hard), ..Default::default() }),
+        ..Default::default()
+    })
+}
+
+pub fn limit_range(quota: &Quota) -> Option<LimitRange> {
+    let mut limits = BTreeMap::new();
+    if let Some(cpu) = &quota.default_cpu {
+        limits.insert("cpu".to_string(), Quantity(cpu.clone()));
+    }
+    if let Some(memory) = &quota.default_memory {
+        limits.insert("memory".to_string(), Quantity(memory.clone()));
+    }
+    if limits.is_empty() {
+        return None;
+    }
+
+    // The containers request what they are limited to by default.
+    let item = LimitRangeItem {
+        type_: "Container".into(),
+        default: Some(limits.clone()),
+        default_request: Some(limits),
+        ..Default::default()
+    };
+    Some(LimitRange { metadata: metadata(LIMIT_RANGE_NAME), spec: Some(LimitRangeSpec { limits: vec![item] }) })
+}
+
+#[inline]
+fn metadata(name: &str) -> ObjectMeta {
+    ObjectMeta {
+        name: Some(name.into()),
+        labels: Some(BTreeMap::from([("app.kubernetes.io/managed-by".into(), "Amphitheatre".into())])),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let defaults = Quota { cpu: Some("4".into()), pods: Some("20".into()), ..Default::default() };
+        let overrides = Quota { cpu: Some("8".into()), ..Default::default() };
+
+        let quota = defaults.merge(&overrides);
+        assert_eq!(quota.cpu, Some("8".into()));
+        assert_eq!(quota.pods, Some("20".into()));
+        assert_eq!(quota.memory, None);
+    }
+
+    #[test]
+    fn test_resource_quota() {
+        assert!(resource_quota(&Quota::default()).is_none());
+
+        let quota = Quota { memory: Some("16Gi".into()), ..Default::default() };
+        let hard = resource_quota(&quota).unwrap().spec.unwrap().hard.unwrap();
+        assert_eq!(hard.get("limits.memory"), Some(&Quantity("16Gi".into())));
+        assert_eq!(hard.get("requests.memory"), Some(&Quantity("16Gi".into())));
+        assert!(!hard.contains_key("pods"));
+    }
+
+    #[test]
+    fn test_limit_range() {
+        assert!(limit_range(&Quota::default()).is_none());
+
+        let quota = Quota { default_cpu: Some("500m".into()), ..Default::default() };
+        let limits = limit_range(&quota).unwrap().spec.unwrap().limits;
+        assert_eq!(limits[0].type_, "Container");
+        assert_eq!(limits[0].default.as_ref().unwrap().get("cpu"), Some(&Quantity("500m".into())));
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use k8s_openapi::api::rbac::v1::{ClusterRole, ClusterRoleBinding, PolicyRule, Role, RoleBinding, RoleRef, Subject};
+use kube::core::ObjectMeta;
+
+use crate::service_account::BUILDER_SERVICE_ACCOUNT;
+
+/// The components of Amphitheatre accessing the Kubernetes API.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Component {
+    Controllers,
+    ApiServer,
+}
+
+impl Component {
+    pub const ALL: [Component; 2] = [Component::Controllers, Component::ApiServer];
+
+    /// Returns the name of the ClusterRole and ClusterRoleBinding of the component.
+    pub fn role_name(&self) -> &'static str {
+        match self {
+            Component::Controllers => "amp-controllers",
+            Component::ApiServer => "amp-apiserver",
+        }
+    }
+}
+
+/// The permission to the resources of an API group required by the components.
+pub struct Permission {
+    pub group: &'static str,
+    pub resources: &'static [&'static str],
+    pub verbs: &'static [&'static str],
+    pub components: &'static [Component],
+}
+
+const READ: &[&str] = &["get", "list", "watch"];
+const WRITE: &[&str] = &["get", "list", "watch", "create", "update", "patch", "delete"];
+
+const CONTROLLERS: &[Component] = &[Component::Controllers];
+const APISERVER: &[Component] = &[Component::ApiServer];
+const ALL: &[Component] = &[Component::Controllers, Component::ApiServer];
+
+/// The registry of the resources touched by the modules of this crate and the
+/// components using them, update it when a resource module is added or changed.
+pub const REGISTRY: &[Permission] = &[
+    // actor, character, playbook
+    Permission {
+        group: "amphitheatre.app",
+        resources: &["playbooks", "actors", "characters"],
+        verbs: WRITE,
+        components: ALL,
+    },
+    Permission {
+        group: "amphitheatre.app",
+        resources: &["playbooks/status", "actors/status"],
+        verbs: &["get", "update", "patch"],
+        components: CONTROLLERS,
+    },
+    Permission {
+        group: "amphitheatre.app",
+        resources: &["playbooks/finalizers", "actors/finalizers"],
+        verbs: &["update"],
+        components: CONTROLLERS,
+    },
+    // namespace, secret, credential, service_account, service, volume
+    Permission {
+        group: "",
+        resources: &["namespaces", "secrets", "serviceaccounts", "services", "persistentvolumeclaims"],
+        verbs: WRITE,
+        components: CONTROLLERS,
+    },
+    // namespace, actor (detached)
+    Permission {
+        group: "",
+        resources: &["namespaces"],
+        verbs: &["get", "create", "patch", "delete"],
+        components: APISERVER,
+    },
+    // credential (plan)
+    Permission { group: "", resources: &["secrets"], verbs: &["get"], components: APISERVER },
+    // workspace, policy, cost
+    Permission { group: "", resources: &["configmaps"], verbs: READ, components: ALL },
+    // template
+    Permission { group: "", resources: &["configmaps"], verbs: &["create"], components: APISERVER },
+    // envset
+    Permission { group: "", resources: &["configmaps"], verbs: &["patch", "delete"], components: APISERVER },
+    // usage, build
+    Permission { group: "", resources: &["configmaps"], verbs: &["create", "patch"], components: CONTROLLERS },
+    // sbom, signing, kpack::syncer
+    Permission { group: "", resources: &["pods", "pods/log"], verbs: READ, components: ALL },
+    Permission { group: "", resources: &["pods/portforward"], verbs: &["get", "create"], components: APISERVER },
+    Permission { group: "", resources: &["events"], verbs: READ, components: ALL },
+    // terminal
+    Permission { group: "", resources: &["pods/exec"], verbs: &["get", "create"], components: APISERVER },
+    // healing, gc
+    Permission { group: "", resources: &["pods"], verbs: &["delete"], components: CONTROLLERS },
+    // reload
+    Permission { group: "", resources: &["pods/exec"], verbs: &["get", "create"], components: CONTROLLERS },
+    // timeout_controller
+    Permission { group: "events.k8s.io", resources: &["events"], verbs: &["create", "patch"], components: CONTROLLERS },
+    // deployment, statefulset
+    Permission { group: "apps", resources: &["deployments", "statefulsets"], verbs: WRITE, components: CONTROLLERS },
+    // canary
+    Permission { group: "apps", resources: &["replicasets"], verbs: READ, components: CONTROLLERS },
+    // job, cronjob, sbom, signing, verification, gc
+    Permission { group: "batch", resources: &["jobs", "cronjobs"], verbs: WRITE, components: CONTROLLERS },
+    // actor status
+    Permission { group: "apps", resources: &["deployments", "statefulsets"], verbs: READ, components: APISERVER },
+    Permission { group: "batch", resources: &["jobs", "cronjobs"], verbs: READ, components: APISERVER },
+    // replicas, hibernation
+    Permission { group: "apps", resources: &["deployments", "statefulsets"], verbs: &["patch"], components: APISERVER },
+    Permission { group: "batch", resources: &["cronjobs"], verbs: &["patch"], components: APISERVER },
+    Permission {
+        group: "amphitheatre.app",
+        resources: &["playbooks/status"],
+        verbs: &["get", "patch"],
+        components: APISERVER,
+    },
+    // playbook resources
+    Permission { group: "", resources: &["services"], verbs: READ, components: APISERVER },
+    // network, exposure
+    Permission {
+        group: "networking.k8s.io",
+        resources: &["networkpolicies", "ingresses"],
+        verbs: WRITE,
+        components: CONTROLLERS,
+    },
+    // exposure
+    Permission {
+        group: "gateway.networking.k8s.io",
+        resources: &["gateways", "httproutes"],
+        verbs: WRITE,
+        components: CONTROLLERS,
+    },
+    Permission { group: "cert-manager.io", resources: &["certificates"], verbs: WRITE, components: CONTROLLERS },
+    // quota
+    Permission { group: "", resources: &["resourcequotas", "limitranges"], verbs: WRITE, components: CONTROLLERS },
+    // secret (external secrets provider)
+    Permission { group: "external-secrets.io", resources: &["externalsecrets"], verbs: WRITE, components: CONTROLLERS },
+    // helm, service_account
+    Permission {
+        group: "rbac.authorization.k8s.io",
+        resources: &["roles", "rolebindings"],
+        verbs: &["get", "create", "patch"],
+        components: CONTROLLERS,
+    },
+    Permission {
+        group: "rbac.authorization.k8s.io",
+        resources: &["clusterroles"],
+        verbs: &["bind"],
+        components: CONTROLLERS,
+    },
+    // kpack
+    Permission {
+        group: "kpack.io",
+        resources: &["images", "clusterbuilders", "clusterstores", "clusterbuildpacks"],
+        verbs: WRITE,
+        components: CONTROLLERS,
+    },
+    // bootstrap (controllers), health
+    Permission {
+        group: "apiextensions.k8s.io",
+        resources: &["customresourcedefinitions"],
+        verbs: &["get", "list", "watch", "create", "patch"],
+        components: CONTROLLERS,
+    },
+    Permission {
+        group: "apiextensions.k8s.io",
+        resources: &["customresourcedefinitions"],
+        verbs: READ,
+        components: APISERVER,
+    },
+    // argocd
+    Permission { group: "argoproj.io", resources: &["applications"], verbs: WRITE, components: CONTROLLERS },
+    // actor::metrics, metrics
+    Permission { group: "metrics.k8s.io", resources: &["pods"], verbs: READ, components: APISERVER },
+];
+
+/// Returns the policy rules required by the component.
+pub fn rules(component: Component) -> Vec<PolicyRule> {
+    REGISTRY
+        .iter()
+        .filter(|permission| permission.components.contains(&component))
+        .map(|permission| PolicyRule {
+            api_groups: Some(vec![permission.group.into()]),
+            resources: Some(permission.resources.iter().map(|r| r.to_string()).collect()),
+            verbs: permission.verbs.iter().map(|v| v.to_string()).collect(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Build the ClusterRole with the least privilege of the component.
+pub fn cluster_role(component: Component) -> ClusterRole {
+    ClusterRole {
+        metadata: ObjectMeta { name: Some(component.role_name().into()), labels: Some(labels()), ..Default::default() },
+        rules: Some(rules(component)),
+        ..Default::default()
+    }
+}
+
+/// Build the ClusterRoleBinding granting the ClusterRole to the ServiceAccount of component.
+pub fn cluster_role_binding(component: Component, namespace: &str, service_account: &str) -> ClusterRoleBinding {
+    ClusterRoleBinding {
+        metadata: ObjectMeta { name: Some(component.role_name().into()), labels: Some(labels()), ..Default::default() },
+        role_ref: RoleRef {
+            api_group: "rbac.authorization.k8s.io".into(),
+            kind: "ClusterRole".into(),
+            name: component.role_name().into(),
+        },
+        subjects: Some(vec![Subject {
+            kind: "ServiceAccount".into(),
+            name: service_account.into(),
+            namespace: Some(namespace.into()),
+            ..Default::default()
+        }]),
+    }
+}
+
+/// Build the Role of the builds in the namespace of a playbook, it permits to
+/// read the given credential Secrets only, and nothing if there are none.
+pub fn builder_role(secrets: &[String]) -> Role {
+    let mut rules = vec![];
+    if !secrets.is_empty() {
+        rules.push(PolicyRule {
+            api_groups: Some(vec!["".into()]),
+            resources: Some(vec!["secrets".into()]),
+            resource_names: Some(secrets.to_vec()),
+            verbs: vec!["get".into()],
+            ..Default::default()
+        });
+    }
+
+    Role {
+        metadata: ObjectMeta {
+            name: Some(BUILDER_SERVICE_ACCOUNT.into()),
+            labels: Some(labels()),
+            ..Default::default()
+        },
+        rules: Some(rules),
+    }
+}
+
+/// Build the RoleBinding granting the builder Role to the builder ServiceAccount in the namespace.
+pub fn builder_role_binding(namespace: &str) -> RoleBinding {
+    RoleBinding {
+        metadata: ObjectMeta {
+            name: Some(BUILDER_SERVICE_ACCOUNT.into()),
+            labels: Some(labels()),
+            ..Default::default()
+        },
+        role_ref: RoleRef {
+            api_group: "rbac.authorization.k8s.io".into(),
+            kind: "Role".into(),
+            name: BUILDER_SERVICE_ACCOUNT.into(),
+        },
+        subjects: Some(vec![Subject {
+            kind: "ServiceAccount".into(),
+            name: BUILDER_SERVICE_ACCOUNT.into(),
+            namespace: Some(namespace.into()),
+            ..Default::default()
+        }]),
+    }
+}
+
+#[inline]
+pub(crate) fn labels() -> BTreeMap<String, String> {
+    BTreeMap::from([("app.kubernetes.io/managed-by".into(), "Amphitheatre".into())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_of_component() {
+        let rules = rules(Component::ApiServer);
+
+        assert!(rules.iter().all(|rule| rule.verbs.iter().all(|verb| verb != "deletecollection")));
+        assert!(rules.iter().any(|rule| rule.resources == Some(vec!["pods/portforward".into()])));
+        assert!(rules.iter().any(|rule| rule.resources == Some(vec!["pods/exec".into()])));
+        assert!(rules
+            .iter()
+            .filter(|rule| rule.api_groups == Some(vec!["batch".into()]))
+            .all(|rule| rule.verbs.iter().all(|verb| READ.contains(&verb.as_str()))));
+        assert!(rules.iter().any(|rule| rule.resources == Some(vec!["customresourcedefinitions".into()])));
+    }
+
+    #[test]
+    fn test_rules_of_controllers() {
+        let rules = rules(Component::Controllers);
+
+        let granted = |group: &str, resource: &str, verb: &str| {
+            rules.iter().any(|rule| {
+                rule.api_groups == Some(vec![group.into()])
+                    && rule.resources.as_ref().is_some_and(|r| r.iter().any(|r| r == resource))
+                    && rule.verbs.iter().any(|v| v == verb)
+            })
+        };
+        assert!(granted("apiextensions.k8s.io", "customresourcedefinitions", "patch"));
+        assert!(granted("argoproj.io", "applications", "patch"));
+    }
+
+    #[test]
+    fn test_cluster_role_binding() {
+        let binding = cluster_role_binding(Component::Controllers, "amp-system", "default");
+
+        assert_eq!(binding.metadata.name, Some("amp-controllers".into()));
+        assert_eq!(binding.role_ref.name, "amp-controllers");
+
+        let subjects = binding.subjects.unwrap();
+        assert_eq!(subjects[0].name, "default");
+        assert_eq!(subjects[0].namespace, Some("amp-system".into()));
+    }
+
+    #[test]
+    fn test_builder_role() {
+        let role = builder_role(&["amp-registry-credentials".into()]);
+        let rules = role.rules.unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].resource_names, Some(vec!["amp-registry-credentials".into()]));
+        assert_eq!(rules[0].verbs, vec!["get".to_string()]);
+
+        // An empty list of resource names would permit all the Secrets.
+        let role = builder_role(&[]);
+        assert_eq!(role.rules, Some(vec![]));
+    }
+
+    #[test]
+    fn test_builder_role_binding() {
+        let binding = builder_role_binding("amp-playbook");
+
+        assert_eq!(binding.role_ref.kind, "Role");
+        assert_eq!(binding.role_ref.name, BUILDER_SERVICE_ACCOUNT);
+
+        let subjects = binding.subjects.unwrap();
+        assert_eq!(subjects[0].name, BUILDER_SERVICE_ACCOUNT);
+        assert_eq!(subjects[0].namespace, Some("amp-playbook".into()));
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use amp_common::config::{Credentials, RegistryCredential};
+use aws_config::timeout::TimeoutConfig;
+use aws_config::{BehaviorVersion, Region};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use tracing::{debug, error, info};
+use url::Url;
+
+use crate::error::{Error, Result};
+
+/// The metadata server of Google Cloud, serving the tokens of workload identity.
+pub(crate) const GCP_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// The username of the refresh tokens of Azure Container Registry.
+const ACR_USERNAME: &str = "00000000-0000-0000-0000-000000000000";
+
+/// How long to wait for the token services, so a hanging one doesn't stall the refresh.
+const MINT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The cloud registries authenticated by short-lived tokens, they are minted
+/// with the identity of the ServiceAccount of Amphitheatre, e.g. IRSA on EKS
+/// and workload identity on GKE and AKS.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CloudRegistry {
+    /// Amazon ECR in the given region.
+    Ecr { region: String },
+    /// Google Container Registry or Artifact Registry.
+    Gcr,
+    /// Azure Container Registry with the given login server.
+    Acr { host: String },
+}
+
+/// The username and password minted for the registry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub username: String,
+    pub password: String,
+}
+
+/// Detect the cloud registry from the server of credential, None if it
+/// isn't a cloud registry and its credential doesn't expire.
+pub fn detect(server: &str) -> Option<CloudRegistry> {
+    let host = match Url::parse(server) {
+        Ok(url) if url.host_str().is_some() => url.host_str().unwrap_or_default().to_string(),
+        _ => server.split(['/', ':']).next().unwrap_or_default().to_string(),
+    };
+    let host = host.to_lowercase();
+
+    // e.g. 123456789012.dkr.ecr.us-east-1.amazonaws.com
+    let labels: Vec<&str> = host.split('.').collect();
+    if let [_, "dkr", "ecr", region, "amazonaws", "com"] | [_, "dkr", "ecr", region, "amazonaws", "com", "cn"] =
+        labels.as_slice()
+    {
+        return Some(CloudRegistry::Ecr { region: region.to_string() });
+    }
+    if host == "gcr.io" || host.ends_with(".gcr.io") || host.ends_with("-docker.pkg.dev") {
+        return Some(CloudRegistry::Gcr);
+    }
+    if host.ends_with(".azurecr.io") {
+        return Some(CloudRegistry::Acr { host });
+    }
+
+    None
+}
+
+/// Mint new tokens for the cloud registries of the credentials, the ones with
+/// static passwords are kept as they are, unless their passwords were `minted`
+/// before. Returns the new tokens by the servers, the failed ones are skipped.
+pub async fn refresh(credentials: &Credentials, minted: &BTreeSet<String>) -> BTreeMap<String, Token> {
+    let mut tokens = BTreeMap::new();
+    for credential in &credentials.registries {
+        if credential.password.is_some() && !minted.contains(&credential.server) {
+            continue;
+        }
+        let Some(registry) = detect(&credential.server) else {
+            continue;
+        };
+        match mint(&registry).await {
+            Ok(token) => {
+                tokens.insert(credential.server.clone(), token);
+                info!("Refreshed the token of registry {}", credential.server);
+            }
+            Err(err) => error!("Failed to refresh the token of registry {}: {}", credential.server, err),
+        }
+    }
+
+    tokens
+}
+
+/// Merge the minted tokens into the credentials by their servers.
+pub fn merge(credentials: &mut Credentials, tokens: &BTreeMap<String, Token>) {
+    for credential in credentials.registries.iter_mut() {
+        if let Some(token) = tokens.get(&credential.server) {
+            apply(credential, token.clone());
+        }
+    }
+}
+
+/// Mint a token for the cloud registry.
+pub async fn mint(registry: &CloudRegistry) -> Result<Token> {
+    debug!("Mint a token for the registry {:?}", registry);
+    match registry {
+        CloudRegistry::Ecr { region } => ecr(region).await,
+        CloudRegistry::Gcr => gcr().await,
+        CloudRegistry::Acr { host } => acr(host).await,
+    }
+}
+
+fn apply(credential: &mut RegistryCredential, token: Token) {
+    credential.username = Some(token.username);
+    credential.password = Some(token.password);
+}
+
+/// The credentials of IRSA are loaded by the default chain of the AWS SDK,
+/// the token is valid for 12 hours.
+async fn ecr(region: &str) -> Result<Token> {
+    let timeout = TimeoutConfig::builder().operation_timeout(MINT_TIMEOUT).build();
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .region(Region::new(region.to_string()))
+        .timeout_config(timeout)
+        .load()
+        .await;
+    let client = aws_sdk_ecr::Client::new(&config);
+
+    let output = client.get_authorization_token().send().await.map_err(token_error)?;
+    let data = output.authorization_data().first();
+    let token = data.and_then(|data| data.authorization_token());
+    let token = token.ok_or_else(|| token_error(anyhow::anyhow!("Missing the authorization token of ECR")))?;
+
+    // The token is `AWS:<password>` encoded in base64.
+    let decoded = String::from_utf8(STANDARD.decode(token).map_err(token_error)?).map_err(token_error)?;
+    let (username, password) = decoded.split_once(':').ok_or_else(|| token_error(anyhow::anyhow!("Invalid token")))?;
+
+    Ok(Token { username: username.into(), password: password.into() })
+}
+
+/// The access token of the workload identity is valid for an hour.
+async fn gcr() -> Result<Token> {
+    let http = reqwest::Client::builder().timeout(MINT_TIMEOUT).build().map_err(token_error)?;
+    let response = http.get(GCP_TOKEN_URL).header("Metadata-Flavor", "Google").send().await.map_err(token_error)?;
+    let body: Value = response.error_for_status().map_err(token_error)?.json().await.map_err(token_error)?;
+
+    let token = body.get("access_token").and_then(Value::as_str);
+    let token = token.ok_or_else(|| token_error(anyhow::anyhow!("Missing the access token of GCP")))?;
+
+    Ok(Token { username: "oauth2accesstoken".into(), password: token.into() })
+}
+
+/// Exchange the Entra ID token of the workload identity for a refresh token
+/// of the registry, which is valid for 3 hours.
+async fn acr(host: &str) -> Result<Token> {
+    let env = |key: &str| std::env::var(key).map_err(|_| token_error(anyhow::anyhow!("{} is not set", key)));
+    let client_id = env("AZURE_CLIENT_ID")?;
+    let tenant_id = env("AZURE_TENANT_ID")?;
+    let assertion = tokio::fs::read_to_string(env("AZURE_FEDERATED_TOKEN_FILE")?).await.map_err(token_error)?;
+    let authority = env("AZURE_AUTHORITY_HOST").unwrap_or_else(|_| "https://login.microsoftonline.com/".into());
+
+    let http = reqwest::Client::builder().timeout(MINT_TIMEOUT).build().map_err(token_error)?;
+    let url = format!("{}/{}/oauth2/v2.0/token", authority.trim_end_matches('/'), tenant_id);
+    let form = [
+        ("grant_type", "client_credentials"),
+        ("client_id", client_id.as_str()),
+        ("scope", "https://management.azure.com/.default"),
+        ("client_assertion_type", "urn:ietf:params:oauth:client-assertion-type:jwt-bearer"),
+        ("client_assertion", assertion.trim()),
+    ];
+    let response = http.post(url).form(&form).send().await.map_err(token_error)?;
+    let body: Value = response.error_for_status().map_err(token_error)?.json().await.map_err(token_error)?;
+    let access_token = body.get("access_token").and_then(Value::as_str);
+    let access_token = access_token.ok_or_else(|| token_error(anyhow::anyhow!("Missing the access token of Azure")))?;
+
+    let url = format!("https://{}/oauth2/exchange", host);
+    let form = [
+        ("grant_type", "access_token"),
+        ("service", host),
+        ("tenant", tenant_id.as_str()),
+        ("access_token", access_token),
+    ];
+    let response = http.post(url).form(&form).send().await.map_err(token_error)?;
+    let body: Value = response.error_for_status().map_err(token_error)?.json().await.map_err(token_error)?;
+    let token = body.get("refresh_token").and_then(Value::as_str);
+    let token = token.ok_or_else(|| token_error(anyhow::anyhow!("Missing the refresh token of {}", host)))?;
+
+    Ok(Token { username: ACR_USERNAME.into(), password: token.into() })
+}
+
+#[inline]
+fn token_error(err: impl Into<anyhow::Error>) -> Error {
+    Error::RegistryTokenError(err.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_ecr() {
+        assert_eq!(
+            detect("123456789012.dkr.ecr.us-east-1.amazonaws.com"),
+            Some(CloudRegistry::Ecr { region: "us-east-1".into() })
+        );
+        assert_eq!(
+            detect("https://123456789012.dkr.ecr.cn-north-1.amazonaws.com.cn"),
+            Some(CloudRegistry::Ecr { region: "cn-north-1".into() })
+        );
+        assert_eq!(detect("public.ecr.aws"), None);
+    }
+
+    #[test]
+    fn test_detect_gcr_and_acr() {
+        assert_eq!(detect("https://gcr.io"), Some(CloudRegistry::Gcr));
+        assert_eq!(detect("eu.gcr.io"), Some(CloudRegistry::Gcr));
+        assert_eq!(detect("us-central1-docker.pkg.dev/project/repo"), Some(CloudRegistry::Gcr));
+        assert_eq!(detect("https://amp.azurecr.io"), Some(CloudRegistry::Acr { host: "amp.azurecr.io".into() }));
+    }
+
+    #[test]
+    fn test_detect_others() {
+        assert_eq!(detect("https://index.docker.io/v1/"), None);
+        assert_eq!(detect("ghcr.io"), None);
+    }
+
+    fn credentials() -> Credentials {
+        Credentials {
+            registries: vec![
+                RegistryCredential {
+                    server: "123456789012.dkr.ecr.us-east-1.amazonaws.com".into(),
+                    username: Some("AWS".into()),
+                    password: Some("static".into()),
+                    ..Default::default()
+                },
+                RegistryCredential { server: "ghcr.io".into(), ..Default::default() },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_keeps_static_passwords() {
+        assert!(refresh(&credentials(), &BTreeSet::new()).await.is_empty());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut credentials = credentials();
+        let token = Token { username: "AWS".into(), password: "minted".into() };
+        let tokens = BTreeMap::from([("123456789012.dkr.ecr.us-east-1.amazonaws.com".to_string(), token)]);
+        merge(&mut credentials, &tokens);
+
+        assert_eq!(credentials.registries[0].password.as_deref(), Some("minted"));
+        assert_eq!(credentials.registries[1].password, None);
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::{Container, PodSpec, Volume, VolumeMount};
+use kube::ResourceExt;
+use serde::{Deserialize, Serialize};
+
+use crate::containers::syncer;
+use crate::error::{Error, Result};
+
+/// The annotation of the live actor holding its reload hook as a JSON
+/// document, e.g. `{"path": "/app", "command": ["kill", "-HUP", "1"]}`.
+/// The playbook declares the hook of a character with the annotation
+/// suffixed by its name, e.g. `amphitheatre.app/reload.web`.
+pub const RELOAD_ANNOTATION: &str = "amphitheatre.app/reload";
+
+/// The subject the syncers report the synced events on, suffixed by the
+/// playbook and actor, e.g. `amp.synced.{playbook}.{actor}`, the payload is
+/// the name of the pod.
+pub const SYNCED_SUBJECT: &str = "amp.synced";
+
+/// The volume shared by the application container and its syncer.
+const LIVE_VOLUME: &str = "amp-live";
+
+/// Where the seeding init container mounts the shared volume.
+const LIVE_DIR: &str = "/amp-live";
+
+/// How the application picks up the files synced into its pod, instead of being redeployed.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Reload {
+    /// The directory of the application container the files are synced into, e.g. `/app`.
+    pub path: String,
+    /// The command run in the application container after the files are
+    /// synced, e.g. `["kill", "-HUP", "1"]` or a rebuild script.
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Restart the application container after the files are synced instead,
+    /// its main process must exit on `SIGTERM`.
+    #[serde(default)]
+    pub restart: bool,
+}
+
+impl Reload {
+    /// Returns the command run in the application container after the files are synced.
+    pub fn command(&self) -> Vec<String> {
+        match self.restart {
+            // The container is restarted by the kubelet once its main process exits,
+            // the builtin of shell is used as the image may not ship the binary.
+            true => vec!["/bin/sh".into(), "-c".into(), "kill 1".into()],
+            false => self.command.clone(),
+        }
+    }
+
+    /// Sync the files into the application container of the pod by a syncer
+    /// running next to it. The directory is seeded with the files of image
+    /// first, as the volume mounted over it is empty.
+    pub fn apply(&self, actor: &Actor, pod: &mut PodSpec) -> Result<()> {
+        let sidecar = syncer::sidecar(actor, LIVE_VOLUME)?;
+        let Some(container) = pod.containers.first_mut() else {
+            return Ok(());
+        };
+
+        let mount = VolumeMount { name: LIVE_VOLUME.into(), mount_path: self.path.clone(), ..Default::default() };
+        container.volume_mounts.get_or_insert_with(Vec::new).push(mount);
+
+        let seed = Container {
+            name: "amp-reload-seed".into(),
+            image: container.image.clone(),
+            command: Some(vec!["/bin/sh".into(), "-c".into(), format!("cp -a {}/. {}/", self.path, LIVE_DIR)]),
+            volume_mounts: Some(vec![VolumeMount {
+                name: LIVE_VOLUME.into(),
+                mount_path: LIVE_DIR.into(),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        pod.init_containers.get_or_insert_with(Vec::new).push(seed);
+        pod.volumes.get_or_insert_with(Vec::new).push(Volume {
+            name: LIVE_VOLUME.into(),
+            empty_dir: Some(Default::default()),
+            ..Default::default()
+        });
+        pod.containers.push(sidecar);
+
+        Ok(())
+    }
+}
+
+/// Returns the reload hook of actor, if any.
+pub fn hook(actor: &Actor) -> Result<Option<Reload>> {
+    let Some(content) = actor.annotations().get(RELOAD_ANNOTATION) else {
+        return Ok(None);
+    };
+    let reload: Reload = serde_json::from_str(content).map_err(Error::SerializationError)?;
+    if !reload.path.starts_with('/') || reload.path == "/" {
+        return Err(Error::InvalidReloadHook("the path must be an absolute directory other than the root".into()));
+    }
+    if reload.restart == !reload.command.is_empty() {
+        return Err(Error::InvalidReloadHook("either the command or the restart is required".into()));
+    }
+
+    Ok(Some(reload))
+}
+
+/// Returns the subject the syncer of the actor reports the synced events on.
+pub fn subject(playbook: &str, actor: &str) -> String {
+    format!("{}.{}.{}", SYNCED_SUBJECT, playbook, actor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+
+    #[test]
+    fn test_parse_hook() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(hook(&actor).unwrap(), None);
+
+        actor.annotations_mut().insert(RELOAD_ANNOTATION.into(), r#"{"path": "/app", "restart": true}"#.into());
+        let reload = hook(&actor).unwrap().unwrap();
+        assert_eq!(reload.command(), vec!["/bin/sh".to_string(), "-c".to_string(), "kill 1".to_string()]);
+
+        actor.annotations_mut().insert(RELOAD_ANNOTATION.into(), r#"{"path": "/app"}"#.into());
+        assert!(hook(&actor).is_err());
+
+        let content = r#"{"path": "app", "command": ["kill", "-HUP", "1"]}"#;
+        actor.annotations_mut().insert(RELOAD_ANNOTATION.into(), content.into());
+        assert!(hook(&actor).is_err());
+    }
+
+    #[test]
+    fn test_apply_hook() {
+        let mut actor =
+            Actor::new("web", ActorSpec { name: "web".into(), image: "web:latest".into(), ..Default::default() });
+        actor.metadata.owner_references =
+            Some(vec![OwnerReference { kind: "Playbook".into(), name: "demo".into(), ..Default::default() }]);
+        let container = Container { name: "web".into(), image: Some("web:latest".into()), ..Default::default() };
+        let mut pod = PodSpec { containers: vec![container], ..Default::default() };
+
+        let reload = Reload { path: "/app".into(), command: vec!["./reload.sh".into()], restart: false };
+        reload.apply(&actor, &mut pod).unwrap();
+
+        assert_eq!(pod.containers.len(), 2);
+        assert_eq!(pod.containers[0].volume_mounts.as_ref().unwrap()[0].mount_path, "/app");
+        assert_eq!(pod.containers[1].name, "amp-syncer");
+        assert!(pod.containers[1].args.as_ref().unwrap().contains(&"--notify=true".to_string()));
+
+        let seed = &pod.init_containers.as_ref().unwrap()[0];
+        assert_eq!(seed.image.as_deref(), Some("web:latest"));
+        assert_eq!(seed.command.as_ref().unwrap()[2], "cp -a /app/. /amp-live/");
+        assert_eq!(subject("demo", "web"), "amp.synced.demo.web");
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::Actor;
+use k8s_openapi::chrono::Utc;
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+use tracing::info;
+
+use super::error::{Error, Result};
+use super::{deployment, statefulset, strategy, REQUESTED_AT_ANNOTATION};
+
+/// The annotation of actor holding its desired number of replicas, which is
+/// set by scaling the actor and kept across the updates of its spec.
+pub const REPLICAS_ANNOTATION: &str = "amphitheatre.app/replicas";
+
+/// Returns the desired number of replicas of actor, None if it's never scaled.
+pub fn desired(actor: &Actor) -> Option<i32> {
+    actor.annotations().get(REPLICAS_ANNOTATION).and_then(|v| v.parse().ok()).filter(|v: &i32| *v >= 0)
+}
+
+/// Scale the actor to the given number of replicas, the desired number is
+/// recorded on the actor for the later updates, and the running Deployment or
+/// StatefulSet is scaled right away.
+pub async fn scale(client: &Client, actor: &Actor, replicas: i32) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let patch = json!({"metadata": { "annotations": {
+        REPLICAS_ANNOTATION: replicas.to_string(),
+        REQUESTED_AT_ANNOTATION: Utc::now().to_rfc3339(),
+    }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Scaled actor {} to {} replicas", actor.name_any(), replicas);
+
+    resize(client, actor, replicas).await
+}
+
+/// Scale the running Deployment or StatefulSet of actor back to its desired
+/// replicas, e.g. after its playbook is woken up.
+pub async fn restore(client: &Client, actor: &Actor) -> Result<()> {
+    resize(client, actor, desired(actor).unwrap_or(1)).await
+}
+
+async fn resize(client: &Client, actor: &Actor, replicas: i32) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    if statefulset::stateful(actor) {
+        if statefulset::exists(client, &namespace, &statefulset::name(actor)).await? {
+            statefulset::scale(client, &namespace, &statefulset::name(actor), replicas).await?;
+        }
+        return Ok(());
+    }
+
+    // The blue/green actor is scaled by the Deployment serving its traffic.
+    let name = strategy::serving(actor);
+    if deployment::exists(client, &namespace, &name).await? {
+        deployment::scale(client, &namespace, &name, replicas).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_desired() {
+        let mut actor = Actor::new("amp-example-nodejs", ActorSpec::default());
+        assert_eq!(desired(&actor), None);
+
+        actor.annotations_mut().insert(REPLICAS_ANNOTATION.into(), "3".into());
+        assert_eq!(desired(&actor), Some(3));
+
+        actor.annotations_mut().insert(REPLICAS_ANNOTATION.into(), "0".into());
+        assert_eq!(desired(&actor), Some(0));
+
+        actor.annotations_mut().insert(REPLICAS_ANNOTATION.into(), "-1".into());
+        assert_eq!(desired(&actor), None);
+    }
+}
@@ -0,0 +1,409 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::str::FromStr;
+use std::time::Duration;
+
+use amp_common::config::{Credential, Credentials};
+use amp_common::resource::Actor;
+use k8s_openapi::chrono::{DateTime, Utc};
+use reqwest::header::{ACCEPT, WWW_AUTHENTICATE};
+use reqwest::{Method, Response, StatusCode};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::error::{Error, Result};
+use crate::{image, rollback};
+
+/// The media types of the manifests resolved to the digests of the tags.
+const MANIFEST_TYPES: &str = "application/vnd.docker.distribution.manifest.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.index.v1+json";
+
+/// The APIs the stale tags are listed and deleted by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegistryApi {
+    /// The Docker Registry HTTP API V2, the registry must allow the deletion.
+    Distribution,
+    /// The API of Harbor, the untagged blobs are freed by its garbage collection.
+    Harbor,
+}
+
+impl FromStr for RegistryApi {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "distribution" | "registry" => Ok(RegistryApi::Distribution),
+            "harbor" => Ok(RegistryApi::Harbor),
+            x => Err(Error::UnknownRegistryApi(x.to_string())),
+        }
+    }
+}
+
+/// The retention of the tags of each repository of the default registry, a
+/// tag is kept if it is one of the latest ones, pushed in the max age, or
+/// deployed by an actor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Retention {
+    pub api: RegistryApi,
+    /// Keep this many of the latest tags.
+    pub keep: usize,
+    /// Keep the tags pushed in this duration, the age is ignored if not set.
+    pub max_age: Option<Duration>,
+}
+
+/// A tag of the repository, with the digest it points to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub digest: String,
+    pub pushed: DateTime<Utc>,
+}
+
+/// Returns the digests of the stale tags by the retention, the digests also
+/// tagged by a kept tag or protected are kept, as they are deleted with all their tags.
+pub fn stale(tags: &[Tag], retention: &Retention, protected: &HashSet<String>, now: DateTime<Utc>) -> Vec<String> {
+    let mut tags: Vec<&Tag> = tags.iter().collect();
+    tags.sort_by_key(|tag| std::cmp::Reverse(tag.pushed));
+
+    let fresh = |tag: &Tag| {
+        retention.max_age.is_some_and(|age| (now - tag.pushed).to_std().map_or(true, |elapsed| elapsed < age))
+    };
+    let (kept, stale): (Vec<(usize, &Tag)>, Vec<(usize, &Tag)>) = tags.into_iter().enumerate().partition(|(i, tag)| {
+        *i < retention.keep || fresh(*tag) || protected.contains(&tag.name) || protected.contains(&tag.digest)
+    });
+
+    let kept: HashSet<&str> = kept.iter().map(|(_, tag)| tag.digest.as_str()).collect();
+    let stale: BTreeSet<&str> = stale.iter().map(|(_, tag)| tag.digest.as_str()).collect();
+    stale.into_iter().filter(|digest| !kept.contains(digest)).map(String::from).collect()
+}
+
+/// Returns the repositories of the images of the actors, with the tags and
+/// digests they deploy, keyed by the repositories without the registry.
+pub fn repositories<'a>(
+    registry: &str,
+    actors: impl IntoIterator<Item = &'a Actor>,
+) -> BTreeMap<String, HashSet<String>> {
+    let mut repositories: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+    for actor in actors {
+        let images = [Some(actor.spec.image.clone()), Some(image::deployed(actor)), rollback::known_good(actor)];
+        for (host, repository, reference) in images.into_iter().flatten().filter_map(|image| parse(&image)) {
+            if host == registry {
+                repositories.entry(repository).or_default().insert(reference);
+            }
+        }
+    }
+
+    repositories
+}
+
+/// Delete the stale tags of the repositories of the actors from the default
+/// registry, the images of the protected actors are kept. Returns the number
+/// of the deleted digests.
+pub async fn prune(
+    credentials: &Credentials,
+    retention: &Retention,
+    actors: &[Actor],
+    protected: &[Actor],
+) -> Result<usize> {
+    let Some(credential) = credentials.default_registry() else {
+        return Ok(0);
+    };
+    let mut registry = Registry::new(credential, retention.api);
+    let protected = repositories(&registry.host, protected);
+
+    let mut deleted = 0;
+    for repository in repositories(&registry.host, actors).into_keys() {
+        let tags = registry.tags(&repository).await?;
+        let protected = protected.get(&repository).cloned().unwrap_or_default();
+        for digest in stale(&tags, retention, &protected, Utc::now()) {
+            registry.delete(&repository, &digest).await?;
+            info!("Deleted the stale image {}/{}@{}", registry.host, repository, digest);
+            deleted += 1;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Returns the registry host, the repository and the tag or digest of the image.
+fn parse(image: &str) -> Option<(String, String, String)> {
+    let (name, reference) = match image.split_once('@') {
+        Some((name, digest)) => (name, digest.to_string()),
+        None => match image.rsplit_once(':').filter(|(_, tag)| !tag.contains('/')) {
+            Some((name, tag)) => (name, tag.to_string()),
+            None => (image, "latest".to_string()),
+        },
+    };
+    let (host, repository) = name.split_once('/')?;
+
+    Some((host.to_string(), repository.to_string(), reference))
+}
+
+/// The client of the registry, authorized by the default registry credential.
+struct Registry {
+    http: reqwest::Client,
+    api: RegistryApi,
+    /// The host of the registry, e.g. `harbor.amp.io`.
+    host: String,
+    base: String,
+    username: String,
+    password: String,
+    /// The bearer token issued by the token service of the Distribution registry.
+    token: Option<String>,
+}
+
+impl Registry {
+    fn new(credential: &Credential, api: RegistryApi) -> Self {
+        let server = credential.server.trim_end_matches('/');
+        let host = server.trim_start_matches("https://").trim_start_matches("http://");
+        let host = host.split('/').next().unwrap_or_default().to_string();
+        let scheme = if server.starts_with("http://") { "http" } else { "https" };
+
+        Registry {
+            http: reqwest::Client::new(),
+            api,
+            base: format!("{}://{}", scheme, host),
+            host,
+            username: credential.username_any(),
+            password: credential.password_any(),
+            token: None,
+        }
+    }
+
+    /// List the tags of the repository with their digests and push times.
+    async fn tags(&mut self, repository: &str) -> Result<Vec<Tag>> {
+        self.token = None;
+        match self.api {
+            RegistryApi::Distribution => self.distribution_tags(repository).await,
+            RegistryApi::Harbor => self.harbor_tags(repository).await,
+        }
+    }
+
+    /// Delete the manifest of the digest along with all its tags.
+    async fn delete(&mut self, repository: &str, digest: &str) -> Result<()> {
+        let url = match self.api {
+            RegistryApi::Distribution => format!("{}/v2/{}/manifests/{}", self.base, repository, digest),
+            RegistryApi::Harbor => {
+                let (project, name) = harbor(repository)?;
+                format!("{}/api/v2.0/projects/{}/repositories/{}/artifacts/{}", self.base, project, name, digest)
+            }
+        };
+        self.send(Method::DELETE, &url, None, repository).await?;
+
+        Ok(())
+    }
+
+    async fn distribution_tags(&mut self, repository: &str) -> Result<Vec<Tag>> {
+        let url = format!("{}/v2/{}/tags/list?n=1000", self.base, repository);
+        let body: Value =
+            self.send(Method::GET, &url, None, repository).await?.json().await.map_err(retention_error)?;
+        let names: Vec<String> =
+            body["tags"].as_array().into_iter().flatten().filter_map(|tag| tag.as_str().map(String::from)).collect();
+
+        let mut tags = vec![];
+        for name in names {
+            let url = format!("{}/v2/{}/manifests/{}", self.base, repository, name);
+            let response = self.send(Method::GET, &url, Some(MANIFEST_TYPES), repository).await?;
+            let Some(digest) = response.headers().get("Docker-Content-Digest").and_then(|v| v.to_str().ok()) else {
+                debug!("Skipped the tag {} of {} without the digest", name, repository);
+                continue;
+            };
+            let digest = digest.to_string();
+            let manifest: Value = response.json().await.map_err(retention_error)?;
+
+            // The push time is approximated by the creation time of the image, the indexes are taken as fresh.
+            let mut pushed = None;
+            if let Some(config) = manifest["config"]["digest"].as_str() {
+                let url = format!("{}/v2/{}/blobs/{}", self.base, repository, config);
+                let config: Value =
+                    self.send(Method::GET, &url, None, repository).await?.json().await.map_err(retention_error)?;
+                pushed = config["created"].as_str().and_then(time);
+            }
+            tags.push(Tag { name, digest, pushed: pushed.unwrap_or_else(Utc::now) });
+        }
+
+        Ok(tags)
+    }
+
+    async fn harbor_tags(&mut self, repository: &str) -> Result<Vec<Tag>> {
+        let (project, name) = harbor(repository)?;
+
+        let mut tags = vec![];
+        for page in 1.. {
+            let url = format!(
+                "{}/api/v2.0/projects/{}/repositories/{}/artifacts?with_tag=true&page={}&page_size=100",
+                self.base, project, name, page
+            );
+            let body: Value =
+                self.send(Method::GET, &url, None, repository).await?.json().await.map_err(retention_error)?;
+            let artifacts = body.as_array().cloned().unwrap_or_default();
+            for artifact in &artifacts {
+                let digest = artifact["digest"].as_str().unwrap_or_default();
+                for tag in artifact["tags"].as_array().into_iter().flatten() {
+                    let pushed = tag["push_time"].as_str().or(artifact["push_time"].as_str()).and_then(time);
+                    tags.push(Tag {
+                        name: tag["name"].as_str().unwrap_or_default().to_string(),
+                        digest: digest.to_string(),
+                        pushed: pushed.unwrap_or_else(Utc::now),
+                    });
+                }
+            }
+            if artifacts.len() < 100 {
+                break;
+            }
+        }
+
+        Ok(tags)
+    }
+
+    /// Send the request by the basic auth, or by the bearer token if the
+    /// Distribution registry challenges for it.
+    async fn send(&mut self, method: Method, url: &str, accept: Option<&str>, repository: &str) -> Result<Response> {
+        let response = self.request(&method, url, accept).send().await.map_err(retention_error)?;
+        if response.status() != StatusCode::UNAUTHORIZED || self.api != RegistryApi::Distribution {
+            return response.error_for_status().map_err(retention_error);
+        }
+
+        let challenge = response.headers().get(WWW_AUTHENTICATE).and_then(|value| value.to_str().ok());
+        let Some(challenge) = challenge.and_then(bearer) else {
+            return response.error_for_status().map_err(retention_error);
+        };
+        self.token = Some(self.authorize(&challenge, repository).await?);
+
+        let response = self.request(&method, url, accept).send().await.map_err(retention_error)?;
+        response.error_for_status().map_err(retention_error)
+    }
+
+    fn request(&self, method: &Method, url: &str, accept: Option<&str>) -> reqwest::RequestBuilder {
+        let mut request = self.http.request(method.clone(), url);
+        if let Some(accept) = accept {
+            request = request.header(ACCEPT, accept);
+        }
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request.basic_auth(&self.username, Some(&self.password)),
+        }
+    }
+
+    /// Request the token of the repository from the token service of the challenge.
+    async fn authorize(&self, challenge: &BTreeMap<String, String>, repository: &str) -> Result<String> {
+        let realm = challenge.get("realm").ok_or_else(|| retention_error(anyhow::anyhow!("missing realm")))?;
+        let scope = format!("repository:{}:pull,delete", repository);
+        let mut query = vec![("scope", scope.as_str())];
+        if let Some(service) = challenge.get("service") {
+            query.push(("service", service));
+        }
+
+        let request = self.http.get(realm).query(&query).basic_auth(&self.username, Some(&self.password));
+        let response = request.send().await.map_err(retention_error)?;
+        let body: Value =
+            response.error_for_status().map_err(retention_error)?.json().await.map_err(retention_error)?;
+        let token = body["token"].as_str().or(body["access_token"].as_str());
+
+        token.map(String::from).ok_or_else(|| retention_error(anyhow::anyhow!("missing token of {}", repository)))
+    }
+}
+
+/// Parse the parameters of the bearer challenge, e.g.
+/// `Bearer realm="https://auth.docker.io/token",service="registry.docker.io"`.
+fn bearer(challenge: &str) -> Option<BTreeMap<String, String>> {
+    let parameters = challenge.strip_prefix("Bearer ")?;
+    let parameters = parameters.split(',').filter_map(|parameter| parameter.trim().split_once('='));
+
+    Some(parameters.map(|(key, value)| (key.to_string(), value.trim_matches('"').to_string())).collect())
+}
+
+/// Returns the project and the URL encoded name of the repository in Harbor,
+/// the slashes in the name are encoded twice as Harbor requires.
+fn harbor(repository: &str) -> Result<(&str, String)> {
+    let (project, name) = repository
+        .split_once('/')
+        .ok_or_else(|| retention_error(anyhow::anyhow!("missing project of {}", repository)))?;
+
+    Ok((project, name.replace('/', "%252F")))
+}
+
+fn time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|time| time.with_timezone(&Utc))
+}
+
+fn retention_error(err: impl Into<anyhow::Error>) -> Error {
+    Error::RetentionError(err.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use k8s_openapi::chrono::Duration as TimeDelta;
+
+    fn tag(name: &str, digest: &str, days: i64) -> Tag {
+        Tag { name: name.into(), digest: digest.into(), pushed: Utc::now() - TimeDelta::days(days) }
+    }
+
+    #[test]
+    fn test_parse_image() {
+        let split = |image| parse(image).unwrap();
+        assert_eq!(split("harbor.amp.io/amp/web:abc123"), ("harbor.amp.io".into(), "amp/web".into(), "abc123".into()));
+        assert_eq!(split("localhost:5000/amp/web").2, "latest");
+        assert_eq!(split("harbor.amp.io/amp/web@sha256:def").2, "sha256:def");
+        assert!(parse("nginx:1.27").is_none());
+    }
+
+    #[test]
+    fn test_stale_tags() {
+        let tags =
+            vec![tag("v5", "d5", 1), tag("v4", "d4", 2), tag("v3", "d3", 40), tag("v2", "d2", 50), tag("v1", "d1", 60)];
+        let retention = Retention { api: RegistryApi::Distribution, keep: 2, max_age: None };
+        let now = Utc::now();
+
+        assert_eq!(stale(&tags, &retention, &HashSet::new(), now), vec!["d1", "d2", "d3"]);
+
+        // The deployed tags and digests are kept.
+        let protected = HashSet::from(["v3".to_string(), "d1".to_string()]);
+        assert_eq!(stale(&tags, &retention, &protected, now), vec!["d2"]);
+
+        // The tags pushed in the max age are kept.
+        let retention = Retention { max_age: Some(Duration::from_secs(45 * 86400)), ..retention };
+        assert_eq!(stale(&tags, &retention, &HashSet::new(), now), vec!["d1", "d2"]);
+    }
+
+    #[test]
+    fn test_stale_digest_tagged_by_kept_tag() {
+        let tags = vec![tag("latest", "d1", 0), tag("v1", "d1", 30), tag("v0", "d0", 60)];
+        let retention = Retention { api: RegistryApi::Harbor, keep: 1, max_age: None };
+
+        assert_eq!(stale(&tags, &retention, &HashSet::new(), Utc::now()), vec!["d0"]);
+    }
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        let challenge = r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io""#;
+        let parameters = bearer(challenge).unwrap();
+
+        assert_eq!(parameters.get("realm").unwrap(), "https://auth.docker.io/token");
+        assert_eq!(parameters.get("service").unwrap(), "registry.docker.io");
+        assert!(bearer("Basic realm=\"x\"").is_none());
+    }
+
+    #[test]
+    fn test_harbor_repository() {
+        assert_eq!(harbor("amp/web").unwrap(), ("amp", "web".to_string()));
+        assert_eq!(harbor("amp/team/web").unwrap(), ("amp", "team%252Fweb".to_string()));
+        assert!(harbor("web").is_err());
+    }
+}
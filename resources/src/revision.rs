@@ -0,0 +1,205 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::{Actor, CharacterSpec, Playbook};
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::chrono::Utc;
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::actor::PLAYBOOK_LABEL;
+use crate::error::{Error, Result};
+use crate::{actor, cluster, namespace, naming};
+
+/// The label of the ConfigMaps holding the revisions, the value is the number of revision.
+pub const REVISION_LABEL: &str = "amphitheatre.app/revision";
+
+/// The key of the ConfigMap holding the revision as a JSON document.
+const REVISION_KEY: &str = "revision";
+
+/// The number of the latest revisions kept for each playbook.
+const HISTORY_LIMIT: usize = 10;
+
+/// A snapshot of the characters of playbook, recorded once its actors are applied.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Revision {
+    pub revision: u64,
+    pub characters: Vec<CharacterSpec>,
+    /// The time the revision was recorded, in RFC 3339 format.
+    pub created_at: String,
+}
+
+/// List the revisions of the playbook from the given namespace, ordered from the oldest.
+pub async fn list(client: &Client, namespace: &str, playbook: &str) -> Result<Vec<Revision>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let params = ListParams::default().labels(&format!("{},{}={}", REVISION_LABEL, PLAYBOOK_LABEL, playbook));
+
+    let mut revisions =
+        api.list(&params).await.map_err(Error::KubeError)?.items.iter().map(from).collect::<Result<Vec<_>>>()?;
+    revisions.sort_by_key(|revision| revision.revision);
+
+    Ok(revisions)
+}
+
+/// Get the revision of the playbook from the given namespace.
+pub async fn get(client: &Client, namespace: &str, playbook: &str, revision: u64) -> Result<Option<Revision>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+
+    match api.get_opt(&name(playbook, revision)).await.map_err(Error::KubeError)? {
+        Some(config_map) => from(&config_map).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Record the characters of playbook as a new revision if they changed since
+/// the latest one, the revisions beyond the limit are pruned.
+pub async fn record(client: &Client, namespace: &str, playbook: &Playbook) -> Result<Option<Revision>> {
+    let characters = playbook.spec.characters.clone().unwrap_or_default();
+    let revisions = list(client, namespace, &playbook.name_any()).await?;
+    if !changed(revisions.last(), &characters)? {
+        return Ok(None);
+    }
+
+    let revision = Revision {
+        revision: revisions.last().map_or(1, |latest| latest.revision + 1),
+        characters,
+        created_at: Utc::now().to_rfc3339(),
+    };
+    let content = serde_json::to_string(&revision).map_err(Error::SerializationError)?;
+    let resource = ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(name(&playbook.name_any(), revision.revision)),
+            labels: Some(BTreeMap::from([
+                (PLAYBOOK_LABEL.into(), playbook.name_any()),
+                (REVISION_LABEL.into(), revision.revision.to_string()),
+                ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+            ])),
+            // The revisions are deleted along with the playbook.
+            owner_references: playbook.controller_owner_ref(&()).map(|owner| vec![owner]),
+            ..Default::default()
+        },
+        data: Some(BTreeMap::from([(REVISION_KEY.into(), content)])),
+        ..Default::default()
+    };
+
+    // The revision is recorded by a concurrent reconciliation already, the
+    // characters are compared with it again in the next one.
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    match api.create(&PostParams::default(), &resource).await {
+        Err(kube::Error::Api(err)) if err.code == 409 => {
+            debug!("Revision {} of playbook {} exists already", revision.revision, playbook.name_any());
+            return Ok(None);
+        }
+        result => result.map_err(Error::KubeError)?,
+    };
+    info!("Recorded revision {} of playbook {}", revision.revision, playbook.name_any());
+
+    let skipped = (revisions.len() + 1).saturating_sub(HISTORY_LIMIT);
+    for pruned in &revisions[..skipped] {
+        api.delete(&name(&playbook.name_any(), pruned.revision), &DeleteParams::default())
+            .await
+            .map_err(Error::KubeError)?;
+    }
+
+    Ok(Some(revision))
+}
+
+/// Replace the characters of playbook with the ones of the revision, its
+/// actors are updated by the reconciliation of the playbook as usual, and
+/// the actors of the characters not in the revision are deleted.
+pub async fn rollback(client: &Client, namespace: &str, playbook: &Playbook, revision: &Revision) -> Result<()> {
+    let api: Api<Playbook> = Api::all(client.clone());
+
+    let patch = json!({"spec": { "characters": revision.characters }});
+    api.patch(&playbook.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Rolled back playbook {} to revision {}", playbook.name_any(), revision.revision);
+
+    let workload = cluster::client(client, namespace, playbook).await?;
+    for name in pruned(playbook, revision, &actor::list_of(&workload, playbook).await?) {
+        actor::delete(&workload, &namespace::of(playbook), &name).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns the names of the actors whose characters are not in the revision.
+fn pruned(playbook: &Playbook, revision: &Revision, actors: &[Actor]) -> Vec<String> {
+    let kept: Vec<String> =
+        revision.characters.iter().map(|character| namespace::actor_name(playbook, &character.meta.name)).collect();
+
+    actors.iter().map(|actor| actor.name_any()).filter(|name| !kept.contains(name)).collect()
+}
+
+/// Returns true if the characters differ from the ones of the latest revision.
+fn changed(latest: Option<&Revision>, characters: &[CharacterSpec]) -> Result<bool> {
+    let Some(latest) = latest else {
+        return Ok(!characters.is_empty());
+    };
+    let value = |characters: &[CharacterSpec]| serde_json::to_value(characters).map_err(Error::SerializationError);
+
+    Ok(value(&latest.characters)? != value(characters)?)
+}
+
+fn from(config_map: &ConfigMap) -> Result<Revision> {
+    let content = config_map.data.as_ref().and_then(|data| data.get(REVISION_KEY));
+    let content = content.ok_or(Error::MissingObjectKey(".data.revision"))?;
+
+    serde_json::from_str(content).map_err(Error::SerializationError)
+}
+
+#[inline]
+fn name(playbook: &str, revision: u64) -> String {
+    naming::name(&["amp-revision", playbook, &revision.to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(name: &str) -> CharacterSpec {
+        let mut character = CharacterSpec::default();
+        character.meta.name = name.into();
+        character
+    }
+
+    #[test]
+    fn test_changed() {
+        assert!(!changed(None, &[]).unwrap());
+        assert!(changed(None, &[character("web")]).unwrap());
+
+        let latest = Revision { revision: 1, characters: vec![character("web")], created_at: String::new() };
+        assert!(!changed(Some(&latest), &[character("web")]).unwrap());
+        assert!(changed(Some(&latest), &[character("web"), character("api")]).unwrap());
+    }
+
+    #[test]
+    fn test_pruned() {
+        let playbook = Playbook::new("9f3e2c1a", Default::default());
+        let revision = Revision { revision: 1, characters: vec![character("web")], created_at: String::new() };
+        let actors = [Actor::new("web", Default::default()), Actor::new("api", Default::default())];
+
+        assert_eq!(pruned(&playbook, &revision, &actors), vec!["api".to_string()]);
+    }
+
+    #[test]
+    fn test_name() {
+        assert_eq!(name("9f3e2c1a", 3), "amp-revision-9f3e2c1a-3");
+    }
+}
@@ -0,0 +1,311 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::apps::v1::{Deployment, StatefulSet};
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::api::{ListParams, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::canary::TRACK_LABEL;
+use crate::error::{Error, Result};
+use crate::{deployment, image, statefulset, strategy};
+
+/// The annotation of actor holding its rollback policy as a JSON document,
+/// e.g. `{"enabled": true, "max_restarts": 3}`. The playbook declares the
+/// policy of a character with the annotation suffixed by its name, e.g.
+/// `amphitheatre.app/rollback.web`.
+pub const ROLLBACK_ANNOTATION: &str = "amphitheatre.app/rollback";
+
+/// The annotation of actor recording the digest reference of the last image
+/// all of its pods were ready with, e.g. `harbor.amp.io/web@sha256:...`.
+pub const KNOWN_GOOD_ANNOTATION: &str = "amphitheatre.app/known-good-image";
+
+/// The annotation of actor recording its last rollback.
+pub const ROLLBACK_STATUS_ANNOTATION: &str = "amphitheatre.app/rollback-status";
+
+/// The condition type of actor reporting its image was rolled back.
+pub const ROLLED_BACK_CONDITION_TYPE: &str = "RolledBack";
+
+/// When the failed rollouts of actor are rolled back to the last known-good image.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Policy {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Roll back if a container in CrashLoopBackOff restarted this many times.
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: i32,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self { enabled: default_enabled(), max_restarts: default_max_restarts() }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_max_restarts() -> i32 {
+    3
+}
+
+/// The last rollback of actor.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Status {
+    /// The image of actor failed to roll out, by its digest if built.
+    pub image: String,
+    /// The known-good image rolled back to.
+    pub to: String,
+    pub reason: String,
+    pub message: String,
+    /// The time of the rollback, in RFC 3339 format.
+    pub time: String,
+}
+
+/// The rollout of the Deployment or StatefulSet serving actor.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rollout {
+    /// All the replicas are updated and ready, and the old ones are gone.
+    pub complete: bool,
+    /// The Deployment reported it is not progressing in its deadline.
+    pub deadline_exceeded: bool,
+    /// The most restarts of the application containers in CrashLoopBackOff.
+    pub restarts: i32,
+    /// The digest reference of the image of the ready pods, None if they run different ones.
+    pub image: Option<String>,
+}
+
+/// Returns the rollback policy of actor, the rollback is enabled by default.
+pub fn policy(actor: &Actor) -> Result<Policy> {
+    match actor.annotations().get(ROLLBACK_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map_err(Error::SerializationError),
+        None => Ok(Policy::default()),
+    }
+}
+
+/// Returns the last known-good image of actor, if any.
+pub fn known_good(actor: &Actor) -> Option<String> {
+    actor.annotations().get(KNOWN_GOOD_ANNOTATION).cloned()
+}
+
+/// Returns the last rollback of actor, if any.
+pub fn status(actor: &Actor) -> Option<Status> {
+    let content = actor.annotations().get(ROLLBACK_STATUS_ANNOTATION);
+    content.and_then(|content| serde_json::from_str(content).ok())
+}
+
+/// Observe the rollout of the Deployment or StatefulSet serving actor, None if it is not deployed by either.
+pub async fn observe(client: &Client, actor: &Actor) -> Result<Option<Rollout>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let (mut rollout, labels) = match statefulset::stateful(actor) {
+        true => {
+            let api: Api<StatefulSet> = Api::namespaced(client.clone(), &namespace);
+            let Some(statefulset) = api.get_opt(&statefulset::name(actor)).await.map_err(Error::KubeError)? else {
+                return Ok(None);
+            };
+
+            // The StatefulSets have no progress deadline, they're rolled back by the restarts only.
+            let generation = statefulset.metadata.generation;
+            let spec = statefulset.spec.unwrap_or_default();
+            let status = statefulset.status.unwrap_or_default();
+            let desired = spec.replicas.unwrap_or(1);
+            let rollout = Rollout {
+                complete: status.observed_generation >= generation
+                    && status.updated_replicas.unwrap_or_default() >= desired
+                    && status.ready_replicas.unwrap_or_default() >= desired
+                    && status.current_revision == status.update_revision,
+                ..Default::default()
+            };
+            (rollout, spec.selector.match_labels.unwrap_or_default())
+        }
+        false => {
+            let api: Api<Deployment> = Api::namespaced(client.clone(), &namespace);
+            let Some(deployment) = api.get_opt(&strategy::serving(actor)).await.map_err(Error::KubeError)? else {
+                return Ok(None);
+            };
+
+            let generation = deployment.metadata.generation;
+            let spec = deployment.spec.unwrap_or_default();
+            let status = deployment.status.unwrap_or_default();
+            let desired = spec.replicas.unwrap_or(1);
+            let rollout = Rollout {
+                complete: status.observed_generation >= generation
+                    && status.updated_replicas.unwrap_or_default() >= desired
+                    && status.ready_replicas.unwrap_or_default() >= desired
+                    && status.replicas.unwrap_or_default() <= desired,
+                deadline_exceeded: status.conditions.iter().flatten().any(|condition| {
+                    condition.type_ == "Progressing" && condition.reason.as_deref() == Some("ProgressDeadlineExceeded")
+                }),
+                ..Default::default()
+            };
+            (rollout, spec.selector.match_labels.unwrap_or_default())
+        }
+    };
+
+    // The canary pods are selected by the stable Deployment as well, they have their own rollback.
+    let mut selector: Vec<String> = labels.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+    selector.push(format!("{}!=canary", TRACK_LABEL));
+    let api: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    let pods = api.list(&ListParams::default().labels(&selector.join(","))).await.map_err(Error::KubeError)?;
+
+    let mut images = BTreeSet::new();
+    let containers = pods.items.into_iter().filter_map(|pod| pod.status?.container_statuses).flatten();
+    for container in containers.filter(|container| container.name == actor.spec.name) {
+        if container.ready {
+            images.insert(reference(&container.image_id));
+        }
+        let waiting = container.state.as_ref().and_then(|state| state.waiting.as_ref());
+        if waiting.and_then(|waiting| waiting.reason.as_deref()) == Some("CrashLoopBackOff") {
+            rollout.restarts = rollout.restarts.max(container.restart_count);
+        }
+    }
+    if images.len() == 1 {
+        rollout.image = images.pop_first().flatten();
+    }
+
+    Ok(Some(rollout))
+}
+
+/// Returns the digest reference of the image ID reported by the container
+/// runtime, None if it is not pulled from a registry.
+fn reference(image_id: &str) -> Option<String> {
+    let reference = image_id.trim_start_matches("docker-pullable://");
+    reference.contains("@sha256:").then(|| reference.to_string())
+}
+
+/// Returns the reason and message of the failure of the rollout, None if it is not failed.
+pub fn failure(policy: &Policy, rollout: &Rollout) -> Option<(&'static str, String)> {
+    if rollout.restarts >= policy.max_restarts {
+        let message = format!("The containers are in CrashLoopBackOff after {} restarts", rollout.restarts);
+        return Some(("CrashLoopBackOff", message));
+    }
+    if rollout.deadline_exceeded {
+        return Some(("ProgressDeadlineExceeded", "The pods are not ready in the progress deadline".into()));
+    }
+
+    None
+}
+
+/// Record the image as the last known-good one of actor.
+pub async fn remember(client: &Client, actor: &Actor, image: &str) -> Result<()> {
+    patch(client, actor, KNOWN_GOOD_ANNOTATION, image.to_string()).await?;
+    info!("Recorded the known-good image {} of Actor {}", image, actor.name_any());
+
+    Ok(())
+}
+
+/// Roll the Deployment or StatefulSet serving actor back to the image, either
+/// the last known-good one or the last verified one, and record the rollback.
+/// It's kept until the actor changes as the workload is only updated by a new spec.
+pub async fn rollback(client: &Client, actor: &Actor, image: &str, reason: &str, message: String) -> Result<Status> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    if statefulset::stateful(actor) {
+        statefulset::set_image(client, &namespace, &statefulset::name(actor), &actor.spec.name, image).await?;
+    } else {
+        deployment::set_image(client, &namespace, &strategy::serving(actor), &actor.spec.name, image).await?;
+    }
+
+    let status = Status {
+        image: image::deployed(actor),
+        to: image.to_string(),
+        reason: reason.to_string(),
+        message,
+        time: Utc::now().to_rfc3339(),
+    };
+    let content = serde_json::to_string(&status).map_err(Error::SerializationError)?;
+    patch(client, actor, ROLLBACK_STATUS_ANNOTATION, content).await?;
+    info!("Rolled back Actor {} to the image {}: {}", actor.name_any(), image, status.message);
+
+    Ok(status)
+}
+
+async fn patch(client: &Client, actor: &Actor, key: &str, value: String) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let annotations = BTreeMap::from([(key.to_string(), value)]);
+    let patch = json!({"metadata": { "annotations": annotations }});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+/// Build the condition reporting the rollback of actor.
+pub fn condition(status: &Status) -> Condition {
+    Condition {
+        type_: ROLLED_BACK_CONDITION_TYPE.into(),
+        status: "True".into(),
+        reason: status.reason.clone(),
+        message: format!("{}, rolled back to {}", status.message, status.to),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// Build the condition reporting the image of actor rolled out after a rollback.
+pub fn recovered(image: &str) -> Condition {
+    Condition {
+        type_: ROLLED_BACK_CONDITION_TYPE.into(),
+        status: "False".into(),
+        reason: "RolledOut".into(),
+        message: format!("The image {} is rolled out", image),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let actor = Actor::new("web", Default::default());
+        assert_eq!(policy(&actor).unwrap(), Policy { enabled: true, max_restarts: 3 });
+
+        let mut actor = actor;
+        actor.annotations_mut().insert(ROLLBACK_ANNOTATION.into(), r#"{"enabled": false}"#.into());
+        assert!(!policy(&actor).unwrap().enabled);
+    }
+
+    #[test]
+    fn test_reference_of_image_id() {
+        let digest = "sha256:2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+        let expected = Some(format!("harbor.amp.io/web@{}", digest));
+        assert_eq!(reference(&format!("harbor.amp.io/web@{}", digest)), expected);
+        assert_eq!(reference(&format!("docker-pullable://harbor.amp.io/web@{}", digest)), expected);
+        assert_eq!(reference(digest), None);
+    }
+
+    #[test]
+    fn test_failure_of_rollout() {
+        let policy = Policy::default();
+        assert_eq!(failure(&policy, &Rollout::default()), None);
+
+        let rollout = Rollout { restarts: 3, ..Default::default() };
+        assert_eq!(failure(&policy, &rollout).map(|(reason, _)| reason), Some("CrashLoopBackOff"));
+
+        let rollout = Rollout { restarts: 1, deadline_exceeded: true, ..Default::default() };
+        assert_eq!(failure(&policy, &rollout).map(|(reason, _)| reason), Some("ProgressDeadlineExceeded"));
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{ListParams, LogParams, Patch, PatchParams, PostParams};
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::artifact::{self, Kind, Store};
+use crate::containers::sbom;
+use crate::error::{Error, Result};
+use crate::{hash, job, naming, LAST_APPLIED_HASH_KEY};
+
+/// The annotation of actor enabling the SBOM generation, the value is the format.
+pub const SBOM_ANNOTATION: &str = "amphitheatre.app/sbom";
+
+/// The annotation of actor recording the SBOM archived to the artifact store
+/// as a JSON document, e.g. `{"image": "...", "digest": "..."}`.
+pub const SBOM_ARCHIVE_ANNOTATION: &str = "amphitheatre.app/sbom-archive";
+
+/// The SBOM archived to the artifact store, of the image.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+struct Archive {
+    image: String,
+    digest: String,
+}
+
+/// The supported SBOM formats.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Format {
+    CycloneDx,
+    Spdx,
+}
+
+impl Format {
+    /// Returns the output format name of syft.
+    pub fn syft_output(&self) -> &'static str {
+        match self {
+            Format::CycloneDx => "cyclonedx-json",
+            Format::Spdx => "spdx-json",
+        }
+    }
+
+    /// Returns the media type of the SBOM artifact.
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Format::CycloneDx => "application/vnd.cyclonedx+json",
+            Format::Spdx => "application/spdx+json",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "cyclonedx" | "cyclonedx-json" => Ok(Format::CycloneDx),
+            "spdx" | "spdx-json" => Ok(Format::Spdx),
+            x => Err(Error::UnknownSbomFormat(x.to_string())),
+        }
+    }
+}
+
+/// Returns the SBOM format if the SBOM generation is enabled for the actor.
+pub fn format(actor: &Actor) -> Result<Option<Format>> {
+    actor.annotations().get(SBOM_ANNOTATION).map(|value| value.parse()).transpose()
+}
+
+/// Generate the SBOM for the current image of the actor, the previous
+/// generation job will be replaced if the actor has changed.
+pub async fn generate(client: &Client, actor: &Actor, format: &Format) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(&actor.spec.name);
+
+    let expected_hash = hash(&actor.spec)?;
+    if let Some(job) = api.get_opt(&name).await.map_err(Error::KubeError)? {
+        if job.annotations().get(LAST_APPLIED_HASH_KEY) == Some(&expected_hash) {
+            debug!("The SBOM Job {} is already up-to-date", name);
+            return Ok(());
+        }
+
+        job::replace(&api, &name).await?;
+        info!("Deleted the outdated SBOM Job {}", name);
+    }
+
+    let resource = job::new(actor, name, sbom::pod(actor, format))?;
+    let job = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+    info!("Created SBOM Job: {}", job.name_any());
+
+    Ok(())
+}
+
+/// Get the SBOM document of the actor's current image, if it has been generated.
+pub async fn get(client: &Client, namespace: &str, name: &str) -> Result<Option<String>> {
+    let api: Api<Pod> = Api::namespaced(client.clone(), namespace);
+    let params = ListParams::default().labels(&format!("job-name={}", job_name(name)));
+    let pods = api.list(&params).await.map_err(Error::KubeError)?;
+
+    // The reader prints the whole document before it exits, read it after the pod succeeded.
+    let pod = pods
+        .items
+        .into_iter()
+        .find(|pod| pod.status.as_ref().and_then(|status| status.phase.as_deref()) == Some("Succeeded"));
+    let Some(pod) = pod else {
+        debug!("The SBOM of Actor {} has not been generated yet", name);
+        return Ok(None);
+    };
+
+    let params = LogParams { container: Some(sbom::READER_CONTAINER_NAME.into()), ..Default::default() };
+    let document = api.logs(&pod.name_any(), &params).await.map_err(Error::KubeError)?;
+
+    Ok(Some(document))
+}
+
+/// Archive the SBOM document of the actor's current image to the artifact
+/// store, so it outlives the generation Job.
+pub async fn archive(client: &Client, store: &Store, actor: &Actor, document: &str) -> Result<()> {
+    let archive = Archive { image: actor.spec.image.clone(), digest: artifact::digest(document.as_bytes()) };
+    if archived_of(actor).as_ref() == Some(&archive) {
+        return Ok(());
+    }
+    store.put(Kind::Sboms, document.as_bytes().to_vec()).await?;
+
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+    let content = serde_json::to_string(&archive).map_err(Error::SerializationError)?;
+    let patch = json!({"metadata": {"annotations": { SBOM_ARCHIVE_ANNOTATION: content }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Archived the SBOM of actor {} as {}", actor.name_any(), archive.digest);
+
+    Ok(())
+}
+
+/// Get the archived SBOM document of the actor's current image, if any.
+pub async fn archived(store: &Store, actor: &Actor) -> Result<Option<String>> {
+    let Some(archive) = archived_of(actor).filter(|archive| archive.image == actor.spec.image) else {
+        return Ok(None);
+    };
+    let content = store.get(Kind::Sboms, &archive.digest).await?;
+
+    Ok(content.map(|content| String::from_utf8_lossy(&content).into_owned()))
+}
+
+fn archived_of(actor: &Actor) -> Option<Archive> {
+    actor.annotations().get(SBOM_ARCHIVE_ANNOTATION).and_then(|content| serde_json::from_str(content).ok())
+}
+
+#[inline]
+fn job_name(actor: &str) -> String {
+    naming::name(&[actor, "sbom"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("cyclonedx".parse::<Format>().unwrap(), Format::CycloneDx);
+        assert_eq!("SPDX".parse::<Format>().unwrap(), Format::Spdx);
+        assert!("unknown".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_format_of_actor() {
+        let mut actor = Actor::new("test", Default::default());
+        assert_eq!(format(&actor).unwrap(), None);
+
+        actor.annotations_mut().insert(SBOM_ANNOTATION.into(), "spdx".into());
+        assert_eq!(format(&actor).unwrap(), Some(Format::Spdx));
+    }
+
+    #[test]
+    fn test_archived_of_actor() {
+        let mut actor = Actor::new("test", Default::default());
+        assert_eq!(archived_of(&actor), None);
+
+        let content = r#"{"image": "registry/web:v1", "digest": "abc"}"#;
+        actor.annotations_mut().insert(SBOM_ARCHIVE_ANNOTATION.into(), content.into());
+        assert_eq!(archived_of(&actor), Some(Archive { image: "registry/web:v1".into(), digest: "abc".into() }));
+    }
+}
@@ -13,22 +13,46 @@
 // limitations under the License.
 
 use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use amp_common::config::{Credential, Scheme};
+use amp_common::config::{Credential, Credentials};
 use amp_common::docker::DockerConfig;
-use k8s_openapi::api::core::v1::Secret;
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::{LocalObjectReference, Secret};
 use k8s_openapi::ByteString;
-use kube::api::{Patch, PatchParams};
-use kube::core::ObjectMeta;
+use kube::api::{DeleteParams, Patch, PatchParams};
+use kube::core::{DynamicObject, GroupVersionKind, ObjectMeta};
+use kube::discovery::ApiResource;
 use kube::{Api, Client, ResourceExt};
+use reqwest::StatusCode;
+use serde_json::{from_value, json, Value};
+use tokio::sync::Mutex;
 use tracing::{debug, info};
-use url::Url;
 
+use super::credential;
 use super::error::{Error, Result};
+use super::git::{self, Auth, KPACK_GIT_ANNOTATION};
+
+/// The Secret in the Amphitheatre namespace holding the credentials.
+pub const CREDENTIALS_SECRET: &str = "amp-credentials";
+
+/// The Secret in the namespaces of playbooks holding the registry credentials.
+pub const REGISTRY_SECRET: &str = "amp-registry-credentials";
+
+/// The annotation of the actor holding the names of its own image pull
+/// secrets, separated by commas, e.g. `ghcr-pull,ecr-pull`.
+pub const IMAGE_PULL_SECRETS_ANNOTATION: &str = "amphitheatre.app/image-pull-secrets";
+
+/// The key of the credentials as a TOML document, in the Secret or in the secret of Vault.
+const CREDENTIALS_KEY: &str = "credentials";
+
+/// The token of the ServiceAccount, used to log in Vault with the Kubernetes auth method.
+const SERVICE_ACCOUNT_TOKEN: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
 
 pub async fn create_registry_secret(client: &Client, namespace: &str, config: DockerConfig) -> Result<Secret> {
     let resource = Secret {
-        metadata: ObjectMeta { name: Some("amp-registry-credentials".to_string()), ..Default::default() },
+        metadata: ObjectMeta { name: Some(REGISTRY_SECRET.to_string()), ..Default::default() },
         type_: Some("kubernetes.io/dockerconfigjson".to_string()),
         data: Some(BTreeMap::from([(
             ".dockerconfigjson".to_string(),
@@ -41,33 +65,55 @@ pub async fn create_registry_secret(client: &Client, namespace: &str, config: Do
     create(client, namespace, resource).await
 }
 
+/// Returns the names of the image pull secrets declared by the actor itself.
+pub fn pull_secrets(actor: &Actor) -> Vec<String> {
+    let value = actor.annotations().get(IMAGE_PULL_SECRETS_ANNOTATION).map(String::as_str).unwrap_or_default();
+    value.split(',').map(str::trim).filter(|name| !name.is_empty()).map(String::from).collect()
+}
+
+/// Returns the image pull secrets of the pods of actor, the registry
+/// credentials synced by Amphitheatre followed by its own ones. They're set
+/// on the pods explicitly instead of the ServiceAccount of the namespace, so
+/// the other workloads in it can't pull with them.
+pub fn image_pull_secrets(actor: &Actor) -> Vec<LocalObjectReference> {
+    let mut names = vec![REGISTRY_SECRET.to_string()];
+    names.extend(pull_secrets(actor).into_iter().filter(|name| name != REGISTRY_SECRET));
+    names.into_iter().map(|name| LocalObjectReference { name: Some(name) }).collect()
+}
+
 pub async fn create_repository_secret(
     client: &Client,
     namespace: &str,
     endpoint: &str,
     credential: &impl Credential,
 ) -> Result<Secret> {
-    let mut secret_type = String::from("Opaque");
-    let mut data = BTreeMap::new();
-
-    match credential.scheme() {
-        Scheme::Basic => {
-            secret_type = String::from("kubernetes.io/basic-auth");
-            data = BTreeMap::from([
-                ("username".to_string(), credential.username_any()),
-                ("password".to_string(), credential.password_any()),
-            ]);
+    let name = git::secret_name(endpoint).ok_or_else(|| Error::InvalidRepositoryAddress(endpoint.into()))?;
+    let (secret_type, data) = match git::auth(endpoint, credential) {
+        Some(Auth::Basic { username, password }) => (
+            "kubernetes.io/basic-auth",
+            BTreeMap::from([("username".to_string(), username), ("password".to_string(), password)]),
+        ),
+        Some(Auth::Ssh { private_key, known_hosts }) => {
+            let mut data = BTreeMap::from([("ssh-privatekey".to_string(), private_key)]);
+            data.extend(known_hosts.map(|known_hosts| ("known_hosts".to_string(), known_hosts)));
+            ("kubernetes.io/ssh-auth", data)
         }
-        Scheme::Bearer => {
-            secret_type = String::from("kubernetes.io/ssh-auth");
-            data = BTreeMap::from([("ssh-privatekey".to_string(), credential.token_any())]);
+        None => ("Opaque", BTreeMap::new()),
+    };
+
+    // The type of Secret is immutable, replace the one created with another type.
+    if let Some(secret) = get_opt(client, namespace, &name).await? {
+        if secret.type_.as_deref() != Some(secret_type) {
+            let api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+            api.delete(&name, &DeleteParams::default()).await.map_err(Error::KubeError)?;
         }
-        Scheme::Unknown => {}
     }
 
+    // kpack picks the credentials of the git servers by the annotation.
+    let annotations = git::kpack_server(endpoint).map(|server| BTreeMap::from([(KPACK_GIT_ANNOTATION.into(), server)]));
     let resource = Secret {
-        metadata: ObjectMeta { name: Some(secret_name(endpoint)?), ..ObjectMeta::default() },
-        type_: Some(secret_type),
+        metadata: ObjectMeta { name: Some(name), annotations, ..ObjectMeta::default() },
+        type_: Some(secret_type.into()),
         string_data: Some(data),
         ..Secret::default()
     };
@@ -76,13 +122,6 @@ pub async fn create_repository_secret(
     create(client, namespace, resource).await
 }
 
-fn secret_name(endpoint: &str) -> Result<String> {
-    let location = Url::parse(endpoint).map_err(Error::UrlParseError)?;
-    let name = format!("amp-repo-credentials-{}-{}", location.scheme(), location.host_str().unwrap(),).to_lowercase();
-
-    Ok(name)
-}
-
 pub async fn create(client: &Client, namespace: &str, resource: Secret) -> Result<Secret> {
     let api: Api<Secret> = Api::namespaced(client.clone(), namespace);
     let name = resource.name_any();
@@ -99,3 +138,289 @@ pub async fn get_opt(client: &Client, namespace: &str, name: &str) -> Result<Opt
     let api: Api<Secret> = Api::namespaced(client.clone(), namespace);
     api.get_opt(name).await.map_err(Error::KubeError)
 }
+
+/// The source of the registry and repository credentials.
+#[derive(Clone, Debug)]
+pub enum Provider {
+    /// The credentials are maintained in the `amp-credentials` Secret by hand.
+    Kubernetes,
+    /// The credentials are read from HashiCorp Vault periodically, nothing is
+    /// stored in the Amphitheatre namespace.
+    Vault(Vault),
+    /// The `amp-credentials` Secret is synced by the External Secrets Operator.
+    ExternalSecrets(ExternalSecret),
+}
+
+impl Provider {
+    /// Load the current credentials from the provider, they're applied by the caller.
+    pub async fn load(&self, client: &Client, namespace: &str) -> Result<Option<Credentials>> {
+        match self {
+            Provider::Kubernetes => credential::load(client, namespace).await,
+            Provider::Vault(vault) => vault.refresh().await,
+            Provider::ExternalSecrets(external) => {
+                // The operator creates the Secret a while later, it's picked up by the watcher then.
+                external.apply(client, namespace).await?;
+                credential::load(client, namespace).await
+            }
+        }
+    }
+
+    /// Read the current credentials from the provider, without applying anything.
+    pub async fn read(&self, client: &Client, namespace: &str) -> Result<Option<Credentials>> {
+        match self {
+            Provider::Kubernetes | Provider::ExternalSecrets(_) => credential::load(client, namespace).await,
+            Provider::Vault(vault) => vault.credentials().await,
+        }
+    }
+}
+
+/// The client of HashiCorp Vault reading the credentials with the Kubernetes
+/// auth method. The token and the last applied credentials are cached and
+/// shared by its clones.
+#[derive(Clone, Debug)]
+pub struct Vault {
+    /// The address of Vault, e.g. `https://vault.example.com:8200`.
+    pub addr: String,
+    /// The role of the Kubernetes auth method bound to the ServiceAccount.
+    pub role: String,
+    /// The path of the secret holding the credentials, e.g. `secret/data/amphitheatre`.
+    pub path: String,
+    http: reqwest::Client,
+    cache: Arc<Mutex<VaultCache>>,
+}
+
+#[derive(Debug, Default)]
+struct VaultCache {
+    /// The client token and when it should be renewed.
+    token: Option<(String, Instant)>,
+    /// The content of the last applied credentials.
+    content: Option<String>,
+    /// The content of the last read credentials, until they're applied.
+    pending: Option<String>,
+}
+
+impl Vault {
+    pub fn new(addr: &str, role: &str, path: &str) -> Self {
+        Self {
+            addr: addr.trim_end_matches('/').into(),
+            role: role.into(),
+            path: path.trim_matches('/').into(),
+            http: reqwest::Client::new(),
+            cache: Arc::new(Mutex::new(VaultCache::default())),
+        }
+    }
+
+    /// Read the credentials from Vault, returns None if they are not changed
+    /// since the last applied ones. Mark them [`applied`](Self::applied) once
+    /// they are, so the rotated credentials are retried until then.
+    pub async fn refresh(&self) -> Result<Option<Credentials>> {
+        let content = self.read().await?;
+        let mut cache = self.cache.lock().await;
+        if content.is_none() || cache.content == content {
+            return Ok(None);
+        }
+
+        let credentials = content.as_deref().map(toml::from_str).transpose().map_err(Error::TomlDeserializeError)?;
+        cache.pending = content;
+        info!("Loaded the credentials from Vault: {}", self.path);
+
+        Ok(credentials)
+    }
+
+    /// Mark the credentials of the last refresh applied.
+    pub async fn applied(&self) {
+        let mut cache = self.cache.lock().await;
+        if let Some(content) = cache.pending.take() {
+            cache.content = Some(content);
+        }
+    }
+
+    /// Read the current credentials from Vault, regardless of the applied ones.
+    pub async fn credentials(&self) -> Result<Option<Credentials>> {
+        let content = self.read().await?;
+        content.as_deref().map(toml::from_str).transpose().map_err(Error::TomlDeserializeError)
+    }
+
+    /// Read the content of the credentials from the secret, None if it doesn't exist.
+    async fn read(&self) -> Result<Option<String>> {
+        let token = self.token().await?;
+        let url = format!("{}/v1/{}", self.addr, self.path);
+        let response = self.http.get(url).header("X-Vault-Token", token).send().await.map_err(vault_error)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => {
+                debug!("The secret {} was not found in Vault", self.path);
+                Ok(None)
+            }
+            StatusCode::FORBIDDEN => {
+                // The token may be revoked before its lease expires, log in again next time.
+                self.cache.lock().await.token = None;
+                Err(Error::SecretProviderError(anyhow::anyhow!("Permission denied to read {}", self.path)))
+            }
+            _ => {
+                let body: Value =
+                    response.error_for_status().map_err(vault_error)?.json().await.map_err(vault_error)?;
+                Ok(content(&body))
+            }
+        }
+    }
+
+    /// Returns the cached client token, or log in again if it's about to expire.
+    async fn token(&self) -> Result<String> {
+        let mut cache = self.cache.lock().await;
+        if let Some((token, renew_at)) = &cache.token {
+            if Instant::now() < *renew_at {
+                return Ok(token.clone());
+            }
+        }
+
+        let jwt = tokio::fs::read_to_string(SERVICE_ACCOUNT_TOKEN).await.map_err(vault_error)?;
+        let url = format!("{}/v1/auth/kubernetes/login", self.addr);
+        let payload = json!({ "role": self.role, "jwt": jwt.trim() });
+        let response = self.http.post(url).json(&payload).send().await.map_err(vault_error)?;
+        let body: Value = response.error_for_status().map_err(vault_error)?.json().await.map_err(vault_error)?;
+
+        let token = body.pointer("/auth/client_token").and_then(Value::as_str);
+        let token = token.ok_or_else(|| Error::SecretProviderError(anyhow::anyhow!("Missing the client token")))?;
+        let lease = body.pointer("/auth/lease_duration").and_then(Value::as_u64).unwrap_or_default();
+        cache.token = Some((token.to_string(), Instant::now() + renew_after(lease)));
+        debug!("Logged in Vault with role {}, the lease duration is {}s", self.role, lease);
+
+        Ok(token.to_string())
+    }
+}
+
+/// Renew the token after two-thirds of its lease, before it expires.
+fn renew_after(lease: u64) -> Duration {
+    Duration::from_secs(lease * 2 / 3)
+}
+
+/// Returns the content of the credentials in the response of Vault, the
+/// secrets of KV version 2 are nested in another `data` field.
+fn content(body: &Value) -> Option<String> {
+    let data = body.pointer("/data/data").filter(|data| data.is_object()).or_else(|| body.get("data"));
+    data.and_then(|data| data.get(CREDENTIALS_KEY)).and_then(Value::as_str).map(String::from)
+}
+
+#[inline]
+fn vault_error(err: impl Into<anyhow::Error>) -> Error {
+    Error::SecretProviderError(err.into())
+}
+
+/// The ExternalSecret syncing the credentials from an external secret store
+/// into the `amp-credentials` Secret, the rotated credentials are synced in
+/// the refresh interval.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExternalSecret {
+    /// The name of the SecretStore or ClusterSecretStore.
+    pub store: String,
+    /// `SecretStore` or `ClusterSecretStore`.
+    pub store_kind: String,
+    /// The key of the credentials in the external secret store.
+    pub key: String,
+    /// How often the operator syncs the credentials, in seconds.
+    pub refresh_interval: u64,
+}
+
+impl ExternalSecret {
+    /// Create or update the ExternalSecret in the given namespace.
+    pub async fn apply(&self, client: &Client, namespace: &str) -> Result<DynamicObject> {
+        let api: Api<DynamicObject> = Api::namespaced_with(client.clone(), namespace, &external_secret_resource());
+
+        let resource = self.resource()?;
+        let params = &PatchParams::apply("amp-controllers").force();
+        let external =
+            api.patch(CREDENTIALS_SECRET, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+        info!("Applied ExternalSecret {} from {} {}", CREDENTIALS_SECRET, self.store_kind, self.store);
+
+        Ok(external)
+    }
+
+    fn resource(&self) -> Result<DynamicObject> {
+        from_value(json!({
+            "apiVersion": "external-secrets.io/v1beta1",
+            "kind": "ExternalSecret",
+            "metadata": {
+                "name": CREDENTIALS_SECRET,
+                "labels": {
+                    "app.kubernetes.io/managed-by": "Amphitheatre",
+                },
+            },
+            "spec": {
+                "refreshInterval": format!("{}s", self.refresh_interval),
+                "secretStoreRef": {
+                    "name": self.store,
+                    "kind": self.store_kind,
+                },
+                "target": {
+                    "name": CREDENTIALS_SECRET,
+                    "creationPolicy": "Owner",
+                },
+                "data": [{
+                    "secretKey": CREDENTIALS_KEY,
+                    "remoteRef": {
+                        "key": self.key,
+                        "property": CREDENTIALS_KEY,
+                    },
+                }],
+            },
+        }))
+        .map_err(Error::SerializationError)
+    }
+}
+
+#[inline]
+fn external_secret_resource() -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk("external-secrets.io", "v1beta1", "ExternalSecret"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_content_of_kv_versions() {
+        let v2 = json!({"data": {"data": {"credentials": "[[registries]]"}, "metadata": {"version": 3}}});
+        assert_eq!(content(&v2), Some("[[registries]]".to_string()));
+
+        let v1 = json!({"data": {"credentials": "[[repositories]]"}});
+        assert_eq!(content(&v1), Some("[[repositories]]".to_string()));
+
+        assert_eq!(content(&json!({"data": {"data": {}}})), None);
+    }
+
+    #[test]
+    fn test_image_pull_secrets() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        let names = |actor: &Actor| image_pull_secrets(actor).into_iter().filter_map(|r| r.name).collect::<Vec<_>>();
+        assert_eq!(names(&actor), vec![REGISTRY_SECRET]);
+
+        let value = "ghcr-pull, ecr-pull,,amp-registry-credentials";
+        actor.annotations_mut().insert(IMAGE_PULL_SECRETS_ANNOTATION.into(), value.into());
+        assert_eq!(pull_secrets(&actor), vec!["ghcr-pull", "ecr-pull", REGISTRY_SECRET]);
+        assert_eq!(names(&actor), vec![REGISTRY_SECRET, "ghcr-pull", "ecr-pull"]);
+    }
+
+    #[test]
+    fn test_renew_after() {
+        assert_eq!(renew_after(3600), Duration::from_secs(2400));
+        assert_eq!(renew_after(0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_external_secret_resource() {
+        let external = ExternalSecret {
+            store: "vault".into(),
+            store_kind: "ClusterSecretStore".into(),
+            key: "amphitheatre/credentials".into(),
+            refresh_interval: 300,
+        };
+        let resource = external.resource().unwrap();
+
+        assert_eq!(resource.name_any(), CREDENTIALS_SECRET);
+        assert_eq!(resource.data.pointer("/spec/refreshInterval"), Some(&json!("300s")));
+        assert_eq!(resource.data.pointer("/spec/target/name"), Some(&json!(CREDENTIALS_SECRET)));
+        assert_eq!(resource.data.pointer("/spec/data/0/remoteRef/key"), Some(&json!("amphitheatre/credentials")));
+    }
+}
@@ -15,19 +15,20 @@
 use std::collections::BTreeMap;
 
 use amp_common::resource::Actor;
-use k8s_openapi::api::core::v1::{Service, ServiceSpec};
+use k8s_openapi::api::core::v1::{Service, ServicePort, ServiceSpec};
 use kube::api::{Patch, PatchParams, PostParams};
 use kube::core::ObjectMeta;
 use kube::{Api, Client, Resource, ResourceExt};
 use tracing::debug;
 
 use super::error::{Error, Result};
-use super::{hash, LAST_APPLIED_HASH_KEY};
+use super::strategy::{self, COLOR_LABEL};
+use super::{hash, image, naming, LAST_APPLIED_HASH_KEY};
 
 pub async fn exists(client: &Client, actor: &Actor) -> Result<bool> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Service> = Api::namespaced(client.clone(), namespace.as_str());
-    let name = actor.name_any();
+    let name = naming::name(&[&actor.name_any()]);
 
     Ok(api.get_opt(&name).await.map_err(Error::KubeError)?.is_some())
 }
@@ -48,7 +49,7 @@ pub async fn create(client: &Client, actor: &Actor) -> Result<Service> {
 pub async fn update(client: &Client, actor: &Actor) -> Result<Service> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
     let api: Api<Service> = Api::namespaced(client.clone(), namespace.as_str());
-    let name = actor.name_any();
+    let name = naming::name(&[&actor.name_any()]);
 
     let mut service = api.get(&name).await.map_err(Error::KubeError)?;
     tracing::debug!("The Service {} already exists: {:?}", &name, service);
@@ -71,7 +72,7 @@ pub async fn update(client: &Client, actor: &Actor) -> Result<Service> {
     Ok(service)
 }
 
-fn new(actor: &Actor) -> Result<Service> {
+pub fn new(actor: &Actor) -> Result<Service> {
     let name = actor.name_any();
 
     // Build the metadata for the service
@@ -82,23 +83,35 @@ fn new(actor: &Actor) -> Result<Service> {
     ]);
     let annotations = BTreeMap::from([(LAST_APPLIED_HASH_KEY.into(), hash(&actor.spec)?)]);
     let metadata = ObjectMeta {
-        name: Some(name),
+        name: Some(naming::name(&[&name])),
         owner_references: Some(vec![owner_reference]),
         labels: Some(labels.clone()),
         annotations: Some(annotations),
         ..Default::default()
     };
 
-    // Extract ports from deploy spec.
-    let mut service_ports = Some(vec![]);
-    if let Some(deploy) = &actor.spec.character.deploy {
-        service_ports = deploy.service_ports();
+    let service_ports = Some(ports(actor));
+
+    // Select the pods of the color serving the traffic for the blue/green actor.
+    let mut selector = labels;
+    if let Some(color) = strategy::active(actor) {
+        selector.insert(COLOR_LABEL.into(), color.as_str().into());
     }
 
     // Build and return the service resource.
     Ok(Service {
         metadata,
-        spec: Some(ServiceSpec { selector: Some(labels), ports: service_ports, ..Default::default() }),
+        spec: Some(ServiceSpec { selector: Some(selector), ports: service_ports, ..Default::default() }),
         ..Default::default()
     })
 }
+
+/// Returns the ports of the Service of actor, extracted from its deploy spec,
+/// or the ports inferred from its image if not declared.
+pub fn ports(actor: &Actor) -> Vec<ServicePort> {
+    let declared = actor.spec.character.deploy.as_ref().and_then(|deploy| deploy.service_ports());
+    match declared.filter(|ports| !ports.is_empty()) {
+        Some(ports) => ports,
+        None => image::inferred(actor).iter().map(|port| port.service_port()).collect(),
+    }
+}
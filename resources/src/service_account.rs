@@ -15,11 +15,18 @@
 use std::collections::HashSet;
 
 use k8s_openapi::api::core::v1::{LocalObjectReference, ObjectReference, Secret, ServiceAccount};
-use kube::api::{Patch, PatchParams};
+use k8s_openapi::api::rbac::v1::{Role, RoleBinding};
+use kube::api::{Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
 use kube::{Api, Client, ResourceExt};
 use serde_json::json;
+use tracing::info;
 
 use super::error::{Error, Result};
+use super::rbac;
+
+/// The dedicated ServiceAccount the builds run under in the namespaces of playbooks.
+pub const BUILDER_SERVICE_ACCOUNT: &str = "amp-builder";
 
 pub async fn patch(
     client: &Client,
@@ -64,3 +71,62 @@ pub async fn patch(
 
     Ok(account)
 }
+
+/// Remove the given secrets from the image pull secrets of the ServiceAccount, they
+/// were patched into it before the pods referenced their image pull secrets explicitly.
+/// It's patched only if any of them is still there.
+pub async fn revoke_image_pull_secrets(client: &Client, namespace: &str, name: &str, secrets: &[Secret]) -> Result<()> {
+    let api: Api<ServiceAccount> = Api::namespaced(client.clone(), namespace);
+    let Some(account) = api.get_opt(name).await.map_err(Error::KubeError)? else {
+        return Ok(());
+    };
+
+    let names: HashSet<String> = secrets.iter().map(|secret| secret.name_any()).collect();
+    let image_pull_secrets = account.image_pull_secrets.unwrap_or_default();
+    let (revoked, kept): (Vec<_>, Vec<_>) = image_pull_secrets
+        .into_iter()
+        .partition(|secret| secret.name.as_ref().is_some_and(|name| names.contains(name)));
+    if revoked.is_empty() {
+        return Ok(());
+    }
+
+    // The image pull secrets have no merge key, so the list is replaced as a whole.
+    let patch = Patch::Merge(json!({ "imagePullSecrets": kept }));
+    api.patch(name, &PatchParams::default(), &patch).await.map_err(Error::KubeError)?;
+    info!("Removed the image pull secrets from Service Account {} in namespace {}", name, namespace);
+
+    Ok(())
+}
+
+/// Create the builder ServiceAccount in the namespace if it doesn't exist,
+/// and grant it to read the given credential Secrets only.
+pub async fn builder(client: &Client, namespace: &str, secrets: &[String]) -> Result<()> {
+    let api: Api<ServiceAccount> = Api::namespaced(client.clone(), namespace);
+    if api.get_opt(BUILDER_SERVICE_ACCOUNT).await.map_err(Error::KubeError)?.is_none() {
+        let resource = ServiceAccount {
+            metadata: ObjectMeta {
+                name: Some(BUILDER_SERVICE_ACCOUNT.into()),
+                labels: Some(rbac::labels()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        match api.create(&PostParams::default(), &resource).await {
+            Ok(account) => info!("Created ServiceAccount {} in namespace {}", account.name_any(), namespace),
+            // The namespace and credentials watchers may create it at the same time.
+            Err(kube::Error::Api(err)) if err.code == 409 => {}
+            Err(err) => return Err(Error::KubeError(err)),
+        }
+    }
+
+    let params = &PatchParams::apply("amp-controllers").force();
+    let api: Api<Role> = Api::namespaced(client.clone(), namespace);
+    let role = rbac::builder_role(secrets);
+    api.patch(BUILDER_SERVICE_ACCOUNT, params, &Patch::Apply(&role)).await.map_err(Error::KubeError)?;
+
+    let api: Api<RoleBinding> = Api::namespaced(client.clone(), namespace);
+    let binding = rbac::builder_role_binding(namespace);
+    api.patch(BUILDER_SERVICE_ACCOUNT, params, &Patch::Apply(&binding)).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::batch::v1::Job;
+use kube::api::{Patch, PatchParams, PostParams};
+use kube::{Api, Client, ResourceExt};
+use oci_distribution::Reference;
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::containers::cosign;
+use crate::error::{Error, Result};
+use crate::{hash, image, job, naming, LAST_APPLIED_HASH_KEY};
+
+/// The annotation of playbook or actor enabling the image signing, the value is the mode.
+pub const SIGNING_ANNOTATION: &str = "amphitheatre.app/signing";
+
+/// The annotation of actor recording the signature reference of its current image.
+pub const SIGNATURE_ANNOTATION: &str = "amphitheatre.app/signature";
+
+/// The Secret holding the cosign private key (`cosign.key`) and its password (`cosign.password`).
+pub const COSIGN_KEY_SECRET_NAME: &str = "amp-cosign-key";
+
+/// The supported signing modes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mode {
+    /// Sign with the private key stored in Secret.
+    Key,
+    /// Sign with the ephemeral key issued for the OIDC identity of ServiceAccount.
+    Keyless,
+}
+
+impl FromStr for Mode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "key" => Ok(Mode::Key),
+            "keyless" => Ok(Mode::Keyless),
+            x => Err(Error::UnknownSigningMode(x.to_string())),
+        }
+    }
+}
+
+/// Returns the signing mode if the image signing is enabled for the actor.
+pub fn mode(actor: &Actor) -> Result<Option<Mode>> {
+    match actor.annotations().get(SIGNING_ANNOTATION).map(|value| value.as_str()) {
+        None | Some("") | Some("none") => Ok(None),
+        Some(value) => value.parse().map(Some),
+    }
+}
+
+/// Sign the current image of the actor by its digest, the tag may be moved by
+/// the later builds. The previous signing job will be replaced if the actor or
+/// the digest has changed.
+pub async fn sign(client: &Client, actor: &Actor, mode: &Mode, digest: &str) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(&actor.spec.name);
+
+    let expected_hash = hash(&(&actor.spec, digest))?;
+    if let Some(job) = api.get_opt(&name).await.map_err(Error::KubeError)? {
+        if job.annotations().get(LAST_APPLIED_HASH_KEY) == Some(&expected_hash) {
+            debug!("The signing Job {} is already up-to-date", name);
+            return Ok(());
+        }
+
+        job::replace(&api, &name).await?;
+        info!("Deleted the outdated signing Job {}", name);
+    }
+
+    let image = image::pinned(&actor.spec.image, digest);
+    let mut resource = job::new(actor, name, cosign::pod(&image, mode))?;
+    resource.annotations_mut().insert(LAST_APPLIED_HASH_KEY.into(), expected_hash);
+    let job = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+    info!("Created signing Job: {}", job.name_any());
+
+    Ok(())
+}
+
+/// Check if the signing job of the actor is completed, returns an error if it failed.
+pub async fn completed(client: &Client, actor: &Actor) -> Result<bool> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(&actor.spec.name);
+
+    let Some(status) = api.get_opt(&name).await.map_err(Error::KubeError)?.and_then(|job| job.status) else {
+        debug!("Not found the status of signing Job {}", name);
+        return Ok(false);
+    };
+    if status.failed >= Some(1) {
+        return Err(Error::SigningFailed(name));
+    }
+
+    Ok(status.succeeded >= Some(1))
+}
+
+/// Record the reference of the signature of the image digest to the actor,
+/// which is where cosign pushes it and the verification looks it up.
+pub async fn record(client: &Client, actor: &Actor, digest: &str) -> Result<String> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), namespace.as_str());
+
+    let reference = signature(&actor.spec.image, digest)?;
+    let annotations = BTreeMap::from([(SIGNATURE_ANNOTATION, reference.as_str())]);
+    let patch = json!({ "metadata": { "annotations": annotations } });
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Recorded the signature {} of Actor {}", reference, actor.name_any());
+
+    Ok(reference)
+}
+
+/// Returns the reference of the signature of the image digest.
+fn signature(image: &str, digest: &str) -> Result<String> {
+    let reference: Reference = image.parse().map_err(|e| Error::ImageInspectError(anyhow::Error::new(e)))?;
+    Ok(image::signature(&reference, digest).whole())
+}
+
+#[inline]
+fn job_name(actor: &str) -> String {
+    naming::name(&[actor, "sign"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!("key".parse::<Mode>().unwrap(), Mode::Key);
+        assert_eq!("Keyless".parse::<Mode>().unwrap(), Mode::Keyless);
+        assert!("unknown".parse::<Mode>().is_err());
+    }
+
+    #[test]
+    fn test_mode_of_actor() {
+        let mut actor = Actor::new("test", Default::default());
+        assert_eq!(mode(&actor).unwrap(), None);
+
+        actor.annotations_mut().insert(SIGNING_ANNOTATION.into(), "none".into());
+        assert_eq!(mode(&actor).unwrap(), None);
+
+        actor.annotations_mut().insert(SIGNING_ANNOTATION.into(), "keyless".into());
+        assert_eq!(mode(&actor).unwrap(), Some(Mode::Keyless));
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            signature("harbor.example.com/library/web:v1", "sha256:abc").unwrap(),
+            "harbor.example.com/library/web:sha256-abc.sig"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::apps::v1::{StatefulSet, StatefulSetPersistentVolumeClaimRetentionPolicy, StatefulSetSpec};
+use k8s_openapi::api::core::v1::{PersistentVolumeClaim, PodSpec, PodTemplateSpec, Service, ServiceSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, Resource, ResourceExt};
+use serde_json::json;
+use tracing::{debug, info};
+
+use super::error::{Error, Result};
+use super::{actor, naming, replicas, volume, LAST_APPLIED_HASH_KEY};
+
+/// The annotation of actor choosing its workload, `deployment` or
+/// `statefulset`, the default is `deployment`. The playbook chooses the
+/// workload of a character with the annotation suffixed by its name, e.g.
+/// `amphitheatre.app/workload.db`. The claims of the StatefulSet are deleted
+/// with it, either the actor is deleted or its workload is switched.
+pub const WORKLOAD_ANNOTATION: &str = "amphitheatre.app/workload";
+
+/// Returns true if the actor is deployed as a StatefulSet, for the characters
+/// needing stable identities and storage, e.g. databases and message brokers.
+pub fn stateful(actor: &Actor) -> bool {
+    actor.annotations().get(WORKLOAD_ANNOTATION).is_some_and(|workload| workload.eq_ignore_ascii_case("statefulset"))
+}
+
+pub async fn exists(client: &Client, namespace: &str, name: &str) -> Result<bool> {
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), namespace);
+    Ok(api.get_opt(name).await.map_err(Error::KubeError)?.is_some())
+}
+
+pub async fn create(client: &Client, namespace: &str, resource: StatefulSet) -> Result<StatefulSet> {
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), namespace);
+    let statefulset = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+    info!("Created StatefulSet: {}", statefulset.name_any());
+
+    Ok(statefulset)
+}
+
+/// Update the StatefulSet if the hash is changed, the volume claim templates
+/// can't be changed, so they are kept as they were created.
+pub async fn update(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+    mut resource: StatefulSet,
+    expected_hash: String,
+) -> Result<StatefulSet> {
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), namespace);
+    let mut statefulset = api.get(name).await.map_err(Error::KubeError)?;
+    debug!("The StatefulSet {} already exists", name);
+
+    let found_hash: String = statefulset.annotations().get(LAST_APPLIED_HASH_KEY).map_or("".into(), |v| v.into());
+    if found_hash == expected_hash {
+        debug!("The StatefulSet {} is already up-to-date", name);
+        return Ok(statefulset);
+    }
+
+    if let (Some(spec), Some(found)) = (resource.spec.as_mut(), statefulset.spec.as_ref()) {
+        spec.volume_claim_templates = found.volume_claim_templates.clone();
+    }
+    debug!("The updating StatefulSet resource:\n {:?}\n", resource);
+
+    let params = &PatchParams::apply("amp-controllers").force();
+    statefulset = api.patch(name, params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+
+    info!("Updated StatefulSet: {}", statefulset.name_any());
+    Ok(statefulset)
+}
+
+/// Delete the StatefulSet and the claims created from its claim templates,
+/// the ones created before the retention policy was set are left otherwise.
+pub async fn delete(client: &Client, namespace: &str, name: &str) -> Result<()> {
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), namespace);
+    let Some(statefulset) = api.get_opt(name).await.map_err(Error::KubeError)? else {
+        return Ok(());
+    };
+    api.delete(name, &DeleteParams::default()).await.map_err(Error::KubeError)?;
+    info!("Deleted StatefulSet {}", name);
+
+    let templates = statefulset.spec.and_then(|spec| spec.volume_claim_templates).unwrap_or_default();
+    if templates.is_empty() {
+        return Ok(());
+    }
+    let claims: Api<PersistentVolumeClaim> = Api::namespaced(client.clone(), namespace);
+    for claim in claims.list(&ListParams::default()).await.map_err(Error::KubeError)?.items {
+        let claim = claim.name_any();
+        if templates.iter().any(|template| volume::created_from(&claim, &template.name_any(), name)) {
+            claims.delete(&claim, &DeleteParams::default()).await.map_err(Error::KubeError)?;
+            info!("Deleted PersistentVolumeClaim {} of StatefulSet {}", claim, name);
+        }
+    }
+
+    Ok(())
+}
+
+/// List all the StatefulSets managed by Amphitheatre in the namespace.
+pub async fn list(client: &Client, namespace: &str) -> Result<Vec<StatefulSet>> {
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), namespace);
+    let params = ListParams::default().labels("app.kubernetes.io/managed-by=Amphitheatre");
+    let statefulsets = api.list(&params).await.map_err(Error::KubeError)?;
+
+    Ok(statefulsets.items)
+}
+
+/// Scale the StatefulSet to the given number of replicas.
+pub async fn scale(client: &Client, namespace: &str, name: &str, replicas: i32) -> Result<StatefulSet> {
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), namespace);
+
+    let patch = json!({"spec": { "replicas": replicas }});
+    let statefulset =
+        api.patch(name, &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Scaled StatefulSet {} to {} replicas", statefulset.name_any(), replicas);
+
+    Ok(statefulset)
+}
+
+/// Set the image of the container of the StatefulSet, e.g. to roll it back.
+pub async fn set_image(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+    container: &str,
+    image: &str,
+) -> Result<StatefulSet> {
+    let api: Api<StatefulSet> = Api::namespaced(client.clone(), namespace);
+
+    let patch = json!({"spec": { "template": { "spec": { "containers": [{ "name": container, "image": image }] } } }});
+    let params = PatchParams::default();
+    let statefulset = api.patch(name, &params, &Patch::Strategic(&patch)).await.map_err(Error::KubeError)?;
+    info!("Set the image of StatefulSet {} to {}", statefulset.name_any(), image);
+
+    Ok(statefulset)
+}
+
+/// Create or update the headless Service governing the network identities of the pods.
+pub async fn apply_headless_service(client: &Client, actor: &Actor) -> Result<Service> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Service> = Api::namespaced(client.clone(), namespace.as_str());
+
+    let resource = headless_service(actor);
+    let params = &PatchParams::apply("amp-controllers").force();
+    let service = api.patch(&resource.name_any(), params, &Patch::Apply(&resource)).await.map_err(Error::KubeError)?;
+    debug!("Applied headless Service: {}", service.name_any());
+
+    Ok(service)
+}
+
+/// Returns the name of the StatefulSet of actor.
+pub fn name(actor: &Actor) -> String {
+    naming::name(&[&actor.name_any()])
+}
+
+/// Returns the name of the headless Service of actor.
+pub fn headless_service_name(actor: &Actor) -> String {
+    naming::name(&[&actor.name_any(), "headless"])
+}
+
+/// Build the headless Service governing the network identities of the pods.
+pub fn headless_service(actor: &Actor) -> Service {
+    let labels = labels(actor);
+    Service {
+        metadata: ObjectMeta {
+            name: Some(headless_service_name(actor)),
+            owner_references: actor.controller_owner_ref(&()).map(|owner| vec![owner]),
+            labels: Some(labels.clone()),
+            ..Default::default()
+        },
+        spec: Some(ServiceSpec { cluster_ip: Some("None".into()), selector: Some(labels), ..Default::default() }),
+        ..Default::default()
+    }
+}
+
+/// Build the StatefulSet of actor, each pod gets its own claims of the templates.
+pub fn new(actor: &Actor, pod: PodSpec, claims: Vec<PersistentVolumeClaim>, hash: String) -> Result<StatefulSet> {
+    let name = name(actor);
+
+    let owner_reference = actor.controller_owner_ref(&()).unwrap();
+    let labels = labels(actor);
+    let annotations = BTreeMap::from([(LAST_APPLIED_HASH_KEY.into(), hash)]);
+    let metadata = ObjectMeta {
+        name: Some(name),
+        owner_references: Some(vec![owner_reference]),
+        labels: Some(labels.clone()),
+        annotations: Some(annotations),
+        ..Default::default()
+    };
+
+    let mut pod_labels = labels.clone();
+    pod_labels.extend(actor::pod_labels(actor));
+    let spec = StatefulSetSpec {
+        replicas: replicas::desired(actor),
+        service_name: headless_service_name(actor),
+        selector: LabelSelector { match_labels: Some(labels), ..Default::default() },
+        template: PodTemplateSpec {
+            metadata: Some(ObjectMeta { labels: Some(pod_labels), ..Default::default() }),
+            spec: Some(pod),
+        },
+        volume_claim_templates: (!claims.is_empty()).then_some(claims),
+        persistent_volume_claim_retention_policy: Some(StatefulSetPersistentVolumeClaimRetentionPolicy {
+            when_deleted: Some("Delete".into()),
+            when_scaled: Some("Retain".into()),
+        }),
+        ..Default::default()
+    };
+
+    Ok(StatefulSet { metadata, spec: Some(spec), ..Default::default() })
+}
+
+fn labels(actor: &Actor) -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("amphitheatre.app/character".into(), actor.name_any()),
+        ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    #[test]
+    fn test_stateful() {
+        let mut actor = Actor::new("db", ActorSpec::default());
+        assert!(!stateful(&actor));
+
+        actor.annotations_mut().insert(WORKLOAD_ANNOTATION.into(), "StatefulSet".into());
+        assert!(stateful(&actor));
+
+        actor.annotations_mut().insert(WORKLOAD_ANNOTATION.into(), "deployment".into());
+        assert!(!stateful(&actor));
+    }
+
+    #[test]
+    fn test_headless_service() {
+        let actor = Actor::new("db", ActorSpec::default());
+        let service = headless_service(&actor);
+
+        assert_eq!(service.name_any(), "db-headless");
+        assert_eq!(service.spec.unwrap().cluster_ip.as_deref(), Some("None"));
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::apps::v1::{Deployment, DeploymentStrategy, RollingUpdateDeployment};
+use k8s_openapi::api::core::v1::Service;
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::error::{Error, Result};
+use crate::{canary, deployment, naming};
+
+/// The annotation of actor holding its update strategy as a JSON document,
+/// e.g. `{"type": "rolling_update", "max_unavailable": "0"}`. The playbook
+/// declares the strategy of a character with the annotation suffixed by its
+/// name, e.g. `amphitheatre.app/strategy.web`.
+pub const STRATEGY_ANNOTATION: &str = "amphitheatre.app/strategy";
+
+/// The annotation of the blue/green actor recording the color serving the traffic.
+pub const ACTIVE_COLOR_ANNOTATION: &str = "amphitheatre.app/active-color";
+
+/// The label of the pods of the blue/green actor, selected by its Service.
+pub const COLOR_LABEL: &str = "amphitheatre.app/color";
+
+/// How the updates of actor are rolled out, the StatefulSets and CronJobs
+/// keep their own strategies.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Strategy {
+    /// Replace the pods gradually, the default of Deployments.
+    #[default]
+    RollingUpdate {
+        /// The number or percentage of the pods created above the replicas, e.g. `25%`.
+        max_surge: Option<String>,
+        /// The number or percentage of the pods unavailable during the update, e.g. `0`.
+        max_unavailable: Option<String>,
+    },
+    /// Kill all the old pods before creating the new ones.
+    Recreate,
+    /// Deploy the new version next to the old one, and switch the traffic
+    /// to it after it is ready.
+    BlueGreen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    Blue,
+    Green,
+}
+
+impl Color {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Color::Blue => "blue",
+            Color::Green => "green",
+        }
+    }
+
+    pub fn other(&self) -> Color {
+        match self {
+            Color::Blue => Color::Green,
+            Color::Green => Color::Blue,
+        }
+    }
+}
+
+impl Strategy {
+    /// Returns the strategy of the Deployment, None for the blue/green one
+    /// as its Deployments are never updated in place.
+    pub fn deployment_strategy(&self) -> Option<DeploymentStrategy> {
+        match self {
+            Strategy::RollingUpdate { max_surge, max_unavailable } => Some(DeploymentStrategy {
+                type_: Some("RollingUpdate".into()),
+                rolling_update: Some(RollingUpdateDeployment {
+                    max_surge: max_surge.as_deref().map(int_or_string),
+                    max_unavailable: max_unavailable.as_deref().map(int_or_string),
+                }),
+            }),
+            Strategy::Recreate => Some(DeploymentStrategy { type_: Some("Recreate".into()), rolling_update: None }),
+            Strategy::BlueGreen => None,
+        }
+    }
+}
+
+fn int_or_string(value: &str) -> IntOrString {
+    value.parse().map(IntOrString::Int).unwrap_or_else(|_| IntOrString::String(value.into()))
+}
+
+/// Returns the update strategy of actor, None if not declared.
+pub fn strategy(actor: &Actor) -> Result<Option<Strategy>> {
+    match actor.annotations().get(STRATEGY_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Returns the color of the blue/green actor serving the traffic, None before the first switch.
+pub fn active(actor: &Actor) -> Option<Color> {
+    match actor.annotations().get(ACTIVE_COLOR_ANNOTATION).map(String::as_str) {
+        Some("blue") => Some(Color::Blue),
+        Some("green") => Some(Color::Green),
+        _ => None,
+    }
+}
+
+/// Returns the name of the Deployment of the color.
+pub fn name(actor: &Actor, color: Color) -> String {
+    naming::name(&[&actor.name_any(), color.as_str()])
+}
+
+/// Returns the name of the Deployment serving the traffic of actor.
+pub fn serving(actor: &Actor) -> String {
+    match active(actor) {
+        Some(color) => name(actor, color),
+        None => deployment::name(actor),
+    }
+}
+
+/// Returns the names of all the Deployments actor may be served by, the
+/// plain one, the ones of the colors and the canary.
+pub fn deployments(actor: &Actor) -> Vec<String> {
+    vec![deployment::name(actor), name(actor, Color::Blue), name(actor, Color::Green), canary::name(actor)]
+}
+
+/// Paint the Deployment of actor with the color, its pods are selected by the color as well.
+pub fn paint(actor: &Actor, deployment: &mut Deployment, color: Color) {
+    let labels = BTreeMap::from([(COLOR_LABEL.to_string(), color.as_str().to_string())]);
+
+    deployment.metadata.name = Some(name(actor, color));
+    deployment.metadata.labels.get_or_insert_with(BTreeMap::new).extend(labels.clone());
+    if let Some(spec) = deployment.spec.as_mut() {
+        spec.selector.match_labels.get_or_insert_with(BTreeMap::new).extend(labels.clone());
+        let metadata = spec.template.metadata.get_or_insert_with(Default::default);
+        metadata.labels.get_or_insert_with(BTreeMap::new).extend(labels);
+    }
+}
+
+/// Switch the traffic of actor to the color, by the selector of its Service
+/// and the annotation of actor read by the later updates of the Service.
+pub async fn switch(client: &Client, actor: &Actor, color: Color) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+
+    let api: Api<Service> = Api::namespaced(client.clone(), &namespace);
+    let service = naming::name(&[&actor.name_any()]);
+    if api.get_opt(&service).await.map_err(Error::KubeError)?.is_some() {
+        let patch = json!({"spec": {"selector": { COLOR_LABEL: color.as_str() }}});
+        api.patch(&service, &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    }
+
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+    let patch = json!({"metadata": {"annotations": { ACTIVE_COLOR_ANNOTATION: color.as_str() }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Switched the traffic of actor {} to {}", actor.name_any(), color.as_str());
+
+    Ok(())
+}
+
+/// Route the traffic of actor to all its pods again, after its strategy is
+/// changed from blue/green to the others.
+pub async fn reset(client: &Client, actor: &Actor) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+
+    let api: Api<Service> = Api::namespaced(client.clone(), &namespace);
+    let service = naming::name(&[&actor.name_any()]);
+    if api.get_opt(&service).await.map_err(Error::KubeError)?.is_some() {
+        let patch = json!({"spec": {"selector": { COLOR_LABEL: null }}});
+        api.patch(&service, &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    }
+
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+    let patch = json!({"metadata": {"annotations": { ACTIVE_COLOR_ANNOTATION: null }}});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Reset the blue/green traffic of actor {}", actor.name_any());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+    use k8s_openapi::api::apps::v1::DeploymentSpec;
+
+    use crate::statefulset::{self, WORKLOAD_ANNOTATION};
+
+    #[test]
+    fn test_parse_strategy() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(strategy(&actor).unwrap(), None);
+
+        actor.annotations_mut().insert(STRATEGY_ANNOTATION.into(), r#"{"type": "blue_green"}"#.into());
+        assert_eq!(strategy(&actor).unwrap(), Some(Strategy::BlueGreen));
+
+        let content = r#"{"type": "rolling_update", "max_surge": "50%", "max_unavailable": "0"}"#;
+        actor.annotations_mut().insert(STRATEGY_ANNOTATION.into(), content.into());
+        let rolling = strategy(&actor).unwrap().unwrap().deployment_strategy().unwrap().rolling_update.unwrap();
+        assert_eq!(rolling.max_surge, Some(IntOrString::String("50%".into())));
+        assert_eq!(rolling.max_unavailable, Some(IntOrString::Int(0)));
+    }
+
+    #[test]
+    fn test_paint() {
+        let mut actor = Actor::new("web", ActorSpec::default());
+        assert_eq!(serving(&actor), "web");
+
+        let mut deployment = Deployment { spec: Some(DeploymentSpec::default()), ..Default::default() };
+        paint(&actor, &mut deployment, Color::Green);
+        assert_eq!(deployment.name_any(), "web-green");
+        let spec = deployment.spec.unwrap();
+        assert_eq!(spec.selector.match_labels.unwrap()[COLOR_LABEL], "green");
+        assert_eq!(spec.template.metadata.unwrap().labels.unwrap()[COLOR_LABEL], "green");
+
+        actor.annotations_mut().insert(ACTIVE_COLOR_ANNOTATION.into(), "blue".into());
+        assert_eq!(active(&actor).map(|color| color.other()), Some(Color::Green));
+        assert_eq!(serving(&actor), "web-blue");
+    }
+
+    #[test]
+    fn test_deployments() {
+        // A blue/green actor changed to a StatefulSet, none of its Deployments is kept.
+        let mut actor = Actor::new("web", ActorSpec::default());
+        actor.annotations_mut().insert(STRATEGY_ANNOTATION.into(), r#"{"type": "blue_green"}"#.into());
+        actor.annotations_mut().insert(ACTIVE_COLOR_ANNOTATION.into(), "green".into());
+        actor.annotations_mut().insert(WORKLOAD_ANNOTATION.into(), "statefulset".into());
+        assert!(statefulset::stateful(&actor));
+
+        let names = deployments(&actor);
+        assert_eq!(names, vec!["web", "web-blue", "web-green", "web-canary"]);
+        assert!(names.contains(&serving(&actor)));
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use kube::{Resource, ResourceExt};
+use opentelemetry::global;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{self, Tracer};
+use opentelemetry_sdk::{runtime, Resource as OtelResource};
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::error::{Error, Result};
+
+/// The annotation of playbook or actor carrying the W3C trace context of the
+/// API request created it, its reconciliations are linked to the trace of the
+/// request instead of being its children, as they keep running long after it.
+pub const TRACE_CONTEXT_ANNOTATION: &str = "amphitheatre.app/traceparent";
+
+/// The header and key of the W3C trace context.
+const TRACEPARENT: &str = "traceparent";
+
+/// Build the layer exporting the spans to the OTLP collector by gRPC, None
+/// if the endpoint is not set, and the spans are only logged then.
+pub fn layer<S>(service: &str, endpoint: Option<&str>) -> Result<Option<OpenTelemetryLayer<S, Tracer>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Some(endpoint) = endpoint else {
+        return Ok(None);
+    };
+
+    let resource = OtelResource::new(vec![KeyValue::new("service.name", service.to_string())]);
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(trace::config().with_resource(resource))
+        .install_batch(runtime::Tokio)
+        .map_err(Error::TelemetryError)?;
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+/// Returns the W3C trace context of the current span, None if it's not traced.
+pub fn inject() -> Option<String> {
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&Span::current().context(), &mut carrier));
+
+    carrier.remove(TRACEPARENT)
+}
+
+/// Continue the trace of the W3C trace context in the span.
+pub fn follow(span: &Span, traceparent: &str) {
+    span.set_parent(extract(traceparent));
+}
+
+/// Link the span to the trace of the API request created the object, if any.
+pub fn link<K: Resource>(span: &Span, object: &K) {
+    let Some(traceparent) = object.annotations().get(TRACE_CONTEXT_ANNOTATION) else {
+        return;
+    };
+    let context = extract(traceparent);
+    let span_context = context.span().span_context().clone();
+    if span_context.is_valid() {
+        span.add_link(span_context);
+    }
+}
+
+fn extract(traceparent: &str) -> Context {
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    TraceContextPropagator::new().extract(&carrier)
+}
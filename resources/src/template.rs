@@ -0,0 +1,183 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::error::{Error, Result};
+
+/// The label of the ConfigMaps holding the playbook templates.
+pub const TEMPLATE_LABEL: &str = "amphitheatre.app/template";
+
+/// The key of the ConfigMap holding the template as a JSON document.
+const TEMPLATE_KEY: &str = "template";
+
+/// Store the template in a ConfigMap of the given namespace.
+pub async fn create<T: Serialize>(client: &Client, namespace: &str, id: &str, template: &T) -> Result<()> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+
+    let content = serde_json::to_string(template).map_err(Error::SerializationError)?;
+    let resource = ConfigMap {
+        metadata: ObjectMeta {
+            name: Some(name(id)),
+            labels: Some(BTreeMap::from([
+                (TEMPLATE_LABEL.into(), id.into()),
+                ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+            ])),
+            ..Default::default()
+        },
+        data: Some(BTreeMap::from([(TEMPLATE_KEY.into(), content)])),
+        ..Default::default()
+    };
+    let config_map = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+    info!("Created template: {}", config_map.name_any());
+
+    Ok(())
+}
+
+/// Replace the template stored in the ConfigMap of the given namespace.
+pub async fn update<T: Serialize>(client: &Client, namespace: &str, id: &str, template: &T) -> Result<()> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+
+    let content = serde_json::to_string(template).map_err(Error::SerializationError)?;
+    let patch = serde_json::json!({ "data": { TEMPLATE_KEY: content } });
+    api.patch(&name(id), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Updated template: {}", id);
+
+    Ok(())
+}
+
+/// Delete the template from the given namespace.
+pub async fn delete(client: &Client, namespace: &str, id: &str) -> Result<()> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    api.delete(&name(id), &DeleteParams::default()).await.map_err(Error::KubeError)?;
+    info!("Deleted template: {}", id);
+
+    Ok(())
+}
+
+/// Get the template from the ConfigMap of the given namespace.
+pub async fn get<T: DeserializeOwned>(client: &Client, namespace: &str, id: &str) -> Result<Option<T>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+
+    match api.get_opt(&name(id)).await.map_err(Error::KubeError)? {
+        Some(config_map) => from(&config_map).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// List all the templates in the given namespace.
+pub async fn list<T: DeserializeOwned>(client: &Client, namespace: &str) -> Result<Vec<T>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let params = ListParams::default().labels(TEMPLATE_LABEL);
+
+    api.list(&params).await.map_err(Error::KubeError)?.items.iter().map(from).collect()
+}
+
+/// Substitute the `{{ name }}` placeholders of the given variables in all the
+/// strings of the template, the other braces are kept as they are, e.g. the
+/// Helm values referring to their own templates.
+pub fn render<T: Serialize + DeserializeOwned>(template: &T, variables: &HashMap<String, String>) -> Result<T> {
+    let mut value = serde_json::to_value(template).map_err(Error::SerializationError)?;
+    substitute(&mut value, variables);
+
+    serde_json::from_value(value).map_err(Error::SerializationError)
+}
+
+fn substitute(value: &mut Value, variables: &HashMap<String, String>) {
+    match value {
+        Value::String(s) => *s = interpolate(s, variables),
+        Value::Array(values) => values.iter_mut().for_each(|v| substitute(v, variables)),
+        Value::Object(map) => map.values_mut().for_each(|v| substitute(v, variables)),
+        _ => {}
+    }
+}
+
+fn interpolate(s: &str, variables: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + end + 2];
+        let value = variables.get(placeholder[2..placeholder.len() - 2].trim());
+
+        result.push_str(&rest[..start]);
+        result.push_str(value.map_or(placeholder, String::as_str));
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+fn from<T: DeserializeOwned>(config_map: &ConfigMap) -> Result<T> {
+    let content = config_map.data.as_ref().and_then(|data| data.get(TEMPLATE_KEY));
+    let content = content.ok_or(Error::MissingObjectKey(".data.template"))?;
+
+    serde_json::from_str(content).map_err(Error::SerializationError)
+}
+
+#[inline]
+fn name(id: &str) -> String {
+    format!("amp-template-{}", id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_render() {
+        let template = json!({
+            "title": "Preview of PR #{{ pr }}",
+            "preface": { "repository": { "repo": "https://github.com/amp/app", "branch": "{{branch}}" } },
+            "replicas": 1,
+        });
+        let variables = HashMap::from([("pr".to_string(), "42".to_string()), ("branch".into(), "feat/x".into())]);
+
+        let rendered = render(&template, &variables).unwrap();
+
+        assert_eq!(rendered["title"], "Preview of PR #42");
+        assert_eq!(rendered["preface"]["repository"]["branch"], "feat/x");
+        assert_eq!(rendered["replicas"], 1);
+    }
+
+    #[test]
+    fn test_render_keeps_unknown_placeholders() {
+        let template = json!({ "title": "{{ pr }} of {{ .Release.Name }}" });
+        let variables = HashMap::from([("pr".to_string(), "42".to_string())]);
+
+        assert_eq!(render(&template, &variables).unwrap()["title"], "42 of {{ .Release.Name }}");
+    }
+
+    #[test]
+    fn test_render_without_placeholders() {
+        let template = json!({ "title": "{ not a placeholder }}" });
+
+        assert_eq!(render(&template, &HashMap::new()).unwrap(), template);
+    }
+}
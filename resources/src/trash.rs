@@ -0,0 +1,95 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Playbook;
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
+use tracing::info;
+
+use super::error::{Error, Result};
+use super::hibernation::{self, Reason};
+use super::playbook;
+
+/// The annotation recording the time the playbook was deleted into the trash,
+/// in RFC 3339 format, it's purged once the retention window elapses unless
+/// it's restored before that.
+pub const DELETED_ANNOTATION: &str = "amphitheatre.app/deleted";
+
+/// Returns the time the playbook was deleted, if it's in the trash.
+pub fn deleted(playbook: &Playbook) -> Option<DateTime<Utc>> {
+    playbook
+        .annotations()
+        .get(DELETED_ANNOTATION)
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Move the playbook into the trash, its actors are scaled to zero and kept
+/// until it's restored or purged, returns the time it was deleted.
+pub async fn delete(client: &Client, playbook: &Playbook) -> Result<DateTime<Utc>> {
+    // Mark it first, so the activities in between don't wake it up again. It's
+    // hibernated again if it's marked already, in case the previous one failed.
+    let time = match deleted(playbook) {
+        Some(time) => time,
+        None => {
+            let now = Utc::now();
+            let annotations = BTreeMap::from([(DELETED_ANNOTATION.to_string(), now.to_rfc3339())]);
+            playbook::annotate(client, playbook, annotations).await?;
+            now
+        }
+    };
+    hibernation::hibernate(client, playbook, Reason::Deleted).await?;
+    info!("Moved the playbook {} into the trash", playbook.name_any());
+
+    Ok(time)
+}
+
+/// Restore the playbook from the trash, its actors are scaled back.
+pub async fn restore(client: &Client, playbook: &Playbook) -> Result<()> {
+    if deleted(playbook).is_none() {
+        return Ok(());
+    }
+
+    let api: Api<Playbook> = Api::all(client.clone());
+    let patch = json!({"metadata": { "annotations": { DELETED_ANNOTATION: null }}});
+    api.patch(&playbook.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    hibernation::wake(client, playbook, Reason::Deleted).await?;
+    info!("Restored the playbook {} from the trash", playbook.name_any());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::PlaybookSpec;
+
+    #[test]
+    fn test_deleted() {
+        let mut playbook = Playbook::new("test", PlaybookSpec::default());
+        assert_eq!(deleted(&playbook), None);
+
+        let time = DateTime::parse_from_rfc3339("2024-08-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        playbook.annotations_mut().insert(DELETED_ANNOTATION.into(), time.to_rfc3339());
+        assert_eq!(deleted(&playbook), Some(time));
+
+        playbook.annotations_mut().insert(DELETED_ANNOTATION.into(), "yesterday".into());
+        assert_eq!(deleted(&playbook), None);
+    }
+}
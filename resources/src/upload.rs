@@ -0,0 +1,94 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::OnceLock;
+
+use amp_common::resource::ActorSpec;
+
+use crate::artifact::{self, Kind};
+
+/// The default download API of the uploaded sources in the artifact store of
+/// the apiserver, they are addressed by the SHA-256 digests of their tarballs.
+pub const DEFAULT_SOURCES_URL: &str = "http://amp-apiserver.amp-system.svc:8170/v1/artifacts/sources";
+
+/// The download API of the uploaded sources configured by the operator.
+static SOURCES: OnceLock<Sources> = OnceLock::new();
+
+struct Sources {
+    url: String,
+    /// The token of the apiserver the downloads are signed with.
+    key: Option<String>,
+}
+
+/// Configure the download API of the uploaded sources, and the token of the
+/// apiserver to sign the downloads with, as the artifacts require it.
+pub fn configure(url: &str, key: Option<String>) {
+    let url = url.trim_end_matches('/').to_string();
+    tracing::info!("Configured the download API of the uploaded sources: {}", url);
+    SOURCES.set(Sources { url, key }).ok();
+}
+
+/// The scheme of the repository of the characters built from the uploaded
+/// sources, e.g. `upload://{id}`.
+const SCHEME: &str = "upload://";
+
+/// Returns the repository of the character built from the uploaded source.
+pub fn repository(id: &str) -> String {
+    format!("{}{}", SCHEME, id)
+}
+
+/// Returns the id of the uploaded source if it's the repository of one.
+pub fn id(repo: &str) -> Option<&str> {
+    repo.strip_prefix(SCHEME).filter(|id| !id.is_empty())
+}
+
+/// Returns the URL the build pods download the uploaded source from, with
+/// the signature granting it if the token of the apiserver is configured.
+pub fn url(id: &str) -> String {
+    let sources = SOURCES.get();
+    let base = sources.map_or(DEFAULT_SOURCES_URL, |sources| sources.url.as_str());
+    match sources.and_then(|sources| sources.key.as_deref()) {
+        Some(key) => format!("{}/{}?token={}", base, id, artifact::signature(key, Kind::Sources, id)),
+        None => format!("{}/{}", base, id),
+    }
+}
+
+/// Returns the id of the uploaded source the actor is built from, if any.
+pub fn of(spec: &ActorSpec) -> Option<&str> {
+    spec.source.as_ref().filter(|_| !spec.live).and_then(|source| id(&source.repo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::schema::GitReference;
+
+    #[test]
+    fn test_id() {
+        assert_eq!(id(&repository("1234abcd")), Some("1234abcd"));
+        assert_eq!(id("upload://"), None);
+        assert_eq!(id("https://github.com/amp/monorepo"), None);
+    }
+
+    #[test]
+    fn test_of() {
+        let source = GitReference { repo: repository("1234abcd"), ..Default::default() };
+        let mut spec = ActorSpec { source: Some(source), ..Default::default() };
+        assert_eq!(of(&spec), Some("1234abcd"));
+
+        spec.live = true;
+        assert_eq!(of(&spec), None);
+    }
+}
@@ -0,0 +1,322 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Playbook;
+use chrono_tz::Tz;
+use k8s_openapi::chrono::{
+    DateTime, Datelike, Duration, DurationRound, FixedOffset, NaiveDateTime, TimeZone, Timelike, Utc,
+};
+use kube::{Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+
+use super::error::{Error, Result};
+use super::playbook;
+
+/// The annotation of playbook holding its uptime schedule as a JSON document,
+/// e.g. `{"start": "0 8 * * 1-5", "stop": "0 19 * * 1-5", "timezone": "Asia/Shanghai"}`.
+pub const UPTIME_ANNOTATION: &str = "amphitheatre.app/uptime";
+
+/// The annotation of playbook recording the time of the latest start or stop
+/// of its schedule that has been applied, in RFC 3339 format.
+pub const UPTIME_APPLIED_ANNOTATION: &str = "amphitheatre.app/uptime-applied";
+
+/// How far back the latest start or stop is looked for, a week and a day.
+const LOOKBACK_MINUTES: i64 = 8 * 24 * 60;
+
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+
+/// The schedule starting and stopping the playbook, e.g. in the working hours,
+/// it's only applied when the time comes, so the manual starts and stops are
+/// kept until the next one.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Uptime {
+    /// When to start the playbook in Cron format, e.g. `0 8 * * 1-5`.
+    pub start: String,
+    /// When to stop the playbook in Cron format, e.g. `0 19 * * 1-5`.
+    pub stop: String,
+    /// The timezone of the schedule as an IANA name, e.g. `Europe/Berlin`,
+    /// which follows its daylight saving time, or an offset from UTC, e.g.
+    /// `+08:00`, the default is `UTC`.
+    pub timezone: Option<String>,
+}
+
+/// The timezone the schedule is evaluated in.
+enum Zone {
+    Offset(FixedOffset),
+    Named(Tz),
+}
+
+/// What the schedule does to the playbook.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Start,
+    Stop,
+}
+
+impl Uptime {
+    /// Check the Cron expressions and the timezone of the schedule.
+    pub fn validate(&self) -> Result<()> {
+        Cron::parse(&self.start)?;
+        Cron::parse(&self.stop)?;
+        self.zone()?;
+        Ok(())
+    }
+
+    /// Returns the latest start or stop up to the given time, and when it was,
+    /// the stop wins if both are at the same time.
+    pub fn latest(&self, now: DateTime<Utc>) -> Result<Option<(Action, DateTime<Utc>)>> {
+        let (start, stop) = (Cron::parse(&self.start)?, Cron::parse(&self.stop)?);
+        match self.zone()? {
+            Zone::Offset(offset) => Ok(scan(&start, &stop, now.with_timezone(&offset))),
+            Zone::Named(tz) => Ok(scan(&start, &stop, now.with_timezone(&tz))),
+        }
+    }
+
+    /// Returns the timezone, either an IANA name or an offset.
+    fn zone(&self) -> Result<Zone> {
+        let timezone = self.timezone.as_deref().map(str::trim).unwrap_or_default();
+        if matches!(timezone, "" | "UTC" | "Z") || timezone.starts_with(['+', '-']) {
+            return self.offset().map(Zone::Offset);
+        }
+
+        timezone.parse::<Tz>().map(Zone::Named).map_err(|_| Error::InvalidUptime(format!("timezone {}", timezone)))
+    }
+
+    /// Returns the offset of the timezone, e.g. `+08:00`, `-0530` or `UTC`.
+    fn offset(&self) -> Result<FixedOffset> {
+        let timezone = self.timezone.as_deref().map(str::trim).unwrap_or_default();
+        if matches!(timezone, "" | "UTC" | "Z") {
+            return Ok(FixedOffset::east_opt(0).unwrap());
+        }
+
+        let invalid = || Error::InvalidUptime(format!("timezone {}", timezone));
+        let sign = match timezone.chars().next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return Err(invalid()),
+        };
+        let digits: String = timezone[1..].chars().filter(|c| *c != ':').collect();
+        if !(digits.len() == 2 || digits.len() == 4) || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+        let minutes: i32 = match digits.len() {
+            4 => digits[2..].parse().map_err(|_| invalid())?,
+            _ => 0,
+        };
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+    }
+}
+
+/// Returns the latest start or stop matched at the local times going back from now.
+fn scan<Z: TimeZone>(start: &Cron, stop: &Cron, now: DateTime<Z>) -> Option<(Action, DateTime<Utc>)> {
+    let now = now.clone().duration_trunc(Duration::minutes(1)).unwrap_or(now);
+    for minutes in 0..=LOOKBACK_MINUTES {
+        let time = now.clone() - Duration::minutes(minutes);
+        if stop.matches(&time.naive_local()) {
+            return Some((Action::Stop, time.with_timezone(&Utc)));
+        }
+        if start.matches(&time.naive_local()) {
+            return Some((Action::Start, time.with_timezone(&Utc)));
+        }
+    }
+
+    None
+}
+
+/// Returns the uptime schedule of playbook, if any.
+pub fn of(playbook: &Playbook) -> Result<Option<Uptime>> {
+    match playbook.annotations().get(UPTIME_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Returns the latest start or stop of the schedule of playbook not applied yet.
+pub fn pending(playbook: &Playbook, now: DateTime<Utc>) -> Result<Option<(Action, DateTime<Utc>)>> {
+    let Some(uptime) = of(playbook)? else {
+        return Ok(None);
+    };
+    let applied = playbook
+        .annotations()
+        .get(UPTIME_APPLIED_ANNOTATION)
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|time| time.with_timezone(&Utc));
+
+    Ok(uptime.latest(now)?.filter(|(_, time)| applied.map_or(true, |applied| *time > applied)))
+}
+
+/// Record the start or stop of the schedule at the given time has been applied.
+pub async fn applied(client: &Client, playbook: &Playbook, time: DateTime<Utc>) -> Result<()> {
+    let annotations = BTreeMap::from([(UPTIME_APPLIED_ANNOTATION.to_string(), time.to_rfc3339())]);
+    playbook::annotate(client, playbook, annotations).await
+}
+
+/// The Cron expression of five fields, each field is a set of the matched
+/// values in bits, e.g. the bit 1 of `weekdays` is Monday.
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    fn parse(expression: &str) -> Result<Cron> {
+        let invalid = || Error::InvalidUptime(format!("Cron expression {}", expression));
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        // Both 0 and 7 are Sunday.
+        let weekdays = field(weekday, 0, 7, &WEEKDAYS).ok_or_else(invalid)?;
+
+        Ok(Cron {
+            minutes: field(minute, 0, 59, &[]).ok_or_else(invalid)?,
+            hours: field(hour, 0, 23, &[]).ok_or_else(invalid)?,
+            days: field(day, 1, 31, &[]).ok_or_else(invalid)?,
+            months: field(month, 1, 12, &MONTHS).ok_or_else(invalid)?,
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: day.starts_with('*') || *day == "?",
+            any_weekday: weekday.starts_with('*') || *weekday == "?",
+        })
+    }
+
+    fn matches(&self, time: &NaiveDateTime) -> bool {
+        let has = |set: u64, value: u32| set & (1 << value) != 0;
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        // Either the day of month or the day of week matches if both are restricted, as cron does.
+        let date = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+
+        date && has(self.minutes, time.minute()) && has(self.hours, time.hour()) && has(self.months, time.month())
+    }
+}
+
+/// Parse a field of Cron expression into the set of values in bits, e.g.
+/// `*/15`, `1-5` or `MON,WED`, the names start from the minimum value.
+fn field(value: &str, min: u32, max: u32, names: &[&str]) -> Option<u64> {
+    let number = |value: &str| {
+        let number = match names.iter().position(|name| name.eq_ignore_ascii_case(value)) {
+            Some(index) => index as u32 + min,
+            None => value.parse().ok()?,
+        };
+        (min..=max).contains(&number).then_some(number)
+    };
+
+    let mut set = 0;
+    for part in value.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" || range == "?" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // A single value with a step runs to the maximum, e.g. `5/15`.
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step) {
+            set |= 1 << value;
+        }
+    }
+
+    Some(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::PlaybookSpec;
+    use k8s_openapi::chrono::TimeZone;
+
+    fn time(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, hour, minute, 0).unwrap()
+    }
+
+    fn working_hours() -> Uptime {
+        Uptime { start: "0 8 * * 1-5".into(), stop: "0 19 * * MON-FRI".into(), timezone: Some("+08:00".into()) }
+    }
+
+    #[test]
+    fn test_field() {
+        assert_eq!(field("*", 0, 6, &[]), Some(0b111_1111));
+        assert_eq!(field("1-5", 0, 6, &WEEKDAYS), Some(0b011_1110));
+        assert_eq!(field("MON,wed", 0, 6, &WEEKDAYS), Some(0b000_1010));
+        assert_eq!(field("*/20", 0, 59, &[]), Some(1 | 1 << 20 | 1 << 40));
+        assert_eq!(field("50/5", 0, 59, &[]), Some(1 << 50 | 1 << 55));
+        assert_eq!(field("JAN", 1, 12, &MONTHS), Some(1 << 1));
+        assert_eq!(field("60", 0, 59, &[]), None);
+        assert_eq!(field("5-1", 0, 59, &[]), None);
+        assert_eq!(field("*/0", 0, 59, &[]), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(working_hours().validate().is_ok());
+        assert!(Uptime { start: "0 8 * *".into(), ..working_hours() }.validate().is_err());
+        assert!(Uptime { timezone: Some("Asia/Shanghai".into()), ..working_hours() }.validate().is_ok());
+        assert!(Uptime { timezone: Some("Mars/Olympus".into()), ..working_hours() }.validate().is_err());
+        assert!(Uptime { timezone: Some("UTC".into()), ..working_hours() }.validate().is_ok());
+        assert!(Uptime { timezone: Some("-0530".into()), ..working_hours() }.validate().is_ok());
+        assert!(Uptime { timezone: Some("+25:00".into()), ..working_hours() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_latest() {
+        let uptime = working_hours();
+        // Thursday 11:00 at +08:00, started at 08:00.
+        assert_eq!(uptime.latest(time(15, 3, 0)).unwrap(), Some((Action::Start, time(15, 0, 0))));
+        // Thursday 20:30 at +08:00, stopped at 19:00.
+        assert_eq!(uptime.latest(time(15, 12, 30)).unwrap(), Some((Action::Stop, time(15, 11, 0))));
+        // Saturday 20:00 at +08:00, stopped on Friday.
+        assert_eq!(uptime.latest(time(17, 12, 0)).unwrap(), Some((Action::Stop, time(16, 11, 0))));
+
+        // Friday 10:00 in Berlin, started at 08:00 in summer time, 06:00 UTC.
+        let uptime = Uptime { timezone: Some("Europe/Berlin".into()), ..working_hours() };
+        assert_eq!(uptime.latest(time(16, 8, 0)).unwrap(), Some((Action::Start, time(16, 6, 0))));
+    }
+
+    #[test]
+    fn test_pending() {
+        let mut playbook = Playbook::new("test", PlaybookSpec::default());
+        assert_eq!(pending(&playbook, time(15, 3, 0)).unwrap(), None);
+
+        let uptime = serde_json::to_string(&working_hours()).unwrap();
+        playbook.annotations_mut().insert(UPTIME_ANNOTATION.into(), uptime);
+        assert_eq!(pending(&playbook, time(15, 3, 0)).unwrap(), Some((Action::Start, time(15, 0, 0))));
+
+        playbook.annotations_mut().insert(UPTIME_APPLIED_ANNOTATION.into(), time(15, 0, 0).to_rfc3339());
+        assert_eq!(pending(&playbook, time(15, 3, 0)).unwrap(), None);
+        assert_eq!(pending(&playbook, time(15, 12, 30)).unwrap(), Some((Action::Stop, time(15, 11, 0))));
+    }
+}
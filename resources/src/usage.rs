@@ -0,0 +1,290 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::core::v1::ConfigMap;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::api::{Patch, PatchParams, PostParams};
+use kube::core::ObjectMeta;
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, warn};
+
+use crate::actor;
+use crate::error::{Error, Result};
+use crate::workspace::WorkspacePolicy;
+
+/// The ConfigMap in the namespace of the actors recording their usages of the
+/// current month as JSON documents keyed by their names. They're not recorded in
+/// the actors, so the periodic metering doesn't trigger their reconciliations.
+pub const USAGE_CONFIG_MAP: &str = "amp-usage";
+
+/// The annotation of actor indicating the new builds are paused, as its budget is exceeded.
+pub const BUILDS_PAUSED_ANNOTATION: &str = "amphitheatre.app/builds-paused";
+
+/// The condition type of actor reporting its builds are paused.
+pub const BUILDS_PAUSED_CONDITION_TYPE: &str = "BuildsPaused";
+
+/// The budgets of actor, checked against its usage of the current month.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Budget {
+    BuildMinutes,
+    RuntimeHours,
+}
+
+/// The usage of actor in a month, it's reset at the beginning of every month.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Usage {
+    /// The month of the usage, e.g. `2026-10`.
+    pub period: String,
+    /// The minutes spent on building the images.
+    pub build_minutes: f64,
+    /// The hours of the running pods, a pod running for an hour is a pod-hour.
+    pub runtime_hours: f64,
+    /// The start time of the last metered build, so that a build is never metered twice.
+    #[serde(default)]
+    pub last_build: Option<String>,
+    /// The last time the runtime was metered.
+    #[serde(default)]
+    pub metered_at: Option<String>,
+    /// The exceeded budgets that have been alerted in this period.
+    #[serde(default)]
+    pub alerted: Vec<Budget>,
+}
+
+impl Usage {
+    /// Add the minutes of the build started at the given time, returns false if it's metered already.
+    pub fn add_build(&mut self, started: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        let started_at = started.to_rfc3339();
+        if self.last_build.as_ref() == Some(&started_at) {
+            return false;
+        }
+
+        self.build_minutes += (now - started).num_seconds().max(0) as f64 / 60.0;
+        self.last_build = Some(started_at);
+        true
+    }
+
+    /// Add the pod-hours of the running replicas since the last metering.
+    pub fn add_runtime(&mut self, replicas: i32, now: DateTime<Utc>) {
+        let last = self.metered_at.as_ref().and_then(|value| DateTime::parse_from_rfc3339(value).ok());
+        if let Some(last) = last {
+            let seconds = (now - last.with_timezone(&Utc)).num_seconds().max(0);
+            self.runtime_hours += replicas.max(0) as f64 * seconds as f64 / 3600.0;
+        }
+        self.metered_at = Some(now.to_rfc3339());
+    }
+
+    /// Returns the budgets of the policy exceeded by this usage.
+    pub fn exceeded(&self, policy: &WorkspacePolicy) -> Vec<Budget> {
+        let mut budgets = vec![];
+        if policy.build_minutes_budget.is_some_and(|budget| self.build_minutes > budget) {
+            budgets.push(Budget::BuildMinutes);
+        }
+        if policy.runtime_hours_budget.is_some_and(|budget| self.runtime_hours > budget) {
+            budgets.push(Budget::RuntimeHours);
+        }
+        budgets
+    }
+}
+
+/// Returns the month of the given time, the usages are tracked per month.
+pub fn period(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m").to_string()
+}
+
+/// Load the usages of the actors in the given namespace, keyed by their names.
+pub async fn load(client: &Client, namespace: &str) -> Result<BTreeMap<String, Usage>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let Some(config_map) = api.get_opt(USAGE_CONFIG_MAP).await.map_err(Error::KubeError)? else {
+        return Ok(BTreeMap::new());
+    };
+
+    let mut usages = BTreeMap::new();
+    for (name, content) in config_map.data.unwrap_or_default() {
+        match serde_json::from_str(&content) {
+            Ok(usage) => {
+                usages.insert(name, usage);
+            }
+            Err(err) => warn!("Skip the malformed usage of actor {}: {}", name, err),
+        }
+    }
+
+    Ok(usages)
+}
+
+/// Returns the usage of actor in the current month from its namespace.
+pub async fn get(client: &Client, actor: &Actor, now: DateTime<Utc>) -> Result<Usage> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let usages = load(client, &namespace).await?;
+
+    Ok(of(&usages, actor, now))
+}
+
+/// Returns the usage of actor in the current month from the loaded usages.
+pub fn of(usages: &BTreeMap<String, Usage>, actor: &Actor, now: DateTime<Utc>) -> Usage {
+    let current = period(now);
+    let usage = usages.get(&actor.name_any()).cloned().unwrap_or_default();
+
+    if usage.period == current {
+        return usage;
+    }
+
+    // Start over in a new month, the runtime since the last metering counts into it.
+    Usage { period: current, metered_at: usage.metered_at, ..Default::default() }
+}
+
+/// Returns true if the new builds of actor are paused.
+pub fn builds_paused(actor: &Actor) -> bool {
+    actor.annotations().contains_key(BUILDS_PAUSED_ANNOTATION)
+}
+
+/// Save the usage of actor, and pause or resume its builds. The actor is
+/// patched only if it's paused or resumed, which is rare.
+pub async fn save(client: &Client, actor: &Actor, usage: &Usage, paused: bool) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), &namespace);
+    let content = serde_json::to_string(usage).map_err(Error::SerializationError)?;
+    let patch = json!({ "data": { actor.name_any(): content } });
+    match api.patch(USAGE_CONFIG_MAP, &PatchParams::default(), &Patch::Merge(&patch)).await {
+        Err(kube::Error::Api(err)) if err.code == 404 => {
+            let resource = ConfigMap {
+                metadata: ObjectMeta {
+                    name: Some(USAGE_CONFIG_MAP.into()),
+                    labels: Some(BTreeMap::from([("app.kubernetes.io/managed-by".into(), "Amphitheatre".into())])),
+                    ..Default::default()
+                },
+                data: Some(BTreeMap::from([(actor.name_any(), content)])),
+                ..Default::default()
+            };
+            api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+        }
+        result => {
+            result.map_err(Error::KubeError)?;
+        }
+    }
+    debug!("Saved the usage of actor {}: {:?}", actor.name_any(), usage);
+
+    if paused != builds_paused(actor) {
+        let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+        let paused = paused.then(|| usage.period.clone());
+        let patch = json!({"metadata": { "annotations": { BUILDS_PAUSED_ANNOTATION: paused }}});
+        api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    }
+
+    Ok(())
+}
+
+/// Meter the build of actor which has just completed, the build started when
+/// the actor transitioned into the building state, the conditions upserted
+/// after that (e.g. paused builds) are always later than it.
+pub async fn record_build(client: &Client, actor: &Actor) -> Result<()> {
+    let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    let Some(started) = conditions.iter().map(|condition| condition.last_transition_time.0).min() else {
+        return Ok(());
+    };
+
+    let now = Utc::now();
+    let mut usage = get(client, actor, now).await?;
+    if usage.add_build(started, now) {
+        save(client, actor, &usage, builds_paused(actor)).await?;
+    }
+
+    Ok(())
+}
+
+/// Report the paused builds in the conditions of actor, unless it's already reported.
+pub async fn pause(client: &Client, actor: &Actor) -> Result<()> {
+    let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    if conditions.iter().any(|c| c.type_ == BUILDS_PAUSED_CONDITION_TYPE) {
+        return Ok(());
+    }
+
+    let condition = Condition {
+        type_: BUILDS_PAUSED_CONDITION_TYPE.into(),
+        status: "True".into(),
+        reason: "BudgetExceeded".into(),
+        message: "The build minutes budget of this month is exceeded".into(),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    };
+    actor::upsert_condition(client, actor, condition).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+    use k8s_openapi::chrono::TimeZone;
+
+    fn time(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_add_build_once() {
+        let mut usage = Usage::default();
+
+        assert!(usage.add_build(time(1, 10), time(1, 11)));
+        assert!(!usage.add_build(time(1, 10), time(1, 12)));
+        assert_eq!(usage.build_minutes, 60.0);
+    }
+
+    #[test]
+    fn test_add_runtime() {
+        let mut usage = Usage::default();
+
+        // The first metering only records the time.
+        usage.add_runtime(2, time(1, 10));
+        assert_eq!(usage.runtime_hours, 0.0);
+
+        usage.add_runtime(2, time(1, 13));
+        assert_eq!(usage.runtime_hours, 6.0);
+    }
+
+    #[test]
+    fn test_exceeded() {
+        let policy = WorkspacePolicy { build_minutes_budget: Some(100.0), ..Default::default() };
+        let usage = Usage { build_minutes: 120.0, runtime_hours: 1000.0, ..Default::default() };
+
+        assert_eq!(usage.exceeded(&policy), vec![Budget::BuildMinutes]);
+        assert!(usage.exceeded(&WorkspacePolicy::default()).is_empty());
+    }
+
+    #[test]
+    fn test_usage_reset_every_month() {
+        let usage = Usage {
+            period: "2026-09".into(),
+            build_minutes: 10.0,
+            metered_at: Some(time(1, 0).to_rfc3339()),
+            ..Default::default()
+        };
+        let actor = Actor::new("web", ActorSpec::default());
+        let usages = BTreeMap::from([("web".to_string(), usage.clone())]);
+
+        let current = of(&usages, &actor, time(2, 0));
+        assert_eq!(current.period, "2026-10");
+        assert_eq!(current.build_minutes, 0.0);
+        assert_eq!(current.metered_at, usage.metered_at);
+
+        assert_eq!(of(&usages, &actor, Utc.with_ymd_and_hms(2026, 9, 30, 0, 0, 0).unwrap()), usage);
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use amp_common::resource::{CharacterSpec, Playbook};
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::error::{Error, Result};
+
+/// The annotation of the playbook holding its variables in JSON, e.g.
+/// `{"API_URL": "https://api.example.com"}`, which are referenced by the
+/// strings of its character specs as `${vars.API_URL}`.
+pub const VARS_ANNOTATION: &str = "amphitheatre.app/vars";
+
+/// The prefix of the references to the variables.
+pub(crate) const PREFIX: &str = "${vars.";
+
+/// Returns the variables of the playbook, empty if it has none.
+pub fn of(playbook: &Playbook) -> Result<BTreeMap<String, String>> {
+    match playbook.annotations().get(VARS_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map_err(Error::SerializationError),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/// Returns true if it's a valid name of variable, e.g. `API_URL`.
+pub fn valid(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace the variables of the playbook as a whole, they're removed if
+/// empty. The actors referencing them are updated on the next reconcile.
+pub async fn replace(client: &Client, playbook: &Playbook, vars: &BTreeMap<String, String>) -> Result<()> {
+    let api: Api<Playbook> = Api::all(client.clone());
+
+    let content = match vars.is_empty() {
+        true => Value::Null,
+        false => json!(serde_json::to_string(vars).map_err(Error::SerializationError)?),
+    };
+    let patch = json!({ "metadata": { "annotations": { VARS_ANNOTATION: content } } });
+    api.patch(&playbook.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Replaced the variables of playbook {}: {:?}", playbook.name_any(), vars.keys());
+
+    Ok(())
+}
+
+/// Returns the character with the references to the variables in its
+/// strings replaced by their values, it fails if a variable is undefined.
+pub fn substitute(character: &CharacterSpec, vars: &BTreeMap<String, String>) -> Result<CharacterSpec> {
+    let mut value = serde_json::to_value(character).map_err(Error::SerializationError)?;
+    if !render_value(&mut value, vars)? {
+        return Ok(character.clone());
+    }
+
+    serde_json::from_value(value).map_err(Error::SerializationError)
+}
+
+/// Render the strings of the value in place, returns true if any is changed.
+fn render_value(value: &mut Value, vars: &BTreeMap<String, String>) -> Result<bool> {
+    let mut changed = false;
+    match value {
+        Value::String(text) if text.contains(PREFIX) => {
+            *text = render(text, vars)?;
+            changed = true;
+        }
+        Value::Array(items) => {
+            for item in items {
+                changed |= render_value(item, vars)?;
+            }
+        }
+        Value::Object(fields) => {
+            for (_, field) in fields.iter_mut() {
+                changed |= render_value(field, vars)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(changed)
+}
+
+/// Replace the references to the variables in the text by their values.
+fn render(text: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(PREFIX) {
+        rendered.push_str(&rest[..start]);
+        let reference = &rest[start + PREFIX.len()..];
+        let end = reference.find('}').ok_or_else(|| Error::InvalidVariable(text.to_string()))?;
+        let name = &reference[..end];
+        let value = vars.get(name).ok_or_else(|| Error::UndefinedVariable(name.to_string()))?;
+        rendered.push_str(value);
+        rest = &reference[end + 1..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars() -> BTreeMap<String, String> {
+        BTreeMap::from([("API_URL".into(), "https://api.example.com".into()), ("TAG".into(), "v1".into())])
+    }
+
+    #[test]
+    fn test_valid() {
+        assert!(valid("API_URL"));
+        assert!(valid("_tag2"));
+        assert!(!valid("2FA"));
+        assert!(!valid("API-URL"));
+        assert!(!valid(""));
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(render("${vars.API_URL}/v2", &vars()).unwrap(), "https://api.example.com/v2");
+        assert_eq!(render("app:${vars.TAG}-${vars.TAG}", &vars()).unwrap(), "app:v1-v1");
+        assert_eq!(render("$HOME and ${HOME}", &vars()).unwrap(), "$HOME and ${HOME}");
+        assert!(matches!(render("${vars.MISSING}", &vars()), Err(Error::UndefinedVariable(name)) if name == "MISSING"));
+        assert!(matches!(render("${vars.API_URL", &vars()), Err(Error::InvalidVariable(_))));
+    }
+
+    #[test]
+    fn test_render_value() {
+        let mut value = json!({
+            "meta": {"name": "web"},
+            "deploy": {"env": {"API_URL": "${vars.API_URL}"}, "replicas": 2},
+            "build": {"args": ["--tag=${vars.TAG}"]},
+        });
+        assert!(render_value(&mut value, &vars()).unwrap());
+        assert_eq!(value["deploy"]["env"]["API_URL"], "https://api.example.com");
+        assert_eq!(value["deploy"]["replicas"], 2);
+        assert_eq!(value["build"]["args"][0], "--tag=v1");
+
+        let mut value = json!({"meta": {"name": "web"}});
+        assert!(!render_value(&mut value, &vars()).unwrap());
+    }
+}
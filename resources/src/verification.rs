@@ -0,0 +1,389 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use amp_common::resource::Actor;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{Container, EnvVar, PodSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::api::{Patch, PatchParams, PostParams};
+use kube::{Api, Client, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+
+use crate::error::{Error, Result};
+use crate::probe::READY_CONDITION_TYPE;
+use crate::{hash, image, job, naming, LAST_APPLIED_HASH_KEY};
+
+/// The annotation of actor holding its post-deploy verification as a JSON
+/// document, e.g. `{"http": [{"path": "/healthz", "port": 80}], "timeout": 300}`.
+/// The playbook declares the verification of a character with the annotation
+/// suffixed by its name, e.g. `amphitheatre.app/verification.web`.
+pub const VERIFICATION_ANNOTATION: &str = "amphitheatre.app/verification";
+
+/// The annotation of actor recording the progress of its verification.
+pub const VERIFICATION_STATUS_ANNOTATION: &str = "amphitheatre.app/verification-status";
+
+/// The condition type of actor reporting the verification of its current image.
+pub const VERIFIED_CONDITION_TYPE: &str = "Verified";
+
+/// The checks run against the Service of actor after its pods are ready,
+/// the actor is reported Ready only after all of them passed.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Verification {
+    /// The HTTP requests expected to respond with the status.
+    #[serde(default)]
+    pub http: Vec<HttpCheck>,
+    /// The Job running the smoke tests, it passes if the Job succeeded.
+    pub job: Option<VerificationJob>,
+    /// Fail the verification if it is not passed in this many seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct HttpCheck {
+    #[serde(default = "default_path")]
+    pub path: String,
+    /// The port of the Service of actor.
+    pub port: i32,
+    #[serde(default = "default_status")]
+    pub status: u16,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct VerificationJob {
+    pub image: String,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+fn default_timeout() -> u64 {
+    300
+}
+
+fn default_path() -> String {
+    "/".into()
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+/// The progress of the verification of an image.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Status {
+    /// The image verified.
+    pub image: String,
+    pub phase: Phase,
+    /// The start time of the verification, in RFC 3339 format.
+    pub started_at: String,
+    #[serde(default)]
+    pub message: String,
+    /// The last image passed the verification, rolled back to if this one
+    /// failed, the rollback is recorded along with the ones of the rollouts.
+    pub verified: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Verifying,
+    Passed,
+    Failed,
+    RolledBack,
+}
+
+/// What to do next with the verification.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decision {
+    /// Check again after the duration.
+    Wait(Duration),
+    /// All the checks passed.
+    Pass,
+    /// The verification failed, with the reason.
+    Fail(String),
+}
+
+/// Returns the verification of actor, None if not declared.
+pub fn verification(actor: &Actor) -> Result<Option<Verification>> {
+    match actor.annotations().get(VERIFICATION_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map(Some).map_err(Error::SerializationError),
+        None => Ok(None),
+    }
+}
+
+/// Returns the progress of the verification of actor, if any.
+pub fn status(actor: &Actor) -> Option<Status> {
+    let content = actor.annotations().get(VERIFICATION_STATUS_ANNOTATION);
+    content.and_then(|content| serde_json::from_str(content).ok())
+}
+
+/// Record the progress of the verification of actor.
+pub async fn record(client: &Client, actor: &Actor, status: &Status) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Actor> = Api::namespaced(client.clone(), &namespace);
+
+    let content = serde_json::to_string(status).map_err(Error::SerializationError)?;
+    let annotations = BTreeMap::from([(VERIFICATION_STATUS_ANNOTATION.to_string(), content)]);
+    let patch = json!({"metadata": { "annotations": annotations }});
+    api.patch(&actor.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+/// Start the verification of the current image of actor, the last verified
+/// image is kept from the previous verification.
+pub fn start(actor: &Actor, previous: Option<&Status>, now: DateTime<Utc>) -> Status {
+    let verified = previous.and_then(|status| match status.phase {
+        Phase::Passed => Some(status.image.clone()),
+        _ => status.verified.clone(),
+    });
+
+    Status {
+        image: image::deployed(actor),
+        phase: Phase::Verifying,
+        started_at: now.to_rfc3339(),
+        message: String::new(),
+        verified,
+    }
+}
+
+/// Run the HTTP checks against the Service of actor, returns the reason of
+/// the first failed one, None if all of them passed.
+pub async fn check(actor: &Actor, checks: &[HttpCheck]) -> Result<Option<String>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let http = reqwest::Client::new();
+
+    for check in checks {
+        let url = format!("http://{}.{}.svc:{}{}", actor.name_any(), namespace, check.port, check.path);
+        match http.get(&url).timeout(Duration::from_secs(10)).send().await {
+            Ok(response) if response.status().as_u16() == check.status => debug!("The check {} passed", url),
+            Ok(response) => return Ok(Some(format!("{} responded {}", url, response.status()))),
+            Err(err) => return Ok(Some(format!("{} is unreachable: {}", url, err))),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Run the verification Job of actor, the previous one will be replaced if the actor has changed.
+pub async fn run(client: &Client, actor: &Actor, verification: &VerificationJob) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(&actor.spec.name);
+
+    // The rebuilds of the same tag are verified again by their digests.
+    let expected_hash = hash(&(&actor.spec, image::deployed(actor)))?;
+    if let Some(job) = api.get_opt(&name).await.map_err(Error::KubeError)? {
+        if job.annotations().get(LAST_APPLIED_HASH_KEY) == Some(&expected_hash) {
+            debug!("The verification Job {} is already up-to-date", name);
+            return Ok(());
+        }
+
+        job::replace(&api, &name).await?;
+        info!("Deleted the outdated verification Job {}", name);
+    }
+
+    let mut resource = job::new(actor, name, pod(actor, &namespace, verification))?;
+    resource.annotations_mut().insert(LAST_APPLIED_HASH_KEY.into(), expected_hash);
+    let job = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+    info!("Created verification Job: {}", job.name_any());
+
+    Ok(())
+}
+
+/// Returns whether the verification Job of actor succeeded, None if it is still running.
+pub async fn completed(client: &Client, actor: &Actor) -> Result<Option<bool>> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Job> = Api::namespaced(client.clone(), namespace.as_str());
+    let name = job_name(&actor.spec.name);
+
+    let Some(status) = api.get_opt(&name).await.map_err(Error::KubeError)?.and_then(|job| job.status) else {
+        return Ok(None);
+    };
+    if status.failed >= Some(1) {
+        return Ok(Some(false));
+    }
+
+    Ok((status.succeeded >= Some(1)).then_some(true))
+}
+
+/// Build the pod of the verification Job, the URL of the Service of actor is given by `AMP_ACTOR_URL`.
+fn pod(actor: &Actor, namespace: &str, verification: &VerificationJob) -> PodSpec {
+    let env = vec![
+        EnvVar { name: "AMP_ACTOR_NAME".into(), value: Some(actor.name_any()), ..Default::default() },
+        EnvVar { name: "AMP_ACTOR_IMAGE".into(), value: Some(image::deployed(actor)), ..Default::default() },
+        EnvVar {
+            name: "AMP_ACTOR_URL".into(),
+            value: Some(format!("http://{}.{}.svc", actor.name_any(), namespace)),
+            ..Default::default()
+        },
+    ];
+
+    PodSpec {
+        containers: vec![Container {
+            name: "verify".into(),
+            image: Some(verification.image.clone()),
+            image_pull_policy: Some("IfNotPresent".into()),
+            command: (!verification.command.is_empty()).then(|| verification.command.clone()),
+            args: (!verification.args.is_empty()).then(|| verification.args.clone()),
+            env: Some(env),
+            ..Default::default()
+        }],
+        restart_policy: Some("Never".into()),
+        ..Default::default()
+    }
+}
+
+/// Decide the next move of the verification by the results of its HTTP
+/// checks and Job. The HTTP checks are retried until the timeout, as the
+/// Service may take a while to route to the new pods.
+pub fn decide(
+    verification: &Verification,
+    status: &Status,
+    failure: Option<String>,
+    job: Option<bool>,
+    now: DateTime<Utc>,
+) -> Decision {
+    if job == Some(false) {
+        return Decision::Fail("The verification Job failed".into());
+    }
+
+    let started = DateTime::parse_from_rfc3339(&status.started_at).map(|t| t.with_timezone(&Utc)).unwrap_or(now);
+    let elapsed = (now - started).to_std().unwrap_or_default();
+    let passed = failure.is_none() && (verification.job.is_none() || job == Some(true));
+    if passed {
+        return Decision::Pass;
+    }
+    if elapsed.as_secs() > verification.timeout {
+        let reason = failure.unwrap_or_else(|| "The verification Job is not completed".into());
+        return Decision::Fail(format!("Not verified in {} seconds: {}", verification.timeout, reason));
+    }
+
+    Decision::Wait(Duration::from_secs(10))
+}
+
+/// Build the condition reporting the verification of actor.
+pub fn condition(phase: Phase, message: String) -> Condition {
+    let (status, reason) = match phase {
+        Phase::Verifying => ("False", "Verifying"),
+        Phase::Passed => ("True", "Passed"),
+        Phase::Failed => ("False", "Failed"),
+        Phase::RolledBack => ("False", "RolledBack"),
+    };
+    Condition {
+        type_: VERIFIED_CONDITION_TYPE.into(),
+        status: status.into(),
+        reason: reason.into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+/// Build the Ready condition of actor whose pods are ready but not verified yet.
+pub fn unready(phase: Phase, message: String) -> Condition {
+    let reason = match phase {
+        Phase::Verifying => "Verifying",
+        _ => "VerificationFailed",
+    };
+    Condition {
+        type_: READY_CONDITION_TYPE.into(),
+        status: "False".into(),
+        reason: reason.into(),
+        message,
+        last_transition_time: Time(Utc::now()),
+        observed_generation: None,
+    }
+}
+
+#[inline]
+fn job_name(actor: &str) -> String {
+    naming::name(&[actor, "verify"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    fn verification() -> Verification {
+        serde_json::from_str(r#"{"http": [{"port": 80}], "timeout": 60}"#).unwrap()
+    }
+
+    fn verifying(now: DateTime<Utc>) -> Status {
+        Status {
+            image: "web:v2".into(),
+            phase: Phase::Verifying,
+            started_at: now.to_rfc3339(),
+            message: String::new(),
+            verified: Some("web:v1".into()),
+        }
+    }
+
+    #[test]
+    fn test_parse_verification() {
+        let verification = verification();
+        assert_eq!(verification.http, vec![HttpCheck { path: "/".into(), port: 80, status: 200 }]);
+        assert_eq!(verification.job, None);
+        assert_eq!(verification.timeout, 60);
+    }
+
+    #[test]
+    fn test_start_keeps_verified_image() {
+        let actor = Actor::new("web", ActorSpec { image: "web:v3".into(), ..Default::default() });
+        let now = Utc::now();
+
+        let mut previous = verifying(now);
+        previous.phase = Phase::Passed;
+        assert_eq!(start(&actor, Some(&previous), now).verified, Some("web:v2".into()));
+
+        previous.phase = Phase::RolledBack;
+        assert_eq!(start(&actor, Some(&previous), now).verified, Some("web:v1".into()));
+        assert_eq!(start(&actor, None, now).verified, None);
+    }
+
+    #[test]
+    fn test_decide_retries_until_timeout() {
+        let now = Utc::now();
+        let status = verifying(now - k8s_openapi::chrono::Duration::seconds(30));
+        let failure = Some("unreachable".to_string());
+
+        assert_eq!(decide(&verification(), &status, None, None, now), Decision::Pass);
+        assert!(matches!(decide(&verification(), &status, failure.clone(), None, now), Decision::Wait(_)));
+
+        let status = verifying(now - k8s_openapi::chrono::Duration::seconds(90));
+        assert!(matches!(decide(&verification(), &status, failure, None, now), Decision::Fail(_)));
+    }
+
+    #[test]
+    fn test_decide_waits_for_job() {
+        let now = Utc::now();
+        let status = verifying(now);
+        let mut verification = verification();
+        verification.job = Some(VerificationJob { image: "smoke".into(), ..Default::default() });
+
+        assert!(matches!(decide(&verification, &status, None, None, now), Decision::Wait(_)));
+        assert_eq!(decide(&verification, &status, None, Some(true), now), Decision::Pass);
+        assert!(matches!(decide(&verification, &status, None, Some(false), now), Decision::Fail(_)));
+    }
+}
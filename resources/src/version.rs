@@ -0,0 +1,71 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use amp_common::resource::Actor;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
+use kube::{Client, Resource, ResourceExt};
+
+use crate::actor;
+use crate::error::{Error, Result};
+
+/// The condition type of actor recording the version its semver range of tags is resolved to.
+pub const VERSION_CONDITION_TYPE: &str = "VersionResolved";
+
+/// Record the version resolved from the range in the status of actor, it's
+/// skipped if the actor has been resolved to the same version already.
+pub async fn record(client: &Client, actor: &Actor, range: &str, tag: &str, rev: &str) -> Result<()> {
+    let condition = condition(range, tag, rev, actor.meta().generation);
+    let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+    if recorded(conditions, &condition) {
+        return Ok(());
+    }
+
+    // Read it again, as the conditions may have been replaced by a state transition.
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let actor = actor::get(client, &namespace, &actor.name_any()).await?;
+
+    actor::upsert_condition(client, &actor, condition).await
+}
+
+fn recorded(conditions: &[Condition], condition: &Condition) -> bool {
+    conditions.iter().any(|c| c.type_ == VERSION_CONDITION_TYPE && c.message == condition.message)
+}
+
+fn condition(range: &str, tag: &str, rev: &str, generation: Option<i64>) -> Condition {
+    Condition {
+        type_: VERSION_CONDITION_TYPE.into(),
+        status: "True".into(),
+        reason: "Resolved".into(),
+        message: format!("Resolved {} to {} ({})", range, tag, rev),
+        last_transition_time: Time(Utc::now()),
+        observed_generation: generation,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded() {
+        let condition = condition("^1.2", "v1.4.0", "abc123", None);
+        assert_eq!(condition.message, "Resolved ^1.2 to v1.4.0 (abc123)");
+        assert!(!recorded(&[], &condition));
+
+        let conditions = vec![condition.clone()];
+        assert!(recorded(&conditions, &condition));
+        assert!(!recorded(&conditions, &self::condition("^1.2", "v1.5.0", "def456", None)));
+    }
+}
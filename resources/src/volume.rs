@@ -16,15 +16,67 @@ use std::collections::BTreeMap;
 use std::env;
 
 use amp_common::resource::Actor;
-use k8s_openapi::api::core::v1::{PersistentVolumeClaim, PersistentVolumeClaimSpec, VolumeResourceRequirements};
+use k8s_openapi::api::core::v1::{
+    ConfigMapVolumeSource, EmptyDirVolumeSource, PersistentVolumeClaim, PersistentVolumeClaimSpec,
+    PersistentVolumeClaimVolumeSource, Secret, SecretVolumeSource, Volume as PodVolume, VolumeMount,
+    VolumeResourceRequirements,
+};
 use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
-use kube::api::PostParams;
+use kube::api::{ListParams, Patch, PatchParams, PostParams};
 use kube::core::ObjectMeta;
 use kube::{Api, Client, Resource, ResourceExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use tracing::{debug, info};
 
+use crate::actor::PLAYBOOK_LABEL;
 use crate::error::{Error, Result};
 use crate::kpack::BuildExt;
+use crate::{metrics, naming};
+
+/// The annotation of actor holding its volumes as a JSON document. The
+/// playbook declares the volumes of a character with the annotation suffixed
+/// by its name, e.g. `amphitheatre.app/volumes.db`.
+pub const VOLUMES_ANNOTATION: &str = "amphitheatre.app/volumes";
+
+/// A volume mounted into the container of actor, e.g.
+/// `{"name": "data", "mount_path": "/var/lib/postgresql/data", "pvc": {"size": "5Gi"}}`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Volume {
+    pub name: String,
+    pub mount_path: String,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(flatten)]
+    pub source: VolumeSource,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeSource {
+    /// A scratch directory sharing the lifetime of the pod.
+    EmptyDir {
+        /// e.g. `Memory`, the node's default medium if not set.
+        medium: Option<String>,
+        size_limit: Option<String>,
+    },
+    /// A PersistentVolumeClaim created for the actor, kept across the restarts
+    /// and deleted with the actor.
+    Pvc {
+        /// e.g. `5Gi`.
+        size: String,
+        /// The default storage class of the cluster if not set.
+        storage_class: Option<String>,
+        /// `ReadWriteOnce` if not set.
+        access_mode: Option<String>,
+    },
+    /// A ConfigMap in the namespace of actor.
+    ConfigMap { name: String },
+    /// A Secret in the namespace of actor, it must be owned by the playbook, or
+    /// labelled with `amphitheatre.app/playbook` for it, so the Secrets of
+    /// Amphitheatre itself, e.g. the registry credentials, can't be mounted.
+    Secret { name: String },
+}
 
 pub async fn exists(client: &Client, actor: &Actor) -> Result<bool> {
     let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
@@ -77,3 +129,290 @@ fn new(actor: &Actor) -> Result<PersistentVolumeClaim> {
         ..Default::default()
     })
 }
+
+/// Returns the volumes declared for the actor.
+pub fn volumes(actor: &Actor) -> Result<Vec<Volume>> {
+    match actor.annotations().get(VOLUMES_ANNOTATION) {
+        Some(content) => serde_json::from_str(content).map_err(Error::SerializationError),
+        None => Ok(vec![]),
+    }
+}
+
+/// Check the Secrets mounted by the actor volumes are allowed for its playbook.
+pub async fn check(client: &Client, actor: &Actor, volumes: &[Volume]) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<Secret> = Api::namespaced(client.clone(), namespace.as_str());
+    let playbook = actor.labels().get(PLAYBOOK_LABEL).cloned().unwrap_or_default();
+
+    for volume in volumes {
+        let VolumeSource::Secret { name } = &volume.source else {
+            continue;
+        };
+        let secret = api.get_opt(name).await.map_err(Error::KubeError)?;
+        if !secret.is_some_and(|secret| allowed(&secret, &playbook)) {
+            return Err(Error::VolumeSecretNotAllowed(name.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the Secret is owned by the playbook or labelled for it.
+fn allowed(secret: &Secret, playbook: &str) -> bool {
+    if playbook.is_empty() {
+        return false;
+    }
+    let owned = secret.owner_references().iter().any(|owner| owner.kind == "Playbook" && owner.name == playbook);
+
+    owned || secret.labels().get(PLAYBOOK_LABEL).is_some_and(|value| value == playbook)
+}
+
+/// Create the PersistentVolumeClaims of the actor volumes if they don't exist,
+/// the existing claims are expanded if their sizes are increased, the other
+/// fields of their specs are immutable.
+pub async fn apply(client: &Client, actor: &Actor, volumes: &[Volume]) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<PersistentVolumeClaim> = Api::namespaced(client.clone(), namespace.as_str());
+
+    for volume in volumes {
+        let Some(resource) = claim(actor, volume) else {
+            continue;
+        };
+        if let Some(existing) = api.get_opt(&resource.name_any()).await.map_err(Error::KubeError)? {
+            expand(&api, &existing, &resource).await?;
+            continue;
+        }
+
+        let pvc = api.create(&PostParams::default(), &resource).await.map_err(Error::KubeError)?;
+        info!("Created PersistentVolumeClaim {} for volume {}", pvc.name_any(), volume.name);
+    }
+
+    Ok(())
+}
+
+/// Expand the claims of the StatefulSet created from its claim templates, e.g.
+/// `data-db-0`, if the sizes of their volumes are increased.
+pub async fn expand_templates(client: &Client, actor: &Actor, statefulset: &str, volumes: &[Volume]) -> Result<()> {
+    let namespace = actor.namespace().ok_or_else(|| Error::MissingObjectKey(".metadata.namespace"))?;
+    let api: Api<PersistentVolumeClaim> = Api::namespaced(client.clone(), namespace.as_str());
+
+    let templates = claim_templates(volumes);
+    if templates.is_empty() {
+        return Ok(());
+    }
+    let claims = api.list(&ListParams::default()).await.map_err(Error::KubeError)?.items;
+    for template in &templates {
+        let created = claims.iter().filter(|claim| created_from(&claim.name_any(), &template.name_any(), statefulset));
+        for existing in created {
+            expand(&api, existing, template).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the claim is created from the claim template of the StatefulSet,
+/// they are named as `{template}-{statefulset}-{ordinal}`.
+pub(crate) fn created_from(claim: &str, template: &str, statefulset: &str) -> bool {
+    let ordinal = claim.strip_prefix(template).and_then(|rest| rest.strip_prefix('-'));
+    let ordinal = ordinal.and_then(|rest| rest.strip_prefix(statefulset)).and_then(|rest| rest.strip_prefix('-'));
+    ordinal.is_some_and(|ordinal| !ordinal.is_empty() && ordinal.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Expand the existing claim to the size of the desired one if it's larger,
+/// the claims can't be shrunk.
+async fn expand(
+    api: &Api<PersistentVolumeClaim>,
+    existing: &PersistentVolumeClaim,
+    desired: &PersistentVolumeClaim,
+) -> Result<()> {
+    let (Some(current), Some(size)) = (storage(existing), storage(desired)) else {
+        return Ok(());
+    };
+    if metrics::quantity(&size.0) <= metrics::quantity(&current.0) {
+        return Ok(());
+    }
+
+    let patch = json!({ "spec": { "resources": { "requests": { "storage": size } } } });
+    let name = existing.name_any();
+    api.patch(&name, &PatchParams::default(), &Patch::Merge(&patch)).await.map_err(Error::KubeError)?;
+    info!("Expanded PersistentVolumeClaim {} from {} to {}", name, current.0, size.0);
+
+    Ok(())
+}
+
+#[inline]
+fn storage(claim: &PersistentVolumeClaim) -> Option<&Quantity> {
+    let requests = claim.spec.as_ref()?.resources.as_ref()?.requests.as_ref()?;
+    requests.get("storage")
+}
+
+/// Build the PersistentVolumeClaim of the volume, None if it's not a PVC volume.
+fn claim(actor: &Actor, volume: &Volume) -> Option<PersistentVolumeClaim> {
+    let VolumeSource::Pvc { size, storage_class, access_mode } = &volume.source else {
+        return None;
+    };
+    let labels = BTreeMap::from([
+        ("amphitheatre.app/character".into(), actor.name_any()),
+        ("app.kubernetes.io/managed-by".into(), "Amphitheatre".into()),
+    ]);
+
+    Some(PersistentVolumeClaim {
+        metadata: ObjectMeta {
+            name: Some(claim_name(actor, volume)),
+            owner_references: actor.controller_owner_ref(&()).map(|owner| vec![owner]),
+            labels: Some(labels),
+            ..Default::default()
+        },
+        spec: Some(claim_spec(size, storage_class, access_mode)),
+        ..Default::default()
+    })
+}
+
+/// Build the volume claim templates of the PVC volumes for a StatefulSet,
+/// they are named after the volumes so the mounts refer to them as they are.
+pub fn claim_templates(volumes: &[Volume]) -> Vec<PersistentVolumeClaim> {
+    volumes
+        .iter()
+        .filter_map(|volume| match &volume.source {
+            VolumeSource::Pvc { size, storage_class, access_mode } => Some(PersistentVolumeClaim {
+                metadata: ObjectMeta { name: Some(volume.name.clone()), ..Default::default() },
+                spec: Some(claim_spec(size, storage_class, access_mode)),
+                ..Default::default()
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn claim_spec(size: &str, storage_class: &Option<String>, access_mode: &Option<String>) -> PersistentVolumeClaimSpec {
+    PersistentVolumeClaimSpec {
+        access_modes: Some(vec![access_mode.clone().unwrap_or_else(|| "ReadWriteOnce".into())]),
+        resources: Some(VolumeResourceRequirements {
+            requests: Some(BTreeMap::from([("storage".into(), Quantity(size.into()))])),
+            ..Default::default()
+        }),
+        storage_class_name: storage_class.clone(),
+        volume_mode: Some("Filesystem".into()),
+        ..Default::default()
+    }
+}
+
+#[inline]
+fn claim_name(actor: &Actor, volume: &Volume) -> String {
+    naming::name(&[&actor.name_any(), &volume.name])
+}
+
+/// Build the pod volumes and the container mounts of the actor volumes.
+pub fn mounts(actor: &Actor, volumes: &[Volume]) -> (Vec<PodVolume>, Vec<VolumeMount>) {
+    let mut pod_volumes = vec![];
+    let mut mounts = vec![];
+
+    for volume in volumes {
+        let mut pod_volume = PodVolume { name: volume.name.clone(), ..Default::default() };
+        match &volume.source {
+            VolumeSource::EmptyDir { medium, size_limit } => {
+                pod_volume.empty_dir =
+                    Some(EmptyDirVolumeSource { medium: medium.clone(), size_limit: size_limit.clone().map(Quantity) });
+            }
+            VolumeSource::Pvc { .. } => {
+                pod_volume.persistent_volume_claim = Some(PersistentVolumeClaimVolumeSource {
+                    claim_name: claim_name(actor, volume),
+                    read_only: Some(volume.read_only),
+                });
+            }
+            VolumeSource::ConfigMap { name } => {
+                pod_volume.config_map = Some(ConfigMapVolumeSource { name: Some(name.clone()), ..Default::default() });
+            }
+            VolumeSource::Secret { name } => {
+                pod_volume.secret = Some(SecretVolumeSource { secret_name: Some(name.clone()), ..Default::default() });
+            }
+        }
+        pod_volumes.push(pod_volume);
+        mounts.push(VolumeMount {
+            name: volume.name.clone(),
+            mount_path: volume.mount_path.clone(),
+            read_only: Some(volume.read_only),
+            ..Default::default()
+        });
+    }
+
+    (pod_volumes, mounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use amp_common::resource::ActorSpec;
+
+    fn actor(volumes: &str) -> Actor {
+        let mut actor = Actor::new("db", ActorSpec::default());
+        actor.annotations_mut().insert(VOLUMES_ANNOTATION.into(), volumes.into());
+        actor
+    }
+
+    #[test]
+    fn test_parse_volumes() {
+        let actor = actor(
+            r#"[{"name": "data", "mount_path": "/data", "pvc": {"size": "5Gi", "storage_class": "fast"}},
+                {"name": "tmp", "mount_path": "/tmp", "empty_dir": {"medium": "Memory"}},
+                {"name": "conf", "mount_path": "/etc/app", "read_only": true, "config_map": {"name": "app"}}]"#,
+        );
+        let volumes = volumes(&actor).unwrap();
+
+        assert_eq!(volumes.len(), 3);
+        assert_eq!(
+            volumes[0].source,
+            VolumeSource::Pvc { size: "5Gi".into(), storage_class: Some("fast".into()), access_mode: None }
+        );
+        assert_eq!(volumes[1].source, VolumeSource::EmptyDir { medium: Some("Memory".into()), size_limit: None });
+        assert!(volumes[2].read_only);
+        assert!(super::volumes(&Actor::new("web", ActorSpec::default())).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mounts_and_claims() {
+        let actor = actor(
+            r#"[{"name": "data", "mount_path": "/data", "pvc": {"size": "5Gi"}},
+                {"name": "keys", "mount_path": "/keys", "secret": {"name": "keys"}}]"#,
+        );
+        let volumes = volumes(&actor).unwrap();
+        let (pod_volumes, mounts) = mounts(&actor, &volumes);
+
+        assert_eq!(pod_volumes[0].persistent_volume_claim.as_ref().unwrap().claim_name, "db-data");
+        assert_eq!(pod_volumes[1].secret.as_ref().unwrap().secret_name.as_deref(), Some("keys"));
+        assert_eq!(mounts[0].mount_path, "/data");
+
+        let pvc = claim(&actor, &volumes[0]).unwrap();
+        assert_eq!(pvc.name_any(), "db-data");
+        assert_eq!(pvc.spec.unwrap().access_modes, Some(vec!["ReadWriteOnce".to_string()]));
+        assert!(claim(&actor, &volumes[1]).is_none());
+
+        let templates = claim_templates(&volumes);
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].name_any(), "data");
+    }
+
+    #[test]
+    fn test_allowed_secrets() {
+        let mut secret = Secret::default();
+        assert!(!allowed(&secret, "demo"));
+
+        secret.labels_mut().insert(PLAYBOOK_LABEL.into(), "other".into());
+        assert!(!allowed(&secret, "demo"));
+
+        secret.labels_mut().insert(PLAYBOOK_LABEL.into(), "demo".into());
+        assert!(allowed(&secret, "demo"));
+        assert!(!allowed(&secret, ""));
+    }
+
+    #[test]
+    fn test_created_from() {
+        assert!(created_from("data-db-0", "data", "db"));
+        assert!(created_from("data-db-12", "data", "db"));
+        assert!(!created_from("data-db-", "data", "db"));
+        assert!(!created_from("data-db-cache-0", "data", "db"));
+        assert!(!created_from("logs-db-0", "data", "db"));
+    }
+}
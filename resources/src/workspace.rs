@@ -0,0 +1,170 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+
+use amp_common::resource::Playbook;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{Api, Client, ResourceExt};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use super::error::{Error, Result};
+
+/// The label of playbook indicating which workspace it belongs to.
+pub const WORKSPACE_LABEL: &str = "amphitheatre.app/workspace";
+
+/// The workspace for playbooks without the workspace label.
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+/// The name of the ConfigMap holding the workspace policies, one TOML document
+/// per workspace keyed by the workspace name.
+const POLICIES_CONFIG_MAP: &str = "amp-workspace-policies";
+
+/// The lifecycle and budget policy applied to all playbooks in a workspace, all durations are in seconds.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct WorkspacePolicy {
+    /// The TTL for playbooks without their own `ttl`.
+    pub default_ttl: Option<i64>,
+    /// The upper bound of the playbook's own `ttl`.
+    pub max_ttl: Option<i64>,
+    /// Scale the actors to zero after the playbook has been idle for this long.
+    pub idle_timeout: Option<i64>,
+    /// The upper bound of the playbook's own idle timeout.
+    pub max_idle_timeout: Option<i64>,
+    /// Archive the playbook after it has been idle for this long.
+    pub archive_after: Option<i64>,
+    /// The monthly budget of build minutes per actor.
+    pub build_minutes_budget: Option<f64>,
+    /// The monthly budget of runtime pod-hours per actor.
+    pub runtime_hours_budget: Option<f64>,
+    /// Pause the new builds of the actors exceeding their build minutes budget.
+    #[serde(default)]
+    pub pause_builds_over_budget: bool,
+}
+
+impl WorkspacePolicy {
+    /// Returns the effective TTL with the playbook's own value bounded by the workspace maximum.
+    pub fn ttl(&self, requested: Option<i64>) -> Option<i64> {
+        bound(requested.or(self.default_ttl), self.max_ttl)
+    }
+
+    /// Returns the effective idle timeout with the playbook's own value bounded by the workspace maximum.
+    pub fn idle_timeout(&self, requested: Option<i64>) -> Option<i64> {
+        bound(requested.or(self.idle_timeout), self.max_idle_timeout)
+    }
+}
+
+#[inline]
+fn bound(value: Option<i64>, max: Option<i64>) -> Option<i64> {
+    match (value, max) {
+        (Some(value), Some(max)) => Some(value.min(max)),
+        (None, Some(max)) => Some(max),
+        (value, None) => value,
+    }
+}
+
+/// Returns the workspace name of the playbook.
+pub fn of(playbook: &Playbook) -> String {
+    playbook.labels().get(WORKSPACE_LABEL).cloned().unwrap_or_else(|| DEFAULT_WORKSPACE.to_string())
+}
+
+/// Load all the workspace policies from the ConfigMap in the given namespace.
+pub async fn load(client: &Client, namespace: &str) -> Result<HashMap<String, WorkspacePolicy>> {
+    let api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+    let Some(config_map) = api.get_opt(POLICIES_CONFIG_MAP).await.map_err(Error::KubeError)? else {
+        debug!("The {} was not found, no workspace policies applied.", POLICIES_CONFIG_MAP);
+        return Ok(HashMap::new());
+    };
+
+    Ok(parse(config_map.data.unwrap_or_default()))
+}
+
+/// Parse the policies of the workspaces, a malformed one is skipped, so it
+/// doesn't take the policies of the other workspaces down with it.
+fn parse(data: BTreeMap<String, String>) -> HashMap<String, WorkspacePolicy> {
+    let mut policies = HashMap::new();
+    for (workspace, content) in data {
+        match toml::from_str::<WorkspacePolicy>(&content) {
+            Ok(policy) => {
+                policies.insert(workspace, policy);
+            }
+            Err(err) => warn!("Skip the malformed policy of workspace {}: {}", workspace, err),
+        }
+    }
+
+    policies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ttl_defaults_to_workspace() {
+        let policy = WorkspacePolicy { default_ttl: Some(3600), ..Default::default() };
+
+        assert_eq!(policy.ttl(None), Some(3600));
+        assert_eq!(policy.ttl(Some(60)), Some(60));
+    }
+
+    #[test]
+    fn test_ttl_bounded_by_workspace_maximum() {
+        let policy = WorkspacePolicy { default_ttl: Some(3600), max_ttl: Some(7200), ..Default::default() };
+
+        assert_eq!(policy.ttl(Some(86400)), Some(7200));
+        assert_eq!(policy.ttl(Some(60)), Some(60));
+        assert_eq!(WorkspacePolicy { max_ttl: Some(7200), ..Default::default() }.ttl(None), Some(7200));
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let policy = WorkspacePolicy { idle_timeout: Some(600), max_idle_timeout: Some(1800), ..Default::default() };
+
+        assert_eq!(policy.idle_timeout(None), Some(600));
+        assert_eq!(policy.idle_timeout(Some(3600)), Some(1800));
+        assert_eq!(WorkspacePolicy::default().idle_timeout(None), None);
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let policy: WorkspacePolicy = toml::from_str("default_ttl = 3600\narchive_after = 86400").unwrap();
+
+        assert_eq!(policy.default_ttl, Some(3600));
+        assert_eq!(policy.archive_after, Some(86400));
+        assert_eq!(policy.max_ttl, None);
+    }
+
+    #[test]
+    fn test_parse_budgets() {
+        let policy: WorkspacePolicy =
+            toml::from_str("build_minutes_budget = 600.0\npause_builds_over_budget = true").unwrap();
+
+        assert_eq!(policy.build_minutes_budget, Some(600.0));
+        assert_eq!(policy.runtime_hours_budget, None);
+        assert!(policy.pause_builds_over_budget);
+    }
+
+    #[test]
+    fn test_parse_skips_malformed_policy() {
+        let data = BTreeMap::from([
+            ("web".to_string(), "default_ttl = 3600".to_string()),
+            ("data".to_string(), "default_ttl = \"forever\"".to_string()),
+        ]);
+        let policies = parse(data);
+
+        assert_eq!(policies.len(), 1);
+        assert_eq!(policies["web"].default_ttl, Some(3600));
+    }
+}
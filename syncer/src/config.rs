@@ -43,4 +43,19 @@ pub struct Config {
     // Exit after sync once (Overwrite).
     #[clap(long, action = clap::ArgAction::Set, default_value = "false", env = "AMP_ONCE")]
     pub once: bool,
+    /// Report the synced events for the reload hooks, it's set when the
+    /// syncer runs next to the application container of the live actor.
+    #[clap(long, action = clap::ArgAction::Set, default_value = "false", env = "AMP_NOTIFY")]
+    pub notify: bool,
+    /// Download the uploaded source from the URL, unpack it into the workspace and
+    /// exit, it's set for the build pods of the actors built from the uploaded sources.
+    #[clap(long, env = "AMP_UPLOAD")]
+    pub upload: Option<String>,
+    /// The directory persisting the manifest of the synced source, outside of
+    /// the workspace, a directory in the temporary one if not set.
+    #[clap(long, env = "AMP_STATE_DIR")]
+    pub state_dir: Option<String>,
+    /// The name of the pod, every pod of the actor consumes all the events.
+    #[clap(long, env = "HOSTNAME", default_value = "")]
+    pub hostname: String,
 }
@@ -12,15 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use amp_common::sync::EventKinds::*;
 use amp_common::sync::Synchronization;
-use async_nats::jetstream::consumer::{pull, PullConsumer};
+use async_nats::jetstream::consumer::{pull, DeliverPolicy, PullConsumer};
 use async_nats::jetstream::{self, stream};
 use clap::Parser;
 use config::Config;
 use futures::StreamExt;
+use manifest::Manifest;
+use tokio::sync::RwLock;
 use tracing::metadata::LevelFilter;
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::layer::SubscriberExt;
@@ -29,6 +33,11 @@ use tracing_subscriber::EnvFilter;
 
 mod config;
 mod handle;
+mod manifest;
+
+/// The key-value bucket holding the stream sequence of the last full sync of
+/// each actor, keyed by its subject, it's written by the API server.
+const SYNCS_BUCKET: &str = "amp-syncs";
 
 #[tokio::main]
 async fn main() -> Result<(), async_nats::Error> {
@@ -50,8 +59,22 @@ async fn main() -> Result<(), async_nats::Error> {
     // initialize some variables
     let workspace = Path::new(&config.workspace);
 
+    // The uploaded source is unpacked once, there are no events to consume.
+    if let Some(url) = &config.upload {
+        return unpack(url, workspace).await;
+    }
+
     debug!("Connecting to NATS server: {}", config.nats_url);
-    let consumer = connect(&config).await?;
+    let client = async_nats::connect(&config.nats_url).await?;
+    let consumer = connect(client.clone(), &config).await?;
+    let notifier = client.clone();
+
+    // The manifest of the source applied by the synced events, it's the
+    // baseline for reporting the differences of the workspace.
+    let state = config.state_dir.as_ref().map(PathBuf::from);
+    let state = state.unwrap_or_else(|| std::env::temp_dir().join("amp-syncer"));
+    let source: Arc<RwLock<Option<Manifest>>> = Arc::new(RwLock::new(manifest::load(&state)));
+    tokio::spawn(serve_diff(client, config.clone(), PathBuf::from(&config.workspace), source.clone()));
 
     // Consume messages from the consumer
     let mut messages = consumer.messages().await?;
@@ -84,11 +107,30 @@ async fn main() -> Result<(), async_nats::Error> {
             error!("Failed to handle message: {}", err);
             continue;
         }
+
+        // Record the manifest of the source for reporting the differences later,
+        // the build workspaces synced once are left untouched.
+        if !config.once {
+            let mut baseline = source.write().await;
+            let manifest = baseline.get_or_insert_with(Manifest::new);
+            if let Err(err) = manifest::apply(manifest, &req).and_then(|_| manifest::save(&state, manifest)) {
+                error!("Failed to record the manifest of source: {}", err);
+            }
+        }
+
         // Acknowledge the message if we handled it successfully.
         if let Err(err) = message.ack().await {
             error!("Failed to acknowledge message: {:?}", err);
         }
 
+        // Report the synced event, the reload hook of actor is run in this pod.
+        if config.notify {
+            let subject = format!("amp.synced.{}.{}", config.playbook, config.actor);
+            if let Err(err) = notifier.publish(subject, config.hostname.clone().into()).await {
+                error!("Failed to report the synced event: {:?}", err);
+            }
+        }
+
         // If we're in once mode, exit after overwrite.
         if config.once && req.kind == Overwrite {
             info!("Finished syncing, exiting...");
@@ -99,15 +141,26 @@ async fn main() -> Result<(), async_nats::Error> {
     Ok(())
 }
 
-/// Connect to NATS server and return a consumer.
-async fn connect(config: &Config) -> Result<PullConsumer, async_nats::Error> {
-    // Connect to NATS server and create a JetStream instance.
-    let client = async_nats::connect(&config.nats_url).await?;
+/// Create a JetStream instance with the NATS client and return a consumer.
+async fn connect(client: async_nats::Client, config: &Config) -> Result<PullConsumer, async_nats::Error> {
     let jetstream = jetstream::new(client);
 
-    // get or create a stream and a consumer
+    // get or create a stream and a consumer, the syncers next to the
+    // application containers consume the events of their own pods from the
+    // last full sync, as their volumes are seeded with the files of image,
+    // and their consumers are removed once the pods are gone.
     let subject = format!("{}.{}", config.playbook, config.actor);
-    let name = "amp-syncer";
+    let name = match config.notify {
+        true => format!("amp-syncer-{}", config.hostname),
+        false => "amp-syncer".to_string(),
+    };
+    let (deliver_policy, inactive_threshold) = match config.notify {
+        true => match last_overwrite(&jetstream, &subject).await {
+            Some(start_sequence) => (DeliverPolicy::ByStartSequence { start_sequence }, Duration::from_secs(3600)),
+            None => (DeliverPolicy::All, Duration::from_secs(3600)),
+        },
+        false => (DeliverPolicy::All, Duration::ZERO),
+    };
     let consumer = jetstream
         // First, on the `JetStream` instance, use method to create Stream.
         .get_or_create_stream(stream::Config {
@@ -118,10 +171,12 @@ async fn connect(config: &Config) -> Result<PullConsumer, async_nats::Error> {
         .await?
         // Then, on that `Stream` use method to create Consumer and bind to it.
         .get_or_create_consumer(
-            name,
+            &name,
             pull::Config {
-                durable_name: Some(name.to_string()),
+                durable_name: Some(name.clone()),
                 filter_subject: subject.clone(),
+                deliver_policy,
+                inactive_threshold,
                 ..Default::default()
             },
         )
@@ -130,3 +185,62 @@ async fn connect(config: &Config) -> Result<PullConsumer, async_nats::Error> {
 
     Ok(consumer)
 }
+
+/// Returns the stream sequence of the last full sync (overwrite) of the
+/// subject, recorded by the API server when it's published.
+async fn last_overwrite(jetstream: &jetstream::Context, subject: &str) -> Option<u64> {
+    let store = jetstream.get_key_value(SYNCS_BUCKET).await.ok()?;
+    match store.get(subject).await {
+        Ok(value) => value.and_then(|value| String::from_utf8_lossy(&value).parse().ok()),
+        Err(err) => {
+            warn!("Failed to read the last full sync of {}: {}", subject, err);
+            None
+        }
+    }
+}
+
+/// Download the uploaded source from the artifact store and unpack it into the workspace.
+async fn unpack(url: &str, workspace: &Path) -> Result<(), async_nats::Error> {
+    let response = reqwest::get(url).await?.error_for_status()?;
+    let payload = response.bytes().await?;
+
+    tar::Archive::new(payload.as_ref()).unpack(workspace)?;
+    info!("Unpacked the uploaded source {} into {}", url, workspace.display());
+
+    Ok(())
+}
+
+/// Reply the differences between the workspace and the source of the last
+/// overwrite event to the requests on `amp.diff.{playbook}.{actor}`.
+async fn serve_diff(
+    client: async_nats::Client,
+    config: Config,
+    workspace: PathBuf,
+    source: Arc<RwLock<Option<Manifest>>>,
+) -> Result<(), async_nats::Error> {
+    let subject = format!("amp.diff.{}.{}", config.playbook, config.actor);
+    let mut requests = client.subscribe(subject.clone()).await?;
+    info!("Serving the workspace differences on subject: {}", subject);
+
+    while let Some(request) = requests.next().await {
+        let Some(reply) = request.reply else {
+            continue;
+        };
+
+        // Reply nothing if no source has been received, the requester will time out.
+        let Some(source) = source.read().await.clone() else {
+            warn!("Received diff request, but no source has been received yet");
+            continue;
+        };
+
+        match manifest::scan(&workspace) {
+            Ok(current) => {
+                let payload = serde_json::to_vec(&manifest::diff(&source, &current))?;
+                client.publish(reply, payload.into()).await?;
+            }
+            Err(err) => error!("Failed to scan the workspace: {}", err),
+        }
+    }
+
+    Ok(())
+}
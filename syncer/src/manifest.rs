@@ -0,0 +1,252 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Result};
+use std::path::Path;
+
+use amp_common::sync::{self, EventKinds, Synchronization};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tar::Archive;
+
+/// The content digests of files, keyed by the path relative to the workspace.
+pub type Manifest = BTreeMap<String, String>;
+
+/// The file in the state directory persisting the manifest of the synced
+/// source, so it survives the restarts of syncer. It's kept outside of the
+/// workspace, so it's never seen by the application or the scans.
+const MANIFEST_FILENAME: &str = "amp-manifest.json";
+
+/// The difference between the synced workspace and its source.
+#[derive(Debug, Default, Serialize)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+/// Build the manifest of the files in the tarball.
+pub fn from_archive(payload: &[u8]) -> Result<Manifest> {
+    let mut manifest = Manifest::new();
+    let mut archive = Archive::new(payload);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = relative(&entry.path()?);
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+        manifest.insert(path, digest(&content));
+    }
+
+    Ok(manifest)
+}
+
+/// Apply the handled synchronization to the manifest of the source, so the
+/// baseline follows every synced change, not only the last overwrite.
+pub fn apply(manifest: &mut Manifest, req: &Synchronization) -> Result<()> {
+    match req.kind {
+        EventKinds::Overwrite => {
+            if let Some(payload) = &req.payload {
+                *manifest = from_archive(payload)?;
+            }
+        }
+        EventKinds::Modify => {
+            if let Some(payload) = &req.payload {
+                manifest.extend(from_archive(payload)?);
+            }
+        }
+        EventKinds::Create => {
+            for path in &req.paths {
+                if let sync::Path::File(file) = path {
+                    manifest.entry(relative(Path::new(file))).or_insert_with(|| digest(&[]));
+                }
+            }
+        }
+        EventKinds::Remove => {
+            for path in &req.paths {
+                match path {
+                    sync::Path::File(file) => {
+                        manifest.remove(&relative(Path::new(file)));
+                    }
+                    sync::Path::Directory(dir) => {
+                        let prefix = format!("{}/", relative(Path::new(dir)));
+                        manifest.retain(|path, _| !path.starts_with(&prefix));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Load the persisted manifest of the source from the state directory, if any.
+pub fn load(state: &Path) -> Option<Manifest> {
+    let content = fs::read(state.join(MANIFEST_FILENAME)).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// Persist the manifest of the source into the state directory.
+pub fn save(state: &Path, manifest: &Manifest) -> Result<()> {
+    fs::create_dir_all(state)?;
+    fs::write(state.join(MANIFEST_FILENAME), serde_json::to_vec(manifest)?)
+}
+
+/// Build the manifest of the files in the workspace.
+pub fn scan(workspace: &Path) -> Result<Manifest> {
+    let mut manifest = Manifest::new();
+    walk(workspace, workspace, &mut manifest)?;
+
+    Ok(manifest)
+}
+
+/// Walk the directory without following the symbolic links, they may point
+/// outside of the workspace or form cycles.
+fn walk(root: &Path, dir: &Path, manifest: &mut Manifest) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let (path, file_type) = (entry.path(), entry.file_type()?);
+        if file_type.is_dir() {
+            walk(root, &path, manifest)?;
+        } else if file_type.is_file() {
+            manifest.insert(relative(path.strip_prefix(root).unwrap_or(&path)), digest(&fs::read(&path)?));
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the current manifest with the source one.
+pub fn diff(source: &Manifest, current: &Manifest) -> Diff {
+    let mut diff = Diff::default();
+
+    for (path, digest) in current {
+        match source.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(expected) if expected != digest => diff.modified.push(path.clone()),
+            _ => {}
+        }
+    }
+    diff.deleted = source.keys().filter(|path| !current.contains_key(*path)).cloned().collect();
+
+    diff
+}
+
+#[inline]
+fn digest(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+#[inline]
+fn relative(path: &Path) -> String {
+    path.to_string_lossy().trim_start_matches("./").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn workspace(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("amp-syncer-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&path).ok();
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_diff() {
+        let source = Manifest::from([("a".into(), "1".into()), ("b".into(), "2".into())]);
+        let current = Manifest::from([("a".into(), "1".into()), ("b".into(), "3".into()), ("c".into(), "4".into())]);
+
+        let diff = diff(&source, &current);
+        assert_eq!(diff.added, vec!["c".to_string()]);
+        assert_eq!(diff.modified, vec!["b".to_string()]);
+        assert!(diff.deleted.is_empty());
+    }
+
+    #[test]
+    fn test_apply_follows_synced_changes() {
+        let mut manifest = Manifest::new();
+        let mut req = Synchronization {
+            kind: EventKinds::Overwrite,
+            paths: vec![],
+            attributes: None,
+            payload: Some(archive(&[("./src/main.rs", "fn main() {}"), ("README.md", "# test")])),
+        };
+        apply(&mut manifest, &req).unwrap();
+        assert_eq!(manifest.keys().collect::<Vec<_>>(), vec!["README.md", "src/main.rs"]);
+
+        req.kind = EventKinds::Modify;
+        req.payload = Some(archive(&[("README.md", "# changed")]));
+        apply(&mut manifest, &req).unwrap();
+        assert_eq!(manifest["README.md"], digest(b"# changed"));
+
+        req.kind = EventKinds::Remove;
+        req.paths = vec![sync::Path::Directory("src".into())];
+        apply(&mut manifest, &req).unwrap();
+        assert_eq!(manifest.keys().collect::<Vec<_>>(), vec!["README.md"]);
+    }
+
+    #[test]
+    fn test_save_outside_workspace() {
+        let workspace = workspace("scan");
+        let state = workspace.with_extension("state");
+        fs::write(workspace.join("a.txt"), "a").unwrap();
+        save(&state, &Manifest::from([("a.txt".into(), digest(b"a"))])).unwrap();
+
+        let manifest = scan(&workspace).unwrap();
+        assert_eq!(manifest, Manifest::from([("a.txt".into(), digest(b"a"))]));
+        assert_eq!(load(&state), Some(manifest));
+        assert_eq!(load(&workspace), None);
+
+        fs::remove_dir_all(&workspace).ok();
+        fs::remove_dir_all(&state).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_does_not_follow_symlinks() {
+        let workspace = workspace("symlink");
+        fs::create_dir_all(workspace.join("dir")).unwrap();
+        fs::write(workspace.join("dir/a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(&workspace, workspace.join("dir/loop")).unwrap();
+        std::os::unix::fs::symlink("/etc/hostname", workspace.join("outside")).unwrap();
+
+        let manifest = scan(&workspace).unwrap();
+        assert_eq!(manifest.keys().collect::<Vec<_>>(), vec!["dir/a.txt"]);
+
+        fs::remove_dir_all(&workspace).ok();
+    }
+}
@@ -18,15 +18,21 @@ use super::DeployingState;
 use crate::errors::{Error, Result};
 use crate::{Context, Intent, State, Task};
 
-use amp_builder::{BuildDirector, KanikoBuilder, KpackBuilder};
+use amp_builder::{BuildDirector, KanikoBuilder, KpackBuilder, LifecycleBuilder, NixpacksBuilder};
 use amp_common::resource::{Actor, ActorState};
 use amp_common::schema::BuildMethod;
 
-use amp_resources::actor;
+use amp_resources::build::{self as resources, Failure, Status};
+use amp_resources::detection::{self, Decision};
+use amp_resources::kpack::reference;
+use amp_resources::{actor, base, image, sbom, signing, upload, usage};
 use async_trait::async_trait;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
 use kube::runtime::controller::Action;
-use kube::ResourceExt;
-use tracing::{error, info, trace};
+use kube::runtime::events::{Event, EventType, Recorder, Reporter};
+use kube::{Resource, ResourceExt};
+use tracing::{error, info, trace, warn};
 
 pub struct BuildingState;
 
@@ -68,15 +74,79 @@ impl Task<Actor> for BuildTask {
         let actor = &ctx.object;
         let build = actor.spec.character.build.clone().unwrap_or_default();
 
-        // Generate `Builder` based on the build method
-        let builder = match build.method() {
-            BuildMethod::Dockerfile => {
+        // The new builds are paused until the budget is raised or reset next month.
+        if usage::builds_paused(actor) {
+            info!("The builds of actor {} are paused, as its budget is exceeded", actor.name_any());
+            usage::pause(&ctx.k8s, actor).await.map_err(Error::ResourceError)?;
+            return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(10 * 60)))));
+        }
+
+        // The failed build is not retried until the spec changes.
+        if resources::failed(actor) {
+            info!("The build of actor {} failed, wait for its spec to change", actor.name_any());
+            return Ok(Some(Intent::Action(Action::await_change())));
+        }
+
+        // Detect the builder from the repository in auto mode, once per generation.
+        let decision = match detection::auto(actor) {
+            true => Some(self.detect(ctx).await?),
+            false => None,
+        };
+
+        // The build pods are placed by the defaults overridden by the actor.
+        let resources = resources::of(actor, &ctx.build).map_err(Error::ResourceError)?;
+        let timeout = resources.timeout;
+
+        // Wait for a build slot, the builds across all the playbooks are capped.
+        let key = Self::key(actor);
+        let running = match ctx.build_queue.limited() {
+            true => resources::running(&ctx.k8s).await.map_err(Error::ResourceError)?,
+            false => vec![],
+        };
+        let position = ctx.build_queue.admit(&key, resources.priority.unwrap_or_default(), &running);
+        self.report(ctx, position).await?;
+        if position.is_some() {
+            return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(10)))));
+        }
+
+        // Generate `Builder` and its name based on the detected or the build method
+        let (name, builder) = match (decision, build.method()) {
+            _ if detection::nixpacks(actor) => {
+                info!("Build the image with Nixpacks");
+                let builder = NixpacksBuilder::new(ctx.k8s.clone(), actor.clone(), resources);
+                ("nixpacks", BuildDirector::new(Box::new(builder)))
+            }
+            (Some(Decision::Buildpacks), _) => {
+                info!("Found a Buildpacks compatible project, build it with the Buildpacks lifecycle");
+                let builder = LifecycleBuilder::new(ctx.k8s.clone(), actor.clone(), resources);
+                ("lifecycle", BuildDirector::new(Box::new(builder)))
+            }
+            (Some(Decision::Dockerfile), _) | (_, BuildMethod::Dockerfile) => {
+                // Wait for the shared base image of the monorepo to be built first.
+                if let Some(base) = base::of(actor).map_err(Error::ResourceError)? {
+                    base::build(&ctx.k8s, actor, &base, &resources).await.map_err(Error::ResourceError)?;
+                    if !base::completed(&ctx.k8s, actor, &base).await.map_err(Error::ResourceError)? {
+                        info!("Base image {} is not built yet, wait for it to finish", base.image);
+                        return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(5)))));
+                    }
+                }
+
                 info!("Found dockerfile, build it with Kaniko");
-                BuildDirector::new(Box::new(KanikoBuilder::new(ctx.k8s.clone(), actor.clone())))
+                let builder = KanikoBuilder::new(ctx.k8s.clone(), actor.clone(), resources);
+                ("kaniko", BuildDirector::new(Box::new(builder)))
             }
-            BuildMethod::Buildpacks => {
+            // kpack fetches the sources by itself, which can't reach the uploaded ones.
+            (_, BuildMethod::Buildpacks) if upload::of(&actor.spec).is_some() => {
+                info!("Build the uploaded source with the Buildpacks lifecycle");
+                let builder = LifecycleBuilder::new(ctx.k8s.clone(), actor.clone(), resources);
+                ("lifecycle", BuildDirector::new(Box::new(builder)))
+            }
+            (_, BuildMethod::Buildpacks) => {
                 info!("Build the image with Cloud Native Buildpacks (kpack)");
-                BuildDirector::new(Box::new(KpackBuilder::new(ctx.k8s.clone(), actor.clone(), ctx.credentials.clone())))
+                let builder = reference::of(actor, ctx.kpack_builder.as_ref()).map_err(Error::ResourceError)?;
+                let credentials = ctx.credentials.clone();
+                let builder = KpackBuilder::new(ctx.k8s.clone(), actor.clone(), credentials, resources, builder);
+                ("kpack", BuildDirector::new(Box::new(builder)))
             }
         };
 
@@ -90,14 +160,181 @@ impl Task<Actor> for BuildTask {
 
         // Check if the build is completed and wait for it to finish.
         if !builder.completed().await.map_err(Error::BuildError)? {
+            if let Some(intent) = self.inspect(ctx, name, &key, timeout).await? {
+                return Ok(Some(intent));
+            }
+            self.snapshot(ctx, name).await;
             info!("Build job is not completed yet, wait for it to finish");
             return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(5)))));
         }
+        ctx.build_queue.release(&key);
+        ctx.images.invalidate(&actor.spec.image);
+
+        // Pin the built image by its digest, the tag may be overwritten by the later builds.
+        let digest = image::digest(&actor.spec.image, &*ctx.credentials.read().await).await;
+        let digest = digest.map_err(Error::ResourceError)?;
+        image::pin(&ctx.k8s, actor, &digest).await.map_err(Error::ResourceError)?;
+
+        // Meter the build minutes, it's metered once even if the signing requeues.
+        if let Err(err) = usage::record_build(&ctx.k8s, actor).await {
+            error!("Failed to record the build usage of actor {}: {}", actor.name_any(), err);
+        }
+
+        // Sign the built image by its digest, and wait for the signature to be pushed.
+        if let Some(mode) = signing::mode(actor).map_err(Error::ResourceError)? {
+            signing::sign(&ctx.k8s, actor, &mode, &digest).await.map_err(Error::ResourceError)?;
+            if !signing::completed(&ctx.k8s, actor).await.map_err(Error::ResourceError)? {
+                info!("Signing job is not completed yet, wait for it to finish");
+                return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(5)))));
+            }
+            signing::record(&ctx.k8s, actor, &digest).await.map_err(Error::ResourceError)?;
+        }
+
+        // Archive the build logs once the signing is done, as it requeues.
+        self.archive(ctx, name, Status::Succeeded, Some(&digest)).await;
+
+        // Generate the SBOM of the built image, it does not block the deployment.
+        if let Err(err) = self.generate_sbom(ctx).await {
+            error!("Failed to generate the SBOM of actor {}: {}", actor.name_any(), err);
+        }
 
         // Patch the status to running
         let condition = ActorState::running(true, "AutoRun", None);
         actor::patch_status(&ctx.k8s, &ctx.object, condition).await.map_err(Error::ResourceError)?;
 
+        // Keep the detected build method in the status of the running actor.
+        if let Some(decision) = decision {
+            detection::record(&ctx.k8s, actor, decision).await.map_err(Error::ResourceError)?;
+        }
+
         Ok(None)
     }
 }
+
+impl BuildTask {
+    /// Returns the key of actor in the build queue.
+    pub fn key(actor: &Actor) -> String {
+        format!("{}/{}", actor.namespace().unwrap_or_default(), actor.name_any())
+    }
+
+    /// Returns the decision of the current generation of actor, or detect it
+    /// from the repository and record it in the status.
+    async fn detect(&self, ctx: &Context<Actor>) -> Result<Decision> {
+        let actor = &ctx.object;
+        if let Some(decision) = detection::decided(actor) {
+            return Ok(decision);
+        }
+
+        let credentials = ctx.credentials.read().await;
+        let decision = amp_resolver::detect(&credentials, &actor.spec).map_err(Error::ResolveError)?;
+        info!("Detected the build method {} of actor {}", decision.reason(), actor.name_any());
+        detection::record(&ctx.k8s, actor, decision).await.map_err(Error::ResourceError)?;
+
+        Ok(decision)
+    }
+
+    /// Kill the timed out build and mark it failed, or report the stuck build
+    /// pods until they make progress again.
+    async fn inspect(
+        &self,
+        ctx: &Context<Actor>,
+        builder: &str,
+        key: &str,
+        timeout: Option<i64>,
+    ) -> Result<Option<Intent<Actor>>> {
+        let actor = &ctx.object;
+        let conditions = actor.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+        let stuck = conditions.iter().find(|c| c.type_ == resources::BUILD_STUCK_CONDITION_TYPE && c.status == "True");
+
+        let Some(failure) = resources::inspect(&ctx.k8s, actor, timeout).await.map_err(Error::ResourceError)? else {
+            if let Some(stuck) = stuck {
+                let condition = Condition {
+                    status: "False".into(),
+                    reason: "Progressing".into(),
+                    last_transition_time: Time(Utc::now()),
+                    ..stuck.clone()
+                };
+                actor::upsert_condition(&ctx.k8s, actor, condition).await.map_err(Error::ResourceError)?;
+            }
+            return Ok(None);
+        };
+
+        if failure.fatal() {
+            warn!("Kill the build of actor {}: {}", actor.name_any(), failure.message());
+            self.archive(ctx, builder, Status::Failed, None).await;
+            resources::kill(&ctx.k8s, actor).await.map_err(Error::ResourceError)?;
+            ctx.build_queue.release(key);
+        } else if stuck.is_some_and(|c| c.reason == failure.reason()) {
+            return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(30)))));
+        }
+
+        let condition = failure.condition(actor.meta().generation);
+        actor::upsert_condition(&ctx.k8s, actor, condition).await.map_err(Error::ResourceError)?;
+        self.publish(ctx, &failure).await;
+
+        match failure.fatal() {
+            true => Ok(Some(Intent::Action(Action::await_change()))),
+            false => Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(30))))),
+        }
+    }
+
+    /// Archive the logs of the finished build, the failures are logged only.
+    async fn archive(&self, ctx: &Context<Actor>, builder: &str, status: Status, digest: Option<&str>) {
+        let archived = resources::archive(&ctx.k8s, &ctx.artifacts, &ctx.object, builder, status, digest).await;
+        if let Err(err) = archived {
+            error!("Failed to archive the build logs of actor {}: {}", ctx.object.name_any(), err);
+        }
+    }
+
+    /// Archive the logs of the running build, the failures are logged only.
+    async fn snapshot(&self, ctx: &Context<Actor>, builder: &str) {
+        if let Err(err) = resources::snapshot(&ctx.k8s, &ctx.artifacts, &ctx.object, builder).await {
+            error!("Failed to snapshot the build logs of actor {}: {}", ctx.object.name_any(), err);
+        }
+    }
+
+    /// Publish the failure as an event of actor, the failures are logged only.
+    async fn publish(&self, ctx: &Context<Actor>, failure: &Failure) {
+        let reporter = Reporter { controller: "amp-controllers".into(), instance: None };
+        let recorder = Recorder::new((*ctx.k8s).clone(), reporter, ctx.object.object_ref(&()));
+        let event = Event {
+            type_: EventType::Warning,
+            reason: format!("Build{}", failure.reason()),
+            note: Some(failure.message()),
+            action: "Build".into(),
+            secondary: None,
+        };
+        if let Err(err) = recorder.publish(event).await {
+            error!("Failed to publish the build event of actor {}: {}", ctx.object.name_any(), err);
+        }
+    }
+
+    /// Report the position of the build in the queue, and the admission of the queued build.
+    async fn report(&self, ctx: &Context<Actor>, position: Option<usize>) -> Result<()> {
+        if resources::position(&ctx.object) != position {
+            resources::place(&ctx.k8s, &ctx.object, position).await.map_err(Error::ResourceError)?;
+        }
+
+        let condition = resources::queued(position);
+        let conditions = ctx.object.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+        let changed = match conditions.iter().find(|c| c.type_ == condition.type_) {
+            Some(current) => current.status != condition.status || current.message != condition.message,
+            None => position.is_some(),
+        };
+        if changed {
+            actor::upsert_condition(&ctx.k8s, &ctx.object, condition).await.map_err(Error::ResourceError)?;
+        }
+
+        Ok(())
+    }
+
+    /// Generate the SBOM if it's enabled for the actor.
+    async fn generate_sbom(&self, ctx: &Context<Actor>) -> Result<(), amp_resources::error::Error> {
+        if let Some(format) = sbom::format(&ctx.object)? {
+            info!("Generate the {:?} SBOM for the image {}", format, ctx.object.spec.image);
+            sbom::generate(&ctx.k8s, &ctx.object, &format).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -12,10 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::BuildTask;
 use crate::errors::{Error, Result};
 use crate::{Context, Intent, State, Task};
 
 use amp_common::resource::Actor;
+use amp_resources::{actor, helm, namespace};
 
 use async_trait::async_trait;
 use k8s_openapi::api::core::v1::Namespace;
@@ -59,6 +61,9 @@ impl Task<Actor> for CleanupTask {
 
     // Execute the task logic for CleanupTask using shared data
     async fn execute(&self, ctx: &Context<Actor>) -> Result<Option<Intent<Actor>>> {
+        // Free the build slot of the deleted actor for the queued builds.
+        ctx.build_queue.release(&BuildTask::key(&ctx.object));
+
         self.cleanup(ctx, &ctx.object).await?;
         Ok(None)
     }
@@ -78,6 +83,16 @@ impl CleanupTask {
 
         info!("Delete Actor `{}`", actor.name_any());
 
+        // The chart release is not owned by the actor, uninstall it with the removed character.
+        if helm::chart(&actor.spec.character).is_some() {
+            helm::uninstall(&ctx.k8s, actor).await.map_err(Error::ResourceError)?;
+        }
+
+        // The namespace of detached actor is not owned by any playbook, delete it with the actor.
+        if actor::detached(actor) {
+            namespace::delete(&ctx.k8s, &namespace).await.map_err(Error::ResourceError)?;
+        }
+
         Ok(())
     }
 }
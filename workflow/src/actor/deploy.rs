@@ -12,22 +12,43 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use crate::errors::Error;
 use crate::errors::Result;
 use crate::Intent;
 use crate::{Context, State, Task};
 
-use amp_common::resource::Actor;
-use amp_resources::containers::application;
+use amp_common::resource::{Actor, ActorState};
+use amp_resolver::patches;
+use amp_resources::actor;
+use amp_resources::argocd;
+use amp_resources::canary::{self, Canary, Decision, Phase};
+use amp_resources::containers::sidecar;
+use amp_resources::cronjob::{self, Schedule};
 use amp_resources::deployment;
+use amp_resources::devcontainer;
+use amp_resources::envset;
 use amp_resources::error::Error as ResourceError;
 use amp_resources::hash;
+use amp_resources::helm::{self, Chart};
+use amp_resources::image::{self, ExposedPort};
+use amp_resources::monorepo;
+use amp_resources::policy;
+use amp_resources::probe;
+use amp_resources::statefulset;
+use amp_resources::strategy::{self, Color, Strategy};
+use amp_resources::volume::{self, Volume};
 
 use async_trait::async_trait;
+use k8s_openapi::api::apps::v1::Deployment;
 use k8s_openapi::api::core::v1::PodSpec;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
+use k8s_openapi::chrono::Utc;
+use kube::runtime::controller::Action;
 use kube::ResourceExt;
 use tracing::trace;
-use tracing::{error, info};
+use tracing::{debug, error, info};
 
 use super::ExposingState;
 
@@ -69,23 +90,188 @@ impl Task<Actor> for DeployTask {
     /// Execute the task logic for DeployTask using shared data
     async fn execute(&self, ctx: &Context<Actor>) -> Result<Option<Intent<Actor>>> {
         info!("Try to deploying the resources for Actor {}", &ctx.object.name_any());
-        self.deploy(ctx, &ctx.object).await.map_err(Error::DeployError)?;
 
-        Ok(None)
+        // Rebuild the actor from the commit pushed to its branch, which changed its files.
+        if let Some(rev) = monorepo::pending(&ctx.object) {
+            return self.rebuild(ctx, &rev).await;
+        }
+
+        // Never deploy an image which is not allowed by the registry policy,
+        // including the init containers and sidecars, and retry later in case
+        // the policy is changed.
+        let containers = sidecar::containers(&ctx.object).map_err(Error::ResourceError)?;
+        let checked = {
+            let policy = ctx.policy.read().await;
+            policy.check(&ctx.object.spec).and_then(|_| policy.check_containers(&containers))
+        };
+        if let Err(err) = checked {
+            policy::reject(&ctx.k8s, &ctx.object, err.to_string()).await.map_err(Error::ResourceError)?;
+            return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(60)))));
+        }
+        policy::accept(&ctx.k8s, &ctx.object).await.map_err(Error::ResourceError)?;
+
+        // The workloads are deployed by ArgoCD from the exported manifests, the
+        // values of the env sets are still copied next to them for their env vars.
+        if argocd::managed(&ctx.object) {
+            debug!("The workloads of actor {} are deployed by ArgoCD", ctx.object.name_any());
+            envset::environment(&ctx.k8s, &ctx.namespace, &ctx.object).await.map_err(Error::ResourceError)?;
+            return Ok(None);
+        }
+
+        // Deploy the chart release instead if the character is a Helm chart.
+        if let Some(chart) = helm::chart(&ctx.object.spec.character) {
+            return self.release(ctx, &chart).await;
+        }
+
+        // Infer the ports from the image if not declared, it does not block the deployment.
+        let ports = match self.infer_ports(ctx).await {
+            Ok(ports) => ports,
+            Err(err) => {
+                error!("Failed to infer the ports of actor {}: {}", ctx.object.name_any(), err);
+                vec![]
+            }
+        };
+
+        self.deploy(ctx, &ctx.object, &ports).await.map_err(Error::DeployError)
     }
 }
 
 impl DeployTask {
-    async fn deploy(&self, ctx: &Context<Actor>, actor: &Actor) -> Result<(), ResourceError> {
-        let name = actor.name_any();
+    /// Replace the revision of source and the image tagged with it, and build it again.
+    async fn rebuild(&self, ctx: &Context<Actor>, rev: &str) -> Result<Option<Intent<Actor>>> {
+        let actor = &ctx.object;
+        let mut spec = actor.spec.clone();
+        spec.image = patches::image(&*ctx.credentials.read().await, &spec, rev).map_err(Error::ResolveError)?;
+        if let Some(source) = spec.source.as_mut() {
+            source.rev = Some(rev.to_string());
+        }
+
+        actor::replace_spec(&ctx.k8s, actor, &spec).await.map_err(Error::ResourceError)?;
+        actor::patch_status(&ctx.k8s, actor, ActorState::pending()).await.map_err(Error::ResourceError)?;
+        info!("Rebuild actor {} from the pushed commit {}", actor.name_any(), rev);
+
+        Ok(Some(Intent::Action(Action::requeue(Duration::ZERO))))
+    }
+
+    /// Install or upgrade the chart release, and track its status in the actor conditions.
+    async fn release(&self, ctx: &Context<Actor>, chart: &Chart) -> Result<Option<Intent<Actor>>> {
+        let actor = &ctx.object;
+        helm::install(&ctx.k8s, actor, chart).await.map_err(Error::DeployError)?;
+
+        let condition = match helm::released(&ctx.k8s, actor).await {
+            Ok(true) => helm::condition(true, "Deployed", None),
+            Ok(false) => {
+                info!("The release of actor {} is not deployed yet, wait for it", actor.name_any());
+                let condition = helm::condition(false, "Pending", None);
+                self.track(ctx, condition).await?;
+                return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(5)))));
+            }
+            Err(err) => helm::condition(false, "Failed", Some(err.to_string())),
+        };
+        self.track(ctx, condition).await?;
+
+        Ok(None)
+    }
+
+    /// Update the release condition of the actor if its reason has changed.
+    async fn track(&self, ctx: &Context<Actor>, condition: Condition) -> Result<()> {
+        let current = ctx.object.status.as_ref().and_then(|status| {
+            status.conditions.iter().find(|c| c.type_ == helm::RELEASE_CONDITION_TYPE).map(|c| c.reason.clone())
+        });
+        if current.as_ref() == Some(&condition.reason) {
+            return Ok(());
+        }
+
+        actor::upsert_condition(&ctx.k8s, &ctx.object, condition).await.map_err(Error::ResourceError)
+    }
+
+    /// Returns the ports inferred from the `EXPOSE` config of the image if
+    /// the character doesn't declare any ports, and records them in the status of actor.
+    async fn infer_ports(&self, ctx: &Context<Actor>) -> Result<Vec<ExposedPort>, ResourceError> {
+        let actor = &ctx.object;
+        let declared = actor.spec.character.deploy.as_ref().and_then(|deploy| deploy.container_ports());
+        if declared.is_some_and(|ports| !ports.is_empty()) {
+            return Ok(vec![]);
+        }
+
+        // The ports have been inferred from the currently deployed image already.
+        if let Some(ports) = image::inferred_of(actor) {
+            return Ok(ports);
+        }
+
+        let ports = image::exposed_ports(&image::deployed(actor), &*ctx.credentials.read().await).await?;
+        image::record(&ctx.k8s, actor, &ports).await?;
+
+        Ok(ports)
+    }
+
+    async fn deploy(
+        &self,
+        ctx: &Context<Actor>,
+        actor: &Actor,
+        ports: &[ExposedPort],
+    ) -> Result<Option<Intent<Actor>>, ResourceError> {
+        let name = deployment::name(actor);
         let namespace = actor.namespace().ok_or_else(|| ResourceError::MissingObjectKey(".metadata.namespace"))?;
 
-        let resource = deployment::new(actor, self.pod(actor))?;
-        match deployment::exists(&ctx.k8s, &namespace, &name).await? {
+        // The shared env sets and the addresses of the sibling actors are resolved
+        // on every deploy, so the changes of them are rolled out like the changes of the spec.
+        let (env, shared_secrets) = envset::environment(&ctx.k8s, &ctx.namespace, actor).await?;
+
+        let volumes = volume::volumes(actor)?;
+        volume::check(&ctx.k8s, actor, &volumes).await?;
+        let containers = sidecar::containers(actor)?;
+        let probes = probe::probes(actor)?;
+
+        // The SSH host key of the dev container is kept in a Secret across the pods.
+        if devcontainer::devcontainer(actor)?.is_some_and(|devcontainer| devcontainer.ssh()) {
+            devcontainer::host_keys(&ctx.k8s, actor).await?;
+        }
+        let (pod, expected_hash) =
+            deployment::render(actor, ports, env, &shared_secrets, &volumes, &containers, &probes)?;
+
+        if let Some(schedule) = cronjob::schedule(actor)? {
+            self.deploy_scheduled(ctx, actor, &schedule, pod, &volumes, expected_hash).await?;
+            return Ok(None);
+        }
+        if statefulset::stateful(actor) {
+            self.deploy_stateful(ctx, actor, pod, &volumes, expected_hash).await?;
+            return Ok(None);
+        }
+        self.prune(ctx, actor, "Deployment").await?;
+
+        // The claims of the volumes are created before the pods mounting them.
+        volume::apply(&ctx.k8s, actor, &volumes).await?;
+
+        // The strategy is not a part of the spec, changing it is a change as well.
+        let strategy = strategy::strategy(actor)?;
+        let expected_hash = match &strategy {
+            Some(strategy) => hash(&(expected_hash, strategy))?,
+            None => expected_hash,
+        };
+        let mut resource = deployment::new(actor, pod, expected_hash.clone())?;
+        if strategy == Some(Strategy::BlueGreen) {
+            return self.deploy_blue_green(ctx, actor, resource, expected_hash).await;
+        }
+        if let (Some(spec), Some(strategy)) = (resource.spec.as_mut(), &strategy) {
+            spec.strategy = strategy.deployment_strategy();
+        }
+
+        // Roll out the changes of the existing Deployment through the canary.
+        let found_hash = deployment::applied_hash(&ctx.k8s, &namespace, &name).await?;
+        if let Some(canary) = canary::canary(actor)?.filter(|canary| !canary.steps.is_empty()) {
+            if found_hash.as_ref().is_some_and(|found| found != &expected_hash) {
+                return self.deploy_canary(ctx, actor, &canary, resource, expected_hash).await;
+            }
+        } else if let Some(status) = canary::status(actor).filter(|status| status.phase == Phase::Progressing) {
+            info!("The canary of actor {name} is not declared anymore, abandon its rollout");
+            self.rollback(ctx, actor, status, "The canary is not declared anymore".into()).await?;
+        }
+
+        match found_hash.is_some() {
             true => {
                 // Deployment already exists, update it if there are new changes
                 info!("Try to refresh an existing Deployment {name}");
-                let expected_hash = hash(&actor.spec)?;
                 deployment::update(&ctx.k8s, &namespace, &name, resource, expected_hash).await?;
             }
             false => {
@@ -95,10 +281,312 @@ impl DeployTask {
             }
         }
 
+        // Retire the Deployments of the colors if the strategy is not blue/green anymore.
+        if strategy::active(actor).is_some() {
+            strategy::reset(&ctx.k8s, actor).await?;
+            for color in [Color::Blue, Color::Green] {
+                let name = strategy::name(actor, color);
+                if deployment::exists(&ctx.k8s, &namespace, &name).await? {
+                    deployment::delete(&ctx.k8s, &namespace, &name).await?;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Roll out the new version of actor to the canary Deployment step by
+    /// step, and promote it to the stable Deployment after all the steps
+    /// passed, or roll it back if the canary pods are unhealthy.
+    async fn deploy_canary(
+        &self,
+        ctx: &Context<Actor>,
+        actor: &Actor,
+        canary: &Canary,
+        resource: Deployment,
+        expected_hash: String,
+    ) -> Result<Option<Intent<Actor>>, ResourceError> {
+        let name = deployment::name(actor);
+        let namespace = actor.namespace().ok_or_else(|| ResourceError::MissingObjectKey(".metadata.namespace"))?;
+        let status = canary::status(actor).filter(|status| status.hash == expected_hash);
+
+        let mut status = match status {
+            Some(status) if status.phase == Phase::RolledBack => {
+                debug!("The version {expected_hash} of actor {name} has been rolled back, wait for a new one");
+                return Ok(None);
+            }
+            Some(status) if status.phase == Phase::Progressing => status,
+            _ => {
+                // The stable Deployment may have been scaled down by the rollout
+                // interrupted by this version, keep the replicas before it.
+                let previous = canary::status(actor).filter(|status| status.phase == Phase::Progressing);
+                let replicas = match (resource.spec.as_ref().and_then(|spec| spec.replicas), previous) {
+                    (Some(replicas), _) => replicas,
+                    (None, Some(previous)) => previous.replicas,
+                    (None, None) => {
+                        probe::replicas(&ctx.k8s, &namespace, &name).await?.map_or(1, |(_, desired)| desired)
+                    }
+                };
+                let status = canary::Status {
+                    hash: expected_hash.clone(),
+                    phase: Phase::Progressing,
+                    step: 0,
+                    started_at: Utc::now().to_rfc3339(),
+                    replicas,
+                };
+                self.step(ctx, actor, canary, &status, resource, expected_hash).await?;
+                return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(10)))));
+            }
+        };
+
+        let (ready, desired) = probe::replicas(&ctx.k8s, &namespace, &canary::name(actor)).await?.unwrap_or_default();
+        let restarts = canary::restarts(&ctx.k8s, actor).await?;
+        match canary::decide(canary, &status, ready >= desired, restarts, Utc::now()) {
+            Decision::Wait(duration) => Ok(Some(Intent::Action(Action::requeue(duration)))),
+            Decision::Advance => {
+                status.step += 1;
+                status.started_at = Utc::now().to_rfc3339();
+                self.step(ctx, actor, canary, &status, resource, expected_hash).await?;
+                Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(10)))))
+            }
+            Decision::Promote => {
+                deployment::update(&ctx.k8s, &namespace, &name, resource, expected_hash).await?;
+                deployment::scale(&ctx.k8s, &namespace, &name, status.replicas).await?;
+                deployment::delete(&ctx.k8s, &namespace, &canary::name(actor)).await?;
+
+                status.phase = Phase::Promoted;
+                canary::record(&ctx.k8s, actor, &status).await?;
+                let message = format!("The version {} is promoted to all the traffic", status.hash);
+                info!("{message} of actor {name}");
+                actor::upsert_condition(&ctx.k8s, actor, canary::condition(Phase::Promoted, message)).await?;
+
+                Ok(None)
+            }
+            Decision::Rollback(reason) => {
+                self.rollback(ctx, actor, status, reason).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Apply the canary Deployment with the replicas of the step. The stable
+    /// Deployment keeps its replicas if the traffic is split by the weighted
+    /// Ingress or HTTPRoute, or is scaled down to make room for the canary.
+    async fn step(
+        &self,
+        ctx: &Context<Actor>,
+        actor: &Actor,
+        canary: &Canary,
+        status: &canary::Status,
+        mut resource: Deployment,
+        expected_hash: String,
+    ) -> Result<(), ResourceError> {
+        let namespace = actor.namespace().ok_or_else(|| ResourceError::MissingObjectKey(".metadata.namespace"))?;
+        let weight = canary.steps[status.step].weight;
+        let (stable, replicas) = canary::split(status.replicas, weight);
+        let exposure = ctx.exposure.of(actor)?;
+        let stable = match exposure.weighted() && exposure.hostname.is_some() {
+            true => status.replicas,
+            false => stable,
+        };
+
+        let name = canary::name(actor);
+        canary::paint(actor, &mut resource, replicas);
+        match deployment::exists(&ctx.k8s, &namespace, &name).await? {
+            true => {
+                deployment::update(&ctx.k8s, &namespace, &name, resource, expected_hash).await?;
+                deployment::scale(&ctx.k8s, &namespace, &name, replicas).await?;
+            }
+            false => {
+                deployment::create(&ctx.k8s, &namespace, resource).await?;
+            }
+        }
+        deployment::scale(&ctx.k8s, &namespace, &deployment::name(actor), stable).await?;
+
+        canary::record(&ctx.k8s, actor, status).await?;
+        let message = format!("The version {} is rolled out to {}% of the traffic", status.hash, weight);
+        info!("{message} of actor {}", actor.name_any());
+        actor::upsert_condition(&ctx.k8s, actor, canary::condition(Phase::Progressing, message)).await
+    }
+
+    /// Delete the canary Deployment and scale the stable one back.
+    async fn rollback(
+        &self,
+        ctx: &Context<Actor>,
+        actor: &Actor,
+        mut status: canary::Status,
+        reason: String,
+    ) -> Result<(), ResourceError> {
+        let name = deployment::name(actor);
+        let namespace = actor.namespace().ok_or_else(|| ResourceError::MissingObjectKey(".metadata.namespace"))?;
+
+        if deployment::exists(&ctx.k8s, &namespace, &canary::name(actor)).await? {
+            deployment::delete(&ctx.k8s, &namespace, &canary::name(actor)).await?;
+        }
+        deployment::scale(&ctx.k8s, &namespace, &name, status.replicas).await?;
+
+        status.phase = Phase::RolledBack;
+        canary::record(&ctx.k8s, actor, &status).await?;
+        let message = format!("The version {} is rolled back: {}", status.hash, reason);
+        error!("{message} of actor {name}");
+        actor::upsert_condition(&ctx.k8s, actor, canary::condition(Phase::RolledBack, message)).await
+    }
+
+    /// Deploy the new version of actor next to the serving one in the other
+    /// color, switch the traffic to it after it is ready, then delete the old one.
+    async fn deploy_blue_green(
+        &self,
+        ctx: &Context<Actor>,
+        actor: &Actor,
+        mut resource: Deployment,
+        expected_hash: String,
+    ) -> Result<Option<Intent<Actor>>, ResourceError> {
+        let namespace = actor.namespace().ok_or_else(|| ResourceError::MissingObjectKey(".metadata.namespace"))?;
+
+        let active = strategy::active(actor);
+        if let Some(color) = active {
+            let serving = strategy::name(actor, color);
+            if deployment::applied_hash(&ctx.k8s, &namespace, &serving).await?.as_ref() == Some(&expected_hash) {
+                debug!("The Deployment {serving} is already up-to-date");
+                return Ok(None);
+            }
+        }
+
+        let color = active.map(|color| color.other()).unwrap_or(Color::Blue);
+        let name = strategy::name(actor, color);
+        strategy::paint(actor, &mut resource, color);
+        match deployment::exists(&ctx.k8s, &namespace, &name).await? {
+            true => {
+                info!("Try to refresh an existing Deployment {name}");
+                deployment::update(&ctx.k8s, &namespace, &name, resource, expected_hash).await?;
+            }
+            false => {
+                deployment::create(&ctx.k8s, &namespace, resource).await?;
+                info!("Created new Deployment: {name}");
+            }
+        }
+
+        // Keep the traffic on the serving pods until the new ones are ready.
+        let (ready, desired) = probe::replicas(&ctx.k8s, &namespace, &name).await?.unwrap_or_default();
+        if ready < desired {
+            info!("{ready} of {desired} replicas of Deployment {name} are ready, wait for them");
+            return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(10)))));
+        }
+
+        strategy::switch(&ctx.k8s, actor, color).await?;
+        let old = [Some(deployment::name(actor)), active.map(|color| strategy::name(actor, color))];
+        for old in old.into_iter().flatten() {
+            if deployment::exists(&ctx.k8s, &namespace, &old).await? {
+                deployment::delete(&ctx.k8s, &namespace, &old).await?;
+            }
+        }
+
+        // Reconcile again with the switched actor, so that its Service is
+        // never updated with the selector of the old color.
+        Ok(Some(Intent::Action(Action::requeue(Duration::ZERO))))
+    }
+
+    /// Deploy the actor as a StatefulSet with a headless Service, its PVC
+    /// volumes are claimed per pod by the volume claim templates.
+    async fn deploy_stateful(
+        &self,
+        ctx: &Context<Actor>,
+        actor: &Actor,
+        mut pod: PodSpec,
+        volumes: &[Volume],
+        expected_hash: String,
+    ) -> Result<(), ResourceError> {
+        let name = statefulset::name(actor);
+        let namespace = actor.namespace().ok_or_else(|| ResourceError::MissingObjectKey(".metadata.namespace"))?;
+
+        self.prune(ctx, actor, "StatefulSet").await?;
+
+        // The PVC volumes are mounted from the claim templates instead.
+        if let Some(volumes) = pod.volumes.as_mut() {
+            volumes.retain(|volume| volume.persistent_volume_claim.is_none());
+        }
+
+        // The claim templates are immutable, the claims created from them are expanded instead.
+        volume::expand_templates(&ctx.k8s, actor, &name, volumes).await?;
+
+        statefulset::apply_headless_service(&ctx.k8s, actor).await?;
+        let resource = statefulset::new(actor, pod, volume::claim_templates(volumes), expected_hash.clone())?;
+        match statefulset::exists(&ctx.k8s, &namespace, &name).await? {
+            true => {
+                info!("Try to refresh an existing StatefulSet {name}");
+                statefulset::update(&ctx.k8s, &namespace, &name, resource, expected_hash).await?;
+            }
+            false => {
+                statefulset::create(&ctx.k8s, &namespace, resource).await?;
+                info!("Created new StatefulSet: {name}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deploy the actor as a CronJob running the scheduled tasks, they can be
+    /// run on demand as well.
+    async fn deploy_scheduled(
+        &self,
+        ctx: &Context<Actor>,
+        actor: &Actor,
+        schedule: &Schedule,
+        mut pod: PodSpec,
+        volumes: &[Volume],
+        expected_hash: String,
+    ) -> Result<(), ResourceError> {
+        let namespace = actor.namespace().ok_or_else(|| ResourceError::MissingObjectKey(".metadata.namespace"))?;
+        self.prune(ctx, actor, "CronJob").await?;
+        volume::apply(&ctx.k8s, actor, volumes).await?;
+
+        // The tasks are not serving, so they are never ready.
+        for container in pod.containers.iter_mut() {
+            container.readiness_probe = None;
+            container.startup_probe = None;
+        }
+
+        // The schedule is not a part of the spec, changing it is a change as well.
+        let expected_hash = hash(&(expected_hash, schedule))?;
+        let resource = cronjob::new(actor, schedule, pod, expected_hash.clone())?;
+        cronjob::apply(&ctx.k8s, &namespace, resource, expected_hash).await?;
+
+        if let Some(requested) = cronjob::requested(actor) {
+            cronjob::trigger(&ctx.k8s, &namespace, &cronjob::name(actor)).await?;
+            cronjob::triggered(&ctx.k8s, actor, &requested).await?;
+        }
+
         Ok(())
     }
 
-    fn pod(&self, actor: &Actor) -> PodSpec {
-        PodSpec { containers: vec![application::container(&actor.spec)], ..Default::default() }
+    /// Delete the workloads of the other kinds, in case the workload of actor is changed.
+    async fn prune(&self, ctx: &Context<Actor>, actor: &Actor, keep: &str) -> Result<(), ResourceError> {
+        let name = actor.name_any();
+        let namespace = actor.namespace().ok_or_else(|| ResourceError::MissingObjectKey(".metadata.namespace"))?;
+
+        if keep != "Deployment" {
+            // The traffic is routed to all the pods again, not only the ones of the color.
+            if strategy::active(actor).is_some() {
+                strategy::reset(&ctx.k8s, actor).await?;
+            }
+            for workload in strategy::deployments(actor) {
+                if deployment::exists(&ctx.k8s, &namespace, &workload).await? {
+                    info!("The workload of actor {name} is changed to {keep}, delete its Deployment {workload}");
+                    deployment::delete(&ctx.k8s, &namespace, &workload).await?;
+                }
+            }
+        }
+        let workload = statefulset::name(actor);
+        if keep != "StatefulSet" && statefulset::exists(&ctx.k8s, &namespace, &workload).await? {
+            info!("The workload of actor {name} is changed to {keep}, delete its StatefulSet and claims");
+            statefulset::delete(&ctx.k8s, &namespace, &workload).await?;
+        }
+        let workload = cronjob::name(actor);
+        if keep != "CronJob" && cronjob::exists(&ctx.k8s, &namespace, &workload).await? {
+            info!("The workload of actor {name} is changed to {keep}, delete its CronJob");
+            cronjob::delete(&ctx.k8s, &namespace, &workload).await?;
+        }
+
+        Ok(())
     }
 }
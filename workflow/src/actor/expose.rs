@@ -12,15 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use crate::errors::{Error, Result};
 use crate::{Context, Intent, State, Task};
 
 use amp_common::resource::Actor;
 
-use amp_resources::service;
+use amp_resources::exposure::{self, Exposed};
+use amp_resources::verification::{self, Decision, Phase, Verification};
+use amp_resources::{actor, argocd, cronjob, helm, image, probe, rollback, service, strategy};
 use async_trait::async_trait;
+use k8s_openapi::chrono::Utc;
+use kube::runtime::controller::Action;
 use kube::ResourceExt;
-use tracing::{error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 pub struct ExposingState;
 
@@ -40,7 +46,164 @@ impl State<Actor> for ExposingState {
             }
         }
 
-        None // No transition, wait for next state
+        // Keep watching the pods until they are ready, as the workloads are not watched.
+        match self.ready(ctx).await {
+            Ok(true) => None, // No transition, wait for next state
+            Ok(false) => Some(Intent::Action(Action::requeue(Duration::from_secs(10)))),
+            Err(err) => {
+                error!("Error during checking the readiness of actor {}: {}", ctx.object.name_any(), err);
+                None
+            }
+        }
+    }
+}
+
+impl ExposingState {
+    /// Report whether the pods of actor are ready in its Ready condition,
+    /// the condition is updated only if it has changed. If a verification
+    /// is declared, the actor is Ready only after its current image passed.
+    /// The failed rollouts are rolled back to the last known-good image.
+    async fn ready(&self, ctx: &Context<Actor>) -> Result<bool> {
+        let actor = &ctx.object;
+        if !actor.status.as_ref().is_some_and(|status| status.running())
+            || helm::chart(&actor.spec.character).is_some()
+            || cronjob::schedule(actor).map_err(Error::ResourceError)?.is_some()
+        {
+            return Ok(true);
+        }
+
+        let namespace = actor.namespace().unwrap_or_default();
+        let name = strategy::serving(actor);
+        let replicas = probe::replicas(&ctx.k8s, &namespace, &name).await.map_err(Error::ResourceError)?;
+        let Some((ready, desired)) = replicas else {
+            return Ok(true);
+        };
+        debug!("{} of {} replicas of actor {} are ready", ready, desired, actor.name_any());
+
+        let mut condition = probe::ready(ready, desired);
+        let mut done = ready >= desired;
+        if let Some(verification) = verification::verification(actor).map_err(Error::ResourceError)? {
+            if done {
+                let status = self.verify(ctx, &verification).await.map_err(Error::ResourceError)?;
+                match status.phase {
+                    Phase::Verifying | Phase::Failed => {
+                        condition = verification::unready(status.phase, status.message);
+                        done = status.phase == Phase::Failed;
+                    }
+                    Phase::Passed | Phase::RolledBack => {}
+                }
+            }
+        }
+
+        let healthy = condition.status == "True";
+        let complete = self.safeguard(ctx, healthy).await.map_err(Error::ResourceError)?;
+
+        let current = actor.status.as_ref().and_then(|status| {
+            let current = status.conditions.iter().find(|c| c.type_ == probe::READY_CONDITION_TYPE)?;
+            Some((&current.status, &current.reason, &current.message))
+        });
+        if current != Some((&condition.status, &condition.reason, &condition.message)) {
+            actor::upsert_condition(&ctx.k8s, actor, condition).await.map_err(Error::ResourceError)?;
+        }
+
+        Ok(done && complete)
+    }
+
+    /// Remember the image of the complete and healthy rollout as the known-good
+    /// one, or roll back the failed rollout to it. Returns whether the rollout
+    /// is complete, a failed rollout is rolled back only once for an image.
+    async fn safeguard(&self, ctx: &Context<Actor>, healthy: bool) -> Result<bool, amp_resources::error::Error> {
+        let actor = &ctx.object;
+        let Some(rollout) = rollback::observe(&ctx.k8s, actor).await? else {
+            return Ok(true);
+        };
+        if rollout.complete {
+            if let Some(image) =
+                rollout.image.filter(|image| healthy && rollback::known_good(actor).as_ref() != Some(image))
+            {
+                rollback::remember(&ctx.k8s, actor, &image).await?;
+                if rollback::status(actor).is_some_and(|status| status.image != image::deployed(actor)) {
+                    actor::upsert_condition(&ctx.k8s, actor, rollback::recovered(&image)).await?;
+                }
+            }
+            return Ok(true);
+        }
+
+        let policy = rollback::policy(actor)?;
+        if !policy.enabled || rollback::status(actor).is_some_and(|status| status.image == image::deployed(actor)) {
+            return Ok(false);
+        }
+        let Some((reason, message)) = rollback::failure(&policy, &rollout) else {
+            return Ok(false);
+        };
+        let Some(image) = rollback::known_good(actor) else {
+            warn!("The rollout of actor {} failed without a known-good image: {}", actor.name_any(), message);
+            return Ok(false);
+        };
+
+        let status = rollback::rollback(&ctx.k8s, actor, &image, reason, message).await?;
+        actor::upsert_condition(&ctx.k8s, actor, rollback::condition(&status)).await?;
+
+        Ok(false)
+    }
+
+    /// Verify the current image of actor against its Service, and roll back
+    /// to the last verified image if it failed.
+    async fn verify(
+        &self,
+        ctx: &Context<Actor>,
+        verification: &Verification,
+    ) -> Result<verification::Status, amp_resources::error::Error> {
+        let actor = &ctx.object;
+        let mut status = match verification::status(actor) {
+            Some(status) if status.image == image::deployed(actor) => status,
+            previous => {
+                let mut status = verification::start(actor, previous.as_ref(), Utc::now());
+                status.message = format!("Verifying the image {}", status.image);
+                verification::record(&ctx.k8s, actor, &status).await?;
+                let condition = verification::condition(status.phase, status.message.clone());
+                actor::upsert_condition(&ctx.k8s, actor, condition).await?;
+                info!("Started the verification of actor {}", actor.name_any());
+                status
+            }
+        };
+        if status.phase != Phase::Verifying {
+            return Ok(status);
+        }
+
+        let mut job = None;
+        if let Some(verification) = &verification.job {
+            verification::run(&ctx.k8s, actor, verification).await?;
+            job = verification::completed(&ctx.k8s, actor).await?;
+        }
+        let failure = verification::check(actor, &verification.http).await?;
+
+        match verification::decide(verification, &status, failure, job, Utc::now()) {
+            Decision::Wait(_) => return Ok(status),
+            Decision::Pass => {
+                status.phase = Phase::Passed;
+                status.message = format!("The image {} is verified", status.image);
+            }
+            Decision::Fail(reason) => match status.verified.clone().filter(|image| image != &status.image) {
+                Some(image) => {
+                    let rolled = rollback::rollback(&ctx.k8s, actor, &image, "VerificationFailed", reason).await?;
+                    actor::upsert_condition(&ctx.k8s, actor, rollback::condition(&rolled)).await?;
+                    status.phase = Phase::RolledBack;
+                    status.message = format!("{}, rolled back to {}", rolled.message, image);
+                }
+                None => {
+                    status.phase = Phase::Failed;
+                    status.message = reason;
+                }
+            },
+        }
+        info!("The verification of actor {}: {}", actor.name_any(), status.message);
+
+        verification::record(&ctx.k8s, actor, &status).await?;
+        let condition = verification::condition(status.phase, status.message.clone());
+        actor::upsert_condition(&ctx.k8s, actor, condition).await?;
+
+        Ok(status)
     }
 }
 
@@ -53,30 +216,54 @@ impl Task<Actor> for ExposeTask {
     }
 
     fn matches(&self, ctx: &Context<Actor>) -> bool {
-        ctx.object.status.as_ref().is_some_and(|status| status.running()) && ctx.object.spec.has_services()
+        ctx.object.status.as_ref().is_some_and(|status| status.running())
+            && helm::chart(&ctx.object.spec.character).is_none()
+            && !argocd::managed(&ctx.object)
+            && (ctx.object.spec.has_services() || !image::inferred(&ctx.object).is_empty())
     }
 
     /// Execute the task logic for ExposeTask using shared data
     async fn execute(&self, ctx: &Context<Actor>) -> Result<Option<Intent<Actor>>> {
-        self.serve(ctx, &ctx.object).await.map_err(Error::ResourceError)?;
+        let Some(exposed) = self.serve(ctx, &ctx.object).await.map_err(Error::ResourceError)? else {
+            return Ok(None);
+        };
+
+        // Report the URL of actor if it has changed.
+        let condition = exposure::condition(&exposed);
+        let current = ctx.object.status.as_ref().and_then(|status| {
+            status.conditions.iter().find(|c| c.type_ == exposure::EXPOSED_CONDITION_TYPE).map(|c| &c.message)
+        });
+        if current != Some(&condition.message) {
+            actor::upsert_condition(&ctx.k8s, &ctx.object, condition).await.map_err(Error::ResourceError)?;
+        }
+
+        // Wait for the certificate to report the HTTPS URL.
+        if exposed.pending {
+            info!("The certificate of actor {} is not issued yet, wait for it", ctx.object.name_any());
+            return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(10)))));
+        }
+
         Ok(None)
     }
 }
 
 impl ExposeTask {
-    async fn serve(&self, ctx: &Context<Actor>, actor: &Actor) -> Result<(), amp_resources::error::Error> {
+    async fn serve(&self, ctx: &Context<Actor>, actor: &Actor) -> Result<Option<Exposed>, amp_resources::error::Error> {
         let name = actor.name_any();
-        match service::exists(&ctx.k8s, actor).await? {
+        let service = match service::exists(&ctx.k8s, actor).await? {
             true => {
                 info!("Try to refresh an existing Service {name}");
-                service::update(&ctx.k8s, actor).await?;
+                service::update(&ctx.k8s, actor).await?
             }
             false => {
-                service::create(&ctx.k8s, actor).await?;
+                let service = service::create(&ctx.k8s, actor).await?;
                 info!("Created new Service: {name}");
+                service
             }
-        }
+        };
 
-        Ok(())
+        // Expose the Service outside of the cluster by the Ingress or Gateway API.
+        let exposure = ctx.exposure.of(actor)?;
+        exposure::apply(&ctx.k8s, actor, &exposure, &service).await
     }
 }
@@ -15,14 +15,22 @@
 use std::time::Duration;
 
 use crate::actor::{BuildingState, DeployingState};
+use crate::breaker;
 use crate::errors::{Error, Result};
-use crate::{Context, Intent, State, Task};
+use crate::{Context, ImageCache, Intent, State, Task};
 
 use amp_common::docker::{self, registry, DockerConfig};
 use amp_common::resource::{Actor, ActorState};
 
-use amp_resources::actor;
+use amp_resolver::errors::ResolveError;
+use amp_resolver::preface::load;
+use amp_resolver::to_actor;
+use amp_resources::detection::{self, Decision};
+use amp_resources::error::Error as ResourceError;
+use amp_resources::{actor, helm, image, policy};
 use async_trait::async_trait;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
 use kube::runtime::controller::Action;
 use kube::ResourceExt;
 use tracing::{error, info, trace};
@@ -75,14 +83,52 @@ impl Task<Actor> for InitTask {
     async fn execute(&self, ctx: &Context<Actor>) -> Result<Option<Intent<Actor>>> {
         let actor = &ctx.object;
 
-        // build if actor is live or the image is not built, else skip to next state
-        if actor.spec.live || !self.built(ctx).await? {
+        // Resolve the spec of the detached actor from its preface first.
+        if actor::detached(actor) && actor.spec.image.is_empty() {
+            return match self.resolve(ctx).await {
+                // Report the policy violation, and retry later in case the policy is changed.
+                Err(Error::ResolveError(ResolveError::ImageNotAllowed(image))) => {
+                    let message = format!("Image {} is not allowed by the registry policy", image);
+                    policy::reject(&ctx.k8s, actor, message).await.map_err(Error::ResourceError)?;
+                    Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(60)))))
+                }
+                result => result.map(|_| Some(Intent::Action(Action::requeue(Duration::ZERO)))),
+            };
+        }
+
+        // build if actor is live or the image is not built, else skip to next state,
+        // the chart characters are deployed by Helm directly, and the prebuilt
+        // images without a repository are deployed once verified.
+        let build = match helm::chart(&actor.spec.character) {
+            Some(_) => false,
+            None if actor.spec.live => true,
+            None => match self.built(ctx).await {
+                // Convert the registry outage into a condition of actor and retry later,
+                // instead of failing the reconciliation repeatedly.
+                Err(Error::RegistryUnavailable(registry)) => {
+                    self.degrade(ctx, &registry).await?;
+                    return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(60)))));
+                }
+                // Report the missing or unverified prebuilt image, and retry later in case it's published.
+                Err(Error::ImageVerificationFailed(message)) => {
+                    policy::reject(&ctx.k8s, actor, message).await.map_err(Error::ResourceError)?;
+                    return Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(60)))));
+                }
+                built => !built?,
+            },
+        };
+
+        if build {
             let condition = ActorState::building();
             actor::patch_status(&ctx.k8s, &ctx.object, condition).await.map_err(Error::ResourceError)?;
         } else {
             // patch the status to running
             let condition = ActorState::running(true, "AutoRun", None);
             actor::patch_status(&ctx.k8s, &ctx.object, condition).await.map_err(Error::ResourceError)?;
+            if detection::auto(actor) && image::prebuilt(&actor.spec) {
+                info!("Skip the build of actor {}, as its image is prebuilt", actor.name_any());
+                detection::record(&ctx.k8s, actor, Decision::Skipped).await.map_err(Error::ResourceError)?;
+            }
         }
 
         // Requeue immediately
@@ -91,26 +137,88 @@ impl Task<Actor> for InitTask {
 }
 
 impl InitTask {
-    /// Check if the image is already built
+    /// Resolve the character from the preface, and replace the spec of actor with it.
+    async fn resolve(&self, ctx: &Context<Actor>) -> Result<()> {
+        let actor = &ctx.object;
+        let preface = actor::preface(actor).map_err(Error::ResourceError)?;
+        let preface = preface.ok_or(Error::ResourceError(ResourceError::MissingObjectKey(".metadata.annotations")))?;
+
+        let credentials = ctx.credentials.read().await;
+        let character = load(&ctx.k8s, &credentials, &preface).await.map_err(Error::ResolveError)?;
+        let policy = ctx.policy.read().await;
+        let mut spec = to_actor(&character, &credentials, &policy).map_err(Error::ResolveError)?;
+        spec.name = actor.name_any();
+
+        actor::replace_spec(&ctx.k8s, actor, &spec).await.map_err(Error::ResourceError)?;
+        info!("Resolved the detached actor {} from preface", actor.name_any());
+
+        Ok(())
+    }
+
+    /// Report the registry outage in the conditions of actor, unless the same
+    /// outage is already reported, the condition will be removed with the
+    /// state transition once the registry is available.
+    async fn degrade(&self, ctx: &Context<Actor>, registry: &str) -> Result<()> {
+        let message = format!("The registry {} is unavailable", registry);
+        let conditions = ctx.object.status.as_ref().map(|status| status.conditions.as_slice()).unwrap_or_default();
+        if conditions.iter().any(|c| c.type_ == image::REGISTRY_AVAILABLE_CONDITION_TYPE && c.message == message) {
+            return Ok(());
+        }
+
+        let condition = Condition {
+            type_: image::REGISTRY_AVAILABLE_CONDITION_TYPE.into(),
+            status: "False".into(),
+            reason: "RegistryUnavailable".into(),
+            message,
+            last_transition_time: Time(Utc::now()),
+            observed_generation: None,
+        };
+        actor::upsert_condition(&ctx.k8s, &ctx.object, condition).await.map_err(Error::ResourceError)
+    }
+
+    /// Check if the image is already built, the prebuilt image without a
+    /// repository can't be built, so it must exist and pass the verification.
     async fn built(&self, ctx: &Context<Actor>) -> Result<bool> {
         let image = &ctx.object.spec.image;
-
+        let prebuilt = image::prebuilt(&ctx.object.spec);
         let credentials = ctx.credentials.read().await;
-        let config = DockerConfig::from(&credentials.registries);
 
-        let credential = match docker::get_credential(&config, image) {
-            Ok(credential) => Some(credential),
-            Err(err) => {
-                error!("Error handling docker configuration: {}", err);
-                None
+        // The existence is cached by the image along with its pinned digest.
+        let digest = ctx.object.annotations().get(image::DIGEST_ANNOTATION).map(String::as_str);
+        let key = ImageCache::key(image, digest);
+        let exists = match ctx.images.get(&key) {
+            Some(exists) => exists,
+            None => {
+                let config = DockerConfig::from(&credentials.registries);
+                let credential = match docker::get_credential(&config, image) {
+                    Ok(credential) => Some(credential),
+                    Err(err) => {
+                        error!("Error handling docker configuration: {}", err);
+                        None
+                    }
+                };
+
+                let exists = breaker::exists(image, || registry::exists(image, credential)).await?;
+                ctx.images.insert(&key, exists);
+                exists
             }
         };
 
-        if registry::exists(image, credential).await.map_err(Error::DockerRegistryError)? {
+        if exists {
             info!("The images already exists");
+            if prebuilt {
+                let reason = image::verify(&ctx.object, &credentials).await.map_err(Error::ResourceError)?;
+                if let Some(reason) = reason {
+                    return Err(Error::ImageVerificationFailed(reason));
+                }
+            }
             return Ok(true);
         }
 
+        if prebuilt {
+            return Err(Error::ImageVerificationFailed(format!("The prebuilt image {} is not found", image)));
+        }
+
         Ok(false)
     }
 }
@@ -0,0 +1,190 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use tracing::{info, warn};
+
+use crate::errors::{Error, Result};
+
+/// The number of consecutive failures opening the circuit of a registry.
+const FAILURE_THRESHOLD: u32 = 3;
+/// How long the circuit keeps open before a probing request is allowed.
+const OPEN_DURATION: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref BREAKERS: Mutex<HashMap<String, CircuitBreaker>> = Mutex::new(HashMap::new());
+}
+
+/// The circuit breaker of a registry.
+#[derive(Debug, Default)]
+struct CircuitBreaker {
+    failures: u32,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    /// Returns true if the request is allowed, only one probing request is
+    /// allowed after the circuit has been open long enough (half-open).
+    fn acquire(&mut self, now: Instant) -> bool {
+        match self.opened_at {
+            None => true,
+            Some(opened_at) if !self.probing && now.duration_since(opened_at) >= OPEN_DURATION => {
+                self.probing = true;
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Record the result of the request, a failed probing request opens the circuit again.
+    fn record(&mut self, success: bool, now: Instant) {
+        if success {
+            *self = CircuitBreaker::default();
+            return;
+        }
+
+        self.failures += 1;
+        if self.probing || self.failures >= FAILURE_THRESHOLD {
+            self.opened_at = Some(now);
+            self.probing = false;
+        }
+    }
+
+    /// Forget the probing request cancelled before its result is recorded,
+    /// so the next request can probe again.
+    fn cancel(&mut self) {
+        self.probing = false;
+    }
+}
+
+/// Cancels the acquired request of the registry if it's dropped before its
+/// result is recorded, e.g. the reconciliation is cancelled while checking.
+struct Guard {
+    host: String,
+    recorded: bool,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if self.recorded {
+            return;
+        }
+        if let Some(breaker) = BREAKERS.lock().ok().as_mut().and_then(|breakers| breakers.get_mut(&self.host)) {
+            breaker.cancel();
+        }
+    }
+}
+
+/// Check if the image exists with the check function guarded by the circuit
+/// breaker of its registry.
+///
+/// Returns [`Error::RegistryUnavailable`] without checking if the circuit is open.
+pub async fn exists<F, Fut>(image: &str, check: F) -> Result<bool>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = anyhow::Result<bool>>,
+{
+    let now = Instant::now();
+    let host = registry(image);
+    if !BREAKERS.lock().unwrap().entry(host.clone()).or_default().acquire(now) {
+        return Err(Error::RegistryUnavailable(host));
+    }
+    let mut guard = Guard { host: host.clone(), recorded: false };
+
+    let result = check().await;
+    let now = Instant::now();
+    {
+        let mut breakers = BREAKERS.lock().unwrap();
+        let breaker = breakers.entry(host.clone()).or_default();
+        let open = breaker.opened_at.is_some();
+        breaker.record(result.is_ok(), now);
+        guard.recorded = true;
+
+        match (open, breaker.opened_at.is_some()) {
+            (false, true) => warn!("The registry {} is unavailable, open its circuit", host),
+            (true, false) => info!("The registry {} is available again, close its circuit", host),
+            _ => {}
+        }
+    }
+
+    result.map_err(Error::DockerRegistryError)
+}
+
+/// Returns the registry host of the image, the default is `docker.io`.
+fn registry(image: &str) -> String {
+    match image.split_once('/') {
+        Some((host, _)) if host.contains('.') || host.contains(':') || host == "localhost" => host.to_string(),
+        _ => "docker.io".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_of_image() {
+        assert_eq!(registry("nginx:latest"), "docker.io");
+        assert_eq!(registry("amphitheatre/amp-example:v1"), "docker.io");
+        assert_eq!(registry("ghcr.io/amphitheatre-app/amp:v1"), "ghcr.io");
+        assert_eq!(registry("localhost:5000/example"), "localhost:5000");
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+
+        // Opened after the consecutive failures.
+        for _ in 0..FAILURE_THRESHOLD {
+            assert!(breaker.acquire(now));
+            breaker.record(false, now);
+        }
+        assert!(!breaker.acquire(now));
+
+        // Only one probing request is allowed when half-open.
+        let later = now + OPEN_DURATION;
+        assert!(breaker.acquire(later));
+        assert!(!breaker.acquire(later));
+
+        // The failed probing opens it again, and the successful one closes it.
+        breaker.record(false, later);
+        assert!(!breaker.acquire(later));
+        assert!(breaker.acquire(later + OPEN_DURATION));
+        breaker.record(true, later + OPEN_DURATION);
+        assert!(breaker.acquire(later + OPEN_DURATION));
+        assert_eq!(breaker.failures, 0);
+    }
+
+    #[test]
+    fn test_cancelled_probing() {
+        let host = "cancelled.example.com".to_string();
+        let now = Instant::now();
+        let breaker =
+            CircuitBreaker { failures: FAILURE_THRESHOLD, opened_at: Some(now - OPEN_DURATION), probing: false };
+        BREAKERS.lock().unwrap().insert(host.clone(), breaker);
+
+        assert!(BREAKERS.lock().unwrap().get_mut(&host).unwrap().acquire(now));
+        drop(Guard { host: host.clone(), recorded: false });
+
+        // The next request can probe again after the probing one is cancelled.
+        assert!(BREAKERS.lock().unwrap().get_mut(&host).unwrap().acquire(now));
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+/// How long a non-existent image is cached, it may be pushed by others soon.
+const NEGATIVE_TTL: Duration = Duration::from_secs(30);
+
+/// Caches the existence of the images in the registries, so the reconciles
+/// don't check the registries every time. The entries are keyed by the image
+/// reference and its pinned digest, and invalidated once the image is built.
+#[derive(Debug)]
+pub struct ImageCache {
+    /// How long an existing image is cached, disabled if it's zero.
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl ImageCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns the key of the image along with its pinned digest, if any.
+    pub fn key(image: &str, digest: Option<&str>) -> String {
+        match digest {
+            Some(digest) => format!("{}#{}", image, digest),
+            None => image.to_string(),
+        }
+    }
+
+    /// Returns the cached existence of the image, if it's not expired.
+    pub fn get(&self, key: &str) -> Option<bool> {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at(&self, key: &str, now: Instant) -> Option<bool> {
+        let entries = self.entries.lock().unwrap();
+        let (exists, at) = entries.get(key)?;
+        let ttl = if *exists { self.ttl } else { NEGATIVE_TTL };
+
+        (now.duration_since(*at) < ttl).then_some(*exists)
+    }
+
+    /// Cache the existence of the image, the expired entries are dropped.
+    pub fn insert(&self, key: &str, exists: bool) {
+        self.insert_at(key, exists, Instant::now());
+    }
+
+    fn insert_at(&self, key: &str, exists: bool, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        let ttl = self.ttl.max(NEGATIVE_TTL);
+        entries.retain(|_, (_, at)| now.duration_since(*at) < ttl);
+        entries.insert(key.to_string(), (exists, now));
+    }
+
+    /// Drop the cached existence of the image, whatever its pinned digest is.
+    pub fn invalidate(&self, image: &str) {
+        let prefix = format!("{}#", image);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|key, _| key != image && !key.starts_with(&prefix));
+        debug!("Invalidated the cached existence of image {}", image);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiration() {
+        let cache = ImageCache::new(Duration::from_secs(300));
+        let now = Instant::now();
+
+        cache.insert_at("nginx:1.27", true, now);
+        cache.insert_at("nginx:1.28", false, now);
+        assert_eq!(cache.get_at("nginx:1.27", now), Some(true));
+        assert_eq!(cache.get_at("nginx:1.28", now), Some(false));
+
+        // The non-existent images expire earlier.
+        let later = now + NEGATIVE_TTL;
+        assert_eq!(cache.get_at("nginx:1.27", later), Some(true));
+        assert_eq!(cache.get_at("nginx:1.28", later), None);
+        assert_eq!(cache.get_at("nginx:1.27", now + Duration::from_secs(300)), None);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let cache = ImageCache::new(Duration::ZERO);
+        let now = Instant::now();
+
+        cache.insert_at("nginx:1.27", true, now);
+        assert_eq!(cache.get_at("nginx:1.27", now), None);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let cache = ImageCache::new(Duration::from_secs(300));
+        let digest = ImageCache::key("web:v1", Some("sha256:abc"));
+        cache.insert("web:v1", false);
+        cache.insert(&digest, false);
+        cache.insert("web:v2", true);
+
+        cache.invalidate("web:v1");
+        assert_eq!(cache.get("web:v1"), None);
+        assert_eq!(cache.get(&digest), None);
+        assert_eq!(cache.get("web:v2"), Some(true));
+    }
+}
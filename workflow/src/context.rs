@@ -13,15 +13,48 @@
 // limitations under the License.
 
 use amp_common::config::Credentials;
+use amp_resources::artifact::Store;
+use amp_resources::build::BuildResources;
+use amp_resources::exposure::Exposure;
+use amp_resources::kpack::reference::BuilderRef;
+use amp_resources::policy::RegistryPolicy;
+use amp_resources::quota::Quota;
+use amp_resources::retention::Retention;
 use async_nats::jetstream;
 
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+use crate::{BuildQueue, ImageCache};
+
 /// Represents the context shared among different states and tasks.
 pub struct Context<T> {
     pub object: Arc<T>,
     pub k8s: Arc<kube::Client>,
     pub credentials: Arc<RwLock<Credentials>>,
+    pub policy: Arc<RwLock<RegistryPolicy>>,
     pub jetstream: Arc<jetstream::Context>,
+    /// The namespace of Amphitheatre, where the shared configurations are stored.
+    pub namespace: String,
+    /// The default quota of the playbook namespaces.
+    pub quota: Quota,
+    /// How the actors are exposed outside of the cluster by default.
+    pub exposure: Exposure,
+    /// The default resources and node placement of the build pods.
+    pub build: BuildResources,
+    /// The queue capping the concurrent builds across all the playbooks.
+    pub build_queue: Arc<BuildQueue>,
+    /// The default kpack builder of the actors built with Buildpacks.
+    pub kpack_builder: Option<BuilderRef>,
+    /// The cached existence of the images in the registries.
+    pub images: Arc<ImageCache>,
+    /// The store the logs of the builds are archived to.
+    pub artifacts: Store,
+    /// The retention of the tags of the default registry, the stale tags of
+    /// the deleted playbooks are pruned by it if set.
+    pub retention: Option<Retention>,
+    /// Reconcile the object again after this interval once the workflow is
+    /// finished, it waits for the changes if not set.
+    pub requeue: Option<Duration>,
 }
@@ -37,6 +37,12 @@ pub enum Error {
     #[error("Docker Registry Error: {0}")]
     DockerRegistryError(#[source] anyhow::Error),
 
+    #[error("Docker Registry {0} is unavailable")]
+    RegistryUnavailable(String),
+
+    #[error("Image Verification Failed: {0}")]
+    ImageVerificationFailed(String),
+
     #[error("Build Error: {0}")]
     BuildError(#[source] amp_builder::errors::Error),
 }
@@ -16,6 +16,14 @@ pub mod actor;
 pub mod errors;
 pub mod playbook;
 
+mod breaker;
+
+mod cache;
+pub use cache::ImageCache;
+
+mod queue;
+pub use queue::BuildQueue;
+
 mod state;
 pub use state::State;
 
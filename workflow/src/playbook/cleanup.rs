@@ -12,11 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::errors::Result;
+use crate::errors::{Error, Result};
 use crate::{Context, Intent, State, Task};
 use amp_common::resource::Playbook;
+use amp_resources::retention::{self, Retention};
+use amp_resources::{actor, cluster, namespace};
 use async_trait::async_trait;
-use kube::ResourceExt;
+use kube::{Client, ResourceExt};
 use tracing::{error, info, trace};
 
 pub struct CleanupState;
@@ -68,6 +70,50 @@ impl CleanupTask {
             info!("Deleted NATS stream for playbook {}", playbook.name_any());
         }
 
+        // Prune the stale tags of the images of the actors before they are deleted.
+        if let Some(retention) = &ctx.retention {
+            let client = match cluster::remote(playbook) {
+                true => cluster::client(&ctx.k8s, &ctx.namespace, playbook).await.map_err(Error::ResourceError)?,
+                false => ctx.k8s.as_ref().clone(),
+            };
+            if let Err(err) = self.prune(ctx, &client, playbook, retention).await {
+                error!("Failed to prune the registry tags of playbook {}: {}", playbook.name_any(), err);
+            }
+        }
+
+        // The namespace and actors in the workload cluster are not owned by the playbook.
+        if cluster::remote(playbook) {
+            let workload = cluster::client(&ctx.k8s, &ctx.namespace, playbook).await.map_err(Error::ResourceError)?;
+            let name = namespace::of(playbook);
+            if !namespace::dedicated(playbook) {
+                // The existing and shared namespaces are kept, only the actors are deleted.
+                for actor in actor::list_of(&workload, playbook).await.map_err(Error::ResourceError)? {
+                    actor::delete(&workload, &name, &actor.name_any()).await.map_err(Error::ResourceError)?;
+                }
+            } else if namespace::exists(&workload, &name).await.map_err(Error::ResourceError)? {
+                namespace::delete(&workload, &name).await.map_err(Error::ResourceError)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prune the stale tags of the repositories of the actors of playbook, the
+    /// images deployed by the actors of the other playbooks are kept.
+    async fn prune(
+        &self,
+        ctx: &Context<Playbook>,
+        client: &Client,
+        playbook: &Playbook,
+        retention: &Retention,
+    ) -> Result<(), amp_resources::error::Error> {
+        let actors = actor::list_of(client, playbook).await?;
+        let mut others = actor::list_all(client).await?;
+        others.retain(|other| actors.iter().all(|actor| actor.uid() != other.uid()));
+
+        let deleted = retention::prune(&*ctx.credentials.read().await, retention, &actors, &others).await?;
+        info!("Pruned {} stale images of playbook {}", deleted, playbook.name_any());
+
         Ok(())
     }
 }
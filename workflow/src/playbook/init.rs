@@ -19,10 +19,11 @@ use crate::{Context, State, Task};
 
 use amp_common::resource::{Playbook, PlaybookState};
 use amp_resolver::preface::load;
-use amp_resources::{namespace, playbook};
+use amp_resolver::validate;
+use amp_resources::{cluster, include, namespace, network, playbook, quota};
 
 use async_trait::async_trait;
-use kube::ResourceExt;
+use kube::{Client, ResourceExt};
 use tracing::{debug, error, info, trace};
 
 use super::ResolvingState;
@@ -64,10 +65,20 @@ impl Task<Playbook> for InitTask {
 
     /// Execute the task logic for InitTask using shared data
     async fn execute(&self, ctx: &Context<Playbook>) -> Result<Option<Intent<Playbook>>> {
-        // Create namespace for this playbook
-        namespace::create(&ctx.k8s, &ctx.object).await.map_err(Error::ResourceError)?;
+        // Create namespace for this playbook, in its workload cluster if any
+        let workload = cluster::client(&ctx.k8s, &ctx.namespace, &ctx.object).await.map_err(Error::ResourceError)?;
+        namespace::create(&workload, &ctx.object).await.map_err(Error::ResourceError)?;
         info!("Created namespace for playbook {}", ctx.object.name_any());
 
+        // The existing and shared namespaces are managed by their owners.
+        if namespace::dedicated(&ctx.object) {
+            // Limit the resources of the namespace
+            let quota = quota::of(&ctx.object, &ctx.quota).map_err(Error::ResourceError)?;
+            quota::apply(&workload, &ctx.object, &quota).await.map_err(Error::ResourceError)?;
+
+            isolate(ctx, &workload).await?;
+        }
+
         // Add the preface to the playbook for first resolving
         self.add_preface(ctx, &ctx.object).await?;
 
@@ -80,15 +91,54 @@ impl Task<Playbook> for InitTask {
     }
 }
 
+/// Isolate the actors if the playbook declares its network, they can still
+/// talk to Amphitheatre and be reached by the ingress controller. It's applied
+/// again by every reconciliation, in case the policies are changed by others.
+pub(super) async fn isolate(ctx: &Context<Playbook>, workload: &Client) -> Result<()> {
+    if let Some(network) = network::of(&ctx.object).map_err(Error::ResourceError)? {
+        let mut system = vec![ctx.namespace.clone()];
+        system.extend(ctx.exposure.ingress_namespace().map(String::from));
+        network::apply(workload, &ctx.object, &network, &system).await.map_err(Error::ResourceError)?;
+    }
+
+    Ok(())
+}
+
 impl InitTask {
     async fn add_preface(&self, ctx: &Context<Playbook>, playbook: &Playbook) -> Result<()> {
+        // The characters of the imported playbook are declared up front, they are already resolved.
+        if playbook.spec.characters.as_ref().is_some_and(|characters| !characters.is_empty()) {
+            debug!("The characters are declared, skip the preface");
+            return Ok(());
+        }
+
         debug!("Build from the starting characters (preface)");
 
         let preface = &playbook.spec.preface;
         let credentials = ctx.credentials.read().await;
         let character = load(&ctx.k8s, &credentials, preface).await.map_err(Error::ResolveError)?;
-        playbook::add(&ctx.k8s, playbook, character).await.map_err(Error::ResourceError)?;
-        info!("Fetch and add the character to this playbook");
+        validate(&character, &credentials).map_err(Error::ResolveError)?;
+
+        // Merge the starting characters of the included playbooks, their
+        // partners are resolved along with the ones of the playbook.
+        let includes = include::of(playbook).map_err(Error::ResourceError)?;
+        let mut included = vec![];
+        for preface in &includes {
+            let character = load(&ctx.k8s, &credentials, preface).await.map_err(Error::ResolveError)?;
+            validate(&character, &credentials).map_err(Error::ResolveError)?;
+            included.push(character);
+        }
+        let characters = match include::merge(vec![character], included) {
+            Ok(characters) => characters,
+            Err(err) => {
+                let condition = include::failed(err.to_string());
+                playbook::upsert_condition(&ctx.k8s, playbook, condition).await.map_err(Error::ResourceError)?;
+                return Err(Error::ResourceError(err));
+            }
+        };
+
+        playbook::extend(&ctx.k8s, playbook, characters).await.map_err(Error::ResourceError)?;
+        info!("Fetch and add the characters to this playbook");
 
         Ok(())
     }
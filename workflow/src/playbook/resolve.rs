@@ -17,12 +17,13 @@ use crate::{Context, Intent, State, Task};
 
 use amp_common::resource::{Partner, Playbook, PlaybookState};
 use amp_resolver::partner::load;
+use amp_resolver::validate;
 
-use amp_resources::playbook;
+use amp_resources::{debug, playbook};
 use async_trait::async_trait;
 use kube::ResourceExt;
 use std::collections::HashSet;
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
 use super::RunningState;
 
@@ -91,17 +92,34 @@ impl ResolveTask {
             debug!("The repositories to be fetched are: {fetches:?}");
         }
 
-        // Fetch the actors from the repositories
+        // Fetch the actors from the repositories, the partners with bad
+        // references are reported in the playbook conditions, and retried
+        // in the next reconciliation.
         //
         let credentials = ctx.credentials.read().await;
+        let mut errors = vec![];
         for (name, partner) in fetches.iter() {
-            let character = load(&ctx.k8s, &credentials, name, partner).await.map_err(Error::ResolveError)?;
+            let character = match load(&ctx.k8s, &credentials, name, partner).await {
+                Ok(character) => character,
+                Err(err) => {
+                    error!("Failed to resolve the partner {}: {}", name, err);
+                    errors.push((name.to_string(), err.to_string()));
+                    continue;
+                }
+            };
+            if let Err(err) = validate(&character, &credentials) {
+                error!("The source reference of partner {} is invalid: {}", name, err);
+                errors.push((name.to_string(), err.to_string()));
+                continue;
+            }
             playbook::add(&ctx.k8s, playbook, character).await.map_err(Error::ResourceError)?;
             info!("Fetch and add the actor to this playbook");
         }
+        self.report(ctx, playbook, &errors).await?;
 
         // If there are no repositories to fetch, then the resolution is complete.
         if fetches.is_empty() {
+            self.debuggers(ctx, playbook).await?;
             let condition = PlaybookState::running(true, "AutoRun", None);
             playbook::patch_status(&ctx.k8s, playbook, condition).await.map_err(Error::ResourceError)?;
             info!("Resolved successfully, Running");
@@ -109,4 +127,36 @@ impl ResolveTask {
 
         Ok(())
     }
+
+    /// Update the partners condition of the playbook if the errors have changed.
+    async fn report(&self, ctx: &Context<Playbook>, playbook: &Playbook, errors: &[(String, String)]) -> Result<()> {
+        let condition = playbook::partners_resolved(errors);
+        let current = playbook.status.as_ref().and_then(|status| {
+            status.conditions.iter().find(|c| c.type_ == playbook::PARTNERS_RESOLVED_CONDITION_TYPE)
+        });
+        match current {
+            Some(current) if current.status == condition.status && current.message == condition.message => Ok(()),
+            // Nothing to report if there was never an error.
+            None if errors.is_empty() => Ok(()),
+            _ => playbook::upsert_condition(&ctx.k8s, playbook, condition).await.map_err(Error::ResourceError),
+        }
+    }
+
+    /// Report the debugged characters which are not in the resolved playbook.
+    async fn debuggers(&self, ctx: &Context<Playbook>, playbook: &Playbook) -> Result<()> {
+        let unknown = debug::unknown(playbook);
+        if !unknown.is_empty() {
+            warn!("The debugged characters {:?} are not in playbook {}", unknown, playbook.name_any());
+        }
+        let current = playbook
+            .status
+            .as_ref()
+            .and_then(|status| status.conditions.iter().find(|c| c.type_ == debug::DEBUGGERS_CONDITION_TYPE));
+        let condition = debug::condition(&unknown);
+        match current {
+            Some(current) if current.status == condition.status && current.message == condition.message => Ok(()),
+            None if unknown.is_empty() => Ok(()),
+            _ => playbook::upsert_condition(&ctx.k8s, playbook, condition).await.map_err(Error::ResourceError),
+        }
+    }
 }
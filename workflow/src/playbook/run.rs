@@ -12,13 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use crate::errors::{Error, Result};
 use crate::{Context, Intent, State, Task};
-use amp_common::resource::Playbook;
+use amp_common::resource::{ActorSpec, Playbook};
+use amp_resolver::errors::ResolveError;
+use amp_resolver::patches::range;
 use amp_resolver::to_actor;
-use amp_resources::actor;
+use amp_resources::argocd::{self, ArgoCd};
+use amp_resources::build as resources;
+use amp_resources::error::Error as ResourceError;
+use amp_resources::export::{self, Export};
+use amp_resources::{actor, cluster, envset, helm, namespace, playbook, policy, promotion, revision, vars, version};
 use async_trait::async_trait;
-use kube::ResourceExt;
+use kube::runtime::controller::Action;
+use kube::{Client, ResourceExt};
 use tracing::{error, info, trace};
 
 pub struct RunningState;
@@ -56,40 +65,145 @@ impl Task<Playbook> for RunTask {
     }
 
     async fn execute(&self, ctx: &Context<Playbook>) -> Result<Option<Intent<Playbook>>> {
-        self.run(ctx, &ctx.object).await?;
-        Ok(None)
+        match self.run(ctx, &ctx.object).await {
+            // Reject the playbook if any of its images is not allowed by the registry policy,
+            // and retry later in case the policy is changed, the Running condition is kept.
+            Err(Error::ResolveError(ResolveError::ImageNotAllowed(image))) => {
+                let condition = policy::condition(format!("Image {} is not allowed by the registry policy", image));
+                playbook::upsert_condition(&ctx.k8s, &ctx.object, condition).await.map_err(Error::ResourceError)?;
+                info!("Rejected playbook {}, the image {} is not allowed", ctx.object.name_any(), image);
+                Ok(Some(Intent::Action(Action::requeue(Duration::from_secs(60)))))
+            }
+            result => result.map(|_| None),
+        }
     }
 }
 
 impl RunTask {
     async fn run(&self, ctx: &Context<Playbook>, playbook: &Playbook) -> Result<()> {
         let credentials = ctx.credentials.read().await;
+        let policy = ctx.policy.read().await;
 
         if playbook.spec.characters.is_none() {
             error!("No characters defined in the playbook");
             return Ok(());
         }
 
-        let characters = playbook.spec.characters.as_ref().unwrap();
-        for character in characters {
+        // The actors are created in the workload cluster of playbook, if any.
+        let workload = cluster::client(&ctx.k8s, &ctx.namespace, playbook).await.map_err(Error::ResourceError)?;
+
+        // Keep the actors isolated, the existing and shared namespaces are managed by their owners.
+        if namespace::dedicated(playbook) {
+            super::init::isolate(ctx, &workload).await?;
+        }
+
+        // The variables of playbook referenced by the characters are replaced by their values.
+        let vars = vars::of(playbook).map_err(Error::ResourceError)?;
+
+        // Resolve all of the characters before applying any of them, so a rejected
+        // image doesn't leave the playbook partially rolled out.
+        let mut resolved = vec![];
+        for character in playbook.spec.characters.as_ref().unwrap() {
+            let character = vars::substitute(character, &vars).map_err(Error::ResourceError)?;
+            let mut spec = to_actor(&character, &credentials, &policy).map_err(Error::ResolveError)?;
+            // The image promoted from another playbook is deployed as is, instead of being built.
+            promotion::apply(playbook, &mut spec).map_err(Error::ResourceError)?;
+            resolved.push((character, spec));
+        }
+
+        // All of the images are allowed now, clear the previous rejection.
+        playbook::remove_condition(&ctx.k8s, playbook, policy::REJECTED_CONDITION_TYPE)
+            .await
+            .map_err(Error::ResourceError)?;
+
+        for (character, spec) in &resolved {
             let name = &character.meta.name;
-            match actor::exists(&ctx.k8s, playbook, name).await.map_err(Error::ResourceError)? {
+            let actor = match actor::exists(&workload, playbook, name).await.map_err(Error::ResourceError)? {
                 true => {
                     // Actor already exists, update it if there are new changes
                     info!("Try to refresh an existing Actor {}", name);
-
-                    let spec = to_actor(character, &credentials).map_err(Error::ResolveError)?;
-                    actor::update(&ctx.k8s, playbook, &spec).await.map_err(Error::ResourceError)?;
+                    actor::update(&workload, playbook, spec).await.map_err(Error::ResourceError)?
                 }
                 false => {
                     // Create a new actor
                     info!("Create new Actor: {}", name);
-
-                    let spec = to_actor(character, &credentials).map_err(Error::ResolveError)?;
-                    actor::create(&ctx.k8s, playbook, &spec).await.map_err(Error::ResourceError)?;
+                    actor::create(&workload, playbook, spec).await.map_err(Error::ResourceError)?
                 }
+            };
+
+            // Record the version the semver range of the tag is resolved to.
+            let declared = ActorSpec::from(character).source.and_then(|source| source.tag);
+            if let (Some(range), Some(source)) = (declared.filter(|tag| range(tag).is_some()), &actor.spec.source) {
+                let (tag, rev) = (source.tag.as_deref().unwrap_or_default(), source.rev.as_deref().unwrap_or_default());
+                version::record(&workload, &actor, &range, tag, rev).await.map_err(Error::ResourceError)?;
+            }
+        }
+
+        // Snapshot the applied characters, so the playbook can be rolled back to them.
+        revision::record(&ctx.k8s, &ctx.namespace, playbook).await.map_err(Error::ResourceError)?;
+
+        // Export the manifests of the actors for the GitOps review flow, if declared.
+        if let Some(export) = export::of(playbook).map_err(Error::ResourceError)? {
+            self.export(ctx, &workload, playbook, &export).await.map_err(Error::ResourceError)?;
+        }
+
+        // The exported manifests are deployed by ArgoCD instead, if declared.
+        if let Some(argocd) = argocd::of(playbook).map_err(Error::ResourceError)? {
+            self.deliver(ctx, &workload, playbook, &argocd).await.map_err(Error::ResourceError)?;
+        }
+
+        Ok(())
+    }
+
+    /// Render the manifests of the actors of playbook and save them, they
+    /// are committed to the repository of the export once they've changed.
+    async fn export(
+        &self,
+        ctx: &Context<Playbook>,
+        workload: &Client,
+        playbook: &Playbook,
+        export: &Export,
+    ) -> Result<(), ResourceError> {
+        let mut objects = vec![];
+        for actor in actor::list_of(workload, playbook).await? {
+            let exposure = ctx.exposure.of(&actor)?;
+            let resources = resources::of(&actor, &ctx.build)?;
+            let (env, _) = envset::environment(&ctx.k8s, &ctx.namespace, &actor).await?;
+            objects.extend(export::render(&actor, &exposure, ctx.kpack_builder.as_ref(), &resources, &env)?);
+        }
+
+        let manifests = export::to_yaml(&objects)?;
+        if export::save(&ctx.k8s, &ctx.namespace, playbook, &manifests).await? {
+            export::commit(&ctx.k8s, &ctx.namespace, playbook, export, &manifests).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the ArgoCD Application syncing the exported manifests, and
+    /// record its health in the conditions of playbook. The Helm charts are
+    /// not exported, so the playbooks with them can't be deployed by ArgoCD.
+    async fn deliver(
+        &self,
+        ctx: &Context<Playbook>,
+        workload: &Client,
+        playbook: &Playbook,
+        argocd: &ArgoCd,
+    ) -> Result<(), ResourceError> {
+        let export = export::of(playbook)?;
+        let export = export.ok_or_else(|| ResourceError::InvalidArgoCd("the export is required".into()))?;
+        for actor in actor::list_of(workload, playbook).await? {
+            if helm::chart(&actor.spec.character).is_some() {
+                let message = format!("the Helm chart of character {} can't be exported", actor.spec.name);
+                return Err(ResourceError::InvalidArgoCd(message));
             }
         }
+        argocd::apply(&ctx.k8s, playbook, argocd, &export).await?;
+
+        if let Some(health) = argocd::health(&ctx.k8s, playbook, argocd).await? {
+            argocd::track(&ctx.k8s, playbook, &health).await?;
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,199 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info};
+
+/// How long an entry is kept without being seen, the queued actors are
+/// requeued every few seconds, so the entries of the deleted actors expire.
+const EXPIRATION: Duration = Duration::from_secs(5 * 60);
+
+/// An actor holding or waiting for a build slot.
+#[derive(Debug)]
+struct Entry {
+    priority: i32,
+    /// The order of arrival, the earlier ones go first among the same priority.
+    sequence: u64,
+    seen_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Slots {
+    running: HashMap<String, Entry>,
+    waiting: HashMap<String, Entry>,
+    sequence: u64,
+}
+
+/// Caps the concurrent builds across all the playbooks, the excess builds
+/// wait in a queue ordered by their priorities, then by their arrival.
+///
+/// The builds are driven by the requeued reconciliations, so the actors ask
+/// for their slots on every reconciliation until they are admitted, and
+/// release them once their builds are completed. The build Jobs running in
+/// the cluster hold their slots too, e.g. the ones admitted before a restart.
+#[derive(Debug)]
+pub struct BuildQueue {
+    /// The maximum number of concurrent builds, unlimited if it's zero.
+    capacity: usize,
+    slots: Mutex<Slots>,
+}
+
+impl BuildQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, slots: Mutex::new(Slots::default()) }
+    }
+
+    /// Returns true if the concurrent builds are capped.
+    pub fn limited(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Ask for a build slot of the actor, returns `None` if it's admitted,
+    /// or its position in the queue starting from 1. The `running` are the
+    /// keys of the actors whose builds are running in the cluster.
+    pub fn admit(&self, key: &str, priority: i32, running: &[String]) -> Option<usize> {
+        self.admit_at(key, priority, running, Instant::now())
+    }
+
+    fn admit_at(&self, key: &str, priority: i32, running: &[String], now: Instant) -> Option<usize> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let mut guard = self.slots.lock().unwrap();
+        let slots = &mut *guard;
+        slots.running.retain(|_, entry| now.duration_since(entry.seen_at) < EXPIRATION);
+        slots.waiting.retain(|_, entry| now.duration_since(entry.seen_at) < EXPIRATION);
+
+        if let Some(entry) = slots.running.get_mut(key) {
+            entry.seen_at = now;
+            return None;
+        }
+
+        // The build already running in the cluster keeps its slot.
+        if running.iter().any(|k| k == key) {
+            let entry = slots.waiting.remove(key).unwrap_or(Entry { priority, sequence: 0, seen_at: now });
+            slots.running.insert(key.to_string(), Entry { seen_at: now, ..entry });
+            return None;
+        }
+
+        let entry = slots.waiting.entry(key.to_string()).or_insert_with(|| {
+            slots.sequence += 1;
+            Entry { priority, sequence: slots.sequence, seen_at: now }
+        });
+        entry.priority = priority;
+        entry.seen_at = now;
+
+        let mut queue: Vec<(&String, &Entry)> = slots.waiting.iter().collect();
+        queue.sort_by_key(|(_, entry)| (-entry.priority, entry.sequence));
+        let position = queue.iter().position(|(k, _)| k.as_str() == key).unwrap_or_default();
+
+        // The ones ahead are admitted first, once they come back for their slots.
+        let occupied: HashSet<&str> =
+            slots.running.keys().map(String::as_str).chain(running.iter().map(String::as_str)).collect();
+        if occupied.len() + position < self.capacity {
+            let entry = slots.waiting.remove(key).unwrap();
+            slots.running.insert(key.to_string(), Entry { seen_at: now, ..entry });
+            info!("Admitted the build of {}, {} builds are running", key, occupied.len() + 1);
+            return None;
+        }
+
+        debug!("The build of {} is queued at position {}", key, position + 1);
+        Some(position + 1)
+    }
+
+    /// Release the build slot of the actor, or leave the queue.
+    pub fn release(&self, key: &str) {
+        let mut slots = self.slots.lock().unwrap();
+        if slots.running.remove(key).is_some() {
+            info!("Released the build slot of {}", key);
+        }
+        slots.waiting.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited() {
+        let queue = BuildQueue::new(0);
+        for i in 0..100 {
+            assert_eq!(queue.admit(&format!("amp-a/web-{}", i), 0, &[]), None);
+        }
+    }
+
+    #[test]
+    fn test_fifo() {
+        let queue = BuildQueue::new(2);
+        assert_eq!(queue.admit("a", 0, &[]), None);
+        assert_eq!(queue.admit("b", 0, &[]), None);
+        assert_eq!(queue.admit("c", 0, &[]), Some(1));
+        assert_eq!(queue.admit("d", 0, &[]), Some(2));
+
+        // Asking again keeps the slot and the position.
+        assert_eq!(queue.admit("a", 0, &[]), None);
+        assert_eq!(queue.admit("d", 0, &[]), Some(2));
+
+        // The freed slot goes to the head of queue only.
+        queue.release("a");
+        assert_eq!(queue.admit("d", 0, &[]), Some(2));
+        assert_eq!(queue.admit("c", 0, &[]), None);
+        assert_eq!(queue.admit("d", 0, &[]), Some(1));
+    }
+
+    #[test]
+    fn test_priority() {
+        let queue = BuildQueue::new(1);
+        assert_eq!(queue.admit("a", 0, &[]), None);
+        assert_eq!(queue.admit("b", 0, &[]), Some(1));
+        assert_eq!(queue.admit("c", 10, &[]), Some(1));
+        assert_eq!(queue.admit("b", 0, &[]), Some(2));
+
+        queue.release("a");
+        assert_eq!(queue.admit("b", 0, &[]), Some(2));
+        assert_eq!(queue.admit("c", 10, &[]), None);
+    }
+
+    #[test]
+    fn test_expiration() {
+        let queue = BuildQueue::new(1);
+        let now = Instant::now();
+        assert_eq!(queue.admit_at("a", 0, &[], now), None);
+        assert_eq!(queue.admit_at("b", 0, &[], now), Some(1));
+
+        // The slot of the deleted actor is freed once it expires.
+        let later = now + EXPIRATION;
+        assert_eq!(queue.admit_at("b", 0, &[], later), None);
+    }
+
+    #[test]
+    fn test_running_in_cluster() {
+        let queue = BuildQueue::new(2);
+        let running = vec!["a".to_string()];
+
+        // The builds running in the cluster hold their slots, e.g. after a restart.
+        assert_eq!(queue.admit("b", 0, &running), None);
+        assert_eq!(queue.admit("c", 0, &running), Some(1));
+        assert_eq!(queue.admit("a", 0, &running), None);
+
+        // The slot is freed once its build Job is finished.
+        queue.release("a");
+        assert_eq!(queue.admit("c", 0, &[]), None);
+    }
+}
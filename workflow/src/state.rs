@@ -21,6 +21,12 @@ use crate::{Context, Intent};
 pub trait State<T>: Send + Sync {
     /// Handles the current state and may transition to a new state.
     async fn handle(&self, ctx: &Context<T>) -> Option<Intent<T>>;
+
+    /// Returns the name of the state, e.g. `RunningState`.
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
 }
 
 #[cfg(test)]
@@ -45,4 +51,16 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_state_name() {
+        #[async_trait]
+        impl State<bool> for TestState {
+            async fn handle(&self, _ctx: &Context<bool>) -> Option<Intent<bool>> {
+                None
+            }
+        }
+
+        assert_eq!(State::<bool>::name(&TestState), "TestState");
+    }
 }
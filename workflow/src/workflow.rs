@@ -16,6 +16,7 @@ use crate::errors::Result;
 use crate::{Context, Intent, State};
 
 use kube::runtime::controller::Action;
+use tracing::{info_span, Instrument};
 
 use std::sync::Arc;
 
@@ -43,7 +44,12 @@ impl<T> Workflow<T> {
 
     /// Runs the workflow until there is no next state to transition to.
     pub async fn run(&mut self) -> Result<Action> {
-        while let Some(intent) = self.state.handle(&self.context).await {
+        loop {
+            // Trace every state, the tasks of a slow state are the ones to look into.
+            let span = info_span!("state", name = self.state.name());
+            let Some(intent) = self.state.handle(&self.context).instrument(span).await else {
+                break;
+            };
             match intent {
                 Intent::State(new_state) => {
                     self.transition(new_state);
@@ -52,6 +58,6 @@ impl<T> Workflow<T> {
             }
         }
 
-        Ok(Action::await_change())
+        Ok(self.context.requeue.map(Action::requeue).unwrap_or_else(Action::await_change))
     }
 }